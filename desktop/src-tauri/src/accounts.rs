@@ -0,0 +1,265 @@
+// More than one account on a server, each staying logged in: `accounts` (or
+// a profile's own) names logins kept next to the usual one, each in its own
+// webview partition (the server's partition plus `#account-<name>`). A
+// window's webview data is fixed when it's built, so switching rebuilds the
+// window on the other account's data, in the same place and at the same
+// size. Window > Switch Account and the tray's submenu list the accounts of
+// the window they act on: the focused one, or else the main one.
+
+use crate::config::{validate_server_url, AppConfig, ConfigState};
+use crate::debug_log::log_backend_error;
+use crate::window::{
+    build_and_setup_window, is_bundled_page_window, profile_name, WindowPartitions, WindowSession,
+    DEFAULT_PARTITION,
+};
+use std::sync::Mutex;
+use tauri::menu::{CheckMenuItem, Submenu};
+use tauri::{AppHandle, Manager, WebviewWindow, Wry};
+
+const ACCOUNT_SEPARATOR: &str = "#account-";
+/// Switch Account items are this followed by the account's name, or by
+/// nothing for the server's usual login.
+pub const SWITCH_ACCOUNT_ID_PREFIX: &str = "switch_account:";
+const DEFAULT_ACCOUNT_LABEL: &str = "Default Account";
+
+/// Managed state: the Switch Account submenus, in the app menu and the tray.
+#[derive(Default)]
+pub struct AccountMenus(Mutex<Vec<Submenu<Wry>>>);
+
+impl AccountMenus {
+    pub fn add(&self, submenu: Submenu<Wry>) {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(submenu);
+    }
+}
+
+/// `partition` without its account, and the account, if it has one.
+pub fn split_account(partition: &str) -> (&str, Option<&str>) {
+    partition
+        .rsplit_once(ACCOUNT_SEPARATOR)
+        .map_or((partition, None), |(base, account)| (base, Some(account)))
+}
+
+fn account_partition(base: &str, account: &str) -> String {
+    format!("{base}{ACCOUNT_SEPARATOR}{account}")
+}
+
+/// The accounts configured next to the usual login on the server of the
+/// windows in `base`.
+pub fn accounts<'a>(config: &'a AppConfig, base: &str) -> &'a [String] {
+    if base == DEFAULT_PARTITION {
+        return &config.accounts;
+    }
+    profile_name(base)
+        .and_then(|name| config.profiles.get(name))
+        .map_or(&[], |profile| &profile.accounts)
+}
+
+/// The session for `account` (or the usual login, with none) on the server
+/// of the windows in `base`. The configured server's usual login is the
+/// default webview data, which has no session.
+pub fn session(
+    config: &AppConfig,
+    base: &str,
+    account: Option<&str>,
+) -> Result<Option<WindowSession>, String> {
+    if let Some(account) =
+        account.filter(|account| !accounts(config, base).iter().any(|a| a == account))
+    {
+        return Err(format!("There's no account named \"{account}\""));
+    }
+    let mut session = if base == DEFAULT_PARTITION {
+        if account.is_none() {
+            return Ok(None);
+        }
+        WindowSession {
+            server_url: validate_server_url(&config.server_url)?,
+            partition: base.to_string(),
+        }
+    } else if let Some(name) = profile_name(base) {
+        WindowSession::for_profile(config, name)?
+    } else {
+        return Err("Only the configured server and profiles have accounts".to_string());
+    };
+    if let Some(account) = account {
+        session.partition = account_partition(base, account);
+    }
+    Ok(Some(session))
+}
+
+/// Rebuild `window` on `account`'s webview data (the usual login's, with
+/// none), in the same place and at the same size. No-op if it's already on
+/// that account.
+pub fn switch_window_account(
+    app: &AppHandle,
+    window: &WebviewWindow,
+    account: Option<&str>,
+) -> Result<(), String> {
+    let partition = app.state::<WindowPartitions>().partition_of(window.label());
+    let (base, current) = split_account(&partition);
+    if current == account {
+        return Ok(());
+    }
+    let session = session(&app.state::<ConfigState>().config(), base, account)?;
+    let replacement = build_and_setup_window(app, session.as_ref())?;
+    if let (Ok(size), Ok(position)) = (window.inner_size(), window.outer_position()) {
+        if let Err(e) = replacement
+            .set_size(size)
+            .and_then(|()| replacement.set_position(position))
+        {
+            log_backend_error(app, &format!("Failed to place switched window: {e}"));
+        }
+    }
+    window.destroy().map_err(|e| e.to_string())
+}
+
+/// The window Switch Account acts on: the focused chat window, or else the
+/// main one, or else any.
+fn target_window(app: &AppHandle) -> Option<WebviewWindow> {
+    let windows: Vec<_> = app
+        .webview_windows()
+        .into_iter()
+        .filter(|(label, _)| !is_bundled_page_window(label))
+        .collect();
+    windows
+        .iter()
+        .find(|(_, window)| window.is_focused().unwrap_or(false))
+        .or_else(|| windows.iter().find(|(label, _)| label == "main"))
+        .or_else(|| windows.first())
+        .map(|(_, window)| window.clone())
+}
+
+/// Refill the Switch Account submenus with the accounts of the window they
+/// act on, checking the one it's on. They're disabled on a server with no
+/// accounts besides the usual one.
+pub fn sync_account_menus(app: &AppHandle) {
+    let partition = target_window(app).map_or_else(
+        || DEFAULT_PARTITION.to_string(),
+        |window| app.state::<WindowPartitions>().partition_of(window.label()),
+    );
+    let (base, current) = split_account(&partition);
+    let config = app.state::<ConfigState>().config();
+    let accounts = accounts(&config, base);
+    // Menus are changed on the main thread, which may be waiting on the
+    // lock, so it isn't held while they are.
+    let menus = app
+        .state::<AccountMenus>()
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone();
+    for submenu in &menus {
+        if let Err(e) = fill_account_menu(app, submenu, accounts, current) {
+            log_backend_error(app, &format!("Failed to update Switch Account menu: {e}"));
+        }
+    }
+}
+
+fn fill_account_menu(
+    app: &AppHandle,
+    submenu: &Submenu<Wry>,
+    accounts: &[String],
+    current: Option<&str>,
+) -> tauri::Result<()> {
+    for item in submenu.items()? {
+        submenu.remove(&item)?;
+    }
+    let entries = std::iter::once((None, DEFAULT_ACCOUNT_LABEL)).chain(
+        accounts
+            .iter()
+            .map(|name| (Some(name.as_str()), name.as_str())),
+    );
+    for (account, label) in entries {
+        submenu.append(&CheckMenuItem::with_id(
+            app,
+            format!("{SWITCH_ACCOUNT_ID_PREFIX}{}", account.unwrap_or_default()),
+            label,
+            true,
+            account == current,
+            None::<&str>,
+        )?)?;
+    }
+    submenu.set_enabled(!accounts.is_empty())
+}
+
+/// Switch to the account of the Switch Account item `id`.
+pub fn handle_menu_event(app: &AppHandle, id: &str) {
+    let Some(account) = id.strip_prefix(SWITCH_ACCOUNT_ID_PREFIX) else {
+        return;
+    };
+    let account = Some(account)
+        .filter(|account| !account.is_empty())
+        .map(str::to_string);
+    let Some(window) = target_window(app) else {
+        return;
+    };
+    // Built off the menu-event thread, like the other windows.
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = switch_window_account(&handle, &window, account.as_deref()) {
+            log_backend_error(&handle, &format!("Failed to switch account: {e}"));
+        }
+        // A click toggles the item's check mark whatever happened.
+        sync_account_menus(&handle);
+    });
+}
+
+/// Switch the calling window to another of its server's accounts, or back
+/// to the usual login with no `account`
+#[tauri::command]
+pub async fn switch_account(
+    app: AppHandle,
+    window: WebviewWindow,
+    account: Option<String>,
+) -> Result<(), String> {
+    switch_window_account(&app, &window, account.as_deref())?;
+    sync_account_menus(&app);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProfileSettings;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn accounts_get_their_own_partition_on_their_server() {
+        let mut config = AppConfig {
+            accounts: vec!["client-a".to_string()],
+            ..AppConfig::default()
+        };
+        config.profiles.insert(
+            "ops".to_string(),
+            ProfileSettings {
+                server_url: "https://ops.example.com".to_string(),
+                titlebar: None,
+                admin_mode: None,
+                accounts: vec!["admin".to_string()],
+            },
+        );
+
+        assert!(session(&config, DEFAULT_PARTITION, None).unwrap().is_none());
+        let client = session(&config, DEFAULT_PARTITION, Some("client-a"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(client.partition, "default#account-client-a");
+        assert_eq!(client.server_url.as_str(), "https://cloud.onyx.app/");
+        assert_eq!(
+            split_account(&client.partition),
+            (DEFAULT_PARTITION, Some("client-a"))
+        );
+
+        let admin = session(&config, "profile-ops", Some("admin"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(admin.partition, "profile-ops#account-admin");
+        assert_eq!(profile_name(&admin.partition), Some("ops"));
+        assert_eq!(split_account("profile-ops"), ("profile-ops", None));
+
+        assert!(session(&config, "profile-ops", Some("client-a")).is_err());
+        assert!(session(&config, "origin-https://onyx.example.com", None).is_err());
+    }
+}
@@ -0,0 +1,316 @@
+// Admin mode: one switch (`admin_mode`, or a profile's own) for the people
+// who run an Onyx deployment rather than chat on it. Windows in an admin
+// profile open the indexing status page instead of the chat, and the
+// server's failed connectors are checked in the background, so a broken
+// connector is reported when it fails rather than when someone asks why
+// search is stale. Both reuse what's already there: the start page is just
+// where the window is pointed, and the check goes through `server_api` as
+// the user logged in to that profile. The same poll also looks for pending
+// approvals (see `approvals.rs`) for any profile whose user is an admin,
+// by `/api/me`, in admin mode or not.
+
+use crate::accounts::split_account;
+use crate::config::{AppConfig, ConfigState};
+use crate::debug_log::{log_backend_error, log_debug};
+use crate::route::Route;
+use crate::server_api::get_as_window_user;
+use crate::window::{is_bundled_page_window, profile_name, WindowPartitions, DEFAULT_PARTITION};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, WebviewWindow};
+use tauri_plugin_dialog::{
+    DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult,
+};
+use url::Url;
+
+/// The admin page admin-mode windows start on.
+const ADMIN_START_PAGE: &str = "indexing/status";
+const FAILED_CONNECTORS_PATH: &str = "/api/manage/admin/connector/failed-indexing-status";
+const POLL_INTERVAL: Duration = Duration::from_mins(5);
+const OPEN_LABEL: &str = "Open";
+const DISMISS_LABEL: &str = "Dismiss";
+
+/// Where admin-mode windows start.
+pub fn admin_start() -> Route {
+    Route::admin(ADMIN_START_PAGE)
+}
+
+/// Whether `config` puts windows in `partition` in admin mode: the
+/// profile's own choice, or `admin_mode`. One-off server windows never are.
+pub fn admin_mode(config: &AppConfig, partition: &str) -> bool {
+    if split_account(partition).0 == DEFAULT_PARTITION {
+        return config.admin_mode;
+    }
+    profile_name(partition)
+        .and_then(|name| config.profiles.get(name))
+        .is_some_and(|profile| profile.admin_mode.unwrap_or(config.admin_mode))
+}
+
+/// The server windows in `partition` are on, for the partitions that can be
+/// in admin mode.
+fn partition_server(config: &AppConfig, partition: &str) -> Option<String> {
+    if split_account(partition).0 == DEFAULT_PARTITION {
+        return Some(config.server_url.clone());
+    }
+    profile_name(partition)
+        .and_then(|name| config.profiles.get(name))
+        .map(|profile| profile.server_url.clone())
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct FailedConnector {
+    cc_pair_id: i64,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+impl FailedConnector {
+    fn display_name(&self) -> String {
+        self.name
+            .clone()
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| format!("Connector {}", self.cc_pair_id))
+    }
+}
+
+/// Managed state: the connectors last seen failing, by partition.
+#[derive(Default)]
+pub struct FailedConnectors(Mutex<BTreeMap<String, BTreeSet<i64>>>);
+
+impl FailedConnectors {
+    /// Record `failed` as what's failing in `partition` now, returning the
+    /// ones that weren't failing last time. A connector that recovers and
+    /// fails again is new again.
+    fn update<'a>(
+        &self,
+        partition: &str,
+        failed: &'a [FailedConnector],
+    ) -> Vec<&'a FailedConnector> {
+        let current = failed
+            .iter()
+            .map(|connector| connector.cc_pair_id)
+            .collect();
+        let previous = self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(partition.to_string(), current);
+        new_failures(previous.as_ref(), failed)
+    }
+}
+
+/// The connectors in `failed` that aren't in `previous`. Nothing is new on
+/// the first check, so connectors that were already broken at launch don't
+/// all announce themselves at once.
+fn new_failures<'a>(
+    previous: Option<&BTreeSet<i64>>,
+    failed: &'a [FailedConnector],
+) -> Vec<&'a FailedConnector> {
+    let Some(previous) = previous else {
+        return Vec::new();
+    };
+    failed
+        .iter()
+        .filter(|connector| !previous.contains(&connector.cc_pair_id))
+        .collect()
+}
+
+/// Show `message` with a button that opens `route` on `server` in `window`,
+/// or keep it for later under Do Not Disturb.
+pub fn offer_open(
+    app: &AppHandle,
+    window: WebviewWindow,
+    server: Url,
+    title: &str,
+    message: String,
+    route: Route,
+) {
+    let title = title.to_string();
+    crate::do_not_disturb::notify(app, &title.clone(), &message.clone(), move |app| {
+        let handle = app.clone();
+        app.dialog()
+            .message(message)
+            .title(title)
+            .kind(MessageDialogKind::Warning)
+            .buttons(MessageDialogButtons::OkCancelCustom(
+                OPEN_LABEL.to_string(),
+                DISMISS_LABEL.to_string(),
+            ))
+            .show_with_result(move |result| {
+                let open = match result {
+                    MessageDialogResult::Ok => true,
+                    MessageDialogResult::Custom(label) => label == OPEN_LABEL,
+                    _ => false,
+                };
+                if open {
+                    open_page(&handle, &window, &server, &route);
+                }
+            });
+    });
+}
+
+/// Point `window` at `route` on `server` and bring it forward.
+pub fn open_page(app: &AppHandle, window: &WebviewWindow, server: &Url, route: &Route) {
+    let url = route.url(server);
+    if let Err(e) = window.navigate(url.clone()) {
+        log_backend_error(app, &format!("Failed to open {url}: {e}"));
+    }
+    if let Err(e) = window.set_focus() {
+        log_backend_error(app, &format!("Failed to focus window: {e}"));
+    }
+}
+
+/// Tell the user about newly failed connectors, offering to open the failed
+/// connector (or the status page, for several) in `window`.
+fn notify(app: &AppHandle, window: WebviewWindow, server: Url, failed: &[&FailedConnector]) {
+    let (message, route) = match failed {
+        [connector] => (
+            format!(
+                "The connector \u{201c}{}\u{201d} failed to index.",
+                connector.display_name()
+            ),
+            Route::admin(format!("connector/{}", connector.cc_pair_id)),
+        ),
+        _ => (
+            format!(
+                "{} connectors failed to index: {}.",
+                failed.len(),
+                failed
+                    .iter()
+                    .map(|connector| connector.display_name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            admin_start(),
+        ),
+    };
+    offer_open(app, window, server, "Connector Failed", message, route);
+}
+
+/// One open window per profile (and the default one), with its server.
+fn server_windows(app: &AppHandle) -> BTreeMap<String, (WebviewWindow, Url)> {
+    let config = app.state::<ConfigState>().config();
+    let partitions = app.state::<WindowPartitions>();
+    let mut windows = BTreeMap::new();
+    for (label, window) in app.webview_windows() {
+        if is_bundled_page_window(&label) {
+            continue;
+        }
+        let partition = partitions.partition_of(&label);
+        if windows.contains_key(&partition) {
+            continue;
+        }
+        if let Some(server) = partition_server(&config, &partition).and_then(|url| url.parse().ok())
+        {
+            windows.insert(partition, (window, server));
+        }
+    }
+    windows
+}
+
+#[derive(Deserialize)]
+struct Me {
+    #[serde(default)]
+    role: Option<String>,
+}
+
+/// Whether the user logged in to `server` in `window` is one of its admins.
+async fn is_admin(app: &AppHandle, window: &WebviewWindow, server: &Url) -> bool {
+    match get_as_window_user::<Me>(window, server, "/api/me").await {
+        Ok(me) => me.and_then(|me| me.role).as_deref() == Some("admin"),
+        Err(e) => {
+            log_debug(app, &format!("Admin check on {server}: {e}"));
+            false
+        }
+    }
+}
+
+async fn check_connectors(app: &AppHandle, partition: &str, window: &WebviewWindow, server: &Url) {
+    let failed: Vec<FailedConnector> =
+        match get_as_window_user(window, server, FAILED_CONNECTORS_PATH).await {
+            Ok(Some(failed)) => failed,
+            Ok(None) => return,
+            Err(e) => {
+                log_debug(app, &format!("Failed connectors ({partition}): {e}"));
+                return;
+            }
+        };
+    let new = app.state::<FailedConnectors>().update(partition, &failed);
+    if !new.is_empty() && app.state::<ConfigState>().config().notifications.enabled {
+        notify(app, window.clone(), server.clone(), &new);
+    }
+}
+
+/// Check each server the user is an admin on for pending approvals, and,
+/// in admin mode, for failed connectors, for as long as the app runs.
+pub fn start_polling(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            for (partition, (window, server)) in server_windows(&app) {
+                if !is_admin(&app, &window, &server).await {
+                    continue;
+                }
+                if admin_mode(&app.state::<ConfigState>().config(), &partition) {
+                    check_connectors(&app, &partition, &window, &server).await;
+                }
+                crate::approvals::check(&app, &partition, &window, &server).await;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProfileSettings;
+
+    fn profile(admin_mode: Option<bool>) -> ProfileSettings {
+        ProfileSettings {
+            server_url: "https://onyx.example.com".to_string(),
+            titlebar: None,
+            admin_mode,
+            accounts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn profiles_can_override_admin_mode() {
+        let mut config = AppConfig {
+            admin_mode: true,
+            ..AppConfig::default()
+        };
+        config.profiles.insert("ops".to_string(), profile(None));
+        config
+            .profiles
+            .insert("chat".to_string(), profile(Some(false)));
+
+        assert!(admin_mode(&config, DEFAULT_PARTITION));
+        assert!(admin_mode(&config, "profile-ops"));
+        assert!(!admin_mode(&config, "profile-chat"));
+        assert!(!admin_mode(&config, "origin-https://onyx.example.com"));
+    }
+
+    #[test]
+    fn only_connectors_that_just_failed_are_new() {
+        let failed = |ids: &[i64]| -> Vec<FailedConnector> {
+            ids.iter()
+                .map(|&cc_pair_id| FailedConnector {
+                    cc_pair_id,
+                    name: None,
+                })
+                .collect()
+        };
+        let ids = |new: Vec<&FailedConnector>| -> Vec<i64> {
+            new.iter().map(|connector| connector.cc_pair_id).collect()
+        };
+
+        assert!(new_failures(None, &failed(&[1, 2])).is_empty());
+        let previous = BTreeSet::from([1, 2]);
+        assert_eq!(ids(new_failures(Some(&previous), &failed(&[2, 3]))), [3]);
+        assert!(new_failures(Some(&previous), &failed(&[])).is_empty());
+    }
+}
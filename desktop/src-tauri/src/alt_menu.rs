@@ -123,17 +123,11 @@ pub use linux::setup_alt_menu_toggle;
 // entirely.
 #[cfg(target_os = "windows")]
 mod windows {
-    use tauri::Webview;
-
-    const ALT_MENU_SCRIPT: &str = include_str!("scripts/alt_menu_windows.js");
+    use tauri::{Manager, Webview};
 
     pub fn inject_alt_menu_script(webview: &Webview) {
-        if let Err(e) = webview.eval(ALT_MENU_SCRIPT) {
-            crate::debug_log::log_backend_error(
-                webview.app_handle(),
-                &format!("Failed to inject Alt-menu toggle script: {e}"),
-            );
-        }
+        let script = crate::assets::script(webview.app_handle(), crate::assets::Script::AltMenu);
+        crate::watchdog::eval(webview, &*script, "inject Alt-menu toggle script");
     }
 }
 
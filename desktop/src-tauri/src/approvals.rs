@@ -0,0 +1,268 @@
+// Pending admin actions, surfaced as they come up: people asking to join
+// the server (which can be approved right from the prompt) and connectors
+// whose credentials have expired or been revoked (which open the connector
+// to fix). Both are to-dos rather than events, so ones already waiting are
+// shown on the first check too, and each is shown once per session. Only
+// checked for admins (see `admin.rs`); servers that don't have an endpoint,
+// such as join requests off Onyx Cloud, are skipped quietly.
+
+use crate::admin::{admin_start, offer_open, open_page};
+use crate::config::ConfigState;
+use crate::debug_log::{log_backend_error, log_debug};
+use crate::route::Route;
+use crate::server_api::{get_as_window_user, post_as_window_user};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, WebviewWindow};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogResult};
+use url::Url;
+
+const PENDING_USERS_PATH: &str = "/api/tenants/users/pending";
+const APPROVE_USER_PATH: &str = "/api/tenants/users/invite/approve";
+const CONNECTOR_STATUS_PATH: &str = "/api/manage/admin/connector/indexing-status";
+const USERS_PAGE: &str = "users";
+const APPROVE_LABEL: &str = "Approve";
+const OPEN_LABEL: &str = "Open";
+const LATER_LABEL: &str = "Later";
+
+#[derive(Debug, Deserialize)]
+struct PendingUser {
+    email: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConnectorStatus {
+    cc_pair_id: i64,
+    #[serde(default)]
+    name: Option<String>,
+    /// `INVALID` once the connector's credential stops working.
+    #[serde(default)]
+    cc_pair_status: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ApproveUser<'a> {
+    email: &'a str,
+}
+
+/// Managed state: the approvals already shown, by partition.
+#[derive(Default)]
+pub struct PendingApprovals(Mutex<BTreeMap<String, BTreeSet<String>>>);
+
+impl PendingApprovals {
+    /// Which of `keys` haven't been shown in `partition` yet, marking them
+    /// shown.
+    fn unseen(&self, partition: &str, keys: impl IntoIterator<Item = String>) -> BTreeSet<String> {
+        let mut shown = self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let in_partition = shown.entry(partition.to_string()).or_default();
+        let unseen = keys
+            .into_iter()
+            .filter(|key| in_partition.insert(key.clone()))
+            .collect();
+        drop(shown);
+        unseen
+    }
+}
+
+/// The connectors in `statuses` whose credentials need renewing.
+fn expired_credentials(statuses: &[ConnectorStatus]) -> Vec<&ConnectorStatus> {
+    statuses
+        .iter()
+        .filter(|status| status.cc_pair_status.as_deref() == Some("INVALID"))
+        .collect()
+}
+
+/// GET `path` for a check, or `None` (logged for `--debug`) when there's
+/// nothing to go on.
+async fn fetch<T: serde::de::DeserializeOwned>(
+    app: &AppHandle,
+    window: &WebviewWindow,
+    server: &Url,
+    path: &str,
+) -> Option<T> {
+    match get_as_window_user(window, server, path).await {
+        Ok(value) => value,
+        Err(e) => {
+            log_debug(app, &format!("Pending approvals ({path}): {e}"));
+            None
+        }
+    }
+}
+
+/// Ask whether to let `email` in, approving it on `server` or opening the
+/// users page in `window`.
+fn offer_approval(app: &AppHandle, window: WebviewWindow, server: Url, email: String) {
+    let title = "Join Request";
+    let message = format!("{email} asked to join Onyx.");
+    crate::do_not_disturb::notify(app, title, &message.clone(), move |app| {
+        let handle = app.clone();
+        app.dialog()
+            .message(message)
+            .title(title)
+            .buttons(MessageDialogButtons::YesNoCancelCustom(
+                APPROVE_LABEL.to_string(),
+                OPEN_LABEL.to_string(),
+                LATER_LABEL.to_string(),
+            ))
+            .show_with_result(move |result| match result {
+                MessageDialogResult::Yes => approve(handle, window, server, email),
+                MessageDialogResult::Custom(label) if label == APPROVE_LABEL => {
+                    approve(handle, window, server, email);
+                }
+                MessageDialogResult::No => {
+                    open_page(&handle, &window, &server, &Route::admin(USERS_PAGE));
+                }
+                MessageDialogResult::Custom(label) if label == OPEN_LABEL => {
+                    open_page(&handle, &window, &server, &Route::admin(USERS_PAGE));
+                }
+                _ => {}
+            });
+    });
+}
+
+fn approve(app: AppHandle, window: WebviewWindow, server: Url, email: String) {
+    // Off the dialog's callback, which runs on the main thread.
+    tauri::async_runtime::spawn(async move {
+        let approved = post_as_window_user(
+            &window,
+            &server,
+            APPROVE_USER_PATH,
+            &ApproveUser { email: &email },
+        )
+        .await;
+        match approved {
+            Ok(true) => log_debug(&app, &format!("Approved {email} on {server}")),
+            Ok(false) => log_backend_error(
+                &app,
+                &format!("Failed to approve {email}: not allowed on {server}"),
+            ),
+            Err(e) => log_backend_error(&app, &format!("Failed to approve {email}: {e}")),
+        }
+    });
+}
+
+async fn check_join_requests(
+    app: &AppHandle,
+    partition: &str,
+    window: &WebviewWindow,
+    server: &Url,
+) {
+    let Some(users) = fetch::<Vec<PendingUser>>(app, window, server, PENDING_USERS_PATH).await
+    else {
+        return;
+    };
+    let unseen: Vec<String> = app
+        .state::<PendingApprovals>()
+        .unseen(
+            partition,
+            users.iter().map(|user| format!("user:{}", user.email)),
+        )
+        .into_iter()
+        .filter_map(|key| key.strip_prefix("user:").map(str::to_string))
+        .collect();
+    match unseen.as_slice() {
+        [] => {}
+        [email] => offer_approval(app, window.clone(), server.clone(), email.clone()),
+        _ => offer_open(
+            app,
+            window.clone(),
+            server.clone(),
+            "Join Requests",
+            format!("{} people asked to join Onyx.", unseen.len()),
+            Route::admin(USERS_PAGE),
+        ),
+    }
+}
+
+async fn check_credentials(app: &AppHandle, partition: &str, window: &WebviewWindow, server: &Url) {
+    let Some(statuses) =
+        fetch::<Vec<ConnectorStatus>>(app, window, server, CONNECTOR_STATUS_PATH).await
+    else {
+        return;
+    };
+    let expired = expired_credentials(&statuses);
+    let unseen = app.state::<PendingApprovals>().unseen(
+        partition,
+        expired
+            .iter()
+            .map(|status| format!("credential:{}", status.cc_pair_id)),
+    );
+    let expired: Vec<_> = expired
+        .into_iter()
+        .filter(|status| unseen.contains(&format!("credential:{}", status.cc_pair_id)))
+        .collect();
+    let (message, route) = match expired.as_slice() {
+        [] => return,
+        [status] => (
+            format!(
+                "The credentials for \u{201c}{}\u{201d} need to be renewed.",
+                status
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| format!("Connector {}", status.cc_pair_id))
+            ),
+            Route::admin(format!("connector/{}", status.cc_pair_id)),
+        ),
+        _ => (
+            format!(
+                "{} connectors need their credentials renewed.",
+                expired.len()
+            ),
+            admin_start(),
+        ),
+    };
+    offer_open(
+        app,
+        window.clone(),
+        server.clone(),
+        "Credentials Expired",
+        message,
+        route,
+    );
+}
+
+/// Look for pending approvals on `server` as the admin logged in to it in
+/// `window`.
+pub async fn check(app: &AppHandle, partition: &str, window: &WebviewWindow, server: &Url) {
+    if !app.state::<ConfigState>().config().notifications.enabled {
+        return;
+    }
+    check_join_requests(app, partition, window, server).await;
+    check_credentials(app, partition, window, server).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn only_invalid_credentials_need_renewing() {
+        let statuses: Vec<ConnectorStatus> = serde_json::from_str(
+            r#"[
+                {"cc_pair_id": 1, "name": "Drive", "cc_pair_status": "ACTIVE"},
+                {"cc_pair_id": 2, "name": "Slack", "cc_pair_status": "INVALID"},
+                {"cc_pair_id": 3}
+            ]"#,
+        )
+        .unwrap();
+        let expired: Vec<i64> = expired_credentials(&statuses)
+            .iter()
+            .map(|status| status.cc_pair_id)
+            .collect();
+        assert_eq!(expired, [2]);
+
+        let approvals = PendingApprovals::default();
+        let keys = |keys: &[&str]| keys.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(approvals.unseen("default", keys(&["a", "b"])).len(), 2);
+        assert_eq!(
+            approvals.unseen("default", keys(&["b", "c"])),
+            BTreeSet::from(["c".to_string()])
+        );
+        assert_eq!(approvals.unseen("profile-ops", keys(&["a"])).len(), 1);
+    }
+}
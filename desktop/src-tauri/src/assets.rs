@@ -0,0 +1,253 @@
+// Scripts injected into server pages. Each is compiled in, but a signed
+// copy in `scripts/` under the config directory takes its place, so a
+// broken script can be hotfixed without shipping a new binary. An override
+// is only used if `<file>.sig` next to it holds a valid Ed25519 signature
+// (base64) by the key the build was made with (`ONYX_SCRIPT_SIGNING_KEY`,
+// base64) of the app's version, a newline and its contents. Binding the
+// version keeps a hotfix for one release out of the others, which bundle
+// different scripts; an update leaves old overrides unused. Builds without a
+// key, or with `updates.script_hotfixes` off, only ever use the bundled
+// scripts.
+//
+// Loaded scripts are cached. An override is re-read only when its file
+// changes, so a rejected one is reported once rather than on every page
+// load, and picked up again as soon as it's replaced.
+
+use crate::config::{get_config_dir, ConfigState};
+use crate::debug_log::log_backend_error;
+use aws_lc_rs::signature::{UnparsedPublicKey, ED25519};
+use base64::Engine;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tauri::{AppHandle, Manager};
+
+const SIGNING_KEY: Option<&str> = option_env!("ONYX_SCRIPT_SIGNING_KEY");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Script {
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    Titlebar,
+    ChatLinkIntercept,
+    ConsoleCapture,
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    AltMenu,
+    ServerFeatures,
+    ContextMenu,
+    Spellcheck,
+    ShortcutKeys,
+    Activity,
+    OfflineDrafts,
+    Snippets,
+    #[cfg(feature = "bridge-bench")]
+    BridgeBench,
+    #[cfg(feature = "ui-automation")]
+    UiAutomation,
+}
+
+impl Script {
+    const fn file_name(self) -> &'static str {
+        match self {
+            Self::Titlebar => "titlebar.js",
+            Self::ChatLinkIntercept => "chat_link_intercept.js",
+            Self::ConsoleCapture => "console_capture.js",
+            Self::AltMenu => "alt_menu_windows.js",
+            Self::ServerFeatures => "server_features.js",
+            Self::ContextMenu => "context_menu.js",
+            Self::Spellcheck => "spellcheck.js",
+            Self::ShortcutKeys => "shortcut_keys.js",
+            Self::Activity => "activity.js",
+            Self::OfflineDrafts => "offline_drafts.js",
+            Self::Snippets => "snippets.js",
+            #[cfg(feature = "bridge-bench")]
+            Self::BridgeBench => "bridge_bench.js",
+            #[cfg(feature = "ui-automation")]
+            Self::UiAutomation => "ui_automation.js",
+        }
+    }
+
+    const fn bundled(self) -> &'static str {
+        match self {
+            Self::Titlebar => include_str!("../../src/titlebar.js"),
+            Self::ChatLinkIntercept => include_str!("scripts/chat_link_intercept.js"),
+            Self::ConsoleCapture => include_str!("scripts/console_capture.js"),
+            Self::AltMenu => include_str!("scripts/alt_menu_windows.js"),
+            Self::ServerFeatures => include_str!("scripts/server_features.js"),
+            Self::ContextMenu => include_str!("scripts/context_menu.js"),
+            Self::Spellcheck => include_str!("scripts/spellcheck.js"),
+            Self::ShortcutKeys => include_str!("scripts/shortcut_keys.js"),
+            Self::Activity => include_str!("scripts/activity.js"),
+            Self::OfflineDrafts => include_str!("scripts/offline_drafts.js"),
+            Self::Snippets => include_str!("scripts/snippets.js"),
+            #[cfg(feature = "bridge-bench")]
+            Self::BridgeBench => include_str!("scripts/bridge_bench.js"),
+            #[cfg(feature = "ui-automation")]
+            Self::UiAutomation => include_str!("scripts/ui_automation.js"),
+        }
+    }
+}
+
+/// Where an injected script came from, for diagnostics.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "source", content = "reason")]
+pub enum ScriptSource {
+    Bundled,
+    Override,
+    /// An override was there but couldn't be used.
+    Rejected(String),
+}
+
+struct Loaded {
+    /// The override's modification time when it was read; `None` when there
+    /// was no override.
+    modified: Option<SystemTime>,
+    source: ScriptSource,
+    script: Arc<str>,
+}
+
+/// Managed state: the scripts loaded so far.
+#[derive(Default)]
+pub struct Scripts(Mutex<HashMap<Script, Loaded>>);
+
+impl Scripts {
+    /// Where each script loaded so far came from, by file name.
+    pub fn sources(&self) -> BTreeMap<&'static str, ScriptSource> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .iter()
+            .map(|(script, loaded)| (script.file_name(), loaded.source.clone()))
+            .collect()
+    }
+}
+
+fn override_path(script: Script) -> Option<PathBuf> {
+    Some(get_config_dir()?.join("scripts").join(script.file_name()))
+}
+
+/// Check `signature` (base64) over `script` against the Ed25519 public key
+/// `key` (base64).
+pub fn verify_signature(key: &str, script: &[u8], signature: &str) -> Result<(), String> {
+    let engine = base64::engine::general_purpose::STANDARD;
+    let key = engine
+        .decode(key.trim())
+        .map_err(|e| format!("Invalid signing key: {e}"))?;
+    let signature = engine
+        .decode(signature.trim())
+        .map_err(|e| format!("Invalid signature: {e}"))?;
+    UnparsedPublicKey::new(&ED25519, key)
+        .verify(script, &signature)
+        .map_err(|_| "The signature doesn't match".to_string())
+}
+
+/// What an override's signature covers: the version it's for, then the
+/// script.
+fn signed_text(version: &str, script: &str) -> String {
+    format!("{version}\n{script}")
+}
+
+/// The override at `path`, if it's correctly signed for this version.
+fn read_override(path: &PathBuf) -> Result<String, String> {
+    let key = SIGNING_KEY.ok_or("This build doesn't accept script overrides")?;
+    let script = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut signature_path = path.clone().into_os_string();
+    signature_path.push(".sig");
+    let signature = fs::read_to_string(&signature_path).map_err(|_| "No signature".to_string())?;
+    let version = env!("CARGO_PKG_VERSION");
+    verify_signature(key, signed_text(version, &script).as_bytes(), &signature)?;
+    Ok(script)
+}
+
+/// The source of `script` to inject: a valid override, or the bundled one.
+pub fn script(app: &AppHandle, script: Script) -> Arc<str> {
+    let path = app
+        .state::<ConfigState>()
+        .config()
+        .updates
+        .script_hotfixes
+        .then(|| override_path(script))
+        .flatten();
+    let modified = path
+        .as_ref()
+        .and_then(|path| fs::metadata(path).ok())
+        .and_then(|metadata| metadata.modified().ok());
+
+    let scripts = app.state::<Scripts>();
+    let mut loaded = scripts
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    if let Some(cached) = loaded.get(&script) {
+        if cached.modified == modified {
+            return cached.script.clone();
+        }
+    }
+
+    let (source, source_text): (ScriptSource, Arc<str>) = match (path, modified) {
+        (Some(path), Some(_)) => match read_override(&path) {
+            Ok(text) => (ScriptSource::Override, Arc::from(text)),
+            Err(e) => {
+                log_backend_error(
+                    app,
+                    &format!("Ignoring {}: {e}; using the bundled script", path.display()),
+                );
+                (ScriptSource::Rejected(e), Arc::from(script.bundled()))
+            }
+        },
+        _ => (ScriptSource::Bundled, Arc::from(script.bundled())),
+    };
+    loaded.insert(
+        script,
+        Loaded {
+            modified,
+            source,
+            script: source_text.clone(),
+        },
+    );
+    source_text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{signed_text, verify_signature};
+    use aws_lc_rs::rand::SystemRandom;
+    use aws_lc_rs::signature::{Ed25519KeyPair, KeyPair};
+    use base64::Engine;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn only_correctly_signed_scripts_verify() {
+        let engine = base64::engine::general_purpose::STANDARD;
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let key = engine.encode(pair.public_key().as_ref());
+        let script = b"console.log('patched');";
+        let signature = engine.encode(pair.sign(script).as_ref());
+
+        assert!(verify_signature(&key, script, &signature).is_ok());
+        assert!(verify_signature(&key, b"console.log('tampered');", &signature).is_err());
+        assert!(verify_signature(&key, script, "not base64").is_err());
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn signatures_only_hold_for_their_version() {
+        let engine = base64::engine::general_purpose::STANDARD;
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let key = engine.encode(pair.public_key().as_ref());
+        let script = "console.log('patched');";
+        let signature = engine.encode(pair.sign(signed_text("1.2.0", script).as_bytes()).as_ref());
+
+        assert!(
+            verify_signature(&key, signed_text("1.2.0", script).as_bytes(), &signature).is_ok()
+        );
+        assert!(
+            verify_signature(&key, signed_text("1.3.0", script).as_bytes(), &signature).is_err()
+        );
+        assert!(verify_signature(&key, script.as_bytes(), &signature).is_err());
+    }
+}
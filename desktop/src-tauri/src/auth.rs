@@ -0,0 +1,506 @@
+// Signing in through the system browser, for identity providers that refuse
+// to run inside an embedded webview. File > Sign In with Browser (or
+// `start_browser_login`) opens the server's desktop sign-in page
+// (`/auth/desktop`) in the default browser, with a one-time `state` and the
+// `onyx://auth/callback` link to come back to. Once the provider is done,
+// the server sends the browser to that link with the session token; the app
+// checks the state, keeps the token in the OS keychain, and sets it as the
+// session cookie in the window that asked, which reloads signed in.
+//
+// The kept token is put back into a new window whose webview data has lost
+// its session cookie (`restore_session`), and forgotten as soon as a window
+// on that data lands on the login page (signed out, or the session ran out).
+//
+// File > Sign Out (or `logout`) ends the session on the server and clears
+// what the webview data keeps for it: the server's cookies, its local and
+// session storage, and the kept token. Every window on that data and server
+// then goes to the login page. Other accounts and profiles have their own
+// data, so they stay signed in.
+//
+// On-prem proxies that sign people in with their Windows account (Kerberos
+// or NTLM) only get an answer from WebView2 for hosts it's told to trust;
+// anywhere else it shows a password prompt for every 401, again and again.
+// With `auth.integrated_windows_auth` the configured servers are on that
+// list. It's one of WebView2's browser arguments, so it's settled at startup
+// (`init_integrated_auth`). WKWebView and WebKitGTK have no such list.
+
+use crate::config::{validate_server_url, AppConfig, ConfigState};
+use crate::debug_log::{log_backend_error, log_debug};
+use crate::route::Route;
+use crate::window::{
+    is_bundled_page_window, open_in_default_browser, same_origin, WindowPartitions,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tauri::webview::Cookie;
+use tauri::{AppHandle, Manager, Webview, WebviewWindow};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+use url::Url;
+
+pub const SCHEME: &str = "onyx";
+const CALLBACK_URL: &str = "onyx://auth/callback";
+const DESKTOP_LOGIN_PATH: &str = "/auth/desktop";
+/// The web app's session cookie.
+const SESSION_COOKIE: &str = "fastapiusersauth";
+/// How long the browser has to come back.
+const LOGIN_TIMEOUT: Duration = Duration::from_mins(10);
+const KEYCHAIN_SERVICE: &str = "app.onyx.desktop";
+/// Run in a window on the server to drop what the page kept for the user.
+const CLEAR_STORAGE_SCRIPT: &str = "try { localStorage.clear(); sessionStorage.clear(); } catch {}";
+
+/// A sign-in waiting in the browser.
+struct PendingLogin {
+    window: String,
+    partition: String,
+    server: Url,
+    started: Instant,
+}
+
+/// Managed state: the sign-ins waiting in the browser, by `state`.
+#[derive(Default)]
+pub struct PendingLogins(Mutex<HashMap<String, PendingLogin>>);
+
+impl PendingLogins {
+    fn insert(&self, state: String, login: PendingLogin) {
+        let mut logins = self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        logins.retain(|_, login| login.started.elapsed() < LOGIN_TIMEOUT);
+        logins.insert(state, login);
+    }
+
+    /// The sign-in `state` was handed out for, once, if it hasn't timed out.
+    fn take(&self, state: &str) -> Option<PendingLogin> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(state)
+            .filter(|login| login.started.elapsed() < LOGIN_TIMEOUT)
+    }
+}
+
+/// What the server sent the browser back with.
+#[derive(Debug, PartialEq, Eq)]
+struct Callback {
+    state: String,
+    token: String,
+}
+
+/// A token kept in the keychain, with the server it's for.
+#[derive(Serialize, Deserialize)]
+struct StoredSession {
+    server: Url,
+    token: String,
+}
+
+/// The hosts `WebView2` may sign in to with the Windows account, as its
+/// `--auth-server-allowlist` takes them. Empty unless it's turned on.
+static INTEGRATED_AUTH_SERVERS: OnceLock<String> = OnceLock::new();
+
+/// The configured servers' hosts (the main one and every profile's).
+fn integrated_auth_allowlist(config: &AppConfig) -> String {
+    let hosts: BTreeSet<String> = std::iter::once(&config.server_url)
+        .chain(config.profiles.values().map(|profile| &profile.server_url))
+        .filter_map(|server| validate_server_url(server).ok())
+        .filter_map(|server| server.host_str().map(str::to_ascii_lowercase))
+        .collect();
+    hosts.into_iter().collect::<Vec<_>>().join(",")
+}
+
+/// Settle which servers get the Windows sign-in. Called once, before any
+/// window exists.
+pub fn init_integrated_auth(config: &AppConfig) {
+    let allowlist = if config.auth.integrated_windows_auth {
+        integrated_auth_allowlist(config)
+    } else {
+        String::new()
+    };
+    let _ = INTEGRATED_AUTH_SERVERS.set(allowlist);
+}
+
+/// The browser arguments for the Windows sign-in, if any (see
+/// `locale::browser_args`).
+pub fn integrated_auth_args() -> String {
+    match INTEGRATED_AUTH_SERVERS.get() {
+        Some(allowlist) if !allowlist.is_empty() => {
+            format!(" --auth-server-allowlist={allowlist}")
+        }
+        _ => String::new(),
+    }
+}
+
+/// The desktop sign-in page on `server`, coming back with `state`.
+fn login_url(server: &Url, state: &str) -> Url {
+    let mut url = server.clone();
+    url.set_fragment(None);
+    url.set_path(DESKTOP_LOGIN_PATH);
+    url.query_pairs_mut()
+        .clear()
+        .append_pair("redirect_uri", CALLBACK_URL)
+        .append_pair("state", state);
+    url
+}
+
+/// Read the callback link: `onyx://auth/callback?state=..&token=..`, or
+/// `error=..` when the server couldn't sign the user in.
+fn parse_callback(url: &Url) -> Result<Callback, String> {
+    if url.host_str() != Some("auth") || url.path() != "/callback" {
+        return Err(format!("Onyx doesn't know what to do with {url}"));
+    }
+    let query = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .filter(|value| !value.is_empty())
+    };
+    if let Some(error) = query("error") {
+        return Err(format!("The server couldn't sign you in: {error}"));
+    }
+    match (query("state"), query("token")) {
+        (Some(state), Some(token)) => Ok(Callback { state, token }),
+        _ => Err("The sign-in link is incomplete. Sign in again from Onyx.".to_string()),
+    }
+}
+
+/// The server `window` is on: its page's, or the configured one on a
+/// bundled page.
+fn window_server(app: &AppHandle, window: &WebviewWindow) -> Result<Url, String> {
+    let url = window
+        .url()
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .map_or_else(
+            || app.state::<ConfigState>().config().server_url.parse(),
+            Ok,
+        )
+        .map_err(|e| format!("Invalid server URL: {e}"))?;
+    url.join("/").map_err(|e| e.to_string())
+}
+
+/// Open the sign-in for `window`'s server in the system browser.
+pub fn start_login(app: &AppHandle, window: &WebviewWindow) -> Result<(), String> {
+    let server = window_server(app, window)?;
+    let state = uuid::Uuid::new_v4().simple().to_string();
+    let url = login_url(&server, &state);
+    app.state::<PendingLogins>().insert(
+        state,
+        PendingLogin {
+            window: window.label().to_string(),
+            partition: app.state::<WindowPartitions>().partition_of(window.label()),
+            server,
+            started: Instant::now(),
+        },
+    );
+    if !open_in_default_browser(url.as_str()) {
+        return Err("Couldn't open the browser to sign in".to_string());
+    }
+    Ok(())
+}
+
+/// The focused chat window, or the main one if none is.
+fn focused_chat_window(app: &AppHandle) -> Option<WebviewWindow> {
+    let windows = app.webview_windows();
+    windows
+        .iter()
+        .find(|(label, window)| {
+            !is_bundled_page_window(label) && window.is_focused().unwrap_or(false)
+        })
+        .map(|(_, window)| window)
+        .or_else(|| windows.get("main"))
+        .cloned()
+}
+
+/// Sign in the focused chat window (the main one if none is) through the
+/// browser.
+pub fn start_focused_login(app: &AppHandle) {
+    if let Some(window) = focused_chat_window(app) {
+        if let Err(e) = start_login(app, &window) {
+            warn(app, e);
+        }
+    }
+}
+
+/// Sign the calling window in through the system browser
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn start_browser_login(app: AppHandle, window: WebviewWindow) -> Result<(), String> {
+    start_login(&app, &window)
+}
+
+/// Handle an `onyx://` link the system opened the app with.
+pub fn open_url(app: &AppHandle, url: &Url) {
+    if url.scheme() != SCHEME {
+        return;
+    }
+    let callback = match parse_callback(url) {
+        Ok(callback) => callback,
+        Err(e) => {
+            warn(app, e);
+            return;
+        }
+    };
+    // Off the event thread: the keychain and cookie store can both block.
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = finish_login(&app, callback) {
+            warn(&app, e);
+        }
+    });
+}
+
+fn finish_login(app: &AppHandle, callback: Callback) -> Result<(), String> {
+    let login = app
+        .state::<PendingLogins>()
+        .take(&callback.state)
+        .ok_or("This sign-in link has expired or was already used. Sign in again from Onyx.")?;
+    let window = app
+        .get_webview_window(&login.window)
+        .ok_or("The window you were signing in to was closed")?;
+    let session = StoredSession {
+        server: login.server.clone(),
+        token: callback.token,
+    };
+    // The window is signed in either way; it's only kept for later.
+    if let Err(e) = store_session(&login.partition, &session) {
+        log_backend_error(app, &format!("Failed to keep the session: {e}"));
+    }
+    set_session_cookie(&window, &session)?;
+    window
+        .navigate(Route::new_chat().url(&login.server))
+        .map_err(|e| e.to_string())?;
+    window.set_focus().map_err(|e| e.to_string())
+}
+
+fn keychain_entry(partition: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, &format!("session:{partition}"))
+        .map_err(|e| format!("Could not access the OS keychain: {e}"))
+}
+
+fn store_session(partition: &str, session: &StoredSession) -> Result<(), String> {
+    let json = serde_json::to_string(session).map_err(|e| e.to_string())?;
+    keychain_entry(partition)?
+        .set_password(&json)
+        .map_err(|e| e.to_string())
+}
+
+fn stored_session(partition: &str) -> Result<Option<StoredSession>, String> {
+    match keychain_entry(partition)?.get_password() {
+        Ok(json) => serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| e.to_string()),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// The session cookie kept from a browser sign-in to `server` in the usual
+/// webview data, for requests made without a window (see `headless.rs`).
+pub fn kept_session_cookie(server: &Url) -> Result<Option<String>, String> {
+    Ok(stored_session(crate::window::DEFAULT_PARTITION)?
+        .filter(|session| same_origin(&session.server, server))
+        .map(|session| format!("{SESSION_COOKIE}={}", session.token)))
+}
+
+fn set_session_cookie(window: &WebviewWindow, session: &StoredSession) -> Result<(), String> {
+    let host = session
+        .server
+        .host_str()
+        .ok_or("The server has no host name")?;
+    let cookie = Cookie::build((SESSION_COOKIE, session.token.clone()))
+        .domain(host.to_string())
+        .path("/")
+        .secure(session.server.scheme() == "https")
+        .http_only(true)
+        .build();
+    window.set_cookie(cookie).map_err(|e| e.to_string())
+}
+
+/// Give `window` back a session kept from a browser sign-in if its webview
+/// data no longer has one, reloading it signed in.
+pub fn restore_session(app: &AppHandle, window: &WebviewWindow) {
+    let partition = app.state::<WindowPartitions>().partition_of(window.label());
+    let window = window.clone();
+    // Reading cookies deadlocks on Windows from the main thread.
+    tauri::async_runtime::spawn(async move {
+        let app = window.app_handle();
+        let session = match stored_session(&partition) {
+            Ok(Some(session)) => session,
+            Ok(None) => return,
+            Err(e) => {
+                log_debug(app, &format!("No kept session for {partition}: {e}"));
+                return;
+            }
+        };
+        let signed_in = window
+            .cookies_for_url(session.server.clone())
+            .is_ok_and(|cookies| cookies.iter().any(|c| c.name() == SESSION_COOKIE));
+        if signed_in {
+            return;
+        }
+        let restored = set_session_cookie(&window, &session).and_then(|()| {
+            window
+                .navigate(Route::new_chat().url(&session.server))
+                .map_err(|e| e.to_string())
+        });
+        if let Err(e) = restored {
+            log_backend_error(app, &format!("Failed to restore the session: {e}"));
+        }
+    });
+}
+
+/// Forget the kept session for `webview`'s data once it shows the login
+/// page, which a working session never does.
+pub fn forget_session_on_login_page(webview: &Webview) {
+    if !webview
+        .url()
+        .is_ok_and(|url| crate::onboarding::is_login_page(&url))
+    {
+        return;
+    }
+    let app = webview.app_handle().clone();
+    let partition = app
+        .state::<WindowPartitions>()
+        .partition_of(webview.label());
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = forget_session(&partition) {
+            log_backend_error(&app, &format!("Failed to forget the session: {e}"));
+        }
+    });
+}
+
+fn forget_session(partition: &str) -> Result<(), String> {
+    match keychain_entry(partition)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Sign `window`'s user out of its server, in every window on its webview
+/// data.
+pub async fn sign_out(app: &AppHandle, window: &WebviewWindow) -> Result<(), String> {
+    let server = window_server(app, window)?;
+    let partitions = app.state::<WindowPartitions>();
+    let partition = partitions.partition_of(window.label());
+
+    // The local session goes either way, even if the server can't be told.
+    if let Err(e) = crate::server_api::sign_out(window, &server).await {
+        log_backend_error(app, &format!("The server didn't take the sign-out: {e}"));
+    }
+    // The partition's windows share one cookie store, so once is enough.
+    // (Reading cookies deadlocks on Windows from the main thread; this runs
+    // on the async runtime.)
+    for cookie in window
+        .cookies_for_url(server.clone())
+        .map_err(|e| e.to_string())?
+    {
+        window
+            .delete_cookie(cookie)
+            .map_err(|e| format!("Failed to clear a cookie: {e}"))?;
+    }
+    if let Err(e) = forget_session(&partition) {
+        log_backend_error(app, &format!("Failed to forget the session: {e}"));
+    }
+    #[cfg(feature = "offline-cache")]
+    crate::offline_cache::clear(app);
+
+    let login = Route::Login.url(&server);
+    for (label, other) in app.webview_windows() {
+        let on_server = other.url().is_ok_and(|url| same_origin(&url, &server));
+        if is_bundled_page_window(&label) || partitions.partition_of(&label) != partition {
+            continue;
+        }
+        if on_server {
+            crate::watchdog::eval(other.as_ref(), CLEAR_STORAGE_SCRIPT, "clear page storage");
+        }
+        if on_server || label == window.label() {
+            other.navigate(login.clone()).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Sign the focused chat window's user out (the main window's if none is
+/// focused).
+pub fn sign_out_focused(app: &AppHandle) {
+    let Some(window) = focused_chat_window(app) else {
+        return;
+    };
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = sign_out(&app, &window).await {
+            warn_titled(&app, "Sign Out", e);
+        }
+    });
+}
+
+/// Sign the calling window's user out of its server, clearing its cookies,
+/// page storage and kept session, and go to the login page in every window
+/// on its webview data
+#[tauri::command]
+pub async fn logout(app: AppHandle, window: WebviewWindow) -> Result<(), String> {
+    sign_out(&app, &window).await
+}
+
+fn warn(app: &AppHandle, message: String) {
+    warn_titled(app, "Sign In", message);
+}
+
+fn warn_titled(app: &AppHandle, title: &str, message: String) {
+    app.dialog()
+        .message(message)
+        .title(title)
+        .kind(MessageDialogKind::Warning)
+        .show(|_| {});
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn the_browser_comes_back_with_the_state_it_was_sent() {
+        let server = Url::parse("https://onyx.example.com/chat?chatId=9").unwrap();
+        assert_eq!(
+            login_url(&server, "abc").as_str(),
+            "https://onyx.example.com/auth/desktop?redirect_uri=onyx%3A%2F%2Fauth%2Fcallback&state=abc"
+        );
+
+        let callback = |link: &str| parse_callback(&Url::parse(link).unwrap());
+        assert_eq!(
+            callback("onyx://auth/callback?state=abc&token=t0k"),
+            Ok(Callback {
+                state: "abc".to_string(),
+                token: "t0k".to_string(),
+            })
+        );
+        assert!(callback("onyx://auth/callback?state=abc").is_err());
+        assert!(callback("onyx://auth/callback?error=access_denied&state=abc").is_err());
+        assert!(callback("onyx://chat/callback?state=abc&token=t0k").is_err());
+    }
+
+    #[test]
+    fn windows_sign_in_is_allowed_for_every_configured_server() {
+        let mut config = AppConfig {
+            server_url: "https://Onyx.corp.example".to_string(),
+            ..AppConfig::default()
+        };
+        config.profiles.insert(
+            "staging".to_string(),
+            crate::config::ProfileSettings {
+                server_url: "https://staging.corp.example:8443".to_string(),
+                titlebar: None,
+                admin_mode: None,
+                accounts: Vec::new(),
+            },
+        );
+        assert_eq!(
+            integrated_auth_allowlist(&config),
+            "onyx.corp.example,staging.corp.example"
+        );
+    }
+}
@@ -0,0 +1,480 @@
+// A local HTTP API for scripts and launchers (Raycast, Alfred, Stream Deck)
+// to drive the app, off unless `automation_api.enabled` is set:
+//
+// - `GET /status`: the app's version, server, open windows, connection
+//   quality, and whether it's locked;
+// - `POST /ask` with `{"query": "..."}`: a new chat that asks right away,
+//   or, for a query starting `local:`, the chats kept on this machine that
+//   have its words (see `local_search.rs`), answered even while the server
+//   is down;
+// - `POST /open-chat/<id>`: the chat with that id.
+//
+// It listens on 127.0.0.1 only (`automation_api.port`), and every request
+// has to carry `Authorization: Bearer <automation_api.token>`. The token is
+// made up when the API is first turned on, kept encrypted with the other
+// secrets, and shown in Settings; a new one takes effect at once. To keep
+// web pages out (a page can reach loopback, or rebind a name to it), a
+// request with an `Origin` header, or a `Host` other than the loopback
+// address, is refused. While the app is locked only `/status` answers.
+//
+// Like the mock server, this is a small blocking HTTP/1.1 server: a thread
+// per connection, closed after one response.
+
+use crate::config::{AutomationApiSettings, ConfigState};
+use crate::route::Route;
+use crate::secrets::SecretString;
+use crate::window::{is_bundled_page_window, open_route, SETTINGS_WINDOW_LABEL};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, WebviewWindow};
+
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// Larger bodies are refused rather than read.
+const MAX_BODY: u64 = 64 * 1024;
+
+/// Managed state: the running listener, if the API is on.
+#[derive(Default)]
+pub struct AutomationApi(Mutex<Option<Listener>>);
+
+/// Register the API's state, with no listener until it's turned on.
+pub fn manage(builder: tauri::Builder<tauri::Wry>) -> tauri::Builder<tauri::Wry> {
+    builder.manage(AutomationApi::default())
+}
+
+struct Listener {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+}
+
+impl Listener {
+    fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Wakes the accept loop so it sees the flag.
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Request {
+    method: String,
+    path: String,
+    host: Option<String>,
+    origin: Option<String>,
+    authorization: Option<String>,
+    body: Vec<u8>,
+}
+
+/// What an allowed request asks for.
+#[derive(Debug, PartialEq)]
+enum Action {
+    Status,
+    Open(Route),
+    #[cfg(feature = "offline-cache")]
+    SearchLocal(String),
+}
+
+#[derive(Debug, PartialEq)]
+struct Response {
+    status: u16,
+    body: Value,
+}
+
+impl Response {
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message }),
+        }
+    }
+}
+
+const fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        423 => "Locked",
+        503 => "Service Unavailable",
+        _ => "Bad Request",
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, u16> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).map_err(|_| 400_u16)?;
+    let mut parts = request_line.split_whitespace();
+    let mut request = Request {
+        method: parts.next().ok_or(400_u16)?.to_string(),
+        path: parts.next().ok_or(400_u16)?.to_string(),
+        ..Request::default()
+    };
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).map_err(|_| 400_u16)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => content_length = value.parse().map_err(|_| 400_u16)?,
+            "host" => request.host = Some(value),
+            "origin" => request.origin = Some(value),
+            "authorization" => request.authorization = Some(value),
+            _ => {}
+        }
+    }
+    if content_length > MAX_BODY {
+        return Err(413);
+    }
+    reader
+        .take(content_length)
+        .read_to_end(&mut request.body)
+        .map_err(|_| 400_u16)?;
+    Ok(request)
+}
+
+/// Whether `request` came from a script on this machine with the token,
+/// rather than from a web page.
+fn check_caller(request: &Request, port: u16, token: Option<&str>) -> Result<(), Response> {
+    let loopback_hosts = [format!("127.0.0.1:{port}"), format!("localhost:{port}")];
+    if request.origin.is_some()
+        || !request
+            .host
+            .as_ref()
+            .is_some_and(|host| loopback_hosts.iter().any(|allowed| allowed == host))
+    {
+        return Err(Response::error(
+            403,
+            "Requests from web pages aren't allowed",
+        ));
+    }
+    let sent = request
+        .authorization
+        .as_deref()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+    match (sent, token) {
+        (Some(sent), Some(token))
+            if aws_lc_rs::constant_time::verify_slices_are_equal(
+                sent.as_bytes(),
+                token.as_bytes(),
+            )
+            .is_ok() =>
+        {
+            Ok(())
+        }
+        _ => Err(Response::error(401, "Missing or wrong token")),
+    }
+}
+
+#[derive(Deserialize)]
+struct Ask {
+    query: String,
+}
+
+/// What `request` asks for, once it's known to be from an allowed caller.
+fn parse_action(request: &Request) -> Result<Action, Response> {
+    let path = request.path.split(['?', '#']).next().unwrap_or_default();
+    match (request.method.as_str(), path) {
+        ("GET", "/status") => Ok(Action::Status),
+        ("POST", "/ask") => {
+            let ask: Ask = serde_json::from_slice(&request.body)
+                .map_err(|_| Response::error(400, "Send {\"query\": \"...\"}"))?;
+            #[cfg(feature = "offline-cache")]
+            if let Some(query) = crate::local_search::local_query(&ask.query) {
+                if query.is_empty() {
+                    return Err(Response::error(400, "The query is empty"));
+                }
+                return Ok(Action::SearchLocal(query.to_string()));
+            }
+            if ask.query.trim().is_empty() {
+                return Err(Response::error(400, "The query is empty"));
+            }
+            Ok(Action::Open(Route::Search { query: ask.query }))
+        }
+        ("POST", path) if path.starts_with("/open-chat/") => {
+            let id = &path["/open-chat/".len()..];
+            if id.is_empty()
+                || !id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(Response::error(400, "That isn't a chat id"));
+            }
+            Ok(Action::Open(Route::Chat { id: id.to_string() }))
+        }
+        (_, "/status" | "/ask") => Err(Response::error(405, "Method not allowed")),
+        (_, path) if path.starts_with("/open-chat/") => {
+            Err(Response::error(405, "Method not allowed"))
+        }
+        _ => Err(Response::error(404, "Not found")),
+    }
+}
+
+fn status(app: &AppHandle) -> Value {
+    let config = app.state::<ConfigState>().config();
+    let windows = app
+        .webview_windows()
+        .keys()
+        .filter(|label| !is_bundled_page_window(label))
+        .count();
+    let connection = crate::connection::get_connection_quality(app.state());
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "server_url": config.server_url,
+        "windows": windows,
+        "connection": connection,
+        "locked": app.state::<crate::lock::AppLock>().is_locked(),
+    })
+}
+
+fn respond(app: &AppHandle, request: &Request, port: u16) -> Response {
+    let config = app.state::<ConfigState>().config();
+    let token = config
+        .automation_api
+        .token
+        .as_ref()
+        .and_then(SecretString::expose);
+    if let Err(response) = check_caller(request, port, token) {
+        return response;
+    }
+    match parse_action(request) {
+        Err(response) => response,
+        Ok(Action::Status) => Response {
+            status: 200,
+            body: status(app),
+        },
+        Ok(_) if app.state::<crate::lock::AppLock>().is_locked() => {
+            Response::error(423, "Onyx is locked")
+        }
+        #[cfg(feature = "offline-cache")]
+        Ok(Action::SearchLocal(query)) => match crate::local_search::search(app, &query) {
+            Ok(results) => Response {
+                status: 200,
+                body: json!({ "results": results }),
+            },
+            Err(e) => Response::error(503, &e),
+        },
+        Ok(Action::Open(route)) => {
+            open_route(app, &route);
+            Response {
+                status: 202,
+                body: json!({ "ok": true }),
+            }
+        }
+    }
+}
+
+fn write_response(mut stream: &TcpStream, response: &Response) -> std::io::Result<()> {
+    let body = response.body.to_string();
+    let authenticate = if response.status == 401 {
+        "WWW-Authenticate: Bearer\r\n"
+    } else {
+        ""
+    };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{authenticate}Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        body.len(),
+    );
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body.as_bytes())
+}
+
+fn handle_connection(app: &AppHandle, stream: &TcpStream, port: u16) {
+    // A client that hangs up early is its own problem.
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let response = match read_request(stream) {
+        Ok(request) => respond(app, &request, port),
+        Err(status) => Response::error(status, "Couldn't read the request"),
+    };
+    let _ = write_response(stream, &response);
+}
+
+fn listen(app: &AppHandle, port: u16) -> Result<Listener, String> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+        .map_err(|e| format!("Failed to start the automation API on port {port}: {e}"))?;
+    let addr = listener.local_addr().map_err(|e| e.to_string())?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+    let app = app.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if stopped.load(Ordering::Relaxed) {
+                break;
+            }
+            let app = app.clone();
+            thread::spawn(move || handle_connection(&app, &stream, addr.port()));
+        }
+    });
+    Ok(Listener { addr, stop })
+}
+
+/// Check `[automation_api]` as submitted from the settings window or the
+/// file.
+pub fn validate(settings: &AutomationApiSettings) -> Result<(), String> {
+    if settings.enabled && settings.port < 1024 {
+        return Err("The automation API needs a port from 1024 up".to_string());
+    }
+    Ok(())
+}
+
+/// A fresh token, 32 random bytes in URL-safe base64.
+fn new_token() -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    aws_lc_rs::rand::fill(&mut bytes).map_err(|_| "Failed to generate a token".to_string())?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// Start, stop, or move the API to match `automation_api`, making up a
+/// token the first time it's turned on. Called at startup and whenever the
+/// settings change.
+pub fn sync(app: &AppHandle) {
+    let state = app.state::<ConfigState>();
+    let settings = state.config().automation_api;
+    let api = app.state::<AutomationApi>();
+    let mut running = api
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    if running
+        .as_ref()
+        .is_some_and(|listener| settings.enabled && listener.addr.port() == settings.port)
+    {
+        return;
+    }
+    if let Some(listener) = running.take() {
+        listener.stop();
+    }
+    if !settings.enabled {
+        return;
+    }
+
+    if settings.token.is_none() {
+        let result = new_token().and_then(|token| {
+            state.update_and_persist(|config| {
+                config.automation_api.token = Some(SecretString::new(token));
+            })
+        });
+        if let Err(e) = result {
+            crate::debug_log::log_backend_error(
+                app,
+                &format!("Failed to set up the automation API: {e}"),
+            );
+            return;
+        }
+    }
+    match listen(app, settings.port) {
+        Ok(listener) => *running = Some(listener),
+        Err(e) => crate::debug_log::log_backend_error(app, &e),
+    }
+}
+
+/// The automation API's token, made up afresh if `regenerate`; only for
+/// Settings
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn automation_token(
+    window: WebviewWindow,
+    state: tauri::State<ConfigState>,
+    regenerate: bool,
+) -> Result<Option<String>, String> {
+    if window.label() != SETTINGS_WINDOW_LABEL {
+        return Err("The automation token is only shown in Settings".to_string());
+    }
+    if regenerate {
+        let token = new_token()?;
+        state.update_and_persist(|config| {
+            config.automation_api.token = Some(SecretString::new(token));
+        })?;
+    }
+    Ok(state
+        .config()
+        .automation_api
+        .token
+        .as_ref()
+        .and_then(|token| token.expose().map(str::to_string)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_local_scripts_with_the_token_get_in() {
+        let request = |path: &str, host: &str, origin: Option<&str>, token: &str| Request {
+            method: "POST".to_string(),
+            path: path.to_string(),
+            host: Some(host.to_string()),
+            origin: origin.map(str::to_string),
+            authorization: Some(format!("Bearer {token}")),
+            body: br#"{"query": "What's our PTO policy?"}"#.to_vec(),
+        };
+
+        let ask = request("/ask", "127.0.0.1:4782", None, "s3cret");
+        assert!(check_caller(&ask, 4782, Some("s3cret")).is_ok());
+        assert_eq!(
+            parse_action(&ask),
+            Ok(Action::Open(Route::Search {
+                query: "What's our PTO policy?".to_string()
+            }))
+        );
+        let wrong_token = request("/ask", "127.0.0.1:4782", None, "guess");
+        assert_eq!(
+            check_caller(&wrong_token, 4782, Some("s3cret")).map_err(|r| r.status),
+            Err(401)
+        );
+        let from_a_page = request(
+            "/ask",
+            "127.0.0.1:4782",
+            Some("https://evil.example"),
+            "s3cret",
+        );
+        assert_eq!(
+            check_caller(&from_a_page, 4782, Some("s3cret")).map_err(|r| r.status),
+            Err(403)
+        );
+        let rebound = request("/ask", "evil.example:4782", None, "s3cret");
+        assert_eq!(
+            check_caller(&rebound, 4782, Some("s3cret")).map_err(|r| r.status),
+            Err(403)
+        );
+        assert_eq!(
+            check_caller(&ask, 4782, None).map_err(|r| r.status),
+            Err(401)
+        );
+
+        assert_eq!(
+            parse_action(&request("/open-chat/3f2c-9a", "", None, "")),
+            Ok(Action::Open(Route::Chat {
+                id: "3f2c-9a".to_string()
+            }))
+        );
+        assert_eq!(
+            parse_action(&request("/open-chat/../admin", "", None, "")).map_err(|r| r.status),
+            Err(400)
+        );
+        assert_eq!(
+            parse_action(&request("/status", "", None, "")).map_err(|r| r.status),
+            Err(405)
+        );
+    }
+}
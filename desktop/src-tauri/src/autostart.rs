@@ -0,0 +1,197 @@
+// Starting Onyx when the user logs in (`launch_at_login`), through each
+// platform's own per-user login items, so nothing needs an administrator: a
+// value under HKCU's `Run` key on Windows, a launch agent on macOS, and an
+// XDG autostart entry on Linux. Each points at the running executable, so
+// it's written again at every start while it's on, in case the app moved.
+//
+// Inside a Flatpak the executable's path only exists in the sandbox, so the
+// host is asked through the Background portal instead, which adds an entry
+// that starts the app the Flatpak way (and may ask the user first). The
+// portal only does that for Flatpaks, so a Snap can't launch at login;
+// that's reported rather than writing an entry that can't work.
+
+use crate::config::ConfigState;
+use crate::debug_log::log_backend_error;
+use crate::sandbox::{sandbox, Sandbox};
+use tauri::{AppHandle, Manager};
+
+/// Add or take out the login item to match `launch_at_login`.
+pub fn sync(app: &AppHandle) {
+    let launch_at_login = app.state::<ConfigState>().config().launch_at_login;
+    let result = match sandbox() {
+        #[cfg(target_os = "linux")]
+        Some(Sandbox::Flatpak) => background_portal::request(launch_at_login),
+        Some(Sandbox::Snap) if launch_at_login => {
+            Err("Launching at login isn't available in a Snap".to_string())
+        }
+        Some(_) => Ok(()),
+        None => sync_login_item(launch_at_login),
+    };
+    if let Err(e) = result {
+        log_backend_error(app, &format!("Failed to update launching at login: {e}"));
+    }
+}
+
+fn sync_login_item(launch_at_login: bool) -> Result<(), String> {
+    if launch_at_login {
+        std::env::current_exe()
+            .map_err(|e| e.to_string())
+            .and_then(|exe| login_item::add(&exe))
+    } else {
+        login_item::remove()
+    }
+}
+
+/// The Background portal, over GIO's D-Bus.
+#[cfg(target_os = "linux")]
+mod background_portal {
+    use gtk::gio::{BusType, Cancellable, DBusCallFlags};
+    use gtk::glib::{ToVariant, Variant, VariantDict};
+
+    /// Ask the host to start the app at login, or no longer. Whatever the
+    /// user answers a prompt is up to the portal; only a failed request is
+    /// an error.
+    pub fn request(autostart: bool) -> Result<(), String> {
+        let bus = gtk::gio::bus_get_sync(BusType::Session, None::<&Cancellable>)
+            .map_err(|e| e.to_string())?;
+        let options = VariantDict::new(None);
+        options.insert_value("reason", &"Open Onyx when you log in".to_variant());
+        options.insert_value("autostart", &autostart.to_variant());
+        let parameters = Variant::tuple_from_iter(["".to_variant(), options.end()]);
+        bus.call_sync(
+            Some("org.freedesktop.portal.Desktop"),
+            "/org/freedesktop/portal/desktop",
+            "org.freedesktop.portal.Background",
+            "RequestBackground",
+            Some(&parameters),
+            None,
+            DBusCallFlags::NONE,
+            5000,
+            None::<&Cancellable>,
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod login_item {
+    use std::io;
+    use std::path::Path;
+    use winreg::enums::HKEY_CURRENT_USER;
+    use winreg::RegKey;
+
+    const RUN_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+    const VALUE_NAME: &str = "Onyx";
+
+    pub fn add(exe: &Path) -> Result<(), String> {
+        let (run, _) = RegKey::predef(HKEY_CURRENT_USER)
+            .create_subkey(RUN_KEY)
+            .map_err(|e| e.to_string())?;
+        run.set_value(VALUE_NAME, &format!("\"{}\"", exe.display()))
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn remove() -> Result<(), String> {
+        let (run, _) = RegKey::predef(HKEY_CURRENT_USER)
+            .create_subkey(RUN_KEY)
+            .map_err(|e| e.to_string())?;
+        match run.delete_value(VALUE_NAME) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod login_item {
+    use directories::BaseDirs;
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    /// Where the login item goes.
+    fn item_path() -> Result<PathBuf, String> {
+        let dirs = BaseDirs::new().ok_or("No home folder to add a login item to")?;
+        if cfg!(target_os = "macos") {
+            Ok(dirs
+                .home_dir()
+                .join("Library/LaunchAgents/app.onyx.desktop.plist"))
+        } else {
+            Ok(dirs.config_dir().join("autostart/onyx.desktop"))
+        }
+    }
+
+    /// A launch agent that starts `exe` once, at login.
+    #[cfg(target_os = "macos")]
+    fn item(exe: &Path) -> String {
+        let exe = exe
+            .display()
+            .to_string()
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>Label</key>
+  <string>app.onyx.desktop</string>
+  <key>ProgramArguments</key>
+  <array>
+    <string>{exe}</string>
+  </array>
+  <key>RunAtLoad</key>
+  <true/>
+</dict>
+</plist>
+"#
+        )
+    }
+
+    /// A desktop entry that starts `exe`, quoted as the spec asks for
+    /// `Exec`.
+    #[cfg(not(target_os = "macos"))]
+    fn item(exe: &Path) -> String {
+        let mut quoted = String::new();
+        for c in exe.display().to_string().chars() {
+            if matches!(c, '"' | '`' | '$' | '\\') {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        format!(
+            "[Desktop Entry]\nType=Application\nName=Onyx\nExec=\"{}\"\nX-GNOME-Autostart-enabled=true\n",
+            quoted.replace('\\', "\\\\")
+        )
+    }
+
+    pub fn add(exe: &Path) -> Result<(), String> {
+        let path = item_path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::write(&path, item(exe)).map_err(|e| e.to_string())
+    }
+
+    pub fn remove() -> Result<(), String> {
+        match fs::remove_file(item_path()?) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(all(test, not(target_os = "macos")))]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn exec_line_is_quoted() {
+            assert!(item(Path::new("/opt/On yx/onyx")).contains("\nExec=\"/opt/On yx/onyx\"\n"));
+            // Escaped once for the quoted argument, then once more for the
+            // string value it sits in.
+            assert!(item(Path::new("/home/$me/onyx")).contains("\nExec=\"/home/\\\\$me/onyx\"\n"));
+        }
+    }
+}
@@ -0,0 +1,191 @@
+// Unread badge on the Dock / taskbar icon, so @-mentions get noticed while
+// the window is in the background. The server's `/api/notifications` lists
+// the user's undismissed notifications; it's polled through `server_api`,
+// as the main window's user. Whatever was unread when an Onyx window was
+// last focused counts as seen, so focusing a window clears the badge until
+// something new arrives. Focus mode keeps it off (see `focus_mode.rs`).
+
+use crate::config::ConfigState;
+use crate::debug_log::log_debug;
+use crate::focus_mode::FocusMode;
+use crate::server_api::get_as_user;
+use crate::window::is_bundled_page_window;
+use serde::Deserialize;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+const POLL_INTERVAL: Duration = Duration::from_mins(1);
+
+#[derive(Default)]
+struct BadgeState {
+    unread: usize,
+    /// How many of `unread` the user has already seen.
+    seen: usize,
+    /// What the icon currently shows, to skip redundant updates.
+    shown: usize,
+}
+
+/// Managed state for the badge.
+#[derive(Default)]
+pub struct UnreadBadge(Mutex<BadgeState>);
+
+#[derive(Deserialize)]
+struct Notification {
+    #[serde(default)]
+    dismissed: bool,
+}
+
+/// The count to show for `unread` notifications, `seen` of which the user
+/// has already seen, and the new `seen`. Dismissing notifications on the
+/// server lowers what counts as seen along with them, so new ones after
+/// that still show.
+const fn badge_count(unread: usize, seen: usize) -> (usize, usize) {
+    let seen = if seen < unread { seen } else { unread };
+    (unread - seen, seen)
+}
+
+/// Undismissed notifications on the main window's server, or `None` when
+/// logged out.
+async fn fetch_unread(app: &AppHandle) -> Result<Option<usize>, String> {
+    let notifications: Option<Vec<Notification>> = get_as_user(app, "/api/notifications").await?;
+    Ok(notifications.map(|notifications| {
+        notifications
+            .iter()
+            .filter(|notification| !notification.dismissed)
+            .count()
+    }))
+}
+
+fn any_window_focused(app: &AppHandle) -> bool {
+    app.webview_windows().iter().any(|(label, window)| {
+        !is_bundled_page_window(label) && window.is_focused().unwrap_or(false)
+    })
+}
+
+/// A small red dot, since Windows overlay icons can't carry a number.
+#[cfg(target_os = "windows")]
+fn unread_dot() -> tauri::image::Image<'static> {
+    const SIZE: u32 = 16;
+    let center = f64::from(SIZE - 1) / 2.0;
+    let radius = f64::from(SIZE) / 2.0 - 1.0;
+    let rgba = (0..SIZE * SIZE)
+        .flat_map(|i| {
+            let distance = (f64::from(i % SIZE) - center).hypot(f64::from(i / SIZE) - center);
+            // Anti-aliased edge; the value is clamped to 0..=255 first.
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let alpha = ((radius - distance + 0.5).clamp(0.0, 1.0) * 255.0) as u8;
+            [0xe5, 0x39, 0x35, alpha]
+        })
+        .collect();
+    tauri::image::Image::new_owned(rgba, SIZE, SIZE)
+}
+
+fn show(app: &AppHandle, count: usize) {
+    #[cfg(not(target_os = "windows"))]
+    if let Some(window) = app.get_webview_window("main") {
+        let count = i64::try_from(count).ok().filter(|count| *count > 0);
+        if let Err(e) = window.set_badge_count(count) {
+            log_debug(app, &format!("Failed to set unread badge: {e}"));
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    for (label, window) in app.webview_windows() {
+        if !is_bundled_page_window(&label) {
+            let icon = (count > 0).then(unread_dot);
+            if let Err(e) = window.set_overlay_icon(icon) {
+                log_debug(app, &format!("Failed to set unread overlay: {e}"));
+            }
+        }
+    }
+}
+
+fn update(app: &AppHandle, unread: usize, focused: bool) {
+    let count = {
+        let badge = app.state::<UnreadBadge>();
+        let mut state = badge
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let seen = if focused { unread } else { state.seen };
+        let (count, seen) = badge_count(unread, seen);
+        state.unread = unread;
+        state.seen = seen;
+        let count = if app.state::<FocusMode>().any() {
+            0
+        } else {
+            count
+        };
+        if count == state.shown {
+            return;
+        }
+        state.shown = count;
+        count
+    };
+    show(app, count);
+}
+
+/// A chat window got focus: everything unread so far has been seen.
+pub fn mark_seen(app: &AppHandle) {
+    let unread = app
+        .state::<UnreadBadge>()
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .unread;
+    update(app, unread, true);
+}
+
+/// Show the badge as it stands, after focus mode started or ended.
+pub fn refresh(app: &AppHandle) {
+    let unread = app
+        .state::<UnreadBadge>()
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .unread;
+    update(app, unread, false);
+}
+
+/// Poll for unread notifications for as long as the app runs.
+pub fn start_polling(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let enabled = app
+                .state::<ConfigState>()
+                .config()
+                .notifications
+                .unread_badge;
+            // A failed poll leaves the badge as it was.
+            let unread = if enabled {
+                fetch_unread(&app)
+                    .await
+                    .map(Option::unwrap_or_default)
+                    .map_err(|e| log_debug(&app, &format!("Unread badge: {e}")))
+                    .ok()
+            } else {
+                Some(0)
+            };
+            if let Some(unread) = unread {
+                update(&app, unread, any_window_focused(&app));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::badge_count;
+
+    #[test]
+    fn badge_counts_only_unseen_notifications() {
+        assert_eq!(badge_count(3, 0), (3, 0));
+        assert_eq!(badge_count(5, 3), (2, 3));
+        // Some were dismissed: the rest still count as seen.
+        assert_eq!(badge_count(1, 3), (0, 1));
+        assert_eq!(badge_count(0, 0), (0, 0));
+    }
+}
@@ -0,0 +1,134 @@
+// Asking the OS to confirm it's still the signed-in user: Touch ID on macOS,
+// Windows Hello (face, fingerprint or its PIN) on Windows. The app lock
+// (see `lock.rs`) takes it in place of the PIN, and settings that protect
+// the app ask for it before they change. Linux has no common API for this,
+// so there it's never available and callers fall back to the PIN.
+
+#[cfg(target_os = "macos")]
+use std::sync::Mutex;
+use tauri::WebviewWindow;
+#[cfg(target_os = "macos")]
+use tokio::sync::oneshot;
+
+/// What the system prompts say each check is for.
+pub const UNLOCK_REASON: &str = "unlock Onyx";
+pub const CHANGE_PIN_REASON: &str = "change the Onyx unlock PIN";
+
+/// The name of what the system checks with, for buttons ("Unlock with
+/// Touch ID"), if this machine can check at all.
+#[cfg(target_os = "macos")]
+pub fn available() -> Option<&'static str> {
+    use objc2_local_authentication::{LAContext, LAPolicy};
+
+    // SAFETY: a fresh context, only asked whether the policy can run.
+    let can = unsafe {
+        LAContext::new().canEvaluatePolicy_error(LAPolicy::DeviceOwnerAuthenticationWithBiometrics)
+    };
+    can.is_ok().then_some("Touch ID")
+}
+
+#[cfg(target_os = "windows")]
+pub fn available() -> Option<&'static str> {
+    use windows::Security::Credentials::UI::{
+        UserConsentVerifier, UserConsentVerifierAvailability,
+    };
+
+    let availability = UserConsentVerifier::CheckAvailabilityAsync()
+        .and_then(|operation| operation.get())
+        .ok()?;
+    (availability == UserConsentVerifierAvailability::Available).then_some("Windows Hello")
+}
+
+#[cfg(target_os = "linux")]
+pub const fn available() -> Option<&'static str> {
+    None
+}
+
+/// Have the user confirm themselves to `reason` (as in "unlock Onyx"),
+/// over `window`.
+#[cfg(target_os = "macos")]
+pub async fn verify(window: &WebviewWindow, reason: &str) -> Result<(), String> {
+    use block2::RcBlock;
+    use objc2::runtime::Bool;
+    use objc2_foundation::{NSError, NSString};
+    use objc2_local_authentication::{LAContext, LAPolicy};
+
+    let _ = window;
+    let (tx, rx) = oneshot::channel();
+    // The reply only ever comes once, but the block has to be `Fn`.
+    let tx = Mutex::new(Some(tx));
+    // In a block of its own: the reply isn't `Send`, so it can't be held
+    // across the wait, and the context keeps its own copy.
+    {
+        let reply = RcBlock::new(move |success: Bool, error: *mut NSError| {
+            // SAFETY: LocalAuthentication passes a valid error (or none) for
+            // the duration of the call.
+            let result = if success.as_bool() {
+                Ok(())
+            } else {
+                Err(unsafe { error.as_ref() }.map_or_else(
+                    || "Touch ID didn't confirm it's you".to_string(),
+                    |error| error.localizedDescription().to_string(),
+                ))
+            };
+            let sender = tx
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .take();
+            if let Some(sender) = sender {
+                let _ = sender.send(result);
+            }
+        });
+        // SAFETY: the context lives until the reply, which it holds on to; the
+        // reply is called once, on a queue of LocalAuthentication's own.
+        unsafe {
+            LAContext::new().evaluatePolicy_localizedReason_reply(
+                LAPolicy::DeviceOwnerAuthenticationWithBiometrics,
+                &NSString::from_str(reason),
+                &reply,
+            );
+        }
+    }
+    rx.await.map_err(|_| "Touch ID didn't answer".to_string())?
+}
+
+#[cfg(target_os = "windows")]
+pub async fn verify(window: &WebviewWindow, reason: &str) -> Result<(), String> {
+    use windows::core::{factory, HSTRING};
+    use windows::Security::Credentials::UI::{UserConsentVerificationResult, UserConsentVerifier};
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::WinRT::IUserConsentVerifierInterop;
+    use windows_future::IAsyncOperation;
+
+    // Passed as an address, as a handle isn't `Send`.
+    let hwnd = window
+        .hwnd()
+        .map_err(|e| e.to_string())?
+        .0
+        .expose_provenance();
+    let reason = HSTRING::from(reason);
+    // Shown over `window`, which the plain `RequestVerificationAsync`
+    // can't do for a desktop app.
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let interop = factory::<UserConsentVerifier, IUserConsentVerifierInterop>()?;
+        let hwnd = HWND(std::ptr::with_exposed_provenance_mut(hwnd));
+        // SAFETY: `hwnd` is the window's own handle, alive while it is.
+        let operation: IAsyncOperation<UserConsentVerificationResult> =
+            unsafe { interop.RequestVerificationForWindowAsync(hwnd, &reason) }?;
+        operation.get()
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Windows Hello didn't answer: {e}"))?;
+    match result {
+        UserConsentVerificationResult::Verified => Ok(()),
+        UserConsentVerificationResult::Canceled => Err("Windows Hello was canceled".to_string()),
+        _ => Err("Windows Hello didn't confirm it's you".to_string()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[allow(clippy::unused_async)]
+pub async fn verify(_window: &WebviewWindow, _reason: &str) -> Result<(), String> {
+    Err("This system can't confirm it's you; use the PIN".to_string())
+}
@@ -0,0 +1,257 @@
+// Hidden `--bench-bridge` diagnostic mode: once the main window has loaded
+// the server page, measure eval round trips (Rust -> webview -> Rust), push
+// throughput (Rust -> webview), and invoke round trips (webview -> Rust ->
+// webview), then store the results in diagnostics. Pushes go through
+// `eval` rather than Tauri events because remote pages aren't granted the
+// event-listen permission -- any native -> web notification we build has to
+// take the same path, so that's the budget worth knowing.
+
+use crate::assets::{script, Script};
+use crate::debug_log::{log_backend_error, log_debug};
+use crate::diagnostics::Diagnostics;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Manager, Webview};
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+
+const ROUND_TRIPS: u32 = 50;
+const PUSH_COUNT: u32 = 1000;
+const STEP_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyStats {
+    pub samples: usize,
+    pub min_ms: f64,
+    pub median_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+impl LatencyStats {
+    /// Nearest-rank summary of `samples_ms`. `None` if there are no samples.
+    pub fn from_samples(samples_ms: &[f64]) -> Option<Self> {
+        if samples_ms.is_empty() {
+            return None;
+        }
+        let mut sorted = samples_ms.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |pct: usize| sorted[(sorted.len() * pct).div_ceil(100).max(1) - 1];
+
+        Some(Self {
+            samples: sorted.len(),
+            min_ms: sorted[0],
+            median_ms: percentile(50),
+            p95_ms: percentile(95),
+            max_ms: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BridgeBenchmark {
+    pub platform: &'static str,
+    pub eval_round_trip: Option<LatencyStats>,
+    pub eval_timeouts: u32,
+    pub invoke_round_trip: Option<LatencyStats>,
+    pub push_count: u32,
+    /// `None` if the webview never acknowledged receiving every push.
+    pub pushes_per_sec: Option<f64>,
+}
+
+impl BridgeBenchmark {
+    fn summary(&self) -> String {
+        let fmt_stats = |stats: &Option<LatencyStats>| {
+            stats.as_ref().map_or_else(
+                || "n/a".to_string(),
+                |s| format!("median {:.2}ms, p95 {:.2}ms", s.median_ms, s.p95_ms),
+            )
+        };
+        format!(
+            "bridge bench ({}): eval rtt {} ({} timeouts), invoke rtt {}, pushes {}",
+            self.platform,
+            fmt_stats(&self.eval_round_trip),
+            self.eval_timeouts,
+            fmt_stats(&self.invoke_round_trip),
+            self.pushes_per_sec
+                .map_or_else(|| "n/a".to_string(), |rate| format!("{rate:.0}/s")),
+        )
+    }
+}
+
+/// Managed state for the benchmark. Inert unless `enabled`.
+pub struct BridgeBench {
+    enabled: bool,
+    started: AtomicBool,
+    acks: Mutex<HashMap<String, oneshot::Sender<()>>>,
+    invoke_report: Mutex<Option<oneshot::Sender<Vec<f64>>>>,
+}
+
+impl BridgeBench {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            started: AtomicBool::new(false),
+            acks: Mutex::new(HashMap::new()),
+            invoke_report: Mutex::new(None),
+        }
+    }
+
+    fn expect_ack(&self, token: String) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        self.acks
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(token, tx);
+        rx
+    }
+
+    fn expect_invoke_report(&self) -> oneshot::Receiver<Vec<f64>> {
+        let (tx, rx) = oneshot::channel();
+        *self
+            .invoke_report
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(tx);
+        rx
+    }
+}
+
+/// Register the benchmark's state, to run it if `enabled`
+/// (`--bench-bridge`).
+pub fn manage(builder: tauri::Builder<tauri::Wry>, enabled: bool) -> tauri::Builder<tauri::Wry> {
+    builder.manage(BridgeBench::new(enabled))
+}
+
+/// Start the benchmark against `webview` if bench mode is on and it hasn't
+/// already run this session.
+pub fn maybe_start(webview: &Webview) {
+    let app = webview.app_handle();
+    let bench = app.state::<BridgeBench>();
+    if !bench.enabled || bench.started.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let script = script(app, Script::BridgeBench);
+    if !crate::watchdog::eval(webview, &*script, "inject bridge bench script") {
+        return;
+    }
+
+    let webview = webview.clone();
+    tauri::async_runtime::spawn(async move {
+        let app = webview.app_handle().clone();
+        match run(&webview).await {
+            Ok(result) => {
+                log_debug(&app, &result.summary());
+                app.state::<Diagnostics>().set_bridge_benchmark(result);
+            }
+            Err(e) => log_backend_error(&app, &format!("Bridge benchmark failed: {e}")),
+        }
+    });
+}
+
+async fn run(webview: &Webview) -> Result<BridgeBenchmark, String> {
+    let app = webview.app_handle();
+    let bench = app.state::<BridgeBench>();
+
+    let mut eval_samples = Vec::new();
+    let mut eval_timeouts = 0;
+    for seq in 0..ROUND_TRIPS {
+        let ack = bench.expect_ack(format!("rtt-{seq}"));
+        let start = Instant::now();
+        webview
+            .eval(format!("window.__ONYX_BRIDGE_BENCH__.pong({seq})"))
+            .map_err(|e| e.to_string())?;
+        match timeout(STEP_TIMEOUT, ack).await {
+            Ok(Ok(())) => eval_samples.push(start.elapsed().as_secs_f64() * 1000.0),
+            _ => eval_timeouts += 1,
+        }
+    }
+
+    let pushes_done = bench.expect_ack("push-done".to_string());
+    let start = Instant::now();
+    for _ in 0..PUSH_COUNT {
+        webview
+            .eval(format!("window.__ONYX_BRIDGE_BENCH__.push({PUSH_COUNT})"))
+            .map_err(|e| e.to_string())?;
+    }
+    let pushes_per_sec = match timeout(STEP_TIMEOUT, pushes_done).await {
+        Ok(Ok(())) => Some(f64::from(PUSH_COUNT) / start.elapsed().as_secs_f64()),
+        _ => None,
+    };
+
+    let invoke_report = bench.expect_invoke_report();
+    webview
+        .eval(format!(
+            "window.__ONYX_BRIDGE_BENCH__.measureInvoke({ROUND_TRIPS})"
+        ))
+        .map_err(|e| e.to_string())?;
+    let invoke_samples = timeout(STEP_TIMEOUT, invoke_report)
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .unwrap_or_default();
+
+    Ok(BridgeBenchmark {
+        platform: std::env::consts::OS,
+        eval_round_trip: LatencyStats::from_samples(&eval_samples),
+        eval_timeouts,
+        invoke_round_trip: LatencyStats::from_samples(&invoke_samples),
+        push_count: PUSH_COUNT,
+        pushes_per_sec,
+    })
+}
+
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn bridge_bench_ack(token: String, bench: tauri::State<BridgeBench>) {
+    let sender = bench
+        .acks
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .remove(&token);
+    if let Some(sender) = sender {
+        let _ = sender.send(());
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn bridge_bench_report(invoke_samples_ms: Vec<f64>, bench: tauri::State<BridgeBench>) {
+    let sender = bench
+        .invoke_report
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .take();
+    if let Some(sender) = sender {
+        let _ = sender.send(invoke_samples_ms);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LatencyStats;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn latency_stats_use_nearest_rank() {
+        let samples: Vec<f64> = (1..=20).rev().map(f64::from).collect();
+        let stats = LatencyStats::from_samples(&samples).unwrap();
+        assert_eq!(stats.samples, 20);
+        assert!((stats.min_ms - 1.0).abs() < f64::EPSILON);
+        assert!((stats.median_ms - 10.0).abs() < f64::EPSILON);
+        assert!((stats.p95_ms - 19.0).abs() < f64::EPSILON);
+        assert!((stats.max_ms - 20.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn latency_stats_handle_single_and_empty_samples() {
+        assert!(LatencyStats::from_samples(&[]).is_none());
+        let stats = LatencyStats::from_samples(&[3.5]).unwrap();
+        assert!((stats.median_ms - 3.5).abs() < f64::EPSILON);
+        assert!((stats.p95_ms - 3.5).abs() < f64::EPSILON);
+    }
+}
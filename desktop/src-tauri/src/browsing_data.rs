@@ -0,0 +1,129 @@
+// Clearing what the webviews keep for the server, to fix a wedged session
+// without deleting the app's data folder. Settings → Clear Browsing Data (or
+// `clear_browsing_data`) takes any of:
+//
+// - cache: the pages' Cache Storage and service workers, which can keep
+//   serving an old web app after the server was upgraded;
+// - cookies: every cookie, which signs the windows out;
+// - storage: local and session storage and IndexedDB.
+//
+// All three together also drop the HTTP cache, which only the webview's own
+// "clear everything" reaches. It's done for the webview data of every open
+// chat window (each profile and account has its own), and those windows
+// reload afterwards.
+
+use crate::window::{is_bundled_page_window, WindowPartitions, SETTINGS_WINDOW_LABEL};
+use serde::Deserialize;
+use std::collections::HashSet;
+use tauri::{AppHandle, Manager, WebviewWindow};
+
+/// What to clear; at least one has to be picked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct BrowsingData {
+    pub cache: bool,
+    pub cookies: bool,
+    pub storage: bool,
+}
+
+impl BrowsingData {
+    const fn everything(self) -> bool {
+        self.cache && self.cookies && self.storage
+    }
+}
+
+const CLEAR_CACHE: &str = "\
+await Promise.all((await caches.keys()).map((key) => caches.delete(key)));
+for (const registration of await navigator.serviceWorker.getRegistrations()) {
+  await registration.unregister();
+}";
+const CLEAR_STORAGE: &str = "\
+localStorage.clear();
+sessionStorage.clear();
+for (const { name } of await indexedDB.databases()) {
+  indexedDB.deleteDatabase(name);
+}";
+
+/// The script that clears `data`'s page side in a window, then reloads it.
+/// Each part goes on if another one fails: either may be missing on a page.
+fn page_script(data: BrowsingData) -> String {
+    let parts: Vec<String> = [(data.cache, CLEAR_CACHE), (data.storage, CLEAR_STORAGE)]
+        .into_iter()
+        .filter(|(picked, _)| *picked)
+        .map(|(_, part)| format!("try {{\n{part}\n}} catch {{}}\n"))
+        .collect();
+    format!(
+        "(async () => {{\n{}location.reload();\n}})();",
+        parts.concat()
+    )
+}
+
+fn clear_cookies(window: &WebviewWindow) -> Result<(), String> {
+    for cookie in window.cookies().map_err(|e| e.to_string())? {
+        window
+            .delete_cookie(cookie)
+            .map_err(|e| format!("Failed to clear a cookie: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Clear the picked browsing data (cache, cookies, storage) for every open
+/// chat window; only from Settings
+#[tauri::command]
+pub async fn clear_browsing_data(
+    app: AppHandle,
+    window: WebviewWindow,
+    data: BrowsingData,
+) -> Result<(), String> {
+    if window.label() != SETTINGS_WINDOW_LABEL {
+        return Err("Browsing data can only be cleared in Settings".to_string());
+    }
+    if data == BrowsingData::default() {
+        return Err("Pick something to clear".to_string());
+    }
+
+    let partitions = app.state::<WindowPartitions>();
+    let mut cleared = HashSet::new();
+    let script = page_script(data);
+    for (label, chat) in app.webview_windows() {
+        if is_bundled_page_window(&label) {
+            continue;
+        }
+        // Windows on the same webview data share its cookies and caches, so
+        // those go once; each page still clears and reloads itself.
+        // (Reading cookies deadlocks on Windows from the main thread; this
+        // runs on the async runtime.)
+        if cleared.insert(partitions.partition_of(&label)) {
+            if data.everything() {
+                chat.clear_all_browsing_data()
+                    .map_err(|e| format!("Failed to clear browsing data: {e}"))?;
+            } else if data.cookies {
+                clear_cookies(&chat)?;
+            }
+        }
+        crate::watchdog::eval(chat.as_ref(), &script, "clear browsing data");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_picked_page_data_is_cleared() {
+        let cookies_only = page_script(BrowsingData {
+            cookies: true,
+            ..BrowsingData::default()
+        });
+        assert_eq!(cookies_only, "(async () => {\nlocation.reload();\n})();");
+
+        let storage = page_script(BrowsingData {
+            storage: true,
+            ..BrowsingData::default()
+        });
+        assert!(storage.contains("localStorage.clear()"));
+        assert!(!storage.contains("caches.keys()"));
+        assert!(storage.ends_with("location.reload();\n})();"));
+    }
+}
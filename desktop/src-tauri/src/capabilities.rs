@@ -0,0 +1,56 @@
+// Which optional subsystems this binary was compiled with. Each Cargo
+// feature that gates a subsystem gets a field here, so the web app and
+// diagnostics can tell "disabled in settings" apart from "not in this build".
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+// One flag per optional Cargo feature is the point of this struct.
+#[allow(clippy::struct_excessive_bools)]
+pub struct Capabilities {
+    /// `DevTools` can be opened (always in debug builds).
+    pub devtools: bool,
+    /// The hidden `--bench-bridge` diagnostic mode.
+    pub bridge_bench: bool,
+    /// The built-in mock server behind `--demo`.
+    pub mock_server: bool,
+    /// The UI test hooks behind `--automation`.
+    pub ui_automation: bool,
+    /// Crash reports and the hidden `--crash-server` that writes minidumps.
+    pub crash_reporting: bool,
+    /// The local HTTP API for scripts, if turned on in settings.
+    pub automation_api: bool,
+    /// Sharing local folders through a file connector, if turned on in
+    /// settings.
+    pub local_files: bool,
+    /// Keeping recent chats to read offline, if turned on in settings.
+    pub offline_cache: bool,
+    /// Keeping recently copied text, if turned on in settings.
+    pub clipboard_history: bool,
+    /// Reading answers aloud with the system's speech engine.
+    pub read_aloud: bool,
+    /// Push to talk and dictation, which record the microphone.
+    pub voice: bool,
+}
+
+pub const fn compiled_capabilities() -> Capabilities {
+    Capabilities {
+        devtools: cfg!(any(debug_assertions, feature = "devtools")),
+        bridge_bench: cfg!(feature = "bridge-bench"),
+        mock_server: cfg!(feature = "mock-server"),
+        ui_automation: cfg!(feature = "ui-automation"),
+        crash_reporting: cfg!(feature = "crash-reporting"),
+        automation_api: cfg!(feature = "automation-api"),
+        local_files: cfg!(feature = "local-files"),
+        offline_cache: cfg!(feature = "offline-cache"),
+        clipboard_history: cfg!(feature = "clipboard-history"),
+        read_aloud: cfg!(feature = "read-aloud"),
+        voice: cfg!(feature = "voice"),
+    }
+}
+
+/// Report the optional subsystems compiled into this build.
+#[tauri::command]
+pub const fn get_capabilities() -> Capabilities {
+    compiled_capabilities()
+}
@@ -0,0 +1,284 @@
+// Certificate pinning for the configured server, for installs handed out on
+// networks where a trusted-looking certificate isn't enough (a corporate
+// TLS-inspecting proxy, a hostile Wi-Fi). `tls.pinned_sha256` lists the
+// SHA-256 fingerprints the server's chain has to include, on top of the
+// usual checks against the system's trust store. Pinning the issuing CA
+// rather than the server's own certificate survives renewals.
+//
+// `tls.trusted_sha256` goes the other way: certificates the user chose to
+// trust although the system doesn't (a homelab's self-signed one, see
+// `cert_trust.rs`). A chain ending in one of those skips the trust store,
+// but not the pins.
+//
+// The app's own requests to the server (`config::http_client_for`, so the
+// connection monitor too) refuse a chain without a pinned certificate. The
+// first refusal of a launch is reported in a native warning, since it means
+// someone may be in the middle; the webviews have no hook for this, so the
+// warning says the windows shouldn't be trusted either.
+
+use crate::config::{validate_server_url, AppConfig, TlsSettings};
+use crate::window::same_origin;
+use aws_lc_rs::digest::{digest, SHA256};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, OtherError, SignatureScheme};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::AppHandle;
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+use url::Url;
+
+pub type Fingerprint = [u8; 32];
+
+/// The fingerprint of the last certificate refused, until it's reported.
+static REFUSED: Mutex<Option<String>> = Mutex::new(None);
+/// Whether this launch has already warned about a refusal.
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// `ab:cd:...` or `abcd...`, in either case.
+pub fn parse_fingerprint(text: &str) -> Option<Fingerprint> {
+    let hex: String = text.chars().filter(|c| *c != ':').collect();
+    if hex.len() != 64 {
+        return None;
+    }
+    let mut fingerprint = [0; 32];
+    for (byte, pair) in fingerprint.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(fingerprint)
+}
+
+/// The SHA-256 fingerprint of a DER certificate.
+pub fn fingerprint_of(certificate: &[u8]) -> Fingerprint {
+    let mut fingerprint = [0; 32];
+    fingerprint.copy_from_slice(digest(&SHA256, certificate).as_ref());
+    fingerprint
+}
+
+/// The DER inside a one-certificate PEM.
+#[cfg(target_os = "windows")]
+pub fn der_from_pem(pem: &str) -> Option<Vec<u8>> {
+    use base64::Engine as _;
+
+    let body: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    base64::engine::general_purpose::STANDARD.decode(body).ok()
+}
+
+/// `AB:CD:...`, the way certificate viewers show it.
+pub fn format_fingerprint(fingerprint: &[u8]) -> String {
+    fingerprint
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Check `[tls]` as submitted from the settings window or the file.
+pub fn validate(settings: &TlsSettings) -> Result<(), String> {
+    settings
+        .pinned_sha256
+        .iter()
+        .chain(&settings.trusted_sha256)
+        .find(|pin| parse_fingerprint(pin).is_none())
+        .map_or(Ok(()), |pin| {
+            Err(format!(
+                "\"{pin}\" isn't a SHA-256 fingerprint (64 hex digits, colons optional)"
+            ))
+        })
+}
+
+fn parse_all(fingerprints: &[String]) -> Vec<Fingerprint> {
+    fingerprints
+        .iter()
+        .filter_map(|fingerprint| parse_fingerprint(fingerprint))
+        .collect()
+}
+
+/// What a server's certificates are held to beyond the system's checks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerCertificates {
+    pins: Vec<Fingerprint>,
+    trusted: Vec<Fingerprint>,
+}
+
+impl ServerCertificates {
+    /// The pins and trusted certificates, if `url` is on `server_url`'s
+    /// origin; nothing otherwise.
+    pub fn for_url(config: &AppConfig, url: &Url) -> Self {
+        let on_server =
+            validate_server_url(&config.server_url).is_ok_and(|server| same_origin(&server, url));
+        if !on_server {
+            return Self::default();
+        }
+        Self {
+            pins: parse_all(&config.tls.pinned_sha256),
+            trusted: parse_all(&config.tls.trusted_sha256),
+        }
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.pins.is_empty() && self.trusted.is_empty()
+    }
+}
+
+/// The system's usual checks, unless the user trusted the certificate, plus
+/// "the chain includes a pinned certificate" if there are pins.
+#[derive(Debug)]
+struct PinnedVerifier {
+    system: Arc<dyn ServerCertVerifier>,
+    certificates: ServerCertificates,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let pins = &self.certificates.pins;
+        if !pins.is_empty()
+            && !std::iter::once(end_entity)
+                .chain(intermediates)
+                .any(|certificate| pins.contains(&fingerprint_of(certificate)))
+        {
+            let fingerprint = format_fingerprint(&fingerprint_of(end_entity));
+            *REFUSED
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(fingerprint.clone());
+            return Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                OtherError(Arc::new(std::io::Error::other(format!(
+                    "the certificate ({fingerprint}) doesn't match `tls.pinned_sha256`"
+                )))),
+            )));
+        }
+        if self
+            .certificates
+            .trusted
+            .contains(&fingerprint_of(end_entity))
+        {
+            return Ok(ServerCertVerified::assertion());
+        }
+        self.system
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.system.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.system.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.system.supported_verify_schemes()
+    }
+}
+
+/// The TLS setup for a server with pinned or trusted certificates, plus the
+/// client certificate (`identity`, as PEM) if there is one.
+pub fn tls_config(
+    certificates: ServerCertificates,
+    identity: Option<&str>,
+) -> Result<rustls::ClientConfig, String> {
+    let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+    let system = rustls_platform_verifier::Verifier::new(provider.clone())
+        .map_err(|e| format!("Failed to load the system's certificates: {e}"))?;
+    let builder = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinnedVerifier {
+            system: Arc::new(system),
+            certificates,
+        }));
+    let Some(identity) = identity else {
+        return Ok(builder.with_no_client_auth());
+    };
+    let chain = CertificateDer::pem_slice_iter(identity.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("The client certificate can't be used: {e}"))?;
+    let key = PrivateKeyDer::from_pem_slice(identity.as_bytes())
+        .map_err(|e| format!("The client certificate's key can't be used: {e}"))?;
+    builder
+        .with_client_auth_cert(chain, key)
+        .map_err(|e| format!("The client certificate can't be used: {e}"))
+}
+
+/// Whether a request since the last call was refused for not matching the
+/// pins; the first time in a launch, say so.
+pub fn warn_if_refused(app: &AppHandle) -> bool {
+    let Some(fingerprint) = REFUSED
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .take()
+    else {
+        return false;
+    };
+    crate::debug_log::log_backend_error(
+        app,
+        &format!("Refused the server's certificate ({fingerprint}): it isn't pinned"),
+    );
+    if WARNED.swap(true, Ordering::Relaxed) {
+        return true;
+    }
+    app.dialog()
+        .message(format!(
+            "The server presented a certificate that doesn't match the ones this app is set to trust (SHA-256 {fingerprint}). Someone may be intercepting the connection, so Onyx won't talk to it, and what the windows show can't be trusted either. If the server's certificate was changed on purpose, update `tls.pinned_sha256`."
+        ))
+        .title("Untrusted Server Certificate")
+        .kind(MessageDialogKind::Warning)
+        .show(|_| {});
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn pins_are_read_with_or_without_colons() {
+        let plain = "ab".repeat(32);
+        let colons = vec!["AB"; 32].join(":");
+        assert_eq!(parse_fingerprint(&plain), Some([0xab; 32]));
+        assert_eq!(parse_fingerprint(&colons), Some([0xab; 32]));
+        assert_eq!(format_fingerprint(&[0xab; 32]), colons);
+        assert_eq!(parse_fingerprint("abcd"), None);
+        assert_eq!(parse_fingerprint(&"zz".repeat(32)), None);
+
+        let mut config = AppConfig {
+            server_url: "https://onyx.example.com".to_string(),
+            ..AppConfig::default()
+        };
+        config.tls.pinned_sha256 = vec![plain];
+        assert!(validate(&config.tls).is_ok());
+        let server = Url::parse("https://onyx.example.com/api/health").unwrap();
+        assert_eq!(
+            ServerCertificates::for_url(&config, &server).pins,
+            vec![[0xab; 32]]
+        );
+        let elsewhere = Url::parse("https://updates.example.com/").unwrap();
+        assert!(ServerCertificates::for_url(&config, &elsewhere).is_empty());
+
+        config.tls.pinned_sha256.push("not a pin".to_string());
+        assert!(validate(&config.tls).is_err());
+    }
+}
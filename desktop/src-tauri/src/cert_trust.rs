@@ -0,0 +1,351 @@
+// Trusting a certificate the system doesn't, for self-hosted servers with a
+// self-signed one (most homelabs). Instead of the blank page a webview shows
+// when it refuses the configured server's certificate, the app asks, in a
+// native dialog with the certificate's SHA-256 fingerprint, whether to
+// trust it. "Trust This Certificate" adds the fingerprint to
+// `tls.trusted_sha256` and reloads the windows that were turned away; from
+// then on that certificate is accepted for the server, by the webviews and
+// by the app's own requests (see `cert_pinning.rs`), and any other is
+// asked about afresh.
+//
+// WebView2 and WebKitGTK report the failure to a handler set up per window
+// (`watch_certificate_errors`). WKWebView asks its navigation delegate, so
+// on macOS the answer comes from the handler `client_cert.rs` installs,
+// which hands server-trust challenges to `server_trust_challenge`.
+
+use crate::cert_pinning::{fingerprint_of, format_fingerprint, parse_fingerprint, Fingerprint};
+use crate::config::{validate_server_url, AppConfig, ConfigState};
+use crate::window::{is_bundled_page_window, same_origin};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, WebviewWindow};
+use tauri_plugin_dialog::{
+    DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult,
+};
+use url::Url;
+
+const TRUST_LABEL: &str = "Trust This Certificate";
+const CANCEL_LABEL: &str = "Cancel";
+
+/// Certificates being asked about, so the requests of one page load don't
+/// each open a dialog.
+static ASKING: Mutex<Option<HashSet<Fingerprint>>> = Mutex::new(None);
+
+/// What to do about a certificate the system refused for `url`.
+#[derive(Debug, PartialEq, Eq)]
+enum Verdict {
+    /// Not the configured server: the webview's own handling.
+    NotOurs,
+    /// Trusted before: let it through.
+    Trusted,
+    /// Turn it away and ask whether to trust it.
+    Ask(Fingerprint),
+}
+
+fn verdict(config: &AppConfig, url: &Url, certificate: &[u8]) -> Verdict {
+    let on_server =
+        validate_server_url(&config.server_url).is_ok_and(|server| same_origin(&server, url));
+    if !on_server {
+        return Verdict::NotOurs;
+    }
+    let fingerprint = fingerprint_of(certificate);
+    let trusted = config
+        .tls
+        .trusted_sha256
+        .iter()
+        .any(|trusted| parse_fingerprint(trusted) == Some(fingerprint));
+    if trusted {
+        Verdict::Trusted
+    } else {
+        Verdict::Ask(fingerprint)
+    }
+}
+
+fn verdict_now(app: &AppHandle, url: &Url, certificate: &[u8]) -> Verdict {
+    verdict(&app.state::<ConfigState>().config(), url, certificate)
+}
+
+/// Ask whether to trust the certificate `url` presented, unless that's
+/// already being asked.
+fn ask(app: &AppHandle, url: &Url, fingerprint: Fingerprint) {
+    if !ASKING
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .get_or_insert_with(HashSet::new)
+        .insert(fingerprint)
+    {
+        return;
+    }
+    let host = url.host_str().unwrap_or_default();
+    let handle = app.clone();
+    let url = url.clone();
+    app.dialog()
+        .message(format!(
+            "{host} presented a certificate this computer doesn't trust, such as a self-signed one.\n\nSHA-256: {}\n\nOnly trust it if that's your server's certificate. Otherwise someone may be intercepting the connection.",
+            format_fingerprint(&fingerprint)
+        ))
+        .title("Untrusted Certificate")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            TRUST_LABEL.to_string(),
+            CANCEL_LABEL.to_string(),
+        ))
+        .show_with_result(move |result| {
+            if let Some(asking) = ASKING
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .as_mut()
+            {
+                asking.remove(&fingerprint);
+            }
+            let trusted = match result {
+                MessageDialogResult::Ok => true,
+                MessageDialogResult::Custom(label) => label == TRUST_LABEL,
+                _ => false,
+            };
+            if trusted {
+                trust(&handle, fingerprint, &url);
+            }
+        });
+}
+
+/// Keep `fingerprint` as trusted and bring back the windows it turned away:
+/// the ones on the server reload, the ones that never got there go to `url`.
+fn trust(app: &AppHandle, fingerprint: Fingerprint, url: &Url) {
+    let formatted = format_fingerprint(&fingerprint);
+    let result = app.state::<ConfigState>().update_and_persist(|config| {
+        if !config.tls.trusted_sha256.contains(&formatted) {
+            config.tls.trusted_sha256.push(formatted.clone());
+        }
+    });
+    if let Err(e) = result {
+        crate::debug_log::log_backend_error(app, &format!("Failed to trust the certificate: {e}"));
+        return;
+    }
+    for (label, window) in app.webview_windows() {
+        if is_bundled_page_window(&label) {
+            continue;
+        }
+        let result = match window.url() {
+            Ok(current) if same_origin(&current, url) => window.reload(),
+            Ok(current) if matches!(current.scheme(), "http" | "https") => continue,
+            _ => window.navigate(url.clone()),
+        };
+        if let Err(e) = result {
+            crate::debug_log::log_backend_error(
+                app,
+                &format!("Failed to reload {label} after trusting the certificate: {e}"),
+            );
+        }
+    }
+}
+
+/// Have `window` ask about the configured server's certificate when its
+/// webview refuses it. Called once per window.
+#[cfg(target_os = "linux")]
+pub fn watch_certificate_errors(app: &AppHandle, window: &WebviewWindow) {
+    use gtk::gio::prelude::TlsCertificateExt;
+    use webkit2gtk::{WebContextExt, WebViewExt};
+
+    let app = app.clone();
+    let result = window.with_webview(move |webview| {
+        webview.inner().connect_load_failed_with_tls_errors(
+            move |webview, failing_uri, certificate, _| {
+                let Ok(url) = Url::parse(failing_uri) else {
+                    return false;
+                };
+                let Some(der) = certificate.certificate() else {
+                    return false;
+                };
+                match verdict_now(&app, &url, &der) {
+                    Verdict::NotOurs => false,
+                    Verdict::Trusted => {
+                        if let (Some(context), Some(host)) = (webview.context(), url.host_str()) {
+                            context.allow_tls_certificate_for_host(certificate, host);
+                        }
+                        webview.load_uri(failing_uri);
+                        true
+                    }
+                    Verdict::Ask(fingerprint) => {
+                        ask(&app, &url, fingerprint);
+                        true
+                    }
+                }
+            },
+        );
+    });
+    if let Err(e) = result {
+        crate::debug_log::log_backend_error(window.app_handle(), &e.to_string());
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn watch_certificate_errors(app: &AppHandle, window: &WebviewWindow) {
+    use webview2_com::Microsoft::Web::WebView2::Win32::{
+        ICoreWebView2_14, COREWEBVIEW2_SERVER_CERTIFICATE_ERROR_ACTION_ALWAYS_ALLOW,
+        COREWEBVIEW2_SERVER_CERTIFICATE_ERROR_ACTION_CANCEL,
+    };
+    use webview2_com::{take_pwstr, ServerCertificateErrorDetectedEventHandler};
+    use windows::core::{Interface, PWSTR};
+
+    let app = app.clone();
+    let result = window.with_webview(move |webview| {
+        // SAFETY: plain COM calls on the webview's own controller, on the
+        // main thread WebView2 was created on.
+        let registered = unsafe {
+            (|| -> windows::core::Result<()> {
+                let core = webview
+                    .controller()
+                    .CoreWebView2()?
+                    .cast::<ICoreWebView2_14>()?;
+                let handler_app = app.clone();
+                let handler =
+                    ServerCertificateErrorDetectedEventHandler::create(Box::new(move |_, args| {
+                        let Some(args) = args else {
+                            return Ok(());
+                        };
+                        let mut uri = PWSTR::null();
+                        args.RequestUri(&raw mut uri)?;
+                        let Ok(url) = Url::parse(&take_pwstr(uri)) else {
+                            return Ok(());
+                        };
+                        let mut pem = PWSTR::null();
+                        args.ServerCertificate()?.ToPemEncoding(&raw mut pem)?;
+                        let Some(der) = crate::cert_pinning::der_from_pem(&take_pwstr(pem)) else {
+                            return Ok(());
+                        };
+                        match verdict_now(&handler_app, &url, &der) {
+                            Verdict::NotOurs => {}
+                            Verdict::Trusted => args.SetAction(
+                                COREWEBVIEW2_SERVER_CERTIFICATE_ERROR_ACTION_ALWAYS_ALLOW,
+                            )?,
+                            Verdict::Ask(fingerprint) => {
+                                args.SetAction(
+                                    COREWEBVIEW2_SERVER_CERTIFICATE_ERROR_ACTION_CANCEL,
+                                )?;
+                                ask(&handler_app, &url, fingerprint);
+                            }
+                        }
+                        Ok(())
+                    }));
+                let mut token = 0;
+                core.add_ServerCertificateErrorDetected(&handler, &raw mut token)?;
+                Ok(())
+            })()
+        };
+        if let Err(e) = registered {
+            crate::debug_log::log_backend_error(
+                &app,
+                &format!("Failed to watch for certificate errors: {e}"),
+            );
+        }
+    });
+    if let Err(e) = result {
+        crate::debug_log::log_backend_error(window.app_handle(), &e.to_string());
+    }
+}
+
+/// `WKWebView`'s come through `server_trust_challenge`.
+#[cfg(target_os = "macos")]
+pub const fn watch_certificate_errors(_app: &AppHandle, _window: &WebviewWindow) {}
+
+/// Answer a `WKWebView` server-trust challenge: the system's verdict when it
+/// trusts the certificate or it isn't the configured server's, otherwise
+/// the user's.
+#[cfg(target_os = "macos")]
+pub fn server_trust_challenge(
+    app: &AppHandle,
+    challenge: &objc2_foundation::NSURLAuthenticationChallenge,
+    completion: &block2::DynBlock<
+        dyn Fn(
+            objc2_foundation::NSURLSessionAuthChallengeDisposition,
+            *mut objc2_foundation::NSURLCredential,
+        ),
+    >,
+) {
+    use core_foundation::base::TCFType;
+    use objc2_foundation::{NSURLCredential, NSURLSessionAuthChallengeDisposition};
+    use security_framework::trust::SecTrust;
+
+    let space = challenge.protectionSpace();
+    let default = || {
+        completion.call((
+            NSURLSessionAuthChallengeDisposition::PerformDefaultHandling,
+            std::ptr::null_mut(),
+        ));
+    };
+    // objc2-foundation leaves out the methods typed with Security.framework's
+    // types, so this one (and `credentialForTrust:`) is sent by name.
+    // SAFETY: `serverTrust` returns the space's `SecTrustRef`, or null, and
+    // the space holds on to it for the rest of the challenge.
+    let server_trust: *mut objc2_security::SecTrust =
+        unsafe { objc2::msg_send![&*space, serverTrust] };
+    // SAFETY: as above.
+    let Some(server_trust) = (unsafe { server_trust.as_ref() }) else {
+        return default();
+    };
+    let Ok(url) = Url::parse(&format!("https://{}:{}/", space.host(), space.port())) else {
+        return default();
+    };
+    // SAFETY: the challenge's `SecTrustRef`, seen through security-framework,
+    // which retains it for as long as `trust` lives.
+    let trust = unsafe {
+        SecTrust::wrap_under_get_rule(std::ptr::from_ref(server_trust).cast_mut().cast())
+    };
+    if trust.evaluate_with_error().is_ok() {
+        return default();
+    }
+    // `chain()`, its replacement, needs macOS 12, and the app still runs on
+    // 10.15.
+    #[allow(deprecated)]
+    let Some(leaf) = trust.certificate_at_index(0) else {
+        return default();
+    };
+    match verdict_now(app, &url, &leaf.to_der()) {
+        Verdict::NotOurs => default(),
+        Verdict::Trusted => {
+            // SAFETY: `credentialForTrust:` takes the challenge's trust and
+            // returns a new credential for it.
+            let credential: objc2::rc::Retained<NSURLCredential> = unsafe {
+                objc2::msg_send![objc2::class!(NSURLCredential), credentialForTrust: server_trust]
+            };
+            completion.call((
+                NSURLSessionAuthChallengeDisposition::UseCredential,
+                objc2::rc::Retained::as_ptr(&credential).cast_mut(),
+            ));
+        }
+        Verdict::Ask(fingerprint) => {
+            ask(app, &url, fingerprint);
+            completion.call((
+                NSURLSessionAuthChallengeDisposition::CancelAuthenticationChallenge,
+                std::ptr::null_mut(),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn only_the_servers_certificates_are_asked_about() {
+        let mut config = AppConfig {
+            server_url: "https://onyx.home.arpa".to_string(),
+            ..AppConfig::default()
+        };
+        let certificate = b"not really DER, but hashed all the same";
+        let server = Url::parse("https://onyx.home.arpa/chat").unwrap();
+        let fingerprint = fingerprint_of(certificate);
+        assert_eq!(
+            verdict(&config, &server, certificate),
+            Verdict::Ask(fingerprint)
+        );
+        let elsewhere = Url::parse("https://router.home.arpa/").unwrap();
+        assert_eq!(verdict(&config, &elsewhere, certificate), Verdict::NotOurs);
+
+        config.tls.trusted_sha256 = vec![format_fingerprint(&fingerprint)];
+        assert_eq!(verdict(&config, &server, certificate), Verdict::Trusted);
+    }
+}
@@ -0,0 +1,494 @@
+// Chat transcripts, kept outside Onyx: File > Export Chat... saves the chat
+// in the focused window, and File > Export All Chats... saves every chat
+// the user has, one file each, into a folder they pick. The chats are read
+// from the server here (as the window's user), not from the page, so the
+// transcript has what the server has -- the answers each message ended up
+// with and the documents they cite -- however much of the chat the page has
+// loaded. Transcripts are Markdown, for reading, JSON, for other tools, or
+// the same JSON as an Onyx Chat file, which opens back in the app (see
+// `documents.rs`); the save dialog's file type picks which.
+
+use crate::config::{validate_server_url, ConfigState};
+use crate::lock::AppLock;
+use crate::route::Route;
+use crate::server_api::{all_chats, chat_transcript, ChatMessage, ChatTranscript};
+use crate::window::is_bundled_page_window;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, WebviewWindow};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    #[default]
+    Markdown,
+    Json,
+    /// JSON, as an `.onyxchat` file.
+    #[serde(rename = "onyxchat")]
+    OnyxChat,
+}
+
+impl TranscriptFormat {
+    const fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Json => "json",
+            Self::OnyxChat => crate::documents::CHAT_EXTENSION,
+        }
+    }
+
+    /// The format a file named `path` is in, going by its extension.
+    fn of(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Some(Self::Markdown),
+            "json" => Some(Self::Json),
+            crate::documents::CHAT_EXTENSION => Some(Self::OnyxChat),
+            _ => None,
+        }
+    }
+}
+
+/// A cited document, as a transcript lists it.
+#[derive(Debug, Serialize)]
+struct Citation<'a> {
+    number: u32,
+    title: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    link: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct Message<'a> {
+    role: &'a str,
+    text: &'a str,
+    sent: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    citations: Vec<Citation<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+/// A transcript's JSON.
+#[derive(Debug, Serialize)]
+pub struct Transcript<'a> {
+    id: &'a str,
+    pub title: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    assistant: Option<&'a str>,
+    created: &'a str,
+    messages: Vec<Message<'a>>,
+}
+
+#[cfg(feature = "offline-cache")]
+impl Transcript<'_> {
+    /// The title and what each message says, as one text.
+    pub fn plain_text(&self) -> String {
+        std::iter::once(self.title)
+            .chain(self.messages.iter().map(|message| message.text))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+fn title(chat: &ChatTranscript) -> &str {
+    chat.description
+        .as_deref()
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .unwrap_or("Untitled Chat")
+}
+
+/// The messages the chat shows: the user's and the assistant's, along the
+/// latest answer to each. A message that was edited or regenerated has
+/// its earlier versions left out, as on the page.
+fn shown_messages(chat: &ChatTranscript) -> Vec<&ChatMessage> {
+    let by_id: HashMap<i64, &ChatMessage> = chat
+        .messages
+        .iter()
+        .map(|message| (message.message_id, message))
+        .collect();
+    let mut thread = Vec::new();
+    let mut next = chat
+        .messages
+        .iter()
+        .find(|message| message.parent_message.is_none());
+    while let Some(message) = next {
+        // A loop in the tree would never end otherwise.
+        if thread.len() > chat.messages.len() {
+            break;
+        }
+        thread.push(message);
+        next = message
+            .latest_child_message
+            .and_then(|id| by_id.get(&id).copied());
+    }
+    thread.retain(|message| matches!(message.message_type.as_str(), "user" | "assistant"));
+    thread
+}
+
+/// What the last answer in `chat` says, if it has one.
+#[cfg(feature = "read-aloud")]
+pub fn last_answer(chat: &ChatTranscript) -> Option<&str> {
+    shown_messages(chat)
+        .into_iter()
+        .rev()
+        .find(|message| message.message_type == "assistant")
+        .map(|message| message.message.trim())
+        .filter(|answer| !answer.is_empty())
+}
+
+fn citations(message: &ChatMessage) -> Vec<Citation<'_>> {
+    let docs = message.context_docs.as_deref().unwrap_or_default();
+    message
+        .citations
+        .iter()
+        .flatten()
+        .filter_map(|(number, document_id)| {
+            let doc = docs.iter().find(|doc| &doc.document_id == document_id)?;
+            Some(Citation {
+                number: *number,
+                title: &doc.semantic_identifier,
+                link: doc.link.as_deref().filter(|link| !link.is_empty()),
+            })
+        })
+        .collect()
+}
+
+/// `chat` as its transcript's JSON has it.
+pub fn transcript(chat: &ChatTranscript) -> Transcript<'_> {
+    Transcript {
+        id: &chat.chat_session_id,
+        title: title(chat),
+        assistant: chat
+            .persona_name
+            .as_deref()
+            .filter(|name| !name.trim().is_empty()),
+        created: &chat.time_created,
+        messages: shown_messages(chat)
+            .into_iter()
+            .map(|message| Message {
+                role: &message.message_type,
+                text: message.message.trim(),
+                sent: &message.time_sent,
+                citations: citations(message),
+                error: message.error.as_deref().filter(|e| !e.is_empty()),
+            })
+            .collect(),
+    }
+}
+
+fn render(chat: &ChatTranscript, format: TranscriptFormat) -> Result<String, String> {
+    let transcript = transcript(chat);
+    match format {
+        TranscriptFormat::Json | TranscriptFormat::OnyxChat => {
+            serde_json::to_string_pretty(&transcript).map_err(|e| e.to_string())
+        }
+        TranscriptFormat::Markdown => {
+            let assistant = transcript.assistant;
+            let mut lines = vec![format!("# {}", transcript.title), String::new()];
+            lines.push(match assistant {
+                Some(assistant) => format!("*{assistant}, {}*", transcript.created),
+                None => format!("*{}*", transcript.created),
+            });
+            for message in transcript.messages {
+                let speaker = if message.role == "user" {
+                    "You"
+                } else {
+                    assistant.unwrap_or("Onyx")
+                };
+                lines.extend([String::new(), format!("## {speaker}"), String::new()]);
+                lines.push(message.text.to_string());
+                if let Some(e) = message.error {
+                    lines.extend([String::new(), format!("**Error:** {e}")]);
+                }
+                if !message.citations.is_empty() {
+                    lines.extend([String::new(), "**Sources:**".to_string(), String::new()]);
+                    lines.extend(message.citations.iter().map(|citation| {
+                        citation.link.map_or_else(
+                            || format!("{}. {}", citation.number, citation.title),
+                            |link| format!("{}. [{}]({link})", citation.number, citation.title),
+                        )
+                    }));
+                }
+            }
+            lines.push(String::new());
+            Ok(lines.join("\n"))
+        }
+    }
+}
+
+/// A file name for a chat's transcript: its title, without what some file
+/// system wouldn't take, and the start of its ID so chats with the same
+/// title don't overwrite each other.
+fn file_name(title: &str, id: &str, format: TranscriptFormat) -> String {
+    let title: String = title
+        .chars()
+        .map(|c| {
+            if c.is_control() || r#"<>:"/\|?*"#.contains(c) {
+                '-'
+            } else {
+                c
+            }
+        })
+        .take(80)
+        .collect();
+    let title = title.trim().trim_end_matches('.');
+    let id: String = id.chars().take(8).collect();
+    format!("{title} ({id}).{}", format.extension())
+}
+
+/// The focused chat window, or the main one if none is.
+fn focused_chat_window(app: &AppHandle) -> Option<WebviewWindow> {
+    let windows = app.webview_windows();
+    windows
+        .iter()
+        .find(|(label, window)| {
+            !is_bundled_page_window(label) && window.is_focused().unwrap_or(false)
+        })
+        .map(|(_, window)| window)
+        .or_else(|| windows.get("main"))
+        .cloned()
+}
+
+/// The chat window to export from: `window` itself, unless it's one of the
+/// app's own pages.
+fn chat_window(app: &AppHandle, window: &WebviewWindow) -> Result<WebviewWindow, String> {
+    if !is_bundled_page_window(window.label()) {
+        return Ok(window.clone());
+    }
+    focused_chat_window(app).ok_or_else(|| "No chat window is open".to_string())
+}
+
+const NOT_SIGNED_IN: &str = "Sign in to Onyx to export chats";
+
+async fn export_one(
+    app: &AppHandle,
+    window: &WebviewWindow,
+    chat_id: Option<String>,
+    format: TranscriptFormat,
+) -> Result<Option<PathBuf>, String> {
+    if app.state::<AppLock>().is_locked() {
+        return Err("Onyx is locked".to_string());
+    }
+    let window = chat_window(app, window)?;
+    let chat_id = match chat_id {
+        Some(id) => id,
+        None => match window.url().ok().as_ref().and_then(Route::from_url) {
+            Some(Route::Chat { id }) => id,
+            _ => return Err("This window isn't showing a saved chat".to_string()),
+        },
+    };
+    let server = validate_server_url(&app.state::<ConfigState>().config().server_url)?;
+    let chat = chat_transcript(&window, &server, &chat_id)
+        .await?
+        .ok_or(NOT_SIGNED_IN)?;
+
+    let Some(path) = app
+        .dialog()
+        .file()
+        .set_title("Export Chat")
+        .set_file_name(file_name(title(&chat), &chat.chat_session_id, format))
+        .add_filter("Markdown", &["md"])
+        .add_filter("JSON", &["json"])
+        .add_filter("Onyx Chat", &[crate::documents::CHAT_EXTENSION])
+        .blocking_save_file()
+    else {
+        return Ok(None);
+    };
+    let path = path.into_path().map_err(|e| e.to_string())?;
+    let transcript = render(&chat, TranscriptFormat::of(&path).unwrap_or(format))?;
+    std::fs::write(&path, transcript).map_err(|e| e.to_string())?;
+    #[cfg(feature = "offline-cache")]
+    crate::local_search::index_export(app, &chat, &path);
+    Ok(Some(path))
+}
+
+/// Write the chat `chat_id`'s transcript into `folder`.
+async fn export_into(
+    window: &WebviewWindow,
+    server: &url::Url,
+    folder: &Path,
+    chat_id: &str,
+    format: TranscriptFormat,
+) -> Result<(), String> {
+    let chat = chat_transcript(window, server, chat_id)
+        .await?
+        .ok_or(NOT_SIGNED_IN)?;
+    let path = folder.join(file_name(title(&chat), &chat.chat_session_id, format));
+    std::fs::write(&path, render(&chat, format)?).map_err(|e| e.to_string())?;
+    #[cfg(feature = "offline-cache")]
+    crate::local_search::index_export(window.app_handle(), &chat, &path);
+    Ok(())
+}
+
+async fn export_all(
+    app: &AppHandle,
+    window: &WebviewWindow,
+    format: TranscriptFormat,
+) -> Result<Option<(usize, PathBuf)>, String> {
+    if app.state::<AppLock>().is_locked() {
+        return Err("Onyx is locked".to_string());
+    }
+    let window = chat_window(app, window)?;
+    let server = validate_server_url(&app.state::<ConfigState>().config().server_url)?;
+    let Some(folder) = app
+        .dialog()
+        .file()
+        .set_title("Export All Chats")
+        .blocking_pick_folder()
+    else {
+        return Ok(None);
+    };
+    let folder = folder.into_path().map_err(|e| e.to_string())?;
+
+    let chats = all_chats(&window, &server).await?.ok_or(NOT_SIGNED_IN)?;
+    let mut written = 0;
+    let mut failed = Vec::new();
+    for summary in &chats {
+        match export_into(&window, &server, &folder, &summary.id, format).await {
+            Ok(()) => written += 1,
+            Err(e) => failed.push(format!(
+                "{}: {e}",
+                summary.name.as_deref().unwrap_or(&summary.id)
+            )),
+        }
+    }
+    if !failed.is_empty() {
+        return Err(format!(
+            "Exported {written} of {} chats to {}. These couldn't be:\n{}",
+            chats.len(),
+            folder.display(),
+            failed.join("\n")
+        ));
+    }
+    Ok(Some((written, folder)))
+}
+
+fn show(app: &AppHandle, title: &str, kind: MessageDialogKind, message: String) {
+    app.dialog()
+        .message(message)
+        .title(title)
+        .kind(kind)
+        .show(|_| {});
+}
+
+/// File > Export Chat...: the focused window's chat, as Markdown unless
+/// the user picks JSON.
+pub fn export_focused_chat(app: &AppHandle) {
+    let Some(window) = focused_chat_window(app) else {
+        return;
+    };
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = export_one(&app, &window, None, TranscriptFormat::Markdown).await {
+            show(&app, "Export Chat", MessageDialogKind::Warning, e);
+        }
+    });
+}
+
+/// File > Export All Chats...: every chat, as Markdown.
+pub fn export_all_focused(app: &AppHandle) {
+    let Some(window) = focused_chat_window(app) else {
+        return;
+    };
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        match export_all(&app, &window, TranscriptFormat::Markdown).await {
+            Ok(Some((written, folder))) => show(
+                &app,
+                "Export All Chats",
+                MessageDialogKind::Info,
+                format!("Exported {written} chats to {}.", folder.display()),
+            ),
+            Ok(None) => {}
+            Err(e) => show(&app, "Export All Chats", MessageDialogKind::Warning, e),
+        }
+    });
+}
+
+/// Save a transcript of the chat `chat_id` (the calling window's chat
+/// without one) where the user chooses; the file written, or `None` if
+/// they cancelled. `format` is the default; the file type picked in the
+/// dialog wins
+#[tauri::command]
+pub async fn export_chat(
+    app: AppHandle,
+    window: WebviewWindow,
+    chat_id: Option<String>,
+    format: Option<TranscriptFormat>,
+) -> Result<Option<PathBuf>, String> {
+    export_one(&app, &window, chat_id, format.unwrap_or_default()).await
+}
+
+/// Save a transcript of every chat the calling window's user has, one file
+/// each, into a folder the user chooses; how many were written, or `None`
+/// if they cancelled
+#[tauri::command]
+pub async fn export_all_chats(
+    app: AppHandle,
+    window: WebviewWindow,
+    format: Option<TranscriptFormat>,
+) -> Result<Option<usize>, String> {
+    let exported = export_all(&app, &window, format.unwrap_or_default()).await?;
+    Ok(exported.map(|(written, _)| written))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn transcripts_follow_the_latest_answers_and_list_citations() {
+        let chat: ChatTranscript = serde_json::from_str(
+            r#"{
+                "chat_session_id": "4f1c2a9e-0000-0000-0000-000000000000",
+                "description": "PTO: how much?",
+                "persona_name": "Onyx",
+                "time_created": "2026-10-01T09:00:00Z",
+                "messages": [
+                    {"message_id": 1, "parent_message": null, "latest_child_message": 2,
+                     "message": "", "message_type": "system", "time_sent": "2026-10-01T09:00:00Z"},
+                    {"message_id": 2, "parent_message": 1, "latest_child_message": 4,
+                     "message": "How much PTO do we get?", "message_type": "user",
+                     "time_sent": "2026-10-01T09:00:01Z"},
+                    {"message_id": 3, "parent_message": 2, "latest_child_message": null,
+                     "message": "An older answer.", "message_type": "assistant",
+                     "time_sent": "2026-10-01T09:00:02Z"},
+                    {"message_id": 4, "parent_message": 2, "latest_child_message": null,
+                     "message": "Twenty days a year [[1]].", "message_type": "assistant",
+                     "citations": {"1": "pto-doc"},
+                     "context_docs": [
+                        {"document_id": "pto-doc", "semantic_identifier": "PTO Policy",
+                         "link": "https://wiki.example.com/pto", "blurb": "..."},
+                        {"document_id": "other", "semantic_identifier": "Uncited", "link": null}
+                     ],
+                     "time_sent": "2026-10-01T09:00:03Z"}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            render(&chat, TranscriptFormat::Markdown).unwrap(),
+            "# PTO: how much?\n\n*Onyx, 2026-10-01T09:00:00Z*\n\n## You\n\nHow much PTO do we get?\n\n## Onyx\n\nTwenty days a year [[1]].\n\n**Sources:**\n\n1. [PTO Policy](https://wiki.example.com/pto)\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&chat, TranscriptFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["messages"].as_array().unwrap().len(), 2);
+        assert_eq!(json["messages"][1]["citations"][0]["title"], "PTO Policy");
+        #[cfg(feature = "read-aloud")]
+        assert_eq!(last_answer(&chat), Some("Twenty days a year [[1]]."));
+
+        assert_eq!(
+            file_name(title(&chat), &chat.chat_session_id, TranscriptFormat::Json),
+            "PTO- how much- (4f1c2a9e).json"
+        );
+        assert_eq!(
+            TranscriptFormat::of(Path::new("chat.JSON")),
+            Some(TranscriptFormat::Json)
+        );
+    }
+}
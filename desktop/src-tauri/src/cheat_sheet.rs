@@ -0,0 +1,70 @@
+// The keyboard shortcut cheat sheet: a translucent, borderless window over
+// the others that lists every shortcut the menus have bound right now
+// (`shortcuts::active`), so customized and skipped ones show as they are.
+// Help > Keyboard Shortcuts (Cmd/Ctrl+/) toggles it. Opened with the keys,
+// it stays up while they're held, and the page (`shortcuts.html`) closes
+// it when they're let go. Opened from the menu, it stays until Esc, a
+// click, or focus moving elsewhere.
+
+use crate::config::ConfigState;
+use crate::debug_log::log_backend_error;
+use crate::magnifier::Anchor;
+use crate::shortcuts::ActiveShortcut;
+use crate::window::{CHEAT_SHEET_WINDOW, CHEAT_SHEET_WINDOW_LABEL};
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder};
+
+/// Show the cheat sheet, or close it if it's up.
+pub fn toggle_cheat_sheet(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window(CHEAT_SHEET_WINDOW_LABEL) {
+        if let Err(e) = window.close() {
+            log_backend_error(app, &format!("Failed to close cheat sheet: {e}"));
+        }
+        return;
+    }
+
+    // Built off the menu-event thread, like the other bundled pages.
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let preset = &CHEAT_SHEET_WINDOW;
+        let result = WebviewWindowBuilder::new(
+            &handle,
+            CHEAT_SHEET_WINDOW_LABEL,
+            WebviewUrl::App("shortcuts.html".into()),
+        )
+        .title("Keyboard Shortcuts")
+        .inner_size(preset.width, preset.height)
+        .min_inner_size(preset.min_width, preset.min_height)
+        .resizable(false)
+        .decorations(false)
+        .transparent(true)
+        .shadow(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .center()
+        .focused(true)
+        .additional_browser_args(&crate::locale::browser_args())
+        .build();
+        match result {
+            Ok(window) => crate::magnifier::bring_into_view(&handle, &window, Anchor::Center),
+            Err(e) => log_backend_error(&handle, &format!("Failed to open cheat sheet: {e}")),
+        }
+    });
+}
+
+/// The shortcuts bound right now, for the cheat sheet
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn get_active_shortcuts(state: tauri::State<ConfigState>) -> Vec<ActiveShortcut> {
+    crate::shortcuts::active(&state.config().shortcuts)
+}
+
+/// Close the cheat sheet
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn close_cheat_sheet(app: AppHandle) {
+    if let Some(window) = app.get_webview_window(CHEAT_SHEET_WINDOW_LABEL) {
+        if let Err(e) = window.close() {
+            log_backend_error(&app, &format!("Failed to close cheat sheet: {e}"));
+        }
+    }
+}
@@ -0,0 +1,513 @@
+// A client certificate for the configured server, for zero-trust gateways
+// that only complete the TLS handshake with one. `[client_certificate]`
+// names either a PKCS#12 file (`path`, with its `password` kept encrypted
+// like the other secrets) or a certificate already in the OS store
+// (`keychain`), and it's only ever offered to `server_url`:
+//
+// - the app's own requests (`config::http_client_for`) present the file;
+//   one from the OS store can't be handed to rustls, so with `keychain`
+//   only the windows present it;
+// - WKWebView, WebView2 and WebKitGTK each ask the app when the server
+//   wants a certificate (`answer_requests`). WebView2 only takes one from
+//   the Windows store, so there a file has to be imported as well; the
+//   store entry with the same certificate is the one picked.
+//
+// Anything wrong with the file (missing, wrong password, no key) is an
+// error when the settings are saved rather than a handshake failing later.
+
+use crate::config::{validate_server_url, AppConfig, ClientCertificateSettings, ConfigState};
+use crate::window::same_origin;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use std::fmt::Write as _;
+use tauri::{AppHandle, Manager, WebviewWindow};
+use url::Url;
+
+/// A PKCS#12 file's contents: the key and chain as one PEM, and the
+/// certificate itself.
+struct Pkcs12Contents {
+    pem: String,
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    leaf: Vec<u8>,
+}
+
+fn password(settings: &ClientCertificateSettings) -> Result<&str, String> {
+    settings.password.as_ref().map_or(Ok(""), |password| {
+        password.expose().ok_or_else(|| {
+            "The client certificate's password couldn't be decrypted; enter it again".to_string()
+        })
+    })
+}
+
+fn pem_block(label: &str, der: &[u8]) -> String {
+    let encoded = BASE64.encode(der);
+    let mut pem = format!("-----BEGIN {label}-----\n");
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(&String::from_utf8_lossy(line));
+        pem.push('\n');
+    }
+    let _ = writeln!(pem, "-----END {label}-----");
+    pem
+}
+
+/// Read `settings.path`, if set.
+fn read_pkcs12(settings: &ClientCertificateSettings) -> Result<Option<Pkcs12Contents>, String> {
+    let Some(path) = &settings.path else {
+        return Ok(None);
+    };
+    let data = std::fs::read(path).map_err(|e| {
+        format!(
+            "Couldn't read the client certificate {}: {e}",
+            path.display()
+        )
+    })?;
+    let keystore =
+        p12_keystore::KeyStore::from_pkcs12(&data, password(settings)?).map_err(|e| {
+            format!(
+                "Couldn't open the client certificate {} (is the password right?): {e}",
+                path.display()
+            )
+        })?;
+    let (_, chain) = keystore.private_key_chain().ok_or_else(|| {
+        format!(
+            "{} has no private key to go with its certificate",
+            path.display()
+        )
+    })?;
+    let leaf = chain
+        .chain()
+        .first()
+        .ok_or_else(|| format!("{} has no certificate", path.display()))?;
+    let mut pem = pem_block("PRIVATE KEY", chain.key());
+    for certificate in chain.chain() {
+        pem.push_str(&pem_block("CERTIFICATE", certificate.as_der()));
+    }
+    Ok(Some(Pkcs12Contents {
+        pem,
+        leaf: leaf.as_der().to_vec(),
+    }))
+}
+
+/// Check `[client_certificate]` as submitted from the settings window or the
+/// file.
+pub fn validate(settings: &ClientCertificateSettings) -> Result<(), String> {
+    if let Some(name) = &settings.keychain {
+        if settings.path.is_some() {
+            return Err(
+                "Set either `client_certificate.path` or `client_certificate.keychain`, not both"
+                    .to_string(),
+            );
+        }
+        if name.trim().is_empty() {
+            return Err("The client certificate's name cannot be empty".to_string());
+        }
+        if cfg!(target_os = "linux") {
+            return Err(
+                "Linux has no certificate store to pick from; set `client_certificate.path`"
+                    .to_string(),
+            );
+        }
+    }
+    read_pkcs12(settings).map(|_| ())
+}
+
+/// The identity the app's own requests to `url` present, as PEM: the
+/// configured file, if `url` is on `server_url`'s origin.
+pub fn identity_pem_for(config: &AppConfig, url: &Url) -> Result<Option<String>, String> {
+    let on_server =
+        validate_server_url(&config.server_url).is_ok_and(|server| same_origin(&server, url));
+    if !on_server {
+        return Ok(None);
+    }
+    Ok(read_pkcs12(&config.client_certificate)?.map(|contents| contents.pem))
+}
+
+/// Whether `host:port` is the configured server.
+fn is_server(config: &AppConfig, host: &str, port: u16) -> bool {
+    validate_server_url(&config.server_url).is_ok_and(|server| {
+        server
+            .host_str()
+            .is_some_and(|server_host| server_host.eq_ignore_ascii_case(host))
+            && server.port_or_known_default() == Some(port)
+    })
+}
+
+/// The certificate settings to answer `host:port`'s request for one with:
+/// `None` (logged) unless it's the configured server and one is set.
+fn requested(app: &AppHandle, host: &str, port: u16) -> Option<ClientCertificateSettings> {
+    let config = app.state::<ConfigState>().config();
+    if !is_server(&config, host, port) {
+        return None;
+    }
+    let settings = config.client_certificate;
+    if settings.path.is_none() && settings.keychain.is_none() {
+        crate::debug_log::log_backend_error(
+            app,
+            &format!(
+                "{host} asked for a client certificate, but none is set up (`client_certificate`)"
+            ),
+        );
+        return None;
+    }
+    Some(settings)
+}
+
+/// Have `window`'s webview present the configured certificate whenever the
+/// server asks for one. Called once per window; the settings are read at
+/// each request, so later changes apply without calling it again.
+#[cfg(target_os = "linux")]
+pub fn answer_requests(app: &AppHandle, window: &WebviewWindow) {
+    use gtk::gio::TlsCertificate;
+    use gtk::glib::translate::{ToGlibPtr, ToGlibPtrMut};
+    use webkit2gtk::{
+        AuthenticationRequestExt, AuthenticationScheme, Credential, CredentialPersistence,
+        WebViewExt,
+    };
+
+    let app = app.clone();
+    let result = window.with_webview(move |webview| {
+        webview.inner().connect_authenticate(move |_, request| {
+            if request.scheme() != AuthenticationScheme::ClientCertificateRequested {
+                return false;
+            }
+            let host = request
+                .host()
+                .map(|host| host.to_string())
+                .unwrap_or_default();
+            let port = u16::try_from(request.port()).unwrap_or_default();
+            let Some(settings) = requested(&app, &host, port) else {
+                return false;
+            };
+            let certificate = read_pkcs12(&settings).and_then(|contents| {
+                let contents = contents.ok_or("Only a certificate file works on Linux")?;
+                TlsCertificate::from_pem(&contents.pem).map_err(|e| e.to_string())
+            });
+            match certificate {
+                Ok(certificate) => {
+                    let mut credential = Credential::for_certificate(
+                        Some(&certificate),
+                        CredentialPersistence::ForSession,
+                    );
+                    // webkit2gtk 2.0 has no binding for answering a request.
+                    // SAFETY: both pointers are borrowed from live wrappers
+                    // for the length of the call, which takes its own ref.
+                    unsafe {
+                        webkit2gtk::ffi::webkit_authentication_request_authenticate(
+                            request.to_glib_none().0,
+                            credential.to_glib_none_mut().0,
+                        );
+                    }
+                    true
+                }
+                Err(e) => {
+                    crate::debug_log::log_backend_error(
+                        &app,
+                        &format!("Couldn't present the client certificate to {host}: {e}"),
+                    );
+                    false
+                }
+            }
+        });
+    });
+    if let Err(e) = result {
+        crate::debug_log::log_backend_error(window.app_handle(), &e.to_string());
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn answer_requests(app: &AppHandle, window: &WebviewWindow) {
+    use webview2_com::Microsoft::Web::WebView2::Win32::{
+        ICoreWebView2ClientCertificate, ICoreWebView2_5,
+    };
+    use webview2_com::{take_pwstr, ClientCertificateRequestedEventHandler};
+    use windows::core::{Interface, PWSTR};
+
+    /// Whether the store's `certificate` is the configured one.
+    unsafe fn matches(
+        certificate: &ICoreWebView2ClientCertificate,
+        settings: &ClientCertificateSettings,
+    ) -> Result<bool, String> {
+        if let Some(name) = &settings.keychain {
+            let mut subject = PWSTR::null();
+            let mut display_name = PWSTR::null();
+            certificate
+                .Subject(&raw mut subject)
+                .map_err(|e| e.to_string())?;
+            certificate
+                .DisplayName(&raw mut display_name)
+                .map_err(|e| e.to_string())?;
+            return Ok([take_pwstr(subject), take_pwstr(display_name)]
+                .iter()
+                .any(|candidate| candidate.eq_ignore_ascii_case(name)));
+        }
+        let Some(contents) = read_pkcs12(settings)? else {
+            return Ok(false);
+        };
+        let mut pem = PWSTR::null();
+        certificate
+            .ToPemEncoding(&raw mut pem)
+            .map_err(|e| e.to_string())?;
+        Ok(crate::cert_pinning::der_from_pem(&take_pwstr(pem))
+            .is_some_and(|der| der == contents.leaf))
+    }
+
+    let app = app.clone();
+    let result = window.with_webview(move |webview| {
+        // SAFETY: plain COM calls on the webview's own controller, on the
+        // main thread WebView2 was created on.
+        let registered = unsafe {
+            (|| -> windows::core::Result<()> {
+                let core = webview
+                    .controller()
+                    .CoreWebView2()?
+                    .cast::<ICoreWebView2_5>()?;
+                let handler_app = app.clone();
+                let handler = ClientCertificateRequestedEventHandler::create(Box::new(
+                    move |_, args| {
+                        let Some(args) = args else {
+                            return Ok(());
+                        };
+                        let mut host = PWSTR::null();
+                        args.Host(&raw mut host)?;
+                        let host = take_pwstr(host);
+                        let mut port = 0;
+                        args.Port(&raw mut port)?;
+                        let port = u16::try_from(port).unwrap_or_default();
+                        let Some(settings) = requested(&handler_app, &host, port) else {
+                            return Ok(());
+                        };
+                        let certificates = args.MutuallyTrustedCertificates()?;
+                        let mut count = 0;
+                        certificates.Count(&raw mut count)?;
+                        for index in 0..count {
+                            let certificate = certificates.GetValueAtIndex(index)?;
+                            match matches(&certificate, &settings) {
+                                Ok(true) => {
+                                    args.SetSelectedCertificate(&certificate)?;
+                                    args.SetHandled(true)?;
+                                    return Ok(());
+                                }
+                                Ok(false) => {}
+                                Err(e) => {
+                                    crate::debug_log::log_backend_error(
+                                        &handler_app,
+                                        &format!("Couldn't present the client certificate to {host}: {e}"),
+                                    );
+                                    return Ok(());
+                                }
+                            }
+                        }
+                        crate::debug_log::log_backend_error(
+                            &handler_app,
+                            &format!(
+                                "{host} asked for a client certificate, but the configured one isn't in the Windows certificate store (or the server doesn't trust it)"
+                            ),
+                        );
+                        Ok(())
+                    },
+                ));
+                let mut token = 0;
+                core.add_ClientCertificateRequested(&handler, &raw mut token)?;
+                Ok(())
+            })()
+        };
+        if let Err(e) = registered {
+            crate::debug_log::log_backend_error(
+                &app,
+                &format!("Failed to set up the client certificate: {e}"),
+            );
+        }
+    });
+    if let Err(e) = result {
+        crate::debug_log::log_backend_error(window.app_handle(), &e.to_string());
+    }
+}
+
+/// The app the macOS challenge handler reports to; the Objective-C method
+/// it's installed as can't capture one.
+#[cfg(target_os = "macos")]
+static CHALLENGE_APP: std::sync::OnceLock<AppHandle> = std::sync::OnceLock::new();
+
+/// wry's navigation delegate doesn't handle authentication challenges, so
+/// the handler is added to its class, once, as
+/// `webView:didReceiveAuthenticationChallenge:completionHandler:`. It also
+/// answers the server-trust ones, for `cert_trust.rs`.
+#[cfg(target_os = "macos")]
+pub fn answer_requests(app: &AppHandle, window: &WebviewWindow) {
+    use objc2::runtime::{AnyObject, Sel};
+    use objc2::sel;
+    use objc2_web_kit::WKWebView;
+
+    if CHALLENGE_APP.set(app.clone()).is_err() {
+        return;
+    }
+    let result = window.with_webview(|webview| {
+        // SAFETY: `inner` is wry's `WKWebView`, alive while the window is,
+        // and this runs on the main thread as WebKit requires. The method
+        // matches the delegate protocol's signature and type encoding.
+        unsafe {
+            let webview = &*webview.inner().cast::<WKWebView>();
+            let Some(delegate) = webview.navigationDelegate() else {
+                return;
+            };
+            let delegate: &AnyObject = delegate.as_ref();
+            let method: unsafe extern "C-unwind" fn(_, Sel, _, _, _) = did_receive_challenge;
+            objc2::ffi::class_addMethod(
+                std::ptr::from_ref(delegate.class()).cast_mut(),
+                sel!(webView:didReceiveAuthenticationChallenge:completionHandler:),
+                std::mem::transmute::<
+                    unsafe extern "C-unwind" fn(_, Sel, _, _, _),
+                    objc2::runtime::Imp,
+                >(method),
+                c"v@:@@@?".as_ptr(),
+            );
+        }
+    });
+    if let Err(e) = result {
+        crate::debug_log::log_backend_error(app, &e.to_string());
+    }
+}
+
+#[cfg(target_os = "macos")]
+unsafe extern "C-unwind" fn did_receive_challenge(
+    _this: &objc2::runtime::AnyObject,
+    _cmd: objc2::runtime::Sel,
+    _webview: &objc2::runtime::AnyObject,
+    challenge: &objc2_foundation::NSURLAuthenticationChallenge,
+    completion: &block2::DynBlock<
+        dyn Fn(
+            objc2_foundation::NSURLSessionAuthChallengeDisposition,
+            *mut objc2_foundation::NSURLCredential,
+        ),
+    >,
+) {
+    use objc2_foundation::{
+        NSURLAuthenticationMethodClientCertificate, NSURLAuthenticationMethodServerTrust,
+        NSURLCredential, NSURLCredentialPersistence, NSURLSessionAuthChallengeDisposition,
+    };
+
+    let space = challenge.protectionSpace();
+    let default = || {
+        completion.call((
+            NSURLSessionAuthChallengeDisposition::PerformDefaultHandling,
+            std::ptr::null_mut(),
+        ));
+    };
+    let Some(app) = CHALLENGE_APP.get() else {
+        return default();
+    };
+    if *space.authenticationMethod() == *NSURLAuthenticationMethodServerTrust {
+        return crate::cert_trust::server_trust_challenge(app, challenge, completion);
+    }
+    if *space.authenticationMethod() != *NSURLAuthenticationMethodClientCertificate {
+        return default();
+    }
+    let host = space.host().to_string();
+    let port = u16::try_from(space.port()).unwrap_or_default();
+    let Some(settings) = requested(app, &host, port) else {
+        return default();
+    };
+    match keychain_identity(&settings) {
+        Ok(identity) => {
+            // SAFETY: a `SecIdentityRef` from Security.framework, which is
+            // what `objc2_security::SecIdentity` describes.
+            let identity = unsafe {
+                &*core_foundation::base::TCFType::as_concrete_TypeRef(&identity)
+                    .cast::<objc2_security::SecIdentity>()
+            };
+            // objc2-foundation leaves out the methods typed with
+            // Security.framework's types, so this one is sent by name.
+            // SAFETY: `identity` outlives the call, and the selector's
+            // arguments are an identity, an optional array and a
+            // persistence, as passed.
+            let credential: objc2::rc::Retained<NSURLCredential> = unsafe {
+                objc2::msg_send![
+                    objc2::class!(NSURLCredential),
+                    credentialWithIdentity: identity,
+                    certificates: None::<&objc2_foundation::NSArray>,
+                    persistence: NSURLCredentialPersistence::ForSession,
+                ]
+            };
+            completion.call((
+                NSURLSessionAuthChallengeDisposition::UseCredential,
+                objc2::rc::Retained::as_ptr(&credential).cast_mut(),
+            ));
+        }
+        Err(e) => {
+            crate::debug_log::log_backend_error(
+                app,
+                &format!("Couldn't present the client certificate to {host}: {e}"),
+            );
+            default();
+        }
+    }
+}
+
+/// The configured certificate and key as a Security.framework identity,
+/// from the file or the Keychain.
+#[cfg(target_os = "macos")]
+fn keychain_identity(
+    settings: &ClientCertificateSettings,
+) -> Result<security_framework::identity::SecIdentity, String> {
+    use security_framework::import_export::Pkcs12ImportOptions;
+    use security_framework::item::{ItemClass, ItemSearchOptions, Reference, SearchResult};
+
+    if let Some(path) = &settings.path {
+        let data = std::fs::read(path).map_err(|e| {
+            format!(
+                "Couldn't read the client certificate {}: {e}",
+                path.display()
+            )
+        })?;
+        return Pkcs12ImportOptions::new()
+            .passphrase(password(settings)?)
+            .import(&data)
+            .map_err(|e| format!("Couldn't open {}: {e}", path.display()))?
+            .into_iter()
+            .find_map(|imported| imported.identity)
+            .ok_or_else(|| format!("{} has no certificate with a key", path.display()));
+    }
+    let name = settings.keychain.as_deref().unwrap_or_default();
+    ItemSearchOptions::new()
+        .class(ItemClass::identity())
+        .label(name)
+        .load_refs(true)
+        .limit(1)
+        .search()
+        .map_err(|e| format!("\"{name}\" isn't in the Keychain: {e}"))?
+        .into_iter()
+        .find_map(|result| match result {
+            SearchResult::Ref(Reference::Identity(identity)) => Some(identity),
+            _ => None,
+        })
+        .ok_or_else(|| format!("\"{name}\" isn't in the Keychain, or has no private key there"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn certificates_are_only_offered_to_the_configured_server() {
+        let config = AppConfig {
+            server_url: "https://onyx.example.com".to_string(),
+            ..AppConfig::default()
+        };
+        assert!(is_server(&config, "onyx.example.com", 443));
+        assert!(is_server(&config, "ONYX.example.com", 443));
+        assert!(!is_server(&config, "onyx.example.com", 8443));
+        assert!(!is_server(&config, "gateway.example.com", 443));
+
+        let both = ClientCertificateSettings {
+            path: Some("client.p12".into()),
+            keychain: Some("Jane Doe".to_string()),
+            ..ClientCertificateSettings::default()
+        };
+        assert!(validate(&both).is_err());
+        assert!(validate(&ClientCertificateSettings::default()).is_ok());
+
+        let pem = pem_block("CERTIFICATE", &[0; 60]);
+        assert!(pem.starts_with("-----BEGIN CERTIFICATE-----\n"));
+        assert!(pem.lines().all(|line| line.len() <= 64));
+    }
+}
@@ -0,0 +1,357 @@
+// An opt-in history of the text copied lately, for building a prompt out of
+// several sources. With `clipboard_history.enabled` on, the clipboard is
+// checked every `POLL_INTERVAL` and each new piece of text is kept, the
+// last `clipboard_history.items` of them. Edit > Clipboard History... opens
+// a picker (`clipboard.html`) to put one or several into the chat box of
+// the window it was opened from (through `snippets.js`).
+//
+// The history is only ever in memory: it isn't written to disk or sent
+// anywhere, and it's gone when Onyx quits, the setting is turned off, or
+// Clear is pressed. Only what's copied after it's turned on is kept, and
+// text longer than `MAX_ITEM_CHARS` isn't.
+
+use crate::config::{ClipboardHistorySettings, ConfigState};
+use crate::consent::{request_consent, Capability};
+use crate::debug_log::log_backend_error;
+use crate::lock::AppLock;
+use crate::window::{
+    is_bundled_page_window, CLIPBOARD_WINDOW, CLIPBOARD_WINDOW_LABEL, SETTINGS_WINDOW_LABEL,
+};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_ITEM_CHARS: usize = 20_000;
+const MAX_ITEMS: usize = 100;
+
+/// A piece of copied text, as the picker lists it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClipboardItem {
+    pub id: u64,
+    pub text: String,
+    /// When it was copied, in milliseconds since the Unix epoch.
+    pub copied_at: u64,
+}
+
+#[derive(Debug, Default)]
+struct History {
+    items: VecDeque<ClipboardItem>,
+    next_id: u64,
+}
+
+impl History {
+    /// Keep `text` as the latest item, and no more than `limit` in all. Text
+    /// copied again moves up rather than showing twice.
+    fn push(&mut self, text: String, copied_at: u64, limit: usize) {
+        self.items.retain(|item| item.text != text);
+        self.next_id += 1;
+        self.items.push_front(ClipboardItem {
+            id: self.next_id,
+            text,
+            copied_at,
+        });
+        self.items.truncate(limit);
+    }
+}
+
+/// Managed state: the history, and the chat window the picker was opened
+/// from.
+#[derive(Default)]
+pub struct ClipboardHistory {
+    history: Mutex<History>,
+    target: Mutex<Option<String>>,
+}
+
+impl ClipboardHistory {
+    fn history(&self) -> MutexGuard<'_, History> {
+        self.history
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn clear(&self) {
+        self.history().items.clear();
+    }
+}
+
+/// Register the history, empty at start.
+pub fn manage(builder: tauri::Builder<tauri::Wry>) -> tauri::Builder<tauri::Wry> {
+    builder.manage(ClipboardHistory::default())
+}
+
+fn keepable(text: &str) -> bool {
+    !text.trim().is_empty() && text.chars().count() <= MAX_ITEM_CHARS
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| {
+            u64::try_from(since.as_millis()).unwrap_or(u64::MAX)
+        })
+}
+
+/// Watch the clipboard for as long as the app runs, keeping what's copied
+/// while the history is on. Only for the copy that holds the data
+/// directory.
+pub fn start_watching(app: &AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let mut clipboard: Option<arboard::Clipboard> = None;
+        // What the clipboard had at the last check, once it's being watched.
+        let mut last: Option<Option<String>> = None;
+        let mut reported = false;
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let settings = app.state::<ConfigState>().config().clipboard_history;
+            if !settings.enabled {
+                // Let go of the clipboard (an X11 connection on Linux).
+                clipboard = None;
+                last = None;
+                continue;
+            }
+            if clipboard.is_none() {
+                match arboard::Clipboard::new() {
+                    Ok(opened) => clipboard = Some(opened),
+                    Err(e) => {
+                        if !reported {
+                            reported = true;
+                            log_backend_error(
+                                &app,
+                                &format!("Clipboard history can't read the clipboard: {e}"),
+                            );
+                        }
+                        continue;
+                    }
+                }
+            }
+            let Some(board) = clipboard.as_mut() else {
+                continue;
+            };
+            // Images and files read as errors: there's no text to keep.
+            let text = board.get_text().ok();
+            let Some(previous) = last.replace(text.clone()) else {
+                // Turned on just now: what was copied before isn't kept.
+                continue;
+            };
+            if text == previous {
+                continue;
+            }
+            if let Some(text) = text.filter(|text| keepable(text)) {
+                app.state::<ClipboardHistory>()
+                    .history()
+                    .push(text, now_millis(), settings.items);
+            }
+        }
+    });
+}
+
+/// Apply a changed `clipboard_history` setting: turned off, the history
+/// goes; otherwise it's cut to the new size.
+pub fn sync(app: &AppHandle) {
+    let settings = app.state::<ConfigState>().config().clipboard_history;
+    let state = app.state::<ClipboardHistory>();
+    if settings.enabled {
+        state.history().items.truncate(settings.items);
+    } else {
+        state.clear();
+    }
+}
+
+pub fn validate(settings: &ClipboardHistorySettings) -> Result<(), String> {
+    if !(1..=MAX_ITEMS).contains(&settings.items) {
+        return Err(format!(
+            "The clipboard history keeps between 1 and {MAX_ITEMS} items"
+        ));
+    }
+    Ok(())
+}
+
+/// Edit > Clipboard History...: the picker, for the focused chat window.
+pub fn open_picker(app: &AppHandle) {
+    if app.state::<AppLock>().is_locked() {
+        return;
+    }
+    let windows = app.webview_windows();
+    let target = windows
+        .iter()
+        .find(|(label, window)| {
+            !is_bundled_page_window(label) && window.is_focused().unwrap_or(false)
+        })
+        .map(|(label, _)| label.clone());
+    if let Some(target) = target {
+        *app.state::<ClipboardHistory>()
+            .target
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(target);
+    }
+
+    if let Some(window) = windows.get(CLIPBOARD_WINDOW_LABEL) {
+        if let Err(e) = window.set_focus() {
+            log_backend_error(app, &format!("Failed to focus the clipboard history: {e}"));
+        }
+        return;
+    }
+    // Built off the menu-event thread, like the other bundled pages.
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let preset = &CLIPBOARD_WINDOW;
+        let result = WebviewWindowBuilder::new(
+            &handle,
+            CLIPBOARD_WINDOW_LABEL,
+            WebviewUrl::App("clipboard.html".into()),
+        )
+        .title("Clipboard History")
+        .inner_size(preset.width, preset.height)
+        .min_inner_size(preset.min_width, preset.min_height)
+        .center()
+        .focused(true)
+        .additional_browser_args(&crate::locale::browser_args())
+        .build();
+        if let Err(e) = result {
+            log_backend_error(
+                &handle,
+                &format!("Failed to open the clipboard history: {e}"),
+            );
+        }
+    });
+}
+
+fn check_caller(app: &AppHandle, window: &WebviewWindow, allowed: &[&str]) -> Result<(), String> {
+    if !allowed.contains(&window.label()) {
+        return Err("Not available in this window".to_string());
+    }
+    if app.state::<AppLock>().is_locked() {
+        return Err("Onyx is locked".to_string());
+    }
+    Ok(())
+}
+
+fn close_picker(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window(CLIPBOARD_WINDOW_LABEL) {
+        if let Err(e) = window.close() {
+            log_backend_error(app, &format!("Failed to close the clipboard history: {e}"));
+        }
+    }
+}
+
+/// The history, latest first; only from the clipboard history window
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn get_clipboard_history(
+    app: AppHandle,
+    window: WebviewWindow,
+) -> Result<Vec<ClipboardItem>, String> {
+    check_caller(&app, &window, &[CLIPBOARD_WINDOW_LABEL])?;
+    if !app
+        .state::<ConfigState>()
+        .config()
+        .clipboard_history
+        .enabled
+    {
+        return Err(
+            "Clipboard history is off. Turn it on in Settings \u{2192} Clipboard.".to_string(),
+        );
+    }
+    Ok(app
+        .state::<ClipboardHistory>()
+        .history()
+        .items
+        .iter()
+        .cloned()
+        .collect())
+}
+
+/// Put the items `ids`, in that order, into the chat box of the window the
+/// picker was opened from, and close the picker; only from the clipboard
+/// history window
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub async fn insert_clipboard_items(
+    app: AppHandle,
+    window: WebviewWindow,
+    ids: Vec<u64>,
+) -> Result<(), String> {
+    check_caller(&app, &window, &[CLIPBOARD_WINDOW_LABEL])?;
+    let state = app.state::<ClipboardHistory>();
+    let text = {
+        let history = state.history();
+        ids.iter()
+            .filter_map(|id| history.items.iter().find(|item| item.id == *id))
+            .map(|item| item.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    };
+    if text.is_empty() {
+        return Err("Those items are no longer in the history".to_string());
+    }
+    let target = state
+        .target
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone()
+        .and_then(|label| app.get_webview_window(&label))
+        .or_else(|| app.get_webview_window("main"))
+        .ok_or("The chat window is gone")?;
+    if !request_consent(&app, &target, Capability::ReadClipboard, None).await? {
+        return Err("Reading the clipboard wasn't allowed".to_string());
+    }
+    let text = serde_json::to_string(&text).map_err(|e| e.to_string())?;
+    crate::watchdog::eval(
+        target.as_ref(),
+        format!("window.__ONYX_SNIPPETS__?.insertText({text})"),
+        "insert clipboard items",
+    );
+    if let Err(e) = target.set_focus() {
+        log_backend_error(&app, &format!("Failed to focus the chat window: {e}"));
+    }
+    close_picker(&app);
+    Ok(())
+}
+
+/// Forget every item; from the clipboard history window or Settings
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn clear_clipboard_history(app: AppHandle, window: WebviewWindow) -> Result<(), String> {
+    check_caller(
+        &app,
+        &window,
+        &[CLIPBOARD_WINDOW_LABEL, SETTINGS_WINDOW_LABEL],
+    )?;
+    app.state::<ClipboardHistory>().clear();
+    Ok(())
+}
+
+/// Close the clipboard history
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn close_clipboard_history(app: AppHandle) {
+    close_picker(&app);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_history_keeps_the_latest_distinct_copies() {
+        let mut history = History::default();
+        for (at, text) in [(1, "alpha"), (2, "beta"), (3, "alpha"), (4, "gamma")] {
+            history.push(text.to_string(), at, 2);
+        }
+        let kept: Vec<_> = history
+            .items
+            .iter()
+            .map(|item| (item.text.as_str(), item.copied_at))
+            .collect();
+        assert_eq!(kept, [("gamma", 4), ("alpha", 3)]);
+        assert_eq!(history.items[0].id, 4);
+
+        assert!(keepable("SELECT * FROM orders"));
+        assert!(!keepable(" \n\t"));
+        assert!(!keepable(&"x".repeat(MAX_ITEM_CHARS + 1)));
+    }
+}
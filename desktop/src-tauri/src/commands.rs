@@ -3,13 +3,30 @@
 // the framework's calling convention, not an oversight.
 #![allow(clippy::needless_pass_by_value)]
 
-use crate::config::{get_config_dir, get_config_path, save_config, AppConfig, ConfigState};
-use crate::window::{build_and_setup_window, open_in_default_browser};
+use crate::config::{
+    get_config_dir, get_config_path, http_client_for, keep_app_state, keep_locked, locked_settings,
+    save_config, validate_config, AppConfig, ConfigState,
+};
+use crate::debug_log::{log_backend_error, log_debug};
+use crate::install_scope::managed_policy;
+use crate::menu::sync_menu_with_config;
+use crate::route::Route;
+use crate::window::{
+    apply_settings_to_window, build_and_setup_window, is_bundled_page_window,
+    open_in_default_browser, set_window_always_on_top, WindowSession, DEFAULT_PARTITION,
+    SETTINGS_WINDOW_LABEL,
+};
 use serde::Serialize;
 use std::fs;
+use std::time::Duration;
 use tauri::Manager;
+use tauri_plugin_dialog::{
+    DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult,
+};
 use url::Url;
 
+const RESET_LABEL: &str = "Reset";
+
 #[tauri::command]
 pub async fn check_server_reachable(state: tauri::State<'_, ConfigState>) -> Result<(), String> {
     let url = state.config().server_url;
@@ -19,10 +36,7 @@ pub async fn check_server_reachable(state: tauri::State<'_, ConfigState>) -> Res
         _ => return Err("URL must use http or https".to_string()),
     }
 
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(5))
-        .build()
-        .map_err(|e| format!("Failed to build HTTP client: {e}"))?;
+    let client = http_client_for(&state.config(), &parsed, Duration::from_secs(5))?;
 
     match client.head(parsed).send().await {
         // Only definitive "server didn't answer" errors count as unreachable.
@@ -59,18 +73,32 @@ pub fn get_server_url(state: tauri::State<ConfigState>) -> String {
 pub struct BootstrapState {
     server_url: String,
     config_exists: bool,
+    /// Where on the server to start, when not the chat.
+    start_path: Option<String>,
 }
 
 /// Get the server URL plus whether a config file exists
 #[tauri::command]
 pub fn get_bootstrap_state(state: tauri::State<ConfigState>) -> BootstrapState {
-    let server_url = state.config().server_url;
-    let config_exists =
-        state.is_config_initialized() && get_config_path().is_some_and(|path| path.exists());
+    let config = state.config();
+    let server_url = config.server_url.clone();
+    // An in-memory (demo) config is already set up, file or not, and so is
+    // one whose server the organization's policy sets.
+    let config_exists = state.is_in_memory()
+        || managed_policy().server_url.is_some()
+        || (state.is_config_initialized() && get_config_path().is_some_and(|path| path.exists()));
 
     BootstrapState {
         server_url,
         config_exists,
+        start_path: config
+            .server_url
+            .parse::<Url>()
+            .ok()
+            .filter(|_| crate::admin::admin_mode(&config, DEFAULT_PARTITION))
+            .map(|server| {
+                crate::admin::admin_start().url(&server)[url::Position::BeforePath..].to_string()
+            }),
     }
 }
 
@@ -89,6 +117,169 @@ pub fn set_server_url(state: tauri::State<ConfigState>, url: String) -> Result<S
     Ok(config.server_url)
 }
 
+/// Get the full config; from Settings only
+#[tauri::command]
+pub fn get_config(
+    window: tauri::WebviewWindow,
+    state: tauri::State<ConfigState>,
+) -> Result<AppConfig, String> {
+    if window.label() != SETTINGS_WINDOW_LABEL {
+        return Err("The settings can only be read from Settings".to_string());
+    }
+    Ok(state.config())
+}
+
+/// The settings that can't be changed (see `locked_fields`), by their path
+/// in the config, for the settings window to show as managed
+#[tauri::command]
+pub fn get_locked_settings(state: tauri::State<ConfigState>) -> Vec<&'static str> {
+    locked_settings(&state.config())
+        .into_iter()
+        .map(|setting| setting.path)
+        .collect()
+}
+
+/// Tidy up and check a whole config as submitted, with managed policy
+/// applied, before it's saved.
+pub fn check_config(config: &mut AppConfig) -> Result<(), String> {
+    config.server_url = config.server_url.trim().trim_end_matches('/').to_string();
+    config.proxy.url = config
+        .proxy
+        .url
+        .take()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty());
+    config.voice.dictation_endpoint = config
+        .voice
+        .dictation_endpoint
+        .take()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty());
+    managed_policy().apply(config);
+    validate_config(config)?;
+    #[cfg(feature = "voice")]
+    crate::push_to_talk::validate(&config.voice)?;
+    #[cfg(feature = "voice")]
+    crate::dictation::validate(&config.voice)?;
+    crate::lock::validate(&config.lock)?;
+    crate::server_headers::validate(config)?;
+    crate::client_cert::validate(&config.client_certificate)?;
+    crate::cert_pinning::validate(&config.tls)?;
+    crate::snippets::validate(&config.snippets)?;
+    crate::scheduler::validate(&config.schedules)?;
+    crate::managed_config::validate(config)?;
+    #[cfg(feature = "crash-reporting")]
+    crate::crash_report::validate(&config.crash_reports)?;
+    #[cfg(feature = "automation-api")]
+    crate::automation_api::validate(&config.automation_api)?;
+    #[cfg(feature = "local-files")]
+    crate::local_files::validate(&config.local_files)?;
+    #[cfg(feature = "offline-cache")]
+    crate::offline_cache::validate(&config.offline_cache)?;
+    #[cfg(feature = "clipboard-history")]
+    crate::clipboard_history::validate(&config.clipboard_history)?;
+    #[cfg(feature = "read-aloud")]
+    crate::read_aloud::validate(&config.read_aloud)?;
+    Ok(())
+}
+
+/// Validate, save, and apply a config edited in the settings window; from
+/// Settings only
+#[tauri::command]
+pub fn set_config(
+    app: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    config: AppConfig,
+) -> Result<AppConfig, String> {
+    if window.label() != SETTINGS_WINDOW_LABEL {
+        return Err("The settings can only be changed from Settings".to_string());
+    }
+    save_settings(&app, config)
+}
+
+/// Validate, save, and apply a whole config, as Settings or `onyx config
+/// set` submits it.
+pub fn save_settings(app: &tauri::AppHandle, mut config: AppConfig) -> Result<AppConfig, String> {
+    if crate::kiosk::is_active() {
+        return Err(crate::kiosk::unavailable());
+    }
+    check_config(&mut config)?;
+
+    let state = app.state::<ConfigState>();
+    let previous = state.config();
+    let saved = state.update_and_persist_backed_up(|c| {
+        // Nor are the locked fields the settings window's to change.
+        keep_app_state(&mut config, c);
+        config.locked_fields.clone_from(&c.locked_fields);
+        *c = config;
+    })?;
+    state.set_config_initialized(true);
+    apply_config_change(app, &previous, &saved);
+    Ok(saved)
+}
+
+/// Bring the running app in line with a config that changed from
+/// `previous` to `saved`: windows, menus, shortcuts and whatever else
+/// follows the settings.
+pub fn apply_config_change(app: &tauri::AppHandle, previous: &AppConfig, saved: &AppConfig) {
+    for (label, window) in app.webview_windows() {
+        if !is_bundled_page_window(&label) {
+            apply_settings_to_window(app, &window);
+        }
+    }
+    sync_menu_with_config(app);
+    #[cfg(feature = "voice")]
+    crate::push_to_talk::sync_shortcut(app);
+    crate::accounts::sync_account_menus(app);
+    crate::do_not_disturb::sync_menu(app);
+    if saved.launch_at_login != previous.launch_at_login {
+        crate::autostart::sync(app);
+    }
+    #[cfg(feature = "local-files")]
+    crate::local_files::sync_menu(app);
+    // A kiosk never starts these (see `setup_app`), whatever the config
+    // comes to say.
+    if !crate::kiosk::is_active() {
+        #[cfg(feature = "automation-api")]
+        crate::automation_api::sync(app);
+        #[cfg(feature = "offline-cache")]
+        if saved.offline_cache != previous.offline_cache {
+            crate::offline_cache::sync(app);
+        }
+        #[cfg(feature = "clipboard-history")]
+        if saved.clipboard_history != previous.clipboard_history {
+            crate::clipboard_history::sync(app);
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    if saved.file_context_menu != previous.file_context_menu {
+        crate::file_menu::sync(app);
+    }
+
+    if saved.always_on_top != previous.always_on_top {
+        for (label, window) in app.webview_windows() {
+            if !is_bundled_page_window(&label) {
+                set_window_always_on_top(app, &window, saved.always_on_top);
+            }
+        }
+    }
+
+    if saved.server_url != previous.server_url {
+        if let (Some(window), Ok(url)) = (
+            app.get_webview_window("main"),
+            Url::parse(&saved.server_url),
+        ) {
+            if let Err(e) = window.navigate(url) {
+                log_backend_error(app, &format!("Failed to navigate to new server: {e}"));
+            }
+        }
+        // The kept chats are the old server's.
+        #[cfg(feature = "offline-cache")]
+        crate::offline_cache::clear(app);
+    }
+}
+
 /// Get the config file path (so users know where to edit)
 #[tauri::command]
 pub fn get_config_path_cmd() -> Result<String, String> {
@@ -118,9 +309,7 @@ pub fn open_config_file() -> Result<(), String> {
 
     #[cfg(target_os = "linux")]
     {
-        std::process::Command::new("xdg-open")
-            .arg(&config_path)
-            .spawn()
+        crate::sandbox::open_path(&config_path)
             .map_err(|e| format!("Failed to open config: {e}"))?;
     }
 
@@ -153,9 +342,7 @@ pub fn open_config_directory() -> Result<(), String> {
 
     #[cfg(target_os = "linux")]
     {
-        std::process::Command::new("xdg-open")
-            .arg(&config_dir)
-            .spawn()
+        crate::sandbox::open_path(&config_dir)
             .map_err(|e| format!("Failed to open directory: {e}"))?;
     }
 
@@ -170,64 +357,170 @@ pub fn open_config_directory() -> Result<(), String> {
     Ok(())
 }
 
-/// Navigate to a specific path on the configured server
+/// Navigate to a route, or a specific path, on the configured server
 #[tauri::command]
-pub fn navigate_to(window: tauri::WebviewWindow, state: tauri::State<ConfigState>, path: &str) {
+pub fn navigate_to(
+    window: tauri::WebviewWindow,
+    state: tauri::State<ConfigState>,
+    path: Option<String>,
+    route: Option<Route>,
+) -> Result<(), String> {
     let base_url = state.config().server_url;
-    let url = format!("{base_url}{path}");
-    if let Err(e) = window.eval(format!("window.location.href = '{url}'")) {
-        crate::debug_log::log_backend_error(
-            window.app_handle(),
-            &format!("Failed to navigate to {path}: {e}"),
-        );
-    }
+    let url = match (route, path) {
+        (Some(route), _) => {
+            let server = base_url
+                .parse()
+                .map_err(|e| format!("Invalid server URL: {e}"))?;
+            route.url(&server).to_string()
+        }
+        (None, Some(path)) => format!("{base_url}{path}"),
+        (None, None) => return Err("Nowhere to navigate to".to_string()),
+    };
+    crate::watchdog::eval(
+        window.as_ref(),
+        format!("window.location.href = '{url}'"),
+        "navigate",
+    );
+    Ok(())
 }
 
 /// Reload the current page
 #[tauri::command]
 pub fn reload_page(window: tauri::WebviewWindow) {
-    if let Err(e) = window.eval("window.location.reload()") {
-        crate::debug_log::log_backend_error(
-            window.app_handle(),
-            &format!("Failed to reload page: {e}"),
-        );
-    }
+    crate::watchdog::eval(window.as_ref(), "window.location.reload()", "reload page");
 }
 
 /// Go back in history
 #[tauri::command]
 pub fn go_back(window: tauri::WebviewWindow) {
-    if let Err(e) = window.eval("window.history.back()") {
-        crate::debug_log::log_backend_error(
-            window.app_handle(),
-            &format!("Failed to go back: {e}"),
-        );
-    }
+    crate::watchdog::eval(window.as_ref(), "window.history.back()", "go back");
 }
 
 /// Go forward in history
 #[tauri::command]
 pub fn go_forward(window: tauri::WebviewWindow) {
-    if let Err(e) = window.eval("window.history.forward()") {
-        crate::debug_log::log_backend_error(
-            window.app_handle(),
-            &format!("Failed to go forward: {e}"),
-        );
+    crate::watchdog::eval(window.as_ref(), "window.history.forward()", "go forward");
+}
+
+/// A page move from the View menu.
+#[derive(Debug, Clone, Copy)]
+pub enum Navigation {
+    Reload,
+    Back,
+    Forward,
+}
+
+/// Reload the focused Onyx window, or move it through its history. Does
+/// nothing when no server window has focus (the menu's accelerators only
+/// fire in Onyx's own windows, and the bundled pages have no history).
+pub fn navigate_focused(app: &tauri::AppHandle, navigation: Navigation) {
+    let Some(window) = app
+        .webview_windows()
+        .into_iter()
+        .find(|(label, window)| {
+            !is_bundled_page_window(label) && window.is_focused().unwrap_or(false)
+        })
+        .map(|(_, window)| window)
+    else {
+        return;
+    };
+    match navigation {
+        Navigation::Reload => reload_page(window),
+        Navigation::Back => go_back(window),
+        Navigation::Forward => go_forward(window),
     }
 }
 
-/// Open a new window
+/// Open a new window, optionally on a configured profile or another server
+/// URL, either of which gets its own isolated webview data
 #[tauri::command]
-pub async fn new_window(app: tauri::AppHandle) -> Result<(), String> {
-    build_and_setup_window(&app).map(|_| ())
+pub async fn new_window(
+    app: tauri::AppHandle,
+    profile: Option<String>,
+    url: Option<String>,
+) -> Result<(), String> {
+    let session = match (profile, url) {
+        (Some(_), Some(_)) => return Err("Pass either a profile or a URL, not both".to_string()),
+        (Some(profile), None) => Some(WindowSession::for_profile(
+            &app.state::<ConfigState>().config(),
+            &profile,
+        )?),
+        (None, Some(url)) => Some(WindowSession::for_url(&url)?),
+        (None, None) => None,
+    };
+    build_and_setup_window(&app, session.as_ref()).map(|_| ())
 }
 
-/// Reset config to defaults
+/// Reset config to defaults, except for what's locked, once the user
+/// confirms; from Settings only. The config file is archived first (see
+/// `config_backup.rs`). Returns the config after, or `None` if the user
+/// cancelled.
 #[tauri::command]
-pub fn reset_config(state: tauri::State<ConfigState>) -> Result<(), String> {
-    state.update_and_persist(|c| *c = AppConfig::default())?;
+pub async fn reset_config(
+    app: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    state: tauri::State<'_, ConfigState>,
+) -> Result<Option<AppConfig>, String> {
+    if window.label() != SETTINGS_WINDOW_LABEL {
+        return Err("Settings can only be reset from Settings".to_string());
+    }
+    if crate::kiosk::is_active() {
+        return Err(crate::kiosk::unavailable());
+    }
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .message(
+            "Every setting goes back to its default, apart from any your organization \
+             manages. The current settings are archived first.",
+        )
+        .title("Reset all settings?")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            RESET_LABEL.to_string(),
+            "Cancel".to_string(),
+        ))
+        .parent(&window)
+        .show_with_result(move |result| {
+            let _ = tx.send(match result {
+                MessageDialogResult::Ok => true,
+                MessageDialogResult::Custom(label) => label == RESET_LABEL,
+                _ => false,
+            });
+        });
+    if !rx.await.unwrap_or(false) {
+        return Ok(None);
+    }
+
+    let previous = state.config();
+    if let Some(path) = get_config_path().filter(|_| !state.is_in_memory()) {
+        if let Ok(contents) = fs::read_to_string(&path) {
+            let archived = crate::config_backup::archive(&path, &contents, &previous)?;
+            log_debug(
+                &app,
+                &format!("Archived the config to {}", archived.display()),
+            );
+        }
+    }
+    let saved = state.update_and_persist(|c| {
+        let current = std::mem::take(c);
+        keep_locked(c, &current);
+    })?;
     state.set_config_initialized(true);
-    Ok(())
+    apply_config_change(&app, &previous, &saved);
+    Ok(Some(saved))
+}
+
+/// Open the window for reviewing remembered permission decisions
+#[tauri::command]
+pub fn open_permissions(app: tauri::AppHandle) {
+    crate::window::open_permissions(&app);
+}
+
+/// Keep the calling window above other windows, or stop doing so
+#[tauri::command]
+pub fn set_always_on_top(window: tauri::WebviewWindow, enabled: bool) {
+    set_window_always_on_top(window.app_handle(), &window, enabled);
 }
 
 /// Start dragging the window
@@ -242,3 +535,14 @@ pub async fn start_drag_window(window: tauri::Window) -> Result<(), String> {
 pub fn toggle_menu_bar(app: tauri::AppHandle) {
     crate::menu::handle_menu_bar_toggle(&app);
 }
+
+/// The injected titlebar couldn't run in the calling window (see
+/// `titlebar.js`); fall back to the native one for its profile
+#[tauri::command]
+pub fn report_titlebar_blocked(
+    app: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    reason: String,
+) {
+    crate::window::fall_back_to_native_titlebar(&app, &window, &reason);
+}
@@ -0,0 +1,307 @@
+// Companion commands, for using Onyx from a terminal:
+//
+//     onyx ask "What's our PTO policy?"
+//     onyx open [CHAT_ID]
+//     onyx config get server_url
+//     onyx config set updates.check_automatically false
+//     onyx share --text "Summarize this" report.pdf
+//     onyx upload report.pdf
+//
+// With Onyx running, the command goes to that copy over its local socket
+// (see `ipc.rs`, which other tools use too) and its answer is printed; it
+// runs there through the same code as the menus and Settings. Otherwise
+// `ask`, `open`, `share` and `upload` start the app on that chat or
+// upload, and `config` edits the config file directly, checked like the
+// settings window's changes, locked settings included. Settings are named
+// by their path in the config file, and values are read as JSON where they
+// parse as it (`true`, `25`, `["en-US"]`), as text otherwise.
+
+use crate::commands::{check_config, save_settings};
+use crate::config::{check_locked, load_config, save_config_backed_up, AppConfig, ConfigState};
+use crate::install_scope::managed_policy;
+use crate::lock::AppLock;
+use crate::route::Route;
+use crate::share::Share;
+use crate::window::{focus_main_window, open_route};
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Ask Onyx a question in a new chat
+    Ask {
+        /// The question; the words are joined, so quoting is optional
+        #[arg(required = true, num_args = 1..)]
+        question: Vec<String>,
+    },
+    /// Open a chat by its ID, or a new one without one
+    Open { chat_id: Option<String> },
+    /// Read or change a setting
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Start a chat with text, a link or files in it, not yet sent
+    Share {
+        /// Into the chat that's open rather than a new one
+        #[arg(long)]
+        current: bool,
+        #[arg(long)]
+        text: Option<String>,
+        #[arg(long)]
+        url: Option<String>,
+        files: Vec<PathBuf>,
+    },
+    /// Upload files to the shared folders' file connector
+    Upload {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ConfigAction {
+    /// Print a setting, such as `server_url` or `lock.after_minutes`
+    Get { key: String },
+    /// Change a setting
+    Set { key: String, value: String },
+}
+
+/// A command as sent to the running copy.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Message {
+    /// A new chat that asks `query`.
+    Ask {
+        query: String,
+    },
+    OpenChat {
+        id: String,
+    },
+    NewChat,
+    /// Bring the main window to the front.
+    Focus,
+    GetConfig {
+        key: String,
+    },
+    SetConfig {
+        key: String,
+        value: String,
+    },
+    Share(Share),
+    Upload {
+        files: Vec<PathBuf>,
+    },
+}
+
+/// What `main` does after a command.
+pub enum Outcome {
+    /// Print this and stop.
+    Done(String),
+    /// Start the app as usual, then open this if there's one.
+    Launch(Option<Route>),
+    /// Start the app as usual, then open this share.
+    Share(Share),
+    /// Start the app as usual, then upload these.
+    Upload(Vec<PathBuf>),
+}
+
+impl Command {
+    fn message(&self) -> Message {
+        match self {
+            Self::Ask { question } => Message::Ask {
+                query: question.join(" "),
+            },
+            Self::Open { chat_id: Some(id) } => Message::OpenChat { id: id.clone() },
+            Self::Open { chat_id: None } => Message::NewChat,
+            Self::Config {
+                action: ConfigAction::Get { key },
+            } => Message::GetConfig { key: key.clone() },
+            Self::Config {
+                action: ConfigAction::Set { key, value },
+            } => Message::SetConfig {
+                key: key.clone(),
+                value: value.clone(),
+            },
+            Self::Share {
+                current,
+                text,
+                url,
+                files,
+            } => Message::Share(Share {
+                text: text.clone(),
+                url: url.clone(),
+                title: None,
+                files: absolute(files),
+                current: *current,
+            }),
+            Self::Upload { files } => Message::Upload {
+                files: absolute(files),
+            },
+        }
+    }
+}
+
+/// `files` from anywhere: the running copy may have started somewhere else.
+fn absolute(files: &[PathBuf]) -> Vec<PathBuf> {
+    files
+        .iter()
+        .map(|file| std::path::absolute(file).unwrap_or_else(|_| file.clone()))
+        .collect()
+}
+
+impl Message {
+    /// The chat this opens, if it opens one.
+    fn route(self) -> Option<Route> {
+        match self {
+            Self::Ask { query } => Some(Route::Search { query }),
+            Self::OpenChat { id } => Some(Route::Chat { id }),
+            Self::NewChat => Some(Route::new_chat()),
+            Self::Focus
+            | Self::GetConfig { .. }
+            | Self::SetConfig { .. }
+            | Self::Share(_)
+            | Self::Upload { .. } => None,
+        }
+    }
+}
+
+/// Run `command`: in the running copy if there is one, here otherwise.
+pub fn run(command: &Command) -> Result<Outcome, String> {
+    let message = command.message();
+    if let Some(output) = crate::ipc::send(&message)? {
+        return Ok(Outcome::Done(output));
+    }
+
+    match message {
+        Message::GetConfig { key } => get_setting(&load_config().0, &key).map(Outcome::Done),
+        Message::SetConfig { key, value } => {
+            let (mut loaded, _) = load_config();
+            let mut config = loaded.clone();
+            set_setting(&mut config, &key, &value)?;
+            check_config(&mut config)?;
+            // Compared as the running app would have it, policy and all.
+            managed_policy().apply(&mut loaded);
+            check_locked(&loaded, &config)?;
+            save_config_backed_up(&config)?;
+            get_setting(&config, &key).map(Outcome::Done)
+        }
+        Message::Share(share) => Ok(Outcome::Share(share)),
+        Message::Upload { files } => Ok(Outcome::Upload(files)),
+        message => Ok(Outcome::Launch(message.route())),
+    }
+}
+
+/// Run a command another program sent; its output.
+pub fn answer(app: &AppHandle, message: Message) -> Result<String, String> {
+    if app.state::<AppLock>().is_locked() {
+        return Err("Onyx is locked".to_string());
+    }
+    let state = app.state::<ConfigState>();
+    match message {
+        Message::Focus => {
+            focus_main_window(app);
+            Ok(String::new())
+        }
+        Message::GetConfig { key } => get_setting(&state.config(), &key),
+        Message::SetConfig { key, value } => {
+            let mut config = state.config();
+            set_setting(&mut config, &key, &value)?;
+            let saved = save_settings(app, config)?;
+            get_setting(&saved, &key)
+        }
+        Message::Share(share) => crate::share::open(app, &share).map(|()| String::new()),
+        Message::Upload { files } => crate::file_menu::upload(app, files).map(|()| String::new()),
+        message => {
+            if let Some(route) = message.route() {
+                open_route(app, &route);
+            }
+            Ok(String::new())
+        }
+    }
+}
+
+/// The setting at `key` (dots between levels), as it would be given to
+/// `set`: text as it is, anything else as JSON.
+fn get_setting(config: &AppConfig, key: &str) -> Result<String, String> {
+    let config = serde_json::to_value(config).map_err(|e| e.to_string())?;
+    let value = key
+        .split('.')
+        .try_fold(&config, |value, part| value.get(part))
+        .ok_or_else(|| format!("There's no setting named \"{key}\""))?;
+    Ok(match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    })
+}
+
+/// Put `value` at `key`, read as JSON where it parses as it.
+fn set_setting(config: &mut AppConfig, key: &str, value: &str) -> Result<(), String> {
+    let mut settings = serde_json::to_value(&*config).map_err(|e| e.to_string())?;
+    let parsed = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+    let (parent, name) = key.rsplit_once('.').unwrap_or(("", key));
+    let section = parent
+        .split('.')
+        .filter(|part| !part.is_empty())
+        .try_fold(&mut settings, |value, part| value.get_mut(part))
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| format!("There's no setting named \"{key}\""))?;
+    section.insert(name.to_string(), parsed.clone());
+
+    let updated: AppConfig = serde_json::from_value(settings)
+        .map_err(|e| format!("\"{value}\" doesn't fit \"{key}\": {e}"))?;
+    // Names the config doesn't have are dropped when it's read back; text
+    // may come back tidied or, for secrets, encrypted.
+    let kept = serde_json::to_value(&updated)
+        .ok()
+        .and_then(|settings| {
+            key.split('.')
+                .try_fold(&settings, |value, part| value.get(part))
+                .cloned()
+        })
+        .is_some_and(|kept| kept == parsed || kept.is_string());
+    if !kept {
+        return Err(format!("There's no setting named \"{key}\""));
+    }
+    *config = updated;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn settings_are_named_by_their_path() {
+        let mut config = AppConfig::default();
+        set_setting(&mut config, "server_url", "https://onyx.example.com").unwrap();
+        assert_eq!(config.server_url, "https://onyx.example.com");
+        set_setting(&mut config, "lock.after_minutes", "15").unwrap();
+        assert_eq!(config.lock.after_minutes, 15);
+        assert_eq!(get_setting(&config, "lock.after_minutes").unwrap(), "15");
+        set_setting(&mut config, "spellcheck.languages", r#"["en-US", "de"]"#).unwrap();
+        assert_eq!(config.spellcheck.languages, vec!["en-US", "de"]);
+
+        assert!(set_setting(&mut config, "lock.after_minutes", "soon").is_err());
+        assert!(set_setting(&mut config, "no_such.setting", "1").is_err());
+        assert!(set_setting(&mut config, "lock.no_such_setting", "1").is_err());
+        assert!(get_setting(&config, "lock.no_such_setting").is_err());
+
+        let ask = Command::Ask {
+            question: vec!["What's".to_string(), "new?".to_string()],
+        };
+        let sent = serde_json::to_string(&ask.message()).unwrap();
+        assert_eq!(sent, r#"{"command":"ask","query":"What's new?"}"#);
+        assert_eq!(
+            serde_json::from_str::<Message>(&sent).unwrap().route(),
+            Some(Route::Search {
+                query: "What's new?".to_string()
+            })
+        );
+    }
+}
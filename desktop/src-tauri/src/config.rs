@@ -2,18 +2,46 @@
 // exists, so failures here go straight to stderr instead.
 #![allow(clippy::print_stderr)]
 
+use crate::config_format::{ConfigFormat, JSON_CONFIG_FILE_NAME, TOML_CONFIG_FILE_NAME};
+use crate::install_scope::managed_policy;
+use crate::secrets::SecretString;
+use crate::window::STANDARD_WINDOW;
 use directories::ProjectDirs;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Mutex, RwLock};
+use std::time::Duration;
 use url::Url;
 
 pub const DEFAULT_SERVER_URL: &str = "https://cloud.onyx.app";
-const CONFIG_FILE_NAME: &str = "config.json";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Schema version written to every saved config. Bump it together with a new
+/// entry in `MIGRATIONS` whenever a field is renamed, restructured, or has its
+/// meaning changed -- additive fields with a `#[serde(default)]` don't need one.
+pub const CURRENT_CONFIG_VERSION: u32 = 1;
+
+/// The first schema version. Files written before versioning existed have no
+/// `version` key and share its layout.
+const FIRST_CONFIG_VERSION: u32 = 1;
+
+/// `MIGRATIONS[n]` upgrades a config object from version
+/// `FIRST_CONFIG_VERSION + n` to the one after it.
+type Migration = fn(&mut Map<String, Value>);
+const MIGRATIONS: [Migration; (CURRENT_CONFIG_VERSION - FIRST_CONFIG_VERSION) as usize] = [];
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+// Plain on/off settings are flat booleans in `config.toml`.
+#[allow(clippy::struct_excessive_bools)]
 pub struct AppConfig {
+    /// Files written before versioning existed have no `version` key and are
+    /// treated as `FIRST_CONFIG_VERSION`.
+    #[serde(default)]
+    pub version: u32,
+
     pub server_url: String,
 
     #[serde(default = "default_window_title")]
@@ -24,6 +52,845 @@ pub struct AppConfig {
 
     #[serde(default)]
     pub hide_window_decorations: bool,
+
+    /// Whether new windows float above other apps' windows. Each window can
+    /// still be toggled on its own.
+    #[serde(default)]
+    pub always_on_top: bool,
+
+    /// Whether focus mode (see `focus_mode.rs`) also makes the window
+    /// fullscreen.
+    #[serde(default)]
+    pub focus_mode_fullscreen: bool,
+
+    /// Admin mode for the default profile (see `admin.rs`): the main window
+    /// opens the indexing status page and connector failures are reported.
+    #[serde(default)]
+    pub admin_mode: bool,
+
+    /// Whether the cheat sheet, the push-to-talk indicator and the mini chat
+    /// are kept inside what the system's screen magnifier shows (see
+    /// `magnifier.rs`).
+    #[serde(default = "default_follow_magnifier")]
+    pub follow_magnifier: bool,
+
+    /// The UI language, such as `de-DE` (see `locale.rs`); unset follows the
+    /// system.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+
+    /// What closing a chat window does.
+    #[serde(default)]
+    pub close_behavior: BackgroundBehavior,
+
+    /// What minimizing a chat window does.
+    #[serde(default)]
+    pub minimize_behavior: BackgroundBehavior,
+
+    /// Whether Onyx starts when the user logs in (see `autostart.rs`).
+    #[serde(default)]
+    pub launch_at_login: bool,
+
+    /// Names of more accounts on `server_url`, each logged in with its own
+    /// webview data next to the usual login (see `accounts.rs`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accounts: Vec<String>,
+
+    /// Whether Explorer's file menu has "Ask Onyx about This File" and
+    /// "Upload to Onyx" (see `file_menu.rs`). Windows only.
+    #[serde(default)]
+    pub file_context_menu: bool,
+
+    /// Settings the app can't change, by their path in the config (one of
+    /// `LOCKABLE`), for deployments that pin them. Managed policy can lock
+    /// more (see `install_scope.rs`). The list itself can't be changed from
+    /// the app either.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locked_fields: Vec<String>,
+
+    /// Where to fetch settings the organization manages from, and the
+    /// base64 Ed25519 public key they're signed with (see
+    /// `managed_config.rs`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub managed_config_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub managed_config_key: Option<String>,
+
+    // Grouped settings are tables in `config.toml`, so they have to come
+    // after every plain value above.
+    #[serde(default)]
+    pub shortcuts: ShortcutSettings,
+
+    #[serde(default)]
+    pub notifications: NotificationSettings,
+
+    #[serde(default)]
+    pub proxy: ProxySettings,
+
+    /// The certificate to present to a server (or the gateway in front of
+    /// it) that asks for one (see `client_cert.rs`).
+    #[serde(default)]
+    pub client_certificate: ClientCertificateSettings,
+
+    /// Certificates the configured server's chain has to include (see
+    /// `cert_pinning.rs`).
+    #[serde(default)]
+    pub tls: TlsSettings,
+
+    /// How windows sign in to a proxy in front of the server (see `auth.rs`).
+    #[serde(default)]
+    pub auth: AuthSettings,
+
+    #[serde(default)]
+    pub updates: UpdateSettings,
+
+    /// Window translucency (see `window::apply_appearance`).
+    #[serde(default)]
+    pub appearance: AppearanceSettings,
+
+    /// The size of chat windows and where new ones open (see
+    /// `window::build_and_setup_window`).
+    #[serde(default)]
+    pub window: WindowSettings,
+
+    /// Spellchecking in the page's text fields (see `spellcheck.rs`).
+    #[serde(default)]
+    pub spellcheck: SpellcheckSettings,
+
+    /// Push-to-talk voice input (see `push_to_talk.rs`).
+    #[serde(default)]
+    pub voice: VoiceSettings,
+
+    /// Locking the app when it isn't used (see `lock.rs`).
+    #[serde(default)]
+    pub lock: LockSettings,
+
+    /// Running as a kiosk on a shared terminal (see `kiosk.rs`).
+    #[serde(default)]
+    pub kiosk: KioskSettings,
+
+    /// Sending crash reports (see `crash_report.rs`).
+    #[serde(default)]
+    pub crash_reports: CrashReportSettings,
+
+    /// The local HTTP API for scripts and launchers (see
+    /// `automation_api.rs`).
+    #[serde(default)]
+    pub automation_api: AutomationApiSettings,
+
+    /// Local folders shared with the server through a file connector (see
+    /// `local_files.rs`).
+    #[serde(default)]
+    pub local_files: LocalFilesSettings,
+
+    /// Keeping recent chats on this machine to read while offline (see
+    /// `offline_cache.rs`).
+    #[serde(default)]
+    pub offline_cache: OfflineCacheSettings,
+
+    /// Keeping the text copied lately, in memory, to insert into a chat (see
+    /// `clipboard_history.rs`).
+    #[serde(default)]
+    pub clipboard_history: ClipboardHistorySettings,
+
+    /// Reading answers aloud (see `read_aloud.rs`).
+    #[serde(default)]
+    pub read_aloud: ReadAloudSettings,
+
+    /// Prompt snippets to insert into the chat box (see `snippets.rs`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub snippets: Vec<Snippet>,
+
+    /// Prompts asked, or reminders shown, on a schedule (see
+    /// `scheduler.rs`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedules: Vec<ScheduledPrompt>,
+
+    /// Extra HTTP headers sent with every request to `server_url`'s origin,
+    /// for access proxies (see `server_headers.rs`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, SecretString>,
+
+    /// Named servers a window can be opened against (`new_window` with a
+    /// `profile`), each with its own webview data.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProfileSettings>,
+
+    /// Saved webview zoom factors (see `zoom.rs`); 100% isn't stored.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub zoom: BTreeMap<String, f64>,
+
+    /// Remembered consent decisions (see `consent.rs`), by webview partition
+    /// and then by capability and scope.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub permissions: BTreeMap<String, BTreeMap<String, PermissionGrant>>,
+}
+
+/// What closing or minimizing a window does, so the app can keep running in
+/// the background.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BackgroundBehavior {
+    /// The platform default: closing the last window quits the app, and
+    /// minimizing goes to the taskbar or Dock.
+    #[default]
+    Quit,
+    /// Hide the window and keep running; the tray icon (or, on macOS, the
+    /// Dock icon) brings it back.
+    Hide,
+    /// Like `Hide`, but on macOS the Dock icon goes too while no window is
+    /// showing, leaving only the menu-bar icon.
+    Tray,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProfileSettings {
+    pub server_url: String,
+    /// Overrides `appearance.titlebar` for this profile's windows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub titlebar: Option<TitlebarMode>,
+    /// Overrides `admin_mode` for this profile's windows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_mode: Option<bool>,
+    /// Like `accounts`, on this profile's server.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accounts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionDecision {
+    Allow,
+    Deny,
+}
+
+/// A remembered decision and when it was made.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PermissionGrant {
+    pub decision: PermissionDecision,
+    /// UTC, as `format_utc_timestamp` writes it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_at: Option<String>,
+}
+
+/// A named piece of prompt text (see `snippets.rs`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Snippet {
+    pub name: String,
+    /// Typed as `;;keyword` in the chat box to expand the snippet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyword: Option<String>,
+    pub text: String,
+}
+
+/// A prompt run at set times (see `scheduler.rs`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ScheduledPrompt {
+    pub name: String,
+    /// When, in cron's five fields (minute, hour, day of month, month, day
+    /// of week) in local time, such as `0 9 * * 1-5`.
+    pub schedule: String,
+    pub prompt: String,
+    /// Only remind: offer the prompt in a new chat rather than asking it.
+    #[serde(default)]
+    pub remind_only: bool,
+}
+
+/// Menu accelerators, in Tauri's accelerator syntax (`CmdOrCtrl+Shift+N`).
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ShortcutSettings {
+    pub new_chat: String,
+    pub new_window: String,
+    pub open_settings: String,
+    pub always_on_top: String,
+    pub mini_chat: String,
+    /// Turns focus mode on and off (see `focus_mode.rs`).
+    pub focus_mode: String,
+    /// Opens the snippet picker (see `snippets.rs`).
+    pub insert_snippet: String,
+    /// Starts and stops dictation into the chat box (see `dictation.rs`).
+    pub dictation: String,
+    /// How a shortcut's key is found on the keyboard (see
+    /// `shortcut_keys.rs`).
+    pub key_matching: KeyMatching,
+}
+
+/// Which key a shortcut like `CmdOrCtrl+[` means.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyMatching {
+    /// Whichever key types the character in the current layout, falling
+    /// back to the key in its US position when the layout doesn't type it
+    /// (Cyrillic letters, AZERTY's brackets).
+    #[default]
+    Character,
+    /// The key in the character's US position, whatever the layout types
+    /// there.
+    Position,
+}
+
+impl Default for ShortcutSettings {
+    fn default() -> Self {
+        Self {
+            new_chat: "CmdOrCtrl+N".to_string(),
+            new_window: "CmdOrCtrl+Shift+N".to_string(),
+            open_settings: "CmdOrCtrl+Comma".to_string(),
+            always_on_top: "CmdOrCtrl+Shift+T".to_string(),
+            mini_chat: "CmdOrCtrl+Shift+M".to_string(),
+            focus_mode: "CmdOrCtrl+Shift+F".to_string(),
+            insert_snippet: "CmdOrCtrl+Shift+Semicolon".to_string(),
+            dictation: "CmdOrCtrl+Shift+D".to_string(),
+            key_matching: KeyMatching::default(),
+        }
+    }
+}
+
+impl ShortcutSettings {
+    /// The shortcuts as this platform should bind them: off macOS, `Cmd`
+    /// and `Super` mean Ctrl, since they'd otherwise need the Windows key
+    /// (and clash with the system's own shortcuts).
+    pub fn for_platform(&self) -> Self {
+        let macos = cfg!(target_os = "macos");
+        Self {
+            new_chat: accelerator_for(&self.new_chat, macos),
+            new_window: accelerator_for(&self.new_window, macos),
+            open_settings: accelerator_for(&self.open_settings, macos),
+            always_on_top: accelerator_for(&self.always_on_top, macos),
+            mini_chat: accelerator_for(&self.mini_chat, macos),
+            focus_mode: accelerator_for(&self.focus_mode, macos),
+            insert_snippet: accelerator_for(&self.insert_snippet, macos),
+            dictation: accelerator_for(&self.dictation, macos),
+            key_matching: self.key_matching,
+        }
+    }
+}
+
+/// `accelerator` with Command-key modifiers turned into Ctrl, unless on
+/// macOS.
+fn accelerator_for(accelerator: &str, macos: bool) -> String {
+    if macos {
+        return accelerator.to_string();
+    }
+    let mut parts: Vec<&str> = Vec::new();
+    for part in accelerator.split('+') {
+        let part = match part.trim().to_ascii_lowercase().as_str() {
+            "cmd" | "command" | "super" | "meta" => "Ctrl",
+            _ => part,
+        };
+        if !(part.eq_ignore_ascii_case("ctrl")
+            && parts.iter().any(|p| p.eq_ignore_ascii_case("ctrl")))
+        {
+            parts.push(part);
+        }
+    }
+    parts.join("+")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    /// Count unread server notifications on the Dock or taskbar icon.
+    pub unread_badge: bool,
+    /// What keeps notifications for the tray's Notifications menu rather
+    /// than showing them (see `do_not_disturb.rs`).
+    pub hold: BTreeSet<HoldNotifications>,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            unread_badge: true,
+            hold: BTreeSet::from([HoldNotifications::SystemFocus]),
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldNotifications {
+    /// The app's own Do Not Disturb, turned on from the tray or Settings.
+    DoNotDisturb,
+    /// The system's Focus or Do Not Disturb.
+    SystemFocus,
+}
+
+/// Proxy for requests the app makes itself (reachability checks, version
+/// lookups). The webview keeps following the system proxy settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ProxySettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct TlsSettings {
+    /// SHA-256 fingerprints, in hex (colons optional), of certificates the
+    /// server's chain has to include: its own, or the CA that issues it.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pinned_sha256: Vec<String>,
+    /// Fingerprints of certificates to accept although the system doesn't
+    /// trust them (self-signed ones); added by "Trust This Certificate".
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub trusted_sha256: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct AuthSettings {
+    /// Let the configured servers' proxies sign windows in with the Windows
+    /// account (Kerberos, or NTLM off the domain). Read at startup.
+    pub integrated_windows_auth: bool,
+}
+
+/// Either a PKCS#12 file or the name of a certificate already in the OS
+/// store; at most one of them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ClientCertificateSettings {
+    /// A `.p12`/`.pfx` file holding the certificate and its private key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// The file's password, if it has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<SecretString>,
+    /// The name of a certificate in the macOS Keychain or the Windows
+    /// certificate store (its common name, or its friendly name on Windows).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keychain: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct UpdateSettings {
+    pub check_automatically: bool,
+    /// Use signed script overrides from the config directory (see
+    /// `assets.rs`).
+    pub script_hotfixes: bool,
+}
+
+impl Default for UpdateSettings {
+    fn default() -> Self {
+        Self {
+            check_automatically: true,
+            script_hotfixes: true,
+        }
+    }
+}
+
+/// The window background behind the page. Linux windows are always opaque.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct AppearanceSettings {
+    /// macOS only.
+    pub vibrancy: VibrancyMaterial,
+    /// Windows only.
+    pub backdrop: WindowsBackdrop,
+    /// Whether the window lets the desktop show through at all. When off,
+    /// windows get a solid background in the system theme and no vibrancy
+    /// or backdrop.
+    pub transparent: bool,
+    /// Rounds the vibrancy layer's corners, in points.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub corner_radius: Option<f64>,
+    /// Window chrome for the default profile and ad-hoc server windows.
+    pub titlebar: TitlebarMode,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct SpellcheckSettings {
+    pub enabled: bool,
+    /// Dictionaries to check against, such as `en_US` or `de_DE`; empty
+    /// follows the system. Windows always uses the system's languages, and
+    /// macOS checks against the first one it has.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub languages: Vec<String>,
+}
+
+impl Default for SpellcheckSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            languages: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct VoiceSettings {
+    /// Held anywhere, even with Onyx in the background, to speak a question
+    /// (`CmdOrCtrl+Shift+Space`); empty leaves it off.
+    pub push_to_talk: String,
+    /// A local Whisper server to send dictation to instead of the Onyx
+    /// server, such as whisper.cpp's `http://127.0.0.1:8080/inference`.
+    pub dictation_endpoint: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct LockSettings {
+    /// Minutes without use before every window is locked behind the PIN;
+    /// 0 never locks.
+    pub after_minutes: u32,
+    /// Whether Touch ID or Windows Hello can stand in for the PIN, where
+    /// the system has it.
+    pub biometric: bool,
+}
+
+impl Default for LockSettings {
+    fn default() -> Self {
+        Self {
+            after_minutes: 0,
+            biometric: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct KioskSettings {
+    /// Start in kiosk mode, as `--kiosk` does.
+    pub enabled: bool,
+    /// Minutes without use before a kiosk starts over with a new chat; 0
+    /// never does.
+    pub reset_after_minutes: u32,
+}
+
+impl Default for KioskSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reset_after_minutes: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct CrashReportSettings {
+    /// Whether the user agreed to send crash reports to `endpoint`; they're
+    /// only kept on this machine until then.
+    pub upload: bool,
+    /// Where crash reports go: an HTTPS URL that takes a multipart POST.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct AutomationApiSettings {
+    /// Whether the API listens at all.
+    pub enabled: bool,
+    /// The port it listens on, on 127.0.0.1.
+    pub port: u16,
+    /// What callers send as `Authorization: Bearer <token>`; made up when
+    /// the API is first turned on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<SecretString>,
+}
+
+impl Default for AutomationApiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 47_821,
+            token: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct LocalFilesSettings {
+    /// Whether the folders are synced at all.
+    pub enabled: bool,
+    /// The folders Onyx may read, as absolute paths; nothing outside them
+    /// is read.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub folders: Vec<String>,
+    /// Which files in them are uploaded, as globs on their path inside the
+    /// folder (`**/*.md`); every file when empty.
+    pub include: Vec<String>,
+    /// Files left out even if they're included.
+    pub exclude: Vec<String>,
+    /// Files bigger than this many megabytes are left out.
+    pub max_file_mb: u64,
+    /// The name of the file connector on the server `folders` go to.
+    pub connector: String,
+    /// More folders, each going to the file connector in a document set.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub watch: Vec<WatchedFolder>,
+    /// Only list what a sync would upload and take out, in Sync Activity
+    /// and the debug log, without touching the server's connectors: to try
+    /// out the folders and globs before sharing anything for real.
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct WatchedFolder {
+    /// The folder, as an absolute path.
+    pub folder: String,
+    /// The name of the document set its files go to, which needs a file
+    /// connector in it.
+    pub document_set: String,
+}
+
+impl Default for LocalFilesSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            folders: Vec::new(),
+            include: [
+                "**/*.md",
+                "**/*.txt",
+                "**/*.pdf",
+                "**/*.docx",
+                "**/*.pptx",
+                "**/*.xlsx",
+                "**/*.csv",
+                "**/*.html",
+            ]
+            .map(str::to_string)
+            .to_vec(),
+            exclude: ["**/.*/**", "**/node_modules/**"]
+                .map(str::to_string)
+                .to_vec(),
+            max_file_mb: 20,
+            connector: "My Desktop Files".to_string(),
+            watch: Vec::new(),
+            dry_run: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct OfflineCacheSettings {
+    /// Whether recent chats are kept at all.
+    pub enabled: bool,
+    /// How many of the most recent chats are kept, up to 100.
+    pub chats: usize,
+}
+
+impl Default for OfflineCacheSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            chats: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ClipboardHistorySettings {
+    /// Whether copied text is kept at all.
+    pub enabled: bool,
+    /// How many of the latest copies are kept, up to 100.
+    pub items: usize,
+}
+
+impl Default for ClipboardHistorySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            items: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ReadAloudSettings {
+    /// The voice's name, or `None` for the system's default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voice: Option<String>,
+    /// How fast, as a percentage of normal speed (50 to 200).
+    pub rate: u32,
+}
+
+impl Default for ReadAloudSettings {
+    fn default() -> Self {
+        Self {
+            voice: None,
+            rate: 100,
+        }
+    }
+}
+
+/// How a chat window's titlebar is drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TitlebarMode {
+    /// On macOS, a titlebar injected into the page under the traffic
+    /// lights; elsewhere the same as `Native`, unless Linux decorations are
+    /// hidden.
+    #[default]
+    Overlay,
+    /// The system's own titlebar and window controls, with no page
+    /// injection; on Linux this keeps decorations even when they're hidden.
+    Native,
+}
+
+impl Default for AppearanceSettings {
+    fn default() -> Self {
+        Self {
+            vibrancy: VibrancyMaterial::Sidebar,
+            backdrop: WindowsBackdrop::Mica,
+            transparent: true,
+            corner_radius: None,
+            titlebar: TitlebarMode::Overlay,
+        }
+    }
+}
+
+/// The size of chat windows, in logical pixels, and where new ones open.
+/// The main window starts at this size the first time; after that it comes
+/// back as it was left.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct WindowSettings {
+    pub width: f64,
+    pub height: f64,
+    pub min_width: f64,
+    pub min_height: f64,
+    pub position: WindowPosition,
+    /// What's different on macOS, for a config shared between platforms.
+    #[serde(skip_serializing_if = "WindowOverrides::is_empty")]
+    pub macos: WindowOverrides,
+    /// What's different on Windows.
+    #[serde(skip_serializing_if = "WindowOverrides::is_empty")]
+    pub windows: WindowOverrides,
+    /// What's different on Linux.
+    #[serde(skip_serializing_if = "WindowOverrides::is_empty")]
+    pub linux: WindowOverrides,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            width: STANDARD_WINDOW.width,
+            height: STANDARD_WINDOW.height,
+            min_width: STANDARD_WINDOW.min_width,
+            min_height: STANDARD_WINDOW.min_height,
+            position: WindowPosition::System,
+            macos: WindowOverrides::default(),
+            windows: WindowOverrides::default(),
+            linux: WindowOverrides::default(),
+        }
+    }
+}
+
+impl WindowSettings {
+    /// These settings with `overrides` laid over them.
+    fn overridden(&self, overrides: &WindowOverrides) -> Self {
+        Self {
+            width: overrides.width.unwrap_or(self.width),
+            height: overrides.height.unwrap_or(self.height),
+            min_width: overrides.min_width.unwrap_or(self.min_width),
+            min_height: overrides.min_height.unwrap_or(self.min_height),
+            position: overrides.position.unwrap_or(self.position),
+            macos: WindowOverrides::default(),
+            windows: WindowOverrides::default(),
+            linux: WindowOverrides::default(),
+        }
+    }
+
+    /// The settings as they are on this platform.
+    pub fn for_this_platform(&self) -> Self {
+        #[cfg(target_os = "macos")]
+        let overrides = &self.macos;
+        #[cfg(target_os = "windows")]
+        let overrides = &self.windows;
+        #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+        let overrides = &self.linux;
+        self.overridden(overrides)
+    }
+
+    /// The settings as they are on each platform, by its name.
+    fn per_platform(&self) -> [(&'static str, Self); 3] {
+        [
+            ("macOS", self.overridden(&self.macos)),
+            ("Windows", self.overridden(&self.windows)),
+            ("Linux", self.overridden(&self.linux)),
+        ]
+    }
+}
+
+/// `WindowSettings` for one platform; what isn't set is as it is for all.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct WindowOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_width: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_height: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<WindowPosition>,
+}
+
+impl WindowOverrides {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Where a new chat window opens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowPosition {
+    /// Wherever the system puts it.
+    #[default]
+    System,
+    /// Centered on the main display.
+    Center,
+    /// Just below and to the right of where the window used last is, or
+    /// was, so it doesn't cover it exactly.
+    LastUsed,
+    /// Centered on the display the mouse pointer is on.
+    CursorDisplay,
+}
+
+/// The `NSVisualEffectMaterial` behind the page, or `none` for no vibrancy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VibrancyMaterial {
+    None,
+    Sidebar,
+    Titlebar,
+    Menu,
+    Popover,
+    HeaderView,
+    Sheet,
+    WindowBackground,
+    HudWindow,
+    ContentBackground,
+    UnderWindowBackground,
+    UnderPageBackground,
+}
+
+/// The system backdrop behind the page on Windows, or `none` for an opaque
+/// window. Mica and tabbed need Windows 11; acrylic works on 10 too. Where
+/// the chosen one isn't available the window falls back to opaque.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowsBackdrop {
+    None,
+    Mica,
+    Tabbed,
+    Acrylic,
 }
 
 fn default_window_title() -> String {
@@ -34,25 +901,560 @@ const fn default_show_menu_bar() -> bool {
     true
 }
 
+const fn default_follow_magnifier() -> bool {
+    true
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
+            version: CURRENT_CONFIG_VERSION,
             server_url: DEFAULT_SERVER_URL.to_string(),
             window_title: default_window_title(),
             show_menu_bar: true,
             hide_window_decorations: false,
+            always_on_top: false,
+            focus_mode_fullscreen: false,
+            admin_mode: false,
+            follow_magnifier: true,
+            locale: None,
+            close_behavior: BackgroundBehavior::Quit,
+            minimize_behavior: BackgroundBehavior::Quit,
+            launch_at_login: false,
+            accounts: Vec::new(),
+            file_context_menu: false,
+            locked_fields: Vec::new(),
+            managed_config_url: None,
+            managed_config_key: None,
+            shortcuts: ShortcutSettings::default(),
+            notifications: NotificationSettings::default(),
+            proxy: ProxySettings::default(),
+            client_certificate: ClientCertificateSettings::default(),
+            tls: TlsSettings::default(),
+            auth: AuthSettings::default(),
+            updates: UpdateSettings::default(),
+            appearance: AppearanceSettings::default(),
+            window: WindowSettings::default(),
+            spellcheck: SpellcheckSettings::default(),
+            voice: VoiceSettings::default(),
+            lock: LockSettings::default(),
+            kiosk: KioskSettings::default(),
+            crash_reports: CrashReportSettings::default(),
+            automation_api: AutomationApiSettings::default(),
+            local_files: LocalFilesSettings::default(),
+            offline_cache: OfflineCacheSettings::default(),
+            clipboard_history: ClipboardHistorySettings::default(),
+            read_aloud: ReadAloudSettings::default(),
+            snippets: Vec::new(),
+            schedules: Vec::new(),
+            headers: BTreeMap::new(),
+            profiles: BTreeMap::new(),
+            zoom: BTreeMap::new(),
+            permissions: BTreeMap::new(),
         }
     }
 }
 
-/// Get the config directory path
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("app", "onyx", "onyx-desktop")
+}
+
+/// Get the config directory path: the portable `data/` folder if there is
+/// one, otherwise the per-user OS config directory (`$XDG_CONFIG_HOME` on
+/// Linux). Only settings belong here -- see `get_data_dir`/`get_state_dir`.
 pub fn get_config_dir() -> Option<PathBuf> {
-    ProjectDirs::from("app", "onyx", "onyx-desktop").map(|dirs| dirs.config_dir().to_path_buf())
+    if let Some(data_dir) = crate::portable::portable_data_dir() {
+        return Some(data_dir.to_path_buf());
+    }
+    project_dirs().map(|dirs| dirs.config_dir().to_path_buf())
+}
+
+/// Where bulky app data (per-profile webview data) lives: `$XDG_DATA_HOME`
+/// on Linux, the non-roaming app data folder on Windows. (macOS partitions
+/// webview data by identifier instead of by directory.)
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+pub fn get_data_dir() -> Option<PathBuf> {
+    if let Some(data_dir) = crate::portable::portable_data_dir() {
+        return Some(data_dir.to_path_buf());
+    }
+    project_dirs().map(|dirs| dirs.data_local_dir().to_path_buf())
+}
+
+/// Where logs live: `$XDG_STATE_HOME` on Linux. Other platforms have no
+/// separate state location, so logs stay next to the config there.
+pub fn get_state_dir() -> Option<PathBuf> {
+    if let Some(data_dir) = crate::portable::portable_data_dir() {
+        return Some(data_dir.to_path_buf());
+    }
+    let dirs = project_dirs()?;
+    Some(
+        dirs.state_dir()
+            .unwrap_or_else(|| dirs.config_dir())
+            .to_path_buf(),
+    )
 }
 
-/// Get the full config file path
+#[cfg(not(target_os = "macos"))]
+type DirResolver = fn() -> Option<PathBuf>;
+
+/// Entries older builds kept in the config directory, and the directory
+/// each one belongs in now. (Windows has no separate place for logs, so
+/// only webview data moves there.)
+#[cfg(not(target_os = "macos"))]
+const LEGACY_ENTRIES: [(&str, DirResolver); 2] = [
+    ("frontend_debug.log", get_state_dir),
+    ("partitions", get_data_dir),
+];
+
+/// One-time move of logs and webview data out of the config directory
+/// (`$XDG_CONFIG_HOME` on Linux, the roaming app data folder on Windows),
+/// where older builds put everything, so it only holds settings. Anything
+/// that can't be moved is left where it is (and stops being used).
+#[cfg(not(target_os = "macos"))]
+pub fn migrate_legacy_layout() {
+    let Some(config_dir) = get_config_dir() else {
+        return;
+    };
+
+    for (name, target_dir) in LEGACY_ENTRIES {
+        let old_path = config_dir.join(name);
+        let Some(target_dir) = target_dir() else {
+            continue;
+        };
+        let new_path = target_dir.join(name);
+        if old_path == new_path || !old_path.exists() || new_path.exists() {
+            continue;
+        }
+
+        let result =
+            fs::create_dir_all(&target_dir).and_then(|()| fs::rename(&old_path, &new_path));
+        if let Err(e) = result {
+            eprintln!(
+                "[ONYX WARN] Could not move {} to {}: {e}",
+                old_path.display(),
+                new_path.display()
+            );
+        }
+    }
+}
+
+/// Get the full config file path: `config.toml` unless only a legacy
+/// `config.json` exists, in which case that file keeps being used.
 pub fn get_config_path() -> Option<PathBuf> {
-    get_config_dir().map(|dir| dir.join(CONFIG_FILE_NAME))
+    let config_dir = get_config_dir()?;
+    let toml_path = config_dir.join(TOML_CONFIG_FILE_NAME);
+    let json_path = config_dir.join(JSON_CONFIG_FILE_NAME);
+    if !toml_path.exists() && json_path.exists() {
+        Some(json_path)
+    } else {
+        Some(toml_path)
+    }
+}
+
+/// Run every migration needed to bring `map` up to
+/// `CURRENT_CONFIG_VERSION`. Returns the version it was written at.
+pub fn migrate(map: &mut Map<String, Value>) -> Result<u32, String> {
+    let from_version = match map.get("version") {
+        None => FIRST_CONFIG_VERSION,
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| format!("invalid config version: {v}"))?,
+    };
+
+    if from_version > CURRENT_CONFIG_VERSION {
+        // Written by a newer build. Parse what we understand rather than
+        // refusing to start, but never downgrade the stored version --
+        // `write_config` won't save over it and drop what we don't know.
+        eprintln!(
+            "[ONYX WARN] Config version {from_version} is newer than supported version {CURRENT_CONFIG_VERSION}; unknown settings will be ignored and changes won't be saved"
+        );
+    } else {
+        let pending = from_version.saturating_sub(FIRST_CONFIG_VERSION) as usize;
+        for migration in &MIGRATIONS[pending..] {
+            migration(map);
+        }
+        map.insert("version".to_string(), CURRENT_CONFIG_VERSION.into());
+    }
+    Ok(from_version)
+}
+
+/// Parse raw config file contents, running every migration needed to bring
+/// it up to `CURRENT_CONFIG_VERSION`. Returns the config together with the
+/// version the file was originally written at.
+pub fn parse_and_migrate(contents: &str, format: ConfigFormat) -> Result<(AppConfig, u32), String> {
+    let Value::Object(mut map) = format.parse(contents)? else {
+        return Err("expected a table of settings at the top level".to_string());
+    };
+    let from_version = migrate(&mut map)?;
+    let config = serde_json::from_value(Value::Object(map)).map_err(|e| e.to_string())?;
+    Ok((config, from_version))
+}
+
+/// Parse a server URL, accepting only http(s).
+pub fn validate_server_url(server_url: &str) -> Result<Url, String> {
+    let url = Url::parse(server_url).map_err(|e| format!("Invalid server URL: {e}"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("Server URL must start with http:// or https://".to_string());
+    }
+    Ok(url)
+}
+
+/// Check a config submitted from the settings window before it's applied.
+pub fn validate_config(config: &AppConfig) -> Result<(), String> {
+    validate_server_url(&config.server_url)?;
+    validate_accounts(&config.accounts)?;
+
+    for (name, profile) in &config.profiles {
+        validate_server_url(&profile.server_url)
+            .and_then(|_| validate_accounts(&profile.accounts))
+            .map_err(|e| format!("Profile \"{name}\": {e}"))?;
+    }
+
+    if config.window_title.trim().is_empty() {
+        return Err("Window title cannot be empty".to_string());
+    }
+
+    let shortcuts = [
+        ("New Chat", &config.shortcuts.new_chat),
+        ("New Window", &config.shortcuts.new_window),
+        ("Settings", &config.shortcuts.open_settings),
+        ("Always on Top", &config.shortcuts.always_on_top),
+        ("Mini Chat", &config.shortcuts.mini_chat),
+        ("Focus Mode", &config.shortcuts.focus_mode),
+        ("Insert Snippet", &config.shortcuts.insert_snippet),
+        ("Dictation", &config.shortcuts.dictation),
+    ];
+    for (name, accelerator) in shortcuts {
+        if accelerator.trim().is_empty() {
+            return Err(format!("The {name} shortcut cannot be empty"));
+        }
+    }
+
+    if let Some(proxy) = &config.proxy.url {
+        let proxy = Url::parse(proxy).map_err(|e| format!("Invalid proxy URL: {e}"))?;
+        if !matches!(proxy.scheme(), "http" | "https" | "socks5" | "socks5h") {
+            return Err("Proxy URL must use http, https, or socks5".to_string());
+        }
+    }
+
+    if let Some(radius) = config.appearance.corner_radius {
+        if !radius.is_finite() || radius < 0.0 {
+            return Err("Corner radius cannot be negative".to_string());
+        }
+    }
+
+    for (platform, window) in config.window.per_platform() {
+        validate_window_size(&window).map_err(|e| format!("{e} (on {platform})"))?;
+    }
+
+    if let Some(language) = config
+        .spellcheck
+        .languages
+        .iter()
+        .find(|language| !is_language_code(language))
+    {
+        return Err(format!("\"{language}\" isn't a language code like en_US"));
+    }
+    if let Some(locale) = config.locale.as_deref().filter(|l| !is_language_code(l)) {
+        return Err(format!("\"{locale}\" isn't a locale like de-DE"));
+    }
+
+    if let Some(field) = config
+        .locked_fields
+        .iter()
+        .find(|field| !LOCKABLE.iter().any(|setting| setting.path == **field))
+    {
+        return Err(format!(
+            "\"{field}\" in locked_fields isn't a setting that can be locked"
+        ));
+    }
+
+    Ok(())
+}
+
+fn validate_window_size(window: &WindowSettings) -> Result<(), String> {
+    let sizes = [
+        ("width", window.width),
+        ("height", window.height),
+        ("minimum width", window.min_width),
+        ("minimum height", window.min_height),
+    ];
+    if let Some((name, _)) = sizes
+        .iter()
+        .find(|(_, size)| !size.is_finite() || *size <= 0.0)
+    {
+        return Err(format!("The window {name} must be more than 0"));
+    }
+    if window.min_width > window.width || window.min_height > window.height {
+        return Err("The window can't be smaller than its minimum size".to_string());
+    }
+    Ok(())
+}
+
+/// A setting a deployment can lock (see `locked_fields`).
+pub struct Lockable {
+    /// Its path in the config.
+    pub path: &'static str,
+    /// What it's called in errors.
+    name: &'static str,
+    /// Whether two configs disagree on it.
+    differs: fn(&AppConfig, &AppConfig) -> bool,
+    /// Copy it from the second config into the first.
+    keep: fn(&mut AppConfig, &AppConfig),
+}
+
+pub const LOCKABLE: &[Lockable] = &[
+    Lockable {
+        path: "server_url",
+        name: "The server URL",
+        differs: |a, b| a.server_url != b.server_url,
+        keep: |to, from| to.server_url.clone_from(&from.server_url),
+    },
+    Lockable {
+        path: "window_title",
+        name: "The window title",
+        differs: |a, b| a.window_title != b.window_title,
+        keep: |to, from| to.window_title.clone_from(&from.window_title),
+    },
+    Lockable {
+        path: "admin_mode",
+        name: "Admin mode",
+        differs: |a, b| a.admin_mode != b.admin_mode,
+        keep: |to, from| to.admin_mode = from.admin_mode,
+    },
+    Lockable {
+        path: "accounts",
+        name: "The list of accounts",
+        differs: |a, b| a.accounts != b.accounts,
+        keep: |to, from| to.accounts.clone_from(&from.accounts),
+    },
+    Lockable {
+        path: "proxy.url",
+        name: "The proxy",
+        differs: |a, b| a.proxy.url != b.proxy.url,
+        keep: |to, from| to.proxy.url.clone_from(&from.proxy.url),
+    },
+    Lockable {
+        path: "updates.check_automatically",
+        name: "Checking for updates",
+        differs: |a, b| a.updates.check_automatically != b.updates.check_automatically,
+        keep: |to, from| to.updates.check_automatically = from.updates.check_automatically,
+    },
+    Lockable {
+        path: "lock.after_minutes",
+        name: "Auto-lock",
+        differs: |a, b| a.lock.after_minutes != b.lock.after_minutes,
+        keep: |to, from| to.lock.after_minutes = from.lock.after_minutes,
+    },
+    Lockable {
+        path: "kiosk.enabled",
+        name: "Kiosk mode",
+        differs: |a, b| a.kiosk.enabled != b.kiosk.enabled,
+        keep: |to, from| to.kiosk.enabled = from.kiosk.enabled,
+    },
+    Lockable {
+        path: "crash_reports.upload",
+        name: "Sending crash reports",
+        differs: |a, b| a.crash_reports.upload != b.crash_reports.upload,
+        keep: |to, from| to.crash_reports.upload = from.crash_reports.upload,
+    },
+    Lockable {
+        path: "automation_api.enabled",
+        name: "The automation API",
+        differs: |a, b| a.automation_api.enabled != b.automation_api.enabled,
+        keep: |to, from| to.automation_api.enabled = from.automation_api.enabled,
+    },
+    Lockable {
+        path: "local_files.enabled",
+        name: "Sharing local files",
+        differs: |a, b| a.local_files.enabled != b.local_files.enabled,
+        keep: |to, from| to.local_files.enabled = from.local_files.enabled,
+    },
+    Lockable {
+        path: "managed_config_url",
+        name: "The managed config",
+        differs: |a, b| {
+            a.managed_config_url != b.managed_config_url
+                || a.managed_config_key != b.managed_config_key
+        },
+        keep: |to, from| {
+            to.managed_config_url.clone_from(&from.managed_config_url);
+            to.managed_config_key.clone_from(&from.managed_config_key);
+        },
+    },
+];
+
+/// The settings locked in `config`: its `locked_fields`, what managed
+/// policy locks, and what managed policy sets.
+pub fn locked_settings(config: &AppConfig) -> Vec<&'static Lockable> {
+    let policy = managed_policy();
+    let managed = policy.managed_settings();
+    LOCKABLE
+        .iter()
+        .filter(|setting| {
+            config
+                .locked_fields
+                .iter()
+                .any(|field| field == setting.path)
+                || policy
+                    .locked_fields
+                    .iter()
+                    .any(|field| field == setting.path)
+                || managed.contains(&setting.path)
+        })
+        .collect()
+}
+
+/// Refuse a change from `previous` to `config` that touches a locked
+/// setting.
+pub fn check_locked(previous: &AppConfig, config: &AppConfig) -> Result<(), String> {
+    if config.locked_fields != previous.locked_fields {
+        return Err("locked_fields can't be changed from the app".to_string());
+    }
+    locked_settings(previous)
+        .into_iter()
+        .find(|setting| (setting.differs)(previous, config))
+        .map_or(Ok(()), |setting| {
+            Err(format!(
+                "{} is set by your organization and can't be changed",
+                setting.name
+            ))
+        })
+}
+
+/// Carry `previous`'s locked settings, and the list of them, over into
+/// `config`.
+pub fn keep_locked(config: &mut AppConfig, previous: &AppConfig) {
+    for setting in locked_settings(previous) {
+        (setting.keep)(config, previous);
+    }
+    config.locked_fields.clone_from(&previous.locked_fields);
+}
+
+/// Carry what only the app itself changes -- the schema version, zoom
+/// levels, remembered permissions and the automation token -- from
+/// `current` over into `config`, a whole config from outside (Settings, a
+/// backup). They may have moved on since it was made, and a permission
+/// revoked since mustn't come back with it.
+pub fn keep_app_state(config: &mut AppConfig, current: &AppConfig) {
+    config.version = current.version;
+    config.zoom.clone_from(&current.zoom);
+    config.permissions.clone_from(&current.permissions);
+    config
+        .automation_api
+        .token
+        .clone_from(&current.automation_api.token);
+}
+
+fn validate_accounts(accounts: &[String]) -> Result<(), String> {
+    for (i, account) in accounts.iter().enumerate() {
+        if account.trim().is_empty() {
+            return Err("Account names cannot be empty".to_string());
+        }
+        if accounts[..i].contains(account) {
+            return Err(format!("There are two accounts named \"{account}\""));
+        }
+    }
+    Ok(())
+}
+
+/// A language code like `en`, `en_US`, or `pt-BR`.
+pub fn is_language_code(code: &str) -> bool {
+    let mut parts = code.split(['_', '-']);
+    parts.next().is_some_and(|language| {
+        (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic())
+    }) && parts.all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// An HTTP client for the app's own requests, honoring the proxy setting and
+/// asking for the UI language.
+pub fn http_client(config: &AppConfig, timeout: Duration) -> Result<reqwest::Client, String> {
+    http_client_builder(config, timeout, reqwest::header::HeaderMap::new())?
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {e}"))
+}
+
+/// An HTTP client for the app's own requests to `server`, as `http_client`,
+/// that also sends the configured headers and client certificate, and
+/// checks the pinned certificates, if `server` is the configured server.
+pub fn http_client_for(
+    config: &AppConfig,
+    server: &Url,
+    timeout: Duration,
+) -> Result<reqwest::Client, String> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    for (name, value) in crate::server_headers::headers_for(config, server) {
+        headers.insert(
+            reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("\"{name}\" isn't a valid header name"))?,
+            reqwest::header::HeaderValue::from_str(&value)
+                .map_err(|_| format!("The value of header \"{name}\" isn't valid"))?,
+        );
+    }
+    let builder = http_client_builder(config, timeout, headers)?;
+    let identity = crate::client_cert::identity_pem_for(config, server)?;
+    let certificates = crate::cert_pinning::ServerCertificates::for_url(config, server);
+    let builder = if !certificates.is_empty() {
+        // Pinned or trusted certificates need a TLS setup of their own, so
+        // the client certificate has to go into that too.
+        builder.tls_backend_preconfigured(crate::cert_pinning::tls_config(
+            certificates,
+            identity.as_deref(),
+        )?)
+    } else if let Some(pem) = identity {
+        builder.identity(
+            reqwest::Identity::from_pem(pem.as_bytes())
+                .map_err(|e| format!("The client certificate can't be used: {e}"))?,
+        )
+    } else {
+        builder
+    };
+    builder
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {e}"))
+}
+
+fn http_client_builder(
+    config: &AppConfig,
+    timeout: Duration,
+    mut headers: reqwest::header::HeaderMap,
+) -> Result<reqwest::ClientBuilder, String> {
+    if let Ok(language) = reqwest::header::HeaderValue::from_str(crate::locale::locale()) {
+        headers.insert(reqwest::header::ACCEPT_LANGUAGE, language);
+    }
+    let builder = reqwest::Client::builder()
+        .timeout(timeout)
+        .default_headers(headers);
+    match &config.proxy.url {
+        Some(proxy) => Ok(builder
+            .proxy(reqwest::Proxy::all(proxy).map_err(|e| format!("Invalid proxy URL: {e}"))?)),
+        None => Ok(builder),
+    }
+}
+
+/// Copy `config_path` aside to `<file>.<suffix>` before anything overwrites
+/// it. An existing backup with the same name is kept, since it's the older
+/// (and so more original) copy.
+fn backup_config_file(config_path: &Path, suffix: &str) -> Option<PathBuf> {
+    let mut backup_name = config_path.file_name()?.to_os_string();
+    backup_name.push(format!(".{suffix}"));
+    let backup_path = config_path.with_file_name(backup_name);
+    if backup_path.exists() {
+        return Some(backup_path);
+    }
+
+    match fs::copy(config_path, &backup_path) {
+        Ok(_) => Some(backup_path),
+        Err(e) => {
+            eprintln!(
+                "[ONYX ERROR] Failed to back up config file {} to {}: {e}",
+                config_path.display(),
+                backup_path.display()
+            );
+            None
+        }
+    }
 }
 
 /// Load config from file, or create default if it doesn't exist
@@ -65,43 +1467,101 @@ pub fn load_config() -> (AppConfig, bool) {
         return (AppConfig::default(), false);
     }
 
-    match fs::read_to_string(&config_path) {
-        Ok(contents) => match serde_json::from_str(&contents) {
-            Ok(config) => (config, true),
-            Err(e) => {
-                eprintln!(
-                    "[ONYX ERROR] Failed to parse config file {}: {e}",
-                    config_path.display()
-                );
-                (AppConfig::default(), false)
-            }
-        },
+    let contents = match fs::read_to_string(&config_path) {
+        Ok(contents) => contents,
         Err(e) => {
             eprintln!(
                 "[ONYX ERROR] Failed to read config file {}: {e}",
                 config_path.display()
             );
+            return (AppConfig::default(), false);
+        }
+    };
+
+    match parse_and_migrate(&contents, ConfigFormat::from_path(&config_path)) {
+        Ok((config, from_version)) => {
+            if from_version < CURRENT_CONFIG_VERSION {
+                // Only rewrite once the original is safely copied aside.
+                if backup_config_file(&config_path, &format!("v{from_version}.bak")).is_some() {
+                    if let Err(e) = save_config(&config) {
+                        eprintln!("[ONYX ERROR] Failed to save migrated config: {e}");
+                    }
+                }
+            }
+            (config, true)
+        }
+        Err(e) => {
+            eprintln!(
+                "[ONYX ERROR] Failed to parse config file {}: {e}",
+                config_path.display()
+            );
+            // Defaults are used for this run and the next save would replace
+            // the unparseable file, so keep the user's copy around.
+            if let Some(backup_path) = backup_config_file(&config_path, "invalid.bak") {
+                eprintln!(
+                    "[ONYX ERROR] Unparseable config preserved at {}",
+                    backup_path.display()
+                );
+            }
             (AppConfig::default(), false)
         }
     }
 }
 
-/// Save config to file
+/// Save config to file, in whichever format `get_config_path` resolves to.
 pub fn save_config(config: &AppConfig) -> Result<(), String> {
+    write_config(config, false)
+}
+
+/// Save the config as `save_config` does, keeping the file it replaces in
+/// `backups/` first (see `config_backup.rs`). Only for changes the user
+/// makes -- Settings, `onyx config set`, an import or a restore -- since the
+/// app's own saves (zoom steps, consent decisions) would soon push every
+/// backup worth having out.
+pub fn save_config_backed_up(config: &AppConfig) -> Result<(), String> {
+    write_config(config, true)
+}
+
+fn write_config(config: &AppConfig, back_up: bool) -> Result<(), String> {
+    check_writable(config)?;
     let config_dir = get_config_dir().ok_or("Could not determine config directory")?;
-    let config_path = config_dir.join(CONFIG_FILE_NAME);
+    let config_path = get_config_path().ok_or("Could not determine config path")?;
 
     // Ensure config directory exists
     fs::create_dir_all(&config_dir).map_err(|e| format!("Failed to create config dir: {e}"))?;
 
-    let json = serde_json::to_string_pretty(config)
+    let existing = fs::read_to_string(&config_path).ok();
+    let contents = ConfigFormat::from_path(&config_path)
+        .render(config, existing.as_deref())
         .map_err(|e| format!("Failed to serialize config: {e}"))?;
 
-    fs::write(&config_path, json).map_err(|e| format!("Failed to write config: {e}"))?;
+    // The version being replaced goes to `backups/` first.
+    if let Some(existing) = existing
+        .as_deref()
+        .filter(|existing| back_up && *existing != contents)
+    {
+        if let Err(e) = crate::config_backup::back_up(&config_path, existing) {
+            eprintln!("[ONYX ERROR] Failed to back up config: {e}");
+        }
+    }
+
+    fs::write(&config_path, contents).map_err(|e| format!("Failed to write config: {e}"))?;
 
     Ok(())
 }
 
+/// A config written by a newer build may hold settings this one parsed
+/// away; saving it would silently drop them.
+fn check_writable(config: &AppConfig) -> Result<(), String> {
+    if config.version > CURRENT_CONFIG_VERSION {
+        return Err(format!(
+            "The config was written by a newer version of Onyx (config version {}); update Onyx to change settings",
+            config.version
+        ));
+    }
+    Ok(())
+}
+
 /// Shared app state: the live config plus a few process-lifetime flags. All
 /// fields are behind locks so this can be safely handed out as managed Tauri
 /// state and accessed from any thread/command.
@@ -116,6 +1576,9 @@ pub struct ConfigState {
     /// the last disk write doesn't match the last in-memory update (A and B
     /// both update, B saves, then A's stale snapshot saves last).
     persist_lock: Mutex<()>,
+    /// Set for `--demo` runs: changes stay in memory and never reach the
+    /// user's real config file.
+    in_memory: bool,
 }
 
 impl ConfigState {
@@ -132,9 +1595,21 @@ impl ConfigState {
             debug_mode,
             debug_log_file: Mutex::new(debug_log_file),
             persist_lock: Mutex::new(()),
+            in_memory: false,
         }
     }
 
+    /// Keep this config in memory only (see `in_memory`).
+    #[cfg(feature = "mock-server")]
+    pub const fn in_memory(mut self) -> Self {
+        self.in_memory = true;
+        self
+    }
+
+    pub const fn is_in_memory(&self) -> bool {
+        self.in_memory
+    }
+
     /// A snapshot of the current config. A panic elsewhere while holding the
     /// write lock poisons it; recovering via `into_inner` means one bad
     /// mutation can't take down every future read.
@@ -158,14 +1633,34 @@ impl ConfigState {
     /// Apply `f` and persist the result to disk as one atomic step, so a
     /// concurrent caller can't save its own update in between this update and
     /// this save (which would otherwise leave `config.json` not matching
-    /// whichever update actually happened last in memory).
+    /// whichever update actually happened last in memory). An update that
+    /// changes a locked setting (see `locked_fields`) is refused whole.
     pub fn update_and_persist(&self, f: impl FnOnce(&mut AppConfig)) -> Result<AppConfig, String> {
+        self.persist(f, false)
+    }
+
+    /// `update_and_persist` for a change the user made, which backs up the
+    /// file it replaces (see `save_config_backed_up`).
+    pub fn update_and_persist_backed_up(
+        &self,
+        f: impl FnOnce(&mut AppConfig),
+    ) -> Result<AppConfig, String> {
+        self.persist(f, true)
+    }
+
+    fn persist(&self, f: impl FnOnce(&mut AppConfig), back_up: bool) -> Result<AppConfig, String> {
         let _guard = self
             .persist_lock
             .lock()
             .unwrap_or_else(std::sync::PoisonError::into_inner);
-        let config = self.update_config(f);
-        save_config(&config)?;
+        let previous = self.config();
+        let mut updated = previous.clone();
+        f(&mut updated);
+        check_locked(&previous, &updated)?;
+        let config = self.update_config(|c| *c = updated);
+        if !self.in_memory {
+            write_config(&config, back_up)?;
+        }
         Ok(config)
     }
 
@@ -197,3 +1692,200 @@ impl ConfigState {
             .unwrap_or_else(std::sync::PoisonError::into_inner) = url;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn unversioned_config_reads_as_the_first_version() {
+        let (config, from_version) = parse_and_migrate(
+            r#"{"server_url": "https://onyx.example.com", "show_menu_bar": false}"#,
+            ConfigFormat::Json,
+        )
+        .unwrap();
+        assert_eq!(from_version, FIRST_CONFIG_VERSION);
+        assert_eq!(config.version, CURRENT_CONFIG_VERSION);
+        assert_eq!(config.server_url, "https://onyx.example.com");
+        assert!(!config.show_menu_bar);
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn current_config_is_not_remigrated() {
+        let contents = format!(
+            r#"{{"version": {CURRENT_CONFIG_VERSION}, "server_url": "https://onyx.example.com"}}"#
+        );
+        let (config, from_version) = parse_and_migrate(&contents, ConfigFormat::Json).unwrap();
+        assert_eq!(from_version, CURRENT_CONFIG_VERSION);
+        assert_eq!(config.version, CURRENT_CONFIG_VERSION);
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn newer_config_keeps_its_version() {
+        let newer = CURRENT_CONFIG_VERSION + 1;
+        let contents =
+            format!(r#"{{"version": {newer}, "server_url": "https://onyx.example.com"}}"#);
+        let (config, from_version) = parse_and_migrate(&contents, ConfigFormat::Json).unwrap();
+        assert_eq!(from_version, newer);
+        assert_eq!(config.version, newer);
+        assert!(check_writable(&config).is_err());
+    }
+
+    #[test]
+    fn malformed_configs_are_rejected() {
+        assert!(parse_and_migrate("[]", ConfigFormat::Json).is_err());
+        assert!(parse_and_migrate(
+            r#"{"version": "one", "server_url": "x"}"#,
+            ConfigFormat::Json
+        )
+        .is_err());
+        assert!(parse_and_migrate(r#"{"version": 1}"#, ConfigFormat::Json).is_err());
+        assert!(parse_and_migrate("server_url = ", ConfigFormat::Toml).is_err());
+    }
+
+    #[test]
+    fn command_shortcuts_use_ctrl_off_macos() {
+        assert_eq!(accelerator_for("Cmd+Shift+N", false), "Ctrl+Shift+N");
+        assert_eq!(accelerator_for("super+K", false), "Ctrl+K");
+        assert_eq!(accelerator_for("Cmd+Ctrl+K", false), "Ctrl+K");
+        assert_eq!(accelerator_for("CmdOrCtrl+N", false), "CmdOrCtrl+N");
+        assert_eq!(accelerator_for("Cmd+Shift+N", true), "Cmd+Shift+N");
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn window_settings_take_each_platforms_overrides() {
+        let mut window = WindowSettings {
+            position: WindowPosition::Center,
+            ..WindowSettings::default()
+        };
+        window.macos.width = Some(1000.0);
+        window.windows.position = Some(WindowPosition::CursorDisplay);
+        let [(_, macos), (_, windows), (_, linux)] = window.per_platform();
+        assert_eq!(
+            (macos.width, macos.height, macos.position),
+            (1000.0, window.height, WindowPosition::Center)
+        );
+        assert_eq!(
+            (windows.width, windows.position),
+            (window.width, WindowPosition::CursorDisplay)
+        );
+        assert!(linux.macos.is_empty() && linux.width == window.width);
+    }
+
+    #[test]
+    fn validate_config_rejects_bad_settings() {
+        assert!(validate_config(&AppConfig::default()).is_ok());
+
+        let config = AppConfig {
+            server_url: "ftp://onyx.example.com".to_string(),
+            ..AppConfig::default()
+        };
+        assert!(validate_config(&config).is_err());
+
+        let mut config = AppConfig::default();
+        config.shortcuts.new_chat = " ".to_string();
+        assert!(validate_config(&config).is_err());
+
+        let mut config = AppConfig::default();
+        config.proxy.url = Some("socks5://127.0.0.1:1080".to_string());
+        assert!(validate_config(&config).is_ok());
+        config.proxy.url = Some("not a url".to_string());
+        assert!(validate_config(&config).is_err());
+
+        let mut config = AppConfig::default();
+        config.appearance.corner_radius = Some(-4.0);
+        assert!(validate_config(&config).is_err());
+
+        let mut config = AppConfig::default();
+        config.window.min_width = 0.0;
+        assert!(validate_config(&config).is_err());
+
+        let mut config = AppConfig::default();
+        config.window.linux.height = Some(500.0);
+        assert!(validate_config(&config).is_err());
+
+        let mut config = AppConfig::default();
+        config.spellcheck.languages = vec!["en_US".to_string(), "pt-BR".to_string()];
+        assert!(validate_config(&config).is_ok());
+        config.spellcheck.languages.push("english".to_string());
+        assert!(validate_config(&config).is_err());
+
+        let mut config = AppConfig {
+            locale: Some("de-DE".to_string()),
+            ..AppConfig::default()
+        };
+        assert!(validate_config(&config).is_ok());
+        config.locale = Some("Deutsch (Deutschland)".to_string());
+        assert!(validate_config(&config).is_err());
+
+        let mut config = AppConfig {
+            accounts: vec!["client-a".to_string(), "client-b".to_string()],
+            ..AppConfig::default()
+        };
+        assert!(validate_config(&config).is_ok());
+        config.accounts.push("client-a".to_string());
+        assert!(validate_config(&config).is_err());
+
+        let mut config = AppConfig::default();
+        config.profiles.insert(
+            "staging".to_string(),
+            ProfileSettings {
+                server_url: "staging.example.com".to_string(),
+                titlebar: None,
+                admin_mode: None,
+                accounts: Vec::new(),
+            },
+        );
+        assert!(validate_config(&config).is_err());
+
+        let mut config = AppConfig {
+            locked_fields: vec!["server_url".to_string()],
+            ..AppConfig::default()
+        };
+        assert!(validate_config(&config).is_ok());
+        config.locked_fields.push("zoom".to_string());
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn locked_settings_stay_put() {
+        let previous = AppConfig {
+            server_url: "https://onyx.example.com".to_string(),
+            locked_fields: vec!["server_url".to_string()],
+            ..AppConfig::default()
+        };
+
+        let mut config = previous.clone();
+        config.admin_mode = true;
+        assert!(check_locked(&previous, &config).is_ok());
+        config.server_url = "https://onyx.example.net".to_string();
+        assert!(check_locked(&previous, &config).is_err());
+        // Nor can the lock itself come off.
+        let config = AppConfig {
+            locked_fields: Vec::new(),
+            ..previous.clone()
+        };
+        assert!(check_locked(&previous, &config).is_err());
+
+        let mut reset = AppConfig::default();
+        keep_locked(&mut reset, &previous);
+        assert_eq!(reset.server_url, previous.server_url);
+        assert_eq!(reset.locked_fields, previous.locked_fields);
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn toml_config_is_parsed_and_migrated() {
+        let (config, from_version) = parse_and_migrate(
+            "# self-hosted\nserver_url = \"https://onyx.example.com\"\n",
+            ConfigFormat::Toml,
+        )
+        .unwrap();
+        assert_eq!(from_version, FIRST_CONFIG_VERSION);
+        assert_eq!(config.server_url, "https://onyx.example.com");
+    }
+}
@@ -0,0 +1,276 @@
+// Earlier versions of the config file, kept in `backups/` under the config
+// directory. Every change the user makes -- from Settings, `onyx config
+// set`, an import or a restore -- copies the file it's about to replace
+// there first, so a bad edit, by hand or by the app, can be undone. The
+// app's own saves (zoom steps, consent decisions, Do Not Disturb) don't, or
+// a few of them would push every backup worth having out. The newest
+// `MAX_BACKUPS` are kept.
+//
+// Restoring one (Settings > Backups), once the user confirms, goes through
+// the usual save, so the config it replaces is backed up in turn and a
+// restore can be undone too. What only the app changes (zoom levels,
+// remembered permissions, the automation token) stays as it is now, as it
+// does for a save from Settings.
+//
+// Resetting the settings archives the config first, to `backups/archived/`,
+// where nothing is ever let go of, with a note next to it of the profiles,
+// accounts and secrets it had. The secrets stay encrypted with this
+// machine's key, so the archive can be put back as `config.toml` later.
+
+use crate::commands::{apply_config_change, check_config};
+use crate::config::{
+    get_config_dir, keep_app_state, keep_locked, parse_and_migrate, AppConfig, ConfigState,
+};
+use crate::config_format::ConfigFormat;
+use crate::window::SETTINGS_WINDOW_LABEL;
+use chrono::{Local, NaiveDateTime};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::WebviewWindow;
+use tauri_plugin_dialog::{
+    DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult,
+};
+
+const RESTORE_LABEL: &str = "Restore";
+
+/// How many backups are kept.
+const MAX_BACKUPS: usize = 20;
+
+/// When a backup was made, as its name has it.
+const TIME_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+
+const PREFIX: &str = "config-";
+
+fn backups_dir() -> Option<PathBuf> {
+    Some(get_config_dir()?.join("backups"))
+}
+
+/// The name of a backup of `config_path` made at `time`.
+fn backup_name(config_path: &Path, time: NaiveDateTime) -> String {
+    let extension = config_path
+        .extension()
+        .map_or_else(String::new, |ext| format!(".{}", ext.to_string_lossy()));
+    format!("{PREFIX}{}{extension}", time.format(TIME_FORMAT))
+}
+
+/// When the backup `name` was made, if it's one.
+fn saved_at(name: &str) -> Option<NaiveDateTime> {
+    let stamp = name.strip_prefix(PREFIX)?;
+    let stamp = stamp.split_once('.').map_or(stamp, |(stamp, _)| stamp);
+    NaiveDateTime::parse_from_str(stamp, TIME_FORMAT).ok()
+}
+
+/// The backups there are, oldest first.
+fn backups() -> Vec<String> {
+    let Some(entries) = backups_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| saved_at(name).is_some())
+        .collect();
+    names.sort();
+    names
+}
+
+/// The backups past the newest `keep`, of `names` sorted oldest first.
+fn stale(names: &[String], keep: usize) -> &[String] {
+    &names[..names.len().saturating_sub(keep)]
+}
+
+/// Keep `contents`, what `config_path` holds before a save replaces it, as
+/// a backup, and let go of the oldest past `MAX_BACKUPS`.
+pub fn back_up(config_path: &Path, contents: &str) -> Result<(), String> {
+    let dir = backups_dir().ok_or("Could not determine the backups folder")?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    let path = dir.join(backup_name(config_path, Local::now().naive_local()));
+    fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    for name in stale(&backups(), MAX_BACKUPS) {
+        let _ = fs::remove_file(dir.join(name));
+    }
+    Ok(())
+}
+
+/// What `config` had that a reset would lose and isn't plain to see in the
+/// file: profiles, accounts, and which secrets were set (never what they
+/// were).
+fn archive_note(config: &AppConfig, time: NaiveDateTime) -> String {
+    let listed = |names: Vec<&str>| {
+        if names.is_empty() {
+            "none".to_string()
+        } else {
+            names.join(", ")
+        }
+    };
+    let mut secrets: Vec<String> = config
+        .headers
+        .keys()
+        .map(|name| format!("access proxy header {name}"))
+        .collect();
+    if config.client_certificate.password.is_some() {
+        secrets.push("client certificate password".to_string());
+    }
+    if config.automation_api.token.is_some() {
+        secrets.push("automation token".to_string());
+    }
+    format!(
+        "Archived before the settings were reset, on {}.\n\
+         Profiles: {}\n\
+         Accounts: {}\n\
+         Secrets: {}\n",
+        time.format("%Y-%m-%d %H:%M:%S"),
+        listed(config.profiles.keys().map(String::as_str).collect()),
+        listed(config.accounts.iter().map(String::as_str).collect()),
+        listed(secrets.iter().map(String::as_str).collect()),
+    )
+}
+
+/// Keep `contents`, what `config_path` holds, for good before `config` is
+/// reset, along with a note of what it had. Returns where it went.
+pub fn archive(config_path: &Path, contents: &str, config: &AppConfig) -> Result<PathBuf, String> {
+    let dir = backups_dir()
+        .ok_or("Could not determine the backups folder")?
+        .join("archived");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    let now = Local::now().naive_local();
+    let path = dir.join(backup_name(config_path, now));
+    fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    let note = path.with_extension("txt");
+    fs::write(&note, archive_note(config, now))
+        .map_err(|e| format!("Failed to write {}: {e}", note.display()))?;
+    Ok(path)
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigBackup {
+    /// What to pass to `restore_config_backup`.
+    name: String,
+    /// When it was made, in local time, as `2026-10-15 14:30:12`.
+    saved_at: String,
+}
+
+/// The config backups there are, newest first
+#[tauri::command]
+pub fn list_config_backups() -> Vec<ConfigBackup> {
+    backups()
+        .into_iter()
+        .rev()
+        .filter_map(|name| {
+            let saved_at = saved_at(&name)?.format("%Y-%m-%d %H:%M:%S").to_string();
+            Some(ConfigBackup { name, saved_at })
+        })
+        .collect()
+}
+
+/// Put the settings back as they were in the backup `name`, except for
+/// what's locked and what only the app changes, and apply them, once the
+/// user confirms; from Settings only. Returns the config after, or `None`
+/// if the user cancelled.
+#[tauri::command]
+pub async fn restore_config_backup(
+    app: tauri::AppHandle,
+    window: WebviewWindow,
+    state: tauri::State<'_, ConfigState>,
+    name: String,
+) -> Result<Option<AppConfig>, String> {
+    if window.label() != SETTINGS_WINDOW_LABEL {
+        return Err("Backups can only be restored from Settings".to_string());
+    }
+    if crate::kiosk::is_active() {
+        return Err(crate::kiosk::unavailable());
+    }
+    // Only ever a file listed in the folder, never a path from the caller.
+    if !backups().contains(&name) {
+        return Err(format!("There's no backup named {name}"));
+    }
+    let path = backups_dir()
+        .ok_or("Could not determine the backups folder")?
+        .join(&name);
+    let contents =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read the backup: {e}"))?;
+    let (mut restored, _) = parse_and_migrate(&contents, ConfigFormat::from_path(&path))
+        .map_err(|e| format!("The backup can't be read: {e}"))?;
+    check_config(&mut restored)?;
+
+    let saved_at = saved_at(&name).map_or_else(
+        || name.clone(),
+        |time| time.format("%Y-%m-%d %H:%M:%S").to_string(),
+    );
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .message(format!(
+            "The settings go back to how they were on {saved_at}, apart from any your \
+             organization manages. The current settings are backed up first."
+        ))
+        .title("Restore this backup?")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            RESTORE_LABEL.to_string(),
+            "Cancel".to_string(),
+        ))
+        .parent(&window)
+        .show_with_result(move |result| {
+            let _ = tx.send(match result {
+                MessageDialogResult::Ok => true,
+                MessageDialogResult::Custom(label) => label == RESTORE_LABEL,
+                _ => false,
+            });
+        });
+    if !rx.await.unwrap_or(false) {
+        return Ok(None);
+    }
+
+    let previous = state.config();
+    let saved = state.update_and_persist_backed_up(|c| {
+        keep_locked(&mut restored, c);
+        keep_app_state(&mut restored, c);
+        *c = restored;
+    })?;
+    state.set_config_initialized(true);
+    apply_config_change(&app, &previous, &saved);
+    Ok(Some(saved))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn backup_names_sort_by_when_they_were_made() {
+        let time = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.3f").unwrap();
+        let earlier = backup_name(Path::new("/c/config.toml"), time("2026-10-15 09:05:00.250"));
+        let later = backup_name(Path::new("/c/config.json"), time("2026-10-15 10:00:00.000"));
+        assert_eq!(earlier, "config-20261015-090500-250.toml");
+        assert!(earlier < later);
+        assert_eq!(saved_at(&earlier), Some(time("2026-10-15 09:05:00.250")));
+        assert_eq!(saved_at("config.toml.invalid.bak"), None);
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn archive_notes_name_secrets_but_not_their_values() {
+        let mut config = AppConfig::default();
+        config.accounts.push("Work".to_string());
+        config.headers.insert(
+            "X-Auth-Token".to_string(),
+            crate::secrets::SecretString::new("hunter2".to_string()),
+        );
+        let time =
+            NaiveDateTime::parse_from_str("2026-10-15 09:05:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let note = archive_note(&config, time);
+        assert!(note.contains("on 2026-10-15 09:05:00."));
+        assert!(note.contains("\nProfiles: none\n"));
+        assert!(note.contains("\nAccounts: Work\n"));
+        assert!(note.contains("\nSecrets: access proxy header X-Auth-Token\n"));
+        assert!(!note.contains("hunter2"));
+    }
+
+    #[test]
+    fn only_the_newest_backups_are_kept() {
+        let names: Vec<String> = (1..=22).map(|n| format!("config-{n:02}")).collect();
+        assert_eq!(stale(&names, MAX_BACKUPS), &names[..2]);
+        assert!(stale(&names[..3], MAX_BACKUPS).is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+// Checking the config file as it is on disk: `check_config_file`, Settings'
+// Check Config File, and `--check-config` on the command line. At startup a
+// file that doesn't parse only earns a line in stderr before the app goes
+// on with the defaults, and a setting it doesn't know is dropped without a
+// word; this lists everything wrong at once, each problem with the setting
+// it's about where there is one: the file's syntax, settings that aren't
+// any (a typo, or one from a newer version), what saving from Settings
+// would refuse, shortcuts the menus can't bind, and a server that doesn't
+// answer.
+//
+// Errors are what keeps the file, or a setting in it, from being used as
+// written. Warnings are what's worth a look but doesn't: an unknown
+// setting, or the server being out of reach right now.
+
+use crate::commands::check_config;
+use crate::config::{get_config_path, migrate, validate_server_url, AppConfig};
+use crate::config_format::ConfigFormat;
+use crate::server_api::server_healthy;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct ConfigProblem {
+    pub severity: Severity,
+    /// The setting it's about, by its path in the config, if it's about one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub setting: Option<String>,
+    pub message: String,
+}
+
+impl ConfigProblem {
+    fn error(setting: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            setting: setting.map(str::to_string),
+            message: message.into(),
+        }
+    }
+
+    fn warning(setting: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            setting: setting.map(str::to_string),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match &self.setting {
+            Some(setting) => write!(f, "{severity}: {setting}: {}", self.message),
+            None => write!(f, "{severity}: {}", self.message),
+        }
+    }
+}
+
+/// Settings whose keys are the user's own (header names) or that hold
+/// secrets, which aren't serialized back to compare with, and the JSON
+/// file's pointer to its schema.
+const OPAQUE: &[&str] = &[
+    "$schema",
+    "headers",
+    "client_certificate.password",
+    "automation_api.token",
+];
+
+/// Settings in `file` that `known` (the config it parsed to, serialized
+/// again) doesn't have, by their path. Empty values are left out, since
+/// empty lists and tables aren't written back.
+fn unknown_settings(
+    file: &Map<String, Value>,
+    known: &Map<String, Value>,
+    path: &str,
+) -> Vec<String> {
+    let mut unknown = Vec::new();
+    for (key, value) in file {
+        let setting = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{path}.{key}")
+        };
+        if OPAQUE.contains(&setting.as_str()) {
+            continue;
+        }
+        match (value, known.get(key)) {
+            (Value::Object(file), Some(Value::Object(known))) => {
+                unknown.extend(unknown_settings(file, known, &setting));
+            }
+            (Value::Array(file), Some(Value::Array(known))) => {
+                for (i, (file, known)) in file.iter().zip(known).enumerate() {
+                    if let (Value::Object(file), Value::Object(known)) = (file, known) {
+                        unknown.extend(unknown_settings(file, known, &format!("{setting}.{i}")));
+                    }
+                }
+            }
+            (value, None) if !is_empty(value) => unknown.push(setting),
+            _ => {}
+        }
+    }
+    unknown
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Array(items) => items.is_empty(),
+        Value::Object(map) => map.is_empty(),
+        _ => false,
+    }
+}
+
+/// `config` serialized as it would be saved, without its secrets.
+fn known_settings(config: &AppConfig) -> Map<String, Value> {
+    let mut config = config.clone();
+    // Cleared so nothing is encrypted just to be compared.
+    config.headers.clear();
+    config.client_certificate.password = None;
+    config.automation_api.token = None;
+    match serde_json::to_value(&config) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    }
+}
+
+/// What's wrong with a config file holding `contents`, and the config it
+/// parses to, if it does. Nothing here needs the network.
+fn check_contents(contents: &str, format: ConfigFormat) -> (Option<AppConfig>, Vec<ConfigProblem>) {
+    const FALLBACK: &str = "The app runs on the defaults instead.";
+    let mut map = match format.parse(contents) {
+        Ok(Value::Object(map)) => map,
+        Ok(_) => {
+            let message = format!("The file isn't a table of settings. {FALLBACK}");
+            return (None, vec![ConfigProblem::error(None, message)]);
+        }
+        Err(e) => {
+            let message = format!("The file can't be parsed: {e}. {FALLBACK}");
+            return (None, vec![ConfigProblem::error(None, message)]);
+        }
+    };
+    if let Err(e) = migrate(&mut map) {
+        return (None, vec![ConfigProblem::error(Some("version"), e)]);
+    }
+    let config: AppConfig = match serde_json::from_value(Value::Object(map.clone())) {
+        Ok(config) => config,
+        Err(e) => {
+            let message = format!("The file has a setting that can't be read: {e}. {FALLBACK}");
+            return (None, vec![ConfigProblem::error(None, message)]);
+        }
+    };
+
+    let mut problems: Vec<ConfigProblem> = unknown_settings(&map, &known_settings(&config), "")
+        .into_iter()
+        .map(|setting| ConfigProblem::warning(Some(&setting), "Not a setting, so it's ignored"))
+        .collect();
+    if let Err(e) = validate_server_url(&config.server_url) {
+        problems.push(ConfigProblem::error(Some("server_url"), e));
+    }
+    for problem in crate::shortcuts::check(&config.shortcuts).1 {
+        problems.push(ConfigProblem::error(Some("shortcuts"), problem.describe()));
+    }
+    // The rest, as a save from Settings checks it. That stops at the first
+    // problem, which may be one already listed.
+    if let Err(e) = check_config(&mut config.clone()) {
+        if !problems.iter().any(|problem| problem.message == e) {
+            problems.push(ConfigProblem::error(None, e));
+        }
+    }
+    (Some(config), problems)
+}
+
+/// What's wrong with the config file, including whether its server
+/// answers. A missing file is fine: the app runs on the defaults until
+/// it's first saved.
+pub async fn check_file() -> Vec<ConfigProblem> {
+    let Some(path) = get_config_path() else {
+        return vec![ConfigProblem::error(
+            None,
+            "Could not determine config path",
+        )];
+    };
+    if !path.exists() {
+        return Vec::new();
+    }
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            let message = format!("Failed to read {}: {e}", path.display());
+            return vec![ConfigProblem::error(None, message)];
+        }
+    };
+    let (config, mut problems) = check_contents(&contents, ConfigFormat::from_path(&path));
+    let Some(mut config) = config else {
+        return problems;
+    };
+    crate::install_scope::managed_policy().apply(&mut config);
+    if let Ok(server) = validate_server_url(&config.server_url) {
+        match server_healthy(&config, &server).await {
+            Ok(true) => {}
+            Ok(false) => problems.push(ConfigProblem::warning(
+                Some("server_url"),
+                format!("{server} answers, but says it isn't working"),
+            )),
+            Err(e) => problems.push(ConfigProblem::warning(
+                Some("server_url"),
+                format!("{server} can't be reached: {e}"),
+            )),
+        }
+    }
+    problems
+}
+
+/// Check the config file, for Settings
+#[tauri::command]
+pub async fn check_config_file() -> Vec<ConfigProblem> {
+    check_file().await
+}
+
+/// `--check-config`: print what's wrong with the config file, one problem
+/// a line. Returns whether it has no errors.
+// Printing to stdout is what the flag is for.
+#[allow(clippy::print_stdout)]
+pub fn run() -> bool {
+    let problems = tauri::async_runtime::block_on(check_file());
+    let path = get_config_path().map_or_else(String::new, |path| path.display().to_string());
+    if problems.is_empty() {
+        println!("No problems in {path}");
+    }
+    for problem in &problems {
+        println!("{problem}");
+    }
+    !problems
+        .iter()
+        .any(|problem| problem.severity == Severity::Error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(problems: &[ConfigProblem]) -> Vec<(Severity, Option<&str>)> {
+        problems
+            .iter()
+            .map(|problem| (problem.severity, problem.setting.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn a_file_that_cant_be_parsed_is_one_error() {
+        let (config, problems) = check_contents("server_url = ", ConfigFormat::Toml);
+        assert!(config.is_none());
+        assert_eq!(settings(&problems), [(Severity::Error, None)]);
+    }
+
+    #[test]
+    fn problems_name_their_settings() {
+        let (config, problems) = check_contents(
+            r#"
+server_url = "onyx.example.com"
+sever_url = "https://onyx.example.com"
+accounts = []
+
+[shortcuts]
+new_chat = "CmdOrCtrl+Nope"
+new_window = "CmdOrCtrl+Shift+N"
+colour = "blue"
+
+[headers]
+X-Auth-Token = "secret"
+"#,
+            ConfigFormat::Toml,
+        );
+        assert!(config.is_some());
+        assert_eq!(
+            settings(&problems),
+            [
+                (Severity::Warning, Some("sever_url")),
+                (Severity::Warning, Some("shortcuts.colour")),
+                (Severity::Error, Some("server_url")),
+                (Severity::Error, Some("shortcuts")),
+            ]
+        );
+    }
+}
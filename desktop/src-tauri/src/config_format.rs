@@ -0,0 +1,187 @@
+// On-disk config formats. `config.toml` is preferred (it's the file users are
+// told to open in an editor, and TOML allows comments); `config.json` is
+// still read and written for installs that already have one.
+//
+// TOML has no `null`, so any `Option` field on `AppConfig` must be
+// `#[serde(skip_serializing_if = "Option::is_none")]`.
+//
+// Both point editors at the schema written next to them (see
+// `config_schema.rs`): a `#:schema` comment heads `config.toml`, and
+// `config.json` has a `$schema` key, which loading ignores.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item, Table};
+
+pub const TOML_CONFIG_FILE_NAME: &str = "config.toml";
+pub const JSON_CONFIG_FILE_NAME: &str = "config.json";
+pub const SCHEMA_FILE_NAME: &str = "config.schema.json";
+
+/// Where the schema is, as TOML editors read it. It has to come first.
+const TOML_SCHEMA_DIRECTIVE: &str = "#:schema ./config.schema.json\n";
+
+const NEW_TOML_HEADER: &str = "\
+#:schema ./config.schema.json
+# Onyx desktop settings. Restart the app after editing this file.
+# Comments (lines starting with #) are kept when the app saves changes.
+
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Self {
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Self::Json
+        } else {
+            Self::Toml
+        }
+    }
+
+    /// Parse file contents into a generic value so migrations can run on it
+    /// regardless of the source format.
+    pub fn parse(self, contents: &str) -> Result<Value, String> {
+        match self {
+            Self::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+            Self::Toml => toml::from_str(contents).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Render `config` for writing to disk. For TOML, `existing` (the file's
+    /// current contents, if any) is updated in place so the user's comments
+    /// and formatting survive the save.
+    pub fn render<T: Serialize>(
+        self,
+        config: &T,
+        existing: Option<&str>,
+    ) -> Result<String, String> {
+        match self {
+            Self::Json => {
+                let mut value = serde_json::to_value(config).map_err(|e| e.to_string())?;
+                if let Value::Object(map) = &mut value {
+                    map.insert(
+                        "$schema".to_string(),
+                        format!("./{SCHEMA_FILE_NAME}").into(),
+                    );
+                }
+                serde_json::to_string_pretty(&value).map_err(|e| e.to_string())
+            }
+            Self::Toml => {
+                let rendered = toml::to_string_pretty(config).map_err(|e| e.to_string())?;
+                let updated: DocumentMut = rendered.parse().map_err(|e| format!("{e}"))?;
+
+                let existing = existing.and_then(|contents| contents.parse::<DocumentMut>().ok());
+                Ok(existing.map_or_else(
+                    || format!("{NEW_TOML_HEADER}{updated}"),
+                    |mut document| {
+                        merge_tables(document.as_table_mut(), updated.as_table());
+                        let rendered = document.to_string();
+                        // Files from before the schema get pointed at it too.
+                        if rendered.starts_with("#:schema") {
+                            rendered
+                        } else {
+                            format!("{TOML_SCHEMA_DIRECTIVE}{rendered}")
+                        }
+                    },
+                ))
+            }
+        }
+    }
+}
+
+/// Make `existing` hold exactly `updated`'s keys and values while keeping
+/// `existing`'s comments, whitespace, and key order wherever a key survives.
+fn merge_tables(existing: &mut Table, updated: &Table) {
+    let stale: Vec<String> = existing
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !updated.contains_key(key))
+        .collect();
+    for key in stale {
+        existing.remove(&key);
+    }
+
+    for (key, item) in updated {
+        match (existing.get_mut(key), item) {
+            (Some(Item::Table(old)), Item::Table(new)) => merge_tables(old, new),
+            (Some(Item::Value(old)), Item::Value(new)) => {
+                // The decor holds the value's surrounding whitespace and any
+                // trailing `# comment` on the same line.
+                let decor = old.decor().clone();
+                *old = new.clone();
+                *old.decor_mut() = decor;
+            }
+            _ => {
+                existing.insert(key, item.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn format_is_detected_from_extension() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("/tmp/config.json")),
+            ConfigFormat::Json
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("/tmp/config.toml")),
+            ConfigFormat::Toml
+        );
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn toml_save_preserves_comments_and_drops_stale_keys() {
+        let existing = "\
+# Point this at our staging box
+server_url = \"https://old.example.com\" # not prod!
+stale = 1
+
+[window]
+# keep it small
+width = 800
+";
+        let updated = json!({
+            "server_url": "https://new.example.com",
+            "window": { "width": 1024, "height": 700 },
+        });
+
+        let rendered = ConfigFormat::Toml.render(&updated, Some(existing)).unwrap();
+        assert!(rendered.starts_with("#:schema ./config.schema.json\n# Point this at"));
+        assert!(rendered.contains("server_url = \"https://new.example.com\" # not prod!"));
+        assert!(rendered.contains("# keep it small\nwidth = 1024"));
+        assert!(rendered.contains("height = 700"));
+        assert!(!rendered.contains("stale"));
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn new_toml_file_gets_header_and_round_trips() {
+        let config = json!({ "server_url": "https://onyx.example.com", "show_menu_bar": false });
+        let rendered = ConfigFormat::Toml.render(&config, None).unwrap();
+        assert!(rendered.starts_with("#:schema ./config.schema.json\n# Onyx desktop settings."));
+        assert_eq!(ConfigFormat::Toml.parse(&rendered).unwrap(), config);
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn json_files_point_at_the_schema() {
+        let config = json!({ "server_url": "https://onyx.example.com" });
+        let rendered = ConfigFormat::Json.render(&config, None).unwrap();
+        assert_eq!(
+            ConfigFormat::Json.parse(&rendered).unwrap(),
+            json!({ "$schema": "./config.schema.json", "server_url": "https://onyx.example.com" })
+        );
+    }
+}
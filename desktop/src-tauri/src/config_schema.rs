@@ -0,0 +1,48 @@
+// A JSON Schema for the config file, so an editor completes and checks
+// settings in a hand-edited `config.toml` or `config.json` (with Taplo or
+// Even Better TOML, or any JSON editor). It's generated from `AppConfig`,
+// doc comments and all, and written next to the config as
+// `config.schema.json` at every start, so it always describes the version
+// that's running. The config points at it (see `config_format.rs`).
+
+use crate::config::{get_config_dir, AppConfig};
+use crate::config_format::SCHEMA_FILE_NAME;
+use std::fs;
+
+/// The schema, as JSON.
+pub fn schema() -> Result<String, String> {
+    let mut schema = schemars::schema_for!(AppConfig);
+    schema.insert("title".to_string(), "Onyx desktop settings".into());
+    serde_json::to_string_pretty(&schema).map_err(|e| e.to_string())
+}
+
+/// Write the schema next to the config, unless it's already there.
+pub fn write() -> Result<(), String> {
+    let dir = get_config_dir().ok_or("Could not determine config directory")?;
+    let path = dir.join(SCHEMA_FILE_NAME);
+    let schema = schema()?;
+    if fs::read_to_string(&path).is_ok_and(|existing| existing == schema) {
+        return Ok(());
+    }
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create config dir: {e}"))?;
+    fs::write(&path, schema).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn schema_describes_the_settings() {
+        let schema: Value = serde_json::from_str(&schema().unwrap()).unwrap();
+        let properties = &schema["properties"];
+        assert_eq!(properties["server_url"]["type"], "string");
+        assert!(properties["shortcuts"].is_object());
+        assert!(properties["launch_at_login"]["description"]
+            .as_str()
+            .unwrap()
+            .contains("logs in"));
+    }
+}
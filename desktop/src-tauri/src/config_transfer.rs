@@ -0,0 +1,312 @@
+// Export... and Import... in the settings window: the config as a file
+// of its own, to move a setup to a new machine or hand a team the same
+// starting point. The file is TOML, or JSON if it's named `.json`, laid out
+// like `config.toml`.
+//
+// Secrets never leave the machine they were made on -- they're encrypted
+// with its keychain anyway -- so access proxy headers, the client
+// certificate's password and the automation token aren't exported, and are
+// kept as they are on import. Neither are what belongs to the deployment or
+// to this machine's own decisions: locked settings, the managed config, and
+// remembered permissions.
+//
+// An import is merged over the current config, so a file with only a few
+// settings changes only those. It's checked as a save from Settings would
+// be, and what it would change is listed for the user to confirm before
+// anything is applied. Locked settings stay as they are.
+
+use crate::commands::{apply_config_change, check_config};
+use crate::config::{keep_locked, migrate, AppConfig, ConfigState};
+use crate::config_format::ConfigFormat;
+use crate::window::SETTINGS_WINDOW_LABEL;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::WebviewWindow;
+use tauri_plugin_dialog::{
+    DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult,
+};
+
+const IMPORT_LABEL: &str = "Import";
+const CANCEL_LABEL: &str = "Cancel";
+
+/// How many changes the confirmation lists before summing up the rest.
+const MAX_CHANGES_SHOWN: usize = 15;
+
+/// Settings that aren't exported or imported, by their key at the top of
+/// the config.
+const NOT_TRANSFERRED: &[&str] = &[
+    "headers",
+    "locked_fields",
+    "managed_config_url",
+    "managed_config_key",
+    "permissions",
+];
+
+/// Secrets inside a table, as (table, key).
+const NESTED_SECRETS: &[(&str, &str)] = &[
+    ("client_certificate", "password"),
+    ("automation_api", "token"),
+];
+
+/// `config` as an export has it.
+fn exportable(config: &AppConfig) -> Result<Value, String> {
+    let mut config = config.clone();
+    // Cleared before serializing, so nothing is encrypted on the way out.
+    config.headers.clear();
+    config.client_certificate.password = None;
+    config.automation_api.token = None;
+    let mut value = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+    if let Value::Object(map) = &mut value {
+        strip(map);
+    }
+    Ok(value)
+}
+
+/// Take what isn't transferred out of `map`.
+fn strip(map: &mut Map<String, Value>) {
+    for key in NOT_TRANSFERRED {
+        map.remove(*key);
+    }
+    for (table, key) in NESTED_SECRETS {
+        if let Some(Value::Object(table)) = map.get_mut(*table) {
+            table.remove(*key);
+        }
+    }
+}
+
+/// `current` with the settings in `contents` (a file in `format`) merged
+/// in, checked, and with locked settings kept.
+fn imported(
+    current: &AppConfig,
+    contents: &str,
+    format: ConfigFormat,
+) -> Result<AppConfig, String> {
+    let Value::Object(mut settings) = format.parse(contents)? else {
+        return Err("The file isn't a table of settings".to_string());
+    };
+    migrate(&mut settings)?;
+    settings.remove("version");
+    strip(&mut settings);
+
+    let mut value = serde_json::to_value(current).map_err(|e| e.to_string())?;
+    crate::managed_config::merge(&mut value, &Value::Object(settings));
+    let mut config: AppConfig =
+        serde_json::from_value(value).map_err(|e| format!("The file has a bad setting: {e}"))?;
+    check_config(&mut config)?;
+    keep_locked(&mut config, current);
+    Ok(config)
+}
+
+/// Every setting in `value` by its path, with tables opened up.
+fn flatten(value: &Value, path: &str, into: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                flatten(value, &path, into);
+            }
+        }
+        value => {
+            into.insert(path.to_string(), value.clone());
+        }
+    }
+}
+
+/// What going from `before` to `after` changes, one line per setting.
+fn changes(before: &Value, after: &Value) -> Vec<String> {
+    let (mut old, mut new) = (BTreeMap::new(), BTreeMap::new());
+    flatten(before, "", &mut old);
+    flatten(after, "", &mut new);
+    let shown =
+        |value: Option<&Value>| value.map_or_else(|| "(not set)".to_string(), Value::to_string);
+    let mut paths: Vec<&String> = old.keys().chain(new.keys()).collect();
+    paths.sort();
+    paths.dedup();
+    paths
+        .into_iter()
+        .filter(|path| old.get(*path) != new.get(*path))
+        .map(|path| {
+            format!(
+                "{path}: {} → {}",
+                shown(old.get(path)),
+                shown(new.get(path))
+            )
+        })
+        .collect()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// Save the settings, without secrets, to a file the user picks. Returns
+/// where, or `None` if they cancelled.
+#[tauri::command]
+pub async fn export_config(
+    app: tauri::AppHandle,
+    window: WebviewWindow,
+    state: tauri::State<'_, ConfigState>,
+) -> Result<Option<PathBuf>, String> {
+    if window.label() != SETTINGS_WINDOW_LABEL {
+        return Err("Settings can only be exported from Settings".to_string());
+    }
+    let Some(path) = app
+        .dialog()
+        .file()
+        .set_title("Export Settings")
+        .set_file_name("Onyx Settings.toml")
+        .add_filter("TOML", &["toml"])
+        .add_filter("JSON", &["json"])
+        .blocking_save_file()
+    else {
+        return Ok(None);
+    };
+    let path = path.into_path().map_err(|e| e.to_string())?;
+    let settings = exportable(&state.config())?;
+    let contents = match ConfigFormat::from_path(&path) {
+        ConfigFormat::Json => serde_json::to_string_pretty(&settings).map_err(|e| e.to_string()),
+        ConfigFormat::Toml => toml::to_string_pretty(&settings).map_err(|e| e.to_string()),
+    }
+    .map_err(|e| format!("Failed to export settings: {e}"))?;
+    fs::write(&path, contents).map_err(|e| format!("Failed to export settings: {e}"))?;
+    Ok(Some(path))
+}
+
+/// Import settings from a file the user picks, once they've seen and
+/// confirmed what would change. Returns the config after, or `None` if
+/// nothing was imported.
+#[tauri::command]
+pub async fn import_config(
+    app: tauri::AppHandle,
+    window: WebviewWindow,
+    state: tauri::State<'_, ConfigState>,
+) -> Result<Option<AppConfig>, String> {
+    if window.label() != SETTINGS_WINDOW_LABEL {
+        return Err("Settings can only be imported from Settings".to_string());
+    }
+    if crate::kiosk::is_active() {
+        return Err(crate::kiosk::unavailable());
+    }
+    let Some(path) = app
+        .dialog()
+        .file()
+        .set_title("Import Settings")
+        .add_filter("Settings", &["toml", "json"])
+        .blocking_pick_file()
+    else {
+        return Ok(None);
+    };
+    let path = path.into_path().map_err(|e| e.to_string())?;
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {e}", file_name(&path)))?;
+
+    let current = state.config();
+    let config = imported(&current, &contents, ConfigFormat::from_path(&path))?;
+    let mut lines = changes(&exportable(&current)?, &exportable(&config)?);
+    if lines.is_empty() {
+        app.dialog()
+            .message(format!(
+                "The settings in {} already match yours.",
+                file_name(&path)
+            ))
+            .title("Import Settings")
+            .kind(MessageDialogKind::Info)
+            .parent(&window)
+            .show(|_| {});
+        return Ok(None);
+    }
+
+    let count = lines.len();
+    if count > MAX_CHANGES_SHOWN {
+        lines.truncate(MAX_CHANGES_SHOWN);
+        lines.push(format!("…and {} more", count - MAX_CHANGES_SHOWN));
+    }
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .message(format!(
+            "Importing {} changes {count} setting{}:\n\n{}",
+            file_name(&path),
+            if count == 1 { "" } else { "s" },
+            lines.join("\n")
+        ))
+        .title("Import Settings")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            IMPORT_LABEL.to_string(),
+            CANCEL_LABEL.to_string(),
+        ))
+        .parent(&window)
+        .show_with_result(move |result| {
+            let _ = tx.send(match result {
+                MessageDialogResult::Ok => true,
+                MessageDialogResult::Custom(label) => label == IMPORT_LABEL,
+                _ => false,
+            });
+        });
+    if !rx.await.unwrap_or(false) {
+        return Ok(None);
+    }
+
+    let previous = state.config();
+    let saved = state.update_and_persist_backed_up(|c| {
+        let mut config = config;
+        keep_locked(&mut config, c);
+        *c = config;
+    })?;
+    state.set_config_initialized(true);
+    apply_config_change(&app, &previous, &saved);
+    Ok(Some(saved))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::SecretString;
+    use serde_json::json;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn exports_leave_out_secrets() {
+        let mut config = AppConfig::default();
+        config.headers.insert(
+            "X-Auth-Token".to_string(),
+            SecretString::new("t".to_string()),
+        );
+        config.client_certificate.password = Some(SecretString::new("p".to_string()));
+        config.automation_api.token = Some(SecretString::new("a".to_string()));
+        config.locked_fields.push("server_url".to_string());
+
+        let exported = exportable(&config).unwrap();
+        assert!(exported.get("headers").is_none());
+        assert!(exported.get("locked_fields").is_none());
+        assert!(exported["client_certificate"].get("password").is_none());
+        assert!(exported["automation_api"].get("token").is_none());
+        assert_eq!(exported["server_url"], json!(config.server_url));
+    }
+
+    #[test]
+    fn changes_list_each_setting_that_differs() {
+        let before = json!({"server_url": "https://a.example", "shortcuts": {"enabled": true}});
+        let after = json!({
+            "server_url": "https://a.example",
+            "shortcuts": {"enabled": false},
+            "locale": "de-DE",
+        });
+        assert_eq!(
+            changes(&before, &after),
+            [
+                "locale: (not set) → \"de-DE\"",
+                "shortcuts.enabled: true → false",
+            ]
+        );
+    }
+}
@@ -0,0 +1,180 @@
+// How well the configured server answers, so someone on a slow VPN can
+// tell whether a sluggish reply is the network or the model. Every
+// `PROBE_INTERVAL` the app times a request to the server's public
+// `/api/health` and rates the connection good, degraded (slow, going by the
+// last few round trips) or offline (no answer). Each measurement goes to
+// the pages as a `connection-quality` event (also `get_connection_quality`),
+// and the tray shows it: in the icon's tooltip, and in its menu where
+// there's no tooltip (Linux).
+
+use crate::config::{validate_server_url, ConfigState};
+use crate::server_api::server_healthy;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+const PROBE_INTERVAL: Duration = Duration::from_secs(15);
+/// Round trips averaged for the rating, so one slow one doesn't flip it.
+const SAMPLES: usize = 4;
+/// An average round trip slower than this is degraded.
+const DEGRADED_AFTER: Duration = Duration::from_millis(400);
+pub const EVENT: &str = "connection-quality";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quality {
+    Good,
+    Degraded,
+    Offline,
+}
+
+/// The latest measurement.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConnectionReport {
+    pub quality: Quality,
+    /// The last round trip, unless the server didn't answer.
+    pub rtt_ms: Option<u64>,
+    /// The average of the last few round trips.
+    pub average_ms: Option<u64>,
+}
+
+impl ConnectionReport {
+    /// What the tray says, as in "Connection: good (42 ms)".
+    pub fn describe(&self) -> String {
+        let quality = match self.quality {
+            Quality::Good => "good",
+            Quality::Degraded => "slow",
+            Quality::Offline => "offline",
+        };
+        self.rtt_ms.map_or_else(
+            || format!("Connection: {quality}"),
+            |rtt| format!("Connection: {quality} ({rtt} ms)"),
+        )
+    }
+}
+
+/// Managed state: the recent round trips and the last report.
+#[derive(Default)]
+pub struct ConnectionQuality(Mutex<Samples>);
+
+#[derive(Default)]
+struct Samples {
+    recent: VecDeque<Duration>,
+    report: Option<ConnectionReport>,
+}
+
+impl Samples {
+    /// Rate the connection with `rtt` (`None` for no answer) added.
+    fn record(&mut self, rtt: Option<Duration>) -> ConnectionReport {
+        let report = match rtt {
+            None => {
+                // Back online starts the average over.
+                self.recent.clear();
+                ConnectionReport {
+                    quality: Quality::Offline,
+                    rtt_ms: None,
+                    average_ms: None,
+                }
+            }
+            Some(rtt) => {
+                if self.recent.len() == SAMPLES {
+                    self.recent.pop_front();
+                }
+                self.recent.push_back(rtt);
+                let total: Duration = self.recent.iter().sum();
+                let count = u32::try_from(self.recent.len()).unwrap_or(u32::MAX);
+                let average = total / count;
+                ConnectionReport {
+                    quality: if average > DEGRADED_AFTER {
+                        Quality::Degraded
+                    } else {
+                        Quality::Good
+                    },
+                    rtt_ms: Some(millis(rtt)),
+                    average_ms: Some(millis(average)),
+                }
+            }
+        };
+        self.report = Some(report.clone());
+        report
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// How long the configured server takes to answer, or `None` if it doesn't.
+async fn measure(app: &AppHandle) -> Option<Duration> {
+    let config = app.state::<ConfigState>().config();
+    let server = validate_server_url(&config.server_url).ok()?;
+    let started = Instant::now();
+    // An answer that the server is unhealthy still went there and back.
+    server_healthy(&config, &server).await.ok()?;
+    Some(started.elapsed())
+}
+
+/// Measure the connection every `PROBE_INTERVAL` for as long as the app runs.
+pub fn start_probing(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let rtt = measure(&app).await;
+            crate::cert_pinning::warn_if_refused(&app);
+            let report = app
+                .state::<ConnectionQuality>()
+                .0
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .record(rtt);
+            crate::menu::show_connection(&app, &report.describe());
+            #[cfg(feature = "offline-cache")]
+            crate::offline_cache::on_connection(&app, report.quality);
+            crate::draft_queue::on_connection(&app, report.quality);
+            if let Err(e) = app.emit(EVENT, &report) {
+                crate::debug_log::log_debug(&app, &format!("Failed to report connection: {e}"));
+            }
+            tokio::time::sleep(PROBE_INTERVAL).await;
+        }
+    });
+}
+
+/// The latest connection measurement, or `null` before the first one
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn get_connection_quality(state: tauri::State<ConnectionQuality>) -> Option<ConnectionReport> {
+    state
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .report
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_rating_follows_the_recent_average() {
+        let mut samples = Samples::default();
+        for _ in 0..3 {
+            samples.record(Some(Duration::from_millis(40)));
+        }
+        // One slow round trip isn't enough; a couple are.
+        let report = samples.record(Some(Duration::from_millis(900)));
+        assert_eq!(report.quality, Quality::Good);
+        assert_eq!(report.describe(), "Connection: good (900 ms)");
+        let report = samples.record(Some(Duration::from_millis(900)));
+        assert_eq!(report.quality, Quality::Degraded);
+        assert_eq!(report.average_ms, Some(470));
+
+        assert_eq!(samples.record(None).describe(), "Connection: offline");
+        assert_eq!(
+            samples.record(Some(Duration::from_millis(40))).average_ms,
+            Some(40)
+        );
+    }
+}
@@ -0,0 +1,415 @@
+// Consent for native capabilities. The first time a page asks for one on a
+// given scope (reading a folder, capturing the screen, reading the
+// clipboard), the user gets a native prompt: allow once, always allow, or
+// deny. "Always allow" and "deny" are remembered in the config per webview
+// partition -- so each profile decides for itself -- and can be reviewed and
+// revoked in the permissions window (`permissions.html`). Besides pages
+// asking up front with `request_permission`, the commands that use these
+// capabilities ask too: screenshots of a window, inserting from the
+// clipboard history, and syncing shared folders (on behalf of the main
+// window, whose user they go up as).
+//
+// Every answer, remembered or not, is also appended to an audit log in the
+// state directory (`consent_audit.log`, one JSON object per line), which is
+// where the permissions window gets "last used" from.
+
+use crate::config::{get_state_dir, ConfigState, PermissionDecision, PermissionGrant};
+use crate::debug_log::{format_utc_timestamp, log_backend_error};
+use crate::window::{WindowPartitions, PERMISSIONS_WINDOW_LABEL, SETTINGS_WINDOW_LABEL};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write as IoWrite;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, WebviewWindow};
+use tauri_plugin_dialog::{
+    DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult,
+};
+
+const ALWAYS_ALLOW_LABEL: &str = "Always Allow";
+const ALLOW_ONCE_LABEL: &str = "Allow Once";
+const DENY_LABEL: &str = "Deny";
+/// The audit log is rolled over to `consent_audit.log.1` past this size.
+const MAX_AUDIT_LOG_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    ReadFolder,
+    CaptureScreen,
+    ReadClipboard,
+}
+
+impl Capability {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::ReadFolder => "read_folder",
+            Self::CaptureScreen => "capture_screen",
+            Self::ReadClipboard => "read_clipboard",
+        }
+    }
+
+    const fn describe(self) -> &'static str {
+        match self {
+            Self::ReadFolder => "read the files in",
+            Self::CaptureScreen => "capture your screen",
+            Self::ReadClipboard => "read your clipboard",
+        }
+    }
+
+    /// Folder access is granted per folder; the others are all-or-nothing.
+    const fn requires_scope(self) -> bool {
+        matches!(self, Self::ReadFolder)
+    }
+}
+
+/// What the user picked in the prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PromptChoice {
+    AllowOnce,
+    AlwaysAllow,
+    Deny,
+    /// Closed without picking; denied this time but not remembered.
+    Dismissed,
+}
+
+impl PromptChoice {
+    /// Platforms report custom buttons either by label or as the stock
+    /// yes/no/cancel slot they were given.
+    fn from_result(result: &MessageDialogResult) -> Self {
+        match result {
+            MessageDialogResult::Yes => Self::AlwaysAllow,
+            MessageDialogResult::No => Self::AllowOnce,
+            MessageDialogResult::Custom(label) if label == ALWAYS_ALLOW_LABEL => Self::AlwaysAllow,
+            MessageDialogResult::Custom(label) if label == ALLOW_ONCE_LABEL => Self::AllowOnce,
+            MessageDialogResult::Custom(label) if label == DENY_LABEL => Self::Deny,
+            _ => Self::Dismissed,
+        }
+    }
+}
+
+/// One remembered decision, for the permissions window.
+#[derive(Debug, Clone, Serialize)]
+pub struct PermissionEntry {
+    partition: String,
+    capability: String,
+    scope: Option<String>,
+    decision: PermissionDecision,
+    decided_at: Option<String>,
+    /// When the page last got access this way, from the audit log.
+    last_used: Option<String>,
+}
+
+/// One line of the audit log.
+#[derive(Debug, Serialize, Deserialize)]
+struct AuditRecord {
+    at: String,
+    partition: String,
+    /// As `permission_key`.
+    permission: String,
+    allowed: bool,
+    /// Whether the user was asked, rather than a remembered decision applied.
+    prompted: bool,
+}
+
+/// Config key for a capability on a scope: `read_folder:/home/me/docs`, or
+/// just `capture_screen`.
+fn permission_key(capability: Capability, scope: Option<&str>) -> String {
+    scope.map_or_else(
+        || capability.as_str().to_string(),
+        |scope| format!("{}:{scope}", capability.as_str()),
+    )
+}
+
+/// Inverse of `permission_key`, as (capability, scope). Capability names
+/// have no colon, so the first one always ends the name.
+fn split_permission_key(key: &str) -> (&str, Option<&str>) {
+    key.split_once(':')
+        .map_or((key, None), |(capability, scope)| (capability, Some(scope)))
+}
+
+fn audit_log_path() -> Option<PathBuf> {
+    get_state_dir().map(|dir| dir.join("consent_audit.log"))
+}
+
+fn append_audit_record(record: &AuditRecord) -> Result<(), String> {
+    let path = audit_log_path().ok_or("No state directory")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    if fs::metadata(&path).is_ok_and(|meta| meta.len() > MAX_AUDIT_LOG_BYTES) {
+        fs::rename(&path, path.with_extension("log.1")).map_err(|e| e.to_string())?;
+    }
+
+    let line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{line}").map_err(|e| e.to_string())
+}
+
+fn audit(app: &AppHandle, partition: &str, key: &str, allowed: bool, prompted: bool) {
+    let record = AuditRecord {
+        at: format_utc_timestamp(),
+        partition: partition.to_string(),
+        permission: key.to_string(),
+        allowed,
+        prompted,
+    };
+    if let Err(e) = append_audit_record(&record) {
+        log_backend_error(app, &format!("Failed to write consent audit log: {e}"));
+    }
+}
+
+/// The last time each (partition, permission) was allowed, from audit log
+/// contents, oldest first. Lines that don't parse are skipped.
+fn last_allowed(log: &str) -> HashMap<(String, String), String> {
+    let mut last = HashMap::new();
+    for record in log
+        .lines()
+        .filter_map(|line| serde_json::from_str::<AuditRecord>(line).ok())
+        .filter(|record| record.allowed)
+    {
+        last.insert((record.partition, record.permission), record.at);
+    }
+    last
+}
+
+fn read_audit_log() -> String {
+    let Some(path) = audit_log_path() else {
+        return String::new();
+    };
+    let rolled = fs::read_to_string(path.with_extension("log.1")).unwrap_or_default();
+    rolled + &fs::read_to_string(path).unwrap_or_default()
+}
+
+async fn prompt(
+    app: &AppHandle,
+    window: &WebviewWindow,
+    capability: Capability,
+    scope: Option<&str>,
+) -> PromptChoice {
+    let requester = window
+        .url()
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| "This page".to_string());
+    let message = scope.map_or_else(
+        || format!("{requester} wants to {}.", capability.describe()),
+        |scope| format!("{requester} wants to {} {scope}.", capability.describe()),
+    );
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .message(message)
+        .title("Allow access?")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::YesNoCancelCustom(
+            ALWAYS_ALLOW_LABEL.to_string(),
+            ALLOW_ONCE_LABEL.to_string(),
+            DENY_LABEL.to_string(),
+        ))
+        .parent(window)
+        .show_with_result(move |result| {
+            let _ = tx.send(PromptChoice::from_result(&result));
+        });
+
+    rx.await.unwrap_or(PromptChoice::Dismissed)
+}
+
+/// Remembered decisions are only listed and revoked from the permissions
+/// window or Settings, never by the pages they're about.
+fn check_caller(window: &WebviewWindow) -> Result<(), String> {
+    if matches!(
+        window.label(),
+        PERMISSIONS_WINDOW_LABEL | SETTINGS_WINDOW_LABEL
+    ) {
+        Ok(())
+    } else {
+        Err("Permissions can only be managed from Settings".to_string())
+    }
+}
+
+/// Whether `window` may use `capability` on `scope`, asking the user unless
+/// they've already decided for this window's profile.
+pub async fn request_consent(
+    app: &AppHandle,
+    window: &WebviewWindow,
+    capability: Capability,
+    scope: Option<&str>,
+) -> Result<bool, String> {
+    let scope = scope.map(str::trim).filter(|scope| !scope.is_empty());
+    if capability.requires_scope() && scope.is_none() {
+        return Err(format!("{} needs a scope", capability.as_str()));
+    }
+    let scope = scope.filter(|_| capability.requires_scope());
+
+    let partition = app.state::<WindowPartitions>().partition_of(window.label());
+    let key = permission_key(capability, scope);
+    let state = app.state::<ConfigState>();
+    let remembered = state
+        .config()
+        .permissions
+        .get(&partition)
+        .and_then(|decisions| decisions.get(&key))
+        .map(|grant| grant.decision);
+    if let Some(decision) = remembered {
+        let allowed = decision == PermissionDecision::Allow;
+        audit(app, &partition, &key, allowed, false);
+        return Ok(allowed);
+    }
+
+    let decision = match prompt(app, window, capability, scope).await {
+        PromptChoice::AllowOnce => {
+            audit(app, &partition, &key, true, true);
+            return Ok(true);
+        }
+        PromptChoice::Dismissed => {
+            audit(app, &partition, &key, false, true);
+            return Ok(false);
+        }
+        PromptChoice::AlwaysAllow => PermissionDecision::Allow,
+        PromptChoice::Deny => PermissionDecision::Deny,
+    };
+    let allowed = decision == PermissionDecision::Allow;
+    audit(app, &partition, &key, allowed, true);
+    let grant = PermissionGrant {
+        decision,
+        decided_at: Some(format_utc_timestamp()),
+    };
+    state.update_and_persist(|c| {
+        c.permissions
+            .entry(partition)
+            .or_default()
+            .insert(key, grant);
+    })?;
+    Ok(allowed)
+}
+
+/// Ask for a native capability on behalf of the calling page
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub async fn request_permission(
+    window: WebviewWindow,
+    capability: Capability,
+    scope: Option<String>,
+) -> Result<bool, String> {
+    request_consent(window.app_handle(), &window, capability, scope.as_deref()).await
+}
+
+/// Every remembered decision, with when it was made and last used, for the
+/// permissions window
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn list_permissions(
+    window: WebviewWindow,
+    state: tauri::State<ConfigState>,
+) -> Result<Vec<PermissionEntry>, String> {
+    check_caller(&window)?;
+    let mut last_used = last_allowed(&read_audit_log());
+    let mut entries = Vec::new();
+    for (partition, grants) in state.config().permissions {
+        for (key, grant) in grants {
+            let (capability, scope) = split_permission_key(&key);
+            entries.push(PermissionEntry {
+                partition: partition.clone(),
+                capability: capability.to_string(),
+                scope: scope.map(str::to_string),
+                decision: grant.decision,
+                decided_at: grant.decided_at,
+                last_used: last_used.remove(&(partition.clone(), key.clone())),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// Forget a remembered decision, so the next request prompts again
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn revoke_permission(
+    window: WebviewWindow,
+    state: tauri::State<ConfigState>,
+    partition: String,
+    capability: String,
+    scope: Option<String>,
+) -> Result<(), String> {
+    check_caller(&window)?;
+    let key = match scope {
+        Some(scope) => format!("{capability}:{scope}"),
+        None => capability,
+    };
+    state.update_and_persist(|c| {
+        if let Some(decisions) = c.permissions.get_mut(&partition) {
+            decisions.remove(&key);
+            if decisions.is_empty() {
+                c.permissions.remove(&partition);
+            }
+        }
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permission_keys_round_trip() {
+        let key = permission_key(Capability::ReadFolder, Some(r"C:\Users\me\Docs"));
+        assert_eq!(key, r"read_folder:C:\Users\me\Docs");
+        assert_eq!(
+            split_permission_key(&key),
+            ("read_folder", Some(r"C:\Users\me\Docs"))
+        );
+
+        let key = permission_key(Capability::CaptureScreen, None);
+        assert_eq!(split_permission_key(&key), ("capture_screen", None));
+    }
+
+    #[test]
+    fn last_use_comes_from_the_latest_allowed_record() {
+        let log = [
+            r#"{"at":"2026-10-01T09:00:00.000Z","partition":"default","permission":"capture_screen","allowed":true,"prompted":true}"#,
+            r#"{"at":"2026-10-02T09:00:00.000Z","partition":"default","permission":"capture_screen","allowed":true,"prompted":false}"#,
+            r#"{"at":"2026-10-03T09:00:00.000Z","partition":"default","permission":"capture_screen","allowed":false,"prompted":true}"#,
+            r#"{"at":"2026-10-04T09:00:00.000Z","partition":"profile-work","permission":"capture_screen","allowed":true,"prompted":false}"#,
+            "not json",
+        ]
+        .join("\n");
+        let last = last_allowed(&log);
+        assert_eq!(last.len(), 2);
+        assert_eq!(
+            last[&("default".to_string(), "capture_screen".to_string())],
+            "2026-10-02T09:00:00.000Z"
+        );
+    }
+
+    #[test]
+    fn prompt_results_map_to_choices() {
+        let custom = |label: &str| MessageDialogResult::Custom(label.to_string());
+        assert_eq!(
+            PromptChoice::from_result(&custom(ALWAYS_ALLOW_LABEL)),
+            PromptChoice::AlwaysAllow
+        );
+        assert_eq!(
+            PromptChoice::from_result(&custom(ALLOW_ONCE_LABEL)),
+            PromptChoice::AllowOnce
+        );
+        assert_eq!(
+            PromptChoice::from_result(&MessageDialogResult::Yes),
+            PromptChoice::AlwaysAllow
+        );
+        assert_eq!(
+            PromptChoice::from_result(&custom(DENY_LABEL)),
+            PromptChoice::Deny
+        );
+        // Closing the dialog denies without remembering it.
+        assert_eq!(
+            PromptChoice::from_result(&MessageDialogResult::Cancel),
+            PromptChoice::Dismissed
+        );
+    }
+}
@@ -0,0 +1,224 @@
+// The native right-click menu for server pages. The webview's own menu is
+// missing on some platforms and different on each of the others, so
+// `context_menu.js` cancels it and asks for this one instead, passing along
+// what was clicked. What the menu acts on is kept here until an item is
+// picked, since menu events only carry the item's ID.
+
+use crate::assets::{script, Script};
+use crate::debug_log::log_backend_error;
+use crate::locale::{text, Text};
+use std::sync::Mutex;
+use tauri::menu::{Menu, MenuBuilder, MenuItem, PredefinedMenuItem};
+use tauri::{AppHandle, Manager, Webview, WebviewWindow, Wry};
+use url::Url;
+
+pub const MENU_SEARCH_SELECTION_ID: &str = "context_search_selection";
+pub const MENU_COPY_LINK_ID: &str = "context_copy_link";
+#[cfg(feature = "read-aloud")]
+pub const MENU_READ_ALOUD_ID: &str = "context_read_aloud";
+#[cfg(any(debug_assertions, feature = "devtools"))]
+pub const MENU_INSPECT_ID: &str = "context_inspect";
+
+/// Longest selection quoted in the search item's label.
+const LABEL_SELECTION_CHARS: usize = 24;
+
+/// What the open context menu was shown for.
+#[derive(Clone)]
+struct Target {
+    window: String,
+    selection: String,
+    link: Option<String>,
+}
+
+/// Managed state: the target of the last context menu shown.
+#[derive(Default)]
+pub struct ContextMenuState(Mutex<Option<Target>>);
+
+impl ContextMenuState {
+    fn target(&self) -> Option<Target> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// "Search "selection" in Onyx" (in the UI language), with long selections
+/// cut short.
+fn search_label(selection: &str) -> String {
+    let selection = selection.split_whitespace().collect::<Vec<_>>().join(" ");
+    let quoted = if selection.chars().count() > LABEL_SELECTION_CHARS {
+        let start: String = selection.chars().take(LABEL_SELECTION_CHARS).collect();
+        format!("{}\u{2026}", start.trim_end())
+    } else {
+        selection
+    };
+    text(Text::SearchInOnyx).replace("{}", &format!("\u{201c}{quoted}\u{201d}"))
+}
+
+/// A new chat on `page`'s server that asks about `selection` right away.
+fn search_url(page: &Url, selection: &str) -> Option<Url> {
+    if !matches!(page.scheme(), "http" | "https") {
+        return None;
+    }
+    Some(
+        crate::route::Route::Search {
+            query: selection.to_string(),
+        }
+        .url(page),
+    )
+}
+
+fn build_menu(
+    app: &AppHandle,
+    selection: &str,
+    link: Option<&str>,
+    editable: bool,
+) -> tauri::Result<Menu<Wry>> {
+    let has_selection = !selection.trim().is_empty();
+    let mut menu = MenuBuilder::new(app);
+    if editable {
+        menu = menu.item(&PredefinedMenuItem::cut(app, None)?);
+    }
+    if has_selection || editable {
+        menu = menu.item(&PredefinedMenuItem::copy(app, None)?);
+    }
+    if editable {
+        menu = menu.item(&PredefinedMenuItem::paste(app, None)?);
+    }
+    menu = menu.item(&PredefinedMenuItem::select_all(app, None)?);
+
+    if has_selection {
+        menu = menu.separator().item(&MenuItem::with_id(
+            app,
+            MENU_SEARCH_SELECTION_ID,
+            search_label(selection),
+            true,
+            None::<&str>,
+        )?);
+        #[cfg(feature = "read-aloud")]
+        {
+            menu = menu.item(&MenuItem::with_id(
+                app,
+                MENU_READ_ALOUD_ID,
+                "Read Aloud",
+                true,
+                None::<&str>,
+            )?);
+        }
+    }
+    if link.is_some() {
+        menu = menu.separator().item(&MenuItem::with_id(
+            app,
+            MENU_COPY_LINK_ID,
+            text(Text::CopyLink),
+            true,
+            None::<&str>,
+        )?);
+    }
+
+    #[cfg(any(debug_assertions, feature = "devtools"))]
+    if !crate::kiosk::is_active() {
+        menu = menu.separator().item(&MenuItem::with_id(
+            app,
+            MENU_INSPECT_ID,
+            "Inspect",
+            true,
+            None::<&str>,
+        )?);
+    }
+
+    menu.build()
+}
+
+pub fn inject_context_menu_script(webview: &Webview) {
+    let script = script(webview.app_handle(), Script::ContextMenu);
+    crate::watchdog::eval(webview, &*script, "inject context menu script");
+}
+
+/// Show the context menu for a right-click on the calling page (see
+/// `context_menu.js`)
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn show_context_menu(
+    window: WebviewWindow,
+    selection: String,
+    link: Option<String>,
+    editable: bool,
+) -> Result<(), String> {
+    let app = window.app_handle();
+    let menu = build_menu(app, &selection, link.as_deref(), editable).map_err(|e| e.to_string())?;
+    *app.state::<ContextMenuState>()
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(Target {
+        window: window.label().to_string(),
+        selection,
+        link,
+    });
+    window.popup_menu(&menu).map_err(|e| e.to_string())
+}
+
+/// Run the context menu item `id` on the window it was opened in.
+pub fn handle_menu_event(app: &AppHandle, id: &str) {
+    let Some(target) = app.state::<ContextMenuState>().target() else {
+        return;
+    };
+    let Some(window) = app.get_webview_window(&target.window) else {
+        return;
+    };
+
+    match id {
+        MENU_SEARCH_SELECTION_ID => {
+            let Some(url) = window
+                .url()
+                .ok()
+                .and_then(|page| search_url(&page, &target.selection))
+            else {
+                return;
+            };
+            if let Err(e) = window.navigate(url) {
+                log_backend_error(app, &format!("Failed to search selection: {e}"));
+            }
+        }
+        MENU_COPY_LINK_ID => {
+            let Some(link) = target.link else {
+                return;
+            };
+            // Serialized as a JS string literal, quotes and all.
+            let text = serde_json::Value::String(link).to_string();
+            crate::watchdog::eval(
+                window.as_ref(),
+                format!("window.__ONYX_CONTEXT_MENU__?.copyText({text})"),
+                "copy link",
+            );
+        }
+        #[cfg(feature = "read-aloud")]
+        MENU_READ_ALOUD_ID => crate::read_aloud::speak(app, &target.selection),
+        #[cfg(any(debug_assertions, feature = "devtools"))]
+        MENU_INSPECT_ID => window.open_devtools(),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn searches_start_a_chat_on_the_page_server() {
+        let page = Url::parse("https://cloud.onyx.app/app?chatId=123").unwrap();
+        assert_eq!(
+            search_url(&page, " quarterly revenue ").unwrap().as_str(),
+            "https://cloud.onyx.app/chat?user-prompt=quarterly+revenue&send-on-load=true"
+        );
+        assert!(search_url(&Url::parse("tauri://localhost/").unwrap(), "x").is_none());
+
+        assert_eq!(search_label("a\n  b"), "Search \u{201c}a b\u{201d} in Onyx");
+        assert_eq!(
+            search_label("the quick brown fox jumps over the lazy dog"),
+            "Search \u{201c}the quick brown fox jump\u{2026}\u{201d} in Onyx"
+        );
+    }
+}
@@ -0,0 +1,435 @@
+// Crash reports, kept in `crashes/` in the state directory. A panic writes
+// a JSON report (the message, where it happened, a backtrace, the version
+// and platform) from the panic hook. A native crash (say, in a webview) gets
+// a minidump, written by a second copy of the app started alongside with the
+// hidden `--crash-server` flag, since a crashed process can't be trusted to
+// dump itself; that copy writes the report next to it and exits with the
+// app.
+//
+// Nothing leaves the machine unless `crash_reports.upload` is on. Then the
+// reports left by earlier runs are sent to `crash_reports.endpoint` at the
+// next launch (a multipart POST with the report as `report` and any
+// minidump as `upload_file_minidump`, which crash collectors take) and
+// deleted once it has them. Otherwise only the newest few are kept.
+
+use crate::config::{get_state_dir, http_client, ConfigState, CrashReportSettings};
+use crate::debug_log::{format_utc_timestamp, log_backend_error, log_debug};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use url::Url;
+
+const CRASHES_DIR: &str = "crashes";
+const REPORT_PREFIX: &str = "crash-";
+/// Reports kept without consent to upload them.
+const KEEP_LOCAL: usize = 10;
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long the crash server gets to start listening.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CrashKind {
+    Panic,
+    Native,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CrashReport {
+    kind: CrashKind,
+    timestamp: String,
+    version: String,
+    os: String,
+    arch: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    thread: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    backtrace: Option<String>,
+    /// The minidump's file name, next to the report.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    minidump: Option<String>,
+}
+
+impl CrashReport {
+    fn new(kind: CrashKind, timestamp: String) -> Self {
+        Self {
+            kind,
+            timestamp,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            message: None,
+            location: None,
+            thread: None,
+            backtrace: None,
+            minidump: None,
+        }
+    }
+}
+
+fn crashes_dir() -> Option<PathBuf> {
+    get_state_dir().map(|dir| dir.join(CRASHES_DIR))
+}
+
+/// `crash-<time>-<pid>`, spelled so every platform accepts it as a file
+/// name and sorting by name sorts by time.
+fn report_stem(timestamp: &str) -> String {
+    format!(
+        "{REPORT_PREFIX}{}-{}",
+        timestamp.replace(':', "-"),
+        std::process::id()
+    )
+}
+
+fn write_report(dir: &Path, stem: &str, report: &CrashReport) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(report).map_err(|e| e.to_string())?;
+    fs::write(dir.join(format!("{stem}.json")), json).map_err(|e| e.to_string())
+}
+
+/// The panic message, which is a `&str` or a `String` unless someone
+/// panicked with something else.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> Option<String> {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| (*message).to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+}
+
+/// Write a report for every panic, then carry on as before (printing it).
+#[allow(clippy::print_stderr)]
+fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let timestamp = format_utc_timestamp();
+        let stem = report_stem(&timestamp);
+        let mut report = CrashReport::new(CrashKind::Panic, timestamp);
+        report.message = panic_message(info.payload());
+        report.location = info.location().map(ToString::to_string);
+        report.thread = std::thread::current().name().map(str::to_string);
+        report.backtrace = Some(std::backtrace::Backtrace::force_capture().to_string());
+        if let Some(dir) = crashes_dir() {
+            if let Err(e) = write_report(&dir, &stem, &report) {
+                eprintln!("[ONYX ERROR] Failed to write crash report: {e}");
+            }
+        }
+        previous(info);
+    }));
+}
+
+/// Start catching panics and native crashes. Call once, early, in the app's
+/// own process (not the crash server's); `data_dir` is the portable or
+/// `--data-dir` folder, if there is one, for the crash server to use too.
+#[allow(clippy::print_stderr)]
+pub fn install(data_dir: Option<&Path>) {
+    install_panic_hook();
+    // Attached off the main thread: the crash server takes a moment to
+    // start, and startup shouldn't wait for it.
+    let data_dir = data_dir.map(Path::to_path_buf);
+    std::thread::spawn(move || {
+        if let Err(e) = attach_native_handler(data_dir.as_deref()) {
+            eprintln!("[ONYX ERROR] Native crashes won't be reported: {e}");
+        }
+    });
+}
+
+fn socket_path() -> PathBuf {
+    std::env::temp_dir().join(format!("onyx-crash-{}.sock", std::process::id()))
+}
+
+fn attach_native_handler(data_dir: Option<&Path>) -> Result<(), String> {
+    let socket = socket_path();
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let mut command = std::process::Command::new(exe);
+    command.arg("--crash-server").arg(&socket);
+    if let Some(dir) = data_dir {
+        command.arg("--data-dir").arg(dir);
+    }
+    let server = command
+        .spawn()
+        .map_err(|e| format!("Failed to start the crash server: {e}"))?;
+
+    let started = std::time::Instant::now();
+    let client = loop {
+        match minidumper::Client::with_name(socket.as_path()) {
+            Ok(client) => break client,
+            Err(e) if started.elapsed() > CONNECT_TIMEOUT => {
+                return Err(format!("Failed to reach the crash server: {e}"));
+            }
+            Err(_) => std::thread::sleep(Duration::from_millis(50)),
+        }
+    };
+
+    // SAFETY: the handler only sends the crash context to the crash server,
+    // which is all that's safe to do in a crashed process.
+    let handler = crash_handler::CrashHandler::attach(unsafe {
+        crash_handler::make_crash_event(move |context: &crash_handler::CrashContext| {
+            crash_handler::CrashEventResult::Handled(client.request_dump(context).is_ok())
+        })
+    })
+    .map_err(|e| e.to_string())?;
+    // The crash server reads this process's memory to write the dump.
+    #[cfg(target_os = "linux")]
+    handler.set_ptracer(Some(server.id()));
+    #[cfg(not(target_os = "linux"))]
+    let _ = server;
+    // Detaching is all dropping the handler does, and it has to stay
+    // attached for as long as the app runs.
+    std::mem::forget(handler);
+    Ok(())
+}
+
+/// Writes the minidumps (and their reports) the app asks for.
+struct CrashServer {
+    dir: PathBuf,
+    /// The stem of the dump being written.
+    stem: std::sync::Mutex<Option<String>>,
+}
+
+impl minidumper::ServerHandler for CrashServer {
+    fn create_minidump_file(&self) -> Result<(fs::File, PathBuf), std::io::Error> {
+        fs::create_dir_all(&self.dir)?;
+        let stem = report_stem(&format_utc_timestamp());
+        let path = self.dir.join(format!("{stem}.dmp"));
+        *self
+            .stem
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(stem);
+        Ok((fs::File::create(&path)?, path))
+    }
+
+    #[allow(clippy::print_stderr)]
+    fn on_minidump_created(
+        &self,
+        result: Result<minidumper::MinidumpBinary, minidumper::Error>,
+    ) -> minidumper::LoopAction {
+        let stem = self
+            .stem
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .take();
+        match (result, stem) {
+            (Ok(binary), Some(stem)) => {
+                let mut report = CrashReport::new(CrashKind::Native, format_utc_timestamp());
+                report.minidump = binary
+                    .path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned());
+                if let Err(e) = write_report(&self.dir, &stem, &report) {
+                    eprintln!("[ONYX ERROR] Failed to write crash report: {e}");
+                }
+            }
+            (Err(e), _) => eprintln!("[ONYX ERROR] Failed to write minidump: {e}"),
+            (Ok(_), None) => {}
+        }
+        // The app is gone after a crash.
+        minidumper::LoopAction::Exit
+    }
+
+    fn on_message(&self, _kind: u32, _buffer: Vec<u8>) {}
+
+    fn on_client_disconnected(&self, clients: usize) -> minidumper::LoopAction {
+        if clients == 0 {
+            minidumper::LoopAction::Exit
+        } else {
+            minidumper::LoopAction::Continue
+        }
+    }
+}
+
+/// Run as the crash server for the app listening on `socket` (the hidden
+/// `--crash-server`), until the app exits or crashes.
+pub fn run_server(socket: &Path) -> Result<(), String> {
+    let dir = crashes_dir().ok_or("Could not determine state directory")?;
+    let mut server = minidumper::Server::with_name(socket).map_err(|e| e.to_string())?;
+    let shutdown = std::sync::atomic::AtomicBool::new(false);
+    let handler = CrashServer {
+        dir,
+        stem: std::sync::Mutex::new(None),
+    };
+    let result = server
+        .run(Box::new(handler), &shutdown, None)
+        .map_err(|e| e.to_string());
+    let _ = fs::remove_file(socket);
+    result
+}
+
+/// Check the crash report settings submitted from the settings window.
+pub fn validate(settings: &CrashReportSettings) -> Result<(), String> {
+    match &settings.endpoint {
+        Some(endpoint) => {
+            let url =
+                Url::parse(endpoint).map_err(|e| format!("Invalid crash report endpoint: {e}"))?;
+            if url.scheme() != "https" {
+                return Err("The crash report endpoint must start with https://".to_string());
+            }
+        }
+        None if settings.upload => {
+            return Err("Set `crash_reports.endpoint` to send crash reports".to_string());
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+/// The reports in `dir`, oldest first.
+fn reports(dir: &Path) -> Vec<PathBuf> {
+    let mut reports: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+                && path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with(REPORT_PREFIX))
+        })
+        .collect();
+    reports.sort();
+    reports
+}
+
+/// The minidump `report` refers to, if any.
+fn minidump_of(dir: &Path, report: &CrashReport) -> Option<PathBuf> {
+    // Only a bare file name, so a report can't point outside `dir`.
+    report
+        .minidump
+        .as_deref()
+        .filter(|name| Path::new(name).file_name() == Some(std::ffi::OsStr::new(name)))
+        .map(|name| dir.join(name))
+}
+
+fn read_report(path: &Path) -> Result<CrashReport, String> {
+    let json = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
+
+fn delete_report(dir: &Path, path: &Path) {
+    if let Some(minidump) = read_report(path)
+        .ok()
+        .and_then(|report| minidump_of(dir, &report))
+    {
+        let _ = fs::remove_file(minidump);
+    }
+    let _ = fs::remove_file(path);
+}
+
+/// All but the newest `keep` of `reports` (oldest first).
+fn stale(reports: &[PathBuf], keep: usize) -> &[PathBuf] {
+    &reports[..reports.len().saturating_sub(keep)]
+}
+
+async fn upload(
+    client: &reqwest::Client,
+    endpoint: &str,
+    dir: &Path,
+    path: &Path,
+) -> Result<(), String> {
+    let json = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let report: CrashReport = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    let mut form = reqwest::multipart::Form::new().text("report", json);
+    if let Some(minidump) = minidump_of(dir, &report).filter(|path| path.exists()) {
+        let name = minidump
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let bytes = fs::read(&minidump).map_err(|e| e.to_string())?;
+        form = form.part(
+            "upload_file_minidump",
+            reqwest::multipart::Part::bytes(bytes)
+                .file_name(name)
+                .mime_str("application/octet-stream")
+                .map_err(|e| e.to_string())?,
+        );
+    }
+    let response = client
+        .post(endpoint)
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("The endpoint answered {}", response.status()));
+    }
+    Ok(())
+}
+
+/// Send the reports earlier runs left, if the user agreed to; otherwise
+/// only keep the newest few.
+pub fn upload_pending(app: &AppHandle) {
+    let config = app.state::<ConfigState>().config();
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let Some(dir) = crashes_dir() else {
+            return;
+        };
+        let pending = reports(&dir);
+        let endpoint = config
+            .crash_reports
+            .endpoint
+            .as_deref()
+            .filter(|_| config.crash_reports.upload);
+        let Some(endpoint) = endpoint else {
+            for path in stale(&pending, KEEP_LOCAL) {
+                delete_report(&dir, path);
+            }
+            return;
+        };
+        if pending.is_empty() {
+            return;
+        }
+        let client = match http_client(&config, UPLOAD_TIMEOUT) {
+            Ok(client) => client,
+            Err(e) => {
+                log_backend_error(&app, &format!("Failed to send crash reports: {e}"));
+                return;
+            }
+        };
+        for path in &pending {
+            // Kept for the next launch if it didn't go through.
+            if let Err(e) = upload(&client, endpoint, &dir, path).await {
+                log_backend_error(&app, &format!("Failed to send a crash report: {e}"));
+                return;
+            }
+            delete_report(&dir, path);
+        }
+        log_debug(&app, &format!("Sent {} crash report(s)", pending.len()));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_newest_reports_are_kept() {
+        let reports: Vec<PathBuf> = (1..=12)
+            .map(|n| PathBuf::from(format!("crash-{n:02}.json")))
+            .collect();
+        assert_eq!(
+            stale(&reports, KEEP_LOCAL),
+            &[
+                PathBuf::from("crash-01.json"),
+                PathBuf::from("crash-02.json")
+            ]
+        );
+        assert!(stale(&reports[..3], KEEP_LOCAL).is_empty());
+
+        let mut report = CrashReport::new(CrashKind::Native, String::new());
+        report.minidump = Some("../config.toml".to_string());
+        assert_eq!(minidump_of(Path::new("crashes"), &report), None);
+        report.minidump = Some("crash-01.dmp".to_string());
+        assert_eq!(
+            minidump_of(Path::new("crashes"), &report),
+            Some(PathBuf::from("crashes/crash-01.dmp"))
+        );
+    }
+}
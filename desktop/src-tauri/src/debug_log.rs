@@ -3,25 +3,61 @@
 // the point, not an oversight.
 #![allow(clippy::print_stderr)]
 
-use crate::config::{get_config_dir, ConfigState};
+use crate::config::{get_state_dir, ConfigState};
 use crate::window::open_in_default_browser;
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::Write as IoWrite;
 use std::path::PathBuf;
+use std::sync::Mutex;
 use std::time::SystemTime;
 use tauri::{AppHandle, Manager, Webview};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
 
 pub const MENU_TOGGLE_DEVTOOLS_ID: &str = "toggle_devtools";
 pub const MENU_OPEN_DEBUG_LOG_ID: &str = "open_debug_log";
 
-const CONSOLE_CAPTURE_SCRIPT: &str = include_str!("scripts/console_capture.js");
+/// How many times one kind of failure happens in a run before the user is
+/// told about it.
+const RECURRING_AFTER: u32 = 3;
+
+/// Managed state: how often each kind of backend failure happened this run
+/// (see `failure_kind`).
+#[derive(Default)]
+pub struct BackendFailures(Mutex<BTreeMap<String, u32>>);
+
+impl BackendFailures {
+    /// Count one failure of `kind`, returning how many there have been.
+    fn record(&self, kind: &str) -> u32 {
+        *self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .entry(kind.to_string())
+            .and_modify(|count| *count += 1)
+            .or_insert(1)
+    }
+
+    pub fn counts(&self) -> BTreeMap<String, u32> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// What a failure message is about, without the error that follows it:
+/// "Failed to set vibrancy: unsupported" is a "Failed to set vibrancy".
+fn failure_kind(message: &str) -> &str {
+    message.split(": ").next().unwrap_or(message).trim()
+}
 
 pub fn is_debug_mode(cli_debug: bool) -> bool {
     cli_debug || std::env::var("ONYX_DEBUG").is_ok()
 }
 
 pub fn get_debug_log_path() -> Option<PathBuf> {
-    get_config_dir().map(|dir| dir.join("frontend_debug.log"))
+    get_state_dir().map(|dir| dir.join("frontend_debug.log"))
 }
 
 pub fn init_debug_log_file() -> Option<fs::File> {
@@ -82,47 +118,78 @@ pub fn format_utc_timestamp() -> String {
     format!("{y:04}-{m:02}-{d:02}T{hours:02}:{mins:02}:{secs:02}.{millis:03}Z")
 }
 
-/// Surface a Rust-side failure the same way frontend errors already are:
-/// always to stderr, and also into the debug log file when debug mode is on.
-/// Used in place of silently swallowing a `Result` with `let _ = ...` for
-/// failures worth knowing about.
-pub fn log_backend_error(app: &AppHandle, message: &str) {
-    eprintln!("[ONYX ERROR] {message}");
-
-    let state = app.state::<ConfigState>();
-    if !state.debug_mode {
-        return;
-    }
+/// Append one timestamped line to the debug log file, if one is open.
+fn append_to_debug_log(state: &ConfigState, level: &str, message: &str) {
     // Bind the lock result to a named local rather than matching on it
     // directly in the `if let` scrutinee: `if let Ok(x) = mutex.lock() { }`
     // extends the whole `Result<MutexGuard, _>` temporary's lifetime to the
-    // end of the enclosing block, which the borrow checker rejects here
-    // since that block also owns `state` (the guard's ultimate borrow
-    // source). A named binding is scoped by normal liveness instead.
+    // end of the enclosing block, which the borrow checker rejects when that
+    // block also owns the guard's ultimate borrow source. A named binding is
+    // scoped by normal liveness instead.
     let lock_result = state.debug_log_file.lock();
     if let Ok(mut guard) = lock_result {
         if let Some(ref mut file) = *guard {
-            let line = format!("[{}] [ERROR] {}", format_utc_timestamp(), message);
+            let line = format!("[{}] [{level}] {message}", format_utc_timestamp());
             let _ = writeln!(file, "{line}");
             let _ = file.flush();
         }
     }
 }
 
-pub fn inject_console_capture(webview: &Webview) {
-    if let Err(e) = webview.eval(CONSOLE_CAPTURE_SCRIPT) {
-        log_backend_error(
-            webview.app_handle(),
-            &format!("Failed to inject console-capture script: {e}"),
-        );
+/// Surface a Rust-side failure the same way frontend errors already are:
+/// always to stderr, and also into the debug log file when debug mode is on.
+/// Used in place of silently swallowing a `Result` with `let _ = ...` for
+/// failures worth knowing about. Failures are counted by kind, and one that
+/// keeps happening is shown to the user once, since a failure that's only
+/// logged is one nobody reports.
+pub fn log_backend_error(app: &AppHandle, message: &str) {
+    eprintln!("[ONYX ERROR] {message}");
+
+    let state = app.state::<ConfigState>();
+    if state.debug_mode {
+        append_to_debug_log(&state, "ERROR", message);
+    }
+
+    let kind = failure_kind(message);
+    if app.state::<BackendFailures>().record(kind) == RECURRING_AFTER {
+        notify_recurring_failure(app, kind);
     }
 }
 
+fn notify_recurring_failure(app: &AppHandle, kind: &str) {
+    app.dialog()
+        .message(format!(
+            "Onyx ran into the same problem {RECURRING_AFTER} times: \"{kind}\". \
+             Some things may not work until you restart the app. If it keeps \
+             happening, please report it; running with --debug records the \
+             details in the debug log."
+        ))
+        .title("Something Keeps Going Wrong")
+        .kind(MessageDialogKind::Warning)
+        .show(|_| {});
+}
+
+/// Diagnostic output that's only useful when actively debugging (timings,
+/// state dumps). A no-op outside debug mode.
+pub fn log_debug(app: &AppHandle, message: &str) {
+    let state = app.state::<ConfigState>();
+    if !state.debug_mode {
+        return;
+    }
+    eprintln!("[ONYX DEBUG] {message}");
+    append_to_debug_log(&state, "DEBUG", message);
+}
+
+pub fn inject_console_capture(webview: &Webview) {
+    let script = crate::assets::script(webview.app_handle(), crate::assets::Script::ConsoleCapture);
+    crate::watchdog::eval(webview, &*script, "inject console-capture script");
+}
+
 pub fn maybe_open_devtools(app: &AppHandle, window: &tauri::WebviewWindow) {
     #[cfg(any(debug_assertions, feature = "devtools"))]
     {
         let state = app.state::<ConfigState>();
-        if state.debug_mode {
+        if state.debug_mode && !crate::kiosk::is_active() {
             window.open_devtools();
         }
     }
@@ -134,7 +201,7 @@ pub fn maybe_open_devtools(app: &AppHandle, window: &tauri::WebviewWindow) {
 
 pub fn handle_toggle_devtools(app: &AppHandle) {
     #[cfg(any(debug_assertions, feature = "devtools"))]
-    {
+    if !crate::kiosk::is_active() {
         let windows: Vec<_> = app.webview_windows().into_values().collect();
         let any_open = windows.iter().any(tauri::WebviewWindow::is_devtools_open);
         for window in &windows {
@@ -200,7 +267,20 @@ pub fn log_from_frontend(level: String, message: String, state: tauri::State<Con
 
 #[cfg(test)]
 mod tests {
-    use super::format_utc_timestamp;
+    use super::{failure_kind, format_utc_timestamp};
+
+    #[test]
+    fn failures_are_grouped_without_their_error() {
+        assert_eq!(
+            failure_kind("Failed to set vibrancy: unsupported"),
+            "Failed to set vibrancy"
+        );
+        assert_eq!(
+            failure_kind("Failed to navigate to /chat: webview gone: closed"),
+            "Failed to navigate to /chat"
+        );
+        assert_eq!(failure_kind("Server unreachable"), "Server unreachable");
+    }
 
     #[test]
     fn timestamp_has_expected_shape() {
@@ -0,0 +1,106 @@
+// Aggregated runtime diagnostics for bug reports and performance budgets.
+// Subsystems record into `Diagnostics` (managed state); `get_diagnostics`
+// returns everything in one snapshot.
+
+use crate::assets::{ScriptSource, Scripts};
+#[cfg(feature = "bridge-bench")]
+use crate::bridge_bench::BridgeBenchmark;
+use crate::capabilities::{compiled_capabilities, Capabilities};
+use crate::debug_log::BackendFailures;
+use crate::install_scope::{
+    install_scope, managed_policy, updates_require_elevation, InstallScope, ManagedPolicy,
+};
+use crate::sandbox::{sandbox, Sandbox};
+use crate::server_features::{FeatureFlags, ServerFeatures};
+use crate::startup_timing::{StartupProfiler, StartupReport};
+use crate::watchdog::{Watchdog, WebviewHealth};
+use crate::window::TitlebarFallbacks;
+use serde::Serialize;
+use std::collections::BTreeMap;
+#[cfg(feature = "bridge-bench")]
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+#[derive(Default)]
+pub struct Diagnostics {
+    #[cfg(feature = "bridge-bench")]
+    bridge_benchmark: Mutex<Option<BridgeBenchmark>>,
+}
+
+impl Diagnostics {
+    #[cfg(feature = "bridge-bench")]
+    pub fn set_bridge_benchmark(&self, result: BridgeBenchmark) {
+        *self
+            .bridge_benchmark
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(result);
+    }
+
+    #[cfg(feature = "bridge-bench")]
+    fn bridge_benchmark(&self) -> Option<BridgeBenchmark> {
+        self.bridge_benchmark
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+}
+
+#[derive(Serialize)]
+pub struct DiagnosticsReport {
+    client_version: &'static str,
+    platform: &'static str,
+    arch: &'static str,
+    /// `portable.flag` next to the executable: config lives in `data/` beside it.
+    portable: bool,
+    install_scope: InstallScope,
+    /// Per-machine Windows installs are updated by an admin-run installer.
+    updates_require_elevation: bool,
+    managed_policy: ManagedPolicy,
+    /// Flatpak or Snap; links and files then go through the XDG portals.
+    sandbox: Option<Sandbox>,
+    /// False without a compositor; windows are then opaque and framed.
+    transparency_supported: bool,
+    /// Profiles (webview partitions) whose server blocked the injected
+    /// titlebar, with why; they use the native titlebar instead.
+    titlebar_fallbacks: BTreeMap<String, String>,
+    capabilities: Capabilities,
+    startup: StartupReport,
+    /// Where each injected script loaded so far came from (bundled or a
+    /// hotfix override), by file name.
+    scripts: BTreeMap<&'static str, ScriptSource>,
+    /// Feature flags each connected server reported, by origin.
+    server_features: BTreeMap<String, FeatureFlags>,
+    /// How often each kind of logged backend failure happened this run.
+    backend_failures: BTreeMap<String, u32>,
+    /// Windows that had an `eval` fail or missed a heartbeat, by label.
+    webview_health: BTreeMap<String, WebviewHealth>,
+    /// Only populated when launched with the hidden `--bench-bridge` flag.
+    #[cfg(feature = "bridge-bench")]
+    bridge_benchmark: Option<BridgeBenchmark>,
+}
+
+/// Snapshot of everything the desktop shell knows about its own health.
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn get_diagnostics(app: AppHandle) -> DiagnosticsReport {
+    DiagnosticsReport {
+        client_version: env!("CARGO_PKG_VERSION"),
+        platform: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        portable: crate::portable::portable_data_dir().is_some(),
+        install_scope: install_scope(),
+        updates_require_elevation: updates_require_elevation(),
+        managed_policy: managed_policy().clone(),
+        sandbox: sandbox(),
+        transparency_supported: crate::window::supports_transparency(),
+        titlebar_fallbacks: app.state::<TitlebarFallbacks>().all(),
+        capabilities: compiled_capabilities(),
+        startup: app.state::<StartupProfiler>().report(),
+        scripts: app.state::<Scripts>().sources(),
+        server_features: app.state::<ServerFeatures>().all(),
+        backend_failures: app.state::<BackendFailures>().counts(),
+        webview_health: app.state::<Watchdog>().report(),
+        #[cfg(feature = "bridge-bench")]
+        bridge_benchmark: app.state::<Diagnostics>().bridge_benchmark(),
+    }
+}
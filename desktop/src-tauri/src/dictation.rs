@@ -0,0 +1,361 @@
+// Dictation: Edit > Start Dictation (or `shortcuts.dictation`) records the
+// default microphone and types what's said into the focused chat window's
+// message box as it goes, for prompting hands-free. The recording is cut at
+// each pause and the pieces are transcribed in turn, by the server as the
+// window's user (the same endpoint as push to talk) or, with
+// `voice.dictation_endpoint` set, by a local Whisper server so the audio
+// stays on the machine. The same shortcut stops it, sending what's left.
+//
+// Like push to talk, the stream lives on a thread of its own (see
+// `push_to_talk.rs`); that thread hands each piece to a task that
+// transcribes them one at a time, so they land in the order they were said.
+
+use crate::config::{validate_server_url, ConfigState, VoiceSettings};
+use crate::debug_log::log_backend_error;
+use crate::push_to_talk::{open_microphone, wav, Transcription, TRANSCRIBE_PATH};
+use crate::server_api::post_audio_as_window_user;
+use crate::window::is_bundled_page_window;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, WebviewWindow};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use url::Url;
+
+/// How often the recording is checked for a pause to cut at.
+const LISTEN: Duration = Duration::from_millis(200);
+/// Pieces are at least this long, so a breath isn't sent on its own.
+const MIN_PIECE: Duration = Duration::from_secs(1);
+/// And at most this long, for someone who doesn't pause.
+const MAX_PIECE: Duration = Duration::from_secs(15);
+/// Quiet for this long is a pause.
+const PAUSE: Duration = Duration::from_millis(600);
+/// Loudness is measured over frames this long.
+const FRAME: Duration = Duration::from_millis(50);
+/// A frame quieter than this (RMS, of 32767) is silence.
+const SILENCE: f64 = 500.0;
+/// A local model can be slow on a laptop's CPU.
+const LOCAL_TIMEOUT: Duration = Duration::from_mins(1);
+
+struct Session {
+    stop: mpsc::Sender<()>,
+}
+
+/// Managed state: the dictation under way.
+#[derive(Default)]
+pub struct Dictation(Mutex<Option<Session>>);
+
+impl Dictation {
+    fn session(&self) -> std::sync::MutexGuard<'_, Option<Session>> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// Register the dictation state.
+pub fn manage(builder: tauri::Builder<tauri::Wry>) -> tauri::Builder<tauri::Wry> {
+    builder.manage(Dictation::default())
+}
+
+fn samples_in(duration: Duration, sample_rate: u32) -> usize {
+    usize::try_from(
+        u64::from(sample_rate) * u64::try_from(duration.as_millis()).unwrap_or(0) / 1000,
+    )
+    .unwrap_or(usize::MAX)
+}
+
+fn is_quiet(frame: &[i16]) -> bool {
+    if frame.is_empty() {
+        return true;
+    }
+    let energy: f64 = frame.iter().map(|&s| f64::from(s) * f64::from(s)).sum();
+    let len = f64::from(u32::try_from(frame.len()).unwrap_or(u32::MAX));
+    (energy / len).sqrt() < SILENCE
+}
+
+/// Whether `samples` are silence throughout.
+fn is_silent(samples: &[i16], sample_rate: u32) -> bool {
+    samples
+        .chunks(samples_in(FRAME, sample_rate).max(1))
+        .all(is_quiet)
+}
+
+/// How many of `samples` make the next piece to transcribe: all of them
+/// once they end in a pause (or reach `MAX_PIECE`); `None` while the
+/// speaker may still be mid-sentence.
+fn cut_point(samples: &[i16], sample_rate: u32) -> Option<usize> {
+    let len = samples.len();
+    if len < samples_in(MIN_PIECE, sample_rate) {
+        return None;
+    }
+    if len >= samples_in(MAX_PIECE, sample_rate) {
+        return Some(len);
+    }
+    let pause = samples_in(PAUSE, sample_rate);
+    is_silent(&samples[len.saturating_sub(pause)..], sample_rate).then_some(len)
+}
+
+/// Record until `stop` says to, sending each piece that isn't silence to
+/// `pieces` as a WAV file. `started` hears once the stream is running, or
+/// why it isn't.
+fn record(
+    stop: &mpsc::Receiver<()>,
+    started: &mpsc::Sender<Result<(), String>>,
+    pieces: &UnboundedSender<Vec<u8>>,
+) {
+    let samples = Arc::new(Mutex::new(Vec::new()));
+    // Pieces are taken out as they're cut, so this only holds one and a bit.
+    let (stream, sample_rate) = match open_microphone(&samples, MAX_PIECE * 2) {
+        Ok(opened) => opened,
+        Err(e) => {
+            let _ = started.send(Err(e));
+            return;
+        }
+    };
+    let _ = started.send(Ok(()));
+
+    let send = |piece: Vec<i16>| {
+        if !is_silent(&piece, sample_rate) {
+            let _ = pieces.send(wav(&piece, sample_rate));
+        }
+    };
+    let take = |cut: fn(&[i16], u32) -> Option<usize>| {
+        let mut samples = samples
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        cut(&samples, sample_rate).map(|n| samples.drain(..n).collect::<Vec<_>>())
+    };
+    while stop.recv_timeout(LISTEN) == Err(mpsc::RecvTimeoutError::Timeout) {
+        if let Some(piece) = take(cut_point) {
+            send(piece);
+        }
+    }
+    drop(stream);
+    if let Some(rest) = take(|samples, _| Some(samples.len())) {
+        send(rest);
+    }
+}
+
+/// What was said in `wav`: by `voice.dictation_endpoint` when it's set,
+/// otherwise by `window`'s server as its user. `None` when nothing was.
+async fn transcribe(
+    app: &AppHandle,
+    window: &WebviewWindow,
+    wav: Vec<u8>,
+) -> Result<Option<String>, String> {
+    let config = app.state::<ConfigState>().config();
+    let transcription: Transcription = if let Some(endpoint) = &config.voice.dictation_endpoint {
+        transcribe_locally(endpoint, wav).await?
+    } else {
+        let server = validate_server_url(&config.server_url)?;
+        post_audio_as_window_user(window, &server, TRANSCRIBE_PATH, wav)
+            .await?
+            .ok_or("Log in to Onyx to dictate")?
+    };
+    let text = transcription.text.trim();
+    Ok((!text.is_empty()).then(|| text.to_string()))
+}
+
+/// Send `wav` to a Whisper server the way whisper.cpp's `/inference` and
+/// OpenAI-style `/v1/audio/transcriptions` endpoints both take it.
+async fn transcribe_locally(endpoint: &str, wav: Vec<u8>) -> Result<Transcription, String> {
+    let file = reqwest::multipart::Part::bytes(wav)
+        .file_name("dictation.wav")
+        .mime_str("audio/wav")
+        .map_err(|e| e.to_string())?;
+    let form = reqwest::multipart::Form::new()
+        .part("file", file)
+        .text("model", "whisper-1")
+        .text("response_format", "json");
+    // It's on this machine, so the proxy and pinned certificates for the
+    // server don't apply.
+    let client = reqwest::Client::builder()
+        .timeout(LOCAL_TIMEOUT)
+        .no_proxy()
+        .build()
+        .map_err(|e| e.to_string())?;
+    client
+        .post(endpoint)
+        .multipart(form)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| format!("The dictation endpoint didn't answer: {e}"))?
+        .text()
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))
+        .map_err(|e| format!("The dictation endpoint's answer can't be read: {e}"))
+}
+
+/// Type what's said in each of `pieces` into `window`'s chat box, until
+/// they run out or one can't be transcribed.
+async fn transcribe_pieces(
+    app: AppHandle,
+    window: WebviewWindow,
+    mut pieces: UnboundedReceiver<Vec<u8>>,
+) {
+    let mut first = true;
+    while let Some(wav) = pieces.recv().await {
+        if app.get_webview_window(window.label()).is_none() {
+            stop(&app);
+            return;
+        }
+        match transcribe(&app, &window, wav).await {
+            Ok(Some(text)) => {
+                let text = if first { text } else { format!(" {text}") };
+                first = false;
+                match serde_json::to_string(&text) {
+                    Ok(text) => {
+                        crate::watchdog::eval(
+                            window.as_ref(),
+                            format!("window.__ONYX_SNIPPETS__?.insertText({text})"),
+                            "insert dictation",
+                        );
+                    }
+                    Err(e) => log_backend_error(&app, &format!("Failed to insert dictation: {e}")),
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                stop(&app);
+                log_backend_error(&app, &format!("Failed to transcribe dictation: {e}"));
+                warn(
+                    &app,
+                    format!("Dictation stopped: what you said couldn't be sent. {e}."),
+                );
+                return;
+            }
+        }
+    }
+}
+
+fn warn(app: &AppHandle, message: String) {
+    app.dialog()
+        .message(message)
+        .title("Dictation")
+        .kind(MessageDialogKind::Warning)
+        .show(|_| {});
+}
+
+fn start(app: &AppHandle, window: WebviewWindow) {
+    let state = app.state::<Dictation>();
+    let mut session = state.session();
+    if session.is_some() {
+        return;
+    }
+
+    let (stop, stopped) = mpsc::channel();
+    let (started, started_rx) = mpsc::channel();
+    let (pieces, pieces_rx) = unbounded_channel();
+    std::thread::spawn(move || record(&stopped, &started, &pieces));
+    match started_rx.recv() {
+        Ok(Ok(())) => {
+            *session = Some(Session { stop });
+            drop(session);
+            crate::menu::sync_dictation_item(app, true);
+            tauri::async_runtime::spawn(transcribe_pieces(app.clone(), window, pieces_rx));
+        }
+        Ok(Err(e)) => {
+            drop(session);
+            log_backend_error(app, &format!("Failed to start dictation: {e}"));
+            warn(app, format!("Onyx couldn't use the microphone. {e}."));
+        }
+        Err(_) => log_backend_error(app, "Failed to start dictation: the recorder stopped"),
+    }
+}
+
+/// Stop dictating, if it's under way; what was said last is still typed.
+pub fn stop(app: &AppHandle) {
+    let Some(session) = app.state::<Dictation>().session().take() else {
+        return;
+    };
+    let _ = session.stop.send(());
+    crate::menu::sync_dictation_item(app, false);
+}
+
+/// Edit > Start Dictation: start dictating into the focused chat window, or
+/// stop.
+pub fn toggle_focused(app: &AppHandle) {
+    if app.state::<Dictation>().session().is_some() {
+        stop(app);
+        return;
+    }
+    let windows = app.webview_windows();
+    let window = windows.into_iter().find(|(label, window)| {
+        !is_bundled_page_window(label) && window.is_focused().unwrap_or(false)
+    });
+    if let Some((_, window)) = window {
+        start(app, window);
+    }
+}
+
+/// Check that `voice.dictation_endpoint` is an HTTP URL, if it's set.
+pub fn validate(voice: &VoiceSettings) -> Result<(), String> {
+    let Some(endpoint) = &voice.dictation_endpoint else {
+        return Ok(());
+    };
+    let url = Url::parse(endpoint).map_err(|e| format!("Invalid dictation endpoint: {e}"))?;
+    if matches!(url.scheme(), "http" | "https") {
+        Ok(())
+    } else {
+        Err("The dictation endpoint must be an http:// or https:// URL".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16_000;
+
+    fn speech(duration: Duration) -> Vec<i16> {
+        (0..samples_in(duration, RATE))
+            .map(|i| if i % 2 == 0 { 4000 } else { -4000 })
+            .collect()
+    }
+
+    fn quiet(duration: Duration) -> Vec<i16> {
+        vec![0; samples_in(duration, RATE)]
+    }
+
+    #[test]
+    fn pieces_are_cut_at_pauses() {
+        let mut said = speech(Duration::from_secs(2));
+        assert_eq!(cut_point(&said, RATE), None);
+        said.extend(quiet(Duration::from_millis(300)));
+        assert_eq!(cut_point(&said, RATE), None);
+        said.extend(quiet(Duration::from_millis(300)));
+        assert_eq!(cut_point(&said, RATE), Some(said.len()));
+
+        // Too short to be worth sending yet, pause or not.
+        let mut short = speech(Duration::from_millis(200));
+        short.extend(quiet(PAUSE));
+        assert_eq!(cut_point(&short, RATE), None);
+
+        let long = speech(MAX_PIECE);
+        assert_eq!(cut_point(&long, RATE), Some(long.len()));
+    }
+
+    #[test]
+    fn silence_is_not_sent() {
+        assert!(is_silent(&quiet(Duration::from_secs(2)), RATE));
+        let mut said = quiet(Duration::from_secs(1));
+        said.extend(speech(FRAME * 2));
+        assert!(!is_silent(&said, RATE));
+    }
+
+    #[test]
+    fn dictation_endpoints_are_http_urls() {
+        let voice = |endpoint: Option<&str>| VoiceSettings {
+            dictation_endpoint: endpoint.map(str::to_string),
+            ..VoiceSettings::default()
+        };
+        assert!(validate(&voice(None)).is_ok());
+        assert!(validate(&voice(Some("http://127.0.0.1:8080/inference"))).is_ok());
+        assert!(validate(&voice(Some("ftp://127.0.0.1/inference"))).is_err());
+        assert!(validate(&voice(Some("127.0.0.1:8080"))).is_err());
+    }
+}
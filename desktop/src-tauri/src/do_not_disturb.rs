@@ -0,0 +1,338 @@
+// Do Not Disturb for the app's own notifications (failed connectors, admin
+// approvals, scheduled prompts): while it's on, or while the system says
+// not to disturb (`system_focus` in `notifications.hold`), they're kept
+// rather than shown, and listed in the tray's Notifications menu until
+// they're opened from there or cleared. The tray's Do Not Disturb item (and
+// Settings) turns it on and off; `do_not_disturb` in `notifications.hold`
+// keeps it across restarts. A window in focus mode holds them back too (see
+// `focus_mode.rs`).
+//
+// The system is asked each time something would be shown. On macOS that's a
+// Focus turned on in Control Center (kept in `Assertions.json`, which macOS
+// 12 and later read it from); on Windows what the shell tells apps about
+// presentations, full-screen apps and quiet hours; on Linux GNOME's Do Not
+// Disturb (`show-banners` off). Elsewhere only the app's own setting counts.
+
+use crate::config::{ConfigState, HoldNotifications};
+use crate::debug_log::log_backend_error;
+use crate::focus_mode::FocusMode;
+use std::sync::Mutex;
+use tauri::menu::{CheckMenuItem, MenuItem, PredefinedMenuItem, Submenu, SubmenuBuilder};
+use tauri::{AppHandle, Manager, Wry};
+
+pub const TOGGLE_ID: &str = "dnd:toggle";
+pub const CLEAR_ID: &str = "dnd:clear";
+/// Each missed notification's item, followed by its number.
+const MISSED_PREFIX: &str = "dnd:missed:";
+/// The most kept; older ones are dropped.
+const MAX_MISSED: usize = 20;
+const LABEL_CHARS: usize = 60;
+
+/// Shows a notification, as it would have been shown.
+type Show = Box<dyn FnOnce(&AppHandle) + Send>;
+
+struct Missed {
+    id: u64,
+    label: String,
+    show: Show,
+}
+
+#[derive(Default)]
+struct Kept {
+    /// Newest first.
+    missed: Vec<Missed>,
+    next_id: u64,
+}
+
+/// The tray's Notifications submenu, and its Do Not Disturb item.
+struct TrayMenu {
+    submenu: Submenu<Wry>,
+    toggle: CheckMenuItem<Wry>,
+}
+
+/// Managed state: the notifications kept, and the tray menu listing them.
+#[derive(Default)]
+pub struct DoNotDisturb {
+    kept: Mutex<Kept>,
+    menu: Mutex<Option<TrayMenu>>,
+}
+
+impl DoNotDisturb {
+    fn kept(&self) -> std::sync::MutexGuard<'_, Kept> {
+        self.kept
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// How a notification is listed: its title and the start of its message.
+fn label(title: &str, message: &str) -> String {
+    let text = format!(
+        "{title}: {}",
+        message.split_whitespace().collect::<Vec<_>>().join(" ")
+    );
+    if text.chars().count() > LABEL_CHARS {
+        let cut: String = text.chars().take(LABEL_CHARS - 1).collect();
+        format!("{}\u{2026}", cut.trim_end())
+    } else {
+        text
+    }
+}
+
+/// Whether notifications are held back now.
+fn quiet(app: &AppHandle) -> bool {
+    let hold = app.state::<ConfigState>().config().notifications.hold;
+    hold.contains(&HoldNotifications::DoNotDisturb)
+        || app.state::<FocusMode>().any()
+        || (hold.contains(&HoldNotifications::SystemFocus) && system_focus())
+}
+
+/// Whether the app's own Do Not Disturb is on.
+fn do_not_disturb(app: &AppHandle) -> bool {
+    app.state::<ConfigState>()
+        .config()
+        .notifications
+        .hold
+        .contains(&HoldNotifications::DoNotDisturb)
+}
+
+/// Show a notification with `show`, or keep it for later if the user isn't
+/// to be disturbed.
+pub fn notify(
+    app: &AppHandle,
+    title: &str,
+    message: &str,
+    show: impl FnOnce(&AppHandle) + Send + 'static,
+) {
+    if !quiet(app) {
+        show(app);
+        return;
+    }
+    {
+        let state = app.state::<DoNotDisturb>();
+        let mut kept = state.kept();
+        kept.next_id += 1;
+        let id = kept.next_id;
+        kept.missed.insert(
+            0,
+            Missed {
+                id,
+                label: label(title, message),
+                show: Box::new(show),
+            },
+        );
+        kept.missed.truncate(MAX_MISSED);
+    }
+    sync_menu(app);
+}
+
+/// The tray's Notifications submenu.
+pub fn tray_menu(app: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+    let toggle = CheckMenuItem::with_id(
+        app,
+        TOGGLE_ID,
+        "Do Not Disturb",
+        true,
+        do_not_disturb(app),
+        None::<&str>,
+    )?;
+    let submenu = SubmenuBuilder::new(app, "Notifications").build()?;
+    *app.state::<DoNotDisturb>()
+        .menu
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(TrayMenu {
+        submenu: submenu.clone(),
+        toggle,
+    });
+    sync_menu(app);
+    Ok(submenu)
+}
+
+fn fill_menu(
+    app: &AppHandle,
+    menu: &TrayMenu,
+    missed: &[(u64, String)],
+    do_not_disturb: bool,
+) -> tauri::Result<()> {
+    let submenu = &menu.submenu;
+    for item in submenu.items()? {
+        submenu.remove(&item)?;
+    }
+    menu.toggle.set_checked(do_not_disturb)?;
+    submenu.append(&menu.toggle)?;
+    submenu.append(&PredefinedMenuItem::separator(app)?)?;
+    if missed.is_empty() {
+        submenu.append(&MenuItem::new(
+            app,
+            "No Missed Notifications",
+            false,
+            None::<&str>,
+        )?)?;
+    }
+    for (id, label) in missed {
+        submenu.append(&MenuItem::with_id(
+            app,
+            format!("{MISSED_PREFIX}{id}"),
+            label,
+            true,
+            None::<&str>,
+        )?)?;
+    }
+    submenu.append(&PredefinedMenuItem::separator(app)?)?;
+    submenu.append(&MenuItem::with_id(
+        app,
+        CLEAR_ID,
+        "Clear Missed Notifications",
+        !missed.is_empty(),
+        None::<&str>,
+    )?)?;
+    submenu.set_text(if missed.is_empty() {
+        "Notifications".to_string()
+    } else {
+        format!("Notifications ({})", missed.len())
+    })
+}
+
+/// Refill the tray's Notifications submenu, checking Do Not Disturb to match
+/// the setting.
+pub fn sync_menu(app: &AppHandle) {
+    let state = app.state::<DoNotDisturb>();
+    let missed: Vec<(u64, String)> = state
+        .kept()
+        .missed
+        .iter()
+        .map(|missed| (missed.id, missed.label.clone()))
+        .collect();
+    let do_not_disturb = do_not_disturb(app);
+    let menu = state
+        .menu
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    if let Some(menu) = menu.as_ref() {
+        if let Err(e) = fill_menu(app, menu, &missed, do_not_disturb) {
+            log_backend_error(
+                app,
+                &format!("Failed to update the Notifications menu: {e}"),
+            );
+        }
+    }
+}
+
+/// The tray's Do Not Disturb item: turn it on or off.
+pub fn toggle(app: &AppHandle) {
+    let saved = app.state::<ConfigState>().update_and_persist(|config| {
+        let hold = &mut config.notifications.hold;
+        if !hold.remove(&HoldNotifications::DoNotDisturb) {
+            hold.insert(HoldNotifications::DoNotDisturb);
+        }
+    });
+    if let Err(e) = saved {
+        log_backend_error(app, &format!("Failed to save Do Not Disturb: {e}"));
+    }
+    sync_menu(app);
+}
+
+/// Clear Missed Notifications.
+pub fn clear(app: &AppHandle) {
+    app.state::<DoNotDisturb>().kept().missed.clear();
+    sync_menu(app);
+}
+
+/// Whether the tray item `id` is a missed notification.
+pub fn is_missed_item(id: &str) -> bool {
+    id.starts_with(MISSED_PREFIX)
+}
+
+/// Show the missed notification the tray item `id` is, and stop listing it.
+pub fn open_missed(app: &AppHandle, id: &str) {
+    let Some(id) = id
+        .strip_prefix(MISSED_PREFIX)
+        .and_then(|id| id.parse::<u64>().ok())
+    else {
+        return;
+    };
+    let missed = {
+        let state = app.state::<DoNotDisturb>();
+        let mut kept = state.kept();
+        kept.missed
+            .iter()
+            .position(|missed| missed.id == id)
+            .map(|index| kept.missed.remove(index))
+    };
+    if let Some(missed) = missed {
+        (missed.show)(app);
+    }
+    sync_menu(app);
+}
+
+/// Whether `db`, macOS's `Assertions.json`, has a Focus turned on.
+#[cfg(any(target_os = "macos", test))]
+fn focus_asserted(db: &serde_json::Value) -> bool {
+    db["data"].as_array().into_iter().flatten().any(|entry| {
+        entry["storeAssertionRecords"]
+            .as_array()
+            .is_some_and(|records| !records.is_empty())
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn system_focus() -> bool {
+    let Some(home) = std::env::var_os("HOME") else {
+        return false;
+    };
+    let path = std::path::Path::new(&home).join("Library/DoNotDisturb/DB/Assertions.json");
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .is_some_and(|db| focus_asserted(&db))
+}
+
+#[cfg(target_os = "windows")]
+fn system_focus() -> bool {
+    use windows::Win32::UI::Shell::{SHQueryUserNotificationState, QUNS_ACCEPTS_NOTIFICATIONS};
+
+    // SAFETY: takes nothing, and only returns the state.
+    unsafe { SHQueryUserNotificationState() }.is_ok_and(|state| state != QUNS_ACCEPTS_NOTIFICATIONS)
+}
+
+#[cfg(target_os = "linux")]
+fn system_focus() -> bool {
+    use gtk::gio::prelude::SettingsExt;
+    use gtk::gio::{Settings, SettingsSchemaSource};
+
+    const SCHEMA: &str = "org.gnome.desktop.notifications";
+    // Looked up first: `Settings::new` aborts on a schema that isn't
+    // installed, as it isn't outside GNOME.
+    let installed = SettingsSchemaSource::default()
+        .and_then(|source| source.lookup(SCHEMA, true))
+        .is_some();
+    installed && !Settings::new(SCHEMA).boolean("show-banners")
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+const fn system_focus() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missed_notifications_are_listed_by_their_start() {
+        assert_eq!(
+            label("Join Request", "ada@example.com asked\nto join Onyx."),
+            "Join Request: ada@example.com asked to join Onyx."
+        );
+        let long = label("Connector Failed", &"Drive ".repeat(20));
+        assert_eq!(long.chars().count(), LABEL_CHARS);
+        assert!(long.ends_with('\u{2026}'));
+
+        let focus = serde_json::json!({
+            "data": [{"storeAssertionRecords": [{"assertionDetails": {}}]}]
+        });
+        assert!(focus_asserted(&focus));
+        assert!(!focus_asserted(&serde_json::json!({"data": [{}]})));
+        assert!(!focus_asserted(&serde_json::json!({})));
+    }
+}
@@ -0,0 +1,194 @@
+// macOS Dock menu (right-click the Dock icon): New Chat, New Window, the
+// user's recent chats, and a server status check. New Chat / New Window
+// reuse the app menu's IDs, so they go through `handle_menu_event` exactly
+// like their shortcuts do.
+//
+// Tauri has no Dock menu API. The menu is built with `muda` directly --
+// what Tauri's own menus are made of, so clicks still arrive as Tauri menu
+// events -- and handed to AppKit by answering `applicationDockMenu:` on
+// tao's app delegate, which doesn't implement it.
+
+use crate::config::ConfigState;
+use crate::debug_log::{log_backend_error, log_debug};
+use crate::menu::{MENU_NEW_CHAT_ID, MENU_NEW_WINDOW_ID};
+use crate::server_api::{recent_chats, server_version, ChatSession};
+use muda::{ContextMenu, Menu, MenuItem, PredefinedMenuItem, Submenu};
+use objc2::runtime::{AnyObject, Imp, Sel};
+use objc2::{class, msg_send, sel};
+use std::cell::RefCell;
+use std::ffi::c_void;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+
+pub const MENU_CHECK_SERVER_ID: &str = "check_server_status";
+/// Recent-chat items are this followed by the chat's ID.
+pub const RECENT_CHAT_ID_PREFIX: &str = "recent_chat:";
+const RECENT_CHAT_LIMIT: usize = 8;
+const REFRESH_INTERVAL: Duration = Duration::from_mins(5);
+
+struct DockMenu {
+    menu: Menu,
+    recent_chats: Submenu,
+}
+
+thread_local! {
+    // muda menus can't leave the main thread, which is also the only one
+    // AppKit asks for the Dock menu on.
+    static DOCK_MENU: RefCell<Option<DockMenu>> = const { RefCell::new(None) };
+}
+
+extern "C-unwind" fn application_dock_menu(
+    _delegate: &AnyObject,
+    _cmd: Sel,
+    _sender: *mut AnyObject,
+) -> *mut c_void {
+    DOCK_MENU.with_borrow(|dock| {
+        dock.as_ref()
+            .map_or(std::ptr::null_mut(), |dock| dock.menu.ns_menu())
+    })
+}
+
+/// Teach the app delegate `applicationDockMenu:`.
+fn answer_dock_menu_requests() -> Result<(), String> {
+    // SAFETY: `application_dock_menu` has the signature AppKit calls
+    // `applicationDockMenu:` with ("@@:@": an `NSMenu *` from `self`, `_cmd`
+    // and the sender), and the method is only added to the delegate's own
+    // class. The menu it returns stays owned by `DOCK_MENU`.
+    unsafe {
+        let ns_app: *mut AnyObject = msg_send![class!(NSApplication), sharedApplication];
+        let delegate: *mut AnyObject = msg_send![ns_app, delegate];
+        let delegate = delegate.as_ref().ok_or("No app delegate")?;
+        let imp = std::mem::transmute::<
+            extern "C-unwind" fn(&AnyObject, Sel, *mut AnyObject) -> *mut c_void,
+            Imp,
+        >(application_dock_menu);
+        let added = objc2::ffi::class_addMethod(
+            std::ptr::from_ref(delegate.class()).cast_mut(),
+            sel!(applicationDockMenu:),
+            imp,
+            c"@@:@".as_ptr(),
+        );
+        if !added.as_bool() {
+            return Err("The app delegate already has a Dock menu".to_string());
+        }
+        // AppKit checks what a delegate responds to when it's set.
+        let () = msg_send![ns_app, setDelegate: delegate];
+    }
+    Ok(())
+}
+
+/// Replace the Recent Chats submenu's entries with `chats`.
+fn show_recent_chats(chats: &[ChatSession]) -> Result<(), String> {
+    DOCK_MENU.with_borrow(|dock| {
+        let Some(dock) = dock else {
+            return Ok(());
+        };
+        while dock.recent_chats.remove_at(0).is_some() {}
+        for chat in chats {
+            let title = chat
+                .name
+                .as_deref()
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .unwrap_or("Untitled Chat");
+            dock.recent_chats
+                .append(&MenuItem::with_id(
+                    format!("{RECENT_CHAT_ID_PREFIX}{}", chat.id),
+                    title,
+                    true,
+                    None,
+                ))
+                .map_err(|e| e.to_string())?;
+        }
+        dock.recent_chats.set_enabled(!chats.is_empty());
+        Ok(())
+    })
+}
+
+/// Keep Recent Chats current for as long as the app runs. A failed fetch
+/// leaves the last list up.
+fn start_refreshing(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match recent_chats(&app, RECENT_CHAT_LIMIT).await {
+                Ok(chats) => {
+                    let chats = chats.unwrap_or_default();
+                    let handle = app.clone();
+                    let result = app.run_on_main_thread(move || {
+                        if let Err(e) = show_recent_chats(&chats) {
+                            log_backend_error(
+                                &handle,
+                                &format!("Failed to list recent chats: {e}"),
+                            );
+                        }
+                    });
+                    if let Err(e) = result {
+                        log_backend_error(&app, &format!("Failed to list recent chats: {e}"));
+                    }
+                }
+                Err(e) => log_debug(&app, &format!("Recent chats: {e}")),
+            }
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+        }
+    });
+}
+
+/// Build the Dock menu. Must run on the main thread.
+pub fn setup_dock_menu(app: &AppHandle) -> Result<(), String> {
+    let recent_chats = Submenu::new("Recent Chats", false);
+    let menu = Menu::new();
+    menu.append_items(&[
+        &MenuItem::with_id(MENU_NEW_CHAT_ID, "New Chat", true, None),
+        &MenuItem::with_id(MENU_NEW_WINDOW_ID, "New Window", true, None),
+        &PredefinedMenuItem::separator(),
+        &recent_chats,
+        &PredefinedMenuItem::separator(),
+        &MenuItem::with_id(MENU_CHECK_SERVER_ID, "Check Server Status", true, None),
+    ])
+    .map_err(|e| e.to_string())?;
+    DOCK_MENU.set(Some(DockMenu { menu, recent_chats }));
+
+    answer_dock_menu_requests()?;
+    start_refreshing(app);
+    Ok(())
+}
+
+/// Open a Recent Chats entry (`id` is its menu ID) in the main window.
+pub fn open_recent_chat(app: &AppHandle, id: &str) {
+    let Some(chat_id) = id.strip_prefix(RECENT_CHAT_ID_PREFIX) else {
+        return;
+    };
+    crate::window::open_route(
+        app,
+        &crate::route::Route::Chat {
+            id: chat_id.to_string(),
+        },
+    );
+}
+
+/// Tell the user whether the configured server answers, and its version.
+pub fn check_server_status(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let config = app.state::<ConfigState>().config();
+        let server = config.server_url.clone();
+        let (message, kind) = match server_version(&config).await {
+            Ok(Some(version)) => (
+                format!("Connected to {server} (Onyx {version})."),
+                MessageDialogKind::Info,
+            ),
+            Ok(None) => (format!("Connected to {server}."), MessageDialogKind::Info),
+            Err(e) => (
+                format!("Couldn't reach {server}: {e}"),
+                MessageDialogKind::Error,
+            ),
+        };
+        app.dialog()
+            .message(message)
+            .title("Server Status")
+            .kind(kind)
+            .show(|_| {});
+    });
+}
@@ -0,0 +1,252 @@
+// Onyx's own file types, which the installers register with the system
+// (`bundle.fileAssociations` in `tauri.conf.json`), so double-clicking one
+// opens it here:
+//
+//     .onyxchat    a chat transcript, as File > Export Chat... saves it with
+//                  the Onyx Chat type (the JSON transcript, see `chat_export.rs`)
+//     .onyxprompt  a prompt, as plain text
+//
+// A transcript opens read-only in the transcript window: it's a copy, not a
+// chat on the server, so it can be read but not continued. A prompt starts
+// a new chat in the main window with it in the message box, not yet sent.
+// On macOS the files arrive as `file://` links along with `onyx://` ones;
+// on Windows and Linux as the path the app is started with, which a second
+// copy hands to the running one like a link (see `instance.rs`). Files are
+// turned away while the app is locked.
+
+use crate::debug_log::log_backend_error;
+use crate::lock::AppLock;
+use crate::route::Route;
+use crate::window::{open_route, TRANSCRIPT_WINDOW, TRANSCRIPT_WINDOW_LABEL};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+use url::Url;
+
+pub const CHAT_EXTENSION: &str = "onyxchat";
+pub const PROMPT_EXTENSION: &str = "onyxprompt";
+/// The biggest file that's read; transcripts of long chats run to a few
+/// hundred KB.
+const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+const MAX_PROMPT_CHARS: usize = 8_000;
+
+/// Managed state: the transcript the transcript window shows.
+#[derive(Default)]
+pub struct OpenTranscript(Mutex<Option<Transcript>>);
+
+/// A transcript file, as the transcript window shows it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Transcript {
+    /// The file's name.
+    file: String,
+    /// The transcript's JSON.
+    transcript: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Chat,
+    Prompt,
+}
+
+fn kind(path: &Path) -> Option<Kind> {
+    match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+        CHAT_EXTENSION => Some(Kind::Chat),
+        PROMPT_EXTENSION => Some(Kind::Prompt),
+        _ => None,
+    }
+}
+
+/// Whether `path` names a file of one of Onyx's types.
+pub fn is_document(path: &Path) -> bool {
+    kind(path).is_some()
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    let size = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if size > MAX_FILE_BYTES {
+        return Err("The file is too big to open".to_string());
+    }
+    std::fs::read_to_string(path).map_err(|e| e.to_string())
+}
+
+/// `json` as a transcript, if it is one.
+fn parse_transcript(json: &str) -> Result<Value, String> {
+    let transcript: Value =
+        serde_json::from_str(json).map_err(|e| format!("This isn't an Onyx chat: {e}"))?;
+    let is_transcript = transcript.get("title").is_some_and(Value::is_string)
+        && transcript
+            .get("messages")
+            .and_then(Value::as_array)
+            .is_some_and(|messages| {
+                messages.iter().all(|message| {
+                    message.get("role").is_some_and(Value::is_string)
+                        && message.get("text").is_some_and(Value::is_string)
+                })
+            });
+    if !is_transcript {
+        return Err("This isn't an Onyx chat".to_string());
+    }
+    Ok(transcript)
+}
+
+/// `text` as the message a prompt file starts.
+fn parse_prompt(text: &str) -> Result<String, String> {
+    let prompt = text.trim_start_matches('\u{feff}').trim();
+    if prompt.is_empty() {
+        return Err("The prompt file is empty".to_string());
+    }
+    Ok(prompt.chars().take(MAX_PROMPT_CHARS).collect())
+}
+
+/// Show the transcript window on what `OpenTranscript` holds.
+fn show_transcript(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window(TRANSCRIPT_WINDOW_LABEL) {
+        let shown = window
+            .reload()
+            .and_then(|()| window.unminimize())
+            .and_then(|()| window.set_focus());
+        if let Err(e) = shown {
+            log_backend_error(app, &format!("Failed to show the transcript: {e}"));
+        }
+        return;
+    }
+    // Built off the calling thread, like the other bundled pages.
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let preset = &TRANSCRIPT_WINDOW;
+        let result = WebviewWindowBuilder::new(
+            &handle,
+            TRANSCRIPT_WINDOW_LABEL,
+            WebviewUrl::App("transcript.html".into()),
+        )
+        .title("Onyx Chat")
+        .inner_size(preset.width, preset.height)
+        .min_inner_size(preset.min_width, preset.min_height)
+        .center()
+        .focused(true)
+        .additional_browser_args(&crate::locale::browser_args())
+        .build();
+        if let Err(e) = result {
+            log_backend_error(&handle, &format!("Failed to open the transcript: {e}"));
+        }
+    });
+}
+
+/// Open the file at `path`: a transcript in the transcript window, a
+/// prompt in a new chat.
+pub fn open(app: &AppHandle, path: &Path) -> Result<(), String> {
+    if app.state::<AppLock>().is_locked() {
+        return Err("Onyx is locked".to_string());
+    }
+    let kind = kind(path).ok_or("Onyx doesn't open this kind of file")?;
+    let text = read(path)?;
+    match kind {
+        Kind::Chat => {
+            let transcript = Transcript {
+                file: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                transcript: parse_transcript(&text)?,
+            };
+            *app.state::<OpenTranscript>()
+                .0
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(transcript);
+            show_transcript(app);
+        }
+        Kind::Prompt => open_route(
+            app,
+            &Route::Draft {
+                message: parse_prompt(&text)?,
+            },
+        ),
+    }
+    Ok(())
+}
+
+fn open_or_warn(app: &AppHandle, path: &Path) {
+    if let Err(e) = open(app, path) {
+        log_backend_error(app, &format!("Failed to open {}: {e}", path.display()));
+        app.dialog()
+            .message(e)
+            .title("Open File")
+            .kind(MessageDialogKind::Warning)
+            .show(|_| {});
+    }
+}
+
+/// Open the file `link` names, if it's one of Onyx's; whether it was.
+pub fn open_url(app: &AppHandle, link: &Url) -> bool {
+    if link.scheme() != "file" {
+        return false;
+    }
+    match link.to_file_path() {
+        Ok(path) if is_document(&path) => {
+            open_or_warn(app, &path);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Open the file at `path`, as the app was started with it, if it's one of
+/// Onyx's; whether it was.
+pub fn open_path(app: &AppHandle, path: &str) -> bool {
+    let path = PathBuf::from(path);
+    if !is_document(&path) || !path.is_file() {
+        return false;
+    }
+    open_or_warn(app, &path);
+    true
+}
+
+/// The transcript to show; only from the transcript window
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn get_open_transcript(app: AppHandle, window: WebviewWindow) -> Result<Transcript, String> {
+    if window.label() != TRANSCRIPT_WINDOW_LABEL {
+        return Err("Not available in this window".to_string());
+    }
+    if app.state::<AppLock>().is_locked() {
+        return Err("Onyx is locked".to_string());
+    }
+    app.state::<OpenTranscript>()
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone()
+        .ok_or_else(|| "No chat is open".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn files_are_read_by_their_type() {
+        assert_eq!(kind(Path::new("/tmp/PTO.onyxchat")), Some(Kind::Chat));
+        assert_eq!(kind(Path::new("Summary.OnyxPrompt")), Some(Kind::Prompt));
+        assert!(!is_document(Path::new("chat.json")));
+
+        let transcript = parse_transcript(
+            r#"{"id": "4f1c", "title": "PTO", "created": "2026-10-01T09:00:00Z",
+                "messages": [{"role": "user", "text": "How much PTO?", "sent": ""}]}"#,
+        )
+        .unwrap();
+        assert_eq!(transcript["messages"][0]["text"], "How much PTO?");
+        assert!(parse_transcript(r#"{"title": "PTO"}"#).is_err());
+        assert!(parse_transcript("# PTO").is_err());
+
+        assert_eq!(
+            parse_prompt("\u{feff}Summarize this week's incidents.\n").unwrap(),
+            "Summarize this week's incidents."
+        );
+        assert!(parse_prompt(" \n").is_err());
+    }
+}
@@ -0,0 +1,376 @@
+// Messages written while the server can't be reached. `offline_drafts.js`
+// watches the page's sends to `/api/chat/send-chat-message`, and one that
+// fails because nothing answered is handed to `queue_draft` instead of
+// being lost. The queue is kept in `draft_queue.json` in the state
+// directory, sealed like the config's secrets (see `secrets.rs`), and the
+// pages on the same webview data show what's in it as pending.
+//
+// Once the connection monitor (see `connection.rs`) hears from the server,
+// the queue is sent from here as the user of a window on the draft's
+// webview data. Drafts go in the order they were written, one at a time,
+// and each after the one before it in the same chat goes after that one's
+// answer rather than wherever the chat has got to, so a chat's drafts read
+// in order next to each other. A draft the server turns down is taken out
+// and shown in a dialog so its text isn't lost; one that still can't reach
+// the server stays, and nothing after it is sent until it goes.
+
+use crate::assets::{script, Script};
+use crate::config::get_state_dir;
+use crate::connection::Quality;
+use crate::debug_log::{format_utc_timestamp, log_backend_error};
+use crate::instance::InstanceLock;
+use crate::lock::AppLock;
+use crate::route::Route;
+use crate::secrets::{encryption_key, open, seal};
+use crate::server_api::{send_chat_message, SendError};
+use crate::window::{is_bundled_page_window, WindowPartitions};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, Webview, WebviewWindow};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+use url::Url;
+
+const QUEUE_FILE: &str = "draft_queue.json";
+/// Longer messages aren't kept; the page can't have sent them anyway.
+const MAX_MESSAGE_CHARS: usize = 100_000;
+
+/// A message waiting to be sent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Draft {
+    pub id: String,
+    /// The chat it goes in, or `None` for a new chat.
+    pub chat_id: Option<String>,
+    pub message: String,
+    /// The body the page sent.
+    pub request: Value,
+    /// The origin of the server the page was on.
+    pub server: String,
+    /// The webview data whose user it's from.
+    pub partition: String,
+    pub queued_at: String,
+}
+
+/// What a page is shown of a draft.
+#[derive(Debug, Serialize)]
+struct Pending<'a> {
+    id: &'a str,
+    chat_id: Option<&'a str>,
+    message: &'a str,
+}
+
+/// Managed state: the queue, and a lock so sends don't overlap.
+#[derive(Default)]
+pub struct DraftQueue {
+    drafts: Mutex<Option<Vec<Draft>>>,
+    sending: tokio::sync::Mutex<()>,
+}
+
+impl DraftQueue {
+    /// Change the queue (read from disk the first time) and save it.
+    fn update<T>(&self, change: impl FnOnce(&mut Vec<Draft>) -> T) -> Result<T, String> {
+        let mut queue = self
+            .drafts
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let drafts = queue.get_or_insert_with(load);
+        let result = change(drafts);
+        // Saved under the lock, so the file ends up as the last change left
+        // the queue.
+        let saved = save(drafts);
+        drop(queue);
+        saved.map(|()| result)
+    }
+
+    fn snapshot(&self) -> Vec<Draft> {
+        self.drafts
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get_or_insert_with(load)
+            .clone()
+    }
+}
+
+fn queue_path() -> Option<PathBuf> {
+    get_state_dir().map(|dir| dir.join(QUEUE_FILE))
+}
+
+/// The saved queue; empty if there's none or it can't be read.
+fn load() -> Vec<Draft> {
+    let Some(sealed) = queue_path().and_then(|path| std::fs::read_to_string(path).ok()) else {
+        return Vec::new();
+    };
+    encryption_key()
+        .and_then(|key| open(key, sealed.trim()))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save(drafts: &[Draft]) -> Result<(), String> {
+    let path = queue_path().ok_or("Could not determine the state directory")?;
+    if drafts.is_empty() {
+        return match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        };
+    }
+    let json = serde_json::to_string(drafts).map_err(|e| e.to_string())?;
+    let sealed = seal(encryption_key()?, &json)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(&path, sealed).map_err(|e| e.to_string())
+}
+
+/// The draft in the page's `request`, from a page on `server`.
+fn draft_from(request: Value, server: &Url, partition: String) -> Result<Draft, String> {
+    let message = request
+        .get("message")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|message| !message.is_empty())
+        .ok_or("There's no message to send")?;
+    if message.chars().count() > MAX_MESSAGE_CHARS {
+        return Err("The message is too long to keep".to_string());
+    }
+    Ok(Draft {
+        id: uuid::Uuid::new_v4().to_string(),
+        chat_id: request
+            .get("chat_session_id")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        message: message.to_string(),
+        server: server.origin().ascii_serialization(),
+        partition,
+        queued_at: format_utc_timestamp(),
+        request,
+    })
+}
+
+/// The request to send `draft` with, after the answer `after` to an earlier
+/// draft in the same chat if there was one.
+fn request_for(draft: &Draft, after: Option<i64>) -> Value {
+    let mut request = draft.request.clone();
+    if let Some(parent) = after {
+        request["parent_message_id"] = Value::from(parent);
+    }
+    request
+}
+
+/// Show each chat window the drafts from its webview data.
+fn show_pending(app: &AppHandle) {
+    let drafts = app.state::<DraftQueue>().snapshot();
+    for (label, window) in app.webview_windows() {
+        if !is_bundled_page_window(&label) {
+            set_page_drafts(app, window.as_ref(), &drafts);
+        }
+    }
+}
+
+fn set_page_drafts(app: &AppHandle, webview: &Webview, drafts: &[Draft]) {
+    let partition = app
+        .state::<WindowPartitions>()
+        .partition_of(webview.label());
+    let pending: Vec<Pending> = drafts
+        .iter()
+        .filter(|draft| draft.partition == partition)
+        .map(|draft| Pending {
+            id: &draft.id,
+            chat_id: draft.chat_id.as_deref(),
+            message: &draft.message,
+        })
+        .collect();
+    let pending = serde_json::to_string(&pending).unwrap_or_else(|_| "[]".to_string());
+    crate::watchdog::eval(
+        webview,
+        format!("window.__ONYX_DRAFTS__?.set({pending})"),
+        "show pending drafts",
+    );
+}
+
+pub fn inject_offline_drafts_script(webview: &Webview) {
+    if is_bundled_page_window(webview.label()) {
+        return;
+    }
+    let app = webview.app_handle();
+    let script = script(app, Script::OfflineDrafts);
+    if crate::watchdog::eval(webview, &*script, "inject offline drafts script") {
+        set_page_drafts(app, webview, &app.state::<DraftQueue>().snapshot());
+    }
+}
+
+/// A window on `partition`'s webview data to send as.
+fn window_on(app: &AppHandle, partition: &str) -> Option<WebviewWindow> {
+    let partitions = app.state::<WindowPartitions>();
+    app.webview_windows()
+        .into_iter()
+        .find(|(label, _)| {
+            !is_bundled_page_window(label) && partitions.partition_of(label) == partition
+        })
+        .map(|(_, window)| window)
+}
+
+/// Reload the windows showing `chat_id`, so they show what was sent.
+fn reload_chat(app: &AppHandle, chat_id: &str) {
+    for (label, window) in app.webview_windows() {
+        let showing = window
+            .url()
+            .ok()
+            .and_then(|url| Route::from_url(&url))
+            .is_some_and(|route| matches!(route, Route::Chat { id } if id == chat_id));
+        if showing {
+            if let Err(e) = window.reload() {
+                log_backend_error(app, &format!("Failed to reload {label}: {e}"));
+            }
+        }
+    }
+}
+
+/// Send what's queued, in order, until it's all gone or the server can't be
+/// reached.
+async fn send_all(app: &AppHandle) {
+    let queue = app.state::<DraftQueue>();
+    let Ok(_sending) = queue.sending.try_lock() else {
+        return;
+    };
+    // The answer to each chat's last sent draft, which the next goes after.
+    let mut answers: HashMap<String, i64> = HashMap::new();
+    // Chats with a draft left waiting, whose later drafts wait behind it.
+    let mut held: HashSet<String> = HashSet::new();
+    for draft in queue.snapshot() {
+        if draft
+            .chat_id
+            .as_ref()
+            .is_some_and(|chat| held.contains(chat))
+        {
+            continue;
+        }
+        // Its user has no window open to send as; it waits for one.
+        let sender = window_on(app, &draft.partition).zip(Url::parse(&draft.server).ok());
+        let Some((window, server)) = sender else {
+            held.extend(draft.chat_id.clone());
+            continue;
+        };
+        let after = draft
+            .chat_id
+            .as_ref()
+            .and_then(|chat| answers.get(chat).copied());
+        match send_chat_message(&window, &server, &request_for(&draft, after)).await {
+            Ok(answer) => {
+                if let Some(chat) = &draft.chat_id {
+                    answers.insert(chat.clone(), answer);
+                }
+            }
+            Err(SendError::Unreachable(e)) => {
+                log_backend_error(app, &format!("A queued message still can't be sent: {e}"));
+                break;
+            }
+            Err(SendError::Rejected(e)) => {
+                app.dialog()
+                    .message(format!(
+                        "A message written while offline couldn't be sent: {e}\n\n{}",
+                        draft.message
+                    ))
+                    .title("Message Not Sent")
+                    .kind(MessageDialogKind::Warning)
+                    .show(|_| {});
+            }
+        }
+        if let Err(e) = queue.update(|drafts| drafts.retain(|queued| queued.id != draft.id)) {
+            log_backend_error(app, &format!("Failed to save the draft queue: {e}"));
+        }
+        show_pending(app);
+        if let Some(chat) = &draft.chat_id {
+            reload_chat(app, chat);
+        }
+    }
+}
+
+/// Follow the connection monitor's latest finding: with the server
+/// answering, send whatever's queued. Only the copy that holds the data
+/// directory sends, so nothing goes twice.
+pub fn on_connection(app: &AppHandle, quality: Quality) {
+    if quality == Quality::Offline
+        || app.state::<InstanceLock>().is_conflicting()
+        || app.state::<DraftQueue>().snapshot().is_empty()
+    {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move { send_all(&app).await });
+}
+
+/// Keep a message the calling page couldn't send, to send once the server
+/// answers; `request` is the body it tried to send
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn queue_draft(app: AppHandle, window: WebviewWindow, request: Value) -> Result<(), String> {
+    if is_bundled_page_window(window.label()) {
+        return Err("Only chat windows send messages".to_string());
+    }
+    if app.state::<AppLock>().is_locked() {
+        return Err("Onyx is locked".to_string());
+    }
+    let server = window
+        .url()
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or("This window isn't on a server")?;
+    let partition = app.state::<WindowPartitions>().partition_of(window.label());
+    let draft = draft_from(request, &server, partition)?;
+    app.state::<DraftQueue>()
+        .update(|drafts| drafts.push(draft))?;
+    show_pending(&app);
+    Ok(())
+}
+
+/// Take a message out of the queue without sending it
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn discard_draft(app: AppHandle, window: WebviewWindow, id: String) -> Result<(), String> {
+    let partition = app.state::<WindowPartitions>().partition_of(window.label());
+    // Only the drafts a window was shown: those from its own webview data.
+    app.state::<DraftQueue>().update(|drafts| {
+        drafts.retain(|draft| draft.id != id || draft.partition != partition);
+    })?;
+    show_pending(&app);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn drafts_keep_the_page_request_and_chain_within_a_chat() {
+        let server = Url::parse("https://onyx.example.com/app?chatId=abc").unwrap();
+        let request = serde_json::json!({
+            "message": " What about contractors? ",
+            "chat_session_id": "abc",
+            "parent_message_id": 7,
+            "llm_override": {"model_version": "gpt-4o"},
+        });
+        let draft = draft_from(request, &server, "default".to_string()).unwrap();
+        assert_eq!(draft.chat_id.as_deref(), Some("abc"));
+        assert_eq!(draft.message, "What about contractors?");
+        assert_eq!(draft.server, "https://onyx.example.com");
+
+        assert_eq!(request_for(&draft, None)["parent_message_id"], 7);
+        let chained = request_for(&draft, Some(12));
+        assert_eq!(chained["parent_message_id"], 12);
+        assert_eq!(chained["llm_override"]["model_version"], "gpt-4o");
+
+        let new_chat = draft_from(
+            serde_json::json!({"message": "hi", "chat_session_info": {"persona_id": 0}}),
+            &server,
+            "default".to_string(),
+        )
+        .unwrap();
+        assert_eq!(new_chat.chat_id, None);
+        assert!(draft_from(serde_json::json!({"message": "  "}), &server, String::new()).is_err());
+    }
+}
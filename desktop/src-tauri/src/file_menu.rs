@@ -0,0 +1,177 @@
+// "Ask Onyx about This File" and "Upload to Onyx" on files in the system's
+// file manager. Asking starts a new chat with the file attached, the way a
+// share does (see `share.rs`); uploading sends it to the shared folders'
+// file connector (see `local_files.rs`), then offers a new chat to ask
+// about it in. Either way the file's path reaches the running copy as a
+// companion command (`onyx share FILE`, `onyx upload FILE`, see
+// `companion.rs`), so a copy that isn't running is started with it.
+//
+// On Windows the items are verbs under `HKCU\Software\Classes\*\shell`,
+// added while `file_context_menu` is on and taken out when it's turned off
+// (and by the uninstaller, see `windows/hooks.nsh`). On macOS they're file
+// services in `Info.plist`, shown in Finder's Quick Actions and Services
+// menus (see `services.rs`); System Settings turns them on and off.
+
+#[cfg(any(feature = "local-files", target_os = "windows"))]
+use crate::debug_log::log_backend_error;
+#[cfg(feature = "local-files")]
+use crate::route::Route;
+#[cfg(feature = "local-files")]
+use crate::window::open_route;
+use std::path::PathBuf;
+use tauri::AppHandle;
+#[cfg(feature = "local-files")]
+use tauri_plugin_dialog::{
+    DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult,
+};
+
+#[cfg(feature = "local-files")]
+const TITLE: &str = "Upload to Onyx";
+#[cfg(feature = "local-files")]
+const NEW_CHAT_LABEL: &str = "New Chat";
+#[cfg(feature = "local-files")]
+const CLOSE_LABEL: &str = "Close";
+
+/// Upload `files` in the background and say how it went.
+#[cfg_attr(not(feature = "local-files"), allow(clippy::needless_pass_by_value))]
+pub fn upload(app: &AppHandle, files: Vec<PathBuf>) -> Result<(), String> {
+    if files.is_empty() {
+        return Err("Choose files to upload".to_string());
+    }
+    #[cfg(not(feature = "local-files"))]
+    {
+        let _ = app;
+        Err("This build of Onyx can't upload files".to_string())
+    }
+    #[cfg(feature = "local-files")]
+    {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            match crate::local_files::upload(&app, &files).await {
+                Ok(connector) => offer_chat(&app, &files, &connector),
+                Err(e) => {
+                    log_backend_error(&app, &format!("Failed to upload files: {e}"));
+                    app.dialog()
+                        .message(e)
+                        .title(TITLE)
+                        .kind(MessageDialogKind::Warning)
+                        .show(|_| {});
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Say where `files` went, with a new chat to ask about them in.
+#[cfg(feature = "local-files")]
+fn offer_chat(app: &AppHandle, files: &[PathBuf], connector: &str) {
+    let names: Vec<String> = files
+        .iter()
+        .filter_map(|path| path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect();
+    let handle = app.clone();
+    app.dialog()
+        .message(format!(
+            "Uploaded {} to {connector}. Chats can draw on it once the server has indexed it.",
+            names.join(", ")
+        ))
+        .title(TITLE)
+        .kind(MessageDialogKind::Info)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            NEW_CHAT_LABEL.to_string(),
+            CLOSE_LABEL.to_string(),
+        ))
+        .show_with_result(move |result| {
+            let chat = match result {
+                MessageDialogResult::Ok => true,
+                MessageDialogResult::Custom(label) => label == NEW_CHAT_LABEL,
+                _ => false,
+            };
+            if chat {
+                open_route(&handle, &Route::new_chat());
+            }
+        });
+}
+
+/// Add or take out Explorer's items to match `file_context_menu`.
+#[cfg(target_os = "windows")]
+pub fn sync(app: &AppHandle) {
+    use crate::config::ConfigState;
+    use tauri::Manager;
+
+    let result = if app.state::<ConfigState>().config().file_context_menu {
+        std::env::current_exe()
+            .map_err(|e| e.to_string())
+            .and_then(|exe| explorer::add(&exe))
+    } else {
+        explorer::remove()
+    };
+    if let Err(e) = result {
+        log_backend_error(app, &format!("Failed to update Explorer's file menu: {e}"));
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod explorer {
+    use std::io;
+    use std::path::Path;
+    use winreg::enums::HKEY_CURRENT_USER;
+    use winreg::RegKey;
+
+    const SHELL_KEY: &str = r"Software\Classes\*\shell";
+
+    /// Each item's key, label and companion command.
+    const VERBS: [(&str, &str, &str); 2] = [
+        ("OnyxAsk", "Ask Onyx about This File", "share"),
+        ("OnyxUpload", "Upload to Onyx", "upload"),
+    ];
+
+    /// What Explorer runs for `subcommand` on the file it's used on.
+    pub fn command_line(exe: &Path, subcommand: &str) -> String {
+        format!("\"{}\" {subcommand} \"%1\"", exe.display())
+    }
+
+    pub fn add(exe: &Path) -> Result<(), String> {
+        let (shell, _) = RegKey::predef(HKEY_CURRENT_USER)
+            .create_subkey(SHELL_KEY)
+            .map_err(|e| e.to_string())?;
+        for (name, label, subcommand) in VERBS {
+            let written = shell.create_subkey(name).and_then(|(verb, _)| {
+                verb.set_value("", &label)?;
+                verb.set_value("Icon", &exe.display().to_string())?;
+                let (command, _) = verb.create_subkey("command")?;
+                command.set_value("", &command_line(exe, subcommand))
+            });
+            written.map_err(|e| format!("Failed to add \"{label}\": {e}"))?;
+        }
+        Ok(())
+    }
+
+    pub fn remove() -> Result<(), String> {
+        let hive = RegKey::predef(HKEY_CURRENT_USER);
+        for (name, label, _) in VERBS {
+            match hive.delete_subkey_all(format!(r"{SHELL_KEY}\{name}")) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(format!("Failed to take out \"{label}\": {e}"));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn paths_with_spaces_are_quoted() {
+            assert_eq!(
+                command_line(Path::new(r"C:\Program Files\Onyx\Onyx.exe"), "upload"),
+                r#""C:\Program Files\Onyx\Onyx.exe" upload "%1""#
+            );
+        }
+    }
+}
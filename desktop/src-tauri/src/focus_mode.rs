@@ -0,0 +1,209 @@
+// Focus mode: a chat window with nothing around the chat, for presenting or
+// sharing the screen. The injected titlebar comes down (with the window
+// frame on macOS, whose traffic lights would otherwise sit on the page), the
+// menu bar is hidden off macOS, and while any window is in focus mode the
+// unread badge and the tray icon are hidden and notifications are kept for
+// later as under Do Not Disturb (see `do_not_disturb.rs`). With
+// `focus_mode_fullscreen` the window also goes fullscreen. Turning it off
+// puts back exactly what was there; the window's chrome is remembered per
+// window label until then, as mini chat does (see `mini_chat.rs`).
+
+use crate::config::ConfigState;
+use crate::debug_log::log_backend_error;
+use crate::mini_chat::MiniChatState;
+use crate::watchdog;
+use crate::window::is_bundled_page_window;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, Webview, WebviewWindow};
+
+/// Hide the injected titlebar and give its strip back to the page. The
+/// class also tells the titlebar script its styles are off on purpose.
+const FOCUS_SCRIPT: &str = r#"(function () {
+  const apply = () => {
+    document.documentElement.classList.add("onyx-desktop-focus-mode");
+    if (document.getElementById("onyx-desktop-focus-mode-style")) return;
+    const style = document.createElement("style");
+    style.id = "onyx-desktop-focus-mode-style";
+    style.textContent = `
+      :root { --onyx-desktop-titlebar-height: 0px !important; }
+      #onyx-desktop-titlebar { display: none !important; }`;
+    (document.head ?? document.documentElement).appendChild(style);
+  };
+  if (document.readyState === "loading") {
+    document.addEventListener("DOMContentLoaded", apply);
+  } else {
+    apply();
+  }
+})();"#;
+
+const UNFOCUS_SCRIPT: &str = r#"document.documentElement.classList.remove("onyx-desktop-focus-mode");
+document.getElementById("onyx-desktop-focus-mode-style")?.remove();"#;
+
+/// What a window looked like before focus mode.
+struct RestoreState {
+    fullscreen: bool,
+    decorated: bool,
+    menu_visible: bool,
+}
+
+/// Managed state: windows currently in focus mode, by label.
+#[derive(Default)]
+pub struct FocusMode(Mutex<HashMap<String, RestoreState>>);
+
+impl FocusMode {
+    pub fn is_focused(&self, label: &str) -> bool {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .contains_key(label)
+    }
+
+    /// Whether any window is in focus mode.
+    pub fn any(&self) -> bool {
+        !self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .is_empty()
+    }
+}
+
+fn log_err<E: std::fmt::Display>(app: &AppHandle, what: &str, result: Result<(), E>) {
+    if let Err(e) = result {
+        log_backend_error(app, &format!("Focus mode: failed to {what}: {e}"));
+    }
+}
+
+/// Hide or show what's hidden while any window is in focus mode.
+fn sync_app_chrome(app: &AppHandle) {
+    let focused = app.state::<FocusMode>().any();
+    crate::menu::set_tray_visible(app, !focused);
+    crate::badge::refresh(app);
+}
+
+/// Clear `window` of everything but the chat. No-op if it's already in
+/// focus mode.
+pub fn enter_focus_mode(app: &AppHandle, window: &WebviewWindow) -> Result<(), String> {
+    let state = app.state::<FocusMode>();
+    if state.is_focused(window.label()) {
+        return Ok(());
+    }
+    if app.state::<MiniChatState>().is_mini(window.label()) {
+        return Err("Expand the mini chat first".to_string());
+    }
+
+    let restore = RestoreState {
+        fullscreen: window.is_fullscreen().map_err(|e| e.to_string())?,
+        decorated: window.is_decorated().unwrap_or(true),
+        menu_visible: window.is_menu_visible().unwrap_or(true),
+    };
+    let fullscreen = restore.fullscreen;
+    state
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .insert(window.label().to_string(), restore);
+
+    if app.state::<ConfigState>().config().focus_mode_fullscreen && !fullscreen {
+        log_err(app, "go fullscreen", window.set_fullscreen(true));
+    } else if cfg!(target_os = "macos") && !fullscreen {
+        log_err(app, "remove decorations", window.set_decorations(false));
+    }
+    if !cfg!(target_os = "macos") {
+        log_err(app, "hide menu bar", window.hide_menu());
+    }
+    watchdog::eval(window.as_ref(), FOCUS_SCRIPT, "enter focus mode");
+    sync_app_chrome(app);
+    Ok(())
+}
+
+/// Put `window` back to how it was before `enter_focus_mode`.
+pub fn exit_focus_mode(app: &AppHandle, window: &WebviewWindow) {
+    let restore = app
+        .state::<FocusMode>()
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .remove(window.label());
+    let Some(restore) = restore else {
+        return;
+    };
+
+    if window.is_fullscreen().unwrap_or(false) != restore.fullscreen {
+        log_err(
+            app,
+            "restore fullscreen",
+            window.set_fullscreen(restore.fullscreen),
+        );
+    }
+    if window.is_decorated().unwrap_or(true) != restore.decorated {
+        log_err(
+            app,
+            "restore decorations",
+            window.set_decorations(restore.decorated),
+        );
+    }
+    if !cfg!(target_os = "macos") && restore.menu_visible {
+        log_err(app, "restore menu bar", window.show_menu());
+    }
+    watchdog::eval(window.as_ref(), UNFOCUS_SCRIPT, "exit focus mode");
+    sync_app_chrome(app);
+}
+
+/// Drop saved state for a window that was closed in focus mode.
+pub fn forget_window(app: &AppHandle, label: &str) {
+    let removed = app
+        .state::<FocusMode>()
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .remove(label)
+        .is_some();
+    if removed {
+        sync_app_chrome(app);
+    }
+}
+
+/// Keep the titlebar down in a page loaded while its window is in focus
+/// mode.
+pub fn apply_to_webview(webview: &Webview) {
+    let focus_mode = webview.app_handle().state::<FocusMode>();
+    if focus_mode.is_focused(webview.window().label()) {
+        watchdog::eval(webview, FOCUS_SCRIPT, "keep focus mode");
+    }
+}
+
+pub fn toggle_focus_mode(app: &AppHandle, window: &WebviewWindow) -> Result<(), String> {
+    if app.state::<FocusMode>().is_focused(window.label()) {
+        exit_focus_mode(app, window);
+        Ok(())
+    } else {
+        enter_focus_mode(app, window)
+    }
+}
+
+/// Menu/shortcut entry point: toggle the focused chat window, or the main
+/// one.
+pub fn toggle_focused_focus_mode(app: &AppHandle) {
+    let windows = app.webview_windows();
+    let window = windows
+        .iter()
+        .find(|(label, window)| {
+            !is_bundled_page_window(label) && window.is_focused().unwrap_or(false)
+        })
+        .map(|(_, window)| window)
+        .or_else(|| windows.get("main"));
+    if let Some(window) = window {
+        if let Err(e) = toggle_focus_mode(app, window) {
+            log_backend_error(app, &format!("Failed to toggle focus mode: {e}"));
+        }
+    }
+}
+
+/// Turn focus mode on or off for the calling window
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn toggle_focus_mode_cmd(window: WebviewWindow) -> Result<(), String> {
+    toggle_focus_mode(window.app_handle(), &window)
+}
@@ -0,0 +1,134 @@
+// Handoffs from the Onyx browser extension. Its "Continue in Onyx" opens
+//
+//     onyx://continue?url=<page>&title=<page title>&text=<selected text>
+//
+// and the app opens a new chat with the selection quoted and the page
+// linked under it, typed into the message box but not sent, so there's room
+// to say what to ask about it. The chat opens in the main window, which is
+// unminimized, shown and raised (see `open_route`); if a window already has
+// this same handoff open (the extension button pressed twice, say), that
+// window comes to the front instead of the chat opening again. Handoffs
+// are ignored while the app is locked.
+
+use crate::debug_log::log_backend_error;
+use crate::lock::AppLock;
+use crate::route::Route;
+use crate::window::{is_bundled_page_window, open_route};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+use url::Url;
+
+const HOST: &str = "continue";
+/// Selections longer than this are cut; the whole message goes in the
+/// chat's URL.
+const MAX_TEXT_CHARS: usize = 8_000;
+
+/// `text` as a Markdown quote, cut to `MAX_TEXT_CHARS`. The macOS Services
+/// item quotes selections the same way (see `services.rs`).
+pub fn quote(text: &str) -> String {
+    let text: String = text.chars().take(MAX_TEXT_CHARS).collect();
+    text.lines()
+        .map(|line| format!("> {line}").trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The draft a handoff link opens.
+fn parse(link: &Url) -> Result<Route, String> {
+    let query = |name: &str| {
+        link.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let page = query("url")
+        .and_then(|page| Url::parse(&page).ok())
+        .filter(|page| matches!(page.scheme(), "http" | "https"))
+        .ok_or("The link from the browser doesn't say which page it came from.")?;
+
+    let mut lines = Vec::new();
+    if let Some(text) = query("text") {
+        lines.push(quote(&text));
+        lines.push(String::new());
+    }
+    lines.push(query("title").map_or_else(
+        || format!("Source: {page}"),
+        |title| format!("Source: [{title}]({page})"),
+    ));
+    Ok(Route::Draft {
+        message: lines.join("\n"),
+    })
+}
+
+/// Open the handoff `link` is, if it's one; whether it was.
+pub fn open_url(app: &AppHandle, link: &Url) -> bool {
+    if link.scheme() != crate::auth::SCHEME || link.host_str() != Some(HOST) {
+        return false;
+    }
+    if app.state::<AppLock>().is_locked() {
+        log_backend_error(app, "Ignored a handoff from the browser while locked");
+        return true;
+    }
+    let route = match parse(link) {
+        Ok(route) => route,
+        Err(e) => {
+            app.dialog()
+                .message(e)
+                .title("Continue in Onyx")
+                .kind(MessageDialogKind::Warning)
+                .show(|_| {});
+            return true;
+        }
+    };
+
+    let windows = app.webview_windows();
+    let open = windows.iter().find(|(label, window)| {
+        !is_bundled_page_window(label)
+            && window
+                .url()
+                .is_ok_and(|url| Route::from_url(&url).as_ref() == Some(&route))
+    });
+    if let Some((label, window)) = open {
+        let raised = window
+            .unminimize()
+            .and_then(|()| window.show())
+            .and_then(|()| window.set_focus());
+        if let Err(e) = raised {
+            log_backend_error(app, &format!("Failed to raise {label} window: {e}"));
+        }
+    } else {
+        open_route(app, &route);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn handoffs_quote_the_selection_and_link_the_page() {
+        let link = Url::parse(
+            "onyx://continue?url=https%3A%2F%2Fwiki.example.com%2Fpto&title=PTO+Policy&text=Twenty+days.%0A%0APlus+holidays.",
+        )
+        .unwrap();
+        assert_eq!(
+            parse(&link).unwrap(),
+            Route::Draft {
+                message: "> Twenty days.\n>\n> Plus holidays.\n\nSource: [PTO Policy](https://wiki.example.com/pto)"
+                    .to_string()
+            }
+        );
+
+        let bare = Url::parse("onyx://continue?url=https://wiki.example.com/pto").unwrap();
+        assert_eq!(
+            parse(&bare).unwrap(),
+            Route::Draft {
+                message: "Source: https://wiki.example.com/pto".to_string()
+            }
+        );
+        let local = Url::parse("onyx://continue?url=file:///etc/passwd&text=hi").unwrap();
+        assert!(parse(&local).is_err());
+    }
+}
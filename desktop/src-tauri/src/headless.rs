@@ -0,0 +1,156 @@
+// Headless mode, for scripts and cron jobs: `onyx --headless "question"`
+// asks the configured server without opening a window and prints the
+// answer, and `onyx --headless` alone asks each line of stdin in turn.
+// Answers come out as Markdown, or with `--format json` as one JSON object
+// per question and line. A question that fails is reported in the output
+// and makes the exit status 1; the rest are still asked.
+//
+// It signs in with `ONYX_API_KEY` if that's set, which suits machines
+// nobody signs in on, and otherwise with the session kept from a browser
+// sign-in (see `auth.rs`). Sessions that only live in the webview's cookies
+// can't be read without a window.
+
+use crate::config::{load_config, validate_server_url};
+use crate::server_api::{ask, Answer, Credentials, Source};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::io::BufRead;
+
+const API_KEY_VAR: &str = "ONYX_API_KEY";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    #[default]
+    Markdown,
+    Json,
+}
+
+/// One question's outcome, as `--format json` prints it.
+#[derive(Debug, Serialize)]
+struct Reply<'a> {
+    query: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    answer: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    sources: &'a [Source],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+fn render(format: Format, query: &str, result: &Result<Answer, String>) -> String {
+    let (answer, sources, error) = match result {
+        Ok(answer) => (
+            Some(answer.text.as_deref().unwrap_or_default()),
+            answer.top_documents.as_slice(),
+            None,
+        ),
+        Err(e) => (None, [].as_slice(), Some(e.as_str())),
+    };
+    match format {
+        Format::Json => serde_json::to_string(&Reply {
+            query,
+            answer,
+            sources,
+            error,
+        })
+        .unwrap_or_default(),
+        Format::Markdown => {
+            let mut lines = vec![format!("## {query}"), String::new()];
+            if let Some(e) = error {
+                lines.push(format!("**Error:** {e}"));
+            } else {
+                lines.push(answer.unwrap_or_default().trim().to_string());
+                if !sources.is_empty() {
+                    lines.extend([String::new(), "**Sources:**".to_string(), String::new()]);
+                    lines.extend(sources.iter().map(|source| {
+                        source.link.as_ref().map_or_else(
+                            || format!("- {}", source.semantic_identifier),
+                            |link| format!("- [{}]({link})", source.semantic_identifier),
+                        )
+                    }));
+                }
+            }
+            lines.join("\n")
+        }
+    }
+}
+
+fn credentials(server: &url::Url) -> Result<Credentials, String> {
+    if let Some(key) = std::env::var(API_KEY_VAR)
+        .ok()
+        .filter(|key| !key.trim().is_empty())
+    {
+        return Ok(Credentials::ApiKey(key.trim().to_string()));
+    }
+    crate::auth::kept_session_cookie(server)?
+        .map(Credentials::Cookie)
+        .ok_or_else(|| {
+            format!(
+                "Not signed in: set {API_KEY_VAR}, or use File > Sign In with Browser in the app"
+            )
+        })
+}
+
+/// Ask `query`, or each line of stdin without one, printing the answers;
+/// whether they all succeeded.
+// Printing the answers is what headless mode is for.
+#[allow(clippy::print_stdout)]
+pub fn run(query: Option<&str>, format: Format) -> Result<bool, String> {
+    let (mut config, _) = load_config();
+    crate::install_scope::managed_policy().apply(&mut config);
+    let server = validate_server_url(&config.server_url)?;
+    let credentials = credentials(&server)?;
+
+    let queries: Vec<String> = query.map_or_else(
+        || {
+            std::io::stdin()
+                .lock()
+                .lines()
+                .map_while(Result::ok)
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .collect()
+        },
+        |query| vec![query.to_string()],
+    );
+    let mut all_answered = true;
+    for (index, query) in queries.iter().enumerate() {
+        let result = tauri::async_runtime::block_on(ask(&config, &server, &credentials, query));
+        all_answered &= result.is_ok();
+        if index > 0 && format == Format::Markdown {
+            println!();
+        }
+        println!("{}", render(format, query, &result));
+    }
+    Ok(all_answered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_render_as_markdown_or_json_lines() {
+        let answered = Ok(Answer {
+            chat_session_id: "4f1c".to_string(),
+            text: Some("Twenty days a year.".to_string()),
+            top_documents: vec![Source {
+                semantic_identifier: "PTO Policy".to_string(),
+                link: Some("https://wiki.example.com/pto".to_string()),
+            }],
+            error_msg: None,
+        });
+        assert_eq!(
+            render(Format::Markdown, "How much PTO?", &answered),
+            "## How much PTO?\n\nTwenty days a year.\n\n**Sources:**\n\n- [PTO Policy](https://wiki.example.com/pto)"
+        );
+        assert_eq!(
+            render(Format::Json, "How much PTO?", &answered),
+            r#"{"query":"How much PTO?","answer":"Twenty days a year.","sources":[{"semantic_identifier":"PTO Policy","link":"https://wiki.example.com/pto"}]}"#
+        );
+        assert_eq!(
+            render(Format::Json, "How much PTO?", &Err("offline".to_string())),
+            r#"{"query":"How much PTO?","error":"offline"}"#
+        );
+    }
+}
@@ -0,0 +1,317 @@
+// How this copy of the app was installed, and the managed policy that goes
+// with it. Organizations deploy per-machine (MSI into Program Files, managed
+// through HKLM policy, updated by an admin-run elevated installer), while
+// individuals install per-user (into their profile, updated in place). The
+// two need different update behavior.
+//
+// Managed policy is what an organization's device management enforces: the
+// registry policy key on Windows (HKLM, and HKCU under it), a configuration
+// profile for the app's domain on macOS, `/etc/onyx/policy.json` on Linux.
+// It applies however the app was installed, so a per-user or portable copy
+// is held to the machine's policy too. Each source uses the
+// same names (`ServerUrl`, `CheckForUpdates`, `SendCrashReports`,
+// `LockedFields`). What it sets wins over the user's config, and like what
+// it locks can't be changed from the app (see `locked_fields` in
+// `config.rs`).
+
+use crate::config::AppConfig;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallScope {
+    /// Installed for every user on the machine by an administrator.
+    PerMachine,
+    /// Installed into one user's profile.
+    PerUser,
+    /// Running from a folder with `portable.flag` (see `portable.rs`).
+    Portable,
+}
+
+/// Settings an administrator can enforce through OS policy. `None` leaves
+/// the user's own setting alone.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "PascalCase"))]
+pub struct ManagedPolicy {
+    pub server_url: Option<String>,
+    pub check_for_updates: Option<bool>,
+    /// Whether crash reports are sent, the app's only telemetry (see
+    /// `crash_report.rs`).
+    pub send_crash_reports: Option<bool>,
+    /// More settings locked as the user's config has them, by their path
+    /// in the config (see `locked_fields` in `config.rs`).
+    #[serde(default)]
+    pub locked_fields: Vec<String>,
+}
+
+impl ManagedPolicy {
+    /// Override `config` with every setting this policy enforces.
+    pub fn apply(&self, config: &mut AppConfig) {
+        if let Some(server_url) = &self.server_url {
+            config.server_url.clone_from(server_url);
+        }
+        if let Some(check_for_updates) = self.check_for_updates {
+            config.updates.check_automatically = check_for_updates;
+        }
+        if let Some(send_crash_reports) = self.send_crash_reports {
+            config.crash_reports.upload = send_crash_reports;
+        }
+    }
+
+    /// The config settings this policy enforces, by their path in the
+    /// config.
+    pub fn managed_settings(&self) -> Vec<&'static str> {
+        [
+            ("server_url", self.server_url.is_some()),
+            (
+                "updates.check_automatically",
+                self.check_for_updates.is_some(),
+            ),
+            ("crash_reports.upload", self.send_crash_reports.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(setting, managed)| managed.then_some(setting))
+        .collect()
+    }
+
+    /// This policy, with what it leaves unset taken from `lower`, a policy
+    /// it overrides. What either locks stays locked.
+    #[cfg_attr(not(any(target_os = "windows", test)), allow(dead_code))]
+    fn over(self, lower: Self) -> Self {
+        let mut locked_fields = self.locked_fields;
+        for field in lower.locked_fields {
+            if !locked_fields.contains(&field) {
+                locked_fields.push(field);
+            }
+        }
+        Self {
+            server_url: self.server_url.or(lower.server_url),
+            check_for_updates: self.check_for_updates.or(lower.check_for_updates),
+            send_crash_reports: self.send_crash_reports.or(lower.send_crash_reports),
+            locked_fields,
+        }
+    }
+
+    /// Tidy the policy as read: the server URL as `set_server_url` saves
+    /// it, and a blank one as not set.
+    fn tidied(mut self) -> Self {
+        self.server_url = self
+            .server_url
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty());
+        self
+    }
+}
+
+static INSTALL_SCOPE: OnceLock<InstallScope> = OnceLock::new();
+static MANAGED_POLICY: OnceLock<ManagedPolicy> = OnceLock::new();
+
+/// Machine-wide install locations: Program Files on Windows, `/Applications`
+/// on macOS, system prefixes on Linux.
+fn machine_install_dirs() -> Vec<PathBuf> {
+    if cfg!(target_os = "windows") {
+        ["ProgramFiles", "ProgramW6432", "ProgramFiles(x86)"]
+            .iter()
+            .filter_map(std::env::var_os)
+            .map(PathBuf::from)
+            .collect()
+    } else if cfg!(target_os = "macos") {
+        vec![PathBuf::from("/Applications")]
+    } else {
+        vec![PathBuf::from("/usr"), PathBuf::from("/opt")]
+    }
+}
+
+fn scope_for_exe(exe: &Path, machine_dirs: &[PathBuf]) -> InstallScope {
+    if machine_dirs.iter().any(|dir| exe.starts_with(dir)) {
+        InstallScope::PerMachine
+    } else {
+        InstallScope::PerUser
+    }
+}
+
+pub fn install_scope() -> InstallScope {
+    *INSTALL_SCOPE.get_or_init(|| {
+        if crate::portable::portable_data_dir().is_some() {
+            return InstallScope::Portable;
+        }
+        std::env::current_exe().map_or(InstallScope::PerUser, |exe| {
+            scope_for_exe(&exe, &machine_install_dirs())
+        })
+    })
+}
+
+/// Per-machine installs are updated by whoever deployed them; replacing
+/// files under Program Files needs an elevated, admin-run installer, so the
+/// app must not try to update itself.
+pub fn updates_require_elevation() -> bool {
+    cfg!(target_os = "windows") && install_scope() == InstallScope::PerMachine
+}
+
+/// The policy for this machine and user, whatever the install scope: HKLM
+/// over HKCU on Windows, the app's configuration profiles on macOS,
+/// `/etc/onyx` on Linux.
+pub fn managed_policy() -> &'static ManagedPolicy {
+    MANAGED_POLICY.get_or_init(|| {
+        #[cfg(target_os = "windows")]
+        let policy = windows_policy::read();
+        #[cfg(target_os = "macos")]
+        let policy = macos_policy::read();
+        #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+        let policy = read_policy_file(Path::new("/etc/onyx/policy.json"));
+        policy.tidied()
+    })
+}
+
+/// A JSON policy file, as on Linux. A missing or unreadable one enforces
+/// nothing.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn read_policy_file(path: &Path) -> ManagedPolicy {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// Configuration profiles for the app's defaults domain. Only keys a profile
+/// forces count; anything the user wrote to the domain themselves doesn't.
+#[cfg(target_os = "macos")]
+mod macos_policy {
+    use super::ManagedPolicy;
+    use objc2_foundation::{NSString, NSUserDefaults};
+
+    pub fn read() -> ManagedPolicy {
+        let defaults = NSUserDefaults::standardUserDefaults();
+        let forced = |name: &str| {
+            let key = NSString::from_str(name);
+            defaults.objectIsForcedForKey(&key).then_some(key)
+        };
+        ManagedPolicy {
+            server_url: forced("ServerUrl")
+                .and_then(|key| defaults.stringForKey(&key))
+                .map(|url| url.to_string()),
+            check_for_updates: forced("CheckForUpdates").map(|key| defaults.boolForKey(&key)),
+            send_crash_reports: forced("SendCrashReports").map(|key| defaults.boolForKey(&key)),
+            locked_fields: forced("LockedFields")
+                .and_then(|key| defaults.stringArrayForKey(&key))
+                .map(|fields| fields.iter().map(|field| field.to_string()).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows_policy {
+    use super::ManagedPolicy;
+    use winreg::enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE};
+    use winreg::RegKey;
+    use winreg::HKEY;
+
+    const POLICY_KEY: &str = r"Software\Policies\Onyx\Desktop";
+
+    /// The machine's policy, over the user's: installing per-user doesn't
+    /// get out from under HKLM.
+    pub fn read() -> ManagedPolicy {
+        read_hive(HKEY_LOCAL_MACHINE).over(read_hive(HKEY_CURRENT_USER))
+    }
+
+    fn read_hive(hive: HKEY) -> ManagedPolicy {
+        let Ok(key) = RegKey::predef(hive).open_subkey(POLICY_KEY) else {
+            return ManagedPolicy::default();
+        };
+        let flag = |name: &str| key.get_value::<u32, _>(name).ok().map(|value| value != 0);
+
+        ManagedPolicy {
+            server_url: key.get_value::<String, _>("ServerUrl").ok(),
+            check_for_updates: flag("CheckForUpdates"),
+            send_crash_reports: flag("SendCrashReports"),
+            locked_fields: key
+                .get_value::<Vec<String>, _>("LockedFields")
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope_follows_install_location() {
+        let machine_dirs = [PathBuf::from("/opt"), PathBuf::from("/usr")];
+        assert_eq!(
+            scope_for_exe(Path::new("/opt/onyx/onyx"), &machine_dirs),
+            InstallScope::PerMachine
+        );
+        assert_eq!(
+            scope_for_exe(Path::new("/home/me/.local/bin/onyx"), &machine_dirs),
+            InstallScope::PerUser
+        );
+        // Component-wise, so a sibling with a shared prefix doesn't match.
+        assert_eq!(
+            scope_for_exe(Path::new("/optional/onyx"), &machine_dirs),
+            InstallScope::PerUser
+        );
+    }
+
+    #[test]
+    fn policy_overrides_only_what_it_sets() {
+        let mut config = AppConfig::default();
+        config.updates.check_automatically = true;
+        let policy = ManagedPolicy {
+            server_url: None,
+            check_for_updates: Some(false),
+            send_crash_reports: None,
+            locked_fields: Vec::new(),
+        };
+        policy.apply(&mut config);
+        assert_eq!(config.server_url, AppConfig::default().server_url);
+        assert!(!config.updates.check_automatically);
+        assert_eq!(policy.managed_settings(), ["updates.check_automatically"]);
+    }
+
+    #[test]
+    fn machine_policy_wins_over_the_users() {
+        let machine = ManagedPolicy {
+            server_url: Some("https://onyx.example.com".to_string()),
+            check_for_updates: None,
+            send_crash_reports: Some(false),
+            locked_fields: vec!["server_url".to_string()],
+        };
+        let user = ManagedPolicy {
+            server_url: Some("https://other.example.com".to_string()),
+            check_for_updates: Some(true),
+            send_crash_reports: Some(true),
+            locked_fields: vec!["server_url".to_string(), "admin_mode".to_string()],
+        };
+        let policy = machine.over(user);
+        assert_eq!(
+            policy.server_url.as_deref(),
+            Some("https://onyx.example.com")
+        );
+        assert_eq!(policy.check_for_updates, Some(true));
+        assert_eq!(policy.send_crash_reports, Some(false));
+        assert_eq!(policy.locked_fields, ["server_url", "admin_mode"]);
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn policy_file_uses_the_policy_names() {
+        let policy = serde_json::from_str::<ManagedPolicy>(
+            r#"{"ServerUrl": " https://onyx.example.com/ ", "SendCrashReports": false}"#,
+        )
+        .unwrap()
+        .tidied();
+        assert_eq!(
+            policy.server_url.as_deref(),
+            Some("https://onyx.example.com")
+        );
+        assert_eq!(policy.check_for_updates, None);
+        assert_eq!(policy.send_crash_reports, Some(false));
+
+        let blank = serde_json::from_str::<ManagedPolicy>(r#"{"ServerUrl": ""}"#).unwrap();
+        assert_eq!(blank.tidied().server_url, None);
+    }
+}
@@ -0,0 +1,430 @@
+// One running copy per data directory. Two copies of the app (a release
+// build and a dev build, say, or two channels) on the same data overwrite
+// each other's saved window positions and would do the same to any other
+// file they both keep open. So each copy takes an OS lock on
+// `instance.lock` in the state directory for as long as it runs, and writes
+// who it is to `instance.json` next to it. A copy that finds the lock held
+// says which copy has it and offers to quit that one, or to start over on a
+// separate profile: its own data directory (`--data-dir`, as in portable
+// mode) and window-state file. The lock goes away with the process, so a
+// crash never leaves a stale one behind.
+//
+// On Windows and Linux a clicked `onyx://` link starts another copy with the
+// link as its argument. The copy holding the lock listens on a loopback
+// port it writes to `instance.json`, and a copy started with a link hands
+// it over there (`hand_off`) and quits instead of asking.
+
+use crate::config::{get_config_path, get_state_dir};
+use crate::debug_log::log_backend_error;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, TryLockError};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::{
+    DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult,
+};
+
+const LOCK_FILE_NAME: &str = "instance.lock";
+const INFO_FILE_NAME: &str = "instance.json";
+/// The window-state plugin's own default.
+const WINDOW_STATE_FILE_NAME: &str = ".window-state.json";
+/// How long the other copy gets to quit.
+const QUIT_TIMEOUT: Duration = Duration::from_secs(10);
+const QUIT_OTHER_LABEL: &str = "Quit Other";
+const SEPARATE_LABEL: &str = "Use Separate Profile";
+const QUIT_LABEL: &str = "Quit";
+/// How long a handed-over link gets to arrive.
+const HANDOFF_TIMEOUT: Duration = Duration::from_secs(2);
+/// Far longer than any link the app is sent.
+const MAX_HANDOFF_LEN: u64 = 16 * 1024;
+
+/// What `instance.json` says about the copy holding the lock.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceInfo {
+    pid: u32,
+    version: String,
+    /// Whether it's a debug build, as `cargo tauri dev` makes.
+    dev_build: bool,
+    #[serde(default)]
+    executable: Option<String>,
+    /// The loopback port it takes handed-over links on.
+    #[serde(default)]
+    handoff_port: Option<u16>,
+}
+
+impl InstanceInfo {
+    fn current(handoff_port: Option<u16>) -> Self {
+        Self {
+            pid: std::process::id(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            dev_build: cfg!(debug_assertions),
+            executable: std::env::current_exe()
+                .ok()
+                .map(|path| path.display().to_string()),
+            handoff_port,
+        }
+    }
+
+    /// "version 1.2.3 (a development build, process 4242)".
+    fn describe(&self) -> String {
+        let build = if self.dev_build {
+            "a development build, "
+        } else {
+            ""
+        };
+        format!("version {} ({build}process {})", self.version, self.pid)
+    }
+}
+
+/// Managed state: the lock this copy holds (kept open until it exits), or
+/// the copy it lost the lock to.
+#[derive(Default)]
+pub struct InstanceLock {
+    held: Mutex<Option<File>>,
+    conflict: Mutex<Option<Conflict>>,
+    handoffs: Mutex<Option<TcpListener>>,
+}
+
+/// The copy that had the lock when this one started, which says who it is
+/// unless it's too old to.
+struct Conflict(Option<InstanceInfo>);
+
+enum Claim {
+    Held(File),
+    /// Another copy has it; it says who it is unless it's too old to.
+    Taken(Option<InstanceInfo>),
+}
+
+fn try_claim(state_dir: &Path, info: &InstanceInfo) -> Result<Claim, String> {
+    std::fs::create_dir_all(state_dir).map_err(|e| e.to_string())?;
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(state_dir.join(LOCK_FILE_NAME))
+        .map_err(|e| e.to_string())?;
+    match file.try_lock() {
+        Ok(()) => {
+            let info = serde_json::to_string_pretty(info).map_err(|e| e.to_string())?;
+            std::fs::write(state_dir.join(INFO_FILE_NAME), info).map_err(|e| e.to_string())?;
+            Ok(Claim::Held(file))
+        }
+        Err(TryLockError::WouldBlock) => Ok(Claim::Taken(
+            std::fs::read_to_string(state_dir.join(INFO_FILE_NAME))
+                .ok()
+                .and_then(|info| serde_json::from_str(&info).ok()),
+        )),
+        Err(TryLockError::Error(e)) => Err(e.to_string()),
+    }
+}
+
+impl InstanceLock {
+    /// Take the data directory's lock, or note who has it. Without a state
+    /// directory (or a filesystem that can't lock) there's nothing to guard.
+    pub fn claim() -> Self {
+        let lock = Self::default();
+        let Some(state_dir) = get_state_dir() else {
+            return lock;
+        };
+        // Without a port, links just can't be handed over.
+        let handoffs = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).ok();
+        let port = handoffs
+            .as_ref()
+            .and_then(|listener| listener.local_addr().ok())
+            .map(|address| address.port());
+        match try_claim(&state_dir, &InstanceInfo::current(port)) {
+            Ok(Claim::Held(file)) => {
+                lock.hold(file);
+                *lock
+                    .handoffs
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner) = handoffs;
+            }
+            Ok(Claim::Taken(other)) => {
+                *lock
+                    .conflict
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(Conflict(other));
+            }
+            // No Tauri app to log to yet.
+            #[allow(clippy::print_stderr)]
+            Err(e) => eprintln!("[ONYX ERROR] Failed to lock the data directory: {e}"),
+        }
+        lock
+    }
+
+    fn hold(&self, file: File) {
+        *self
+            .held
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(file);
+    }
+
+    /// Whether another copy has the lock. That copy's window state is left
+    /// alone: this one neither restores nor saves any.
+    pub fn is_conflicting(&self) -> bool {
+        self.conflict
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .is_some()
+    }
+
+    fn take_conflict(&self) -> Option<Conflict> {
+        self.conflict
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .take()
+    }
+
+    /// Give `link` to the copy that has the lock; false if there's none
+    /// or it didn't take it.
+    pub fn hand_off(&self, link: &str) -> bool {
+        let port = self
+            .conflict
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_ref()
+            .and_then(|Conflict(other)| other.as_ref())
+            .and_then(|other| other.handoff_port);
+        let Some(port) = port else {
+            return false;
+        };
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        TcpStream::connect_timeout(&address, HANDOFF_TIMEOUT)
+            .and_then(|mut stream| writeln!(stream, "{link}"))
+            .is_ok()
+    }
+
+    /// Pass every link handed over by another copy to `open`, for as long
+    /// as the app runs.
+    pub fn listen_for_handoffs(&self, app: &AppHandle, open: fn(&AppHandle, &str)) {
+        let Some(listener) = self
+            .handoffs
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .take()
+        else {
+            return;
+        };
+        let app = app.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut link = String::new();
+                let read = stream
+                    .set_read_timeout(Some(HANDOFF_TIMEOUT))
+                    .and_then(|()| {
+                        BufReader::new(stream.take(MAX_HANDOFF_LEN)).read_line(&mut link)
+                    });
+                match read {
+                    Ok(_) => open(&app, link.trim()),
+                    Err(e) => log_backend_error(&app, &format!("Failed to take a link: {e}")),
+                }
+            }
+        });
+    }
+}
+
+/// The data directory for a separate profile of this build, next to the
+/// usual one: one per version, and one shared by dev builds.
+fn separate_data_dir(state_dir: &Path, info: &InstanceInfo) -> PathBuf {
+    let name = if info.dev_build {
+        "dev".to_string()
+    } else {
+        format!("v{}", info.version)
+    };
+    state_dir.join("profiles").join(name)
+}
+
+/// The window-state plugin's file, which it keeps in Tauri's own app
+/// directory rather than ours: its own per `--data-dir`.
+pub fn window_state_file_name(data_dir: Option<&Path>) -> String {
+    data_dir.map_or_else(
+        || WINDOW_STATE_FILE_NAME.to_string(),
+        |dir| {
+            let id =
+                uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_URL, dir.to_string_lossy().as_bytes());
+            format!(".window-state-{id}.json")
+        },
+    )
+}
+
+/// Ask the other copy to quit (the way closing it from the OS would), then
+/// wait for its lock.
+fn quit_other(app: &AppHandle, other: Option<&InstanceInfo>) {
+    let Some(other) = other else {
+        log_backend_error(
+            app,
+            "Failed to quit the other copy: it didn't say which process it is",
+        );
+        show_quit_failed(app);
+        return;
+    };
+    let pid = other.pid.to_string();
+    #[cfg(unix)]
+    let asked = std::process::Command::new("kill").arg(&pid).status();
+    #[cfg(windows)]
+    let asked = std::process::Command::new("taskkill")
+        .args(["/PID", &pid])
+        .status();
+    if let Err(e) = asked {
+        log_backend_error(app, &format!("Failed to quit the other copy: {e}"));
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let Some(state_dir) = get_state_dir() else {
+            return;
+        };
+        let started = std::time::Instant::now();
+        while started.elapsed() < QUIT_TIMEOUT {
+            // Links can't be handed to this copy, which has no port.
+            match try_claim(&state_dir, &InstanceInfo::current(None)) {
+                Ok(Claim::Held(file)) => {
+                    app.state::<InstanceLock>().hold(file);
+                    return;
+                }
+                Ok(Claim::Taken(_)) => tokio::time::sleep(Duration::from_millis(250)).await,
+                Err(e) => {
+                    log_backend_error(&app, &format!("Failed to lock the data directory: {e}"));
+                    return;
+                }
+            }
+        }
+        show_quit_failed(&app);
+    });
+}
+
+fn show_quit_failed(app: &AppHandle) {
+    let handle = app.clone();
+    app.dialog()
+        .message("The other copy of Onyx didn't quit. Quit it yourself, then open Onyx again.")
+        .title("Onyx Is Already Running")
+        .kind(MessageDialogKind::Error)
+        .show(move |_| handle.exit(0));
+}
+
+/// Start this build over on its own data directory, carrying the config
+/// across so it's on the same server.
+fn use_separate_profile(app: &AppHandle) -> Result<(), String> {
+    let state_dir = get_state_dir().ok_or("No state directory")?;
+    let data_dir = separate_data_dir(&state_dir, &InstanceInfo::current(None));
+    std::fs::create_dir_all(&data_dir).map_err(|e| e.to_string())?;
+    if let Some(config_path) = get_config_path().filter(|path| path.exists()) {
+        let copied = config_path
+            .file_name()
+            .map(|name| data_dir.join(name))
+            .filter(|path| !path.exists());
+        if let Some(copied) = copied {
+            std::fs::copy(&config_path, copied).map_err(|e| e.to_string())?;
+        }
+    }
+
+    // An `AppImage` is restarted from the image, not its mount point.
+    let executable = std::env::var_os("APPIMAGE")
+        .map(PathBuf::from)
+        .map_or_else(std::env::current_exe, Ok)
+        .map_err(|e| e.to_string())?;
+    std::process::Command::new(executable)
+        .args(std::env::args_os().skip(1))
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    app.exit(0);
+    Ok(())
+}
+
+/// If another copy has this data directory, say which and let the user pick
+/// how to go on. Anything but quitting one of them stops this copy.
+pub fn warn_if_conflicting(app: &AppHandle) {
+    let Some(Conflict(other)) = app.state::<InstanceLock>().take_conflict() else {
+        return;
+    };
+    let who = other
+        .as_ref()
+        .map_or_else(|| "an older version".to_string(), InstanceInfo::describe);
+    let handle = app.clone();
+    app.dialog()
+        .message(format!(
+            "Another copy of Onyx, {who}, is already using this app's data. Running \
+             both at once corrupts saved window positions and other app data.\n\n\
+             Quit the other copy, or keep it and start this one on a separate \
+             profile with its own data and sign-ins."
+        ))
+        .title("Onyx Is Already Running")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::YesNoCancelCustom(
+            QUIT_OTHER_LABEL.to_string(),
+            SEPARATE_LABEL.to_string(),
+            QUIT_LABEL.to_string(),
+        ))
+        .show_with_result(move |result| match result {
+            MessageDialogResult::Yes => quit_other(&handle, other.as_ref()),
+            MessageDialogResult::Custom(label) if label == QUIT_OTHER_LABEL => {
+                quit_other(&handle, other.as_ref());
+            }
+            MessageDialogResult::No => separate_or_quit(&handle),
+            MessageDialogResult::Custom(label) if label == SEPARATE_LABEL => {
+                separate_or_quit(&handle);
+            }
+            _ => handle.exit(0),
+        });
+}
+
+fn separate_or_quit(app: &AppHandle) {
+    if let Err(e) = use_separate_profile(app) {
+        log_backend_error(app, &format!("Failed to start a separate profile: {e}"));
+        app.exit(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn a_second_claim_sees_who_holds_the_lock() {
+        let dir = std::env::temp_dir().join(format!("onyx-instance-{}", std::process::id()));
+        let info = InstanceInfo::current(Some(4242));
+        let held = try_claim(&dir, &info).unwrap();
+        assert!(matches!(held, Claim::Held(_)));
+        assert!(matches!(
+            try_claim(&dir, &InstanceInfo::current(None)).unwrap(),
+            Claim::Taken(Some(other)) if other == info
+        ));
+
+        drop(held);
+        assert!(matches!(try_claim(&dir, &info).unwrap(), Claim::Held(_)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn separate_profiles_are_per_build() {
+        let info = |version: &str, dev_build| InstanceInfo {
+            pid: 1,
+            version: version.to_string(),
+            dev_build,
+            executable: None,
+            handoff_port: None,
+        };
+        let state = Path::new("state");
+        assert_eq!(
+            separate_data_dir(state, &info("1.2.3", false)),
+            state.join("profiles").join("v1.2.3")
+        );
+        assert_eq!(
+            separate_data_dir(state, &info("1.2.3", true)),
+            state.join("profiles").join("dev")
+        );
+        assert_eq!(window_state_file_name(None), ".window-state.json");
+        assert_ne!(
+            window_state_file_name(Some(Path::new("a"))),
+            window_state_file_name(Some(Path::new("b")))
+        );
+    }
+}
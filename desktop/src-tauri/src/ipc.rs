@@ -0,0 +1,286 @@
+// A local socket for editor plugins and other tools on this machine to
+// drive the running app without HTTP: a Unix socket, `ipc/onyx.sock` in
+// the state directory, or on Windows the named pipe
+// `\\.\pipe\onyx-desktop-<SID>`, named for the user (and
+// `onyx-desktop-<SID>-<id>` for a `--data-dir` profile). Only the user
+// running Onyx can connect: the socket is in a directory only they can
+// enter, and a pipe's default access only lets its owner write.
+//
+// Each message is a frame: its length as a 4-byte big-endian number, then
+// that much UTF-8 JSON. A request is one of the companion commands (see
+// `companion::Message`), for example `{"command": "ask", "query": "..."}`,
+// `{"command": "open_chat", "id": "..."}` or `{"command": "focus"}`, and
+// each gets a frame back: `{"ok": true, "output": "..."}` or
+// `{"ok": false, "error": "..."}`. A connection can send any number of
+// requests. The `onyx` commands in a terminal use the same socket.
+
+use crate::companion::{answer, Message};
+use crate::debug_log::log_backend_error;
+use interprocess::local_socket::prelude::*;
+use interprocess::local_socket::{ListenerOptions, Name, Stream};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use tauri::AppHandle;
+
+/// Far larger than any request.
+const MAX_FRAME_LEN: u32 = 64 * 1024;
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Reply {
+    ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl From<Result<String, String>> for Reply {
+    fn from(result: Result<String, String>) -> Self {
+        match result {
+            Ok(output) => Self {
+                ok: true,
+                output: Some(output),
+                error: None,
+            },
+            Err(error) => Self {
+                ok: false,
+                output: None,
+                error: Some(error),
+            },
+        }
+    }
+}
+
+#[cfg(unix)]
+fn socket_dir() -> io::Result<std::path::PathBuf> {
+    crate::config::get_state_dir()
+        .map(|dir| dir.join("ipc"))
+        .ok_or_else(|| io::Error::other("Could not determine the state directory"))
+}
+
+#[cfg(unix)]
+fn socket_path() -> io::Result<std::path::PathBuf> {
+    socket_dir().map(|dir| dir.join("onyx.sock"))
+}
+
+/// Create the socket's directory, private from the start, and make sure one
+/// that was already there is too, since the socket is only as private as
+/// the directory it's in.
+#[cfg(unix)]
+fn private_socket_dir() -> io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let dir = socket_dir()?;
+    if let Some(parent) = dir.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+        _ => {}
+    }
+    let meta = std::fs::symlink_metadata(&dir)?;
+    if !meta.is_dir() || meta.permissions().mode() & 0o077 != 0 {
+        return Err(io::Error::other(format!(
+            "{} isn't private to this user",
+            dir.display()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn socket_name() -> io::Result<Name<'static>> {
+    use interprocess::local_socket::GenericFilePath;
+
+    socket_path()?.to_fs_name::<GenericFilePath>()
+}
+
+#[cfg(windows)]
+fn socket_name() -> io::Result<Name<'static>> {
+    use interprocess::local_socket::GenericNamespaced;
+
+    // Pipes are shared by every session on the machine, so each user gets
+    // their own rather than reaching whoever started Onyx first.
+    let user = format!("onyx-desktop-{}", user_sid()?);
+    let name = crate::portable::portable_data_dir().map_or_else(
+        || user.clone(),
+        |dir| {
+            let id =
+                uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_URL, dir.to_string_lossy().as_bytes());
+            format!("{user}-{id}")
+        },
+    );
+    name.to_ns_name::<GenericNamespaced>()
+}
+
+/// The current user's SID, as `S-1-5-21-...`.
+#[cfg(windows)]
+fn user_sid() -> io::Result<String> {
+    use windows::core::PWSTR;
+    use windows::Win32::Foundation::{CloseHandle, LocalFree, HANDLE, HLOCAL};
+    use windows::Win32::Security::Authorization::ConvertSidToStringSidW;
+    use windows::Win32::Security::{GetTokenInformation, TokenUser, TOKEN_QUERY, TOKEN_USER};
+    use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    // SAFETY: the token is this process's own and closed before returning;
+    // `TOKEN_USER` is read from a buffer of the size Windows asked for,
+    // aligned for it as a `u64` buffer, and its SID string is freed with
+    // `LocalFree` as `ConvertSidToStringSidW` requires.
+    unsafe {
+        let mut token = HANDLE::default();
+        OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &raw mut token)?;
+        let mut len = 0;
+        // Fails, saying how big the buffer has to be.
+        let _ = GetTokenInformation(token, TokenUser, None, 0, &raw mut len);
+        let mut buffer = vec![0_u64; (len as usize).div_ceil(8)];
+        let read = GetTokenInformation(
+            token,
+            TokenUser,
+            Some(buffer.as_mut_ptr().cast()),
+            len,
+            &raw mut len,
+        );
+        let _ = CloseHandle(token);
+        read?;
+        let user = &*buffer.as_ptr().cast::<TOKEN_USER>();
+        let mut sid = PWSTR::null();
+        ConvertSidToStringSidW(user.User.Sid, &raw mut sid)?;
+        let text = sid.to_string().map_err(io::Error::other);
+        LocalFree(Some(HLOCAL(sid.0.cast())));
+        text
+    }
+}
+
+/// The next frame from `reader`, or `None` once the other end is done.
+fn read_frame(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::other(format!(
+            "A {len}-byte message is too long"
+        )));
+    }
+    let mut frame = vec![0; usize::try_from(len).map_err(io::Error::other)?];
+    reader.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+fn write_frame(writer: &mut impl Write, frame: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(frame.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME_LEN)
+        .ok_or_else(|| io::Error::other("The message is too long"))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(frame)?;
+    writer.flush()
+}
+
+fn serve(app: &AppHandle, mut stream: Stream) -> io::Result<()> {
+    while let Some(frame) = read_frame(&mut stream)? {
+        let reply: Reply = serde_json::from_slice::<Message>(&frame)
+            .map_err(|e| format!("Not a command Onyx knows: {e}"))
+            .and_then(|message| answer(app, message))
+            .into();
+        write_frame(&mut stream, &serde_json::to_vec(&reply)?)?;
+    }
+    Ok(())
+}
+
+/// Take requests on the socket for as long as the app runs. Only for the
+/// copy that holds the data directory.
+pub fn listen(app: &AppHandle) {
+    let listener = socket_name().and_then(|name| {
+        #[cfg(unix)]
+        {
+            private_socket_dir()?;
+            // A socket file left by a copy that crashed; the instance lock
+            // says none is running now.
+            let _ = std::fs::remove_file(socket_path()?);
+        }
+        ListenerOptions::new().name(name).create_sync()
+    });
+    let listener = match listener {
+        Ok(listener) => listener,
+        Err(e) => {
+            log_backend_error(app, &format!("Failed to open the local socket: {e}"));
+            return;
+        }
+    };
+    let app = app.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let app = app.clone();
+                    std::thread::spawn(move || {
+                        if let Err(e) = serve(&app, stream) {
+                            log_backend_error(&app, &format!("Local socket request failed: {e}"));
+                        }
+                    });
+                }
+                Err(e) => log_backend_error(&app, &format!("Local socket failed: {e}")),
+            }
+        }
+    });
+}
+
+/// Send `message` to the running copy and return its output; `Ok(None)` if
+/// no copy is listening.
+pub fn send(message: &Message) -> Result<Option<String>, String> {
+    let name = socket_name().map_err(|e| e.to_string())?;
+    let mut stream = match Stream::connect(name) {
+        Ok(stream) => stream,
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Ok(None);
+        }
+        Err(e) => return Err(format!("Couldn't reach the running copy of Onyx: {e}")),
+    };
+    let request = serde_json::to_vec(message).map_err(|e| e.to_string())?;
+    write_frame(&mut stream, &request).map_err(|e| e.to_string())?;
+    let frame = read_frame(&mut stream)
+        .map_err(|e| e.to_string())?
+        .ok_or("The running copy of Onyx didn't answer")?;
+    let reply: Reply = serde_json::from_slice(&frame).map_err(|e| e.to_string())?;
+    if reply.ok {
+        Ok(Some(reply.output.unwrap_or_default()))
+    } else {
+        Err(reply.error.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn requests_and_replies_are_length_prefixed_json() {
+        let mut sent = Vec::new();
+        let message = serde_json::to_vec(&Message::Focus).unwrap();
+        write_frame(&mut sent, &message).unwrap();
+        write_frame(&mut sent, b"{}").unwrap();
+        assert_eq!(&sent[..4], &[0, 0, 0, 19]);
+        assert_eq!(&sent[4..23], br#"{"command":"focus"}"#);
+
+        let mut received = sent.as_slice();
+        assert_eq!(read_frame(&mut received).unwrap(), Some(message));
+        assert_eq!(read_frame(&mut received).unwrap(), Some(b"{}".to_vec()));
+        assert_eq!(read_frame(&mut received).unwrap(), None);
+
+        let too_long = (MAX_FRAME_LEN + 1).to_be_bytes();
+        assert!(read_frame(&mut too_long.as_slice()).is_err());
+        assert_eq!(
+            serde_json::to_string(&Reply::from(Err("Onyx is locked".to_string()))).unwrap(),
+            r#"{"ok":false,"error":"Onyx is locked"}"#
+        );
+    }
+}
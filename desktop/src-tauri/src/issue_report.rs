@@ -0,0 +1,109 @@
+// Help > Report an Issue: the pieces of a useful bug report, gathered in
+// one folder. It holds a screenshot of the window the problem is in (see
+// `screenshot.rs`, with its text blurred if the user prefers) and the same
+// diagnostics `get_diagnostics` returns. The folder is opened next to a new
+// GitHub issue, and the user decides what to attach; nothing is uploaded
+// from here.
+
+use crate::config::get_state_dir;
+use crate::debug_log::{format_utc_timestamp, log_backend_error};
+use crate::window::{is_bundled_page_window, open_in_default_browser};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, WebviewWindow};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogResult};
+
+const NEW_ISSUE_URL: &str = "https://github.com/onyx-dot-app/onyx/issues/new";
+const BLUR_LABEL: &str = "Blur Text";
+const KEEP_LABEL: &str = "Keep Text";
+const CANCEL_LABEL: &str = "Cancel";
+
+/// The window being reported on: the focused Onyx window, or the main one.
+fn report_window(app: &AppHandle) -> Option<WebviewWindow> {
+    app.webview_windows()
+        .into_iter()
+        .find(|(label, window)| {
+            !is_bundled_page_window(label) && window.is_focused().unwrap_or(false)
+        })
+        .map(|(_, window)| window)
+        .or_else(|| app.get_webview_window("main"))
+}
+
+/// `reports/report-<time>` in the state directory, spelled so every
+/// platform accepts it as a folder name.
+fn report_dir(state_dir: &Path, timestamp: &str) -> PathBuf {
+    state_dir
+        .join("reports")
+        .join(format!("report-{}", timestamp.replace(':', "-")))
+}
+
+async fn write_report(
+    app: &AppHandle,
+    window: &WebviewWindow,
+    blur_text: bool,
+) -> Result<PathBuf, String> {
+    let dir = report_dir(
+        &get_state_dir().ok_or("No state directory")?,
+        &format_utc_timestamp(),
+    );
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let screenshot = crate::screenshot::capture(window, blur_text).await?;
+    std::fs::write(dir.join("screenshot.png"), screenshot).map_err(|e| e.to_string())?;
+    let diagnostics =
+        serde_json::to_string_pretty(&crate::diagnostics::get_diagnostics(app.clone()))
+            .map_err(|e| e.to_string())?;
+    std::fs::write(dir.join("diagnostics.json"), diagnostics).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+fn report(app: AppHandle, window: WebviewWindow, blur_text: bool) {
+    tauri::async_runtime::spawn(async move {
+        let dir = match write_report(&app, &window, blur_text).await {
+            Ok(dir) => dir,
+            Err(e) => {
+                log_backend_error(&app, &format!("Failed to prepare issue report: {e}"));
+                return;
+            }
+        };
+        let url_path = dir.to_string_lossy().replace('\\', "/");
+        if !open_in_default_browser(&format!("file:///{}", url_path.trim_start_matches('/'))) {
+            log_backend_error(
+                &app,
+                &format!("Failed to open issue report at {}", dir.display()),
+            );
+        }
+        if !open_in_default_browser(NEW_ISSUE_URL) {
+            log_backend_error(&app, "Failed to open the new issue page");
+        }
+    });
+}
+
+/// Ask whether to blur the screenshot's text, then gather the report.
+pub fn start_report(app: &AppHandle) {
+    let Some(window) = report_window(app) else {
+        return;
+    };
+    let handle = app.clone();
+    app.dialog()
+        .message(
+            "A screenshot of this window and diagnostics about the app will be saved \
+             to a folder for you to attach to a new issue. Blur the text in the \
+             screenshot?",
+        )
+        .title("Report an Issue")
+        .buttons(MessageDialogButtons::YesNoCancelCustom(
+            BLUR_LABEL.to_string(),
+            KEEP_LABEL.to_string(),
+            CANCEL_LABEL.to_string(),
+        ))
+        .show_with_result(move |result| {
+            let blur_text = match result {
+                MessageDialogResult::Yes => true,
+                MessageDialogResult::No => false,
+                MessageDialogResult::Custom(label) if label == BLUR_LABEL => true,
+                MessageDialogResult::Custom(label) if label == KEEP_LABEL => false,
+                _ => return,
+            };
+            report(handle, window, blur_text);
+        });
+}
@@ -0,0 +1,91 @@
+// Kiosk mode, for an Onyx answer station on a shared terminal (a shop floor,
+// a reception desk): started with `--kiosk`, or with `kiosk.enabled` in the
+// config. The main window fills the screen and can't be closed, and it's the
+// only window there is: New Window, Settings, the menus and tray with every
+// shortcut, links from other apps, push to talk and devtools are all left
+// out, and links to other sites don't open at all rather than opening in the
+// browser. Nor does anything run that keeps what one person did for the
+// next: the automation API, AppleScript and Services, shared folders, the
+// offline cache and the clipboard history. After
+// `kiosk.reset_after_minutes` without use the station starts over: whoever
+// was signed in is signed out, on the server too, and the page's cookies
+// and storage (drafts, what the web app remembers) are cleared, so the next
+// person can't open the last one's conversations. The station is left on
+// the login page. Use is what auto-lock counts (see `lock.rs`).
+//
+// Whether the app is a kiosk is decided once, at startup, and kept for the
+// run; changing `kiosk.enabled` takes a restart.
+
+use crate::config::ConfigState;
+use crate::debug_log::log_backend_error;
+use crate::lock::AppLock;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, WebviewWindow};
+
+static KIOSK: OnceLock<bool> = OnceLock::new();
+
+/// How often the idle time is checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Run as a kiosk for the rest of the run. Must run before the app is set
+/// up.
+pub fn enable() {
+    let _ = KIOSK.set(true);
+}
+
+/// Whether the app is running as a kiosk.
+pub fn is_active() -> bool {
+    KIOSK.get().copied().unwrap_or(false)
+}
+
+/// The error for what a kiosk doesn't do.
+pub fn unavailable() -> String {
+    "Not available in kiosk mode".to_string()
+}
+
+/// Fill the screen with `window`, the kiosk's only one.
+pub fn setup_window(app: &AppHandle, window: &WebviewWindow) {
+    if let Err(e) = window.set_fullscreen(true) {
+        log_backend_error(app, &format!("Kiosk: failed to go fullscreen: {e}"));
+    }
+}
+
+/// Start over in `window`: end the session, clear what the page keeps, and
+/// go to the login page.
+async fn reset(app: &AppHandle, window: &WebviewWindow) {
+    if let Err(e) = crate::auth::sign_out(app, window).await {
+        log_backend_error(app, &format!("Kiosk: failed to sign out: {e}"));
+    }
+}
+
+/// Start over whenever the kiosk has gone `kiosk.reset_after_minutes`
+/// without use, once for each time it's left alone.
+pub fn watch_for_idle(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut reset_done = false;
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let minutes = app
+                .state::<ConfigState>()
+                .config()
+                .kiosk
+                .reset_after_minutes;
+            let lock = app.state::<AppLock>();
+            let idle =
+                minutes > 0 && lock.idle_for() >= Duration::from_secs(u64::from(minutes) * 60);
+            if !idle {
+                reset_done = false;
+                continue;
+            }
+            if reset_done || lock.is_locked() {
+                continue;
+            }
+            reset_done = true;
+            if let Some(window) = app.get_webview_window("main") {
+                reset(&app, &window).await;
+            }
+        }
+    });
+}
@@ -0,0 +1,873 @@
+// Local folders shared with the server, which makes the app a small
+// personal connector. The user names folders in `local_files.folders`, and
+// every ten minutes (and when Settings or the tray asks) the files in them
+// that match `include`, don't match `exclude` and fit under `max_file_mb`
+// go up to the file connector named `local_files.connector` ("My Desktop
+// Files") as the main window's user, so chats can draw on them like any
+// other source. Watched folders (`local_files.watch`) work the same way,
+// each going to the file connector in the document set named with it.
+// Adding those connectors is up to a curator or admin on the server; the
+// app only fills them, and needs a curator's or admin's sign-in to.
+//
+// What's been uploaded is kept in `local_files.json` in the state
+// directory (per connector, each file's size, modification time, and the
+// server's ID for it), so only new and changed files go up again, and
+// changed and deleted ones are taken out of the connector. Uploads go in
+// batches of up to `BATCH_BYTES`, and what's been sent is saved after
+// each, so an interrupted sync picks up where it stopped. The same file
+// keeps the last few files synced, and whether each made it, for the tray's
+// Sync Activity submenu. Zip files are left out, since the server would
+// unpack them; so are symlinks, which could point outside the folders.
+//
+// With `dry_run` on, a sync still finds the connectors, but only lists what
+// it would upload and take out, under Sync Activity and in the debug log;
+// nothing is sent, and what's been uploaded stays as it was.
+
+use crate::config::{get_state_dir, validate_server_url, ConfigState, LocalFilesSettings};
+use crate::consent::{request_consent, Capability};
+use crate::debug_log::{log_backend_error, log_debug};
+use crate::server_api::{
+    connectors, editable_document_sets, pair_connector, update_connector_files,
+};
+use crate::window::SETTINGS_WINDOW_LABEL;
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::menu::{MenuItem, PredefinedMenuItem, Submenu, SubmenuBuilder};
+use tauri::{AppHandle, Manager, WebviewWindow, Wry};
+use url::Url;
+use walkdir::WalkDir;
+
+const STATE_FILE: &str = "local_files.json";
+const SYNC_INTERVAL: Duration = Duration::from_mins(10);
+/// The most one upload request carries, unless a single file is bigger.
+const BATCH_BYTES: u64 = 32 * 1024 * 1024;
+/// The `source` of file connectors.
+const FILE_SOURCE: &str = "file";
+const NOT_ALLOWED: &str = "Sign in as a curator or admin in the main window to share folders";
+/// How many of the last files synced Sync Activity lists.
+const RECENT_ACTIVITY: usize = 10;
+/// The tray's Sync Now item.
+pub const SYNC_NOW_ID: &str = "local_files:sync_now";
+
+#[derive(Default)]
+pub struct LocalFiles {
+    /// Held while a sync runs, so there's only one at a time.
+    syncing: tokio::sync::Mutex<()>,
+    /// The tray's Sync Activity submenu.
+    menu: Mutex<Option<Submenu<Wry>>>,
+}
+
+/// Register the sync state.
+pub fn manage(builder: tauri::Builder<Wry>) -> tauri::Builder<Wry> {
+    builder.manage(LocalFiles::default())
+}
+
+/// A file as it was on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Stamp {
+    size: u64,
+    /// Seconds since the Unix epoch.
+    modified: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Uploaded {
+    #[serde(flatten)]
+    stamp: Stamp,
+    file_id: String,
+}
+
+/// A file a sync sent, or tried to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Activity {
+    file: String,
+    /// The connector or document set it went to.
+    target: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Whether it was only listed, by a dry run.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    dry_run: bool,
+    /// Whether it was to be taken out rather than sent.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    removed: bool,
+}
+
+impl Activity {
+    fn label(&self) -> String {
+        match &self.error {
+            None if self.dry_run && self.removed => {
+                format!("Would take {} out of {}", self.file, self.target)
+            }
+            None if self.dry_run => format!("Would upload {} to {}", self.file, self.target),
+            None => format!("Uploaded {} to {}", self.file, self.target),
+            Some(error) => {
+                let error: String = error.chars().take(60).collect();
+                format!("Couldn't upload {}: {error}", self.file)
+            }
+        }
+    }
+}
+
+/// What's in the connectors, as of the last sync.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+struct SyncState {
+    /// The files uploaded to each connector, by its ID.
+    connectors: BTreeMap<u64, BTreeMap<PathBuf, Uploaded>>,
+    /// The last files synced, newest first.
+    recent: Vec<Activity>,
+}
+
+impl SyncState {
+    fn record(&mut self, path: &Path, target: &str, error: Option<String>) {
+        self.push(Activity {
+            file: file_name(path),
+            target: target.to_string(),
+            error,
+            dry_run: false,
+            removed: false,
+        });
+    }
+
+    /// Note that a dry run would have uploaded `path`.
+    fn record_dry_run(&mut self, path: &Path, target: &str) {
+        self.push(Activity {
+            file: file_name(path),
+            target: target.to_string(),
+            error: None,
+            dry_run: true,
+            removed: false,
+        });
+    }
+
+    /// Note that a dry run would have taken `path` out.
+    fn record_dry_run_removal(&mut self, path: &Path, target: &str) {
+        self.push(Activity {
+            file: file_name(path),
+            target: target.to_string(),
+            error: None,
+            dry_run: true,
+            removed: true,
+        });
+    }
+
+    fn push(&mut self, activity: Activity) {
+        self.recent.insert(0, activity);
+        self.recent.truncate(RECENT_ACTIVITY);
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn state_path() -> Option<PathBuf> {
+    get_state_dir().map(|dir| dir.join(STATE_FILE))
+}
+
+fn load_state() -> SyncState {
+    state_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(state: &SyncState) -> Result<(), String> {
+    let path = state_path().ok_or("Could not determine the state directory")?;
+    let json = serde_json::to_string_pretty(state).map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| format!("Failed to save what's been uploaded: {e}"))
+}
+
+/// Which files in the folders are shared.
+struct Filter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    max_bytes: u64,
+}
+
+fn patterns(globs: &[String]) -> Result<Vec<Pattern>, String> {
+    globs
+        .iter()
+        .map(|glob| Pattern::new(glob).map_err(|e| format!("\"{glob}\" isn't a glob: {e}")))
+        .collect()
+}
+
+impl Filter {
+    fn new(settings: &LocalFilesSettings) -> Result<Self, String> {
+        Ok(Self {
+            include: patterns(&settings.include)?,
+            exclude: patterns(&settings.exclude)?,
+            max_bytes: settings.max_file_mb.saturating_mul(1024 * 1024),
+        })
+    }
+
+    /// Whether the file at `relative` (inside its folder), of `size` bytes,
+    /// is shared.
+    fn allows(&self, relative: &Path, size: u64) -> bool {
+        // `*` stays within a directory; `**` crosses them.
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        let matches = |pattern: &Pattern| pattern.matches_path_with(relative, options);
+        size <= self.max_bytes
+            && !relative
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+            && (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+/// The files in `folders` that `filter` lets through.
+fn scan(folders: &[String], filter: &Filter) -> BTreeMap<PathBuf, Stamp> {
+    let mut found = BTreeMap::new();
+    for folder in folders {
+        let folder = Path::new(folder);
+        for entry in WalkDir::new(folder).into_iter().filter_map(Result::ok) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let (Ok(metadata), Ok(relative)) =
+                (entry.metadata(), entry.path().strip_prefix(folder))
+            else {
+                continue;
+            };
+            if !filter.allows(relative, metadata.len()) {
+                continue;
+            }
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_secs());
+            found.insert(
+                entry.path().to_path_buf(),
+                Stamp {
+                    size: metadata.len(),
+                    modified,
+                },
+            );
+        }
+    }
+    found
+}
+
+/// What a sync has to do.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Plan {
+    /// Files that are new or changed.
+    upload: Vec<PathBuf>,
+    /// The IDs of uploaded files that changed or are gone.
+    remove: Vec<String>,
+}
+
+fn plan(uploaded: &BTreeMap<PathBuf, Uploaded>, found: &BTreeMap<PathBuf, Stamp>) -> Plan {
+    Plan {
+        upload: found
+            .iter()
+            .filter(|(path, stamp)| {
+                uploaded
+                    .get(*path)
+                    .is_none_or(|uploaded| uploaded.stamp != **stamp)
+            })
+            .map(|(path, _)| path.clone())
+            .collect(),
+        remove: uploaded
+            .iter()
+            .filter(|(path, uploaded)| found.get(*path) != Some(&uploaded.stamp))
+            .map(|(_, uploaded)| uploaded.file_id.clone())
+            .collect(),
+    }
+}
+
+/// `upload` split into requests of up to `BATCH_BYTES` each.
+fn batches<'a>(upload: &'a [PathBuf], found: &BTreeMap<PathBuf, Stamp>) -> Vec<Vec<&'a PathBuf>> {
+    let mut batches: Vec<Vec<&PathBuf>> = Vec::new();
+    let mut batch_size = 0;
+    for path in upload {
+        let size = found.get(path).map_or(0, |stamp| stamp.size);
+        match batches.last_mut() {
+            Some(batch) if batch_size + size <= BATCH_BYTES => {
+                batch.push(path);
+                batch_size += size;
+            }
+            _ => {
+                batches.push(vec![path]);
+                batch_size = size;
+            }
+        }
+    }
+    batches
+}
+
+/// A connector and the folders that go to it.
+struct Target {
+    connector_id: u64,
+    /// The connector's or document set's name, for Sync Activity.
+    name: String,
+    folders: Vec<String>,
+}
+
+fn add_target(targets: &mut Vec<Target>, connector_id: u64, name: &str, folders: &[String]) {
+    if let Some(target) = targets
+        .iter_mut()
+        .find(|target| target.connector_id == connector_id)
+    {
+        target.folders.extend_from_slice(folders);
+    } else {
+        targets.push(Target {
+            connector_id,
+            name: name.to_string(),
+            folders: folders.to_vec(),
+        });
+    }
+}
+
+/// Where the folders go, and why any can't go anywhere.
+async fn find_targets(
+    window: &WebviewWindow,
+    server: &Url,
+    settings: &LocalFilesSettings,
+) -> Result<(Vec<Target>, Vec<String>), String> {
+    let mut targets = Vec::new();
+    let mut errors = Vec::new();
+    if !settings.folders.is_empty() {
+        let connector = connectors(window, server)
+            .await?
+            .ok_or(NOT_ALLOWED)?
+            .into_iter()
+            .find(|connector| {
+                connector.source == FILE_SOURCE && connector.name == settings.connector
+            });
+        match connector {
+            Some(connector) => add_target(
+                &mut targets,
+                connector.id,
+                &settings.connector,
+                &settings.folders,
+            ),
+            None => errors.push(format!(
+                "The server has no file connector named \"{}\"; an admin can add one",
+                settings.connector
+            )),
+        }
+    }
+    if !settings.watch.is_empty() {
+        let sets = editable_document_sets(window, server)
+            .await?
+            .ok_or(NOT_ALLOWED)?;
+        for watched in &settings.watch {
+            let pair = sets
+                .iter()
+                .find(|set| set.name == watched.document_set)
+                .and_then(|set| {
+                    set.cc_pair_summaries
+                        .iter()
+                        .find(|pair| pair.source == FILE_SOURCE)
+                });
+            let Some(pair) = pair else {
+                errors.push(format!(
+                    "There's no document set named \"{}\" with a file connector that you can edit",
+                    watched.document_set
+                ));
+                continue;
+            };
+            let connector = pair_connector(window, server, pair.id)
+                .await?
+                .ok_or(NOT_ALLOWED)?;
+            add_target(
+                &mut targets,
+                connector.id,
+                &watched.document_set,
+                std::slice::from_ref(&watched.folder),
+            );
+        }
+    }
+    Ok((targets, errors))
+}
+
+/// How many files a sync sent, took out, and couldn't send.
+#[derive(Debug, Default)]
+struct Counts {
+    uploaded: usize,
+    removed: usize,
+    failed: usize,
+}
+
+/// Save `state` and show its activity in the tray.
+fn save(app: &AppHandle, state: &SyncState) -> Result<(), String> {
+    save_state(state)?;
+    show_activity(app, &state.recent);
+    Ok(())
+}
+
+/// List what bringing `target`'s connector up to date would upload and
+/// take out, for a dry run, without sending anything.
+fn dry_run_target(
+    app: &AppHandle,
+    filter: &Filter,
+    target: &Target,
+    state: &mut SyncState,
+    counts: &mut Counts,
+) {
+    let found = scan(&target.folders, filter);
+    let uploaded = state
+        .connectors
+        .get(&target.connector_id)
+        .cloned()
+        .unwrap_or_default();
+    let Plan { upload, remove } = plan(&uploaded, &found);
+    // Changed files are taken out as well as sent again, as in a real sync.
+    for (path, _) in uploaded
+        .iter()
+        .filter(|(_, uploaded)| remove.contains(&uploaded.file_id))
+    {
+        log_debug(
+            app,
+            &format!(
+                "Dry run: would take {} out of {}",
+                path.display(),
+                target.name
+            ),
+        );
+        state.record_dry_run_removal(path, &target.name);
+    }
+    for path in &upload {
+        log_debug(
+            app,
+            &format!(
+                "Dry run: would upload {} to {}",
+                path.display(),
+                target.name
+            ),
+        );
+        state.record_dry_run(path, &target.name);
+    }
+    counts.uploaded += upload.len();
+    counts.removed += remove.len();
+}
+
+/// Bring `target`'s connector up to date with its folders. Files that
+/// couldn't be read or sent are counted and recorded, not errors.
+async fn sync_target(
+    app: &AppHandle,
+    window: &WebviewWindow,
+    server: &Url,
+    filter: &Filter,
+    target: &Target,
+    state: &mut SyncState,
+    counts: &mut Counts,
+) -> Result<(), String> {
+    let id = target.connector_id;
+    let found = scan(&target.folders, filter);
+    let Plan { upload, mut remove } = plan(
+        state.connectors.get(&id).unwrap_or(&BTreeMap::new()),
+        &found,
+    );
+    let mut batches = batches(&upload, &found);
+    if batches.is_empty() && !remove.is_empty() {
+        batches.push(Vec::new());
+    }
+
+    for batch in batches {
+        let mut files = Vec::new();
+        let mut sent = Vec::new();
+        for path in batch {
+            match fs::read(path) {
+                Ok(bytes) => {
+                    files.push((file_name(path), bytes));
+                    sent.push(path);
+                }
+                Err(e) => {
+                    counts.failed += 1;
+                    log_backend_error(app, &format!("Failed to read {}: {e}", path.display()));
+                    state.record(path, &target.name, Some(e.to_string()));
+                }
+            }
+        }
+        if files.is_empty() && remove.is_empty() {
+            continue;
+        }
+        match update_connector_files(window, server, id, files, &remove).await {
+            Ok(Some(ids)) => {
+                let gone: HashSet<String> = std::mem::take(&mut remove).into_iter().collect();
+                counts.removed += gone.len();
+                let uploaded = state.connectors.entry(id).or_default();
+                uploaded.retain(|_, uploaded| !gone.contains(&uploaded.file_id));
+                for (path, file_id) in sent.iter().zip(ids) {
+                    if let Some(&stamp) = found.get(*path) {
+                        uploaded.insert((*path).clone(), Uploaded { stamp, file_id });
+                    }
+                }
+                counts.uploaded += sent.len();
+                for path in sent {
+                    state.record(path, &target.name, None);
+                }
+            }
+            Ok(None) => return Err(NOT_ALLOWED.to_string()),
+            // The next sync tries these again.
+            Err(e) => {
+                counts.failed += sent.len();
+                for path in sent {
+                    state.record(path, &target.name, Some(e.clone()));
+                }
+            }
+        }
+        save(app, state)?;
+    }
+    Ok(())
+}
+
+/// Bring the connectors up to date with the folders; what changed.
+async fn sync(app: &AppHandle) -> Result<String, String> {
+    let local_files = app.state::<LocalFiles>();
+    let Ok(_syncing) = local_files.syncing.try_lock() else {
+        return Err("The folders are already syncing".to_string());
+    };
+    let config = app.state::<ConfigState>().config();
+    let settings = &config.local_files;
+    if !settings.enabled || (settings.folders.is_empty() && settings.watch.is_empty()) {
+        return Ok("No folders are shared".to_string());
+    }
+    let server = validate_server_url(&config.server_url)?;
+    let window = app.get_webview_window("main").ok_or("No main window")?;
+    let filter = Filter::new(settings)?;
+    let (found, mut errors) = find_targets(&window, &server, settings).await?;
+    // A connector is only kept up to date once the main window's profile may
+    // read every folder that goes to it.
+    let mut targets = Vec::new();
+    'targets: for target in found {
+        for folder in &target.folders {
+            if !request_consent(app, &window, Capability::ReadFolder, Some(folder)).await? {
+                errors.push(format!("Reading {folder} wasn't allowed"));
+                continue 'targets;
+            }
+        }
+        targets.push(target);
+    }
+    if targets.is_empty() {
+        return Err(errors.join("; "));
+    }
+
+    let mut state = load_state();
+    // Connectors nothing goes to any more keep their files; they just
+    // aren't kept up to date.
+    if errors.is_empty() && !settings.dry_run {
+        state
+            .connectors
+            .retain(|id, _| targets.iter().any(|target| target.connector_id == *id));
+    }
+    let mut counts = Counts::default();
+    for target in &targets {
+        if settings.dry_run {
+            dry_run_target(app, &filter, target, &mut state, &mut counts);
+            continue;
+        }
+        sync_target(
+            app,
+            &window,
+            &server,
+            &filter,
+            target,
+            &mut state,
+            &mut counts,
+        )
+        .await?;
+    }
+    save(app, &state)?;
+
+    let (uploaded, removed) = if settings.dry_run {
+        ("would be uploaded", "would be taken out")
+    } else {
+        ("uploaded", "taken out")
+    };
+    let mut summary = vec![
+        format!("{} {uploaded}", counts.uploaded),
+        format!("{} {removed}", counts.removed),
+    ];
+    if counts.failed > 0 {
+        summary.push(format!("{} failed", counts.failed));
+    }
+    errors.insert(0, summary.join(", "));
+    Ok(errors.join("; "))
+}
+
+/// Sync now, from the tray.
+pub fn sync_now(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        match sync(&app).await {
+            Ok(summary) => log_debug(&app, &format!("Synced local folders: {summary}")),
+            Err(e) => log_backend_error(&app, &format!("Failed to sync local folders: {e}")),
+        }
+    });
+}
+
+/// Upload `files` to the shared folders' connector once, as "Upload to
+/// Onyx" does (see `file_menu.rs`); the connector's name. They aren't
+/// tracked like the folders' files, so syncs neither update nor remove them.
+pub async fn upload(app: &AppHandle, files: &[PathBuf]) -> Result<String, String> {
+    let config = app.state::<ConfigState>().config();
+    let settings = &config.local_files;
+    let server = validate_server_url(&config.server_url)?;
+    let window = app.get_webview_window("main").ok_or("No main window")?;
+    let connector = connectors(&window, &server)
+        .await?
+        .ok_or(NOT_ALLOWED)?
+        .into_iter()
+        .find(|connector| connector.source == FILE_SOURCE && connector.name == settings.connector)
+        .ok_or_else(|| {
+            format!(
+                "The server has no file connector named \"{}\"; an admin can add one",
+                settings.connector
+            )
+        })?;
+
+    let max_bytes = settings.max_file_mb.saturating_mul(1024 * 1024);
+    let mut read = Vec::new();
+    for path in files {
+        let name = file_name(path);
+        let size = fs::metadata(path)
+            .map_err(|e| format!("Couldn't read {name}: {e}"))?
+            .len();
+        if size > max_bytes {
+            return Err(format!(
+                "{name} is bigger than {} MB, the most that's shared",
+                settings.max_file_mb
+            ));
+        }
+        let bytes = fs::read(path).map_err(|e| format!("Couldn't read {name}: {e}"))?;
+        read.push((name, bytes));
+    }
+
+    let local_files = app.state::<LocalFiles>();
+    let _syncing = local_files.syncing.lock().await;
+    let mut state = load_state();
+    let result = update_connector_files(&window, &server, connector.id, read, &[]).await;
+    let error = match &result {
+        Ok(Some(_)) => None,
+        Ok(None) => Some(NOT_ALLOWED.to_string()),
+        Err(e) => Some(e.clone()),
+    };
+    for path in files {
+        state.record(path, &settings.connector, error.clone());
+    }
+    save(app, &state)?;
+    error.map_or_else(|| Ok(settings.connector.clone()), Err)
+}
+
+/// The tray's Sync Activity submenu, as of the last sync.
+pub fn activity_menu(app: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+    let submenu = SubmenuBuilder::new(app, "Sync Activity").build()?;
+    fill_activity_menu(app, &submenu, &load_state().recent)?;
+    *app.state::<LocalFiles>()
+        .menu
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(submenu.clone());
+    Ok(submenu)
+}
+
+fn fill_activity_menu(
+    app: &AppHandle,
+    submenu: &Submenu<Wry>,
+    recent: &[Activity],
+) -> tauri::Result<()> {
+    for item in submenu.items()? {
+        submenu.remove(&item)?;
+    }
+    if recent.is_empty() {
+        submenu.append(&MenuItem::new(
+            app,
+            "Nothing synced yet",
+            false,
+            None::<&str>,
+        )?)?;
+    }
+    for activity in recent {
+        submenu.append(&MenuItem::new(app, activity.label(), false, None::<&str>)?)?;
+    }
+    submenu.append(&PredefinedMenuItem::separator(app)?)?;
+    submenu.append(&MenuItem::with_id(
+        app,
+        SYNC_NOW_ID,
+        "Sync Now",
+        true,
+        None::<&str>,
+    )?)?;
+    submenu.set_enabled(app.state::<ConfigState>().config().local_files.enabled)
+}
+
+/// Refill Sync Activity with `recent`.
+fn show_activity(app: &AppHandle, recent: &[Activity]) {
+    // Menus are changed on the main thread, which may be waiting on the
+    // lock, so it isn't held while they are.
+    let menu = app
+        .state::<LocalFiles>()
+        .menu
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone();
+    if let Some(submenu) = menu {
+        if let Err(e) = fill_activity_menu(app, &submenu, recent) {
+            log_backend_error(app, &format!("Failed to update Sync Activity menu: {e}"));
+        }
+    }
+}
+
+/// Enable or disable Sync Activity with the setting.
+pub fn sync_menu(app: &AppHandle) {
+    show_activity(app, &load_state().recent);
+}
+
+/// Sync the shared folders every `SYNC_INTERVAL` for as long as the app
+/// runs. Only for the copy that holds the data directory.
+pub fn start_syncing(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if app.state::<ConfigState>().config().local_files.enabled {
+                match sync(&app).await {
+                    Ok(summary) => log_debug(&app, &format!("Synced local folders: {summary}")),
+                    Err(e) => {
+                        log_backend_error(&app, &format!("Failed to sync local folders: {e}"));
+                    }
+                }
+            }
+            tokio::time::sleep(SYNC_INTERVAL).await;
+        }
+    });
+}
+
+/// Check the shared folder settings before they're saved.
+pub fn validate(settings: &LocalFilesSettings) -> Result<(), String> {
+    if let Some(folder) = settings
+        .folders
+        .iter()
+        .chain(settings.watch.iter().map(|watched| &watched.folder))
+        .find(|folder| !Path::new(folder).is_absolute())
+    {
+        return Err(format!("Shared folders need their full path: \"{folder}\""));
+    }
+    if settings
+        .watch
+        .iter()
+        .any(|watched| watched.document_set.trim().is_empty())
+    {
+        return Err("Name the document set each watched folder goes to".to_string());
+    }
+    Filter::new(settings)?;
+    if settings.max_file_mb == 0 {
+        return Err("The largest file to share has to be at least 1 MB".to_string());
+    }
+    if settings.connector.trim().is_empty() {
+        return Err("Name the file connector the shared folders go to".to_string());
+    }
+    Ok(())
+}
+
+/// Sync the shared folders now; what changed. Only from Settings
+#[tauri::command]
+pub async fn sync_local_files(app: AppHandle, window: WebviewWindow) -> Result<String, String> {
+    if window.label() != SETTINGS_WINDOW_LABEL {
+        return Err("Shared folders can only be synced from Settings".to_string());
+    }
+    sync(&app).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn only_new_changed_and_gone_files_are_synced() {
+        let filter = Filter::new(&LocalFilesSettings::default()).unwrap();
+        assert!(filter.allows(Path::new("notes.md"), 10));
+        assert!(filter.allows(Path::new("specs/q3/plan.pdf"), 10));
+        assert!(!filter.allows(Path::new("notes.md"), 21 * 1024 * 1024));
+        assert!(!filter.allows(Path::new("build.rs"), 10));
+        assert!(!filter.allows(Path::new(".git/description.txt"), 10));
+        assert!(!filter.allows(Path::new("app/node_modules/pkg/README.md"), 10));
+
+        let stamp = |modified| Stamp { size: 10, modified };
+        let uploaded = |modified, file_id: &str| Uploaded {
+            stamp: stamp(modified),
+            file_id: file_id.to_string(),
+        };
+        let files = BTreeMap::from([
+            (PathBuf::from("/docs/same.md"), uploaded(1, "a")),
+            (PathBuf::from("/docs/changed.md"), uploaded(1, "b")),
+            (PathBuf::from("/docs/gone.md"), uploaded(1, "c")),
+        ]);
+        let found = BTreeMap::from([
+            (PathBuf::from("/docs/same.md"), stamp(1)),
+            (PathBuf::from("/docs/changed.md"), stamp(2)),
+            (PathBuf::from("/docs/new.md"), stamp(1)),
+        ]);
+        assert_eq!(
+            plan(&files, &found),
+            Plan {
+                upload: vec![
+                    PathBuf::from("/docs/changed.md"),
+                    PathBuf::from("/docs/new.md")
+                ],
+                remove: vec!["b".to_string(), "c".to_string()],
+            }
+        );
+
+        let big = BTreeMap::from([
+            (
+                PathBuf::from("a"),
+                Stamp {
+                    size: BATCH_BYTES,
+                    modified: 0,
+                },
+            ),
+            (
+                PathBuf::from("b"),
+                Stamp {
+                    size: 1,
+                    modified: 0,
+                },
+            ),
+            (
+                PathBuf::from("c"),
+                Stamp {
+                    size: 1,
+                    modified: 0,
+                },
+            ),
+        ]);
+        let upload: Vec<PathBuf> = big.keys().cloned().collect();
+        assert_eq!(batches(&upload, &big).len(), 2);
+
+        let mut state = SyncState::default();
+        for n in 0..=RECENT_ACTIVITY {
+            state.record(Path::new(&format!("/docs/{n}.md")), "Finance", None);
+        }
+        state.record(
+            Path::new("/docs/big.pdf"),
+            "Finance",
+            Some("413 Payload Too Large".to_string()),
+        );
+        assert_eq!(state.recent.len(), RECENT_ACTIVITY);
+        assert_eq!(
+            state.recent[0].label(),
+            "Couldn't upload big.pdf: 413 Payload Too Large"
+        );
+        assert_eq!(state.recent[1].label(), "Uploaded 10.md to Finance");
+        state.record_dry_run(Path::new("/docs/plan.md"), "Finance");
+        assert_eq!(state.recent[0].label(), "Would upload plan.md to Finance");
+        state.record_dry_run_removal(Path::new("/docs/old.md"), "Finance");
+        assert_eq!(state.recent[0].label(), "Would take old.md out of Finance");
+    }
+}
@@ -0,0 +1,435 @@
+// Searching past answers on this machine, for when the server is slow or
+// down. The chats the offline cache keeps (see `offline_cache.rs`), and the
+// transcripts exported while it's on (see `chat_export.rs`), are indexed
+// word by word in the cache's database. A launcher searches them through
+// the automation API by asking with a `local:` prefix (see
+// `automation_api.rs`); the rest of the query is the words to find, all of
+// them, whole.
+//
+// The kept chats are sealed, and a plain full-text index beside them would
+// give their words away. So each word is kept as a keyed hash instead
+// (HMAC-SHA256, with a key derived from the one the secrets use): it finds
+// whole words but can't be read back. What an exported transcript says,
+// its title and where it was saved are sealed like the chats. Whatever
+// clears the cache clears the index with it.
+
+use crate::chat_export::transcript;
+use crate::config::ConfigState;
+use crate::debug_log::log_backend_error;
+use crate::offline_cache::{load, open_db};
+use crate::secrets::{encryption_key, open, seal};
+use crate::server_api::ChatTranscript;
+use aws_lc_rs::hmac;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+/// What a query starts with to be searched here.
+const PREFIX: &str = "local:";
+/// Shorter words aren't indexed.
+const MIN_WORD_CHARS: usize = 2;
+/// Words of a query past these are left out.
+const MAX_QUERY_WORDS: usize = 16;
+const MAX_HITS: usize = 20;
+/// Characters shown on each side of the word a snippet is about.
+const SNIPPET_CONTEXT: usize = 60;
+/// What an exported transcript's source starts with; a kept chat's source
+/// is its ID.
+const EXPORT_SOURCE: &str = "export:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HitKind {
+    /// A chat the offline cache keeps.
+    Cached,
+    /// A transcript exported to a file.
+    Exported,
+}
+
+/// A chat that has every word searched for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Hit {
+    pub kind: HitKind,
+    pub chat_id: String,
+    pub title: String,
+    pub snippet: String,
+    /// Where an exported transcript was saved.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+/// What to search for, if `query` has the `local:` prefix.
+pub fn local_query(query: &str) -> Option<&str> {
+    let query = query.trim_start();
+    query
+        .get(..PREFIX.len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case(PREFIX))
+        .map(|_| query[PREFIX.len()..].trim())
+}
+
+/// Each word in `text`, with where it starts.
+fn word_spans(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut start = None;
+    text.char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+        .filter_map(move |(i, c)| {
+            if c.is_alphanumeric() {
+                if start.is_none() {
+                    start = Some(i);
+                }
+                None
+            } else {
+                start.take().map(|start| (start, &text[start..i]))
+            }
+        })
+}
+
+/// The words of `text` that are indexed, lowercased.
+fn words(text: &str) -> BTreeSet<String> {
+    word_spans(text)
+        .map(|(_, word)| word)
+        .filter(|word| word.chars().count() >= MIN_WORD_CHARS)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// The key words are hashed with: derived from `key`, so the index says
+/// nothing about what's sealed with it.
+fn term_key(key: &[u8; 32]) -> hmac::Key {
+    let derived = hmac::sign(
+        &hmac::Key::new(hmac::HMAC_SHA256, key),
+        b"onyx local search terms",
+    );
+    hmac::Key::new(hmac::HMAC_SHA256, derived.as_ref())
+}
+
+fn term(key: &hmac::Key, word: &str) -> String {
+    URL_SAFE_NO_PAD.encode(&hmac::sign(key, word.as_bytes()).as_ref()[..16])
+}
+
+pub fn create_tables(db: &Connection) -> Result<(), String> {
+    db.execute_batch(
+        "CREATE TABLE IF NOT EXISTS search_terms (
+            term TEXT NOT NULL,
+            source TEXT NOT NULL,
+            PRIMARY KEY (term, source)
+        ) WITHOUT ROWID;
+        CREATE INDEX IF NOT EXISTS search_terms_by_source ON search_terms (source);
+        CREATE TABLE IF NOT EXISTS exported (
+            source TEXT PRIMARY KEY,
+            chat_id TEXT NOT NULL,
+            title TEXT NOT NULL,
+            path TEXT NOT NULL,
+            text TEXT NOT NULL
+        )",
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Drop what's indexed for `source`.
+pub fn forget(db: &Connection, source: &str) -> Result<(), String> {
+    db.execute(
+        "DELETE FROM search_terms WHERE source = ?1",
+        params![source],
+    )
+    .map_err(|e| e.to_string())?;
+    db.execute("DELETE FROM exported WHERE source = ?1", params![source])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn index_text(db: &Connection, key: &[u8; 32], source: &str, text: &str) -> Result<(), String> {
+    let term_key = term_key(key);
+    let tx = db.unchecked_transaction().map_err(|e| e.to_string())?;
+    forget(&tx, source)?;
+    {
+        let mut insert = tx
+            .prepare("INSERT OR IGNORE INTO search_terms (term, source) VALUES (?1, ?2)")
+            .map_err(|e| e.to_string())?;
+        for word in words(text) {
+            insert
+                .execute(params![term(&term_key, &word), source])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Index a kept chat, in place of what was indexed for it before.
+pub fn index_chat(db: &Connection, key: &[u8; 32], chat: &ChatTranscript) -> Result<(), String> {
+    index_text(
+        db,
+        key,
+        &chat.chat_session_id,
+        &transcript(chat).plain_text(),
+    )
+}
+
+/// Index the kept chats that aren't yet: those kept before there was an
+/// index.
+pub fn index_unindexed(db: &Connection, key: &[u8; 32]) -> Result<(), String> {
+    let ids = {
+        let mut query = db
+            .prepare("SELECT id FROM chats WHERE id NOT IN (SELECT source FROM search_terms)")
+            .map_err(|e| e.to_string())?;
+        let rows = query
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?
+    };
+    for id in ids {
+        if let Some(chat) = load(db, key, &id)? {
+            index_chat(db, key, &chat)?;
+        }
+    }
+    Ok(())
+}
+
+fn store_export(
+    db: &Connection,
+    key: &[u8; 32],
+    chat: &ChatTranscript,
+    path: &Path,
+) -> Result<(), String> {
+    let text = transcript(chat).plain_text();
+    let path = path.to_string_lossy();
+    // The same file exported again replaces what it had.
+    let source = format!("{EXPORT_SOURCE}{}", term(&term_key(key), &path));
+    index_text(db, key, &source, &text)?;
+    db.execute(
+        "INSERT INTO exported (source, chat_id, title, path, text) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            source,
+            chat.chat_session_id,
+            seal(key, transcript(chat).title)?,
+            seal(key, &path)?,
+            seal(key, &text)?,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Index a transcript just exported to `path`, if the offline cache is on.
+pub fn index_export(app: &AppHandle, chat: &ChatTranscript, path: &Path) {
+    if !app.state::<ConfigState>().config().offline_cache.enabled {
+        return;
+    }
+    let result = encryption_key().and_then(|key| store_export(&open_db()?, key, chat, path));
+    if let Err(e) = result {
+        log_backend_error(app, &format!("Failed to index an exported chat: {e}"));
+    }
+}
+
+/// Up to `SNIPPET_CONTEXT` characters either side of the first of `words`
+/// in `text` (or the start of it), on one line.
+fn snippet(text: &str, words: &BTreeSet<String>) -> String {
+    let at = word_spans(text)
+        .find(|(_, word)| words.contains(&word.to_lowercase()))
+        .map_or(0, |(at, _)| at);
+    let start = text[..at]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT - 1)
+        .map_or(0, |(i, _)| i);
+    let end = text[at..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT * 2)
+        .map_or(text.len(), |(i, _)| at + i);
+    let mut snippet = text[start..end]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if start > 0 {
+        snippet.insert(0, '\u{2026}');
+    }
+    if end < text.len() {
+        snippet.push('\u{2026}');
+    }
+    snippet
+}
+
+/// What an exported transcript's row has: its chat, title, path and text.
+fn load_export(
+    db: &Connection,
+    key: &[u8; 32],
+    source: &str,
+) -> Result<Option<(String, String, PathBuf, String)>, String> {
+    let row: Option<(String, String, String, String)> = db
+        .query_row(
+            "SELECT chat_id, title, path, text FROM exported WHERE source = ?1",
+            params![source],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    row.map(|(chat_id, title, path, text)| {
+        Ok((
+            chat_id,
+            open(key, &title)?,
+            PathBuf::from(open(key, &path)?),
+            open(key, &text)?,
+        ))
+    })
+    .transpose()
+}
+
+/// The kept chats, most recently changed first, then the exported
+/// transcripts, that have every word of `query`.
+fn search_db(db: &Connection, key: &[u8; 32], query: &str) -> Result<Vec<Hit>, String> {
+    let words: BTreeSet<String> = words(query).into_iter().take(MAX_QUERY_WORDS).collect();
+    if words.is_empty() {
+        return Ok(Vec::new());
+    }
+    let term_key = term_key(key);
+    let terms: Vec<String> = words.iter().map(|word| term(&term_key, word)).collect();
+    let sources = {
+        let mut query = db
+            .prepare(&format!(
+                "SELECT search_terms.source FROM search_terms
+                 LEFT JOIN chats ON chats.id = search_terms.source
+                 WHERE search_terms.term IN ({})
+                 GROUP BY search_terms.source
+                 HAVING COUNT(*) = {}
+                 ORDER BY chats.updated IS NULL, chats.updated DESC",
+                vec!["?"; terms.len()].join(", "),
+                terms.len()
+            ))
+            .map_err(|e| e.to_string())?;
+        let rows = query
+            .query_map(params_from_iter(&terms), |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?
+    };
+
+    let mut hits = Vec::new();
+    for source in sources {
+        if hits.len() == MAX_HITS {
+            break;
+        }
+        if source.starts_with(EXPORT_SOURCE) {
+            let Some((chat_id, title, path, text)) = load_export(db, key, &source)? else {
+                continue;
+            };
+            // Moved or deleted since: it's no longer there to find.
+            if !path.is_file() {
+                forget(db, &source)?;
+                continue;
+            }
+            hits.push(Hit {
+                kind: HitKind::Exported,
+                chat_id,
+                title,
+                snippet: snippet(&text, &words),
+                path: Some(path),
+            });
+        } else if let Some(chat) = load(db, key, &source)? {
+            hits.push(Hit {
+                kind: HitKind::Cached,
+                title: transcript(&chat).title.to_string(),
+                snippet: snippet(&transcript(&chat).plain_text(), &words),
+                chat_id: source,
+                path: None,
+            });
+        }
+    }
+    Ok(hits)
+}
+
+/// Search what's kept on this machine for `query`.
+pub fn search(app: &AppHandle, query: &str) -> Result<Vec<Hit>, String> {
+    if !app.state::<ConfigState>().config().offline_cache.enabled {
+        return Err("Searching this computer needs the offline cache turned on".to_string());
+    }
+    search_db(&open_db()?, encryption_key()?, query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn kept_and_exported_chats_are_found_by_hashed_words() {
+        let key = [7; 32];
+        let db = Connection::open_in_memory().unwrap();
+        crate::offline_cache::create_table(&db).unwrap();
+        let chat = |id: &str, title: &str, answer: &str| -> ChatTranscript {
+            serde_json::from_value(serde_json::json!({
+                "chat_session_id": id,
+                "description": title,
+                "time_created": "2026-10-01T09:00:00Z",
+                "messages": [
+                    {"message_id": 1, "message": "How much?", "message_type": "user",
+                     "latest_child_message": 2, "time_sent": "2026-10-01T09:00:00Z"},
+                    {"message_id": 2, "parent_message": 1, "message": answer,
+                     "message_type": "assistant", "time_sent": "2026-10-01T09:00:05Z"},
+                ],
+            }))
+            .unwrap()
+        };
+        crate::offline_cache::store(
+            &db,
+            &key,
+            "2026-10-02T09:00:00Z",
+            &chat(
+                "a",
+                "PTO policy",
+                "Full-time staff get 25 days of PTO a year.",
+            ),
+        )
+        .unwrap();
+        let exported =
+            std::env::temp_dir().join(format!("onyx-search-{}.md", uuid::Uuid::new_v4()));
+        std::fs::write(&exported, "# Expenses").unwrap();
+        store_export(
+            &db,
+            &key,
+            &chat("b", "Expenses", "Submit receipts within 25 days."),
+            &exported,
+        )
+        .unwrap();
+
+        assert_eq!(local_query("  Local: 25 days "), Some("25 days"));
+        assert_eq!(local_query("PTO policy"), None);
+
+        let hits = search_db(&db, &key, "25 DAYS").unwrap();
+        let found: Vec<_> = hits
+            .iter()
+            .map(|hit| (hit.kind, hit.chat_id.as_str()))
+            .collect();
+        assert_eq!(found, [(HitKind::Cached, "a"), (HitKind::Exported, "b")]);
+        assert_eq!(
+            hits[0].snippet,
+            "PTO policy How much? Full-time staff get 25 days of PTO a year."
+        );
+        assert_eq!(hits[1].path.as_deref(), Some(exported.as_path()));
+        assert!(search_db(&db, &key, "pto receipts").unwrap().is_empty());
+        assert!(search_db(&db, &[8; 32], "days").unwrap().is_empty());
+
+        let stored: Vec<String> = db
+            .prepare("SELECT term FROM search_terms UNION ALL SELECT title FROM exported")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(stored
+            .iter()
+            .all(|value| !value.to_lowercase().contains("days") && !value.contains("Expenses")));
+
+        std::fs::remove_file(&exported).unwrap();
+        assert_eq!(search_db(&db, &key, "days").unwrap().len(), 1);
+        let source = format!(
+            "{EXPORT_SOURCE}{}",
+            term(&term_key(&key), &exported.to_string_lossy())
+        );
+        assert!(load_export(&db, &key, &source).unwrap().is_none());
+    }
+}
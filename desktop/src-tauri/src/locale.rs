@@ -0,0 +1,244 @@
+// The UI language: `locale` in the config, or the system's. It's fixed when
+// the app starts, since WebView2 can't mix languages within one profile,
+// and goes to the web app the way each engine lets it: its
+// `Accept-Language` (and `navigator.languages`), set through `--lang` on
+// WebView2, the web context on WebKitGTK, and the app's `AppleLanguages` on
+// macOS. The app's own requests send it too, and the native strings it draws
+// itself (the File and tray menus, the context menu) come from `text`.
+
+use crate::config::{is_language_code, AppConfig};
+use std::sync::OnceLock;
+
+/// Used when neither the config nor the system has a usable language.
+const FALLBACK_LOCALE: &str = "en-US";
+
+static LOCALE: OnceLock<String> = OnceLock::new();
+
+/// `raw` (`de_DE.UTF-8`, `pt-br`, `ja`) as a BCP 47 tag like `de-DE`, or
+/// `None` for the POSIX default and anything that isn't a language.
+fn language_tag(raw: &str) -> Option<String> {
+    let code = raw.split(['.', '@']).next()?.trim();
+    if matches!(code, "C" | "POSIX") || !is_language_code(code) {
+        return None;
+    }
+    let mut parts = code.split(['_', '-']);
+    let language = parts.next()?.to_ascii_lowercase();
+    Some(
+        std::iter::once(language)
+            .chain(parts.map(|part| {
+                if part.len() == 2 {
+                    part.to_ascii_uppercase()
+                } else {
+                    part.to_string()
+                }
+            }))
+            .collect::<Vec<_>>()
+            .join("-"),
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| language_tag(&value))
+}
+
+#[cfg(target_os = "macos")]
+fn system_locale() -> Option<String> {
+    use objc2_foundation::NSLocale;
+
+    NSLocale::preferredLanguages()
+        .firstObject()
+        .and_then(|language| language_tag(&language.to_string()))
+}
+
+#[cfg(target_os = "windows")]
+fn system_locale() -> Option<String> {
+    use winreg::enums::HKEY_CURRENT_USER;
+    use winreg::RegKey;
+
+    RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey(r"Control Panel\International")
+        .and_then(|key| key.get_value::<String, _>("LocaleName"))
+        .ok()
+        .and_then(|name| language_tag(&name))
+}
+
+/// Make `locale` the app's language for `WKWebView`, or go back to the
+/// system's when it's `None`. `AppleLanguages` in the app's own defaults
+/// wins over the system list for this app only.
+#[cfg(target_os = "macos")]
+fn set_apple_languages(locale: Option<&str>) {
+    use objc2_foundation::{NSArray, NSString, NSUserDefaults};
+
+    let defaults = NSUserDefaults::standardUserDefaults();
+    let key = NSString::from_str("AppleLanguages");
+    match locale {
+        Some(locale) => {
+            let languages = NSArray::from_retained_slice(&[NSString::from_str(locale)]);
+            // SAFETY: an array of strings is a property-list value, which is
+            // all `setObject:forKey:` requires.
+            unsafe { defaults.setObject_forKey(Some(&languages), &key) };
+        }
+        None => defaults.removeObjectForKey(&key),
+    }
+}
+
+/// Settle the session's language from `config`. Called once, before any
+/// window exists.
+pub fn init(config: &AppConfig) {
+    let configured = config.locale.as_deref().and_then(language_tag);
+    // Cleared first when following the system, so the system list isn't
+    // read back from a previous session's choice.
+    #[cfg(target_os = "macos")]
+    set_apple_languages(configured.as_deref());
+    let locale = configured
+        .or_else(system_locale)
+        .unwrap_or_else(|| FALLBACK_LOCALE.to_string());
+    let _ = LOCALE.set(locale);
+}
+
+/// The session's language, as a BCP 47 tag.
+pub fn locale() -> &'static str {
+    LOCALE.get().map_or(FALLBACK_LOCALE, String::as_str)
+}
+
+/// `WebView2`'s browser arguments: its defaults (which setting any replaces)
+/// plus the language and the Windows sign-in (see `auth.rs`). Every webview
+/// has to be given the same ones.
+pub fn browser_args() -> String {
+    format!(
+        "--disable-features=msWebOOUI,msPdfOOUI,msSmartScreenProtection --lang={}{}",
+        locale(),
+        crate::auth::integrated_auth_args()
+    )
+}
+
+/// Have `window`'s web context ask for the session's language.
+#[cfg(target_os = "linux")]
+pub fn apply_to_webview(app: &tauri::AppHandle, window: &tauri::WebviewWindow) {
+    use webkit2gtk::{WebContextExt, WebViewExt};
+
+    if let Err(e) = window.with_webview(|webview| {
+        if let Some(context) = webview.inner().context() {
+            context.set_preferred_languages(&[locale()]);
+        }
+    }) {
+        crate::debug_log::log_backend_error(app, &format!("Failed to apply locale: {e}"));
+    }
+}
+
+/// The other engines get the language when they're created.
+#[cfg(not(target_os = "linux"))]
+pub const fn apply_to_webview(_app: &tauri::AppHandle, _window: &tauri::WebviewWindow) {}
+
+/// A native string the app draws itself.
+#[derive(Debug, Clone, Copy)]
+pub enum Text {
+    NewChat,
+    NewWindow,
+    Settings,
+    OpenApp,
+    OpenChatWindow,
+    ShowInMenuBar,
+    QuitApp,
+    CopyLink,
+    /// Takes the quoted selection in place of `{}`.
+    SearchInOnyx,
+}
+
+impl Text {
+    const fn english(self) -> &'static str {
+        match self {
+            Self::NewChat => "New Chat",
+            Self::NewWindow => "New Window",
+            Self::Settings => "Settings...",
+            Self::OpenApp => "Open Onyx",
+            Self::OpenChatWindow => "Open Chat Window",
+            Self::ShowInMenuBar => "Show in Menu Bar",
+            Self::QuitApp => "Quit Onyx",
+            Self::CopyLink => "Copy Link",
+            Self::SearchInOnyx => "Search {} in Onyx",
+        }
+    }
+}
+
+/// `text` in `locale`'s language, or in English for the languages there's
+/// no translation for.
+fn translate(locale: &str, text: Text) -> &'static str {
+    let language = locale.split('-').next().unwrap_or_default();
+    match (language, text) {
+        ("de", Text::NewChat) => "Neuer Chat",
+        ("de", Text::NewWindow) => "Neues Fenster",
+        ("de", Text::Settings) => "Einstellungen\u{2026}",
+        ("de", Text::OpenApp) => "Onyx \u{f6}ffnen",
+        ("de", Text::OpenChatWindow) => "Chatfenster \u{f6}ffnen",
+        ("de", Text::ShowInMenuBar) => "In der Men\u{fc}leiste anzeigen",
+        ("de", Text::QuitApp) => "Onyx beenden",
+        ("de", Text::CopyLink) => "Link kopieren",
+        ("de", Text::SearchInOnyx) => "{} in Onyx suchen",
+        ("es", Text::NewChat) => "Nuevo chat",
+        ("es", Text::NewWindow) => "Nueva ventana",
+        ("es", Text::Settings) => "Configuraci\u{f3}n\u{2026}",
+        ("es", Text::OpenApp) => "Abrir Onyx",
+        ("es", Text::OpenChatWindow) => "Abrir ventana de chat",
+        ("es", Text::ShowInMenuBar) => "Mostrar en la barra de men\u{fa}s",
+        ("es", Text::QuitApp) => "Salir de Onyx",
+        ("es", Text::CopyLink) => "Copiar enlace",
+        ("es", Text::SearchInOnyx) => "Buscar {} en Onyx",
+        ("fr", Text::NewChat) => "Nouvelle discussion",
+        ("fr", Text::NewWindow) => "Nouvelle fen\u{ea}tre",
+        ("fr", Text::Settings) => "R\u{e9}glages\u{2026}",
+        ("fr", Text::OpenApp) => "Ouvrir Onyx",
+        ("fr", Text::OpenChatWindow) => "Ouvrir une fen\u{ea}tre de discussion",
+        ("fr", Text::ShowInMenuBar) => "Afficher dans la barre des menus",
+        ("fr", Text::QuitApp) => "Quitter Onyx",
+        ("fr", Text::CopyLink) => "Copier le lien",
+        ("fr", Text::SearchInOnyx) => "Rechercher {} dans Onyx",
+        ("ja", Text::NewChat) => "\u{65b0}\u{898f}\u{30c1}\u{30e3}\u{30c3}\u{30c8}",
+        ("ja", Text::NewWindow) => "\u{65b0}\u{898f}\u{30a6}\u{30a4}\u{30f3}\u{30c9}\u{30a6}",
+        ("ja", Text::Settings) => "\u{8a2d}\u{5b9a}\u{2026}",
+        ("ja", Text::OpenApp) => "Onyx\u{3092}\u{958b}\u{304f}",
+        ("ja", Text::OpenChatWindow) => {
+            "\u{30c1}\u{30e3}\u{30c3}\u{30c8}\u{30a6}\u{30a4}\u{30f3}\u{30c9}\u{30a6}\u{3092}\u{958b}\u{304f}"
+        }
+        ("ja", Text::ShowInMenuBar) => {
+            "\u{30e1}\u{30cb}\u{30e5}\u{30fc}\u{30d0}\u{30fc}\u{306b}\u{8868}\u{793a}"
+        }
+        ("ja", Text::QuitApp) => "Onyx\u{3092}\u{7d42}\u{4e86}",
+        ("ja", Text::CopyLink) => "\u{30ea}\u{30f3}\u{30af}\u{3092}\u{30b3}\u{30d4}\u{30fc}",
+        ("ja", Text::SearchInOnyx) => "Onyx\u{3067}{}\u{3092}\u{691c}\u{7d22}",
+        _ => text.english(),
+    }
+}
+
+/// `text` in the session's language.
+pub fn text(text: Text) -> &'static str {
+    translate(locale(), text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_locales_become_language_tags() {
+        assert_eq!(language_tag("de_DE.UTF-8").as_deref(), Some("de-DE"));
+        assert_eq!(language_tag("pt-br").as_deref(), Some("pt-BR"));
+        assert_eq!(language_tag("sr_RS@latin").as_deref(), Some("sr-RS"));
+        assert_eq!(language_tag("zh-Hant-TW").as_deref(), Some("zh-Hant-TW"));
+        assert_eq!(language_tag("C.UTF-8"), None);
+        assert_eq!(language_tag("POSIX"), None);
+        assert_eq!(language_tag(""), None);
+    }
+
+    #[test]
+    fn untranslated_languages_fall_back_to_english() {
+        assert_eq!(translate("de-AT", Text::CopyLink), "Link kopieren");
+        assert_eq!(translate("en-GB", Text::CopyLink), "Copy Link");
+        assert_eq!(translate("ko-KR", Text::QuitApp), "Quit Onyx");
+    }
+}
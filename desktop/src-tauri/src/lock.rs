@@ -0,0 +1,435 @@
+// Locking the app after a while without use, for machines that are shared
+// or left unattended. Pages report input (`activity.js`), as does focusing
+// a window; once `lock.after_minutes` pass without any, every window is
+// hidden and the lock window (`lock.html`) takes their place until the
+// user's PIN is entered. The windows that were showing then come back as
+// they were.
+//
+// The PIN is set in Settings and kept in the OS keychain as a salted
+// PBKDF2 hash, never in the config. Auto-lock can't be turned on without
+// one, and wrong guesses slow down after a few tries. Where the system can
+// confirm the user (Touch ID, Windows Hello; see `biometric.rs`) that
+// unlocks too, and is asked for before an existing PIN is changed.
+
+use crate::assets::{script, Script};
+use crate::biometric;
+use crate::config::{ConfigState, LockSettings};
+use crate::debug_log::log_backend_error;
+use crate::window::{LOCK_WINDOW, LOCK_WINDOW_LABEL, SETTINGS_WINDOW_LABEL};
+use aws_lc_rs::pbkdf2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use serde::Serialize;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, Webview, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+
+const KEYCHAIN_SERVICE: &str = "app.onyx.desktop";
+const KEYCHAIN_ACCOUNT: &str = "lock-pin";
+const PIN_ITERATIONS: NonZeroU32 = NonZeroU32::MIN.saturating_add(209_999);
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+const MIN_PIN_LEN: usize = 4;
+/// How often the idle time is checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+/// Wrong PINs allowed before each further try has to wait `RETRY_DELAY`.
+const FREE_ATTEMPTS: u32 = 5;
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Managed state: when the app was last used, and what locking hid.
+pub struct AppLock {
+    last_activity: Mutex<Instant>,
+    locked: AtomicBool,
+    /// The windows that were showing when the app locked, by label.
+    hidden: Mutex<Vec<String>>,
+    /// Wrong PINs in a row, and when the last one was entered.
+    failures: Mutex<(u32, Option<Instant>)>,
+}
+
+impl Default for AppLock {
+    fn default() -> Self {
+        Self {
+            last_activity: Mutex::new(Instant::now()),
+            locked: AtomicBool::new(false),
+            hidden: Mutex::new(Vec::new()),
+            failures: Mutex::new((0, None)),
+        }
+    }
+}
+
+impl AppLock {
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::SeqCst)
+    }
+
+    fn touch(&self) {
+        *self
+            .last_activity
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Instant::now();
+    }
+
+    pub fn idle_for(&self) -> Duration {
+        self.last_activity
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .elapsed()
+    }
+
+    /// How long until another PIN may be tried, if it has to wait.
+    fn retry_wait(&self) -> Option<Duration> {
+        let (failures, last) = *self
+            .failures
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let elapsed = last?.elapsed();
+        (failures >= FREE_ATTEMPTS && elapsed < RETRY_DELAY)
+            .then(|| RETRY_DELAY.saturating_sub(elapsed))
+    }
+
+    fn record_attempt(&self, correct: bool) {
+        let mut failures = self
+            .failures
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        *failures = if correct {
+            (0, None)
+        } else {
+            (failures.0 + 1, Some(Instant::now()))
+        };
+    }
+}
+
+/// `pin` hashed for keeping, as `<salt>:<hash>` (both base64).
+fn hash_pin(pin: &str) -> Result<String, String> {
+    let mut salt = [0u8; SALT_LEN];
+    aws_lc_rs::rand::fill(&mut salt).map_err(|_| "Failed to generate a salt".to_string())?;
+    let mut hash = [0u8; HASH_LEN];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        PIN_ITERATIONS,
+        &salt,
+        pin.as_bytes(),
+        &mut hash,
+    );
+    Ok(format!("{}:{}", BASE64.encode(salt), BASE64.encode(hash)))
+}
+
+/// Whether `pin` is the one `stored` (from `hash_pin`) was made from.
+fn pin_matches(stored: &str, pin: &str) -> bool {
+    let Some((salt, hash)) = stored.split_once(':') else {
+        return false;
+    };
+    let (Ok(salt), Ok(hash)) = (BASE64.decode(salt), BASE64.decode(hash)) else {
+        return false;
+    };
+    pbkdf2::verify(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        PIN_ITERATIONS,
+        &salt,
+        pin.as_bytes(),
+        &hash,
+    )
+    .is_ok()
+}
+
+fn validate_pin(pin: &str) -> Result<(), String> {
+    if pin.chars().count() < MIN_PIN_LEN {
+        return Err(format!("The PIN needs at least {MIN_PIN_LEN} characters"));
+    }
+    Ok(())
+}
+
+fn keychain_entry() -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT)
+        .map_err(|e| format!("Could not access the OS keychain: {e}"))
+}
+
+fn stored_pin() -> Result<Option<String>, String> {
+    match keychain_entry()?.get_password() {
+        Ok(stored) => Ok(Some(stored)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Could not read the PIN: {e}")),
+    }
+}
+
+/// How the lock window can be unlocked.
+#[derive(Debug, Clone, Serialize)]
+pub struct UnlockOptions {
+    /// What the system confirms the user with, if it's available and in
+    /// use, such as "Touch ID".
+    pub biometric: Option<&'static str>,
+}
+
+fn unlock_options(app: &AppHandle) -> UnlockOptions {
+    let enabled = app.state::<ConfigState>().config().lock.biometric;
+    UnlockOptions {
+        biometric: if enabled {
+            biometric::available()
+        } else {
+            None
+        },
+    }
+}
+
+/// Check lock settings from the settings window: auto-lock needs a PIN to
+/// unlock with.
+pub fn validate(settings: &LockSettings) -> Result<(), String> {
+    if settings.after_minutes > 0 && stored_pin()?.is_none() {
+        return Err("Set a PIN before turning on auto-lock".to_string());
+    }
+    Ok(())
+}
+
+/// Check for idleness every `CHECK_INTERVAL` for as long as the app runs.
+pub fn watch_for_idle(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let minutes = app.state::<ConfigState>().config().lock.after_minutes;
+            let lock = app.state::<AppLock>();
+            if minutes > 0
+                && !lock.is_locked()
+                && lock.idle_for() >= Duration::from_secs(u64::from(minutes) * 60)
+            {
+                lock_app(&app);
+            }
+        }
+    });
+}
+
+pub fn inject_activity_script(webview: &Webview) {
+    if webview.label() == LOCK_WINDOW_LABEL {
+        return;
+    }
+    let script = script(webview.app_handle(), Script::Activity);
+    crate::watchdog::eval(webview, &*script, "inject activity script");
+}
+
+/// A window came to the front: that's use, unless the app is locked, in
+/// which case it goes away again in favor of the lock window.
+pub fn on_window_focused(app: &AppHandle, label: &str) {
+    let lock = app.state::<AppLock>();
+    if !lock.is_locked() {
+        lock.touch();
+        return;
+    }
+    if label == LOCK_WINDOW_LABEL {
+        return;
+    }
+    if let Some(window) = app.get_webview_window(label) {
+        match window.hide() {
+            Ok(()) => {
+                let mut hidden = lock
+                    .hidden
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                if !hidden.iter().any(|hidden| hidden == label) {
+                    hidden.push(label.to_string());
+                }
+            }
+            Err(e) => log_backend_error(app, &format!("Failed to hide {label} while locked: {e}")),
+        }
+    }
+    if let Some(window) = app.get_webview_window(LOCK_WINDOW_LABEL) {
+        let _ = window.set_focus();
+    }
+}
+
+/// Hide every window behind the lock window.
+pub fn lock_app(app: &AppHandle) {
+    let lock = app.state::<AppLock>();
+    if lock.locked.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let mut hidden = Vec::new();
+    for (label, window) in app.webview_windows() {
+        if !window.is_visible().unwrap_or(false) {
+            continue;
+        }
+        match window.hide() {
+            Ok(()) => hidden.push(label),
+            Err(e) => log_backend_error(app, &format!("Failed to hide {label} to lock: {e}")),
+        }
+    }
+    *lock
+        .hidden
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = hidden;
+    // What's on screen is hidden; what's being read aloud and dictation
+    // stop too.
+    #[cfg(feature = "read-aloud")]
+    crate::read_aloud::stop(app);
+    #[cfg(feature = "voice")]
+    crate::dictation::stop(app);
+
+    // Built off the calling thread, like the other bundled pages.
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let preset = &LOCK_WINDOW;
+        let result = WebviewWindowBuilder::new(
+            &handle,
+            LOCK_WINDOW_LABEL,
+            WebviewUrl::App("lock.html".into()),
+        )
+        .title("Onyx Is Locked")
+        .inner_size(preset.width, preset.height)
+        .min_inner_size(preset.min_width, preset.min_height)
+        .resizable(false)
+        .minimizable(false)
+        .closable(false)
+        .center()
+        .focused(true)
+        .additional_browser_args(&crate::locale::browser_args())
+        .build();
+        if let Err(e) = result {
+            log_backend_error(&handle, &format!("Failed to open the lock window: {e}"));
+        }
+    });
+}
+
+fn unlock(app: &AppHandle) {
+    let lock = app.state::<AppLock>();
+    lock.touch();
+    lock.locked.store(false, Ordering::SeqCst);
+    let hidden = std::mem::take(
+        &mut *lock
+            .hidden
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner),
+    );
+    for label in hidden {
+        if let Some(window) = app.get_webview_window(&label) {
+            if let Err(e) = window.show() {
+                log_backend_error(app, &format!("Failed to show {label} after unlock: {e}"));
+            }
+        }
+    }
+    if let Some(window) = app.get_webview_window(LOCK_WINDOW_LABEL) {
+        if let Err(e) = window.destroy() {
+            log_backend_error(app, &format!("Failed to close the lock window: {e}"));
+        }
+    }
+}
+
+/// Note that a page was used (see `activity.js`)
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn report_activity(lock: tauri::State<AppLock>) {
+    if !lock.is_locked() {
+        lock.touch();
+    }
+}
+
+/// How the lock window can be unlocked
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn get_unlock_options(app: AppHandle) -> UnlockOptions {
+    unlock_options(&app)
+}
+
+/// Unlock the app with the PIN, or with none, by having the system confirm
+/// the user (Touch ID, Windows Hello); only the lock window can
+#[tauri::command]
+pub async fn unlock_app(
+    app: AppHandle,
+    window: WebviewWindow,
+    pin: Option<String>,
+) -> Result<(), String> {
+    if window.label() != LOCK_WINDOW_LABEL {
+        return Err("Only the lock window can unlock Onyx".to_string());
+    }
+    let Some(pin) = pin else {
+        if unlock_options(&app).biometric.is_none() {
+            return Err("Enter the PIN to unlock".to_string());
+        }
+        biometric::verify(&window, biometric::UNLOCK_REASON).await?;
+        unlock(&app);
+        return Ok(());
+    };
+    let lock = app.state::<AppLock>();
+    if let Some(wait) = lock.retry_wait() {
+        return Err(format!(
+            "Too many wrong PINs. Try again in {} seconds",
+            wait.as_secs().max(1)
+        ));
+    }
+    let correct = stored_pin()?.is_some_and(|stored| pin_matches(&stored, &pin));
+    lock.record_attempt(correct);
+    if !correct {
+        return Err("That's not the PIN".to_string());
+    }
+    unlock(&app);
+    Ok(())
+}
+
+/// Set the PIN that unlocks the app, or with none, remove it (turning
+/// auto-lock off); only the settings window can
+#[tauri::command]
+pub async fn set_lock_pin(
+    app: AppHandle,
+    window: WebviewWindow,
+    pin: Option<String>,
+) -> Result<(), String> {
+    if window.label() != SETTINGS_WINDOW_LABEL {
+        return Err("The PIN can only be changed in Settings".to_string());
+    }
+    // Whoever set the PIN has to be the one changing it, where the system
+    // can tell.
+    if stored_pin()?.is_some() && unlock_options(&app).biometric.is_some() {
+        biometric::verify(&window, biometric::CHANGE_PIN_REASON).await?;
+    }
+    let entry = keychain_entry()?;
+    let Some(pin) = pin else {
+        app.state::<ConfigState>()
+            .update_and_persist(|config| config.lock.after_minutes = 0)?;
+        return match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("Could not remove the PIN: {e}")),
+        };
+    };
+    validate_pin(&pin)?;
+    entry
+        .set_password(&hash_pin(&pin)?)
+        .map_err(|e| format!("Could not store the PIN: {e}"))
+}
+
+/// Whether an unlock PIN is set
+#[tauri::command]
+pub async fn has_lock_pin() -> Result<bool, String> {
+    Ok(stored_pin()?.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn only_the_pin_that_was_set_unlocks() {
+        let stored = hash_pin("2468").unwrap();
+        assert!(!stored.contains("2468"));
+        assert!(pin_matches(&stored, "2468"));
+        assert!(!pin_matches(&stored, "2469"));
+        assert!(!pin_matches("not a hash", "2468"));
+        assert_ne!(hash_pin("2468").unwrap(), stored);
+
+        assert!(validate_pin("123").is_err());
+        assert!(validate_pin("1234").is_ok());
+    }
+
+    #[test]
+    fn wrong_pins_slow_down_after_a_few_tries() {
+        let lock = AppLock::default();
+        for _ in 0..FREE_ATTEMPTS {
+            assert!(lock.retry_wait().is_none());
+            lock.record_attempt(false);
+        }
+        assert!(lock.retry_wait().is_some());
+        lock.record_attempt(true);
+        assert!(lock.retry_wait().is_none());
+    }
+}
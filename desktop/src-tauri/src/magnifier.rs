@@ -0,0 +1,233 @@
+// Keeping the app's floating windows (the shortcut cheat sheet, the
+// push-to-talk indicator and the mini chat) where someone zoomed in with the
+// system's screen magnifier can see them, unless `follow_magnifier` is off.
+// Each window is placed for the whole screen as usual, then handed to
+// `bring_into_view`: macOS Zoom is asked to pan to it (`UAZoomChangeFocus`),
+// and since the Windows Magnifier can't be asked, the window is moved into
+// the part of the screen it's showing (`MagGetFullscreenTransform`). Linux
+// magnifiers don't say where they're looking, so windows stay put there.
+
+use crate::config::ConfigState;
+use crate::debug_log::log_backend_error;
+use tauri::{AppHandle, Manager, WebviewWindow};
+
+/// Where in the magnified view a moved window goes, matching where it sits
+/// on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    Center,
+    /// Push to talk's recording window.
+    #[cfg_attr(not(feature = "voice"), allow(dead_code))]
+    TopCenter,
+    BottomRight,
+}
+
+/// Logical pixels kept between a moved window and the edge of the view.
+#[cfg(target_os = "windows")]
+const VIEW_MARGIN: f64 = 16.0;
+
+/// A part of the screen, in physical pixels.
+#[cfg(any(target_os = "windows", test))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Area {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+#[cfg(any(target_os = "windows", test))]
+impl Area {
+    const fn contains(&self, other: &Self) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x + other.width <= self.x + self.width
+            && other.y + other.height <= self.y + self.height
+    }
+}
+
+/// Keep `window` where the screen magnifier shows it, if the screen is
+/// zoomed in.
+pub fn bring_into_view(app: &AppHandle, window: &WebviewWindow, anchor: Anchor) {
+    if !app.state::<ConfigState>().config().follow_magnifier {
+        return;
+    }
+    if let Err(e) = follow(app, window, anchor) {
+        log_backend_error(app, &format!("Failed to follow the screen magnifier: {e}"));
+    }
+}
+
+/// Where a `size` window at `anchor` goes in `view`, `margin` in from the
+/// edges. One too big for the view keeps its top left corner in it.
+#[cfg(any(target_os = "windows", test))]
+fn position_in(view: Area, size: (i32, i32), anchor: Anchor, margin: i32) -> (i32, i32) {
+    let (width, height) = size;
+    let center_x = view.x + (view.width - width) / 2;
+    let (x, y) = match anchor {
+        Anchor::Center => (center_x, view.y + (view.height - height) / 2),
+        Anchor::TopCenter => (center_x, view.y + margin),
+        Anchor::BottomRight => (
+            view.x + view.width - width - margin,
+            view.y + view.height - height - margin,
+        ),
+    };
+    (x.max(view.x), y.max(view.y))
+}
+
+#[cfg(target_os = "macos")]
+fn follow(_app: &AppHandle, window: &WebviewWindow, _anchor: Anchor) -> Result<(), String> {
+    #[repr(C)]
+    struct CGRect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    }
+
+    /// `kUAZoomFocusTypeOther`: a window rather than a text cursor.
+    const FOCUS_TYPE_OTHER: u32 = 0;
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn UAZoomEnabled() -> u8;
+        fn UAZoomChangeFocus(
+            in_rect: *const CGRect,
+            in_highlight_rect: *const CGRect,
+            in_type: u32,
+        ) -> i32;
+    }
+
+    // SAFETY: takes no arguments and only reads the Zoom preference.
+    if unsafe { UAZoomEnabled() } == 0 {
+        return Ok(());
+    }
+    let scale = window.scale_factor().map_err(|e| e.to_string())?;
+    let position = window
+        .outer_position()
+        .map_err(|e| e.to_string())?
+        .to_logical::<f64>(scale);
+    let size = window
+        .outer_size()
+        .map_err(|e| e.to_string())?
+        .to_logical::<f64>(scale);
+    // Zoom's rects are in points from the top left of the main display,
+    // like the window's logical position.
+    let rect = CGRect {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    };
+    // SAFETY: `rect` outlives the call, and no highlight rect is allowed.
+    let status = unsafe { UAZoomChangeFocus(&raw const rect, std::ptr::null(), FOCUS_TYPE_OTHER) };
+    if status != 0 {
+        return Err(format!("Zoom didn't move (error {status})"));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn follow(app: &AppHandle, window: &WebviewWindow, anchor: Anchor) -> Result<(), String> {
+    use tauri::{LogicalSize, PhysicalPosition, PhysicalSize};
+    use windows::Win32::UI::Magnification::{MagGetFullscreenTransform, MagInitialize};
+
+    let (mut level, mut x, mut y) = (1.0_f32, 0_i32, 0_i32);
+    // SAFETY: the out-pointers are live locals for the length of the call.
+    let read = unsafe {
+        MagInitialize().as_bool()
+            && MagGetFullscreenTransform(&raw mut level, &raw mut x, &raw mut y).as_bool()
+    };
+    if !read || level <= 1.0 {
+        return Ok(());
+    }
+
+    // The full-screen magnifier offsets are on the primary screen.
+    let screen = app
+        .primary_monitor()
+        .map_err(|e| e.to_string())?
+        .ok_or("No screen is being magnified")?;
+    let shown = PhysicalSize::new(
+        f64::from(screen.size().width) / f64::from(level),
+        f64::from(screen.size().height) / f64::from(level),
+    )
+    .cast::<i32>();
+    let view = Area {
+        x,
+        y,
+        width: shown.width,
+        height: shown.height,
+    };
+
+    let position = window.outer_position().map_err(|e| e.to_string())?;
+    let size = window
+        .outer_size()
+        .map_err(|e| e.to_string())?
+        .cast::<i32>();
+    let current = Area {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    };
+    if view.contains(&current) {
+        return Ok(());
+    }
+    let scale = window.scale_factor().map_err(|e| e.to_string())?;
+    let margin = LogicalSize::new(VIEW_MARGIN, 0.0)
+        .to_physical::<i32>(scale)
+        .width;
+    let (x, y) = position_in(view, (size.width, size.height), anchor, margin);
+    window
+        .set_position(PhysicalPosition::new(x, y))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+#[allow(clippy::unnecessary_wraps)]
+const fn follow(_app: &AppHandle, _window: &WebviewWindow, _anchor: Anchor) -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_are_moved_into_the_magnified_view() {
+        let view = Area {
+            x: 800,
+            y: 400,
+            width: 960,
+            height: 540,
+        };
+        assert_eq!(
+            position_in(view, (400, 300), Anchor::Center, 20),
+            (1080, 520)
+        );
+        assert_eq!(
+            position_in(view, (220, 56), Anchor::TopCenter, 20),
+            (1170, 420)
+        );
+        assert_eq!(
+            position_in(view, (400, 300), Anchor::BottomRight, 20),
+            (1340, 620)
+        );
+        // Too big to fit: the top left stays in view.
+        assert_eq!(
+            position_in(view, (1200, 700), Anchor::BottomRight, 20),
+            (800, 400)
+        );
+        assert!(view.contains(&Area {
+            x: 1000,
+            y: 500,
+            width: 100,
+            height: 100,
+        }));
+        assert!(!view.contains(&Area {
+            x: 1700,
+            y: 500,
+            width: 100,
+            height: 100,
+        }));
+    }
+}
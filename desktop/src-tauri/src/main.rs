@@ -4,19 +4,114 @@
 // (e.g. `base64`, `syn`, `windows-sys`) that we don't control.
 #![allow(clippy::multiple_crate_versions)]
 
+mod accounts;
+mod admin;
 mod alt_menu;
+mod approvals;
+mod assets;
+mod auth;
+#[cfg(feature = "automation-api")]
+mod automation_api;
+mod autostart;
+mod badge;
+mod biometric;
+#[cfg(feature = "bridge-bench")]
+mod bridge_bench;
+mod browsing_data;
+mod capabilities;
+mod cert_pinning;
+mod cert_trust;
+mod chat_export;
+mod cheat_sheet;
+mod client_cert;
+#[cfg(feature = "clipboard-history")]
+mod clipboard_history;
 mod commands;
+mod companion;
 mod config;
+mod config_backup;
+mod config_check;
+mod config_format;
+mod config_schema;
+mod config_transfer;
+mod connection;
+mod consent;
+mod context_menu;
+#[cfg(feature = "crash-reporting")]
+mod crash_report;
 mod debug_log;
+mod diagnostics;
+#[cfg(feature = "voice")]
+mod dictation;
+mod do_not_disturb;
+#[cfg(target_os = "macos")]
+mod dock_menu;
+mod documents;
+mod draft_queue;
+mod file_menu;
+mod focus_mode;
+mod handoff;
+mod headless;
+mod install_scope;
+mod instance;
+mod ipc;
+mod issue_report;
+mod kiosk;
+#[cfg(feature = "local-files")]
+mod local_files;
+#[cfg(feature = "offline-cache")]
+mod local_search;
+mod locale;
+mod lock;
+mod magnifier;
+mod managed_config;
 mod menu;
+mod mini_chat;
+#[cfg(feature = "mock-server")]
+mod mock_server;
+#[cfg(feature = "offline-cache")]
+mod offline_cache;
+mod onboarding;
+mod portable;
+#[cfg(feature = "voice")]
+mod push_to_talk;
+#[cfg(feature = "read-aloud")]
+mod read_aloud;
+mod route;
+mod sandbox;
+mod scheduler;
+mod screenshot;
+#[cfg(target_os = "macos")]
+mod scripting;
+mod secrets;
+mod server_api;
+mod server_features;
+mod server_headers;
+mod server_status;
+#[cfg(target_os = "macos")]
+mod services;
+mod share;
+mod shortcut_keys;
+mod shortcuts;
+mod snippets;
+mod spellcheck;
+mod split_view;
+mod startup_timing;
+#[cfg(feature = "ui-automation")]
+mod ui_automation;
+mod watchdog;
 mod window;
+mod zoom;
 
 use clap::Parser;
-use config::ConfigState;
-use serde::Deserialize;
-use tauri::{webview::PageLoadPayload, Manager, Webview, Wry};
-#[cfg(target_os = "macos")]
-use window_vibrancy::{apply_vibrancy, NSVisualEffectMaterial};
+use config::{BackgroundBehavior, ConfigState};
+use diagnostics::Diagnostics;
+use startup_timing::StartupProfiler;
+use tauri::{
+    plugin::TauriPlugin,
+    webview::{PageLoadEvent, PageLoadPayload},
+    Manager, Webview, Wry,
+};
 
 // ============================================================================
 // CLI flags
@@ -26,47 +121,152 @@ use window_vibrancy::{apply_vibrancy, NSVisualEffectMaterial};
 #[derive(Parser)]
 #[command(name = "onyx", long_about = None, disable_version_flag = true)]
 struct Cli {
-    // Handled manually rather than via `#[command(version)]` so it can also
-    // report the connected server's version, not just the client build.
-    /// Print client and server version and exit
-    #[arg(short = 'v', long)]
-    version: bool,
+    #[command(flatten)]
+    mode: Mode,
 
     /// Enable verbose logging, auto-open `DevTools`, and capture webview console output
     #[arg(long)]
     debug: bool,
+
+    /// Keep all config and data in DIR, as in portable mode, to run a
+    /// separate profile next to the usual one
+    #[arg(long, value_name = "DIR")]
+    data_dir: Option<std::path::PathBuf>,
+
+    /// Ask QUERY, or each line of stdin without one, without opening a
+    /// window, and print the answers
+    #[arg(long, value_name = "QUERY", num_args = 0..=1, default_missing_value = "")]
+    headless: Option<String>,
+
+    /// How `--headless` prints answers
+    #[arg(long, value_enum, default_value_t, requires = "headless")]
+    format: headless::Format,
+
+    /// Run against a built-in mock server (on PORT, or any free port) with a
+    /// throwaway config, for demos and end-to-end tests
+    #[cfg(feature = "mock-server")]
+    #[arg(long, value_name = "PORT", num_args = 0..=1, default_missing_value = "0")]
+    demo: Option<u16>,
+
+    /// Write minidumps for the app listening on SOCKET (started by the app
+    /// itself; see `crash_report.rs`)
+    #[cfg(feature = "crash-reporting")]
+    #[arg(long, value_name = "SOCKET", hide = true)]
+    crash_server: Option<std::path::PathBuf>,
+
+    /// An `onyx://` link to open, as the system passes one that was clicked
+    #[arg(value_name = "LINK", hide = true)]
+    link: Option<String>,
+    #[command(subcommand)]
+    command: Option<companion::Command>,
+}
+
+/// What a launch is for, picked by at most one flag.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum Mode {
+    /// Open the app as usual.
+    #[default]
+    Normal,
+    Version,
+    CheckConfig,
+    Kiosk,
+    #[cfg(feature = "bridge-bench")]
+    BenchBridge,
+    #[cfg(feature = "ui-automation")]
+    Automation,
+}
+
+impl Mode {
+    /// Each mode's flag, in the order `--help` lists them.
+    fn flags() -> Vec<(Self, clap::Arg)> {
+        let flag = |id: &'static str, help: &'static str| {
+            clap::Arg::new(id)
+                .long(id)
+                .help(help)
+                .action(clap::ArgAction::SetTrue)
+        };
+        vec![
+            // Handled manually rather than via `#[command(version)]` so it can
+            // also report the connected server's version, not just the client
+            // build.
+            (
+                Self::Version,
+                flag("version", "Print client and server version and exit").short('v'),
+            ),
+            (
+                Self::CheckConfig,
+                flag(
+                    "check-config",
+                    "Check the config file, print what's wrong with it, and exit \
+                     (with status 1 if it has errors)",
+                ),
+            ),
+            (
+                Self::Kiosk,
+                flag(
+                    "kiosk",
+                    "Run as a kiosk: fullscreen, a single window, no settings, menus \
+                     or shortcuts, and a fresh chat after a while without use",
+                ),
+            ),
+            #[cfg(feature = "bridge-bench")]
+            (
+                Self::BenchBridge,
+                flag(
+                    "bench-bridge",
+                    "Measure Rust <-> webview bridge latency/throughput once the \
+                     server page loads, recording the results in diagnostics",
+                )
+                .hide(true),
+            ),
+            #[cfg(feature = "ui-automation")]
+            (
+                Self::Automation,
+                flag(
+                    "automation",
+                    "Give controls stable identifiers and accept `ui_action`, for \
+                     UI tests",
+                )
+                .hide(true),
+            ),
+        ]
+    }
 }
 
-#[derive(Deserialize)]
-struct VersionResponse {
-    backend_version: String,
+impl clap::FromArgMatches for Mode {
+    fn from_arg_matches(matches: &clap::ArgMatches) -> Result<Self, clap::Error> {
+        Ok(Self::flags()
+            .into_iter()
+            .find(|(_, flag)| matches.get_flag(flag.get_id().as_str()))
+            .map_or(Self::Normal, |(mode, _)| mode))
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &clap::ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl clap::Args for Mode {
+    fn augment_args(command: clap::Command) -> clap::Command {
+        let (ids, flags): (Vec<_>, Vec<_>) = Self::flags()
+            .into_iter()
+            .map(|(_, flag)| (flag.get_id().clone(), flag))
+            .unzip();
+        command
+            .args(flags)
+            .group(clap::ArgGroup::new("mode").args(ids))
+    }
+
+    fn augment_args_for_update(command: clap::Command) -> clap::Command {
+        Self::augment_args(command)
+    }
 }
 
 /// Fetch the backend version from the configured server's public `/api/version`
 /// endpoint. `Ok(None)` means the server answered but reported no version.
-fn fetch_server_version(server_url: &str) -> Result<Option<String>, String> {
-    let url = format!("{}/api/version", server_url.trim_end_matches('/'));
-
-    tauri::async_runtime::block_on(async move {
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(5))
-            .build()
-            .map_err(|e| e.to_string())?;
-        let resp = client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?
-            .error_for_status()
-            .map_err(|e| e.to_string())?;
-        let text = resp.text().await.map_err(|e| e.to_string())?;
-        let body: VersionResponse = serde_json::from_str(&text).map_err(|e| e.to_string())?;
-        if body.backend_version.trim().is_empty() {
-            Ok(None)
-        } else {
-            Ok(Some(body.backend_version))
-        }
-    })
+fn fetch_server_version(config: &config::AppConfig) -> Result<Option<String>, String> {
+    tauri::async_runtime::block_on(server_api::server_version(config))
 }
 
 /// Print client and (if reachable) server version, mirroring the CLI's
@@ -78,12 +278,26 @@ fn print_version_info() {
     println!("Client version: {}", env!("CARGO_PKG_VERSION"));
 
     let (config, _) = config::load_config();
-    let server_url = config.server_url;
 
-    match fetch_server_version(&server_url) {
+    match fetch_server_version(&config) {
         Ok(Some(version)) => println!("Server version: {version}"),
-        Ok(None) => println!("Server version: unknown (empty response from {server_url})"),
-        Err(_) => println!("Server version: unknown (could not fetch from {server_url})"),
+        Ok(None) => println!(
+            "Server version: unknown (empty response from {})",
+            config.server_url
+        ),
+        Err(_) => println!(
+            "Server version: unknown (could not fetch from {})",
+            config.server_url
+        ),
+    }
+}
+
+/// What a companion command (`onyx config get`, say) answered with.
+// Printing to stdout is what the command is for.
+#[allow(clippy::print_stdout)]
+fn print_command_output(output: &str) {
+    if !output.is_empty() {
+        println!("{output}");
     }
 }
 
@@ -93,6 +307,11 @@ fn print_version_info() {
 
 // Printed before the app (and thus `log_backend_error`'s `AppHandle`) exists,
 // so this can't go through the usual logging path.
+#[allow(clippy::print_stderr)]
+fn print_startup_error(message: &str) {
+    eprintln!("[ONYX ERROR] {message}");
+}
+
 #[allow(clippy::print_stderr)]
 fn print_debug_startup_banner() {
     eprintln!("[ONYX DEBUG] Debug mode enabled");
@@ -103,27 +322,153 @@ fn print_debug_startup_banner() {
     eprintln!("[ONYX DEBUG] Capturing console.log/warn/error/info/debug from webview");
 }
 
-/// Everything that runs once the Tauri app is up: menu/tray, the main
-/// window's platform tweaks, and Alt-menu/devtools wiring. Every failure here
+/// Everything that runs once the Tauri app is up: menu/tray, background
+/// polling, and the main window's setup. Every failure here
 /// is logged and non-fatal, so this never needs to return a `Result`.
 fn setup_app(app: &tauri::AppHandle) {
-    if let Err(e) = menu::setup_app_menu(app) {
-        debug_log::log_backend_error(app, &format!("Failed to setup menu: {e}"));
-    }
+    let profiler = app.state::<StartupProfiler>();
+    let kiosk = kiosk::is_active();
 
-    if let Err(e) = menu::setup_tray_icon(app) {
-        debug_log::log_backend_error(app, &format!("Failed to setup tray icon: {e}"));
+    // A kiosk has no menus or shortcuts (see `kiosk.rs`).
+    if !kiosk {
+        if let Err(e) = profiler.time("menu_setup", || menu::setup_app_menu(app)) {
+            debug_log::log_backend_error(app, &format!("Failed to setup menu: {e}"));
+        }
+        shortcuts::report_problems(app);
+    }
+    // Another copy on the same data holds the push-to-talk shortcut, and
+    // takes the links.
+    if !app.state::<instance::InstanceLock>().is_conflicting() {
+        // Nothing that reaches past a kiosk's one window, or keeps what one
+        // user did for the next.
+        if !kiosk {
+            #[cfg(feature = "voice")]
+            push_to_talk::sync_shortcut(app);
+            listen_for_links(app);
+            ipc::listen(app);
+            #[cfg(feature = "automation-api")]
+            automation_api::sync(app);
+            #[cfg(feature = "local-files")]
+            local_files::start_syncing(app);
+            #[cfg(feature = "offline-cache")]
+            offline_cache::start_refreshing(app);
+            #[cfg(feature = "clipboard-history")]
+            clipboard_history::start_watching(app);
+        }
+        scheduler::start_checking(app);
+        autostart::sync(app);
+        managed_config::start_refreshing(app);
+        if !app.state::<ConfigState>().is_in_memory() {
+            if let Err(e) = config_schema::write() {
+                debug_log::log_backend_error(
+                    app,
+                    &format!("Failed to write the config schema: {e}"),
+                );
+            }
+        }
+        #[cfg(target_os = "windows")]
+        file_menu::sync(app);
     }
 
-    let Some(window) = app.get_webview_window("main") else {
-        return;
-    };
+    if !kiosk {
+        if let Err(e) = profiler.time("tray_setup", || menu::setup_tray_icon(app)) {
+            debug_log::log_backend_error(app, &format!("Failed to setup tray icon: {e}"));
+        }
+        accounts::sync_account_menus(app);
+    }
 
     #[cfg(target_os = "macos")]
-    if let Err(e) = apply_vibrancy(&window, NSVisualEffectMaterial::Sidebar, None, None) {
-        debug_log::log_backend_error(app, &format!("Failed to apply vibrancy effect: {e}"));
+    if !kiosk {
+        if let Err(e) = profiler.time("dock_menu_setup", || dock_menu::setup_dock_menu(app)) {
+            debug_log::log_backend_error(app, &format!("Failed to setup Dock menu: {e}"));
+        }
+    }
+    #[cfg(target_os = "macos")]
+    if !kiosk {
+        if let Err(e) = scripting::setup(app) {
+            debug_log::log_backend_error(app, &format!("Failed to set up AppleScript: {e}"));
+        }
+        if let Err(e) = services::setup(app) {
+            debug_log::log_backend_error(app, &format!("Failed to set up Services: {e}"));
+        }
     }
 
+    instance::warn_if_conflicting(app);
+    badge::start_polling(app);
+    lock::watch_for_idle(app);
+    if kiosk {
+        kiosk::watch_for_idle(app);
+    }
+    #[cfg(feature = "crash-reporting")]
+    crash_report::upload_pending(app);
+    admin::start_polling(app);
+    server_status::check_at_startup(app);
+    connection::start_probing(app);
+    watchdog::start(app);
+
+    if let Some(window) = app.get_webview_window("main") {
+        setup_main_window(app, &window);
+    }
+    if onboarding::is_needed(app) {
+        window::open_onboarding(app);
+    }
+}
+
+/// Send `onyx://` links to `handoff`, `share` or `auth`, and Onyx's files
+/// to `documents`: ones the system opens the app with, and ones another
+/// copy was started with (see `instance.rs`).
+fn listen_for_links(app: &tauri::AppHandle) {
+    use tauri_plugin_deep_link::DeepLinkExt;
+
+    fn open_url(app: &tauri::AppHandle, url: &url::Url) {
+        if !handoff::open_url(app, url)
+            && !share::open_url(app, url)
+            && !documents::open_url(app, url)
+        {
+            auth::open_url(app, url);
+        }
+    }
+
+    fn open(app: &tauri::AppHandle, link: &str) {
+        // A file another copy was started with.
+        if documents::open_path(app, link) {
+            return;
+        }
+        match link.parse() {
+            Ok(url) => open_url(app, &url),
+            Err(e) => debug_log::log_backend_error(app, &format!("Failed to read link: {e}")),
+        }
+    }
+
+    // Installers register the scheme; this covers portable and dev builds.
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    if let Err(e) = app.deep_link().register_all() {
+        debug_log::log_backend_error(app, &format!("Failed to register onyx:// links: {e}"));
+    }
+    app.state::<instance::InstanceLock>()
+        .listen_for_handoffs(app, open);
+    let handle = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            open_url(&handle, &url);
+        }
+    });
+    // Started by a link (Windows and Linux).
+    if let Ok(Some(urls)) = app.deep_link().get_current() {
+        for url in urls {
+            open_url(app, &url);
+        }
+    }
+}
+
+/// The main window's platform tweaks, Alt-menu and devtools wiring, and
+/// settings, which windows built from config don't get from a builder. Run
+/// at startup and again whenever the main window is rebuilt.
+fn setup_main_window(app: &tauri::AppHandle, window: &tauri::WebviewWindow) {
+    let profiler = app.state::<StartupProfiler>();
+
+    profiler.time("appearance", || window::apply_appearance(app, window));
+
     if let Ok(url) = window.url() {
         let mut base_url = url;
         base_url.set_query(None);
@@ -135,29 +480,283 @@ fn setup_app(app: &tauri::AppHandle) {
     #[cfg(target_os = "macos")]
     window::inject_titlebar(window.clone());
 
-    window::apply_settings_to_window(app, &window);
+    profiler.time("window_settings", || {
+        window::apply_settings_to_window(app, window);
+        // New windows get this from their builder; the main window is
+        // created from `tauri.conf.json`.
+        if app.state::<ConfigState>().config().always_on_top {
+            window::set_window_always_on_top(app, window, true);
+        }
+        zoom::apply_saved_zoom(app, window);
+    });
+    auth::restore_session(app, window);
+    client_cert::answer_requests(app, window);
+    cert_trust::watch_certificate_errors(app, window);
 
     #[cfg(target_os = "linux")]
-    alt_menu::setup_alt_menu_toggle(app, &window);
+    profiler.time("alt_menu_setup", || {
+        alt_menu::setup_alt_menu_toggle(app, window);
+    });
 
-    debug_log::maybe_open_devtools(app, &window);
+    debug_log::maybe_open_devtools(app, window);
+    if kiosk::is_active() {
+        kiosk::setup_window(app, window);
+    }
 
-    if let Err(e) = window.set_focus() {
-        debug_log::log_backend_error(app, &format!("Failed to focus main window: {e}"));
+    // Hidden during onboarding, which shows it when it's done.
+    if window.is_visible().unwrap_or(true) {
+        if let Err(e) = window.set_focus() {
+            debug_log::log_backend_error(app, &format!("Failed to focus main window: {e}"));
+        }
     }
 }
 
-fn main() {
-    let cli = Cli::parse();
+/// Track the main window's first loads: the bundled page, then the first
+/// server page, which is where startup is considered done.
+fn record_startup_page_load(webview: &Webview, payload: &PageLoadPayload) {
+    if webview.label() != "main" || payload.event() != PageLoadEvent::Finished {
+        return;
+    }
+    let app = webview.app_handle();
+    let profiler = app.state::<StartupProfiler>();
 
-    if cli.version {
-        print_version_info();
+    if !matches!(payload.url().scheme(), "http" | "https") {
+        profiler.milestone("first_page_load");
         return;
     }
 
-    let (app_config, config_initialized) = config::load_config();
-    let debug_mode = debug_log::is_debug_mode(cli.debug);
+    profiler.milestone("first_server_page_load");
+    if profiler.finish() {
+        for line in profiler.report().summary_lines() {
+            debug_log::log_debug(app, &line);
+        }
+    }
+
+    #[cfg(feature = "bridge-bench")]
+    bridge_bench::maybe_start(webview);
+}
+
+/// Sends links clicked inside a chat session to the default browser instead
+/// of navigating the webview away from the chat.
+fn external_navigation_plugin() -> TauriPlugin<Wry> {
+    tauri::plugin::Builder::<Wry>::new("chat-external-navigation-handler")
+        .on_navigation(|webview, destination_url| {
+            let Ok(current_url) = webview.url() else {
+                return server_headers::allow_navigation(webview, destination_url);
+            };
+
+            if window::should_open_in_external_browser(&current_url, destination_url) {
+                // A kiosk stays on the server.
+                if kiosk::is_active() {
+                    return false;
+                }
+                if !window::open_in_default_browser(destination_url.as_str()) {
+                    debug_log::log_backend_error(
+                        webview.app_handle(),
+                        &format!(
+                            "Failed to open external URL in default browser: {destination_url}"
+                        ),
+                    );
+                }
+                return false;
+            }
+
+            server_headers::allow_navigation(webview, destination_url)
+        })
+        .build()
+}
+
+fn handle_window_event(window: &tauri::Window, event: &tauri::WindowEvent) {
+    match event {
+        // The "Always on Top" checkbox follows the focused window.
+        tauri::WindowEvent::Focused(true) => {
+            lock::on_window_focused(window.app_handle(), window.label());
+            if let Ok(on_top) = window.is_always_on_top() {
+                menu::sync_always_on_top_checkbox(window.app_handle(), on_top);
+            }
+            if !window::is_bundled_page_window(window.label()) {
+                badge::mark_seen(window.app_handle());
+                accounts::sync_account_menus(window.app_handle());
+                window::remember_position(window);
+            }
+        }
+        tauri::WindowEvent::Moved(_) => window::remember_position(window),
+        tauri::WindowEvent::CloseRequested { api, .. }
+            if window.label() == window::LOCK_WINDOW_LABEL
+                || (window.label() == "main" && kiosk::is_active()) =>
+        {
+            api.prevent_close();
+        }
+        tauri::WindowEvent::CloseRequested { api, .. } => {
+            let behavior = window.state::<ConfigState>().config().close_behavior;
+            if behavior != BackgroundBehavior::Quit
+                && !window::is_bundled_page_window(window.label())
+            {
+                api.prevent_close();
+                window::hide_to_background(window.app_handle(), window, behavior);
+            }
+        }
+        // There's no minimize event; a resize that leaves the window
+        // minimized is the closest thing.
+        tauri::WindowEvent::Resized(_) if window.is_minimized().unwrap_or(false) => {
+            let behavior = window.state::<ConfigState>().config().minimize_behavior;
+            if behavior != BackgroundBehavior::Quit
+                && !window::is_bundled_page_window(window.label())
+            {
+                window::hide_to_background(window.app_handle(), window, behavior);
+            }
+        }
+        tauri::WindowEvent::Destroyed => {
+            if window.label() == "main" && window::rebuilding_main_window() {
+                let app = window.app_handle().clone();
+                tauri::async_runtime::spawn(async move { rebuild_main_window(&app) });
+            }
+            window.state::<watchdog::Watchdog>().forget(window.label());
+            mini_chat::forget_window(window.app_handle(), window.label());
+            focus_mode::forget_window(window.app_handle(), window.label());
+            split_view::forget_window(window.app_handle(), window.label());
+            if window.label() == window::ONBOARDING_WINDOW_LABEL {
+                onboarding::closed(window.app_handle());
+            }
+            window
+                .state::<window::WindowPartitions>()
+                .remove(window.label());
+        }
+        _ => {}
+    }
+}
+
+/// Build the main window again after `window::recreate_window` destroyed it.
+fn rebuild_main_window(app: &tauri::AppHandle) {
+    match window::create_main_window(app) {
+        Ok(window) => {
+            setup_main_window(app, &window);
+            if let Some(url) = window::finish_main_window_rebuild() {
+                if let Err(e) = window.navigate(url) {
+                    debug_log::log_backend_error(app, &format!("Failed to reopen page: {e}"));
+                }
+            }
+        }
+        Err(e) => {
+            window::finish_main_window_rebuild();
+            debug_log::log_backend_error(app, &format!("Failed to rebuild main window: {e}"));
+        }
+    }
+}
+
+fn handle_menu_event(app: &tauri::AppHandle, id: &str) {
+    // A kiosk's only menu is the page's context menu, less Inspect.
+    if kiosk::is_active()
+        && !matches!(
+            id,
+            context_menu::MENU_SEARCH_SELECTION_ID | context_menu::MENU_COPY_LINK_ID
+        )
+    {
+        return;
+    }
+    // The page reports shortcuts the menus may have caught too.
+    if !app.state::<shortcut_keys::RecentPress>().first(id) {
+        return;
+    }
+    match id {
+        menu::MENU_OPEN_DOCS_ID => window::open_docs(app),
+        menu::MENU_CHEAT_SHEET_ID => cheat_sheet::toggle_cheat_sheet(app),
+        menu::MENU_REPORT_ISSUE_ID => issue_report::start_report(app),
+        menu::MENU_NEW_CHAT_ID => window::trigger_new_chat(app),
+        menu::MENU_NEW_WINDOW_ID => window::trigger_new_window(app),
+        menu::MENU_OPEN_SETTINGS_ID => window::open_settings(app),
+        menu::MENU_BROWSER_SIGN_IN_ID => auth::start_focused_login(app),
+        menu::MENU_SIGN_OUT_ID => auth::sign_out_focused(app),
+        menu::MENU_EXPORT_CHAT_ID => chat_export::export_focused_chat(app),
+        menu::MENU_EXPORT_ALL_CHATS_ID => chat_export::export_all_focused(app),
+        menu::MENU_SHOW_MENU_BAR_ID => menu::handle_menu_bar_toggle(app),
+        menu::MENU_ALWAYS_ON_TOP_ID => window::toggle_always_on_top(app),
+        menu::MENU_MINI_CHAT_ID => mini_chat::toggle_focused_mini_chat(app),
+        menu::MENU_FOCUS_MODE_ID => focus_mode::toggle_focused_focus_mode(app),
+        menu::MENU_INSERT_SNIPPET_ID => snippets::pick_focused(app),
+        #[cfg(feature = "voice")]
+        menu::MENU_DICTATION_ID => dictation::toggle_focused(app),
+        #[cfg(feature = "clipboard-history")]
+        menu::MENU_CLIPBOARD_HISTORY_ID => clipboard_history::open_picker(app),
+        #[cfg(feature = "read-aloud")]
+        menu::MENU_READ_SELECTION_ID => read_aloud::read_focused_selection(app),
+        #[cfg(feature = "read-aloud")]
+        menu::MENU_READ_LAST_RESPONSE_ID => read_aloud::read_focused_last_response(app),
+        menu::MENU_SPLIT_VIEW_ID => split_view::trigger_split_view(app),
+        menu::MENU_RELOAD_ID => commands::navigate_focused(app, commands::Navigation::Reload),
+        menu::MENU_GO_BACK_ID => commands::navigate_focused(app, commands::Navigation::Back),
+        menu::MENU_GO_FORWARD_ID => {
+            commands::navigate_focused(app, commands::Navigation::Forward);
+        }
+        menu::MENU_ZOOM_IN_ID => zoom::change_focused_zoom(app, zoom::ZoomChange::In),
+        menu::MENU_ZOOM_OUT_ID => zoom::change_focused_zoom(app, zoom::ZoomChange::Out),
+        menu::MENU_ZOOM_RESET_ID => zoom::change_focused_zoom(app, zoom::ZoomChange::Reset),
+        #[cfg(target_os = "linux")]
+        menu::MENU_HIDE_DECORATIONS_ID => menu::handle_decorations_toggle(app),
+        debug_log::MENU_TOGGLE_DEVTOOLS_ID => debug_log::handle_toggle_devtools(app),
+        debug_log::MENU_OPEN_DEBUG_LOG_ID => debug_log::handle_open_debug_log(),
+        context_menu::MENU_SEARCH_SELECTION_ID | context_menu::MENU_COPY_LINK_ID => {
+            context_menu::handle_menu_event(app, id);
+        }
+        #[cfg(feature = "read-aloud")]
+        context_menu::MENU_READ_ALOUD_ID => context_menu::handle_menu_event(app, id),
+        #[cfg(any(debug_assertions, feature = "devtools"))]
+        context_menu::MENU_INSPECT_ID => context_menu::handle_menu_event(app, id),
+        #[cfg(target_os = "macos")]
+        dock_menu::MENU_CHECK_SERVER_ID => dock_menu::check_server_status(app),
+        #[cfg(target_os = "macos")]
+        id if id.starts_with(dock_menu::RECENT_CHAT_ID_PREFIX) => {
+            dock_menu::open_recent_chat(app, id);
+        }
+        id if id.starts_with(accounts::SWITCH_ACCOUNT_ID_PREFIX) => {
+            accounts::handle_menu_event(app, id);
+        }
+        _ => {}
+    }
+}
+
+fn on_page_load(webview: &Webview, payload: &PageLoadPayload) {
+    let app = webview.app_handle();
+    app.state::<StartupProfiler>().time("page_injection", || {
+        window::inject_chat_link_intercept(webview);
+        context_menu::inject_context_menu_script(webview);
+        spellcheck::inject_spellcheck_script(webview);
+        shortcut_keys::inject_shortcut_keys_script(webview);
+        lock::inject_activity_script(webview);
+        draft_queue::inject_offline_drafts_script(webview);
+        snippets::inject_snippets_script(webview);
+        #[cfg(feature = "ui-automation")]
+        ui_automation::inject_ui_automation_script(webview);
+
+        if app.state::<ConfigState>().debug_mode {
+            debug_log::inject_console_capture(webview);
+        }
+
+        #[cfg(target_os = "macos")]
+        window::eval_titlebar_script(webview);
+        focus_mode::apply_to_webview(webview);
+
+        #[cfg(target_os = "windows")]
+        alt_menu::inject_alt_menu_script(webview);
+
+        if payload.event() == PageLoadEvent::Finished {
+            server_features::request_server_features(webview);
+            onboarding::fill_login_email(webview);
+            auth::forget_session_on_login_page(webview);
+            share::attach_pending(webview);
+        }
+    });
+
+    record_startup_page_load(webview, payload);
+}
 
+/// The config the app runs with: the user's config file with managed policy
+/// applied, or for `--demo` a default config pointed at a freshly started
+/// mock server that's never saved.
+// Only the mock server can fail to start.
+#[cfg_attr(not(feature = "mock-server"), allow(clippy::unnecessary_wraps))]
+fn load_config_state(cli: &Cli, profiler: &StartupProfiler) -> Result<ConfigState, String> {
+    let debug_mode = debug_log::is_debug_mode(cli.debug);
     let debug_log_file = if debug_mode {
         print_debug_startup_banner();
         debug_log::init_debug_log_file()
@@ -165,90 +764,421 @@ fn main() {
         None
     };
 
-    // Fatal: if the Tauri runtime can't start there's nothing else to do.
-    // `tauri::generate_context!()` also expands to code that calls
-    // `std::process::exit` internally on some platforms.
-    #[allow(clippy::expect_used, clippy::exit)]
-    tauri::Builder::default()
+    #[cfg(feature = "mock-server")]
+    if let Some(port) = cli.demo {
+        let addr = mock_server::start(port)?;
+        let app_config = config::AppConfig {
+            server_url: format!("http://{addr}"),
+            ..config::AppConfig::default()
+        };
+        return Ok(ConfigState::new(app_config, true, debug_mode, debug_log_file).in_memory());
+    }
+
+    let (mut app_config, config_initialized) = profiler.time("config_load", config::load_config);
+    install_scope::managed_policy().apply(&mut app_config);
+    Ok(ConfigState::new(
+        app_config,
+        config_initialized,
+        debug_mode,
+        debug_log_file,
+    ))
+}
+
+/// Take the data directory for this copy (see `instance.rs`). Demos run on
+/// a throwaway config, next to a real copy if need be.
+#[cfg_attr(not(feature = "mock-server"), allow(unused_variables))]
+fn claim_instance(cli: &Cli) -> instance::InstanceLock {
+    #[cfg(feature = "mock-server")]
+    if cli.demo.is_some() {
+        return instance::InstanceLock::default();
+    }
+    instance::InstanceLock::claim()
+}
+
+/// The plugins. A copy that found another one on its data directory leaves
+/// the window state to that one.
+fn with_plugins(
+    builder: tauri::Builder<tauri::Wry>,
+    cli: &Cli,
+    instance: &instance::InstanceLock,
+) -> tauri::Builder<tauri::Wry> {
+    let builder = builder
         .plugin(tauri_plugin_shell::init())
-        .plugin(
-            tauri::plugin::Builder::<Wry>::new("chat-external-navigation-handler")
-                .on_navigation(|webview, destination_url| {
-                    let Ok(current_url) = webview.url() else {
-                        return true;
-                    };
-
-                    if window::should_open_in_external_browser(&current_url, destination_url) {
-                        if !window::open_in_default_browser(destination_url.as_str()) {
-                            debug_log::log_backend_error(
-                                webview.app_handle(),
-                                &format!(
-                                    "Failed to open external URL in default browser: {destination_url}"
-                                ),
-                            );
-                        }
-                        return false;
-                    }
-
-                    true
-                })
-                .build(),
-        )
-        .plugin(tauri_plugin_window_state::Builder::default().build())
-        .manage(ConfigState::new(
-            app_config,
-            config_initialized,
-            debug_mode,
-            debug_log_file,
-        ))
-        .invoke_handler(tauri::generate_handler![
-            commands::get_server_url,
-            commands::get_bootstrap_state,
-            commands::set_server_url,
-            commands::get_config_path_cmd,
-            commands::check_server_reachable,
-            commands::open_in_browser,
-            commands::open_config_file,
-            commands::open_config_directory,
-            commands::navigate_to,
-            commands::reload_page,
-            commands::go_back,
-            commands::go_forward,
-            commands::new_window,
-            commands::reset_config,
-            commands::start_drag_window,
-            commands::toggle_menu_bar,
-            debug_log::log_from_frontend
-        ])
-        .on_menu_event(|app, event| match event.id().as_ref() {
-            menu::MENU_OPEN_DOCS_ID => window::open_docs(app),
-            menu::MENU_NEW_CHAT_ID => window::trigger_new_chat(app),
-            menu::MENU_NEW_WINDOW_ID => window::trigger_new_window(app),
-            menu::MENU_OPEN_SETTINGS_ID => window::open_settings(app),
-            menu::MENU_SHOW_MENU_BAR_ID => menu::handle_menu_bar_toggle(app),
-            #[cfg(target_os = "linux")]
-            menu::MENU_HIDE_DECORATIONS_ID => menu::handle_decorations_toggle(app),
-            debug_log::MENU_TOGGLE_DEVTOOLS_ID => debug_log::handle_toggle_devtools(app),
-            debug_log::MENU_OPEN_DEBUG_LOG_ID => debug_log::handle_open_debug_log(),
-            _ => {}
-        })
-        .setup(|app| {
-            setup_app(&app.handle().clone());
-            Ok(())
-        })
-        .on_page_load(|webview: &Webview, _payload: &PageLoadPayload| {
-            window::inject_chat_link_intercept(webview);
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(external_navigation_plugin());
+    if instance.is_conflicting() {
+        return builder;
+    }
+    builder.plugin(
+        tauri_plugin_window_state::Builder::default()
+            .with_filename(instance::window_state_file_name(cli.data_dir.as_deref()))
+            .build(),
+    )
+}
+
+/// Register the per-session state that starts out empty.
+fn manage_session_state(builder: tauri::Builder<tauri::Wry>) -> tauri::Builder<tauri::Wry> {
+    let builder = builder
+        .manage(Diagnostics::default())
+        .manage(debug_log::BackendFailures::default())
+        .manage(assets::Scripts::default())
+        .manage(mini_chat::MiniChatState::default())
+        .manage(focus_mode::FocusMode::default())
+        .manage(window::WindowPartitions::default())
+        .manage(window::TitlebarFallbacks::default())
+        .manage(split_view::SplitViews::default())
+        .manage(server_features::ServerFeatures::default())
+        .manage(badge::UnreadBadge::default())
+        .manage(context_menu::ContextMenuState::default())
+        .manage(onboarding::LoginPrefill::default())
+        .manage(watchdog::Watchdog::default())
+        .manage(admin::FailedConnectors::default())
+        .manage(approvals::PendingApprovals::default())
+        .manage(do_not_disturb::DoNotDisturb::default())
+        .manage(shortcut_keys::RecentPress::default())
+        .manage(share::PendingShare::default())
+        .manage(documents::OpenTranscript::default())
+        .manage(accounts::AccountMenus::default())
+        .manage(auth::PendingLogins::default())
+        .manage(lock::AppLock::default())
+        .manage(connection::ConnectionQuality::default())
+        .manage(draft_queue::DraftQueue::default());
+    #[cfg(target_os = "windows")]
+    let builder = builder.manage(server_headers::HeaderHandlers::default());
+    #[cfg(not(target_os = "windows"))]
+    let builder = builder.manage(server_headers::HeaderedLoads::default());
+    builder
+}
+
+/// Register the state of the features this build has.
+#[cfg_attr(
+    not(any(feature = "bridge-bench", feature = "ui-automation")),
+    allow(unused_variables)
+)]
+// Only passes the builder through in a build with none of them.
+#[cfg_attr(
+    not(any(
+        feature = "bridge-bench",
+        feature = "ui-automation",
+        feature = "automation-api",
+        feature = "local-files",
+        feature = "offline-cache",
+        feature = "clipboard-history",
+        feature = "read-aloud",
+        feature = "voice"
+    )),
+    allow(clippy::missing_const_for_fn)
+)]
+fn manage_feature_state(
+    builder: tauri::Builder<tauri::Wry>,
+    cli: &Cli,
+) -> tauri::Builder<tauri::Wry> {
+    #[cfg(feature = "bridge-bench")]
+    let builder = bridge_bench::manage(builder, cli.mode == Mode::BenchBridge);
+    #[cfg(feature = "ui-automation")]
+    let builder = ui_automation::manage(builder, cli.mode == Mode::Automation);
+    #[cfg(feature = "automation-api")]
+    let builder = automation_api::manage(builder);
+    #[cfg(feature = "local-files")]
+    let builder = local_files::manage(builder);
+    #[cfg(feature = "offline-cache")]
+    let builder = offline_cache::manage(builder);
+    #[cfg(feature = "clipboard-history")]
+    let builder = clipboard_history::manage(builder);
+    #[cfg(feature = "read-aloud")]
+    let builder = read_aloud::manage(builder);
+    #[cfg(feature = "voice")]
+    let builder = dictation::manage(push_to_talk::manage(builder));
+    builder
+}
+
+/// The commands the pages can invoke.
+// A list, however long it gets.
+#[allow(clippy::too_many_lines)]
+fn with_commands(builder: tauri::Builder<tauri::Wry>) -> tauri::Builder<tauri::Wry> {
+    builder.invoke_handler(tauri::generate_handler![
+        commands::get_server_url,
+        commands::get_bootstrap_state,
+        commands::set_server_url,
+        onboarding::prefill_login_email,
+        onboarding::check_onboarding_server,
+        onboarding::finish_onboarding,
+        commands::get_config,
+        commands::get_locked_settings,
+        commands::set_config,
+        commands::get_config_path_cmd,
+        commands::check_server_reachable,
+        commands::open_in_browser,
+        commands::open_config_file,
+        commands::open_config_directory,
+        commands::navigate_to,
+        commands::reload_page,
+        commands::go_back,
+        commands::go_forward,
+        commands::new_window,
+        accounts::switch_account,
+        auth::start_browser_login,
+        auth::logout,
+        server_status::check_server_status,
+        connection::get_connection_quality,
+        browsing_data::clear_browsing_data,
+        lock::report_activity,
+        lock::get_unlock_options,
+        lock::unlock_app,
+        lock::set_lock_pin,
+        lock::has_lock_pin,
+        commands::reset_config,
+        config_transfer::export_config,
+        config_transfer::import_config,
+        config_backup::list_config_backups,
+        config_backup::restore_config_backup,
+        config_check::check_config_file,
+        commands::set_always_on_top,
+        commands::open_permissions,
+        consent::request_permission,
+        consent::list_permissions,
+        consent::revoke_permission,
+        mini_chat::toggle_mini_chat_cmd,
+        focus_mode::toggle_focus_mode_cmd,
+        zoom::zoom_in,
+        zoom::zoom_out,
+        zoom::zoom_reset,
+        split_view::open_split_view_cmd,
+        split_view::swap_split_panes,
+        split_view::resize_split_view,
+        server_features::report_server_settings,
+        server_features::get_server_features,
+        commands::start_drag_window,
+        commands::report_titlebar_blocked,
+        commands::toggle_menu_bar,
+        context_menu::show_context_menu,
+        spellcheck::set_spellcheck,
+        debug_log::log_from_frontend,
+        startup_timing::get_startup_report,
+        diagnostics::get_diagnostics,
+        capabilities::get_capabilities,
+        screenshot::capture_window_screenshot,
+        cheat_sheet::get_active_shortcuts,
+        shortcut_keys::press_shortcut,
+        shortcut_keys::capture_shortcut,
+        cheat_sheet::close_cheat_sheet,
+        chat_export::export_chat,
+        chat_export::export_all_chats,
+        documents::get_open_transcript,
+        draft_queue::queue_draft,
+        draft_queue::discard_draft,
+        #[cfg(feature = "bridge-bench")]
+        bridge_bench::bridge_bench_ack,
+        #[cfg(feature = "bridge-bench")]
+        bridge_bench::bridge_bench_report,
+        #[cfg(feature = "ui-automation")]
+        ui_automation::ui_action,
+        #[cfg(feature = "automation-api")]
+        automation_api::automation_token,
+        #[cfg(feature = "local-files")]
+        local_files::sync_local_files,
+        #[cfg(feature = "offline-cache")]
+        offline_cache::get_offline_chats,
+        #[cfg(feature = "offline-cache")]
+        offline_cache::get_offline_chat,
+        #[cfg(feature = "clipboard-history")]
+        clipboard_history::get_clipboard_history,
+        #[cfg(feature = "clipboard-history")]
+        clipboard_history::insert_clipboard_items,
+        #[cfg(feature = "clipboard-history")]
+        clipboard_history::clear_clipboard_history,
+        #[cfg(feature = "clipboard-history")]
+        clipboard_history::close_clipboard_history,
+        #[cfg(feature = "read-aloud")]
+        read_aloud::speak_selection,
+        #[cfg(feature = "read-aloud")]
+        read_aloud::speak_last_response,
+        #[cfg(feature = "read-aloud")]
+        read_aloud::stop_reading,
+        #[cfg(feature = "read-aloud")]
+        read_aloud::get_read_aloud_voices
+    ])
+}
 
-            if webview.app_handle().state::<ConfigState>().debug_mode {
-                debug_log::inject_console_capture(webview);
+/// Handle the flags that do their work without opening the app. Returns
+/// whether one did.
+fn run_without_window(cli: &Cli) -> bool {
+    if cli.mode == Mode::CheckConfig {
+        if !config_check::run() {
+            #[allow(clippy::exit)]
+            std::process::exit(1);
+        }
+        return true;
+    }
+
+    if let Some(query) = &cli.headless {
+        let query = Some(query.as_str()).filter(|query| !query.is_empty());
+        let all_answered = headless::run(query, cli.format).unwrap_or_else(|e| {
+            print_startup_error(&e);
+            false
+        });
+        if !all_answered {
+            #[allow(clippy::exit)]
+            std::process::exit(1);
+        }
+        return true;
+    }
+
+    #[cfg(feature = "crash-reporting")]
+    if let Some(socket) = &cli.crash_server {
+        if let Err(e) = crash_report::run_server(socket) {
+            print_startup_error(&format!("Crash server failed: {e}"));
+        }
+        return true;
+    }
+    false
+}
+
+/// Open what the app was started on, once the main window is up: a file,
+/// or what `onyx ask`, `onyx open`, `onyx share` or `onyx upload` asked for.
+fn open_at_start(
+    app: &tauri::AppHandle,
+    start: Option<companion::Outcome>,
+    document: Option<&str>,
+) {
+    match start {
+        Some(companion::Outcome::Launch(Some(route))) => window::open_route(app, &route),
+        Some(companion::Outcome::Share(share)) => {
+            if let Err(e) = share::open(app, &share) {
+                debug_log::log_backend_error(app, &format!("Failed to share: {e}"));
             }
+        }
+        Some(companion::Outcome::Upload(files)) => {
+            if let Err(e) = file_menu::upload(app, files) {
+                debug_log::log_backend_error(app, &format!("Failed to upload: {e}"));
+            }
+        }
+        Some(companion::Outcome::Launch(None) | companion::Outcome::Done(_)) | None => {}
+    }
+    if let Some(path) = document {
+        documents::open_path(app, path);
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    if cli.mode == Mode::Version {
+        print_version_info();
+        return;
+    }
+
+    if let Some(dir) = &cli.data_dir {
+        portable::use_data_dir(std::path::absolute(dir).unwrap_or_else(|_| dir.clone()));
+    }
 
-            #[cfg(target_os = "macos")]
-            window::eval_titlebar_script(webview);
+    if run_without_window(&cli) {
+        return;
+    }
+    portable::redirect_webview_storage();
+    sandbox::prefer_portals();
+    #[cfg(not(target_os = "macos"))]
+    config::migrate_legacy_layout();
+    // After the webview storage is redirected, which has to happen while
+    // there's only one thread.
+    #[cfg(feature = "crash-reporting")]
+    crash_report::install(portable::portable_data_dir());
 
-            #[cfg(target_os = "windows")]
-            alt_menu::inject_alt_menu_script(webview);
+    let profiler = StartupProfiler::new();
+    let config_state = match load_config_state(&cli, &profiler) {
+        Ok(state) => state,
+        Err(e) => {
+            print_startup_error(&e);
+            return;
+        }
+    };
+    if cli.mode == Mode::Kiosk || config_state.config().kiosk.enabled {
+        kiosk::enable();
+    }
+
+    let instance = claim_instance(&cli);
+    // A link clicked while Onyx runs (on Windows and Linux) starts a copy
+    // just to pass it on.
+    if cli
+        .link
+        .as_deref()
+        .is_some_and(|link| instance.is_conflicting() && instance.hand_off(link))
+    {
+        return;
+    }
+    // Started on an Onyx file (Windows and Linux).
+    let start_document = cli
+        .link
+        .clone()
+        .filter(|link| documents::is_document(std::path::Path::new(link)));
+    let start = match cli.command.as_ref().map(companion::run).transpose() {
+        Ok(Some(companion::Outcome::Done(output))) => {
+            print_command_output(&output);
+            return;
+        }
+        Ok(start) => start,
+        Err(e) => {
+            print_startup_error(&e);
+            #[allow(clippy::exit)]
+            std::process::exit(1);
+        }
+    };
+
+    let builder = with_plugins(tauri::Builder::default(), &cli, &instance)
+        .manage(config_state)
+        .manage(profiler)
+        .manage(instance);
+    let builder = with_commands(manage_feature_state(manage_session_state(builder), &cli))
+        .on_menu_event(|app, event| handle_menu_event(app, event.id().as_ref()))
+        .on_window_event(handle_window_event)
+        .setup(move |app| {
+            // Before the menus and windows, which are drawn in it.
+            locale::init(&app.state::<ConfigState>().config());
+            auth::init_integrated_auth(&app.state::<ConfigState>().config());
+            window::create_main_window(app.handle())?;
+            // Covers Tauri runtime start-up plus creating the main window.
+            app.state::<StartupProfiler>()
+                .milestone("runtime_and_main_window");
+            setup_app(&app.handle().clone());
+            open_at_start(app.handle(), start, start_document.as_deref());
+            Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .on_page_load(on_page_load);
+
+    // Fatal: if the Tauri runtime can't start there's nothing else to do.
+    // `tauri::generate_context!()` also expands to code that calls
+    // `std::process::exit` internally on some platforms.
+    #[allow(clippy::expect_used, clippy::exit)]
+    builder
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| handle_run_event(app, &event));
+}
+
+/// App-level events. On macOS, clicking the Dock icon while every window is
+/// hidden (see `BackgroundBehavior`) brings them back.
+// Only has a body on macOS.
+#[cfg_attr(not(target_os = "macos"), allow(clippy::missing_const_for_fn))]
+fn handle_run_event(app: &tauri::AppHandle, event: &tauri::RunEvent) {
+    // Rebuilding the main window closes the last window for a moment.
+    if let tauri::RunEvent::ExitRequested {
+        code: None, api, ..
+    } = event
+    {
+        if window::rebuilding_main_window() {
+            api.prevent_exit();
+        }
+    }
+    #[cfg(target_os = "macos")]
+    if let tauri::RunEvent::Reopen {
+        has_visible_windows: false,
+        ..
+    } = event
+    {
+        window::focus_main_window(app);
+    }
+    #[cfg(not(target_os = "macos"))]
+    let _ = app;
 }
@@ -0,0 +1,212 @@
+// Settings an organization pushes from a server of its own
+// (`managed_config_url`), so IT can change them without repackaging or
+// touching each machine. The document there is part of a config, as JSON
+// (`{"shortcuts": {"enabled": false}, "updates": {"check_automatically":
+// false}}`), and `<url>.sig` next to it holds an Ed25519 signature of it
+// (base64) by `managed_config_key`, so only what the organization signed is
+// ever applied. It's fetched at startup and every hour after.
+//
+// A new version is merged into the config once, as the organization's
+// defaults: the user can still change what it sets, until the next version
+// sets it again. To keep a setting from changing at all, lock it too (see
+// `locked_fields`); locked settings stay as they are whatever the document
+// says. The document can't set where it comes from, which settings are
+// locked, or what only the app itself keeps (`PROTECTED_KEYS`).
+//
+// The last version applied is kept as `managed_config.json` in the config
+// directory, which is how a new one is told apart.
+
+use crate::commands::{apply_config_change, check_config};
+use crate::config::{get_config_dir, http_client, keep_locked, AppConfig, ConfigState};
+use crate::debug_log::{log_backend_error, log_debug};
+use base64::Engine;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use url::Url;
+
+/// How often the document is fetched again.
+const REFRESH_INTERVAL: Duration = Duration::from_hours(1);
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Settings the document can't set, by their key at the top of the config.
+const PROTECTED_KEYS: &[&str] = &[
+    "version",
+    "managed_config_url",
+    "managed_config_key",
+    "locked_fields",
+    "headers",
+    "permissions",
+    "zoom",
+];
+
+/// Check the managed config settings in `config`.
+pub fn validate(config: &AppConfig) -> Result<(), String> {
+    let Some(url) = &config.managed_config_url else {
+        return Ok(());
+    };
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid managed config URL: {e}"))?;
+    if parsed.scheme() != "https" {
+        return Err("The managed config URL must use https".to_string());
+    }
+    let key = config
+        .managed_config_key
+        .as_deref()
+        .ok_or("A managed config URL needs a managed_config_key to check it with")?;
+    match base64::engine::general_purpose::STANDARD.decode(key.trim()) {
+        Ok(key) if key.len() == 32 => Ok(()),
+        _ => Err("managed_config_key isn't a base64 Ed25519 public key".to_string()),
+    }
+}
+
+fn cache_path() -> Option<PathBuf> {
+    Some(get_config_dir()?.join("managed_config.json"))
+}
+
+/// The document at `url`, once its signature checks out against `key`.
+async fn fetch(config: &AppConfig, url: &str, key: &str) -> Result<String, String> {
+    let client = http_client(config, FETCH_TIMEOUT)?;
+    let get = |url: String| {
+        let client = client.clone();
+        async move {
+            client
+                .get(url)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(|e| e.to_string())?
+                .text()
+                .await
+                .map_err(|e| e.to_string())
+        }
+    };
+    let document = get(url.to_string()).await?;
+    let signature = get(format!("{url}.sig")).await?;
+    crate::assets::verify_signature(key, document.as_bytes(), &signature)?;
+    Ok(document)
+}
+
+/// Refuse a document that isn't an object of settings or that sets one of
+/// `PROTECTED_KEYS`.
+fn check_document(document: &Value) -> Result<&Map<String, Value>, String> {
+    let settings = document
+        .as_object()
+        .ok_or("The managed config isn't a JSON object")?;
+    PROTECTED_KEYS
+        .iter()
+        .find(|key| settings.contains_key(**key))
+        .map_or(Ok(settings), |key| {
+            Err(format!("The managed config can't set {key}"))
+        })
+}
+
+/// Lay `from` over `into`: tables are merged key by key, anything else is
+/// replaced.
+pub fn merge(into: &mut Value, from: &Value) {
+    match (into, from) {
+        (Value::Object(into), Value::Object(from)) => {
+            for (key, value) in from {
+                merge(into.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (into, from) => *into = from.clone(),
+    }
+}
+
+/// `config` with `document` merged in, checked as the settings window's
+/// would be.
+fn merged(config: &AppConfig, document: &str) -> Result<AppConfig, String> {
+    let document: Value =
+        serde_json::from_str(document).map_err(|e| format!("Invalid managed config: {e}"))?;
+    let settings = check_document(&document)?;
+    let mut value = serde_json::to_value(config).map_err(|e| e.to_string())?;
+    merge(&mut value, &Value::Object(settings.clone()));
+    let mut merged: AppConfig =
+        serde_json::from_value(value).map_err(|e| format!("Invalid managed config: {e}"))?;
+    check_config(&mut merged)?;
+    Ok(merged)
+}
+
+/// Fetch the document and apply it if it's new.
+async fn refresh(app: &AppHandle) -> Result<(), String> {
+    let state = app.state::<ConfigState>();
+    let config = state.config();
+    let (Some(url), Some(key)) = (&config.managed_config_url, &config.managed_config_key) else {
+        return Ok(());
+    };
+    let document = fetch(&config, url, key).await?;
+    let cache = cache_path();
+    if cache
+        .as_ref()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .is_some_and(|applied| applied == document)
+    {
+        return Ok(());
+    }
+
+    let previous = state.config();
+    let mut updated = merged(&previous, &document)?;
+    let saved = state.update_and_persist(|c| {
+        keep_locked(&mut updated, c);
+        *c = updated;
+    })?;
+    if let Some(path) = cache {
+        fs::write(path, &document).map_err(|e| e.to_string())?;
+    }
+    apply_config_change(app, &previous, &saved);
+    log_debug(app, &format!("Applied the managed config from {url}"));
+    Ok(())
+}
+
+/// Keep the config up to date with the managed config for as long as the
+/// app runs. Only for the copy that holds the data directory.
+pub fn start_refreshing(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if let Err(e) = refresh(&app).await {
+                log_backend_error(&app, &format!("Failed to apply the managed config: {e}"));
+            }
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn documents_merge_into_the_config() {
+        let mut config = json!({
+            "server_url": "https://onyx.example.com",
+            "shortcuts": {"enabled": true, "new_chat": "Ctrl+N"},
+        });
+        let document = json!({
+            "shortcuts": {"enabled": false},
+            "spellcheck": {"languages": ["de_DE"]},
+        });
+        check_document(&document).unwrap();
+        merge(&mut config, &document);
+        assert_eq!(
+            config,
+            json!({
+                "server_url": "https://onyx.example.com",
+                "shortcuts": {"enabled": false, "new_chat": "Ctrl+N"},
+                "spellcheck": {"languages": ["de_DE"]},
+            })
+        );
+    }
+
+    #[test]
+    fn documents_cant_set_protected_keys() {
+        assert!(check_document(&json!(["server_url"])).is_err());
+        assert!(check_document(&json!({"locked_fields": []})).is_err());
+        assert!(check_document(&json!({"managed_config_url": "https://evil.example"})).is_err());
+    }
+}
@@ -1,11 +1,18 @@
-use crate::config::ConfigState;
+use crate::accounts::AccountMenus;
+use crate::config::{ConfigState, ShortcutSettings};
 use crate::debug_log::{log_backend_error, MENU_OPEN_DEBUG_LOG_ID, MENU_TOGGLE_DEVTOOLS_ID};
+use crate::locale::{text, Text};
+use crate::shortcuts::{
+    bindable, CHEAT_SHEET_ACCELERATOR, GO_BACK_ACCELERATOR, GO_FORWARD_ACCELERATOR,
+    RELOAD_ACCELERATOR, ZOOM_IN_ACCELERATOR, ZOOM_OUT_ACCELERATOR, ZOOM_RESET_ACCELERATOR,
+};
 use crate::window::{focus_main_window, open_chat_window};
 use tauri::image::Image;
 #[cfg(not(target_os = "macos"))]
 use tauri::menu::AboutMetadataBuilder;
 use tauri::menu::{
-    CheckMenuItem, Menu, MenuBuilder, MenuItem, PredefinedMenuItem, SubmenuBuilder, HELP_SUBMENU_ID,
+    CheckMenuItem, Menu, MenuBuilder, MenuItem, PredefinedMenuItem, Submenu, SubmenuBuilder,
+    HELP_SUBMENU_ID,
 };
 use tauri::tray::{TrayIconBuilder, TrayIconEvent};
 use tauri::{AppHandle, Manager, Wry};
@@ -26,7 +33,31 @@ pub const MENU_HIDE_DECORATIONS_ID: &str = "hide_window_decorations";
 pub const MENU_NEW_CHAT_ID: &str = "new_chat";
 pub const MENU_NEW_WINDOW_ID: &str = "new_window";
 pub const MENU_OPEN_SETTINGS_ID: &str = "open_settings";
+pub const MENU_BROWSER_SIGN_IN_ID: &str = "browser_sign_in";
+pub const MENU_SIGN_OUT_ID: &str = "sign_out";
+pub const MENU_EXPORT_CHAT_ID: &str = "export_chat";
+pub const MENU_EXPORT_ALL_CHATS_ID: &str = "export_all_chats";
 pub const MENU_OPEN_DOCS_ID: &str = "open_docs";
+pub const MENU_REPORT_ISSUE_ID: &str = "report_issue";
+pub const MENU_CHEAT_SHEET_ID: &str = "cheat_sheet";
+pub const MENU_ALWAYS_ON_TOP_ID: &str = "always_on_top";
+pub const MENU_MINI_CHAT_ID: &str = "mini_chat";
+pub const MENU_FOCUS_MODE_ID: &str = "focus_mode";
+pub const MENU_INSERT_SNIPPET_ID: &str = "insert_snippet";
+pub const MENU_DICTATION_ID: &str = "dictation";
+#[cfg(feature = "clipboard-history")]
+pub const MENU_CLIPBOARD_HISTORY_ID: &str = "clipboard_history";
+#[cfg(feature = "read-aloud")]
+pub const MENU_READ_SELECTION_ID: &str = "read_selection";
+#[cfg(feature = "read-aloud")]
+pub const MENU_READ_LAST_RESPONSE_ID: &str = "read_last_response";
+pub const MENU_SPLIT_VIEW_ID: &str = "split_view";
+pub const MENU_ZOOM_IN_ID: &str = "zoom_in";
+pub const MENU_ZOOM_OUT_ID: &str = "zoom_out";
+pub const MENU_ZOOM_RESET_ID: &str = "zoom_reset";
+pub const MENU_RELOAD_ID: &str = "reload";
+pub const MENU_GO_BACK_ID: &str = "go_back";
+pub const MENU_GO_FORWARD_ID: &str = "go_forward";
 
 /// Handles to the checkable menu items, populated once in `setup_app_menu`.
 /// Toggling reaches for these directly instead of re-walking the whole menu
@@ -39,23 +70,80 @@ pub struct MenuHandles {
     pub hide_decorations: CheckMenuItem<Wry>,
 }
 
-fn build_file_menu(app: &AppHandle, menu: &Menu<Wry>) -> tauri::Result<()> {
-    let new_chat_item =
-        MenuItem::with_id(app, MENU_NEW_CHAT_ID, "New Chat", true, Some("CmdOrCtrl+N"))?;
+/// Picks a menu item's accelerator out of the shortcut settings.
+type ShortcutOf = fn(&ShortcutSettings) -> &String;
+
+/// Handles to the menu items with user-configurable accelerators, so a
+/// shortcut changed in settings can be applied without rebuilding the menu.
+pub struct ShortcutMenuItems(Vec<(MenuItem<Wry>, ShortcutOf)>);
+
+/// The "Always on Top" checkbox. Always-on-top is per window, so this shows
+/// the state of whichever window was focused last.
+pub struct AlwaysOnTopMenuItem(CheckMenuItem<Wry>);
+
+/// Edit > Start Dictation, which says Stop Dictation while dictating (see
+/// `dictation.rs`).
+#[cfg(feature = "voice")]
+pub struct DictationMenuItem(MenuItem<Wry>);
+
+/// The tray menu's (disabled) line saying how the connection is (see
+/// `connection.rs`).
+pub struct ConnectionMenuItem(MenuItem<Wry>);
+
+fn build_file_menu(
+    app: &AppHandle,
+    menu: &Menu<Wry>,
+    shortcut_items: &mut Vec<(MenuItem<Wry>, ShortcutOf)>,
+) -> tauri::Result<()> {
+    let shortcuts = bindable(&app.state::<ConfigState>().config().shortcuts);
+
+    let new_chat_item = MenuItem::with_id(
+        app,
+        MENU_NEW_CHAT_ID,
+        text(Text::NewChat),
+        true,
+        Some(&shortcuts.new_chat),
+    )?;
     let new_window_item = MenuItem::with_id(
         app,
         MENU_NEW_WINDOW_ID,
-        "New Window",
+        text(Text::NewWindow),
         true,
-        Some("CmdOrCtrl+Shift+N"),
+        Some(&shortcuts.new_window),
     )?;
     let settings_item = MenuItem::with_id(
         app,
         MENU_OPEN_SETTINGS_ID,
-        "Settings...",
+        text(Text::Settings),
         true,
-        Some("CmdOrCtrl+Comma"),
+        Some(&shortcuts.open_settings),
     )?;
+    let browser_sign_in_item = MenuItem::with_id(
+        app,
+        MENU_BROWSER_SIGN_IN_ID,
+        "Sign In with Browser...",
+        true,
+        None::<&str>,
+    )?;
+    let sign_out_item = MenuItem::with_id(app, MENU_SIGN_OUT_ID, "Sign Out", true, None::<&str>)?;
+    let export_chat_item = MenuItem::with_id(
+        app,
+        MENU_EXPORT_CHAT_ID,
+        "Export Chat...",
+        true,
+        None::<&str>,
+    )?;
+    let export_all_chats_item = MenuItem::with_id(
+        app,
+        MENU_EXPORT_ALL_CHATS_ID,
+        "Export All Chats...",
+        true,
+        None::<&str>,
+    )?;
+
+    shortcut_items.push((new_chat_item.clone(), |s| &s.new_chat));
+    shortcut_items.push((new_window_item.clone(), |s| &s.new_window));
+    shortcut_items.push((settings_item.clone(), |s| &s.open_settings));
 
     if let Some(file_menu) = menu
         .items()?
@@ -63,13 +151,28 @@ fn build_file_menu(app: &AppHandle, menu: &Menu<Wry>) -> tauri::Result<()> {
         .filter_map(|item| item.as_submenu().cloned())
         .find(|submenu| submenu.text().ok().as_deref() == Some("File"))
     {
-        file_menu.insert_items(&[&new_chat_item, &new_window_item, &settings_item], 0)?;
+        file_menu.insert_items(
+            &[
+                &new_chat_item,
+                &new_window_item,
+                &export_chat_item,
+                &export_all_chats_item,
+                &settings_item,
+                &browser_sign_in_item,
+                &sign_out_item,
+            ],
+            0,
+        )?;
     } else {
         let file_menu = SubmenuBuilder::new(app, "File")
             .items(&[
                 &new_chat_item,
                 &new_window_item,
+                &export_chat_item,
+                &export_all_chats_item,
                 &settings_item,
+                &browser_sign_in_item,
+                &sign_out_item,
                 &PredefinedMenuItem::close_window(app, None)?,
             ])
             .build()?;
@@ -139,6 +242,257 @@ fn build_window_menu(app: &AppHandle, menu: &Menu<Wry>) -> tauri::Result<()> {
     Ok(())
 }
 
+/// Add the window-mode items ("Always on Top", "Mini Chat", "Focus Mode",
+/// "New Split View") to the Window
+/// menu, which exists on every platform by now (`Menu::default` provides it
+/// on macOS, `build_window_menu` elsewhere).
+fn build_window_mode_items(
+    app: &AppHandle,
+    menu: &Menu<Wry>,
+    shortcut_items: &mut Vec<(MenuItem<Wry>, ShortcutOf)>,
+) -> tauri::Result<()> {
+    let config = app.state::<ConfigState>().config();
+    let shortcuts = bindable(&config.shortcuts);
+    let always_on_top_item = CheckMenuItem::with_id(
+        app,
+        MENU_ALWAYS_ON_TOP_ID,
+        "Always on Top",
+        true,
+        config.always_on_top,
+        Some(&shortcuts.always_on_top),
+    )?;
+    app.manage(AlwaysOnTopMenuItem(always_on_top_item.clone()));
+
+    let mini_chat_item = MenuItem::with_id(
+        app,
+        MENU_MINI_CHAT_ID,
+        "Mini Chat",
+        true,
+        Some(&shortcuts.mini_chat),
+    )?;
+    shortcut_items.push((mini_chat_item.clone(), |s| &s.mini_chat));
+
+    let focus_mode_item = MenuItem::with_id(
+        app,
+        MENU_FOCUS_MODE_ID,
+        "Focus Mode",
+        true,
+        Some(&shortcuts.focus_mode),
+    )?;
+    shortcut_items.push((focus_mode_item.clone(), |s| &s.focus_mode));
+
+    let split_view_item = MenuItem::with_id(
+        app,
+        MENU_SPLIT_VIEW_ID,
+        "New Split View",
+        true,
+        None::<&str>,
+    )?;
+    let accounts_menu = account_menu(app)?;
+
+    if let Some(window_menu) = menu
+        .items()?
+        .into_iter()
+        .filter_map(|item| item.as_submenu().cloned())
+        .find(|submenu| submenu.text().ok().as_deref() == Some("Window"))
+    {
+        window_menu.append_items(&[
+            &always_on_top_item,
+            &mini_chat_item,
+            &focus_mode_item,
+            &split_view_item,
+            &accounts_menu,
+        ])?;
+    }
+
+    Ok(())
+}
+
+/// Add "Insert Snippet...", "Clipboard History...", "Start Dictation" and
+/// the Read Aloud items to the Edit menu, which `Menu::default` provides on every platform.
+fn build_edit_items(
+    app: &AppHandle,
+    menu: &Menu<Wry>,
+    shortcut_items: &mut Vec<(MenuItem<Wry>, ShortcutOf)>,
+) -> tauri::Result<()> {
+    let shortcuts = bindable(&app.state::<ConfigState>().config().shortcuts);
+    let insert_snippet_item = MenuItem::with_id(
+        app,
+        MENU_INSERT_SNIPPET_ID,
+        "Insert Snippet...",
+        true,
+        Some(&shortcuts.insert_snippet),
+    )?;
+    shortcut_items.push((insert_snippet_item.clone(), |s| &s.insert_snippet));
+    #[cfg(feature = "voice")]
+    let dictation_item = MenuItem::with_id(
+        app,
+        MENU_DICTATION_ID,
+        "Start Dictation",
+        true,
+        Some(&shortcuts.dictation),
+    )?;
+    #[cfg(feature = "voice")]
+    {
+        shortcut_items.push((dictation_item.clone(), |s| &s.dictation));
+        app.manage(DictationMenuItem(dictation_item.clone()));
+    }
+
+    if let Some(edit_menu) = menu
+        .items()?
+        .into_iter()
+        .filter_map(|item| item.as_submenu().cloned())
+        .find(|submenu| submenu.text().ok().as_deref() == Some("Edit"))
+    {
+        edit_menu.append_items(&[&PredefinedMenuItem::separator(app)?, &insert_snippet_item])?;
+        #[cfg(feature = "clipboard-history")]
+        edit_menu.append(&MenuItem::with_id(
+            app,
+            MENU_CLIPBOARD_HISTORY_ID,
+            "Clipboard History...",
+            true,
+            None::<&str>,
+        )?)?;
+        #[cfg(feature = "voice")]
+        edit_menu.append(&dictation_item)?;
+        #[cfg(feature = "read-aloud")]
+        edit_menu.append_items(&[
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(
+                app,
+                MENU_READ_SELECTION_ID,
+                "Read Selection Aloud",
+                true,
+                None::<&str>,
+            )?,
+            &MenuItem::with_id(
+                app,
+                MENU_READ_LAST_RESPONSE_ID,
+                "Read Last Response Aloud",
+                true,
+                None::<&str>,
+            )?,
+        ])?;
+    }
+
+    Ok(())
+}
+
+/// An empty Switch Account submenu, filled in by `sync_account_menus`.
+fn account_menu(app: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+    let submenu = SubmenuBuilder::new(app, "Switch Account").build()?;
+    app.state::<AccountMenus>().add(submenu.clone());
+    Ok(submenu)
+}
+
+pub fn sync_always_on_top_checkbox(app: &AppHandle, on_top: bool) {
+    if let Some(item) = app.try_state::<AlwaysOnTopMenuItem>() {
+        if let Err(e) = item.0.set_checked(on_top) {
+            log_backend_error(app, &format!("Failed to sync always-on-top checkbox: {e}"));
+        }
+    }
+}
+
+/// Say whether dictation is under way on Edit > Start Dictation.
+#[cfg(feature = "voice")]
+pub fn sync_dictation_item(app: &AppHandle, dictating: bool) {
+    if let Some(item) = app.try_state::<DictationMenuItem>() {
+        let label = if dictating {
+            "Stop Dictation"
+        } else {
+            "Start Dictation"
+        };
+        if let Err(e) = item.0.set_text(label) {
+            log_backend_error(
+                app,
+                &format!("Failed to update the dictation menu item: {e}"),
+            );
+        }
+    }
+}
+
+/// Add the navigation and zoom items to the View menu (which
+/// `Menu::default` only provides on macOS), creating one before "Window"
+/// elsewhere. These are menu accelerators rather than global shortcuts, so
+/// they only fire while an Onyx window has focus.
+fn build_view_menu(app: &AppHandle, menu: &Menu<Wry>) -> tauri::Result<()> {
+    let reload_item = MenuItem::with_id(
+        app,
+        MENU_RELOAD_ID,
+        "Reload",
+        true,
+        Some(RELOAD_ACCELERATOR),
+    )?;
+    let back_item = MenuItem::with_id(
+        app,
+        MENU_GO_BACK_ID,
+        "Back",
+        true,
+        Some(GO_BACK_ACCELERATOR),
+    )?;
+    let forward_item = MenuItem::with_id(
+        app,
+        MENU_GO_FORWARD_ID,
+        "Forward",
+        true,
+        Some(GO_FORWARD_ACCELERATOR),
+    )?;
+    let zoom_in_item = MenuItem::with_id(
+        app,
+        MENU_ZOOM_IN_ID,
+        "Zoom In",
+        true,
+        Some(ZOOM_IN_ACCELERATOR),
+    )?;
+    let zoom_out_item = MenuItem::with_id(
+        app,
+        MENU_ZOOM_OUT_ID,
+        "Zoom Out",
+        true,
+        Some(ZOOM_OUT_ACCELERATOR),
+    )?;
+    let zoom_reset_item = MenuItem::with_id(
+        app,
+        MENU_ZOOM_RESET_ID,
+        "Actual Size",
+        true,
+        Some(ZOOM_RESET_ACCELERATOR),
+    )?;
+
+    let items = menu.items()?;
+    if let Some(view_menu) = items
+        .iter()
+        .filter_map(|item| item.as_submenu().cloned())
+        .find(|submenu| submenu.text().ok().as_deref() == Some("View"))
+    {
+        view_menu.prepend_items(&[
+            &reload_item,
+            &back_item,
+            &forward_item,
+            &PredefinedMenuItem::separator(app)?,
+            &zoom_in_item,
+            &zoom_out_item,
+            &zoom_reset_item,
+            &PredefinedMenuItem::separator(app)?,
+        ])?;
+    } else {
+        let view_menu = SubmenuBuilder::new(app, "View")
+            .items(&[&reload_item, &back_item, &forward_item])
+            .separator()
+            .items(&[&zoom_in_item, &zoom_out_item, &zoom_reset_item])
+            .build()?;
+        let window_idx = items
+            .iter()
+            .position(|item| {
+                item.as_submenu().and_then(|s| s.text().ok()).as_deref() == Some("Window")
+            })
+            .unwrap_or(items.len());
+        menu.insert(&view_menu, window_idx)?;
+    }
+
+    Ok(())
+}
+
 fn build_help_menu(app: &AppHandle, menu: &Menu<Wry>) -> tauri::Result<()> {
     let docs_item = MenuItem::with_id(
         app,
@@ -147,6 +501,20 @@ fn build_help_menu(app: &AppHandle, menu: &Menu<Wry>) -> tauri::Result<()> {
         true,
         None::<&str>,
     )?;
+    let cheat_sheet_item = MenuItem::with_id(
+        app,
+        MENU_CHEAT_SHEET_ID,
+        "Keyboard Shortcuts",
+        true,
+        Some(CHEAT_SHEET_ACCELERATOR),
+    )?;
+    let report_item = MenuItem::with_id(
+        app,
+        MENU_REPORT_ISSUE_ID,
+        "Report an Issue\u{2026}",
+        true,
+        None::<&str>,
+    )?;
 
     if let Some(help_menu) = menu
         .get(HELP_SUBMENU_ID)
@@ -172,10 +540,10 @@ fn build_help_menu(app: &AppHandle, menu: &Menu<Wry>) -> tauri::Result<()> {
             help_menu.remove_at(0)?;
             help_menu.insert(&about_item, 0)?;
         }
-        help_menu.append(&docs_item)?;
+        help_menu.append_items(&[&docs_item, &cheat_sheet_item, &report_item])?;
     } else {
         let help_menu = SubmenuBuilder::with_id(app, HELP_SUBMENU_ID, "Help")
-            .item(&docs_item)
+            .items(&[&docs_item, &cheat_sheet_item, &report_item])
             .build()?;
         menu.append(&help_menu)?;
     }
@@ -215,11 +583,16 @@ fn build_debug_menu(app: &AppHandle, menu: &Menu<Wry>) -> tauri::Result<()> {
 pub fn setup_app_menu(app: &AppHandle) -> tauri::Result<()> {
     let menu = app.menu().unwrap_or(Menu::default(app)?);
 
-    build_file_menu(app, &menu)?;
+    let mut shortcut_items = Vec::new();
+    build_file_menu(app, &menu, &mut shortcut_items)?;
+    build_edit_items(app, &menu, &mut shortcut_items)?;
     #[cfg(not(target_os = "macos"))]
     build_window_menu(app, &menu)?;
+    build_view_menu(app, &menu)?;
+    build_window_mode_items(app, &menu, &mut shortcut_items)?;
     build_help_menu(app, &menu)?;
     build_debug_menu(app, &menu)?;
+    app.manage(ShortcutMenuItems(shortcut_items));
 
     app.set_menu(menu)?;
     Ok(())
@@ -287,30 +660,118 @@ pub fn handle_decorations_toggle(app: &AppHandle) {
     }
 }
 
+/// Bring the menu in line with the current config after it was changed as a
+/// whole (from the settings window): accelerators and checkbox states. Any
+/// shortcut that can't be bound is reported.
+pub fn sync_menu_with_config(app: &AppHandle) {
+    let config = app.state::<ConfigState>().config();
+    let shortcuts = bindable(&config.shortcuts);
+
+    if let Some(items) = app.try_state::<ShortcutMenuItems>() {
+        for (item, shortcut_of) in &items.0 {
+            let accelerator = shortcut_of(&shortcuts);
+            if let Err(e) = item.set_accelerator(Some(accelerator)) {
+                log_backend_error(
+                    app,
+                    &format!("Failed to apply shortcut \"{accelerator}\": {e}"),
+                );
+            }
+        }
+    }
+    if let Some(item) = app.try_state::<AlwaysOnTopMenuItem>() {
+        let accelerator = &shortcuts.always_on_top;
+        if let Err(e) = item.0.set_accelerator(Some(accelerator)) {
+            log_backend_error(
+                app,
+                &format!("Failed to apply shortcut \"{accelerator}\": {e}"),
+            );
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    if let Some(handles) = app.try_state::<MenuHandles>() {
+        if let Err(e) = handles.show_menu_bar.set_checked(config.show_menu_bar) {
+            log_backend_error(app, &format!("Failed to sync menu-bar checkbox: {e}"));
+        }
+        #[cfg(target_os = "linux")]
+        if let Err(e) = handles
+            .hide_decorations
+            .set_checked(config.hide_window_decorations)
+        {
+            log_backend_error(app, &format!("Failed to sync decorations checkbox: {e}"));
+        }
+    }
+
+    crate::shortcuts::report_problems(app);
+}
+
+/// Show or hide the tray icon (focus mode hides it, see `focus_mode.rs`).
+pub fn set_tray_visible(app: &AppHandle, visible: bool) {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        if let Err(e) = tray.set_visible(visible) {
+            log_backend_error(app, &format!("Failed to show or hide the tray icon: {e}"));
+        }
+    }
+}
+
+/// Show `status` (as in "Connection: good (42 ms)") in the tray icon's
+/// tooltip and menu.
+pub fn show_connection(app: &AppHandle, status: &str) {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        if let Err(e) = tray.set_tooltip(Some(format!("Onyx \u{2014} {status}"))) {
+            log_backend_error(app, &format!("Failed to update tray tooltip: {e}"));
+        }
+    }
+    if let Some(item) = app.try_state::<ConnectionMenuItem>() {
+        if let Err(e) = item.0.set_text(status) {
+            log_backend_error(app, &format!("Failed to update connection status: {e}"));
+        }
+    }
+}
+
 fn build_tray_menu(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
-    let open_app = MenuItem::with_id(app, TRAY_MENU_OPEN_APP_ID, "Open Onyx", true, None::<&str>)?;
+    let open_app = MenuItem::with_id(
+        app,
+        TRAY_MENU_OPEN_APP_ID,
+        text(Text::OpenApp),
+        true,
+        None::<&str>,
+    )?;
     let open_chat = MenuItem::with_id(
         app,
         TRAY_MENU_OPEN_CHAT_ID,
-        "Open Chat Window",
+        text(Text::OpenChatWindow),
         true,
         None::<&str>,
     )?;
     let show_in_menu_bar = CheckMenuItem::with_id(
         app,
         TRAY_MENU_SHOW_IN_BAR_ID,
-        "Show in Menu Bar",
+        text(Text::ShowInMenuBar),
         true,
         true,
         None::<&str>,
     )?;
     // Keep it visible/pinned without letting users uncheck (avoids orphaning the tray)
     show_in_menu_bar.set_enabled(false)?;
-    let quit = PredefinedMenuItem::quit(app, Some("Quit Onyx"))?;
+    let quit = PredefinedMenuItem::quit(app, Some(text(Text::QuitApp)))?;
+    let connection = MenuItem::new(app, "Connection: checking...", false, None::<&str>)?;
+    app.manage(ConnectionMenuItem(connection.clone()));
 
-    MenuBuilder::new(app)
+    let accounts_menu = account_menu(app)?;
+
+    let builder = MenuBuilder::new(app)
         .item(&open_app)
         .item(&open_chat)
+        .item(&accounts_menu)
+        .separator()
+        .item(&connection)
+        .item(&crate::do_not_disturb::tray_menu(app)?);
+    #[cfg(feature = "local-files")]
+    let builder = builder.item(&crate::local_files::activity_menu(app)?);
+    #[cfg(feature = "read-aloud")]
+    let builder = builder.item(&crate::read_aloud::tray_menu(app)?);
+    builder
         .separator()
         .item(&show_in_menu_bar)
         .separator()
@@ -335,6 +796,21 @@ fn handle_tray_menu_event(app: &AppHandle, id: &str) {
             app.exit(0);
         }
         TRAY_MENU_SHOW_IN_BAR_ID => {}
+        crate::do_not_disturb::TOGGLE_ID => crate::do_not_disturb::toggle(app),
+        crate::do_not_disturb::CLEAR_ID => crate::do_not_disturb::clear(app),
+        id if crate::do_not_disturb::is_missed_item(id) => {
+            crate::do_not_disturb::open_missed(app, id);
+        }
+        #[cfg(feature = "local-files")]
+        crate::local_files::SYNC_NOW_ID => crate::local_files::sync_now(app),
+        #[cfg(feature = "read-aloud")]
+        crate::read_aloud::READ_LAST_RESPONSE_ID => {
+            crate::read_aloud::read_focused_last_response(app);
+        }
+        #[cfg(feature = "read-aloud")]
+        crate::read_aloud::PAUSE_ID => crate::read_aloud::toggle_pause(app),
+        #[cfg(feature = "read-aloud")]
+        crate::read_aloud::STOP_ID => crate::read_aloud::stop(app),
         _ => {}
     }
 }
@@ -0,0 +1,242 @@
+// Compact "mini chat" mode: shrinks a window into a slim, frameless,
+// always-on-top view of the chat page parked in the corner of its screen,
+// and expands it back to exactly where it was. The window's previous
+// geometry and chrome are remembered per window label until it's expanded.
+
+use crate::config::ConfigState;
+use crate::debug_log::log_backend_error;
+use crate::focus_mode::FocusMode;
+use crate::route::Route;
+use crate::window::{MINI_CHAT_WINDOW, STANDARD_WINDOW};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, LogicalSize, Manager, PhysicalPosition, PhysicalSize, Size, WebviewWindow};
+
+/// Gap between the mini window and the screen's work-area edges, in logical
+/// pixels.
+const SCREEN_MARGIN: f64 = 16.0;
+
+/// What a window looked like before it went mini.
+struct RestoreState {
+    size: PhysicalSize<u32>,
+    position: PhysicalPosition<i32>,
+    decorated: bool,
+    always_on_top: bool,
+    menu_visible: bool,
+}
+
+/// Managed state: windows currently in mini mode, by label.
+#[derive(Default)]
+pub struct MiniChatState(Mutex<HashMap<String, RestoreState>>);
+
+impl MiniChatState {
+    pub fn is_mini(&self, label: &str) -> bool {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .contains_key(label)
+    }
+}
+
+/// Top-left corner that puts a `size` window in the bottom-right corner of
+/// the work area, `margin` away from its edges (all physical pixels).
+fn bottom_right_position(
+    area_position: (i32, i32),
+    area_size: (u32, u32),
+    size: (u32, u32),
+    margin: u32,
+) -> (i32, i32) {
+    let offset = |area: u32, window: u32| {
+        i32::try_from(area.saturating_sub(window).saturating_sub(margin)).unwrap_or(0)
+    };
+    (
+        area_position.0 + offset(area_size.0, size.0),
+        area_position.1 + offset(area_size.1, size.1),
+    )
+}
+
+fn log_err<E: std::fmt::Display>(app: &AppHandle, what: &str, result: Result<(), E>) {
+    if let Err(e) = result {
+        log_backend_error(app, &format!("Mini chat: failed to {what}: {e}"));
+    }
+}
+
+/// Shrink `window` into the mini chat view. No-op if it's already mini.
+pub fn enter_mini_chat(app: &AppHandle, window: &WebviewWindow) -> Result<(), String> {
+    let state = app.state::<MiniChatState>();
+    if state.is_mini(window.label()) {
+        return Ok(());
+    }
+    if app.state::<FocusMode>().is_focused(window.label()) {
+        return Err("Turn off focus mode first".to_string());
+    }
+
+    let restore = RestoreState {
+        size: window.inner_size().map_err(|e| e.to_string())?,
+        position: window.outer_position().map_err(|e| e.to_string())?,
+        decorated: window.is_decorated().unwrap_or(true),
+        always_on_top: window.is_always_on_top().unwrap_or(false),
+        menu_visible: window.is_menu_visible().unwrap_or(true),
+    };
+    state
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .insert(window.label().to_string(), restore);
+
+    let mini_size = LogicalSize::new(MINI_CHAT_WINDOW.width, MINI_CHAT_WINDOW.height);
+    log_err(
+        app,
+        "lift the minimum size",
+        window.set_min_size(Some(Size::Logical(LogicalSize::new(
+            MINI_CHAT_WINDOW.min_width,
+            MINI_CHAT_WINDOW.min_height,
+        )))),
+    );
+    log_err(app, "resize window", window.set_size(mini_size));
+    log_err(app, "remove decorations", window.set_decorations(false));
+    log_err(app, "keep window on top", window.set_always_on_top(true));
+    if !cfg!(target_os = "macos") {
+        log_err(app, "hide menu bar", window.hide_menu());
+    }
+
+    if let Ok(Some(monitor)) = window.current_monitor() {
+        let area = monitor.work_area();
+        let scale = monitor.scale_factor();
+        let physical = mini_size.to_physical::<u32>(scale);
+        let (x, y) = bottom_right_position(
+            (area.position.x, area.position.y),
+            (area.size.width, area.size.height),
+            (physical.width, physical.height),
+            LogicalSize::new(SCREEN_MARGIN, 0.0)
+                .to_physical::<u32>(scale)
+                .width,
+        );
+        log_err(
+            app,
+            "move window",
+            window.set_position(PhysicalPosition::new(x, y)),
+        );
+    }
+    crate::magnifier::bring_into_view(app, window, crate::magnifier::Anchor::BottomRight);
+
+    // Stay on the window's own server (it may be a profile window).
+    let target = window
+        .url()
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .or_else(|| app.state::<ConfigState>().config().server_url.parse().ok());
+    if let Some(server) = target {
+        log_err(
+            app,
+            "open chat",
+            window.navigate(Route::new_chat().url(&server)),
+        );
+    }
+
+    Ok(())
+}
+
+/// Expand `window` back to how it was before `enter_mini_chat`. The page is
+/// left alone, so the conversation started in the mini view carries on.
+pub fn exit_mini_chat(app: &AppHandle, window: &WebviewWindow) {
+    let restore = app
+        .state::<MiniChatState>()
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .remove(window.label());
+    let Some(restore) = restore else {
+        return;
+    };
+
+    log_err(
+        app,
+        "restore decorations",
+        window.set_decorations(restore.decorated),
+    );
+    log_err(
+        app,
+        "restore always-on-top",
+        window.set_always_on_top(restore.always_on_top),
+    );
+    if !cfg!(target_os = "macos") && restore.menu_visible {
+        log_err(app, "restore menu bar", window.show_menu());
+    }
+    log_err(app, "restore size", window.set_size(restore.size));
+    log_err(
+        app,
+        "restore position",
+        window.set_position(restore.position),
+    );
+    log_err(
+        app,
+        "restore minimum size",
+        window.set_min_size(Some(Size::Logical(LogicalSize::new(
+            STANDARD_WINDOW.min_width,
+            STANDARD_WINDOW.min_height,
+        )))),
+    );
+}
+
+/// Drop saved state for a window that was closed while mini.
+pub fn forget_window(app: &AppHandle, label: &str) {
+    app.state::<MiniChatState>()
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .remove(label);
+}
+
+pub fn toggle_mini_chat(app: &AppHandle, window: &WebviewWindow) -> Result<(), String> {
+    if app.state::<MiniChatState>().is_mini(window.label()) {
+        exit_mini_chat(app, window);
+        Ok(())
+    } else {
+        enter_mini_chat(app, window)
+    }
+}
+
+/// Menu/shortcut entry point: toggle the focused window, or the main one.
+pub fn toggle_focused_mini_chat(app: &AppHandle) {
+    let windows = app.webview_windows();
+    let window = windows
+        .values()
+        .find(|window| window.is_focused().unwrap_or(false))
+        .or_else(|| windows.get("main"));
+    if let Some(window) = window {
+        if let Err(e) = toggle_mini_chat(app, window) {
+            log_backend_error(app, &format!("Failed to toggle mini chat: {e}"));
+        }
+    }
+}
+
+/// Shrink the calling window into mini chat, or expand it back
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn toggle_mini_chat_cmd(window: WebviewWindow) -> Result<(), String> {
+    toggle_mini_chat(window.app_handle(), &window)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::bottom_right_position;
+
+    #[test]
+    fn mini_window_is_parked_bottom_right() {
+        assert_eq!(
+            bottom_right_position((0, 0), (1920, 1040), (380, 560), 16),
+            (1524, 464)
+        );
+        // Secondary monitor to the left of the primary one.
+        assert_eq!(
+            bottom_right_position((-1280, 0), (1280, 1000), (380, 560), 16),
+            (-396, 424)
+        );
+        // A window larger than the screen is pinned to the top-left.
+        assert_eq!(
+            bottom_right_position((0, 0), (300, 300), (380, 560), 16),
+            (0, 0)
+        );
+    }
+}
@@ -0,0 +1,230 @@
+// Embedded mock Onyx server for `--demo` runs and end-to-end tests: static
+// fixtures (`fixtures/mock_server/`) behind a tiny blocking HTTP/1.1 server
+// on loopback. Only what the desktop itself touches is faked -- `/api/health`,
+// `/api/version`, `/api/me`, `/api/settings`, `/api/notifications`, the
+// chat history, and a streamed chat reply -- and every other path gets a
+// demo page, so navigation has somewhere to go. Pages set a dummy session
+// cookie, like a login would, for features that borrow the webview's
+// session. One thread per connection and every connection is closed after
+// one response; this never listens beyond 127.0.0.1.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+const INDEX_PAGE: &str = include_str!("fixtures/mock_server/index.html");
+const ME: &str = include_str!("fixtures/mock_server/me.json");
+const SETTINGS: &str = include_str!("fixtures/mock_server/settings.json");
+const NOTIFICATIONS: &str = include_str!("fixtures/mock_server/notifications.json");
+const CHAT_SESSIONS: &str = include_str!("fixtures/mock_server/chat_sessions.json");
+const CHAT_STREAM: &str = include_str!("fixtures/mock_server/chat_stream.jsonl");
+/// Pause between chat packets, so the reply visibly streams.
+const STREAM_INTERVAL: Duration = Duration::from_millis(40);
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+const SESSION_COOKIE: &str = "Set-Cookie: fastapiusersauth=demo; Path=/; HttpOnly\r\n";
+
+#[derive(Debug, PartialEq, Eq)]
+enum Body {
+    Fixed(&'static str),
+    /// Newline-delimited JSON, sent a line at a time like Onyx's chat stream.
+    Stream(&'static str),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct MockResponse {
+    status: u16,
+    content_type: &'static str,
+    body: Body,
+}
+
+impl MockResponse {
+    const fn json(status: u16, body: &'static str) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: Body::Fixed(body),
+        }
+    }
+}
+
+fn route(method: &str, target: &str) -> MockResponse {
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    match (method, path) {
+        ("GET" | "HEAD", "/api/health") => {
+            MockResponse::json(200, r#"{"success": true, "message": "ok"}"#)
+        }
+        ("GET" | "HEAD", "/api/version") => {
+            MockResponse::json(200, r#"{"backend_version": "mock"}"#)
+        }
+        ("GET" | "HEAD", "/api/me") => MockResponse::json(200, ME),
+        ("GET" | "HEAD", "/api/settings") => MockResponse::json(200, SETTINGS),
+        ("GET" | "HEAD", "/api/notifications") => MockResponse::json(200, NOTIFICATIONS),
+        ("GET" | "HEAD", "/api/chat/get-user-chat-sessions") => {
+            MockResponse::json(200, CHAT_SESSIONS)
+        }
+        ("POST", "/api/chat/send-message") => MockResponse {
+            status: 200,
+            content_type: "application/json",
+            body: Body::Stream(CHAT_STREAM),
+        },
+        (_, path) if path == "/api" || path.starts_with("/api/") => {
+            MockResponse::json(404, r#"{"detail": "Not Found"}"#)
+        }
+        ("GET" | "HEAD", _) => MockResponse {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            body: Body::Fixed(INDEX_PAGE),
+        },
+        _ => MockResponse::json(405, r#"{"detail": "Method Not Allowed"}"#),
+    }
+}
+
+const fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Bad Request",
+    }
+}
+
+/// Method and target of the next request on `stream`. Headers and body are
+/// read off the socket (clients may see a reset otherwise) and ignored.
+fn read_request(stream: &TcpStream) -> Option<(String, String)> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).ok()?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?.to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).ok()? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    std::io::copy(&mut reader.take(content_length), &mut std::io::sink()).ok()?;
+    Some((method, target))
+}
+
+fn write_response(
+    mut stream: &TcpStream,
+    method: &str,
+    response: &MockResponse,
+) -> std::io::Result<()> {
+    let length = match response.body {
+        Body::Fixed(body) => format!("Content-Length: {}\r\n", body.len()),
+        Body::Stream(_) => String::new(),
+    };
+    let cookie = if response.content_type.starts_with("text/html") {
+        SESSION_COOKIE
+    } else {
+        ""
+    };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\n{length}{cookie}Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+    );
+    stream.write_all(head.as_bytes())?;
+    if method == "HEAD" {
+        return Ok(());
+    }
+
+    match response.body {
+        Body::Fixed(body) => stream.write_all(body.as_bytes()),
+        Body::Stream(packets) => {
+            for packet in packets.lines() {
+                stream.write_all(packet.as_bytes())?;
+                stream.write_all(b"\n")?;
+                stream.flush()?;
+                thread::sleep(STREAM_INTERVAL);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn handle_connection(stream: &TcpStream) {
+    // A client that hangs up early is its own problem; there's nobody to
+    // report it to.
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    if let Some((method, target)) = read_request(stream) {
+        let _ = write_response(stream, &method, &route(&method, &target));
+    }
+}
+
+/// Start the mock server on loopback `port` (0 picks a free one) and return
+/// its address. It serves until the process exits.
+pub fn start(port: u16) -> Result<SocketAddr, String> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+        .map_err(|e| format!("Failed to start the mock server on port {port}: {e}"))?;
+    let addr = listener.local_addr().map_err(|e| e.to_string())?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || handle_connection(&stream));
+        }
+    });
+    Ok(addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::unwrap_used)]
+    fn request(addr: SocketAddr, raw: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(raw.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn routes_api_fixtures_and_pages() {
+        assert_eq!(route("GET", "/api/me"), MockResponse::json(200, ME));
+        assert_eq!(
+            route("GET", "/api/settings"),
+            MockResponse::json(200, SETTINGS)
+        );
+        assert_eq!(route("HEAD", "/api/health?ping=1").status, 200);
+        assert_eq!(route("GET", "/api/unknown").status, 404);
+        assert_eq!(route("DELETE", "/chat").status, 405);
+        assert_eq!(route("GET", "/chat").body, Body::Fixed(INDEX_PAGE));
+        assert_eq!(
+            route("POST", "/api/chat/send-message").body,
+            Body::Stream(CHAT_STREAM)
+        );
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn serves_health_and_streams_chat_over_http() {
+        let addr = start(0).unwrap();
+
+        let health = request(addr, "GET /api/health HTTP/1.1\r\nHost: x\r\n\r\n");
+        assert!(health.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(health.ends_with(r#"{"success": true, "message": "ok"}"#));
+
+        let body = r#"{"message": "Hello"}"#;
+        let chat = request(
+            addr,
+            &format!(
+                "POST /api/chat/send-message HTTP/1.1\r\nHost: x\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            ),
+        );
+        let (_, packets) = chat.split_once("\r\n\r\n").unwrap();
+        assert_eq!(packets, CHAT_STREAM);
+    }
+}
@@ -0,0 +1,384 @@
+// Recent chats kept on this machine, to read when the server can't be
+// reached. With `offline_cache.enabled` on, the last `offline_cache.chats`
+// chats are fetched from the server (as the main window's user) every
+// `REFRESH_INTERVAL` and whenever the connection comes back, and kept in
+// `offline_cache.sqlite3` in the state directory. Each chat is sealed with
+// the key the config's secrets use (see `secrets.rs`), so the file on its
+// own only shows how many chats there are and when they changed.
+//
+// When the connection monitor (see `connection.rs`) finds the server
+// offline, the offline window (`offline.html`) opens with the kept chats to
+// read -- nothing can be sent from it -- and it closes again once the
+// server answers. Turning the cache off, signing out or changing servers
+// deletes it. The same database holds the index `local_search.rs` searches.
+
+use crate::config::{get_state_dir, validate_server_url, ConfigState, OfflineCacheSettings};
+use crate::connection::Quality;
+use crate::debug_log::log_backend_error;
+use crate::lock::AppLock;
+use crate::secrets::{encryption_key, open, seal};
+use crate::server_api::{chat_transcript, recent_chats, ChatTranscript};
+use crate::window::{OFFLINE_WINDOW, OFFLINE_WINDOW_LABEL};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+
+const DB_FILE: &str = "offline_cache.sqlite3";
+const REFRESH_INTERVAL: Duration = Duration::from_mins(10);
+/// The most chats the server lists at once.
+const MAX_CHATS: usize = 100;
+
+/// Managed state: what the connection monitor last found, and a lock so
+/// refreshes don't overlap.
+#[derive(Default)]
+pub struct OfflineCache {
+    offline: AtomicBool,
+    refreshing: tokio::sync::Mutex<()>,
+}
+
+/// Register the cache's state.
+pub fn manage(builder: tauri::Builder<tauri::Wry>) -> tauri::Builder<tauri::Wry> {
+    builder.manage(OfflineCache::default())
+}
+
+/// A kept chat, as the offline window lists it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CachedChat {
+    pub id: String,
+    pub title: String,
+    pub updated: String,
+}
+
+fn db_path() -> Result<PathBuf, String> {
+    get_state_dir()
+        .map(|dir| dir.join(DB_FILE))
+        .ok_or_else(|| "Could not determine the state directory".to_string())
+}
+
+pub fn open_db() -> Result<Connection, String> {
+    let db = Connection::open(db_path()?).map_err(|e| e.to_string())?;
+    create_table(&db)?;
+    Ok(db)
+}
+
+pub fn create_table(db: &Connection) -> Result<(), String> {
+    db.execute_batch(
+        "CREATE TABLE IF NOT EXISTS chats (
+            id TEXT PRIMARY KEY,
+            updated TEXT NOT NULL,
+            chat TEXT NOT NULL
+        )",
+    )
+    .map_err(|e| e.to_string())?;
+    crate::local_search::create_tables(db)
+}
+
+/// When each kept chat last changed, by ID.
+fn kept_versions(db: &Connection) -> Result<HashMap<String, String>, String> {
+    let mut query = db
+        .prepare("SELECT id, updated FROM chats")
+        .map_err(|e| e.to_string())?;
+    let rows = query
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+}
+
+pub fn store(
+    db: &Connection,
+    key: &[u8; 32],
+    updated: &str,
+    chat: &ChatTranscript,
+) -> Result<(), String> {
+    let json = serde_json::to_string(chat).map_err(|e| e.to_string())?;
+    db.execute(
+        "INSERT OR REPLACE INTO chats (id, updated, chat) VALUES (?1, ?2, ?3)",
+        params![chat.chat_session_id, updated, seal(key, &json)?],
+    )
+    .map_err(|e| e.to_string())?;
+    crate::local_search::index_chat(db, key, chat)
+}
+
+/// Drop every kept chat but `ids`.
+fn keep_only(db: &mut Connection, ids: &HashSet<&str>) -> Result<(), String> {
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+    for id in kept_versions(&tx)?.into_keys() {
+        if !ids.contains(id.as_str()) {
+            tx.execute("DELETE FROM chats WHERE id = ?1", params![id])
+                .map_err(|e| e.to_string())?;
+            crate::local_search::forget(&tx, &id)?;
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+pub fn load(db: &Connection, key: &[u8; 32], id: &str) -> Result<Option<ChatTranscript>, String> {
+    let sealed: Option<String> = db
+        .query_row("SELECT chat FROM chats WHERE id = ?1", params![id], |row| {
+            row.get(0)
+        })
+        .optional()
+        .map_err(|e| e.to_string())?;
+    sealed
+        .map(|sealed| serde_json::from_str(&open(key, &sealed)?).map_err(|e| e.to_string()))
+        .transpose()
+}
+
+/// The kept chats, most recently changed first.
+fn list(db: &Connection, key: &[u8; 32]) -> Result<Vec<CachedChat>, String> {
+    let mut query = db
+        .prepare("SELECT id, updated FROM chats ORDER BY updated DESC")
+        .map_err(|e| e.to_string())?;
+    let rows = query
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?;
+    let mut chats = Vec::new();
+    for row in rows {
+        let (id, updated) = row.map_err(|e| e.to_string())?;
+        if let Some(chat) = load(db, key, &id)? {
+            chats.push(CachedChat {
+                title: crate::chat_export::transcript(&chat).title.to_string(),
+                id,
+                updated,
+            });
+        }
+    }
+    Ok(chats)
+}
+
+/// Bring the kept chats up to date with the server's most recent ones.
+async fn refresh(app: &AppHandle) -> Result<(), String> {
+    let state = app.state::<OfflineCache>();
+    let _refreshing = state.refreshing.lock().await;
+    let config = app.state::<ConfigState>().config();
+    if !config.offline_cache.enabled {
+        return Ok(());
+    }
+    let server = validate_server_url(&config.server_url)?;
+    let window = app.get_webview_window("main").ok_or("No main window")?;
+    // Logged out: what's kept stays until they sign out here.
+    let Some(chats) = recent_chats(app, config.offline_cache.chats).await? else {
+        return Ok(());
+    };
+
+    let kept = kept_versions(&open_db()?)?;
+    let mut changed = Vec::new();
+    for chat in &chats {
+        if kept.get(&chat.id) == Some(&chat.time_updated) {
+            continue;
+        }
+        let Some(transcript) = chat_transcript(&window, &server, &chat.id).await? else {
+            return Ok(());
+        };
+        changed.push((chat.time_updated.as_str(), transcript));
+    }
+
+    let key = encryption_key()?;
+    let mut db = open_db()?;
+    for (updated, chat) in &changed {
+        store(&db, key, updated, chat)?;
+    }
+    crate::local_search::index_unindexed(&db, key)?;
+    keep_only(
+        &mut db,
+        &chats.iter().map(|chat| chat.id.as_str()).collect(),
+    )
+}
+
+fn refresh_in_background(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = refresh(&app).await {
+            log_backend_error(&app, &format!("Failed to refresh the offline chats: {e}"));
+        }
+    });
+}
+
+/// Keep the cache up to date for as long as the app runs. Only for the
+/// copy that holds the data directory.
+pub fn start_refreshing(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if !app.state::<OfflineCache>().offline.load(Ordering::SeqCst) {
+                if let Err(e) = refresh(&app).await {
+                    log_backend_error(&app, &format!("Failed to refresh the offline chats: {e}"));
+                }
+            }
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+        }
+    });
+}
+
+/// Delete the kept chats.
+pub fn clear(app: &AppHandle) {
+    let path = match db_path() {
+        Ok(path) => path,
+        Err(e) => {
+            log_backend_error(app, &format!("Failed to clear the offline chats: {e}"));
+            return;
+        }
+    };
+    match std::fs::remove_file(&path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => log_backend_error(app, &format!("Failed to clear the offline chats: {e}")),
+    }
+}
+
+/// Apply a changed `offline_cache` setting: turned off, the kept chats go.
+pub fn sync(app: &AppHandle) {
+    if app.state::<ConfigState>().config().offline_cache.enabled {
+        refresh_in_background(app);
+    } else {
+        clear(app);
+    }
+}
+
+/// Open the offline window on the kept chats.
+fn open_window(app: &AppHandle) {
+    if app.get_webview_window(OFFLINE_WINDOW_LABEL).is_some() {
+        return;
+    }
+    // Built off the calling thread, like the other bundled pages.
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let preset = &OFFLINE_WINDOW;
+        let result = WebviewWindowBuilder::new(
+            &handle,
+            OFFLINE_WINDOW_LABEL,
+            WebviewUrl::App("offline.html".into()),
+        )
+        .title("Onyx (Offline)")
+        .inner_size(preset.width, preset.height)
+        .min_inner_size(preset.min_width, preset.min_height)
+        .center()
+        .focused(true)
+        .additional_browser_args(&crate::locale::browser_args())
+        .build();
+        if let Err(e) = result {
+            log_backend_error(&handle, &format!("Failed to open the offline window: {e}"));
+        }
+    });
+}
+
+/// Follow the connection monitor's latest finding: going offline opens the
+/// offline window, and coming back closes it and catches the cache up.
+pub fn on_connection(app: &AppHandle, quality: Quality) {
+    let offline = quality == Quality::Offline;
+    if app
+        .state::<OfflineCache>()
+        .offline
+        .swap(offline, Ordering::SeqCst)
+        == offline
+    {
+        return;
+    }
+    if offline {
+        // Opened while locked, it would show the chats the lock hides; on a
+        // kiosk, earlier users' chats.
+        if app.state::<ConfigState>().config().offline_cache.enabled
+            && !app.state::<AppLock>().is_locked()
+            && !crate::kiosk::is_active()
+        {
+            open_window(app);
+        }
+    } else {
+        if let Some(window) = app.get_webview_window(OFFLINE_WINDOW_LABEL) {
+            if let Err(e) = window.close() {
+                log_backend_error(app, &format!("Failed to close the offline window: {e}"));
+            }
+        }
+        refresh_in_background(app);
+    }
+}
+
+pub fn validate(settings: &OfflineCacheSettings) -> Result<(), String> {
+    if !(1..=MAX_CHATS).contains(&settings.chats) {
+        return Err(format!(
+            "The offline cache keeps between 1 and {MAX_CHATS} chats"
+        ));
+    }
+    Ok(())
+}
+
+fn check_caller(app: &AppHandle, window: &WebviewWindow) -> Result<&'static [u8; 32], String> {
+    if window.label() != OFFLINE_WINDOW_LABEL {
+        return Err("Offline chats can only be read in the offline window".to_string());
+    }
+    if app.state::<AppLock>().is_locked() {
+        return Err("Onyx is locked".to_string());
+    }
+    encryption_key()
+}
+
+/// The kept chats, most recently changed first; only from the offline
+/// window
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn get_offline_chats(app: AppHandle, window: WebviewWindow) -> Result<Vec<CachedChat>, String> {
+    let key = check_caller(&app, &window)?;
+    list(&open_db()?, key)
+}
+
+/// A kept chat's transcript, as `export_chat` writes it in JSON; only from
+/// the offline window
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn get_offline_chat(
+    app: AppHandle,
+    window: WebviewWindow,
+    id: String,
+) -> Result<serde_json::Value, String> {
+    let key = check_caller(&app, &window)?;
+    let chat = load(&open_db()?, key, &id)?.ok_or("That chat isn't kept offline")?;
+    serde_json::to_value(crate::chat_export::transcript(&chat)).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn chats_are_kept_sealed_and_pruned_to_the_recent_ones() {
+        let key = [7; 32];
+        let mut db = Connection::open_in_memory().unwrap();
+        create_table(&db).unwrap();
+        let chat = |id: &str, title: &str| -> ChatTranscript {
+            serde_json::from_value(serde_json::json!({
+                "chat_session_id": id,
+                "description": title,
+                "time_created": "2026-10-01T09:00:00Z",
+                "messages": [],
+            }))
+            .unwrap()
+        };
+        store(&db, &key, "2026-10-01T09:00:00Z", &chat("a", "PTO policy")).unwrap();
+        store(&db, &key, "2026-10-02T09:00:00Z", &chat("b", "Expenses")).unwrap();
+
+        let sealed: String = db
+            .query_row("SELECT chat FROM chats WHERE id = 'a'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert!(!sealed.contains("PTO policy"));
+        assert_eq!(
+            list(&db, &key)
+                .unwrap()
+                .iter()
+                .map(|chat| chat.title.as_str())
+                .collect::<Vec<_>>(),
+            ["Expenses", "PTO policy"]
+        );
+
+        keep_only(&mut db, &HashSet::from(["b"])).unwrap();
+        assert!(load(&db, &key, "a").unwrap().is_none());
+        assert_eq!(load(&db, &key, "b").unwrap().unwrap().chat_session_id, "b");
+        assert!(load(&db, &[8; 32], "b").is_err());
+    }
+}
@@ -0,0 +1,222 @@
+// First run. With no config yet, the main window waits hidden behind the
+// onboarding window (the bundled `onboarding.html`), which asks where the
+// server is -- Onyx Cloud, or a self-hosted URL checked against its
+// `/api/health` as it's typed -- and how the app should behave, then writes
+// the first config and swaps itself for the main window. Closed early, it
+// leaves the main window's own setup form to take over. Nothing asks when
+// the server is already decided: by managed policy (see `install_scope.rs`),
+// in a kiosk or in a demo.
+//
+// Carrying a browser sign-in over to the desktop on first run. Browsers
+// keep their cookies encrypted (Chromium, with a key in the system keychain)
+// or locked inside their own sandbox (Safari), so the session itself can't
+// be imported; instead the first-run page asks for the email the user signs
+// in with, and it's filled into the server's login form when that loads.
+// The email lives only in memory, until it's been filled in once.
+
+use crate::config::{validate_server_url, BackgroundBehavior, ConfigState};
+use crate::debug_log::log_backend_error;
+use crate::install_scope::managed_policy;
+use crate::route::Route;
+use crate::server_api::server_healthy;
+use crate::window::ONBOARDING_WINDOW_LABEL;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, Webview, WebviewWindow};
+use url::Url;
+
+/// Managed state: the email to fill in on the next login page.
+#[derive(Default)]
+pub struct LoginPrefill(Mutex<Option<String>>);
+
+impl LoginPrefill {
+    fn take(&self) -> Option<String> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .take()
+    }
+}
+
+/// Whether this run starts with onboarding: there's no config yet, and
+/// nothing else has picked the server.
+pub fn is_needed(app: &AppHandle) -> bool {
+    let state = app.state::<ConfigState>();
+    !state.is_config_initialized()
+        && !state.is_in_memory()
+        && managed_policy().server_url.is_none()
+        && !crate::kiosk::is_active()
+}
+
+/// What the onboarding page asked.
+#[derive(Deserialize)]
+pub struct OnboardingChoices {
+    server_url: String,
+    notifications: bool,
+    launch_at_login: bool,
+    /// Whether closing a window keeps the app running in the background.
+    keep_running: bool,
+}
+
+/// Check that `url` is an Onyx server that's up, as the onboarding page's
+/// URL is typed, and return it as it would be saved
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub async fn check_onboarding_server(
+    state: tauri::State<'_, ConfigState>,
+    url: String,
+) -> Result<String, String> {
+    let url = url.trim().trim_end_matches('/');
+    let server = validate_server_url(url)?;
+    match server_healthy(&state.config(), &server).await {
+        Ok(true) => Ok(url.to_string()),
+        Ok(false) => Err("The server answered, but says it isn't working right now".to_string()),
+        Err(e) => Err(format!("No Onyx server answered there: {e}")),
+    }
+}
+
+fn show(app: &AppHandle, window: &WebviewWindow) {
+    if let Err(e) = window.show() {
+        log_backend_error(app, &format!("Failed to show main window: {e}"));
+    }
+    if let Err(e) = window.set_focus() {
+        log_backend_error(app, &format!("Failed to focus main window: {e}"));
+    }
+}
+
+/// Write the first config from the onboarding page's `choices`, then swap
+/// the onboarding window for the main one
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn finish_onboarding(
+    app: AppHandle,
+    state: tauri::State<ConfigState>,
+    choices: OnboardingChoices,
+) -> Result<(), String> {
+    let server_url = choices.server_url.trim().trim_end_matches('/').to_string();
+    validate_server_url(&server_url)?;
+    state.update_and_persist(|config| {
+        config.server_url = server_url;
+        config.notifications.enabled = choices.notifications;
+        config.launch_at_login = choices.launch_at_login;
+        if choices.keep_running {
+            config.close_behavior = BackgroundBehavior::Hide;
+        }
+    })?;
+    state.set_config_initialized(true);
+    crate::autostart::sync(&app);
+
+    if let Some(main) = app.get_webview_window("main") {
+        // The main window's page goes on to the server now there's a
+        // config.
+        crate::watchdog::eval(
+            main.as_ref(),
+            "location.reload();",
+            "start after onboarding",
+        );
+        show(&app, &main);
+    }
+    if let Some(window) = app.get_webview_window(ONBOARDING_WINDOW_LABEL) {
+        if let Err(e) = window.close() {
+            log_backend_error(&app, &format!("Failed to close onboarding: {e}"));
+        }
+    }
+    Ok(())
+}
+
+/// The onboarding window closed. If that was before it finished, show the
+/// main window, whose own setup form takes over.
+pub fn closed(app: &AppHandle) {
+    if let Some(main) = app.get_webview_window("main") {
+        if !main.is_visible().unwrap_or(true) {
+            show(app, &main);
+        }
+    }
+}
+
+/// Whether `url` is a server's email/password login page.
+pub fn is_login_page(url: &Url) -> bool {
+    matches!(url.scheme(), "http" | "https") && Route::from_url(url) == Some(Route::Login)
+}
+
+/// Fill the login form with `email` once it renders (a few seconds at
+/// most), in a way React-controlled inputs notice.
+fn prefill_script(email: &str) -> Result<String, String> {
+    #[derive(Serialize)]
+    struct Args<'a> {
+        email: &'a str,
+    }
+    let args = serde_json::to_string(&Args { email }).map_err(|e| e.to_string())?;
+    Ok(format!(
+        r#"(({{ email }}) => {{
+  let attempts = 0;
+  const fill = () => {{
+    const input = document.querySelector('input[type="email"], input[name="email"]');
+    if (!input) {{
+      if (++attempts < 50) setTimeout(fill, 100);
+      return;
+    }}
+    if (input.value) return;
+    const setValue = Object.getOwnPropertyDescriptor(HTMLInputElement.prototype, "value").set;
+    setValue.call(input, email);
+    input.dispatchEvent(new Event("input", {{ bubbles: true }}));
+    document.querySelector('input[type="password"]')?.focus();
+  }};
+  fill();
+}})({args});"#
+    ))
+}
+
+/// Remember `email` for the login page the first-run page is about to open
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn prefill_login_email(state: tauri::State<LoginPrefill>, email: String) -> Result<(), String> {
+    let email = email.trim();
+    if !email.contains('@') || email.chars().any(char::is_whitespace) {
+        return Err("Not an email address".to_string());
+    }
+    *state
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(email.to_string());
+    Ok(())
+}
+
+/// Fill in the remembered email if `webview` just loaded a login page.
+pub fn fill_login_email(webview: &Webview) {
+    if !webview.url().is_ok_and(|url| is_login_page(&url)) {
+        return;
+    }
+    let Some(email) = webview.app_handle().state::<LoginPrefill>().take() else {
+        return;
+    };
+    match prefill_script(&email) {
+        Ok(script) => {
+            crate::watchdog::eval(webview, script, "prefill login email");
+        }
+        Err(e) => crate::debug_log::log_backend_error(
+            webview.app_handle(),
+            &format!("Failed to prefill login email: {e}"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn only_the_login_page_gets_the_email() {
+        let url = |s: &str| Url::parse(s).unwrap();
+        assert!(is_login_page(&url("https://cloud.onyx.app/auth/login")));
+        assert!(is_login_page(&url(
+            "https://cloud.onyx.app/auth/login/?next=%2Fchat"
+        )));
+        assert!(!is_login_page(&url("https://cloud.onyx.app/auth/signup")));
+        assert!(!is_login_page(&url("tauri://localhost/auth/login")));
+
+        let script = prefill_script("o'brien\"@example.com").unwrap();
+        assert!(script.ends_with(r#"({"email":"o'brien\"@example.com"});"#));
+    }
+}
@@ -0,0 +1,95 @@
+// Portable mode: a `portable.flag` file next to the executable keeps all of
+// the app's config and data in a `data/` folder beside it instead of the
+// per-user OS locations, so the app can run from a USB stick or on a machine
+// without install rights.
+//
+// The webview engines pick their storage locations themselves, before any
+// of our code could hand them a path for the main window (which is created
+// from `tauri.conf.json`), so they're redirected through the environment
+// variables they read at start-up. WKWebView has no such knob, so on macOS
+// webview data (cookies, local storage) stays in the usual place.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub const PORTABLE_FLAG_FILE_NAME: &str = "portable.flag";
+const PORTABLE_DATA_DIR_NAME: &str = "data";
+
+static PORTABLE_DATA_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// The `data/` folder to use if `dir` holds a portable flag file.
+fn portable_data_dir_in(dir: &Path) -> Option<PathBuf> {
+    dir.join(PORTABLE_FLAG_FILE_NAME)
+        .is_file()
+        .then(|| dir.join(PORTABLE_DATA_DIR_NAME))
+}
+
+/// The folder the flag file is looked for in. For an `AppImage` that's the
+/// folder holding the image itself, not its read-only mount point.
+fn executable_dir() -> Option<PathBuf> {
+    if let Some(appimage) = std::env::var_os("APPIMAGE") {
+        return Path::new(&appimage).parent().map(Path::to_path_buf);
+    }
+    std::env::current_exe()
+        .ok()?
+        .parent()
+        .map(Path::to_path_buf)
+}
+
+/// Where all config and data lives in portable mode; `None` when the app is
+/// running as a normal install.
+pub fn portable_data_dir() -> Option<&'static Path> {
+    PORTABLE_DATA_DIR
+        .get_or_init(|| executable_dir().and_then(|dir| portable_data_dir_in(&dir)))
+        .as_deref()
+}
+
+/// Keep all config and data in `dir` instead, as if it were the portable
+/// `data/` folder (`--data-dir`). Must run before anything asks where data
+/// lives.
+pub fn use_data_dir(dir: PathBuf) {
+    let _ = PORTABLE_DATA_DIR.set(Some(dir));
+}
+
+/// Point the webview engine's storage into the portable data folder. Must
+/// run before the Tauri runtime starts, while the process is still
+/// single-threaded.
+pub fn redirect_webview_storage() {
+    let Some(data_dir) = portable_data_dir() else {
+        return;
+    };
+
+    #[cfg(target_os = "windows")]
+    std::env::set_var("WEBVIEW2_USER_DATA_FOLDER", data_dir.join("webview"));
+
+    // WebKitGTK and the window-state plugin resolve their directories from
+    // the XDG base-directory variables.
+    #[cfg(target_os = "linux")]
+    {
+        std::env::set_var("XDG_CONFIG_HOME", data_dir.join("config"));
+        std::env::set_var("XDG_DATA_HOME", data_dir.join("share"));
+        std::env::set_var("XDG_CACHE_HOME", data_dir.join("cache"));
+    }
+
+    #[cfg(target_os = "macos")]
+    let _ = data_dir;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn flag_file_enables_portable_data_dir() {
+        let dir = std::env::temp_dir().join(format!("onyx-portable-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(portable_data_dir_in(&dir), None);
+
+        fs::write(dir.join(PORTABLE_FLAG_FILE_NAME), "").unwrap();
+        assert_eq!(portable_data_dir_in(&dir), Some(dir.join("data")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
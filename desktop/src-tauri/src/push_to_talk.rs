@@ -0,0 +1,435 @@
+// Push-to-talk voice input. While `voice.push_to_talk` is held (a global
+// shortcut, so it works with Onyx in the background), the default
+// microphone is recorded and a small indicator (`recording.html`) floats
+// over the other windows. On release the recording goes to the server's
+// transcription endpoint as the main window's user, and what was said is
+// asked in a new chat there. It's the one shortcut that isn't a window
+// shortcut, so it's off until one is set.
+//
+// Audio streams aren't `Send` on every platform, so each recording owns
+// its stream on a thread of its own, which hands the samples back when
+// told to stop. Dictation (see `dictation.rs`) records the same way.
+
+use crate::config::{ConfigState, VoiceSettings};
+use crate::debug_log::log_backend_error;
+use crate::route::Route;
+use crate::server_api::post_audio_as_window_user;
+use crate::window::{RECORDING_WINDOW, RECORDING_WINDOW_LABEL};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+use serde::Deserialize;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tauri::{AppHandle, LogicalPosition, Manager, WebviewUrl, WebviewWindowBuilder};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+pub const TRANSCRIBE_PATH: &str = "/api/voice/transcribe";
+/// Longer recordings are cut off here rather than held in memory.
+const MAX_RECORDING: Duration = Duration::from_mins(2);
+/// Shorter ones were a tap on the shortcut, not something said.
+const MIN_RECORDING: Duration = Duration::from_millis(300);
+/// Gap between the indicator and the top of the screen, in logical pixels.
+const SCREEN_MARGIN: f64 = 24.0;
+
+/// Mono 16-bit samples, as recorded.
+struct Audio {
+    samples: Vec<i16>,
+    sample_rate: u32,
+}
+
+impl Audio {
+    fn duration(&self) -> Duration {
+        let samples = u64::try_from(self.samples.len()).unwrap_or(u64::MAX);
+        Duration::from_millis(samples.saturating_mul(1000) / u64::from(self.sample_rate.max(1)))
+    }
+}
+
+struct Recording {
+    stop: mpsc::Sender<()>,
+    thread: JoinHandle<Result<Audio, String>>,
+}
+
+/// Managed state: the shortcut registered now, and the recording under way.
+#[derive(Default)]
+pub struct PushToTalk {
+    shortcut: Mutex<Option<Shortcut>>,
+    recording: Mutex<Option<Recording>>,
+}
+
+/// Register the push to talk state; the shortcut is set up with the app.
+pub fn manage(builder: tauri::Builder<tauri::Wry>) -> tauri::Builder<tauri::Wry> {
+    builder.manage(PushToTalk::default())
+}
+
+#[derive(Deserialize)]
+pub struct Transcription {
+    pub text: String,
+}
+
+/// `samples` as a WAV file.
+pub fn wav(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+    let data_len = u32::try_from(samples.len() * 2).unwrap_or(u32::MAX);
+    let mut wav = Vec::with_capacity(44 + samples.len() * 2);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, one channel.
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+/// Start recording the default microphone into `samples`, keeping no more
+/// than `max` of audio there; the stream (which records until it's
+/// dropped) and its sample rate.
+pub fn open_microphone(
+    samples: &Arc<Mutex<Vec<i16>>>,
+    max: Duration,
+) -> Result<(cpal::Stream, u32), String> {
+    let device = cpal::default_host()
+        .default_input_device()
+        .ok_or("No microphone is available")?;
+    let config = device
+        .default_input_config()
+        .map_err(|e| format!("The microphone can't record: {e}"))?;
+    let sample_rate = config.sample_rate().0;
+    let channels = usize::from(config.channels()).max(1);
+    let max_samples = usize::try_from(u64::from(sample_rate) * max.as_secs()).unwrap_or(usize::MAX);
+
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::I16 => {
+            input_stream::<i16>(&device, &config.into(), channels, max_samples, samples)
+        }
+        cpal::SampleFormat::U16 => {
+            input_stream::<u16>(&device, &config.into(), channels, max_samples, samples)
+        }
+        cpal::SampleFormat::I32 => {
+            input_stream::<i32>(&device, &config.into(), channels, max_samples, samples)
+        }
+        cpal::SampleFormat::F32 => {
+            input_stream::<f32>(&device, &config.into(), channels, max_samples, samples)
+        }
+        format => Err(format!(
+            "The microphone records {format}, which isn't supported"
+        )),
+    }?;
+    stream.play().map_err(|e| e.to_string())?;
+    Ok((stream, sample_rate))
+}
+
+/// Record the default microphone until `stop` says to (or `MAX_RECORDING`
+/// passes). `started` hears once the stream is running, or why it isn't.
+fn record(
+    stop: &mpsc::Receiver<()>,
+    started: &mpsc::Sender<Result<(), String>>,
+) -> Result<Audio, String> {
+    let samples = Arc::new(Mutex::new(Vec::new()));
+    let (stream, sample_rate) = match open_microphone(&samples, MAX_RECORDING) {
+        Ok(opened) => opened,
+        Err(e) => {
+            let _ = started.send(Err(e.clone()));
+            return Err(e);
+        }
+    };
+    let _ = started.send(Ok(()));
+
+    let _ = stop.recv_timeout(MAX_RECORDING);
+    drop(stream);
+    let samples = std::mem::take(
+        &mut *samples
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner),
+    );
+    Ok(Audio {
+        samples,
+        sample_rate,
+    })
+}
+
+/// An input stream keeping the first channel of each frame in `samples`.
+fn input_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    channels: usize,
+    max_samples: usize,
+    samples: &Arc<Mutex<Vec<i16>>>,
+) -> Result<cpal::Stream, String>
+where
+    T: SizedSample,
+    i16: FromSample<T>,
+{
+    let samples = samples.clone();
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _: &cpal::InputCallbackInfo| {
+                let mut samples = samples
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                let room = max_samples.saturating_sub(samples.len());
+                samples.extend(
+                    data.chunks(channels)
+                        .take(room)
+                        .map(|frame| frame[0].to_sample::<i16>()),
+                );
+            },
+            // A stream that fails comes back short, and is dropped like a
+            // tap on the shortcut.
+            |_| {},
+            None,
+        )
+        .map_err(|e| format!("The microphone can't record: {e}"))
+}
+
+fn show_indicator(app: &AppHandle) {
+    let recording = || {
+        app.state::<PushToTalk>()
+            .recording
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .is_some()
+    };
+    let preset = &RECORDING_WINDOW;
+    let result = WebviewWindowBuilder::new(
+        app,
+        RECORDING_WINDOW_LABEL,
+        WebviewUrl::App("recording.html".into()),
+    )
+    .title("Recording")
+    .inner_size(preset.width, preset.height)
+    .resizable(false)
+    .decorations(false)
+    .transparent(true)
+    .shadow(false)
+    .always_on_top(true)
+    .skip_taskbar(true)
+    // Whatever the user is in keeps the keyboard.
+    .focused(false)
+    .visible(false)
+    .additional_browser_args(&crate::locale::browser_args())
+    .build();
+    let window = match result {
+        Ok(window) => window,
+        Err(e) => {
+            log_backend_error(app, &format!("Failed to show recording indicator: {e}"));
+            return;
+        }
+    };
+    // Top center of the screen the user is on.
+    let monitor = app
+        .get_webview_window("main")
+        .and_then(|main| main.current_monitor().ok().flatten())
+        .or_else(|| app.primary_monitor().ok().flatten());
+    if let Some(monitor) = monitor {
+        let area = monitor.work_area();
+        let scale = monitor.scale_factor();
+        let origin = area.position.to_logical::<f64>(scale);
+        let size = area.size.to_logical::<f64>(scale);
+        let position = LogicalPosition::new(
+            origin.x + (size.width - preset.width) / 2.0,
+            origin.y + SCREEN_MARGIN,
+        );
+        if let Err(e) = window.set_position(position) {
+            log_backend_error(app, &format!("Failed to place recording indicator: {e}"));
+        }
+    }
+    crate::magnifier::bring_into_view(app, &window, crate::magnifier::Anchor::TopCenter);
+    // Released while it was being built.
+    if !recording() {
+        close_indicator(app);
+        return;
+    }
+    if let Err(e) = window.show() {
+        log_backend_error(app, &format!("Failed to show recording indicator: {e}"));
+    }
+}
+
+fn close_indicator(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window(RECORDING_WINDOW_LABEL) {
+        if let Err(e) = window.close() {
+            log_backend_error(app, &format!("Failed to close recording indicator: {e}"));
+        }
+    }
+}
+
+fn warn(app: &AppHandle, message: String) {
+    app.dialog()
+        .message(message)
+        .title("Voice Input")
+        .kind(MessageDialogKind::Warning)
+        .show(|_| {});
+}
+
+fn start(app: &AppHandle) {
+    let state = app.state::<PushToTalk>();
+    let mut recording = state
+        .recording
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    // Held keys repeat on some platforms.
+    if recording.is_some() {
+        return;
+    }
+
+    let (stop, stopped) = mpsc::channel();
+    let (started, started_rx) = mpsc::channel();
+    let thread = std::thread::spawn(move || record(&stopped, &started));
+    match started_rx.recv() {
+        Ok(Ok(())) => {
+            *recording = Some(Recording { stop, thread });
+            drop(recording);
+            // Built off the shortcut's thread, like the other windows.
+            let handle = app.clone();
+            tauri::async_runtime::spawn(async move { show_indicator(&handle) });
+        }
+        Ok(Err(e)) => {
+            drop(recording);
+            log_backend_error(app, &format!("Failed to start recording: {e}"));
+            warn(app, format!("Onyx couldn't use the microphone. {e}."));
+        }
+        Err(_) => log_backend_error(app, "Failed to start recording: the recorder stopped"),
+    }
+}
+
+fn finish(app: &AppHandle) {
+    let Some(recording) = app
+        .state::<PushToTalk>()
+        .recording
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .take()
+    else {
+        return;
+    };
+    close_indicator(app);
+    let _ = recording.stop.send(());
+
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let audio = match recording.thread.join() {
+            Ok(Ok(audio)) => audio,
+            Ok(Err(e)) => {
+                log_backend_error(&handle, &format!("Failed to record: {e}"));
+                return;
+            }
+            Err(_) => {
+                log_backend_error(&handle, "Failed to record: the recorder crashed");
+                return;
+            }
+        };
+        if audio.duration() < MIN_RECORDING {
+            return;
+        }
+        match transcribe(&handle, &audio).await {
+            Ok(Some(text)) => {
+                crate::window::open_route(&handle, &Route::Search { query: text });
+            }
+            Ok(None) => {}
+            Err(e) => {
+                log_backend_error(&handle, &format!("Failed to transcribe recording: {e}"));
+                warn(&handle, format!("What you said couldn't be sent. {e}."));
+            }
+        }
+    });
+}
+
+/// What was said in `audio`, per the main window's server; `None` when
+/// nothing was.
+async fn transcribe(app: &AppHandle, audio: &Audio) -> Result<Option<String>, String> {
+    let server =
+        crate::config::validate_server_url(&app.state::<ConfigState>().config().server_url)?;
+    let window = app.get_webview_window("main").ok_or("No main window")?;
+    let transcription: Transcription = post_audio_as_window_user(
+        &window,
+        &server,
+        TRANSCRIBE_PATH,
+        wav(&audio.samples, audio.sample_rate),
+    )
+    .await?
+    .ok_or("Log in to Onyx to use voice input")?;
+    let text = transcription.text.trim();
+    Ok((!text.is_empty()).then(|| text.to_string()))
+}
+
+/// Check that `voice.push_to_talk` is a shortcut, if it's set.
+pub fn validate(voice: &VoiceSettings) -> Result<(), String> {
+    let shortcut = voice.push_to_talk.trim();
+    if shortcut.is_empty() || shortcut.parse::<Shortcut>().is_ok() {
+        Ok(())
+    } else {
+        Err(format!("\"{shortcut}\" isn't a push-to-talk shortcut"))
+    }
+}
+
+/// Register `voice.push_to_talk` as configured now, replacing the one
+/// registered before. A shortcut another app holds is reported and left
+/// off.
+pub fn sync_shortcut(app: &AppHandle) {
+    let configured = app.state::<ConfigState>().config().voice.push_to_talk;
+    let configured = configured.trim();
+    let state = app.state::<PushToTalk>();
+    let mut registered = state
+        .shortcut
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let wanted = configured.parse::<Shortcut>().ok();
+    if *registered == wanted {
+        return;
+    }
+
+    let shortcuts = app.global_shortcut();
+    if let Some(previous) = registered.take() {
+        if let Err(e) = shortcuts.unregister(previous) {
+            log_backend_error(app, &format!("Failed to unregister push to talk: {e}"));
+        }
+    }
+    let Some(shortcut) = wanted else {
+        return;
+    };
+    let result = shortcuts.on_shortcut(shortcut, |app, _, event| match event.state() {
+        ShortcutState::Pressed => start(app),
+        ShortcutState::Released => finish(app),
+    });
+    match result {
+        Ok(()) => *registered = Some(shortcut),
+        Err(e) => {
+            drop(registered);
+            log_backend_error(app, &format!("Failed to register push to talk: {e}"));
+            warn(
+                app,
+                format!("The push-to-talk shortcut {configured} couldn't be set: another app may be using it."),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::wav;
+
+    #[test]
+    fn recordings_are_sent_as_mono_wav() {
+        let file = wav(&[0, 1, -1], 16_000);
+        assert_eq!(file.len(), 44 + 6);
+        assert_eq!(&file[..4], b"RIFF");
+        assert_eq!(&file[4..8], &42u32.to_le_bytes());
+        assert_eq!(&file[8..16], b"WAVEfmt ");
+        assert_eq!(&file[22..24], &1u16.to_le_bytes());
+        assert_eq!(&file[24..28], &16_000u32.to_le_bytes());
+        assert_eq!(&file[40..44], &6u32.to_le_bytes());
+        assert_eq!(&file[44..], &[0, 0, 1, 0, 0xff, 0xff]);
+    }
+}
@@ -0,0 +1,660 @@
+// Reading answers aloud with the system's speech engine (AVSpeechSynthesizer
+// on macOS, WinRT speech on Windows, Speech Dispatcher on Linux), for
+// listening to a long answer while doing something else. What's read is
+// the selection (the context menu's Read Aloud, or Edit > Read Selection
+// Aloud) or the last answer in the focused chat, fetched from the server
+// (Edit > Read Last Response Aloud, or the tray). The tray's Read Aloud
+// menu pauses, resumes and stops it, and so do the media keys: while
+// something's read it's the system's media session (see `media_session`),
+// shown in Now Playing on macOS and the media overlay on Windows, where
+// next and previous skip ahead a part or go back one.
+//
+// The engine lives on its own thread, started the first time something is
+// read. Text is read a few sentences at a time, so pausing stops the
+// engine and resuming starts again at the beginning of the part that was
+// cut off. `read_aloud.voice` picks a voice by name (the system's default
+// otherwise) and `read_aloud.rate` the speed, as a percentage of normal.
+
+use crate::config::{validate_server_url, ConfigState, ReadAloudSettings};
+use crate::debug_log::log_backend_error;
+use crate::lock::AppLock;
+use crate::route::Route;
+use crate::server_api::chat_transcript;
+use crate::window::{is_bundled_page_window, SETTINGS_WINDOW_LABEL};
+use souvlaki::{MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, PlatformConfig};
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::menu::{MenuItem, PredefinedMenuItem, Submenu, SubmenuBuilder};
+use tauri::{AppHandle, Manager, WebviewWindow, Wry};
+
+pub const READ_LAST_RESPONSE_ID: &str = "read_aloud:last_response";
+pub const PAUSE_ID: &str = "read_aloud:pause";
+pub const STOP_ID: &str = "read_aloud:stop";
+
+/// How often the engine is asked whether it's done with a part.
+const POLL_INTERVAL: Duration = Duration::from_millis(150);
+/// How long a part has to start before a silent engine counts as done.
+const START_GRACE: Duration = Duration::from_millis(500);
+/// Roughly how long each part read in one go is.
+const PART_CHARS: usize = 300;
+/// How far into a part Previous goes back to the part before it rather
+/// than to its start.
+const RESTART_WINDOW: Duration = Duration::from_secs(3);
+/// The longest title the media session is given.
+const TITLE_CHARS: usize = 80;
+const MIN_RATE: u32 = 50;
+const MAX_RATE: u32 = 200;
+
+enum Request {
+    Speak(String),
+    TogglePause,
+    Pause,
+    Resume,
+    /// Skip the rest of the part being read.
+    Next,
+    /// Go back to the start of the part being read, or to the one before.
+    Previous,
+    Stop,
+    Voices(Sender<Result<Vec<String>, String>>),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Playback {
+    #[default]
+    Idle,
+    Speaking,
+    Paused,
+}
+
+/// What the engine is reading.
+#[derive(Debug, Default)]
+struct Reading {
+    /// What's read, as the media session names it.
+    title: String,
+    /// The parts still to read.
+    queue: VecDeque<String>,
+    /// The part being read, and when it was started.
+    current: Option<(String, Instant)>,
+    /// The parts already read, for going back to.
+    read: Vec<String>,
+    playback: Playback,
+}
+
+impl Reading {
+    /// Stop the engine, keeping the part it was reading to start again.
+    fn pause(&mut self, tts: &mut tts::Tts) {
+        if self.playback != Playback::Speaking {
+            return;
+        }
+        let _ = tts.stop();
+        if let Some((part, _)) = self.current.take() {
+            self.queue.push_front(part);
+        }
+        self.playback = Playback::Paused;
+    }
+
+    fn resume(&mut self) {
+        if self.playback == Playback::Paused {
+            self.playback = Playback::Speaking;
+        }
+    }
+
+    fn next(&mut self, tts: &mut tts::Tts) {
+        if self.playback == Playback::Idle {
+            return;
+        }
+        let _ = tts.stop();
+        // While paused, the part that was cut off is first in the queue.
+        let skipped = match self.current.take() {
+            Some((part, _)) => Some(part),
+            None => self.queue.pop_front(),
+        };
+        self.read.extend(skipped);
+    }
+
+    /// Start the next part once the engine's done with the current one.
+    fn advance(&mut self, app: &AppHandle, tts: &mut tts::Tts) {
+        if self.playback != Playback::Speaking {
+            return;
+        }
+        let done = self.current.as_ref().is_none_or(|(_, started)| {
+            started.elapsed() > START_GRACE && !tts.is_speaking().unwrap_or(false)
+        });
+        if !done {
+            return;
+        }
+        if let Some((part, _)) = self.current.take() {
+            self.read.push(part);
+        }
+        match self.queue.pop_front() {
+            Some(part) => {
+                if let Err(e) = tts.speak(part.as_str(), false) {
+                    log_backend_error(app, &format!("Failed to read aloud: {e}"));
+                    self.queue.clear();
+                }
+                self.current = Some((part, Instant::now()));
+            }
+            None => *self = Self::default(),
+        }
+    }
+
+    fn previous(&mut self, tts: &mut tts::Tts) {
+        if self.playback == Playback::Idle {
+            return;
+        }
+        let _ = tts.stop();
+        // A part that's only just started goes back to the one before, the
+        // way a track does.
+        let just_started = match self.current.take() {
+            Some((part, started)) => {
+                self.queue.push_front(part);
+                started.elapsed() < RESTART_WINDOW
+            }
+            None => true,
+        };
+        if just_started {
+            if let Some(part) = self.read.pop() {
+                self.queue.push_front(part);
+            }
+        }
+    }
+}
+
+/// The tray's Read Aloud items that change with what's playing.
+struct Controls {
+    pause: MenuItem<Wry>,
+    stop: MenuItem<Wry>,
+}
+
+/// Managed state: the way to the engine's thread once it's started, and
+/// the tray's controls.
+#[derive(Default)]
+pub struct ReadAloud {
+    requests: Mutex<Option<Sender<Request>>>,
+    controls: Mutex<Option<Controls>>,
+}
+
+/// Register the Read Aloud state; the engine starts on first use.
+pub fn manage(builder: tauri::Builder<Wry>) -> tauri::Builder<Wry> {
+    builder.manage(ReadAloud::default())
+}
+
+/// `text` without the Markdown and citation marks that would be read out:
+/// links keep their text, code blocks and `[1]`-style citations go.
+fn speakable(text: &str) -> String {
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        lines.push(line.trim_start_matches(['#', '>', ' ']).trim_end());
+    }
+    let text = lines.join("\n");
+
+    let mut spoken = String::with_capacity(text.len());
+    let mut rest = text.as_str();
+    while let Some(open) = rest.find('[') {
+        spoken.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let Some(close) = after.find(']') else {
+            rest = after;
+            continue;
+        };
+        let label = &after[..close];
+        // `(link)` after the brackets is dropped, and with it a
+        // citation's `[[1]](link)`.
+        let mut next = after[close + 1..].trim_start_matches(']');
+        if next.starts_with('(') {
+            if let Some(end) = next.find(')') {
+                next = &next[end + 1..];
+            }
+        }
+        let label = label.trim_matches(['[', ']']);
+        if !label.chars().all(|c| c.is_ascii_digit()) {
+            spoken.push_str(label);
+        }
+        rest = next;
+    }
+    spoken.push_str(rest);
+    spoken.replace(['*', '`', '_'], "")
+}
+
+/// `text` in parts of whole sentences, each around `PART_CHARS` long.
+fn parts(text: &str) -> VecDeque<String> {
+    let mut parts = VecDeque::new();
+    let mut part = String::new();
+    for sentence in text.split_inclusive(['.', '!', '?', '\n']) {
+        if !part.is_empty() && part.len() + sentence.len() > PART_CHARS {
+            parts.push_back(std::mem::take(&mut part).trim().to_string());
+        }
+        part.push_str(sentence);
+    }
+    parts.push_back(part.trim().to_string());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// What's read, named by its first line.
+fn title(text: &str) -> String {
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("Read Aloud");
+    if line.chars().count() > TITLE_CHARS {
+        let cut: String = line.chars().take(TITLE_CHARS - 1).collect();
+        format!("{}\u{2026}", cut.trim_end())
+    } else {
+        line.to_string()
+    }
+}
+
+/// The engine's rate for `percent` of normal speed, between its slowest
+/// and fastest.
+fn engine_rate(percent: u32, min: f32, normal: f32, max: f32) -> f32 {
+    let percent = percent.clamp(MIN_RATE, MAX_RATE);
+    // Both are at most 200, so exact as `f32`.
+    #[allow(clippy::cast_precision_loss)]
+    let (percent, low, high) = (percent as f32, MIN_RATE as f32, MAX_RATE as f32);
+    if percent < 100.0 {
+        min + (normal - min) * (percent - low) / (100.0 - low)
+    } else {
+        normal + (max - normal) * (percent - 100.0) / (high - 100.0)
+    }
+}
+
+fn apply_settings(tts: &mut tts::Tts, settings: &ReadAloudSettings) -> Result<(), String> {
+    let rate = engine_rate(
+        settings.rate,
+        tts.min_rate(),
+        tts.normal_rate(),
+        tts.max_rate(),
+    );
+    tts.set_rate(rate).map_err(|e| e.to_string())?;
+    let Some(name) = settings.voice.as_deref().filter(|name| !name.is_empty()) else {
+        return Ok(());
+    };
+    let voices = tts.voices().map_err(|e| e.to_string())?;
+    let voice = voices
+        .iter()
+        .find(|voice| voice.name().eq_ignore_ascii_case(name) || voice.id() == name)
+        .ok_or_else(|| format!("There's no voice named \u{201c}{name}\u{201d}"))?;
+    tts.set_voice(voice).map_err(|e| e.to_string())
+}
+
+fn voice_names(tts: &tts::Tts) -> Result<Vec<String>, String> {
+    let mut names: Vec<String> = tts
+        .voices()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(tts::Voice::name)
+        .collect();
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/// The engine's thread: read what's asked, a part at a time.
+fn run_engine(app: &AppHandle, requests: &std::sync::mpsc::Receiver<Request>) {
+    let mut tts = match tts::Tts::default() {
+        Ok(tts) => tts,
+        Err(e) => {
+            log_backend_error(
+                app,
+                &format!("Read aloud can't start the speech engine: {e}"),
+            );
+            // Answer the requests already waiting, then stop taking them.
+            while let Ok(request) = requests.try_recv() {
+                if let Request::Voices(reply) = request {
+                    let _ = reply.send(Err(e.to_string()));
+                }
+            }
+            *app.state::<ReadAloud>()
+                .requests
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) = None;
+            return;
+        }
+    };
+    let mut media = media_session(app);
+    let mut reading = Reading::default();
+    let mut shown = Playback::Idle;
+    loop {
+        match requests.recv_timeout(POLL_INTERVAL) {
+            Ok(Request::Speak(text)) => {
+                let _ = tts.stop();
+                let settings = app.state::<ConfigState>().config().read_aloud;
+                if let Err(e) = apply_settings(&mut tts, &settings) {
+                    log_backend_error(app, &format!("Failed to apply read aloud settings: {e}"));
+                }
+                let text = speakable(&text);
+                reading = Reading {
+                    title: title(&text),
+                    queue: parts(&text),
+                    playback: Playback::Speaking,
+                    ..Reading::default()
+                };
+                if let Some(media) = media.as_mut() {
+                    let metadata = MediaMetadata {
+                        title: Some(&reading.title),
+                        artist: Some("Onyx"),
+                        ..MediaMetadata::default()
+                    };
+                    if let Err(e) = media.set_metadata(metadata) {
+                        log_backend_error(app, &format!("Failed to update Now Playing: {e:?}"));
+                    }
+                }
+            }
+            Ok(Request::TogglePause) => {
+                if reading.playback == Playback::Paused {
+                    reading.resume();
+                } else {
+                    reading.pause(&mut tts);
+                }
+            }
+            Ok(Request::Pause) => reading.pause(&mut tts),
+            Ok(Request::Resume) => reading.resume(),
+            Ok(Request::Next) => reading.next(&mut tts),
+            Ok(Request::Previous) => reading.previous(&mut tts),
+            Ok(Request::Stop) => {
+                let _ = tts.stop();
+                reading = Reading::default();
+            }
+            Ok(Request::Voices(reply)) => {
+                let _ = reply.send(voice_names(&tts));
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        reading.advance(app, &mut tts);
+        if reading.playback != shown {
+            show_playback(app, reading.playback);
+            if let Some(media) = media.as_mut() {
+                let playback = match reading.playback {
+                    Playback::Idle => MediaPlayback::Stopped,
+                    Playback::Speaking => MediaPlayback::Playing { progress: None },
+                    Playback::Paused => MediaPlayback::Paused { progress: None },
+                };
+                if let Err(e) = media.set_playback(playback) {
+                    log_backend_error(app, &format!("Failed to update Now Playing: {e:?}"));
+                }
+            }
+            shown = reading.playback;
+        }
+    }
+}
+
+/// The system's media session, which takes the media keys and shows what's
+/// read (Now Playing on macOS, the media overlay on Windows, MPRIS on
+/// Linux). Reading works without one.
+fn media_session(app: &AppHandle) -> Option<MediaControls> {
+    #[cfg(target_os = "windows")]
+    let hwnd = app
+        .get_webview_window("main")
+        .and_then(|window| window.hwnd().ok())
+        .map(|hwnd| hwnd.0);
+    #[cfg(not(target_os = "windows"))]
+    let hwnd = None;
+    let config = PlatformConfig {
+        dbus_name: "onyx_desktop",
+        display_name: "Onyx",
+        hwnd,
+    };
+    let mut controls = match MediaControls::new(config) {
+        Ok(controls) => controls,
+        Err(e) => {
+            log_backend_error(app, &format!("Read aloud has no media session: {e:?}"));
+            return None;
+        }
+    };
+    let handle = app.clone();
+    let attached = controls.attach(move |event| {
+        let request = match event {
+            MediaControlEvent::Toggle => Request::TogglePause,
+            MediaControlEvent::Play => Request::Resume,
+            MediaControlEvent::Pause => Request::Pause,
+            MediaControlEvent::Next => Request::Next,
+            MediaControlEvent::Previous => Request::Previous,
+            MediaControlEvent::Stop => Request::Stop,
+            _ => return,
+        };
+        send(&handle, request);
+    });
+    if let Err(e) = attached {
+        log_backend_error(app, &format!("Read aloud has no media session: {e:?}"));
+        return None;
+    }
+    Some(controls)
+}
+
+/// Hand `request` to the engine's thread, starting it if need be.
+fn send(app: &AppHandle, request: Request) {
+    let state = app.state::<ReadAloud>();
+    let mut requests = state
+        .requests
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let sender = requests.get_or_insert_with(|| {
+        let (sender, receiver) = channel();
+        let handle = app.clone();
+        std::thread::spawn(move || run_engine(&handle, &receiver));
+        sender
+    });
+    if sender.send(request).is_err() {
+        *requests = None;
+    }
+}
+
+/// Set the tray's controls for `playback`.
+fn show_playback(app: &AppHandle, playback: Playback) {
+    // Menus are changed on the main thread, which may be waiting on the
+    // lock, so it isn't held while they are.
+    let read_aloud = app.state::<ReadAloud>();
+    let Some((pause, stop)) = read_aloud
+        .controls
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .as_ref()
+        .map(|controls| (controls.pause.clone(), controls.stop.clone()))
+    else {
+        return;
+    };
+    let label = if playback == Playback::Paused {
+        "Resume"
+    } else {
+        "Pause"
+    };
+    let result = pause
+        .set_text(label)
+        .and_then(|()| pause.set_enabled(playback != Playback::Idle))
+        .and_then(|()| stop.set_enabled(playback != Playback::Idle));
+    if let Err(e) = result {
+        log_backend_error(app, &format!("Failed to update the Read Aloud menu: {e}"));
+    }
+}
+
+/// The tray's Read Aloud submenu.
+pub fn tray_menu(app: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+    let pause = MenuItem::with_id(app, PAUSE_ID, "Pause", false, None::<&str>)?;
+    let stop = MenuItem::with_id(app, STOP_ID, "Stop", false, None::<&str>)?;
+    let submenu = SubmenuBuilder::new(app, "Read Aloud")
+        .item(&MenuItem::with_id(
+            app,
+            READ_LAST_RESPONSE_ID,
+            "Read Last Response",
+            true,
+            None::<&str>,
+        )?)
+        .item(&PredefinedMenuItem::separator(app)?)
+        .item(&pause)
+        .item(&stop)
+        .build()?;
+    *app.state::<ReadAloud>()
+        .controls
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(Controls { pause, stop });
+    Ok(submenu)
+}
+
+/// Read `text` aloud, in place of anything being read.
+pub fn speak(app: &AppHandle, text: &str) {
+    if app.state::<AppLock>().is_locked() || text.trim().is_empty() {
+        return;
+    }
+    send(app, Request::Speak(text.to_string()));
+}
+
+pub fn toggle_pause(app: &AppHandle) {
+    send(app, Request::TogglePause);
+}
+
+pub fn stop(app: &AppHandle) {
+    // Nothing to stop if the engine never started.
+    if app
+        .state::<ReadAloud>()
+        .requests
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .is_some()
+    {
+        send(app, Request::Stop);
+    }
+}
+
+/// The chat window to read from: the focused one, or the main window.
+fn focused_chat_window(app: &AppHandle) -> Option<WebviewWindow> {
+    let windows = app.webview_windows();
+    windows
+        .iter()
+        .find(|(label, window)| {
+            !is_bundled_page_window(label) && window.is_focused().unwrap_or(false)
+        })
+        .map(|(_, window)| window)
+        .or_else(|| windows.get("main"))
+        .cloned()
+}
+
+/// Read the last answer in `window`'s chat aloud.
+async fn read_last_response(app: &AppHandle, window: &WebviewWindow) -> Result<(), String> {
+    let Some(Route::Chat { id }) = window.url().ok().as_ref().and_then(Route::from_url) else {
+        return Err("This window isn't showing a saved chat".to_string());
+    };
+    let server = validate_server_url(&app.state::<ConfigState>().config().server_url)?;
+    let chat = chat_transcript(window, &server, &id)
+        .await?
+        .ok_or("Not signed in")?;
+    let answer = crate::chat_export::last_answer(&chat).ok_or("This chat has no answer yet")?;
+    speak(app, answer);
+    Ok(())
+}
+
+/// Edit > Read Last Response Aloud and the tray's Read Last Response.
+pub fn read_focused_last_response(app: &AppHandle) {
+    let Some(window) = focused_chat_window(app) else {
+        return;
+    };
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = read_last_response(&app, &window).await {
+            log_backend_error(&app, &format!("Failed to read the last response: {e}"));
+        }
+    });
+}
+
+/// Edit > Read Selection Aloud: the focused page hands its selection to
+/// `speak_selection`.
+pub fn read_focused_selection(app: &AppHandle) {
+    let Some(window) = focused_chat_window(app) else {
+        return;
+    };
+    crate::watchdog::eval(
+        window.as_ref(),
+        "(window.__TAURI__?.core?.invoke || window.__TAURI_INTERNALS__?.invoke)?.(\
+         \"speak_selection\", { text: String(window.getSelection() ?? \"\") })",
+        "read selection aloud",
+    );
+}
+
+pub fn validate(settings: &ReadAloudSettings) -> Result<(), String> {
+    if !(MIN_RATE..=MAX_RATE).contains(&settings.rate) {
+        return Err(format!(
+            "The reading speed is between {MIN_RATE}% and {MAX_RATE}%"
+        ));
+    }
+    Ok(())
+}
+
+/// Read `text`, the calling page's selection, aloud
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn speak_selection(app: AppHandle, text: String) {
+    speak(&app, &text);
+}
+
+/// Read the last answer in the calling window's chat aloud
+#[tauri::command]
+pub async fn speak_last_response(app: AppHandle, window: WebviewWindow) -> Result<(), String> {
+    read_last_response(&app, &window).await
+}
+
+/// Stop reading aloud
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn stop_reading(app: AppHandle) {
+    stop(&app);
+}
+
+/// The voices the speech engine has, by name; only from Settings
+#[tauri::command]
+pub async fn get_read_aloud_voices(
+    app: AppHandle,
+    window: WebviewWindow,
+) -> Result<Vec<String>, String> {
+    if window.label() != SETTINGS_WINDOW_LABEL {
+        return Err("Not available in this window".to_string());
+    }
+    let (reply, answer) = channel();
+    send(&app, Request::Voices(reply));
+    tauri::async_runtime::spawn_blocking(move || answer.recv_timeout(Duration::from_secs(10)))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|_| "The speech engine didn't answer".to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_are_read_without_markup_a_few_sentences_at_a_time() {
+        assert_eq!(
+            speakable(
+                "## Leave\n\nYou get **20 days** [[1]](https://wiki/pto), see the \
+                 [policy](https://wiki/pto) [2].\n```\ncode\n```\nDone."
+            ),
+            "Leave\n\nYou get 20 days , see the policy .\nDone."
+        );
+
+        let sentence = "This sentence is exactly fifty characters long ok. ";
+        let parts = parts(&sentence.repeat(10));
+        assert_eq!(parts.len(), 2);
+        assert!(parts.iter().all(|part| part.len() <= PART_CHARS));
+
+        assert_eq!(title("\n  Leave\n\nYou get 20 days."), "Leave");
+        assert_eq!(title(&sentence.repeat(2)).chars().count(), TITLE_CHARS);
+        assert_eq!(title(""), "Read Aloud");
+
+        assert!((engine_rate(100, 0.1, 0.5, 1.0) - 0.5).abs() < f32::EPSILON);
+        assert!((engine_rate(200, 0.1, 0.5, 1.0) - 1.0).abs() < f32::EPSILON);
+        assert!((engine_rate(50, 0.1, 0.5, 1.0) - 0.1).abs() < f32::EPSILON);
+        assert!((engine_rate(75, -100.0, 0.0, 100.0) + 50.0).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,191 @@
+// The server pages the app sends windows to, as types rather than strings:
+// a chat, a new chat (optionally with an assistant), a search (a new chat
+// that asks right away), a draft (a new chat with its message typed in but
+// not sent), the admin pages, and the login page. Everything that points a
+// window at the server (menus, shortcuts, the Dock and tray, the context
+// menu, `navigate_to`) builds its URL here, and `Route::from_url` reads one
+// back, so when the web app moves a page only this file changes.
+
+use serde::Deserialize;
+use url::Url;
+
+/// Where chats live. The web app also serves them under `/app`, which is
+/// read back as a chat too.
+const CHAT_PATH: &str = "/chat";
+const APP_PATH: &str = "/app";
+const ADMIN_PATH: &str = "/admin";
+const LOGIN_PATH: &str = "/auth/login";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "route", rename_all = "snake_case")]
+pub enum Route {
+    Chat {
+        id: String,
+    },
+    NewChat {
+        #[serde(default)]
+        assistant: Option<u64>,
+    },
+    /// A new chat that sends `query` as soon as it loads.
+    Search {
+        query: String,
+    },
+    /// A new chat with `message` in the message box, left for the user to
+    /// edit and send.
+    Draft {
+        message: String,
+    },
+    /// `/admin/<page>`, such as `indexing/status` or `connector/12`.
+    Admin {
+        page: String,
+    },
+    /// The email/password login page, where signing out ends up.
+    Login,
+}
+
+impl Route {
+    pub const fn new_chat() -> Self {
+        Self::NewChat { assistant: None }
+    }
+
+    pub fn admin(page: impl Into<String>) -> Self {
+        Self::Admin { page: page.into() }
+    }
+
+    /// The route on `server`.
+    pub fn url(&self, server: &Url) -> Url {
+        let mut url = server.clone();
+        url.set_query(None);
+        url.set_fragment(None);
+        match self {
+            Self::Chat { id } => {
+                url.set_path(CHAT_PATH);
+                url.query_pairs_mut().append_pair("chatId", id);
+            }
+            Self::NewChat { assistant } => {
+                url.set_path(CHAT_PATH);
+                if let Some(assistant) = assistant {
+                    url.query_pairs_mut()
+                        .append_pair("assistantId", &assistant.to_string());
+                }
+            }
+            Self::Search { query } => {
+                url.set_path(CHAT_PATH);
+                url.query_pairs_mut()
+                    .append_pair("user-prompt", query.trim())
+                    .append_pair("send-on-load", "true");
+            }
+            Self::Draft { message } => {
+                url.set_path(CHAT_PATH);
+                url.query_pairs_mut().append_pair("user-prompt", message);
+            }
+            Self::Admin { page } => {
+                url.set_path(&format!("{ADMIN_PATH}/{}", page.trim_start_matches('/')));
+            }
+            Self::Login => url.set_path(LOGIN_PATH),
+        }
+        url
+    }
+
+    /// The route `url` is on, if it's one of these.
+    pub fn from_url(url: &Url) -> Option<Self> {
+        let path = url.path().trim_end_matches('/');
+        if let Some(page) = path
+            .strip_prefix(ADMIN_PATH)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .filter(|page| !page.is_empty())
+        {
+            return Some(Self::admin(page));
+        }
+        if path == LOGIN_PATH {
+            return Some(Self::Login);
+        }
+        if path != CHAT_PATH && path != APP_PATH {
+            return None;
+        }
+        let query = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+        if let Some(id) = query("chatId") {
+            return Some(Self::Chat { id });
+        }
+        if let Some(prompt) = query("user-prompt") {
+            return Some(if query("send-on-load").as_deref() == Some("true") {
+                Self::Search { query: prompt }
+            } else {
+                Self::Draft { message: prompt }
+            });
+        }
+        Some(Self::NewChat {
+            assistant: query("assistantId").and_then(|id| id.parse().ok()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn routes_build_and_read_back_their_urls() {
+        let server = Url::parse("https://onyx.example.com/app?chatId=9#top").unwrap();
+        let routes = [
+            (
+                Route::Chat {
+                    id: "a b".to_string(),
+                },
+                "https://onyx.example.com/chat?chatId=a+b",
+            ),
+            (Route::new_chat(), "https://onyx.example.com/chat"),
+            (
+                Route::NewChat { assistant: Some(3) },
+                "https://onyx.example.com/chat?assistantId=3",
+            ),
+            (
+                Route::Search {
+                    query: " quarterly revenue ".to_string(),
+                },
+                "https://onyx.example.com/chat?user-prompt=quarterly+revenue&send-on-load=true",
+            ),
+            (
+                Route::Draft {
+                    message: "> Q3 is up\n\nWhy?".to_string(),
+                },
+                "https://onyx.example.com/chat?user-prompt=%3E+Q3+is+up%0A%0AWhy%3F",
+            ),
+            (
+                Route::admin("connector/12"),
+                "https://onyx.example.com/admin/connector/12",
+            ),
+            (Route::Login, "https://onyx.example.com/auth/login"),
+        ];
+        for (route, expected) in routes {
+            let url = route.url(&server);
+            assert_eq!(url.as_str(), expected);
+            let read_back = Route::from_url(&url).unwrap();
+            match route {
+                Route::Search { query } => {
+                    assert_eq!(
+                        read_back,
+                        Route::Search {
+                            query: query.trim().to_string()
+                        }
+                    );
+                }
+                route => assert_eq!(read_back, route),
+            }
+        }
+
+        assert_eq!(
+            Route::from_url(&server),
+            Some(Route::Chat {
+                id: "9".to_string()
+            })
+        );
+        let other = Url::parse("https://onyx.example.com/settings?chatId=9").unwrap();
+        assert_eq!(Route::from_url(&other), None);
+    }
+}
@@ -0,0 +1,107 @@
+// Linux app sandboxes (Flatpak, Snap). Inside one, `xdg-open` is either
+// missing or can't see the app's private files, and GTK's built-in file
+// chooser only browses the sandbox -- both fail without telling anyone. The
+// XDG desktop portals are the sanctioned way out: they hand links and files
+// to the host and show the host's own file picker. GIO talks to them for
+// us, so links and files are launched through GIO when sandboxed and GTK is
+// told to prefer portal dialogs.
+//
+// Outside Linux nothing is ever reported as sandboxed, so callers don't need
+// their own `cfg`s around `sandbox()`.
+
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sandbox {
+    Flatpak,
+    Snap,
+}
+
+static SANDBOX: OnceLock<Option<Sandbox>> = OnceLock::new();
+
+/// Flatpak mounts `/.flatpak-info` into every sandbox; snapd sets `SNAP` to
+/// the snap's mount point.
+fn detect(flatpak_info_exists: bool, snap: Option<&OsStr>) -> Option<Sandbox> {
+    if flatpak_info_exists {
+        Some(Sandbox::Flatpak)
+    } else if snap.is_some_and(|snap| !snap.is_empty()) {
+        Some(Sandbox::Snap)
+    } else {
+        None
+    }
+}
+
+/// The sandbox this process runs in, if any.
+pub fn sandbox() -> Option<Sandbox> {
+    *SANDBOX.get_or_init(|| {
+        if cfg!(target_os = "linux") {
+            detect(
+                Path::new("/.flatpak-info").exists(),
+                std::env::var_os("SNAP").as_deref(),
+            )
+        } else {
+            None
+        }
+    })
+}
+
+/// Route GTK's dialogs -- notably the file chooser behind the webview's
+/// `<input type="file">` uploads -- through the portals. Flatpak's GTK does
+/// this on its own, Snap's doesn't. Must run before GTK initializes, while
+/// the process is still single-threaded.
+// Only has a body on Linux.
+#[cfg_attr(not(target_os = "linux"), allow(clippy::missing_const_for_fn))]
+pub fn prefer_portals() {
+    #[cfg(target_os = "linux")]
+    if sandbox().is_some() && std::env::var_os("GTK_USE_PORTAL").is_none() {
+        std::env::set_var("GTK_USE_PORTAL", "1");
+    }
+}
+
+/// Hand `uri` (a web link or a `file://` URI) to the host's default
+/// handler through the `OpenURI` portal.
+#[cfg(target_os = "linux")]
+pub fn open_uri(uri: &str) -> Result<(), String> {
+    gtk::gio::AppInfo::launch_default_for_uri(uri, None::<&gtk::gio::AppLaunchContext>)
+        .map_err(|e| e.to_string())
+}
+
+/// Open a file or folder with the user's default app: through the portal
+/// when sandboxed (the host can't see our paths), `xdg-open` otherwise.
+#[cfg(target_os = "linux")]
+pub fn open_path(path: &Path) -> Result<(), String> {
+    if sandbox().is_some() {
+        let uri = url::Url::from_file_path(path)
+            .map_err(|()| format!("Not an absolute path: {}", path.display()))?;
+        return open_uri(uri.as_str());
+    }
+
+    std::process::Command::new("xdg-open")
+        .arg(path)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_flatpak_before_snap() {
+        assert_eq!(detect(false, None), None);
+        assert_eq!(detect(false, Some(OsStr::new(""))), None);
+        assert_eq!(
+            detect(false, Some(OsStr::new("/snap/onyx/12"))),
+            Some(Sandbox::Snap)
+        );
+        assert_eq!(
+            detect(true, Some(OsStr::new("/snap/onyx/12"))),
+            Some(Sandbox::Flatpak)
+        );
+    }
+}
@@ -0,0 +1,386 @@
+// Prompts run at set times, kept in the config as `[[schedules]]`: each has
+// a name, a prompt, and a cron schedule in local time (`0 9 * * 1-5` is
+// 9:00 on weekdays; `@hourly`, `@daily`, `@weekly` and `@monthly` work too).
+// When one comes due the prompt is asked in a new chat on the server, as
+// the main window's user, and a dialog shows the start of the answer with a
+// button to open the chat. One with `remind_only` isn't asked: the dialog
+// offers it in a new chat to edit and send.
+//
+// When each schedule was last checked is kept in `schedules.json` in the
+// state directory, so a run missed while the computer slept or Onyx was
+// closed happens once on the next check, if it was due within `CATCH_UP`;
+// older ones are skipped. A run that fails (offline, logged out, locked)
+// stays due and is tried again at the next check.
+
+use crate::admin::open_page;
+use crate::config::{get_state_dir, validate_server_url, ConfigState, ScheduledPrompt};
+use crate::debug_log::log_backend_error;
+use crate::lock::AppLock;
+use crate::route::Route;
+use crate::server_api::{ask, window_credentials};
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeDelta, Timelike};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::{
+    DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult,
+};
+
+const STATE_FILE: &str = "schedules.json";
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// How late a missed run can still happen.
+const CATCH_UP: TimeDelta = TimeDelta::days(1);
+const MAX_SCHEDULES: usize = 50;
+const MAX_PROMPT_CHARS: usize = 20_000;
+/// How much of the answer the dialog shows.
+const PREVIEW_CHARS: usize = 400;
+const OPEN_LABEL: &str = "Open Chat";
+const ASK_LABEL: &str = "Ask";
+const DISMISS_LABEL: &str = "Dismiss";
+
+/// The minutes, hours, days, months and weekdays a cron schedule matches,
+/// as bit sets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    /// Sunday is 0.
+    weekdays: u64,
+    /// Whether day of month and day of week were both restricted, in which
+    /// case either matching is enough, as in cron.
+    either_day: bool,
+}
+
+/// The bits for one cron field: `*`, a number, a range `a-b`, any of those
+/// with a step `/n`, or a list of them separated by commas.
+fn parse_field(field: &str, min: u32, max: u32) -> Option<u64> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<usize>().ok().filter(|&s| s > 0)?),
+            None => (part, 1),
+        };
+        let (first, last) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((first, last)) => (first.parse().ok()?, last.parse().ok()?),
+                // `5/15` runs from 5 to the end, as in cron.
+                None if part.contains('/') => (range.parse().ok()?, max),
+                None => {
+                    let value = range.parse().ok()?;
+                    (value, value)
+                }
+            },
+        };
+        if first < min || last > max || first > last {
+            return None;
+        }
+        for value in (first..=last).step_by(step) {
+            bits |= 1 << value;
+        }
+    }
+    Some(bits)
+}
+
+impl Cron {
+    pub fn parse(schedule: &str) -> Option<Self> {
+        let schedule = match schedule.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            schedule => schedule,
+        };
+        let fields: Vec<&str> = schedule.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields.as_slice() else {
+            return None;
+        };
+        let mut weekday_bits = parse_field(weekdays, 0, 7)?;
+        // 7 is Sunday too.
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits = (weekday_bits & !(1 << 7)) | 1;
+        }
+        Some(Self {
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays: weekday_bits,
+            either_day: !days.starts_with('*') && !weekdays.starts_with('*'),
+        })
+    }
+
+    fn matches(&self, time: NaiveDateTime) -> bool {
+        let has = |bits: u64, value: u32| bits & (1 << value) != 0;
+        let day = has(self.days, time.day());
+        let weekday = has(self.weekdays, time.weekday().num_days_from_sunday());
+        has(self.minutes, time.minute())
+            && has(self.hours, time.hour())
+            && has(self.months, time.month())
+            && if self.either_day {
+                day || weekday
+            } else {
+                day && weekday
+            }
+    }
+
+    /// The latest minute the schedule matches after `after` and up to
+    /// `now`, going back no further than `CATCH_UP`.
+    fn last_due(&self, after: NaiveDateTime, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let earliest = after.max(now - CATCH_UP);
+        let mut minute = now.with_second(0)?.with_nanosecond(0)?;
+        while minute > earliest {
+            if self.matches(minute) {
+                return Some(minute);
+            }
+            minute -= TimeDelta::minutes(1);
+        }
+        None
+    }
+}
+
+pub fn validate(schedules: &[ScheduledPrompt]) -> Result<(), String> {
+    if schedules.len() > MAX_SCHEDULES {
+        return Err(format!(
+            "There can be up to {MAX_SCHEDULES} scheduled prompts"
+        ));
+    }
+    let mut names = HashSet::new();
+    for scheduled in schedules {
+        let name = scheduled.name.trim();
+        if name.is_empty() {
+            return Err("Every scheduled prompt needs a name".to_string());
+        }
+        if !names.insert(name.to_lowercase()) {
+            return Err(format!(
+                "There are two scheduled prompts named \u{201c}{name}\u{201d}"
+            ));
+        }
+        if scheduled.prompt.trim().is_empty() {
+            return Err(format!(
+                "The scheduled prompt \u{201c}{name}\u{201d} is empty"
+            ));
+        }
+        if scheduled.prompt.chars().count() > MAX_PROMPT_CHARS {
+            return Err(format!(
+                "The scheduled prompt \u{201c}{name}\u{201d} is longer than {MAX_PROMPT_CHARS} \
+                 characters"
+            ));
+        }
+        if Cron::parse(&scheduled.schedule).is_none() {
+            return Err(format!(
+                "The scheduled prompt \u{201c}{name}\u{201d}: \u{201c}{}\u{201d} isn't a cron \
+                 schedule (minute hour day month weekday, such as 0 9 * * 1-5)",
+                scheduled.schedule
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn state_path() -> Option<PathBuf> {
+    get_state_dir().map(|dir| dir.join(STATE_FILE))
+}
+
+/// When each schedule was last checked, by name, in seconds since the Unix
+/// epoch; empty if nothing's saved or it can't be read.
+fn load_checked() -> BTreeMap<String, i64> {
+    state_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save_checked(checked: &BTreeMap<String, i64>) -> Result<(), String> {
+    let path = state_path().ok_or("Could not determine the state directory")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(checked).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| e.to_string())
+}
+
+fn local_time(timestamp: i64) -> Option<NaiveDateTime> {
+    DateTime::from_timestamp(timestamp, 0).map(|utc| utc.with_timezone(&Local).naive_local())
+}
+
+/// Ask `scheduled`'s prompt, or remind of it, and show the outcome.
+async fn run(app: &AppHandle, scheduled: &ScheduledPrompt) -> Result<(), String> {
+    if app.state::<AppLock>().is_locked() {
+        return Err("Onyx is locked".to_string());
+    }
+    let config = app.state::<ConfigState>().config();
+    let server = validate_server_url(&config.server_url)?;
+    let window = app.get_webview_window("main").ok_or("No main window")?;
+    let name = scheduled.name.trim().to_string();
+
+    if scheduled.remind_only {
+        let route = Route::Draft {
+            message: scheduled.prompt.clone(),
+        };
+        show(
+            app,
+            &name,
+            scheduled.prompt.trim().to_string(),
+            ASK_LABEL,
+            move |app| {
+                open_page(app, &window, &server, &route);
+            },
+        );
+        return Ok(());
+    }
+
+    let credentials = window_credentials(&window, &server)?.ok_or("Not signed in")?;
+    let answer = ask(&config, &server, &credentials, &scheduled.prompt).await?;
+    let text = answer.text.as_deref().unwrap_or_default().trim();
+    let mut preview: String = text.chars().take(PREVIEW_CHARS).collect();
+    if preview.len() < text.len() {
+        preview.push('\u{2026}');
+    }
+    let route = Route::Chat {
+        id: answer.chat_session_id,
+    };
+    show(app, &name, preview, OPEN_LABEL, move |app| {
+        open_page(app, &window, &server, &route);
+    });
+    Ok(())
+}
+
+/// Show `message` under `title`, calling `open` if the `open_label` button
+/// is pressed; or keep it for later under Do Not Disturb.
+fn show(
+    app: &AppHandle,
+    title: &str,
+    message: String,
+    open_label: &'static str,
+    open: impl FnOnce(&AppHandle) + Send + 'static,
+) {
+    let title = title.to_string();
+    crate::do_not_disturb::notify(app, &title.clone(), &message.clone(), move |app| {
+        let handle = app.clone();
+        app.dialog()
+            .message(message)
+            .title(title)
+            .kind(MessageDialogKind::Info)
+            .buttons(MessageDialogButtons::OkCancelCustom(
+                open_label.to_string(),
+                DISMISS_LABEL.to_string(),
+            ))
+            .show_with_result(move |result| {
+                let chosen = match result {
+                    MessageDialogResult::Ok => true,
+                    MessageDialogResult::Custom(label) => label == open_label,
+                    _ => false,
+                };
+                if chosen {
+                    open(&handle);
+                }
+            });
+    });
+}
+
+/// Run whatever schedules have come due since they were last checked.
+async fn check(app: &AppHandle) {
+    let schedules = app.state::<ConfigState>().config().schedules;
+    let now = Local::now();
+    let mut checked = load_checked();
+    let before = checked.clone();
+    // Schedules taken out of the config are forgotten.
+    checked.retain(|name, _| schedules.iter().any(|s| s.name.trim() == name));
+
+    for scheduled in &schedules {
+        let name = scheduled.name.trim().to_string();
+        let Some(cron) = Cron::parse(&scheduled.schedule) else {
+            continue;
+        };
+        // New schedules start from now rather than catching up.
+        let Some(after) = checked.get(&name).and_then(|&at| local_time(at)) else {
+            checked.insert(name, now.timestamp());
+            continue;
+        };
+        if cron.last_due(after, now.naive_local()).is_some() {
+            if let Err(e) = run(app, scheduled).await {
+                log_backend_error(
+                    app,
+                    &format!("Failed to run the scheduled prompt \u{201c}{name}\u{201d}: {e}"),
+                );
+                continue;
+            }
+        }
+        checked.insert(name, now.timestamp());
+    }
+
+    if checked != before {
+        if let Err(e) = save_checked(&checked) {
+            log_backend_error(app, &format!("Failed to save the schedules: {e}"));
+        }
+    }
+}
+
+/// Check the schedules for as long as the app runs. Only for the copy that
+/// holds the data directory.
+pub fn start_checking(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            check(&app).await;
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::unwrap_used)]
+    fn at(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn cron_schedules_match_and_catch_up_once() {
+        let weekdays = Cron::parse("0 9 * * 1-5").unwrap();
+        // 2026-10-16 is a Friday.
+        assert!(weekdays.matches(at("2026-10-16 09:00")));
+        assert!(!weekdays.matches(at("2026-10-17 09:00")));
+        assert!(!weekdays.matches(at("2026-10-16 09:01")));
+
+        let steps = Cron::parse("*/15 8-17/3 1,15 * 7").unwrap();
+        // The 15th or a Sunday, as in cron.
+        assert!(steps.matches(at("2026-10-15 14:45")));
+        assert!(steps.matches(at("2026-10-18 08:30")));
+        assert!(!steps.matches(at("2026-10-16 08:30")));
+        assert!(!steps.matches(at("2026-10-15 09:00")));
+        assert_eq!(Cron::parse("@daily"), Cron::parse("0 0 * * *"));
+        for bad in [
+            "",
+            "0 9 * *",
+            "60 * * * *",
+            "0 9 * * 8",
+            "5-1 * * * *",
+            "*/0 * * * *",
+        ] {
+            assert_eq!(Cron::parse(bad), None, "{bad}");
+        }
+
+        // Asleep from Thursday evening to Friday 10:30: the 9:00 run is due.
+        assert_eq!(
+            weekdays.last_due(at("2026-10-15 18:00"), at("2026-10-16 10:30")),
+            Some(at("2026-10-16 09:00"))
+        );
+        assert_eq!(
+            weekdays.last_due(at("2026-10-16 09:00"), at("2026-10-16 10:30")),
+            None
+        );
+        // Missed for longer than `CATCH_UP`: skipped.
+        assert_eq!(
+            weekdays.last_due(at("2026-10-12 18:00"), at("2026-10-17 10:30")),
+            None
+        );
+    }
+}
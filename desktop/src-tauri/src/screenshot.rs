@@ -0,0 +1,392 @@
+// Screenshots of a window's page, for bug reports. Each engine has its own
+// snapshot call (WebKitGTK's `snapshot`, WKWebView's
+// `takeSnapshotWithConfiguration`, WebView2's `CapturePreview`), all
+// asynchronous and all on the main thread; each one ends up as PNG bytes
+// here. With `blur_text`, the page reports where its text is drawn and
+// those regions are pixelated before the image leaves this module, so a
+// report can show the layout without the conversation.
+
+use crate::consent::{request_consent, Capability};
+use base64::Engine;
+use serde::Deserialize;
+use std::io::Cursor;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{Manager, WebviewWindow};
+use tokio::sync::oneshot;
+
+/// How long the page gets to list its text.
+const TEXT_REGIONS_TIMEOUT: Duration = Duration::from_secs(5);
+/// Side of the squares text is pixelated into, in image pixels.
+const PIXEL_CELL: usize = 12;
+
+/// Where the page draws text: rectangles in CSS pixels of a viewport
+/// `width` wide. Text typed into fields counts too.
+const TEXT_REGIONS_SCRIPT: &str = r#"(() => {
+  const rects = [];
+  const add = (r) => {
+    if (r.width > 0 && r.height > 0 && r.bottom > 0 && r.right > 0 &&
+        r.top < innerHeight && r.left < innerWidth && rects.length < 5000) {
+      rects.push([r.left, r.top, r.width, r.height]);
+    }
+  };
+  const walker = document.createTreeWalker(document.body, NodeFilter.SHOW_TEXT);
+  const range = document.createRange();
+  while (walker.nextNode()) {
+    if (!walker.currentNode.textContent.trim()) continue;
+    range.selectNodeContents(walker.currentNode);
+    for (const r of range.getClientRects()) add(r);
+  }
+  document.querySelectorAll("input, textarea, [contenteditable]").forEach((el) => {
+    add(el.getBoundingClientRect());
+  });
+  return JSON.stringify({ width: innerWidth, rects });
+})()"#;
+
+#[derive(Debug, Deserialize)]
+struct TextRegions {
+    width: f64,
+    rects: Vec<[f64; 4]>,
+}
+
+/// One sender shared by callbacks the engines might call more than once;
+/// only the first answer counts.
+type Reply<T> = Mutex<Option<oneshot::Sender<Result<T, String>>>>;
+
+fn reply<T>(reply: &Reply<T>, result: Result<T, String>) {
+    let sender = reply
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .take();
+    if let Some(sender) = sender {
+        let _ = sender.send(result);
+    }
+}
+
+#[cfg(target_os = "linux")]
+async fn snapshot_png(window: &WebviewWindow) -> Result<Vec<u8>, String> {
+    use gtk::cairo::ImageSurface;
+    use webkit2gtk::{SnapshotOptions, SnapshotRegion, WebViewExt};
+
+    let (tx, rx) = oneshot::channel();
+    let tx: Reply<Vec<u8>> = Mutex::new(Some(tx));
+    window
+        .with_webview(move |webview| {
+            webview.inner().snapshot(
+                SnapshotRegion::Visible,
+                SnapshotOptions::NONE,
+                None::<&gtk::gio::Cancellable>,
+                move |result| {
+                    let png = result.map_err(|e| e.to_string()).and_then(|surface| {
+                        let surface = ImageSurface::try_from(surface)
+                            .map_err(|_| "The snapshot isn't an image".to_string())?;
+                        let mut png = Vec::new();
+                        surface.write_to_png(&mut png).map_err(|e| e.to_string())?;
+                        Ok(png)
+                    });
+                    reply(&tx, png);
+                },
+            );
+        })
+        .map_err(|e| e.to_string())?;
+    rx.await
+        .map_err(|_| "The webview dropped the snapshot".to_string())?
+}
+
+#[cfg(target_os = "macos")]
+async fn snapshot_png(window: &WebviewWindow) -> Result<Vec<u8>, String> {
+    use block2::RcBlock;
+    use objc2_app_kit::{NSBitmapImageFileType, NSBitmapImageRep, NSImage};
+    use objc2_foundation::{NSDictionary, NSError};
+    use objc2_web_kit::WKWebView;
+
+    fn png(image: &NSImage) -> Result<Vec<u8>, String> {
+        let tiff = image
+            .TIFFRepresentation()
+            .ok_or("The snapshot has no image data")?;
+        let bitmap = NSBitmapImageRep::imageRepWithData(&tiff).ok_or("Unreadable snapshot")?;
+        // SAFETY: an empty dictionary is a valid set of (no) properties.
+        let png = unsafe {
+            bitmap.representationUsingType_properties(
+                NSBitmapImageFileType::PNG,
+                &NSDictionary::new(),
+            )
+        }
+        .ok_or("Couldn't encode the snapshot")?;
+        Ok(png.to_vec())
+    }
+
+    let (tx, rx) = oneshot::channel();
+    let tx: Reply<Vec<u8>> = Mutex::new(Some(tx));
+    window
+        .with_webview(move |webview| {
+            let handler = RcBlock::new(move |image: *mut NSImage, error: *mut NSError| {
+                // SAFETY: WebKit passes either a valid image or a valid
+                // error, each only for the duration of the call.
+                let result = match unsafe { (image.as_ref(), error.as_ref()) } {
+                    (Some(image), _) => png(image),
+                    (None, Some(error)) => Err(error.localizedDescription().to_string()),
+                    (None, None) => Err("The webview returned no snapshot".to_string()),
+                };
+                reply(&tx, result);
+            });
+            // SAFETY: `inner` is wry's `WKWebView`, alive while the window
+            // is, and this runs on the main thread as WebKit requires.
+            unsafe {
+                let webview = &*webview.inner().cast::<WKWebView>();
+                webview.takeSnapshotWithConfiguration_completionHandler(None, &handler);
+            }
+        })
+        .map_err(|e| e.to_string())?;
+    rx.await
+        .map_err(|_| "The webview dropped the snapshot".to_string())?
+}
+
+#[cfg(target_os = "windows")]
+async fn snapshot_png(window: &WebviewWindow) -> Result<Vec<u8>, String> {
+    use std::sync::Arc;
+    use webview2_com::CapturePreviewCompletedHandler;
+    use webview2_com::Microsoft::Web::WebView2::Win32::COREWEBVIEW2_CAPTURE_PREVIEW_IMAGE_FORMAT_PNG;
+    use windows::Win32::Foundation::HGLOBAL;
+    use windows::Win32::System::Com::StructuredStorage::CreateStreamOnHGlobal;
+    use windows::Win32::System::Com::{IStream, STREAM_SEEK_SET};
+
+    fn read_all(stream: &IStream) -> Result<Vec<u8>, String> {
+        let mut png = Vec::new();
+        let mut chunk = vec![0_u8; 64 * 1024];
+        // SAFETY: `chunk` outlives each read, which writes at most its
+        // length and reports how much it wrote.
+        unsafe {
+            stream
+                .Seek(0, STREAM_SEEK_SET, None)
+                .map_err(|e| e.to_string())?;
+            loop {
+                let mut read = 0_u32;
+                stream
+                    .Read(chunk.as_mut_ptr().cast(), 64 * 1024, Some(&raw mut read))
+                    .ok()
+                    .map_err(|e| e.to_string())?;
+                if read == 0 {
+                    return Ok(png);
+                }
+                png.extend_from_slice(&chunk[..read as usize]);
+            }
+        }
+    }
+
+    let (tx, rx) = oneshot::channel();
+    let tx: Arc<Reply<Vec<u8>>> = Arc::new(Mutex::new(Some(tx)));
+    window
+        .with_webview(move |webview| {
+            let on_error = Arc::clone(&tx);
+            // SAFETY: plain COM calls on the webview's own controller, on
+            // the main thread WebView2 was created on.
+            let started = unsafe {
+                CreateStreamOnHGlobal(HGLOBAL::default(), true).and_then(|stream| {
+                    let core = webview.controller().CoreWebView2()?;
+                    let captured = stream.clone();
+                    let handler = CapturePreviewCompletedHandler::create(Box::new(move |result| {
+                        reply(
+                            &tx,
+                            result
+                                .map_err(|e| e.to_string())
+                                .and_then(|()| read_all(&captured)),
+                        );
+                        Ok(())
+                    }));
+                    core.CapturePreview(
+                        COREWEBVIEW2_CAPTURE_PREVIEW_IMAGE_FORMAT_PNG,
+                        &stream,
+                        &handler,
+                    )
+                })
+            };
+            if let Err(e) = started {
+                reply(&on_error, Err(e.to_string()));
+            }
+        })
+        .map_err(|e| e.to_string())?;
+    rx.await
+        .map_err(|_| "The webview dropped the snapshot".to_string())?
+}
+
+/// Ask `window`'s page where it draws text.
+async fn text_regions(window: &WebviewWindow) -> Result<TextRegions, String> {
+    let (tx, rx) = oneshot::channel();
+    let tx: Reply<String> = Mutex::new(Some(tx));
+    window
+        .eval_with_callback(TEXT_REGIONS_SCRIPT, move |result| reply(&tx, Ok(result)))
+        .map_err(|e| e.to_string())?;
+    let result = tokio::time::timeout(TEXT_REGIONS_TIMEOUT, rx)
+        .await
+        .map_err(|_| "The page didn't answer".to_string())?
+        .map_err(|_| "The page didn't answer".to_string())??;
+    parse_text_regions(&result)
+}
+
+/// The script's answer, which some engines hand back JSON-encoded once more
+/// (as a string).
+fn parse_text_regions(result: &str) -> Result<TextRegions, String> {
+    let value: serde_json::Value = serde_json::from_str(result).map_err(|e| e.to_string())?;
+    match value {
+        serde_json::Value::String(inner) => serde_json::from_str(&inner),
+        value => serde_json::from_value(value),
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Fill each `cell`-sized square of `rect` (`x`, `y`, `width`, `height` in
+/// pixels, clipped to the image) with its average color. `pixels` holds
+/// `channels` bytes per pixel, rows of `stride` bytes.
+fn pixelate(
+    pixels: &mut [u8],
+    (stride, channels): (usize, usize),
+    (image_width, image_height): (usize, usize),
+    [x, y, width, height]: [usize; 4],
+    cell: usize,
+) {
+    let (x_end, y_end) = ((x + width).min(image_width), (y + height).min(image_height));
+    for cell_y in (y..y_end).step_by(cell) {
+        for cell_x in (x..x_end).step_by(cell) {
+            let rows = cell_y..(cell_y + cell).min(y_end);
+            let columns = cell_x..(cell_x + cell).min(x_end);
+            let offsets = || {
+                rows.clone().flat_map(|row| {
+                    columns
+                        .clone()
+                        .map(move |column| row * stride + column * channels)
+                })
+            };
+            let count = offsets().count();
+            let mut sums = [0_usize; 4];
+            for offset in offsets() {
+                for (channel, sum) in sums.iter_mut().enumerate().take(channels) {
+                    *sum += usize::from(pixels[offset + channel]);
+                }
+            }
+            for offset in offsets() {
+                for (channel, sum) in sums.iter().enumerate().take(channels) {
+                    pixels[offset + channel] = u8::try_from(sum / count).unwrap_or(u8::MAX);
+                }
+            }
+        }
+    }
+}
+
+/// `png` with `regions` pixelated, at the image's scale.
+fn blur_regions(png: &[u8], regions: &TextRegions) -> Result<Vec<u8>, String> {
+    let mut decoder = png::Decoder::new(Cursor::new(png));
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut pixels = vec![
+        0;
+        reader
+            .output_buffer_size()
+            .ok_or("The snapshot is too large")?
+    ];
+    let info = reader.next_frame(&mut pixels).map_err(|e| e.to_string())?;
+    pixels.truncate(info.buffer_size());
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    let scale = if regions.width > 0.0 {
+        f64::from(info.width) / regions.width
+    } else {
+        1.0
+    };
+    for rect in &regions.rects {
+        // Rounded outwards (and a pixel wider) so no edge of a glyph
+        // survives; off-image parts fall away once clipped.
+        let [x, y, w, h] = rect.map(|value| value * scale);
+        // Clamped to zero first, and `as` saturates anything too large.
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let to_pixel = |value: f64| value.max(0.0) as usize;
+        let [left, top] = [to_pixel(x.floor() - 1.0), to_pixel(y.floor() - 1.0)];
+        let [right, bottom] = [
+            to_pixel((x + w).ceil() + 1.0),
+            to_pixel((y + h).ceil() + 1.0),
+        ];
+        pixelate(
+            &mut pixels,
+            (info.line_size, info.color_type.samples()),
+            (width, height),
+            [
+                left,
+                top,
+                right.saturating_sub(left),
+                bottom.saturating_sub(top),
+            ],
+            PIXEL_CELL,
+        );
+    }
+
+    let mut blurred = Vec::new();
+    let mut encoder = png::Encoder::new(&mut blurred, info.width, info.height);
+    encoder.set_color(info.color_type);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer
+        .write_image_data(&pixels)
+        .map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(blurred)
+}
+
+/// A PNG of what `window` shows right now, with its text pixelated when
+/// `blur_text` is set.
+pub async fn capture(window: &WebviewWindow, blur_text: bool) -> Result<Vec<u8>, String> {
+    // Measured first, so the text hasn't moved by the time it's blurred.
+    let regions = if blur_text {
+        Some(text_regions(window).await?)
+    } else {
+        None
+    };
+    let png = snapshot_png(window).await?;
+    match regions {
+        Some(regions) => blur_regions(&png, &regions),
+        None => Ok(png),
+    }
+}
+
+/// A base64 PNG screenshot of the calling window, optionally with its text
+/// blurred
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub async fn capture_window_screenshot(
+    window: WebviewWindow,
+    blur_text: bool,
+) -> Result<String, String> {
+    let app = window.app_handle();
+    if !request_consent(app, &window, Capability::CaptureScreen, None).await? {
+        return Err("Capturing the screen wasn't allowed".to_string());
+    }
+    let png = capture(&window, blur_text).await?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(png))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixelated_cells_take_their_average_color() {
+        // A 4x2 grayscale image; the left 2x2 block is pixelated.
+        let mut pixels = vec![0, 100, 7, 7, 200, 100, 7, 7];
+        pixelate(&mut pixels, (4, 1), (4, 2), [0, 0, 2, 2], 2);
+        assert_eq!(pixels, [100, 100, 7, 7, 100, 100, 7, 7]);
+
+        // Clipped to the image rather than overrunning it.
+        let mut pixels = vec![10, 20, 30, 40];
+        pixelate(&mut pixels, (4, 1), (4, 1), [2, 0, 10, 10], 4);
+        assert_eq!(pixels, [10, 20, 35, 35]);
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn text_regions_may_come_back_encoded_twice() {
+        let direct = parse_text_regions(r#"{"width": 800, "rects": [[1, 2, 3, 4]]}"#).unwrap();
+        let encoded =
+            parse_text_regions(r#""{\"width\": 800, \"rects\": [[1, 2, 3, 4]]}""#).unwrap();
+        assert_eq!(direct.rects, encoded.rects);
+        assert!(parse_text_regions("null").is_err());
+    }
+}
@@ -0,0 +1,185 @@
+// AppleScript support on macOS, so scripts and Shortcuts ("Run AppleScript")
+// can drive the app:
+//
+//     tell application "Onyx" to new chat
+//     tell application "Onyx" to ask "What's our PTO policy?"
+//     tell application "Onyx" to current chat URL
+//
+// The dictionary is `Onyx.sdef` in the bundle's resources. Its commands
+// arrive as Apple events, answered here by a handler added to tao's app
+// delegate (as the Dock menu is), and do what the app's own menus and
+// commands do: `trigger_new_chat` and `open_route`. While the app is locked
+// they fail with an error, and the URL isn't given out.
+
+use crate::lock::AppLock;
+use crate::route::Route;
+use crate::window::{is_bundled_page_window, open_route, trigger_new_chat};
+use objc2::runtime::{AnyObject, Imp, Sel};
+use objc2::{class, msg_send, sel};
+use objc2_foundation::NSString;
+use std::sync::OnceLock;
+use tauri::{AppHandle, Manager};
+
+/// Where the handler finds the app.
+static SCRIPTING_APP: OnceLock<AppHandle> = OnceLock::new();
+
+const fn four_char_code(code: [u8; 4]) -> u32 {
+    u32::from_be_bytes(code)
+}
+
+/// The suite's event class, and its commands' event IDs (see `Onyx.sdef`).
+const EVENT_CLASS: u32 = four_char_code(*b"Onyx");
+const NEW_CHAT: u32 = four_char_code(*b"NewC");
+const ASK: u32 = four_char_code(*b"Ask ");
+const CURRENT_CHAT_URL: u32 = four_char_code(*b"CURL");
+/// `keyDirectObject`, `keyErrorNumber`, and `keyErrorString`.
+const DIRECT_OBJECT: u32 = four_char_code(*b"----");
+const ERROR_NUMBER: u32 = four_char_code(*b"errn");
+const ERROR_STRING: u32 = four_char_code(*b"errs");
+/// `errAEEventFailed`.
+const EVENT_FAILED: i32 = -10_000;
+
+/// Run the command `event_id`, with its direct parameter `text`; the
+/// command's text result, if it has one.
+fn run(app: &AppHandle, event_id: u32, text: Option<String>) -> Result<Option<String>, String> {
+    if app.state::<AppLock>().is_locked() {
+        return Err("Onyx is locked.".to_string());
+    }
+    match event_id {
+        NEW_CHAT => {
+            trigger_new_chat(app);
+            Ok(None)
+        }
+        ASK => {
+            let query = text
+                .filter(|query| !query.trim().is_empty())
+                .ok_or("Say what to ask, as in: ask \"What's our PTO policy?\"")?;
+            open_route(app, &Route::Search { query });
+            Ok(None)
+        }
+        CURRENT_CHAT_URL => {
+            let windows = app.webview_windows();
+            let window = windows
+                .iter()
+                .find(|(label, window)| {
+                    !is_bundled_page_window(label) && window.is_focused().unwrap_or(false)
+                })
+                .map(|(_, window)| window)
+                .or_else(|| windows.get("main"))
+                .ok_or("No chat window is open.")?;
+            let url = window.url().map_err(|e| e.to_string())?;
+            Ok(Some(url.to_string()))
+        }
+        _ => Err("Onyx doesn't know that command.".to_string()),
+    }
+}
+
+/// Answer `reply` with `text` under `keyword`.
+///
+/// # Safety
+///
+/// `reply` has to be an `NSAppleEventDescriptor`, or null.
+unsafe fn reply_with(reply: *mut AnyObject, keyword: u32, text: &str) {
+    if reply.is_null() {
+        return;
+    }
+    let descriptor: *mut AnyObject = msg_send![
+        class!(NSAppleEventDescriptor),
+        descriptorWithString: &*NSString::from_str(text)
+    ];
+    let () = msg_send![reply, setParamDescriptor: descriptor, forKeyword: keyword];
+}
+
+extern "C-unwind" fn handle_event(
+    _delegate: &AnyObject,
+    _cmd: Sel,
+    event: *mut AnyObject,
+    reply: *mut AnyObject,
+) {
+    let Some(app) = SCRIPTING_APP.get() else {
+        return;
+    };
+    // SAFETY: AppKit passes the event and its reply as
+    // `NSAppleEventDescriptor`s (the reply may be null), and handlers run
+    // on the main thread.
+    unsafe {
+        let event_id: u32 = msg_send![event, eventID];
+        let direct: *mut AnyObject = msg_send![event, paramDescriptorForKeyword: DIRECT_OBJECT];
+        let text = direct.as_ref().and_then(|direct| {
+            let text: *mut NSString = msg_send![direct, stringValue];
+            text.as_ref().map(NSString::to_string)
+        });
+        match run(app, event_id, text) {
+            Ok(Some(result)) => reply_with(reply, DIRECT_OBJECT, &result),
+            Ok(None) => {}
+            Err(e) => {
+                reply_with(reply, ERROR_STRING, &e);
+                if !reply.is_null() {
+                    let number: *mut AnyObject = msg_send![
+                        class!(NSAppleEventDescriptor),
+                        descriptorWithInt32: EVENT_FAILED
+                    ];
+                    let () = msg_send![reply, setParamDescriptor: number, forKeyword: ERROR_NUMBER];
+                }
+            }
+        }
+    }
+}
+
+/// Start answering the dictionary's commands. Must run on the main thread,
+/// once.
+pub fn setup(app: &AppHandle) -> Result<(), String> {
+    if SCRIPTING_APP.set(app.clone()).is_err() {
+        return Ok(());
+    }
+    // SAFETY: `handle_event` has the signature Apple event handlers are
+    // called with ("v@:@@": `self`, `_cmd`, the event and the reply), and
+    // is only added to the delegate's own class, which lives as long as the
+    // app.
+    unsafe {
+        let ns_app: *mut AnyObject = msg_send![class!(NSApplication), sharedApplication];
+        let delegate: *mut AnyObject = msg_send![ns_app, delegate];
+        let delegate = delegate.as_ref().ok_or("No app delegate")?;
+        let imp = std::mem::transmute::<
+            extern "C-unwind" fn(&AnyObject, Sel, *mut AnyObject, *mut AnyObject),
+            Imp,
+        >(handle_event);
+        let selector = sel!(handleOnyxScriptEvent:withReplyEvent:);
+        let added = objc2::ffi::class_addMethod(
+            std::ptr::from_ref(delegate.class()).cast_mut(),
+            selector,
+            imp,
+            c"v@:@@".as_ptr(),
+        );
+        if !added.as_bool() {
+            return Err("The app delegate already handles script commands".to_string());
+        }
+        let manager: *mut AnyObject =
+            msg_send![class!(NSAppleEventManager), sharedAppleEventManager];
+        for event_id in [NEW_CHAT, ASK, CURRENT_CHAT_URL] {
+            let () = msg_send![
+                manager,
+                setEventHandler: delegate,
+                andSelector: selector,
+                forEventClass: EVENT_CLASS,
+                andEventID: event_id
+            ];
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_dictionary_uses_the_handled_codes() {
+        let dictionary = include_str!("../Onyx.sdef");
+        for event_id in [NEW_CHAT, ASK, CURRENT_CHAT_URL] {
+            let code = [EVENT_CLASS.to_be_bytes(), event_id.to_be_bytes()].concat();
+            let code = String::from_utf8(code).unwrap_or_default();
+            assert!(dictionary.contains(&format!("code=\"{code}\"")), "{code}");
+        }
+    }
+}
@@ -0,0 +1,29 @@
+(() => {
+  if (window.__ONYX_ACTIVITY__) {
+    return;
+  }
+  window.__ONYX_ACTIVITY__ = true;
+
+  const invoke =
+    window.__TAURI__?.core?.invoke || window.__TAURI_INTERNALS__?.invoke;
+  if (typeof invoke !== "function") {
+    return;
+  }
+
+  // Auto-lock counts in minutes, so telling it now and then is plenty.
+  const REPORT_INTERVAL_MS = 10_000;
+  let lastReport = 0;
+
+  const report = () => {
+    const now = Date.now();
+    if (now - lastReport < REPORT_INTERVAL_MS) {
+      return;
+    }
+    lastReport = now;
+    invoke("report_activity").catch(() => {});
+  };
+
+  for (const type of ["keydown", "pointerdown", "pointermove", "wheel"]) {
+    window.addEventListener(type, report, { capture: true, passive: true });
+  }
+})();
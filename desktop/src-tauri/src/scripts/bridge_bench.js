@@ -0,0 +1,49 @@
+(() => {
+  if (window.__ONYX_BRIDGE_BENCH__) {
+    return;
+  }
+
+  const invoke =
+    window.__TAURI__?.core?.invoke || window.__TAURI_INTERNALS__?.invoke;
+  if (typeof invoke !== "function") {
+    return;
+  }
+
+  const ack = (token) =>
+    invoke("bridge_bench_ack", { token }).catch(() => {});
+
+  let pushesReceived = 0;
+
+  window.__ONYX_BRIDGE_BENCH__ = {
+    // Rust -> webview -> Rust: Rust times the gap between its eval and this ack.
+    pong(seq) {
+      void ack(`rtt-${seq}`);
+    },
+
+    // Rust -> webview throughput: acks once every push has arrived.
+    push(total) {
+      pushesReceived += 1;
+      if (pushesReceived === total) {
+        pushesReceived = 0;
+        void ack("push-done");
+      }
+    },
+
+    // Webview -> Rust -> webview: timed here, reported back in one batch.
+    async measureInvoke(iterations) {
+      const samples = [];
+      for (let i = 0; i < iterations; i += 1) {
+        const start = performance.now();
+        try {
+          await invoke("bridge_bench_ack", { token: "echo" });
+        } catch {
+          continue;
+        }
+        samples.push(performance.now() - start);
+      }
+      await invoke("bridge_bench_report", { invokeSamplesMs: samples }).catch(
+        () => {}
+      );
+    },
+  };
+})();
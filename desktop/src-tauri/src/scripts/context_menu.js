@@ -0,0 +1,50 @@
+// Replaces the webview's own context menu, which is missing or differs per
+// platform, with the desktop's native one (see `context_menu.rs`). Pages
+// that show their own menu (and so cancel the event) keep it.
+(() => {
+  if (window.__ONYX_CONTEXT_MENU__) {
+    return;
+  }
+
+  const invoke =
+    window.__TAURI__?.core?.invoke || window.__TAURI_INTERNALS__?.invoke;
+  if (typeof invoke !== "function") {
+    return;
+  }
+
+  const isEditable = (element) =>
+    element instanceof HTMLElement &&
+    (element.isContentEditable ||
+      (element instanceof HTMLInputElement && !element.readOnly) ||
+      (element instanceof HTMLTextAreaElement && !element.readOnly));
+
+  window.__ONYX_CONTEXT_MENU__ = {
+    // Called by the desktop for "Copy Link".
+    copyText(text) {
+      navigator.clipboard.writeText(text).catch(() => {
+        const field = document.createElement("textarea");
+        field.value = text;
+        field.style.position = "fixed";
+        field.style.opacity = "0";
+        document.body.appendChild(field);
+        field.select();
+        document.execCommand("copy");
+        field.remove();
+      });
+    },
+  };
+
+  window.addEventListener("contextmenu", (event) => {
+    if (event.defaultPrevented) {
+      return;
+    }
+    event.preventDefault();
+    const target = event.target instanceof Element ? event.target : null;
+    const link = target?.closest("a[href]")?.href ?? null;
+    invoke("show_context_menu", {
+      selection: window.getSelection()?.toString() ?? "",
+      link,
+      editable: isEditable(target),
+    }).catch(() => {});
+  });
+})();
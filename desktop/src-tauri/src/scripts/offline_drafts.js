@@ -0,0 +1,144 @@
+// Keeps messages the page couldn't send because the server didn't answer
+// (see `draft_queue.rs`). A send to the chat endpoint that fails at the
+// network is handed to the desktop, which sends it once the server is back;
+// until then a pill at the bottom of the page says what's waiting, and
+// clicking it lists the messages with a way to discard each. The desktop
+// calls `window.__ONYX_DRAFTS__.set(drafts)` after injecting this and
+// whenever the queue changes.
+(() => {
+  if (window.__ONYX_DRAFTS__) {
+    return;
+  }
+
+  const invoke =
+    window.__TAURI__?.core?.invoke || window.__TAURI_INTERNALS__?.invoke;
+  if (typeof invoke !== "function") {
+    return;
+  }
+
+  const SEND_PATH = "/api/chat/send-chat-message";
+
+  const queue = (input, init) => {
+    const request = input instanceof Request ? input : null;
+    const method = (init?.method || request?.method || "GET").toUpperCase();
+    const url = new URL(request ? request.url : String(input), location.href);
+    if (method !== "POST" || url.origin !== location.origin) {
+      return;
+    }
+    if (!url.pathname.endsWith(SEND_PATH) || typeof init?.body !== "string") {
+      return;
+    }
+    let body;
+    try {
+      body = JSON.parse(init.body);
+    } catch {
+      return;
+    }
+    invoke("queue_draft", { request: body }).catch(() => {});
+  };
+
+  const originalFetch = window.fetch.bind(window);
+  window.fetch = async (input, init) => {
+    try {
+      return await originalFetch(input, init);
+    } catch (error) {
+      // Stopping a send aborts it; that isn't the network.
+      if (error?.name !== "AbortError") {
+        queue(input, init);
+      }
+      throw error;
+    }
+  };
+
+  const pill = document.createElement("div");
+  pill.setAttribute("role", "status");
+  Object.assign(pill.style, {
+    position: "fixed",
+    bottom: "16px",
+    left: "50%",
+    transform: "translateX(-50%)",
+    zIndex: "2147483647",
+    maxWidth: "min(480px, calc(100vw - 32px))",
+    padding: "8px 14px",
+    borderRadius: "14px",
+    background: "rgba(30, 30, 30, 0.92)",
+    color: "#fff",
+    font: "13px -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif",
+    boxShadow: "0 4px 16px rgba(0, 0, 0, 0.25)",
+    cursor: "pointer",
+    display: "none",
+  });
+  const summary = document.createElement("div");
+  const list = document.createElement("ul");
+  Object.assign(list.style, {
+    listStyle: "none",
+    margin: "8px 0 0",
+    padding: "0",
+    display: "none",
+    maxHeight: "40vh",
+    overflowY: "auto",
+  });
+  pill.append(summary, list);
+  pill.addEventListener("click", (event) => {
+    if (event.target.tagName !== "BUTTON") {
+      list.style.display = list.style.display === "none" ? "block" : "none";
+    }
+  });
+
+  const render = (drafts) => {
+    pill.style.display = drafts.length ? "block" : "none";
+    summary.textContent =
+      drafts.length === 1
+        ? "1 message is waiting to send. Onyx sends it when the server is back."
+        : `${drafts.length} messages are waiting to send. Onyx sends them when the server is back.`;
+    list.replaceChildren(
+      ...drafts.map((draft) => {
+        const item = document.createElement("li");
+        Object.assign(item.style, {
+          display: "flex",
+          gap: "8px",
+          alignItems: "center",
+          padding: "4px 0",
+          borderTop: "1px solid rgba(255, 255, 255, 0.15)",
+        });
+        const text = document.createElement("span");
+        text.textContent = draft.message;
+        Object.assign(text.style, {
+          flex: "1",
+          overflow: "hidden",
+          textOverflow: "ellipsis",
+          whiteSpace: "nowrap",
+        });
+        const discard = document.createElement("button");
+        discard.textContent = "Discard";
+        Object.assign(discard.style, {
+          font: "inherit",
+          color: "inherit",
+          background: "none",
+          border: "1px solid rgba(255, 255, 255, 0.4)",
+          borderRadius: "6px",
+          padding: "1px 8px",
+          cursor: "pointer",
+        });
+        discard.addEventListener("click", () => {
+          invoke("discard_draft", { id: draft.id }).catch(() => {});
+        });
+        item.append(text, discard);
+        return item;
+      }),
+    );
+  };
+
+  const attach = () => document.body?.append(pill);
+  if (document.body) {
+    attach();
+  } else {
+    document.addEventListener("DOMContentLoaded", attach, { once: true });
+  }
+
+  window.__ONYX_DRAFTS__ = {
+    set(drafts) {
+      render(drafts);
+    },
+  };
+})();
@@ -0,0 +1,24 @@
+// Fetches the server's settings with this page's own session and reports
+// them to the desktop (see `server_features.rs`). Does nothing when logged
+// out or on servers without the endpoint; the next page load tries again.
+(() => {
+  if (window.__ONYX_SERVER_FEATURES_REQUESTED__) {
+    return;
+  }
+  window.__ONYX_SERVER_FEATURES_REQUESTED__ = true;
+
+  const invoke =
+    window.__TAURI__?.core?.invoke || window.__TAURI_INTERNALS__?.invoke;
+  if (typeof invoke !== "function") {
+    return;
+  }
+
+  fetch("/api/settings", { credentials: "include" })
+    .then((response) => (response.ok ? response.json() : null))
+    .then((settings) => {
+      if (settings && typeof settings === "object") {
+        return invoke("report_server_settings", { settings });
+      }
+    })
+    .catch(() => {});
+})();
@@ -0,0 +1,73 @@
+// Fires the shortcuts the menus miss because of the keyboard layout (see
+// `shortcut_keys.rs`): a key whose character the layout doesn't type, like
+// the Cyrillic letter on the R key, is found by its US position instead,
+// and a character typed with AltGr (AZERTY's brackets) by what it types.
+// With `key_matching` set to `position`, the US position always counts.
+// The desktop calls `window.__ONYX_SHORTCUT_KEYS__.set(bindings, matching)`
+// after injecting this and whenever the shortcuts change.
+(() => {
+  if (window.__ONYX_SHORTCUT_KEYS__) {
+    return;
+  }
+
+  const invoke =
+    window.__TAURI__?.core?.invoke || window.__TAURI_INTERNALS__?.invoke;
+  if (typeof invoke !== "function") {
+    return;
+  }
+
+  let bindings = [];
+  let matching = "character";
+
+  window.__ONYX_SHORTCUT_KEYS__ = {
+    set(nextBindings, nextMatching) {
+      bindings = nextBindings;
+      matching = nextMatching;
+    },
+  };
+
+  // Whether `key` is a character a US layout types, rather than a letter
+  // from another script or a dead key.
+  const typesUsCharacter = (key) => /^[\x21-\x7e]$/.test(key);
+
+  document.addEventListener(
+    "keydown",
+    (event) => {
+      if (event.repeat || !(event.ctrlKey || event.metaKey || event.altKey)) {
+        return;
+      }
+      // AltGr arrives as Ctrl+Alt on Windows; it's how the character was
+      // typed rather than part of the shortcut.
+      const altGraph = event.getModifierState?.("AltGraph") ?? false;
+      const held = [
+        event.ctrlKey && !altGraph && "ctrl",
+        event.altKey && !altGraph && "alt",
+        event.shiftKey && "shift",
+        event.metaKey && "meta",
+      ]
+        .filter(Boolean)
+        .join("+");
+      const key = event.key.toLowerCase();
+
+      const binding = bindings.find((binding) => {
+        if (binding.modifiers.join("+") !== held) {
+          return false;
+        }
+        if (binding.character !== null && key === binding.character) {
+          // The menus see the character, unless it took AltGr to type.
+          return matching === "character" && altGraph;
+        }
+        return (
+          binding.code === event.code &&
+          (matching === "position" || !typesUsCharacter(event.key))
+        );
+      });
+      if (!binding) {
+        return;
+      }
+      event.preventDefault();
+      invoke("press_shortcut", { id: binding.id }).catch(() => {});
+    },
+    true,
+  );
+})();
@@ -0,0 +1,308 @@
+// Prompt snippets in the chat box (see `snippets.rs`): `pick()` opens a
+// picker over the page to insert one by name, and typing `;;keyword` and
+// then a space or Tab swaps in the snippet with that keyword. The desktop
+// calls `window.__ONYX_SNIPPETS__.set(snippets)` after injecting this and
+// whenever they change, and `insertText(text)` to put other text in the
+// same way (the clipboard history's picker, see `clipboard_history.rs`,
+// and dictation, see `dictation.rs`). `attachFiles(files)` adds files to
+// the message as if they'd been picked with its attach button (see
+// `share.rs`).
+(() => {
+  if (window.__ONYX_SNIPPETS__) {
+    return;
+  }
+
+  const CHAT_INPUT = "#onyx-chat-input-textbox";
+  const KEYWORD = /;;([\p{L}\p{N}_-]+)$/u;
+  // How long a page that's still rendering gets to show the chat box.
+  const CHAT_INPUT_WAIT_MS = 10000;
+
+  let snippets = [];
+
+  const isTextField = (element) =>
+    element instanceof HTMLTextAreaElement ||
+    (element instanceof HTMLInputElement &&
+      ["text", "search", ""].includes(element.getAttribute("type") || ""));
+
+  // The snippet's text with `{{date}}` filled in, and where in it the caret
+  // goes.
+  const expand = (text) => {
+    const filled = text.replaceAll("{{date}}", new Date().toLocaleDateString());
+    const caret = filled.indexOf("{{cursor}}");
+    const expanded = filled.replaceAll("{{cursor}}", "");
+    return { text: expanded, caret: caret === -1 ? expanded.length : caret };
+  };
+
+  // Put `text` in place of `field`'s text from `start` to `end` the way
+  // typing would, so React sees the change, with the caret `caret` into it.
+  const insert = (field, start, end, { text, caret }) => {
+    const prototype =
+      field instanceof HTMLTextAreaElement
+        ? HTMLTextAreaElement.prototype
+        : HTMLInputElement.prototype;
+    const setValue = Object.getOwnPropertyDescriptor(prototype, "value")?.set;
+    if (!setValue) {
+      return;
+    }
+    field.focus();
+    setValue.call(
+      field,
+      field.value.slice(0, start) + text + field.value.slice(end),
+    );
+    field.dispatchEvent(new Event("input", { bubbles: true }));
+    field.setSelectionRange(start + caret, start + caret);
+  };
+
+  document.addEventListener(
+    "keydown",
+    (event) => {
+      if (
+        (event.key !== " " && event.key !== "Tab") ||
+        event.isComposing ||
+        event.ctrlKey ||
+        event.metaKey ||
+        event.altKey ||
+        !isTextField(event.target)
+      ) {
+        return;
+      }
+      const field = event.target;
+      const before = field.value.slice(0, field.selectionStart);
+      const match = KEYWORD.exec(before);
+      if (!match) {
+        return;
+      }
+      const keyword = match[1].toLowerCase();
+      const snippet = snippets.find(
+        (snippet) => snippet.keyword?.toLowerCase() === keyword,
+      );
+      if (!snippet) {
+        return;
+      }
+      event.preventDefault();
+      insert(
+        field,
+        field.selectionStart - match[0].length,
+        field.selectionEnd,
+        expand(snippet.text),
+      );
+    },
+    true,
+  );
+
+  let picker = null;
+
+  const closePicker = () => {
+    picker?.overlay.remove();
+    picker = null;
+  };
+
+  const openPicker = () => {
+    if (picker) {
+      picker.filter.focus();
+      return;
+    }
+    // Where the snippet goes: the field being typed in, or the chat box.
+    const active = document.activeElement;
+    const field = isTextField(active)
+      ? active
+      : document.querySelector(CHAT_INPUT);
+    const start = field ? field.selectionStart : 0;
+    const end = field ? field.selectionEnd : 0;
+
+    const overlay = document.createElement("div");
+    Object.assign(overlay.style, {
+      position: "fixed",
+      inset: "0",
+      zIndex: "2147483647",
+      display: "flex",
+      justifyContent: "center",
+      alignItems: "flex-start",
+      paddingTop: "15vh",
+      background: "rgba(0, 0, 0, 0.2)",
+      font: "14px -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif",
+    });
+    const panel = document.createElement("div");
+    panel.setAttribute("role", "dialog");
+    panel.setAttribute("aria-label", "Insert Snippet");
+    Object.assign(panel.style, {
+      width: "min(480px, calc(100vw - 32px))",
+      maxHeight: "60vh",
+      display: "flex",
+      flexDirection: "column",
+      borderRadius: "12px",
+      background: "rgba(30, 30, 30, 0.96)",
+      color: "#fff",
+      boxShadow: "0 8px 32px rgba(0, 0, 0, 0.35)",
+      overflow: "hidden",
+    });
+    const filter = document.createElement("input");
+    filter.type = "text";
+    filter.placeholder = "Search snippets";
+    filter.setAttribute("aria-label", "Search snippets");
+    Object.assign(filter.style, {
+      font: "inherit",
+      color: "inherit",
+      background: "transparent",
+      border: "none",
+      borderBottom: "1px solid rgba(255, 255, 255, 0.15)",
+      outline: "none",
+      padding: "12px 14px",
+    });
+    const list = document.createElement("ul");
+    list.setAttribute("role", "listbox");
+    Object.assign(list.style, {
+      listStyle: "none",
+      margin: "0",
+      padding: "6px",
+      overflowY: "auto",
+    });
+    panel.append(filter, list);
+    overlay.append(panel);
+
+    let shown = [];
+    let selected = 0;
+
+    const choose = (snippet) => {
+      closePicker();
+      if (field && snippet) {
+        insert(field, start, end, expand(snippet.text));
+      }
+    };
+
+    const render = () => {
+      const query = filter.value.trim().toLowerCase();
+      shown = snippets.filter(
+        (snippet) =>
+          snippet.name.toLowerCase().includes(query) ||
+          snippet.keyword?.toLowerCase().includes(query),
+      );
+      selected = Math.min(selected, Math.max(shown.length - 1, 0));
+      if (!shown.length) {
+        const empty = document.createElement("li");
+        empty.textContent = snippets.length
+          ? "No snippet matches."
+          : "No snippets yet. Add them in Settings.";
+        Object.assign(empty.style, { padding: "8px", opacity: "0.6" });
+        list.replaceChildren(empty);
+        return;
+      }
+      list.replaceChildren(
+        ...shown.map((snippet, index) => {
+          const item = document.createElement("li");
+          item.setAttribute("role", "option");
+          item.setAttribute("aria-selected", String(index === selected));
+          Object.assign(item.style, {
+            padding: "8px",
+            borderRadius: "8px",
+            cursor: "pointer",
+            background:
+              index === selected ? "rgba(255, 255, 255, 0.12)" : "transparent",
+          });
+          const name = document.createElement("div");
+          name.textContent = snippet.keyword
+            ? `${snippet.name}  ;;${snippet.keyword}`
+            : snippet.name;
+          const preview = document.createElement("div");
+          preview.textContent = snippet.text.split("\n")[0];
+          Object.assign(preview.style, {
+            fontSize: "12px",
+            opacity: "0.6",
+            overflow: "hidden",
+            textOverflow: "ellipsis",
+            whiteSpace: "nowrap",
+          });
+          item.append(name, preview);
+          item.addEventListener("mousedown", (event) => {
+            event.preventDefault();
+            choose(snippet);
+          });
+          return item;
+        }),
+      );
+      list.children[selected]?.scrollIntoView({ block: "nearest" });
+    };
+
+    filter.addEventListener("input", () => {
+      selected = 0;
+      render();
+    });
+    filter.addEventListener("keydown", (event) => {
+      if (event.key === "Escape") {
+        event.preventDefault();
+        closePicker();
+        field?.focus();
+      } else if (event.key === "ArrowDown" || event.key === "ArrowUp") {
+        event.preventDefault();
+        const step = event.key === "ArrowDown" ? 1 : -1;
+        selected = (selected + step + shown.length) % Math.max(shown.length, 1);
+        render();
+      } else if (event.key === "Enter" && !event.isComposing) {
+        event.preventDefault();
+        choose(shown[selected]);
+      }
+    });
+    overlay.addEventListener("mousedown", (event) => {
+      if (event.target === overlay) {
+        closePicker();
+      }
+    });
+
+    picker = { overlay, filter, render };
+    document.body.append(overlay);
+    render();
+    filter.focus();
+  };
+
+  window.__ONYX_SNIPPETS__ = {
+    set(nextSnippets) {
+      snippets = nextSnippets;
+      picker?.render();
+    },
+    pick() {
+      if (document.body) {
+        openPicker();
+      }
+    },
+    async attachFiles(files) {
+      const started = Date.now();
+      while (
+        !document.querySelector(CHAT_INPUT) &&
+        Date.now() - started < CHAT_INPUT_WAIT_MS
+      ) {
+        await new Promise((resolve) => setTimeout(resolve, 200));
+      }
+      const transfer = new DataTransfer();
+      for (const { name, type, data } of files) {
+        const bytes = Uint8Array.from(atob(data), (c) => c.charCodeAt(0));
+        transfer.items.add(new File([bytes], name, { type }));
+      }
+      const input = document.querySelector('input[type="file"]');
+      if (input) {
+        input.files = transfer.files;
+        input.dispatchEvent(new Event("change", { bubbles: true }));
+      } else {
+        // No attach button: drop them on the chat box instead.
+        document.querySelector(CHAT_INPUT)?.dispatchEvent(
+          new DragEvent("drop", {
+            bubbles: true,
+            cancelable: true,
+            dataTransfer: transfer,
+          }),
+        );
+      }
+    },
+    insertText(text) {
+      const active = document.activeElement;
+      const field = isTextField(active)
+        ? active
+        : document.querySelector(CHAT_INPUT);
+      if (field) {
+        insert(field, field.selectionStart, field.selectionEnd, {
+          text,
+          caret: text.length,
+        });
+      }
+    },
+  };
+})();
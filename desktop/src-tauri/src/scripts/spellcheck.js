@@ -0,0 +1,57 @@
+// Turns spellchecking off in the page's text fields while the desktop's
+// `spellcheck.enabled` is off, and back to whatever the page asked for when
+// it's turned on again (see `spellcheck.rs`). The desktop calls
+// `window.__ONYX_SPELLCHECK__.set(enabled)` after injecting this and
+// whenever the setting changes.
+(() => {
+  if (window.__ONYX_SPELLCHECK__) {
+    return;
+  }
+
+  const EDITABLE =
+    'textarea, input:not([type]), input[type="text"], input[type="search"], [contenteditable]:not([contenteditable="false"])';
+  // The page's own `spellcheck` value, kept while it's overridden.
+  const ORIGINAL = "data-onyx-spellcheck";
+  let enabled = true;
+
+  const apply = (element) => {
+    if (!enabled) {
+      if (!element.hasAttribute(ORIGINAL)) {
+        element.setAttribute(ORIGINAL, element.getAttribute("spellcheck") ?? "");
+      }
+      element.setAttribute("spellcheck", "false");
+    } else if (element.hasAttribute(ORIGINAL)) {
+      const original = element.getAttribute(ORIGINAL);
+      if (original) {
+        element.setAttribute("spellcheck", original);
+      } else {
+        element.removeAttribute("spellcheck");
+      }
+      element.removeAttribute(ORIGINAL);
+    }
+  };
+
+  const applyWithin = (root) => {
+    if (root.matches?.(EDITABLE)) {
+      apply(root);
+    }
+    root.querySelectorAll?.(EDITABLE).forEach(apply);
+  };
+
+  // The chat box and other fields come and go as the app renders.
+  new MutationObserver((records) => {
+    if (enabled) {
+      return;
+    }
+    for (const record of records) {
+      record.addedNodes.forEach(applyWithin);
+    }
+  }).observe(document.documentElement, { childList: true, subtree: true });
+
+  window.__ONYX_SPELLCHECK__ = {
+    set(value) {
+      enabled = value;
+      applyWithin(document);
+    },
+  };
+})();
@@ -0,0 +1,87 @@
+(() => {
+  if (window.__ONYX_UI__) {
+    return;
+  }
+
+  // Controls without a `data-testid` of their own, by the identifier tests
+  // use for them.
+  const NAMED = {
+    "chat-input": "#onyx-chat-input-textbox",
+    "send-button": "#onyx-chat-input-send-button",
+  };
+
+  const identify = (element, id) => {
+    element.setAttribute("data-onyx-ui", id);
+    // The accessibility tree reports the DOM id; never take one that's in use.
+    if (!element.id && !document.getElementById(id)) {
+      element.id = id;
+    }
+  };
+
+  const register = () => {
+    for (const [id, selector] of Object.entries(NAMED)) {
+      const element = document.querySelector(selector);
+      if (element && element.getAttribute("data-onyx-ui") !== id) {
+        identify(element, id);
+      }
+    }
+    for (const element of document.querySelectorAll(
+      "[data-testid]:not([data-onyx-ui])"
+    )) {
+      identify(element, element.getAttribute("data-testid"));
+    }
+  };
+
+  const find = (id) =>
+    document.querySelector(`[data-onyx-ui="${CSS.escape(id)}"]`) ||
+    document.getElementById(id);
+
+  // Set the value the way a keystroke would, so React sees the change.
+  const type = (element, text) => {
+    if (element.isContentEditable) {
+      document.execCommand("insertText", false, text);
+      return;
+    }
+    const prototype =
+      element instanceof HTMLTextAreaElement
+        ? HTMLTextAreaElement.prototype
+        : HTMLInputElement.prototype;
+    const setValue = Object.getOwnPropertyDescriptor(prototype, "value")?.set;
+    if (!setValue) {
+      throw new Error("can't be typed into");
+    }
+    setValue.call(element, element.value + text);
+    element.dispatchEvent(new Event("input", { bubbles: true }));
+  };
+
+  window.__ONYX_UI__ = {
+    // Returns an error message, or null once it's done.
+    perform(action, id, text) {
+      register();
+      const element = find(id);
+      if (!element) {
+        return `No element ${id}`;
+      }
+      try {
+        element.scrollIntoView({ block: "nearest" });
+        if (action === "click") {
+          element.click();
+        } else {
+          element.focus();
+          if (action === "type") {
+            type(element, text);
+          }
+        }
+      } catch (e) {
+        return `Couldn't ${action} ${id}: ${e.message}`;
+      }
+      return null;
+    },
+  };
+
+  register();
+  new MutationObserver(register).observe(document.documentElement, {
+    childList: true,
+    subtree: true,
+  });
+})();
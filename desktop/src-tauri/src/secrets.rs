@@ -0,0 +1,229 @@
+// Transparent encryption for sensitive config fields (proxy credentials,
+// header tokens, ...). A field typed `SecretString` is plaintext in memory
+// but serializes as `enc:v1:<base64(nonce || ciphertext || tag)>`, sealed
+// with AES-256-GCM under a random key kept in the OS keychain (macOS
+// Keychain, Windows Credential Manager, Secret Service on Linux). The config
+// file on its own is therefore useless to anyone who copies it. The offline
+// copy of recent chats (see `offline_cache.rs`) is sealed the same way.
+//
+// Config (de)serialization happens before any `AppHandle` exists and inside
+// serde hooks that can't take one, so the key is cached in a process-global
+// rather than managed state.
+
+use aws_lc_rs::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::{ser, Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::OnceLock;
+
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const KEYCHAIN_SERVICE: &str = "app.onyx.desktop";
+const KEYCHAIN_ACCOUNT: &str = "config-encryption-key";
+const KEY_LEN: usize = 32;
+
+static ENCRYPTION_KEY: OnceLock<[u8; KEY_LEN]> = OnceLock::new();
+
+/// Fetch the config encryption key from the OS keychain, generating and
+/// storing one on first use. Only successful lookups are cached, so a
+/// keychain that was locked or unavailable gets retried next time.
+pub fn encryption_key() -> Result<&'static [u8; KEY_LEN], String> {
+    if let Some(key) = ENCRYPTION_KEY.get() {
+        return Ok(key);
+    }
+
+    let entry = keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT)
+        .map_err(|e| format!("Could not access the OS keychain: {e}"))?;
+    let key = match entry.get_secret() {
+        Ok(bytes) => <[u8; KEY_LEN]>::try_from(bytes.as_slice())
+            .map_err(|_| "Config encryption key in the OS keychain is corrupt".to_string())?,
+        Err(keyring::Error::NoEntry) => {
+            let mut key = [0u8; KEY_LEN];
+            aws_lc_rs::rand::fill(&mut key)
+                .map_err(|_| "Failed to generate a config encryption key".to_string())?;
+            entry
+                .set_secret(&key)
+                .map_err(|e| format!("Could not store the config encryption key: {e}"))?;
+            key
+        }
+        Err(e) => return Err(format!("Could not read the config encryption key: {e}")),
+    };
+
+    Ok(ENCRYPTION_KEY.get_or_init(|| key))
+}
+
+fn aead_key(key: &[u8; KEY_LEN]) -> Result<LessSafeKey, String> {
+    UnboundKey::new(&AES_256_GCM, key)
+        .map(LessSafeKey::new)
+        .map_err(|_| "Invalid config encryption key".to_string())
+}
+
+/// Encrypt `plaintext` into the on-disk `enc:v1:` form.
+pub fn seal(key: &[u8; KEY_LEN], plaintext: &str) -> Result<String, String> {
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    aws_lc_rs::rand::fill(&mut nonce_bytes).map_err(|_| "Failed to generate nonce".to_string())?;
+
+    let mut in_out = plaintext.as_bytes().to_vec();
+    aead_key(key)?
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce_bytes),
+            Aad::empty(),
+            &mut in_out,
+        )
+        .map_err(|_| "Failed to encrypt secret".to_string())?;
+
+    let mut payload = nonce_bytes.to_vec();
+    payload.extend_from_slice(&in_out);
+    Ok(format!("{ENCRYPTED_PREFIX}{}", BASE64.encode(payload)))
+}
+
+/// Decrypt an `enc:v1:` value produced by `seal`.
+pub fn open(key: &[u8; KEY_LEN], encoded: &str) -> Result<String, String> {
+    let payload = encoded
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .ok_or("Not an encrypted value")?;
+    let mut payload = BASE64
+        .decode(payload)
+        .map_err(|e| format!("Malformed encrypted value: {e}"))?;
+    if payload.len() < NONCE_LEN {
+        return Err("Malformed encrypted value: too short".to_string());
+    }
+
+    let mut ciphertext = payload.split_off(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(&payload)
+        .map_err(|_| "Malformed encrypted value: bad nonce".to_string())?;
+    let plaintext = aead_key(key)?
+        .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+        .map_err(|_| "Could not decrypt secret (wrong key or tampered value)".to_string())?;
+
+    String::from_utf8(plaintext.to_vec()).map_err(|_| "Decrypted secret is not UTF-8".to_string())
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum Inner {
+    Plain(String),
+    /// Ciphertext we couldn't decrypt (keychain entry lost, config copied
+    /// from another machine). Kept verbatim so saving doesn't destroy it,
+    /// and so a later launch with a working keychain can still read it.
+    Undecryptable(String),
+}
+
+/// A config value that is never written to disk in plaintext.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretString(Inner);
+
+impl SecretString {
+    pub const fn new(value: String) -> Self {
+        Self(Inner::Plain(value))
+    }
+
+    /// The plaintext, or `None` if the stored value couldn't be decrypted.
+    pub fn expose(&self) -> Option<&str> {
+        match &self.0 {
+            Inner::Plain(value) => Some(value),
+            Inner::Undecryptable(_) => None,
+        }
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(<redacted>)")
+    }
+}
+
+impl Serialize for SecretString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.0 {
+            Inner::Plain(value) => {
+                let key = encryption_key().map_err(ser::Error::custom)?;
+                serializer.serialize_str(&seal(key, value).map_err(ser::Error::custom)?)
+            }
+            Inner::Undecryptable(ciphertext) => serializer.serialize_str(ciphertext),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        if !raw.starts_with(ENCRYPTED_PREFIX) {
+            // Hand-entered plaintext; it gets encrypted on the next save.
+            return Ok(Self::new(raw));
+        }
+
+        // Deliberately not a deserialization error: one unreadable secret
+        // shouldn't make the whole config fall back to defaults.
+        Ok(encryption_key()
+            .and_then(|key| open(key, &raw))
+            .map_or_else(|_| Self(Inner::Undecryptable(raw)), Self::new))
+    }
+}
+
+impl JsonSchema for SecretString {
+    fn schema_name() -> Cow<'static, str> {
+        "SecretString".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "Encrypted when the app saves the file; type it in as plain text.",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_KEY: [u8; KEY_LEN] = [7; KEY_LEN];
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn seal_and_open_round_trip_without_leaking_plaintext() {
+        let sealed = seal(&TEST_KEY, "hunter2").unwrap();
+        assert!(sealed.starts_with(ENCRYPTED_PREFIX));
+        assert!(!sealed.contains("hunter2"));
+        assert_eq!(open(&TEST_KEY, &sealed).unwrap(), "hunter2");
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn sealing_twice_uses_fresh_nonces() {
+        assert_ne!(
+            seal(&TEST_KEY, "same").unwrap(),
+            seal(&TEST_KEY, "same").unwrap()
+        );
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn open_rejects_wrong_key_and_tampering() {
+        let sealed = seal(&TEST_KEY, "token").unwrap();
+        assert!(open(&[8; KEY_LEN], &sealed).is_err());
+
+        let mut tampered = sealed.into_bytes();
+        let last = tampered.len() - 2;
+        tampered[last] = if tampered[last] == b'A' { b'B' } else { b'A' };
+        assert!(open(&TEST_KEY, &String::from_utf8(tampered).unwrap()).is_err());
+
+        assert!(open(&TEST_KEY, "enc:v1:AAAA").is_err());
+        assert!(open(&TEST_KEY, "plain").is_err());
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn hand_entered_plaintext_is_accepted() {
+        let secret: SecretString = serde_json::from_str("\"hunter2\"").unwrap();
+        assert_eq!(secret.expose(), Some("hunter2"));
+    }
+
+    #[test]
+    fn debug_output_is_redacted() {
+        let secret = SecretString::new("hunter2".to_string());
+        assert!(!format!("{secret:?}").contains("hunter2"));
+    }
+}
@@ -0,0 +1,654 @@
+// A small client for the few Onyx server endpoints the desktop shell reads
+// itself, outside any webview: its health and version, unread notifications (the
+// badge), recent chats (the Dock menu, the offline cache), whole chats
+// (transcript export, the offline cache), failed connectors and pending
+// approvals (admin mode), plus approving those, transcribing voice input
+// (push to talk), signing out, asking a question outright (headless mode),
+// sending messages written offline (the draft queue), and updating the
+// files in a file connector (the shared local folders).
+// Authenticated calls borrow a window's session cookies -- usually the main
+// window's -- so they see what the user is logged in as there; there's no
+// separate login. Without a window, an API key or the session kept from a
+// browser sign-in stands in (`Credentials`).
+
+use crate::config::{http_client_for, validate_server_url, AppConfig, ConfigState};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, WebviewWindow};
+use url::Url;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Transcribing takes about as long as what was said.
+#[cfg(feature = "voice")]
+const AUDIO_UPLOAD_TIMEOUT: Duration = Duration::from_mins(2);
+/// An answer can take a while to write.
+const ANSWER_TIMEOUT: Duration = Duration::from_mins(5);
+/// A batch of local files can be tens of megabytes.
+#[cfg(feature = "local-files")]
+const FILE_UPLOAD_TIMEOUT: Duration = Duration::from_mins(10);
+
+/// GET `path` on `server` and parse the JSON reply. `Ok(None)` means the
+/// server turned the request away as logged out.
+async fn get_json<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    server: &Url,
+    path: &str,
+    cookies: Option<&str>,
+) -> Result<Option<T>, String> {
+    let url = server.join(path).map_err(|e| e.to_string())?;
+    let mut request = client.get(url);
+    if let Some(cookies) = cookies {
+        request = request.header(reqwest::header::COOKIE, cookies);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    if matches!(
+        response.status(),
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+    ) {
+        return Ok(None);
+    }
+    let text = response
+        .error_for_status()
+        .map_err(|e| e.to_string())?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct VersionResponse {
+    backend_version: String,
+}
+
+/// The configured server's backend version, from its public `/api/version`
+/// endpoint. `Ok(None)` means the server answered but reported no version.
+pub async fn server_version(config: &AppConfig) -> Result<Option<String>, String> {
+    let server = validate_server_url(&config.server_url)?;
+    server_version_at(config, &server).await
+}
+
+/// `server`'s backend version, as `server_version` does for the configured
+/// server.
+pub async fn server_version_at(config: &AppConfig, server: &Url) -> Result<Option<String>, String> {
+    let client = http_client_for(config, server, Duration::from_secs(5))?;
+    let body: Option<VersionResponse> = get_json(&client, server, "/api/version", None).await?;
+    Ok(body
+        .map(|body| body.backend_version)
+        .filter(|version| !version.trim().is_empty()))
+}
+
+#[derive(Deserialize)]
+struct HealthResponse {
+    success: bool,
+}
+
+/// Whether `server`'s public `/api/health` endpoint says it's working. An
+/// error means it didn't answer (or not as an Onyx server).
+pub async fn server_healthy(config: &AppConfig, server: &Url) -> Result<bool, String> {
+    let client = http_client_for(config, server, Duration::from_secs(5))?;
+    let url = server.join("/api/health").map_err(|e| e.to_string())?;
+    let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if response.status().is_server_error() {
+        return Ok(false);
+    }
+    let text = response
+        .error_for_status()
+        .map_err(|e| e.to_string())?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+    let body: HealthResponse = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    Ok(body.success)
+}
+
+/// GET `path` on the configured server as the main window's user, or
+/// `Ok(None)` when they're logged out.
+pub async fn get_as_user<T: DeserializeOwned>(
+    app: &AppHandle,
+    path: &str,
+) -> Result<Option<T>, String> {
+    let config = app.state::<ConfigState>().config();
+    let server = validate_server_url(&config.server_url)?;
+    let window = app.get_webview_window("main").ok_or("No main window")?;
+    get_as_window_user(&window, &server, path).await
+}
+
+/// GET `path` on `server` as the user logged in there in `window`'s webview
+/// data, or `Ok(None)` when they're logged out.
+pub async fn get_as_window_user<T: DeserializeOwned>(
+    window: &WebviewWindow,
+    server: &Url,
+    path: &str,
+) -> Result<Option<T>, String> {
+    let Some(cookies) = cookie_header(window, server)? else {
+        return Ok(None);
+    };
+    let config = window.app_handle().state::<ConfigState>().config();
+    let client = http_client_for(&config, server, REQUEST_TIMEOUT)?;
+    get_json(&client, server, path, Some(&cookies)).await
+}
+
+/// POST `body` as JSON to `path` on `server` as the user logged in there in
+/// `window`'s webview data. `Ok(false)` means they're logged out (or not
+/// allowed to).
+pub async fn post_as_window_user<B: Serialize + Sync>(
+    window: &WebviewWindow,
+    server: &Url,
+    path: &str,
+    body: &B,
+) -> Result<bool, String> {
+    let Some(cookies) = cookie_header(window, server)? else {
+        return Ok(false);
+    };
+    let config = window.app_handle().state::<ConfigState>().config();
+    let client = http_client_for(&config, server, REQUEST_TIMEOUT)?;
+    let body = serde_json::to_string(body).map_err(|e| e.to_string())?;
+    let response = client
+        .post(server.join(path).map_err(|e| e.to_string())?)
+        .header(reqwest::header::COOKIE, cookies)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if matches!(
+        response.status(),
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+    ) {
+        return Ok(false);
+    }
+    response.error_for_status().map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+/// POST `wav` to `path` on `server` as the user logged in there in
+/// `window`'s webview data, and parse the JSON reply. `Ok(None)` means
+/// they're logged out.
+#[cfg(feature = "voice")]
+pub async fn post_audio_as_window_user<T: DeserializeOwned>(
+    window: &WebviewWindow,
+    server: &Url,
+    path: &str,
+    wav: Vec<u8>,
+) -> Result<Option<T>, String> {
+    let Some(cookies) = cookie_header(window, server)? else {
+        return Ok(None);
+    };
+    let config = window.app_handle().state::<ConfigState>().config();
+    let client = http_client_for(&config, server, AUDIO_UPLOAD_TIMEOUT)?;
+    let response = client
+        .post(server.join(path).map_err(|e| e.to_string())?)
+        .header(reqwest::header::COOKIE, cookies)
+        .header(reqwest::header::CONTENT_TYPE, "audio/wav")
+        .body(wav)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    match response.status() {
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => return Ok(None),
+        reqwest::StatusCode::NOT_FOUND => {
+            return Err("This server doesn't transcribe voice".to_string());
+        }
+        _ => {}
+    }
+    let text = response
+        .error_for_status()
+        .map_err(|e| e.to_string())?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+/// End the session `window`'s webview data has on `server`. `Ok(false)`
+/// means it had none to end.
+pub async fn sign_out(window: &WebviewWindow, server: &Url) -> Result<bool, String> {
+    post_as_window_user(window, server, "/api/auth/logout", &serde_json::json!({})).await
+}
+
+/// A connector on the server. (Only the shared local folders look them
+/// up.)
+#[cfg(feature = "local-files")]
+#[derive(Debug, Clone, Deserialize)]
+pub struct Connector {
+    pub id: u64,
+    pub name: String,
+    pub source: String,
+}
+
+/// Every connector on `server`, as `window`'s user sees them, or
+/// `Ok(None)` when they're logged out or not a curator or admin.
+#[cfg(feature = "local-files")]
+pub async fn connectors(
+    window: &WebviewWindow,
+    server: &Url,
+) -> Result<Option<Vec<Connector>>, String> {
+    get_as_window_user(window, server, "/api/manage/connector").await
+}
+
+/// A document set on the server, and the connectors in it. (Only watched
+/// folders look them up.)
+#[cfg(feature = "local-files")]
+#[derive(Debug, Clone, Deserialize)]
+pub struct DocumentSet {
+    pub name: String,
+    pub cc_pair_summaries: Vec<ConnectorPair>,
+}
+
+/// One of a document set's connectors, with the credential it indexes with.
+#[cfg(feature = "local-files")]
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConnectorPair {
+    pub id: u64,
+    pub source: String,
+}
+
+/// The document sets `window`'s user can edit on `server`, or `Ok(None)`
+/// when they're logged out.
+#[cfg(feature = "local-files")]
+pub async fn editable_document_sets(
+    window: &WebviewWindow,
+    server: &Url,
+) -> Result<Option<Vec<DocumentSet>>, String> {
+    get_as_window_user(window, server, "/api/manage/document-set?get_editable=true").await
+}
+
+#[cfg(feature = "local-files")]
+#[derive(Deserialize)]
+struct ConnectorPairInfo {
+    connector: Connector,
+}
+
+/// The connector in the connector pair `pair_id`, or `Ok(None)` when
+/// `window`'s user is logged out or not a curator or admin.
+#[cfg(feature = "local-files")]
+pub async fn pair_connector(
+    window: &WebviewWindow,
+    server: &Url,
+    pair_id: u64,
+) -> Result<Option<Connector>, String> {
+    let info: Option<ConnectorPairInfo> = get_as_window_user(
+        window,
+        server,
+        &format!("/api/manage/admin/cc-pair/{pair_id}"),
+    )
+    .await?;
+    Ok(info.map(|info| info.connector))
+}
+
+#[cfg(feature = "local-files")]
+#[derive(Deserialize)]
+struct ConnectorFiles {
+    file_paths: Vec<String>,
+}
+
+/// Add `files` (name and contents) to the file connector `connector_id`
+/// and take out the files with the IDs in `remove`, as `window`'s user;
+/// the IDs the server gave the added files, in order, or `Ok(None)` when
+/// they're logged out or not allowed to. The server reindexes the
+/// connector afterwards.
+#[cfg(feature = "local-files")]
+pub async fn update_connector_files(
+    window: &WebviewWindow,
+    server: &Url,
+    connector_id: u64,
+    files: Vec<(String, Vec<u8>)>,
+    remove: &[String],
+) -> Result<Option<Vec<String>>, String> {
+    let Some(cookies) = cookie_header(window, server)? else {
+        return Ok(None);
+    };
+    let config = window.app_handle().state::<ConfigState>().config();
+    let client = http_client_for(&config, server, FILE_UPLOAD_TIMEOUT)?;
+    let added = files.len();
+    let mut form = reqwest::multipart::Form::new().text(
+        "file_ids_to_remove",
+        serde_json::to_string(remove).map_err(|e| e.to_string())?,
+    );
+    for (name, bytes) in files {
+        form = form.part(
+            "files",
+            reqwest::multipart::Part::bytes(bytes).file_name(name),
+        );
+    }
+    let path = format!("/api/manage/admin/connector/{connector_id}/files/update");
+    let response = client
+        .post(server.join(&path).map_err(|e| e.to_string())?)
+        .header(reqwest::header::COOKIE, cookies)
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if matches!(
+        response.status(),
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+    ) {
+        return Ok(None);
+    }
+    let text = response
+        .error_for_status()
+        .map_err(|e| e.to_string())?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut files: ConnectorFiles = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    // The connector's whole list comes back, with the new files last.
+    let start = files
+        .file_paths
+        .len()
+        .checked_sub(added)
+        .ok_or("The server's file list is missing the new files")?;
+    Ok(Some(files.file_paths.split_off(start)))
+}
+
+/// Why a chat message didn't go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendError {
+    /// Nothing answered; it may go later.
+    Unreachable(String),
+    /// The server answered and turned it down (or the user is logged out).
+    Rejected(String),
+}
+
+#[derive(Deserialize)]
+struct SentMessage {
+    message_id: i64,
+    #[serde(default)]
+    error_msg: Option<String>,
+}
+
+/// Send a chat message as `window`'s user on `server` and wait for the
+/// whole answer. `request` is the body the page would send, less
+/// streaming; the answer's message ID comes back.
+pub async fn send_chat_message(
+    window: &WebviewWindow,
+    server: &Url,
+    request: &serde_json::Value,
+) -> Result<i64, SendError> {
+    let cookies = cookie_header(window, server)
+        .map_err(SendError::Rejected)?
+        .ok_or_else(|| SendError::Rejected("Not signed in".to_string()))?;
+    let config = window.app_handle().state::<ConfigState>().config();
+    let client = http_client_for(&config, server, ANSWER_TIMEOUT).map_err(SendError::Rejected)?;
+    let mut request = request.clone();
+    request["stream"] = serde_json::Value::Bool(false);
+    let url = server
+        .join("/api/chat/send-chat-message")
+        .map_err(|e| SendError::Rejected(e.to_string()))?;
+    let response = client
+        .post(url)
+        .header(reqwest::header::COOKIE, cookies)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(request.to_string())
+        .send()
+        .await
+        .map_err(|e| SendError::Unreachable(e.to_string()))?;
+    // A server that's up but failing is as good as unreachable for now.
+    if response.status().is_server_error() {
+        return Err(SendError::Unreachable(format!(
+            "The server answered {}",
+            response.status()
+        )));
+    }
+    let text = response
+        .error_for_status()
+        .map_err(|e| SendError::Rejected(e.to_string()))?
+        .text()
+        .await
+        .map_err(|e| SendError::Unreachable(e.to_string()))?;
+    let sent: SentMessage =
+        serde_json::from_str(&text).map_err(|e| SendError::Rejected(e.to_string()))?;
+    if let Some(e) = sent.error_msg.filter(|e| !e.is_empty()) {
+        return Err(SendError::Rejected(e));
+    }
+    Ok(sent.message_id)
+}
+
+/// `window`'s cookies for `server` as a `Cookie` header, or `None` when it
+/// has none (so can't be logged in).
+fn cookie_header(window: &WebviewWindow, server: &Url) -> Result<Option<String>, String> {
+    // Reading cookies deadlocks on Windows from the main thread; callers run
+    // on the async runtime.
+    let cookies = window
+        .cookies_for_url(server.clone())
+        .map_err(|e| e.to_string())?;
+    if cookies.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(
+        cookies
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name(), cookie.value()))
+            .collect::<Vec<_>>()
+            .join("; "),
+    ))
+}
+
+/// Who a request made without a window is from.
+pub enum Credentials {
+    /// An Onyx API key, sent as a bearer token.
+    ApiKey(String),
+    /// A `Cookie` header with the session.
+    Cookie(String),
+}
+
+/// The session of the user logged in to `server` in `window`'s webview
+/// data, to make requests as them without the window, or `None` when
+/// they're logged out.
+pub fn window_credentials(
+    window: &WebviewWindow,
+    server: &Url,
+) -> Result<Option<Credentials>, String> {
+    Ok(cookie_header(window, server)?.map(Credentials::Cookie))
+}
+
+/// POST `body` as JSON to `path` on `server` as `credentials`, and parse
+/// the JSON reply.
+async fn post_json<B: Serialize + Sync, T: DeserializeOwned>(
+    client: &reqwest::Client,
+    server: &Url,
+    path: &str,
+    credentials: &Credentials,
+    body: &B,
+) -> Result<T, String> {
+    let body = serde_json::to_string(body).map_err(|e| e.to_string())?;
+    let request = client
+        .post(server.join(path).map_err(|e| e.to_string())?)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body);
+    let request = match credentials {
+        Credentials::ApiKey(key) => request.bearer_auth(key),
+        Credentials::Cookie(cookies) => request.header(reqwest::header::COOKIE, cookies),
+    };
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    if matches!(
+        response.status(),
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+    ) {
+        return Err(
+            "The server turned the request away: sign in again, or check the API key".to_string(),
+        );
+    }
+    let text = response
+        .error_for_status()
+        .map_err(|e| e.to_string())?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+    serde_json::from_str(&text).map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct CreatedChat {
+    chat_session_id: String,
+}
+
+/// A document an answer drew on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Source {
+    pub semantic_identifier: String,
+    #[serde(default)]
+    pub link: Option<String>,
+}
+
+/// The server's whole answer to a question.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Answer {
+    /// The chat it was asked in; filled in by `ask`.
+    #[serde(skip)]
+    pub chat_session_id: String,
+    #[serde(default, rename = "answer")]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub top_documents: Vec<Source>,
+    #[serde(default)]
+    pub error_msg: Option<String>,
+}
+
+/// Ask `query` in a new chat on `server` (with the default assistant) and
+/// wait for the whole answer.
+pub async fn ask(
+    config: &AppConfig,
+    server: &Url,
+    credentials: &Credentials,
+    query: &str,
+) -> Result<Answer, String> {
+    let client = http_client_for(config, server, ANSWER_TIMEOUT)?;
+    let chat: CreatedChat = post_json(
+        &client,
+        server,
+        "/api/chat/create-chat-session",
+        credentials,
+        &serde_json::json!({ "persona_id": 0 }),
+    )
+    .await?;
+    let mut answer: Answer = post_json(
+        &client,
+        server,
+        "/api/chat/send-message-simple-api",
+        credentials,
+        &serde_json::json!({ "chat_session_id": chat.chat_session_id, "message": query }),
+    )
+    .await?;
+    if let Some(e) = answer.error_msg.as_ref().filter(|e| !e.is_empty()) {
+        return Err(e.clone());
+    }
+    answer.chat_session_id = chat.chat_session_id;
+    Ok(answer)
+}
+
+/// A chat in the user's history.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatSession {
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub time_updated: String,
+}
+
+#[derive(Deserialize)]
+struct ChatSessions {
+    sessions: Vec<ChatSession>,
+    #[serde(default)]
+    has_more: bool,
+}
+
+/// The user's most recent chats (at most 100), newest first (as the server
+/// orders them), or `Ok(None)` when logged out.
+#[cfg(any(feature = "offline-cache", target_os = "macos"))]
+pub async fn recent_chats(
+    app: &AppHandle,
+    limit: usize,
+) -> Result<Option<Vec<ChatSession>>, String> {
+    let path = format!(
+        "/api/chat/get-user-chat-sessions?page_size={}",
+        limit.min(100)
+    );
+    let chats: Option<ChatSessions> = get_as_user(app, &path).await?;
+    Ok(chats.map(|chats| chats.sessions.into_iter().take(limit).collect()))
+}
+
+/// Every chat `window`'s user has on `server`, most recently updated first,
+/// or `Ok(None)` when they're logged out. (Exporting all chats lists them.)
+pub async fn all_chats(
+    window: &WebviewWindow,
+    server: &Url,
+) -> Result<Option<Vec<ChatSession>>, String> {
+    let mut all = Vec::new();
+    loop {
+        let mut path = "/api/chat/get-user-chat-sessions?page_size=100".to_string();
+        if let Some(last) = all.last().map(|chat: &ChatSession| &chat.time_updated) {
+            path.push_str("&before=");
+            path.extend(url::form_urlencoded::byte_serialize(last.as_bytes()));
+        }
+        let Some(page) = get_as_window_user::<ChatSessions>(window, server, &path).await? else {
+            return Ok(None);
+        };
+        let done = !page.has_more || page.sessions.is_empty();
+        all.extend(page.sessions);
+        if done {
+            return Ok(Some(all));
+        }
+    }
+}
+
+/// A document a chat message drew on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextDoc {
+    pub document_id: String,
+    pub semantic_identifier: String,
+    #[serde(default)]
+    pub link: Option<String>,
+}
+
+/// One message in a chat, as the server keeps it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub message_id: i64,
+    #[serde(default)]
+    pub parent_message: Option<i64>,
+    #[serde(default)]
+    pub latest_child_message: Option<i64>,
+    pub message: String,
+    /// `user`, `assistant`, or one of the kinds the page doesn't show.
+    pub message_type: String,
+    #[serde(default)]
+    pub context_docs: Option<Vec<ContextDoc>>,
+    /// Citation numbers in `message`, and the documents they point to.
+    #[serde(default)]
+    pub citations: Option<BTreeMap<u32, String>>,
+    pub time_sent: String,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// A whole chat, as the server keeps it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatTranscript {
+    pub chat_session_id: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub persona_name: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    pub time_created: String,
+}
+
+/// The chat `chat_id` with all its messages, as `window`'s user on
+/// `server` sees it, or `Ok(None)` when they're logged out.
+pub async fn chat_transcript(
+    window: &WebviewWindow,
+    server: &Url,
+    chat_id: &str,
+) -> Result<Option<ChatTranscript>, String> {
+    let id: String = url::form_urlencoded::byte_serialize(chat_id.as_bytes()).collect();
+    get_as_window_user(window, server, &format!("/api/chat/get-chat-session/{id}")).await
+}
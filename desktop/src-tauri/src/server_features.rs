@@ -0,0 +1,132 @@
+// Feature flags from the connected Onyx server, so the desktop can adapt to
+// what a deployment has turned on instead of breaking when a server has a
+// feature disabled. The flags are the boolean fields of the server's
+// `/api/settings`, which only answers a logged-in session -- so instead of
+// fetching from Rust (which has no session), a script injected into server
+// pages fetches them with the page's own cookies and reports back. Every
+// page load refreshes them, which covers logging in. Flags are kept per
+// server origin, since profile windows can be on other servers.
+
+use crate::assets::{script, Script};
+use crate::debug_log::log_debug;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use tauri::{Manager, Webview};
+
+pub type FeatureFlags = BTreeMap<String, bool>;
+
+/// Managed state: the flags each server origin last reported.
+#[derive(Default)]
+pub struct ServerFeatures(RwLock<BTreeMap<String, FeatureFlags>>);
+
+impl ServerFeatures {
+    /// Flags of the server at `origin` (`https://onyx.example.com`); empty
+    /// until a logged-in page on it has loaded.
+    pub fn flags_for(&self, origin: &str) -> FeatureFlags {
+        self.0
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(origin)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn all(&self) -> BTreeMap<String, FeatureFlags> {
+        self.0
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Store `flags` for `origin`, returning whether they changed.
+    fn set(&self, origin: String, flags: &FeatureFlags) -> bool {
+        let previous = self
+            .0
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(origin, flags.clone());
+        previous.as_ref() != Some(flags)
+    }
+}
+
+/// The boolean fields of a `/api/settings` response.
+fn flags_from_settings(settings: &serde_json::Value) -> FeatureFlags {
+    settings
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, value)| value.as_bool().map(|enabled| (name.clone(), enabled)))
+        .collect()
+}
+
+fn server_origin(webview: &Webview) -> Result<String, String> {
+    let url = webview.url().map_err(|e| e.to_string())?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("Not a server page".to_string());
+    }
+    Ok(url.origin().ascii_serialization())
+}
+
+/// Ask a freshly loaded server page to report its server's flags.
+pub fn request_server_features(webview: &Webview) {
+    if server_origin(webview).is_err() {
+        return;
+    }
+    let script = script(webview.app_handle(), Script::ServerFeatures);
+    crate::watchdog::eval(webview, &*script, "inject server features script");
+}
+
+/// Receive the calling page's `/api/settings` (see `server_features.js`).
+/// A page can only speak for its own origin.
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn report_server_settings(webview: Webview, settings: serde_json::Value) -> Result<(), String> {
+    let origin = server_origin(&webview)?;
+    let flags = flags_from_settings(&settings);
+    let app = webview.app_handle();
+    if app.state::<ServerFeatures>().set(origin.clone(), &flags) {
+        log_debug(app, &format!("Server features for {origin}: {flags:?}"));
+    }
+    Ok(())
+}
+
+/// Feature flags of the calling page's server, by name (for example
+/// `chat_page_enabled`); empty if it hasn't reported any
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn get_server_features(webview: Webview) -> FeatureFlags {
+    server_origin(&webview).map_or_else(
+        |_| FeatureFlags::new(),
+        |origin| {
+            webview
+                .app_handle()
+                .state::<ServerFeatures>()
+                .flags_for(&origin)
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_are_the_boolean_settings() {
+        let settings = serde_json::json!({
+            "chat_page_enabled": true,
+            "search_page_enabled": false,
+            "default_page": "chat",
+            "maximum_chat_retention_days": null,
+            "notifications": [],
+        });
+        let flags = flags_from_settings(&settings);
+        assert_eq!(
+            flags,
+            FeatureFlags::from([
+                ("chat_page_enabled".to_string(), true),
+                ("search_page_enabled".to_string(), false),
+            ])
+        );
+        assert!(flags_from_settings(&serde_json::json!([true])).is_empty());
+    }
+}
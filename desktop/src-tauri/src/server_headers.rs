@@ -0,0 +1,287 @@
+// Extra HTTP headers for the configured server, for deployments behind an
+// access proxy that wants a token on every request (`X-Auth-Token`,
+// Cloudflare Access's `CF-Access-Client-Id`/`-Secret`, ...). They're set in
+// `[headers]`, kept encrypted like the other secrets (see `secrets.rs`),
+// and only ever sent to `server_url`'s origin:
+//
+// - the app's own requests (`config::http_client_for`) always carry them;
+// - on Windows, WebView2 adds them to every request a window makes there;
+// - WKWebView and WebKitGTK can't change the headers of requests a page
+//   makes, so there a window's first page load on the server is reissued
+//   with them. Access proxies answer that with a session cookie, which
+//   carries the rest of the requests.
+
+use crate::config::{validate_server_url, AppConfig, ConfigState};
+use crate::window::same_origin;
+#[cfg(not(target_os = "windows"))]
+use std::collections::HashSet;
+#[cfg(not(target_os = "windows"))]
+use std::sync::Mutex;
+use tauri::{Manager, Webview};
+use url::Url;
+
+/// The headers `config` sends to `url`: its `[headers]` if `url` is on
+/// `server_url`'s origin, otherwise none. A value that couldn't be
+/// decrypted is left out.
+pub fn headers_for(config: &AppConfig, url: &Url) -> Vec<(String, String)> {
+    let on_server =
+        validate_server_url(&config.server_url).is_ok_and(|server| same_origin(&server, url));
+    if !on_server {
+        return Vec::new();
+    }
+    config
+        .headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.clone(), value.expose()?.to_string())))
+        .collect()
+}
+
+/// Check `[headers]` as submitted from the settings window or the file.
+pub fn validate(config: &AppConfig) -> Result<(), String> {
+    for (name, value) in &config.headers {
+        reqwest::header::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("\"{name}\" isn't a valid header name"))?;
+        if let Some(value) = value.expose() {
+            reqwest::header::HeaderValue::from_str(value)
+                .map_err(|_| format!("The value of header \"{name}\" isn't valid"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Managed state: server pages being reloaded with the headers, by webview
+/// label, so the reissued load isn't caught again.
+#[cfg(not(target_os = "windows"))]
+#[derive(Default)]
+pub struct HeaderedLoads(Mutex<HashSet<(String, Url)>>);
+
+/// Whether `webview` may go to `url` as it is. A first load of a server
+/// page that should carry headers is stopped and made again with them.
+#[cfg(not(target_os = "windows"))]
+pub fn allow_navigation(webview: &Webview, url: &Url) -> bool {
+    let app = webview.app_handle();
+    let loads = app.state::<HeaderedLoads>();
+    let key = (webview.label().to_string(), url.clone());
+    if loads
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .remove(&key)
+    {
+        return true;
+    }
+    // Once on the server, the proxy's cookie carries the session.
+    if webview
+        .url()
+        .is_ok_and(|current| same_origin(&current, url))
+    {
+        return true;
+    }
+    let headers = headers_for(&app.state::<ConfigState>().config(), url);
+    if headers.is_empty() {
+        return true;
+    }
+
+    loads
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .insert(key.clone());
+    if let Err(e) = load_with_headers(webview, url, headers) {
+        loads
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(&key);
+        crate::debug_log::log_backend_error(
+            app,
+            &format!("Failed to load {url} with the configured headers: {e}"),
+        );
+        return true;
+    }
+    false
+}
+
+#[cfg(target_os = "windows")]
+pub const fn allow_navigation(_webview: &Webview, _url: &Url) -> bool {
+    true
+}
+
+#[cfg(target_os = "macos")]
+fn load_with_headers(
+    webview: &Webview,
+    url: &Url,
+    headers: Vec<(String, String)>,
+) -> Result<(), String> {
+    use objc2_foundation::{NSMutableURLRequest, NSString, NSURL};
+    use objc2_web_kit::WKWebView;
+
+    let url = url.to_string();
+    webview
+        .with_webview(move |webview| {
+            let Some(ns_url) = NSURL::URLWithString(&NSString::from_str(&url)) else {
+                return;
+            };
+            let request = NSMutableURLRequest::requestWithURL(&ns_url);
+            for (name, value) in &headers {
+                request.setValue_forHTTPHeaderField(
+                    Some(&NSString::from_str(value)),
+                    &NSString::from_str(name),
+                );
+            }
+            // SAFETY: `inner` is wry's `WKWebView`, alive while the window
+            // is, and this runs on the main thread as WebKit requires.
+            unsafe {
+                let webview = &*webview.inner().cast::<WKWebView>();
+                webview.loadRequest(&request);
+            }
+        })
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn load_with_headers(
+    webview: &Webview,
+    url: &Url,
+    headers: Vec<(String, String)>,
+) -> Result<(), String> {
+    use webkit2gtk::{URIRequest, URIRequestExt, WebViewExt};
+
+    let url = url.to_string();
+    webview
+        .with_webview(move |webview| {
+            let request = URIRequest::new(&url);
+            if let Some(request_headers) = request.http_headers() {
+                for (name, value) in &headers {
+                    request_headers.append(name, value);
+                }
+            }
+            webview.inner().load_request(&request);
+        })
+        .map_err(|e| e.to_string())
+}
+
+/// Managed state: each window's `WebView2` request handler, to replace when
+/// the headers change.
+#[cfg(target_os = "windows")]
+#[derive(Default)]
+pub struct HeaderHandlers(std::sync::Mutex<std::collections::HashMap<String, (i64, String)>>);
+
+/// Have `WebView2` add the configured headers to every request `webview`
+/// makes to the server, replacing what an earlier call set up.
+#[cfg(target_os = "windows")]
+pub fn apply_to_webview(app: &tauri::AppHandle, webview: &tauri::WebviewWindow) {
+    use webview2_com::Microsoft::Web::WebView2::Win32::COREWEBVIEW2_WEB_RESOURCE_CONTEXT_ALL;
+    use webview2_com::WebResourceRequestedEventHandler;
+    use windows::core::HSTRING;
+
+    let config = app.state::<ConfigState>().config();
+    let server = validate_server_url(&config.server_url).ok();
+    let headers = server
+        .as_ref()
+        .map(|server| headers_for(&config, server))
+        .unwrap_or_default();
+    let filter = server.map(|server| format!("{}/*", server.origin().ascii_serialization()));
+    let label = webview.label().to_string();
+    let handlers = app.state::<HeaderHandlers>();
+    let previous = handlers
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .remove(&label);
+    if previous.is_none() && headers.is_empty() {
+        return;
+    }
+
+    let app = app.clone();
+    let result = webview.with_webview(move |webview| {
+        // SAFETY: plain COM calls on the webview's own controller, on the
+        // main thread WebView2 was created on.
+        let registered = unsafe {
+            (|| -> windows::core::Result<Option<(i64, String)>> {
+                let core = webview.controller().CoreWebView2()?;
+                if let Some((token, filter)) = previous {
+                    core.remove_WebResourceRequested(token)?;
+                    core.RemoveWebResourceRequestedFilter(
+                        &HSTRING::from(filter),
+                        COREWEBVIEW2_WEB_RESOURCE_CONTEXT_ALL,
+                    )?;
+                }
+                let Some(filter) = filter.filter(|_| !headers.is_empty()) else {
+                    return Ok(None);
+                };
+                core.AddWebResourceRequestedFilter(
+                    &HSTRING::from(&filter),
+                    COREWEBVIEW2_WEB_RESOURCE_CONTEXT_ALL,
+                )?;
+                let handler = WebResourceRequestedEventHandler::create(Box::new(move |_, args| {
+                    if let Some(args) = args {
+                        let request_headers = args.Request()?.Headers()?;
+                        for (name, value) in &headers {
+                            request_headers
+                                .SetHeader(&HSTRING::from(name), &HSTRING::from(value))?;
+                        }
+                    }
+                    Ok(())
+                }));
+                let mut token = 0;
+                core.add_WebResourceRequested(&handler, &raw mut token)?;
+                Ok(Some((token, filter)))
+            })()
+        };
+        match registered {
+            Ok(Some(handler)) => {
+                app.state::<HeaderHandlers>()
+                    .0
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .insert(label, handler);
+            }
+            Ok(None) => {}
+            Err(e) => crate::debug_log::log_backend_error(
+                &app,
+                &format!("Failed to set up the configured headers: {e}"),
+            ),
+        }
+    });
+    if let Err(e) = result {
+        crate::debug_log::log_backend_error(webview.app_handle(), &e.to_string());
+    }
+}
+
+/// The other engines get the headers per page load (`allow_navigation`).
+#[cfg(not(target_os = "windows"))]
+pub const fn apply_to_webview(_app: &tauri::AppHandle, _webview: &tauri::WebviewWindow) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::SecretString;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn headers_only_go_to_the_configured_origin() {
+        let mut config = AppConfig {
+            server_url: "https://onyx.example.com".to_string(),
+            ..AppConfig::default()
+        };
+        config.headers.insert(
+            "CF-Access-Client-Id".to_string(),
+            SecretString::new("client".to_string()),
+        );
+
+        let page = Url::parse("https://onyx.example.com/chat").unwrap();
+        assert_eq!(
+            headers_for(&config, &page),
+            vec![("CF-Access-Client-Id".to_string(), "client".to_string())]
+        );
+        let elsewhere = Url::parse("https://cdn.example.com/app.js").unwrap();
+        assert!(headers_for(&config, &elsewhere).is_empty());
+
+        assert!(validate(&config).is_ok());
+        config
+            .headers
+            .insert("Bad Name".to_string(), SecretString::new(String::new()));
+        assert!(validate(&config).is_err());
+    }
+}
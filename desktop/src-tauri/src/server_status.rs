@@ -0,0 +1,175 @@
+// Whether the configured server is up and new enough for this app, checked
+// from Rust once at startup (and on demand with `check_server_status`), so
+// a server that's down or too old gets a native dialog saying so rather
+// than a window that silently doesn't work. The probe reads the server's
+// public `/api/health` and `/api/version`; a version it can't read (a
+// development build, say) is taken as compatible.
+
+use crate::config::{validate_server_url, AppConfig, ConfigState};
+use crate::server_api::{server_healthy, server_version_at};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::{
+    DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult,
+};
+
+/// The oldest server release whose pages and endpoints the app is written
+/// against.
+const MINIMUM_SERVER_VERSION: Version = Version(1, 0, 0);
+const SETTINGS_LABEL: &str = "Open Settings";
+const CONTINUE_LABEL: &str = "Continue";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Version(u32, u32, u32);
+
+impl Version {
+    /// `v2.3.1`, `2.3`, or `2.3.1-cloud`; `None` for anything else.
+    fn parse(version: &str) -> Option<Self> {
+        let version = version.trim().trim_start_matches('v');
+        let numbers = version.split(['-', '+']).next()?;
+        let mut parts = numbers.split('.').map(str::parse::<u32>);
+        let major = parts.next()?.ok()?;
+        let minor = parts.next().transpose().ok()?.unwrap_or(0);
+        let patch = parts.next().transpose().ok()?.unwrap_or(0);
+        Some(Self(major, minor, patch))
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+/// What the probe found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum ServerStatus {
+    /// Up, on a version this app works with (or one it can't tell).
+    Ok { version: Option<String> },
+    /// Up, but older than the app supports.
+    TooOld { version: String, minimum: String },
+    /// Answering, but not healthy.
+    Unhealthy { version: Option<String> },
+    /// Not answering at all.
+    Unreachable { error: String },
+}
+
+fn status_of(healthy: Result<bool, String>, version: Option<String>) -> ServerStatus {
+    match healthy {
+        Err(error) => ServerStatus::Unreachable { error },
+        Ok(false) => ServerStatus::Unhealthy { version },
+        Ok(true) => match version.as_deref().and_then(Version::parse) {
+            Some(parsed) if parsed < MINIMUM_SERVER_VERSION => ServerStatus::TooOld {
+                version: version.unwrap_or_default(),
+                minimum: MINIMUM_SERVER_VERSION.to_string(),
+            },
+            _ => ServerStatus::Ok { version },
+        },
+    }
+}
+
+async fn probe(config: &AppConfig) -> ServerStatus {
+    let server = match validate_server_url(&config.server_url) {
+        Ok(server) => server,
+        Err(error) => return ServerStatus::Unreachable { error },
+    };
+    let healthy = server_healthy(config, &server).await;
+    let version = if healthy.is_ok() {
+        server_version_at(config, &server).await.ok().flatten()
+    } else {
+        None
+    };
+    status_of(healthy, version)
+}
+
+/// Whether the configured server is up and new enough for this app
+#[tauri::command]
+pub async fn check_server_status(app: AppHandle) -> ServerStatus {
+    probe(&app.state::<ConfigState>().config()).await
+}
+
+/// What to tell the user about `status`, if anything.
+fn problem(server: &str, status: &ServerStatus) -> Option<String> {
+    match status {
+        ServerStatus::Ok { .. } => None,
+        ServerStatus::TooOld { version, minimum } => Some(format!(
+            "{server} runs Onyx {version}, but this app needs {minimum} or later. Some things won't work until the server is upgraded."
+        )),
+        ServerStatus::Unhealthy { .. } => Some(format!(
+            "{server} answers, but reports a problem. Onyx may not work until it's fixed."
+        )),
+        ServerStatus::Unreachable { error } => Some(format!("Couldn't reach {server}: {error}")),
+    }
+}
+
+/// Check the configured server once the app is up, and say so if it's down
+/// or too old. Skipped until first-run setup has picked a server.
+pub fn check_at_startup(app: &AppHandle) {
+    let state = app.state::<ConfigState>();
+    if !state.is_config_initialized() {
+        return;
+    }
+    let config = state.config();
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let status = probe(&config).await;
+        // That warning says more than "couldn't reach".
+        if crate::cert_pinning::warn_if_refused(&app) {
+            return;
+        }
+        let Some(message) = problem(&config.server_url, &status) else {
+            return;
+        };
+        crate::debug_log::log_backend_error(&app, &message);
+        let handle = app.clone();
+        app.dialog()
+            .message(message)
+            .title("Server Status")
+            .kind(MessageDialogKind::Warning)
+            .buttons(MessageDialogButtons::OkCancelCustom(
+                SETTINGS_LABEL.to_string(),
+                CONTINUE_LABEL.to_string(),
+            ))
+            .show_with_result(move |result| {
+                let settings = match result {
+                    MessageDialogResult::Ok => true,
+                    MessageDialogResult::Custom(label) => label == SETTINGS_LABEL,
+                    _ => false,
+                };
+                if settings {
+                    crate::window::open_settings(&handle);
+                }
+            });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_compared_where_they_can_be_read() {
+        assert_eq!(Version::parse("v2.3.1"), Some(Version(2, 3, 1)));
+        assert_eq!(Version::parse("1.4-cloud"), Some(Version(1, 4, 0)));
+        assert_eq!(Version::parse("development"), None);
+
+        assert_eq!(
+            status_of(Ok(true), Some("v0.29.1".to_string())),
+            ServerStatus::TooOld {
+                version: "v0.29.1".to_string(),
+                minimum: "1.0.0".to_string(),
+            }
+        );
+        assert_eq!(
+            status_of(Ok(true), Some("mock".to_string())),
+            ServerStatus::Ok {
+                version: Some("mock".to_string())
+            }
+        );
+        assert_eq!(
+            status_of(Ok(false), None),
+            ServerStatus::Unhealthy { version: None }
+        );
+    }
+}
@@ -0,0 +1,229 @@
+// The macOS Services menu's items. "Ask Onyx about Selection": text
+// selected in any app goes to a new chat in the main window, quoted in the
+// message box the way a browser handoff is (see `handoff.rs`), ready for
+// the question. "Ask Onyx about File" and "Upload to Onyx" are on files in
+// Finder, under Quick Actions too (see `file_menu.rs`). A keyboard shortcut
+// for any of them can be set in System Settings > Keyboard > Keyboard
+// Shortcuts > Services.
+//
+// The items are declared under `NSServices` in `Info.plist`; each one's
+// message arrives as a method call on the services provider, which is tao's
+// app delegate with methods added for them (as the Apple event handler is,
+// see `scripting.rs`). Everything is turned away while the app is locked.
+
+use crate::handoff::quote;
+use crate::lock::AppLock;
+use crate::route::Route;
+use crate::share::Share;
+use crate::window::open_route;
+use objc2::rc::Retained;
+use objc2::runtime::{AnyObject, Imp, Sel};
+use objc2::{class, msg_send, sel};
+use objc2_foundation::NSString;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tauri::{AppHandle, Manager};
+use url::Url;
+
+/// Where the provider finds the app.
+static SERVICES_APP: OnceLock<AppHandle> = OnceLock::new();
+
+/// `NSPasteboardTypeString`.
+const PLAIN_TEXT: &str = "public.utf8-plain-text";
+/// `NSPasteboardTypeFileURL`.
+const FILE_URL: &str = "public.file-url";
+
+/// What a service message is sent with: `self`, `_cmd`, the pasteboard, the
+/// user data and the error out-parameter.
+type Message =
+    extern "C-unwind" fn(&AnyObject, Sel, *mut AnyObject, *mut AnyObject, *mut *mut NSString);
+
+#[link(name = "AppKit", kind = "framework")]
+extern "C" {
+    fn NSUpdateDynamicServices();
+}
+
+/// The text on `pasteboard`, an `NSPasteboard`.
+fn text(pasteboard: &AnyObject) -> Option<String> {
+    // SAFETY: `stringForType:` takes a type name and returns a string or
+    // null.
+    unsafe {
+        let text: *mut NSString =
+            msg_send![pasteboard, stringForType: &*NSString::from_str(PLAIN_TEXT)];
+        text.as_ref().map(NSString::to_string)
+    }
+}
+
+/// The files on `pasteboard`, an `NSPasteboard`: one per item.
+fn files(pasteboard: &AnyObject) -> Vec<PathBuf> {
+    let file_url = NSString::from_str(FILE_URL);
+    // SAFETY: `pasteboardItems` is null or an array of `NSPasteboardItem`s,
+    // which answer `stringForType:` like the pasteboard does.
+    unsafe {
+        let items: *mut AnyObject = msg_send![pasteboard, pasteboardItems];
+        let Some(items) = items.as_ref() else {
+            return Vec::new();
+        };
+        let count: usize = msg_send![items, count];
+        (0..count)
+            .filter_map(|index| {
+                let item: *mut AnyObject = msg_send![items, objectAtIndex: index];
+                let url: *mut NSString = msg_send![item, stringForType: &*file_url];
+                url.as_ref().map(NSString::to_string)
+            })
+            .filter_map(|url| Url::parse(&url).ok()?.to_file_path().ok())
+            .collect()
+    }
+}
+
+/// Open a new chat about `selection`.
+fn ask_about(app: &AppHandle, selection: Option<String>) -> Result<(), String> {
+    let selection = selection
+        .filter(|selection| !selection.trim().is_empty())
+        .ok_or("Select some text to ask about.")?;
+    open_route(
+        app,
+        &Route::Draft {
+            message: format!("{}\n\n", quote(selection.trim())),
+        },
+    );
+    Ok(())
+}
+
+/// Run `service` on the pasteboard a message came with, and put its error
+/// where `AppKit` asked for it.
+///
+/// # Safety
+///
+/// `pasteboard` is null or an `NSPasteboard`, and `error` null or somewhere
+/// to put an `NSString`, as `AppKit` passes them on the main thread.
+unsafe fn provide(
+    pasteboard: *mut AnyObject,
+    error: *mut *mut NSString,
+    service: impl FnOnce(&AppHandle, &AnyObject) -> Result<(), String>,
+) {
+    let Some(app) = SERVICES_APP.get() else {
+        return;
+    };
+    let result = if app.state::<AppLock>().is_locked() {
+        Err("Onyx is locked.".to_string())
+    } else {
+        pasteboard
+            .as_ref()
+            .ok_or_else(|| "Nothing was sent.".to_string())
+            .and_then(|pasteboard| service(app, pasteboard))
+    };
+    if let Err(e) = result {
+        if !error.is_null() {
+            *error = Retained::autorelease_ptr(NSString::from_str(&e));
+        }
+    }
+}
+
+extern "C-unwind" fn ask_about_selection(
+    _provider: &AnyObject,
+    _cmd: Sel,
+    pasteboard: *mut AnyObject,
+    _user_data: *mut AnyObject,
+    error: *mut *mut NSString,
+) {
+    // SAFETY: straight from AppKit.
+    unsafe {
+        provide(pasteboard, error, |app, pasteboard| {
+            ask_about(app, text(pasteboard))
+        });
+    }
+}
+
+extern "C-unwind" fn ask_about_files(
+    _provider: &AnyObject,
+    _cmd: Sel,
+    pasteboard: *mut AnyObject,
+    _user_data: *mut AnyObject,
+    error: *mut *mut NSString,
+) {
+    // SAFETY: straight from AppKit.
+    unsafe {
+        provide(pasteboard, error, |app, pasteboard| {
+            crate::share::open(
+                app,
+                &Share {
+                    files: files(pasteboard),
+                    ..Share::default()
+                },
+            )
+        });
+    }
+}
+
+extern "C-unwind" fn upload_files(
+    _provider: &AnyObject,
+    _cmd: Sel,
+    pasteboard: *mut AnyObject,
+    _user_data: *mut AnyObject,
+    error: *mut *mut NSString,
+) {
+    // SAFETY: straight from AppKit.
+    unsafe {
+        provide(pasteboard, error, |app, pasteboard| {
+            crate::file_menu::upload(app, files(pasteboard))
+        });
+    }
+}
+
+/// Provide the Services items. Must run on the main thread, once.
+pub fn setup(app: &AppHandle) -> Result<(), String> {
+    if SERVICES_APP.set(app.clone()).is_err() {
+        return Ok(());
+    }
+    // Each `NSMessage` in `Info.plist`, with the arguments every service
+    // message takes.
+    let messages: [(Sel, Message); 3] = [
+        (
+            sel!(askOnyxAboutSelection:userData:error:),
+            ask_about_selection,
+        ),
+        (sel!(askOnyxAboutFiles:userData:error:), ask_about_files),
+        (sel!(uploadFilesToOnyx:userData:error:), upload_files),
+    ];
+    // SAFETY: the methods have the signature service messages are sent with
+    // ("v@:@@^@"), and are only added to the delegate's own class, which
+    // lives as long as the app.
+    unsafe {
+        let ns_app: *mut AnyObject = msg_send![class!(NSApplication), sharedApplication];
+        let delegate: *mut AnyObject = msg_send![ns_app, delegate];
+        let delegate = delegate.as_ref().ok_or("No app delegate")?;
+        for (sel, method) in messages {
+            let added = objc2::ffi::class_addMethod(
+                std::ptr::from_ref(delegate.class()).cast_mut(),
+                sel,
+                std::mem::transmute::<Message, Imp>(method),
+                c"v@:@@^@".as_ptr(),
+            );
+            if !added.as_bool() {
+                return Err("The app delegate already provides services".to_string());
+            }
+        }
+        let () = msg_send![ns_app, setServicesProvider: delegate];
+        // So the items show up without logging out after an install or
+        // update.
+        NSUpdateDynamicServices();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn the_plist_declares_the_provided_messages() {
+        let plist = include_str!("../Info.plist");
+        assert!(plist.contains("<key>NSServices</key>"));
+        for message in [
+            "askOnyxAboutSelection",
+            "askOnyxAboutFiles",
+            "uploadFilesToOnyx",
+        ] {
+            assert!(plist.contains(&format!("<string>{message}</string>")));
+        }
+    }
+}
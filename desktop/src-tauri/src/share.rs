@@ -0,0 +1,393 @@
+// "Share to Onyx": text, links and files shared from other apps start a new
+// chat with them in the message box (typed, not sent) or, asked to, go into
+// the chat that's open. Shares arrive two ways:
+//
+//     onyx://share?text=<text>&url=<link>&title=<link title>&file=<path>&to=current
+//     onyx share [--current] [--text TEXT] [--url URL] [FILE]...
+//
+// The link is what a macOS Shortcut shown in the share sheet opens (an app
+// extension, which the share sheet otherwise needs, isn't something the
+// bundler builds), and `onyx share` is what the Windows installer's Send To
+// entry runs (see `windows/hooks.nsh`; the Windows share pane only offers
+// packaged apps). Files are read here and handed to the page to attach (see
+// `attachFiles` in `snippets.js`), so a link naming files is confirmed
+// first: any page could open one. Shares are ignored while the app is
+// locked, like handoffs (see `handoff.rs`).
+
+use crate::debug_log::log_backend_error;
+use crate::lock::AppLock;
+use crate::route::Route;
+use crate::window::{is_bundled_page_window, open_route};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, Webview, WebviewWindow};
+use tauri_plugin_dialog::{
+    DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult,
+};
+use url::Url;
+
+const HOST: &str = "share";
+const TITLE: &str = "Share to Onyx";
+const ATTACH_LABEL: &str = "Attach";
+const CANCEL_LABEL: &str = "Cancel";
+/// Longer text is cut; a new chat's whole message goes in its URL.
+const MAX_TEXT_CHARS: usize = 8_000;
+const MAX_FILES: usize = 10;
+/// Each file goes to the page whole, as base64.
+const MAX_FILE_BYTES: u64 = 20 * 1024 * 1024;
+
+/// What was shared.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Share {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The link's title, shown in place of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<PathBuf>,
+    /// Into the chat that's open rather than a new one.
+    #[serde(default)]
+    pub current: bool,
+}
+
+/// A file as the page gets it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct SharedFile {
+    name: String,
+    #[serde(rename = "type")]
+    mime_type: String,
+    /// The contents, base64.
+    data: String,
+}
+
+/// Managed state: files waiting for the new chat they were shared to to
+/// load.
+#[derive(Default)]
+pub struct PendingShare(Mutex<Vec<SharedFile>>);
+
+impl PendingShare {
+    fn files(&self) -> std::sync::MutexGuard<'_, Vec<SharedFile>> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// The share a link is.
+fn parse(link: &Url) -> Share {
+    let query = |name: &str| {
+        link.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let files = link
+        .query_pairs()
+        .filter(|(key, _)| key == "file")
+        .filter_map(|(_, value)| {
+            let value = value.trim();
+            if value.starts_with("file:") {
+                Url::parse(value).ok()?.to_file_path().ok()
+            } else {
+                (!value.is_empty()).then(|| PathBuf::from(value))
+            }
+        })
+        .collect();
+    Share {
+        text: query("text"),
+        url: query("url"),
+        title: query("title"),
+        files,
+        current: query("to").as_deref() == Some("current"),
+    }
+}
+
+/// The message `share` starts: its text, then its link.
+fn draft(share: &Share) -> Result<String, String> {
+    let mut parts = Vec::new();
+    if let Some(text) = share.text.as_deref().map(str::trim) {
+        parts.push(text.chars().take(MAX_TEXT_CHARS).collect::<String>());
+    }
+    if let Some(link) = &share.url {
+        let link = Url::parse(link.trim())
+            .ok()
+            .filter(|link| matches!(link.scheme(), "http" | "https"))
+            .ok_or_else(|| format!("\"{link}\" isn't a web link"))?;
+        // Browsers share a page as both its text and its link.
+        if !parts
+            .iter()
+            .any(|text| text.trim_end_matches('/') == link.as_str().trim_end_matches('/'))
+        {
+            parts.push(
+                share
+                    .title
+                    .as_ref()
+                    .map_or_else(|| link.to_string(), |title| format!("[{title}]({link})")),
+            );
+        }
+    }
+    parts.retain(|part| !part.is_empty());
+    Ok(parts.join("\n\n"))
+}
+
+/// What the page says a file is, from its extension.
+fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("pdf") => "application/pdf",
+        Some("txt" | "log") => "text/plain",
+        Some("md") => "text/markdown",
+        Some("csv") => "text/csv",
+        Some("html" | "htm") => "text/html",
+        Some("json") => "application/json",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("docx") => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        Some("xlsx") => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        Some("pptx") => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        _ => "application/octet-stream",
+    }
+}
+
+fn read_files(paths: &[PathBuf]) -> Result<Vec<SharedFile>, String> {
+    if paths.len() > MAX_FILES {
+        return Err(format!("Share up to {MAX_FILES} files at a time"));
+    }
+    paths
+        .iter()
+        .map(|path| {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .ok_or_else(|| format!("{} isn't a file", path.display()))?;
+            let metadata =
+                std::fs::metadata(path).map_err(|e| format!("{name} can't be read: {e}"))?;
+            if !metadata.is_file() {
+                return Err(format!("{name} isn't a file"));
+            }
+            if metadata.len() > MAX_FILE_BYTES {
+                return Err(format!(
+                    "{name} is over {} MB",
+                    MAX_FILE_BYTES / 1024 / 1024
+                ));
+            }
+            let bytes = std::fs::read(path).map_err(|e| format!("{name} can't be read: {e}"))?;
+            Ok(SharedFile {
+                name,
+                mime_type: mime_type(path).to_string(),
+                data: base64::engine::general_purpose::STANDARD.encode(bytes),
+            })
+        })
+        .collect()
+}
+
+fn attach(window: &Webview, files: &[SharedFile]) {
+    match serde_json::to_string(files) {
+        Ok(files) => {
+            crate::watchdog::eval(
+                window,
+                format!("window.__ONYX_SNIPPETS__?.attachFiles({files})"),
+                "attach shared files",
+            );
+        }
+        Err(e) => log_backend_error(
+            window.app_handle(),
+            &format!("Failed to attach shared files: {e}"),
+        ),
+    }
+}
+
+fn focused_chat_window(app: &AppHandle) -> Option<WebviewWindow> {
+    let windows = app.webview_windows();
+    windows
+        .iter()
+        .find(|(label, window)| {
+            !is_bundled_page_window(label) && window.is_focused().unwrap_or(false)
+        })
+        .map(|(_, window)| window)
+        .or_else(|| windows.get("main"))
+        .cloned()
+}
+
+/// Open `share`: in the chat that's open if it asks to be and there is
+/// one, in a new chat otherwise.
+pub fn open(app: &AppHandle, share: &Share) -> Result<(), String> {
+    let message = draft(share)?;
+    let files = read_files(&share.files)?;
+    if message.is_empty() && files.is_empty() {
+        return Err("Nothing was shared".to_string());
+    }
+
+    let current = share
+        .current
+        .then(|| focused_chat_window(app))
+        .flatten()
+        .filter(|window| {
+            window
+                .url()
+                .is_ok_and(|url| Route::from_url(&url).is_some())
+        });
+    if let Some(window) = current {
+        let raised = window
+            .unminimize()
+            .and_then(|()| window.show())
+            .and_then(|()| window.set_focus());
+        if let Err(e) = raised {
+            log_backend_error(app, &format!("Failed to raise the chat window: {e}"));
+        }
+        if !message.is_empty() {
+            let text = serde_json::to_string(&message).map_err(|e| e.to_string())?;
+            crate::watchdog::eval(
+                window.as_ref(),
+                format!("window.__ONYX_SNIPPETS__?.insertText({text})"),
+                "insert shared text",
+            );
+        }
+        if !files.is_empty() {
+            attach(window.as_ref(), &files);
+        }
+        return Ok(());
+    }
+
+    *app.state::<PendingShare>().files() = files;
+    if message.is_empty() {
+        open_route(app, &Route::new_chat());
+    } else {
+        open_route(app, &Route::Draft { message });
+    }
+    Ok(())
+}
+
+/// Attach the files waiting for a new chat, once one has loaded in
+/// `webview`.
+pub fn attach_pending(webview: &Webview) {
+    if is_bundled_page_window(webview.label()) {
+        return;
+    }
+    let is_new_chat = webview.url().is_ok_and(|url| {
+        matches!(
+            Route::from_url(&url),
+            Some(Route::NewChat { .. } | Route::Draft { .. })
+        )
+    });
+    if !is_new_chat {
+        return;
+    }
+    let files = std::mem::take(&mut *webview.app_handle().state::<PendingShare>().files());
+    if !files.is_empty() {
+        attach(webview, &files);
+    }
+}
+
+fn warn(app: &AppHandle, message: String) {
+    app.dialog()
+        .message(message)
+        .title(TITLE)
+        .kind(MessageDialogKind::Warning)
+        .show(|_| {});
+}
+
+/// Open the share `link` is, if it's one; whether it was.
+pub fn open_url(app: &AppHandle, link: &Url) -> bool {
+    if link.scheme() != crate::auth::SCHEME || link.host_str() != Some(HOST) {
+        return false;
+    }
+    if app.state::<AppLock>().is_locked() {
+        log_backend_error(app, "Ignored a share while locked");
+        return true;
+    }
+    let share = parse(link);
+    if share.files.is_empty() {
+        if let Err(e) = open(app, &share) {
+            warn(app, e);
+        }
+        return true;
+    }
+
+    let names: Vec<String> = share
+        .files
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    let handle = app.clone();
+    app.dialog()
+        .message(format!(
+            "A link asked to attach these files to a chat:\n\n{}",
+            names.join("\n")
+        ))
+        .title(TITLE)
+        .kind(MessageDialogKind::Info)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            ATTACH_LABEL.to_string(),
+            CANCEL_LABEL.to_string(),
+        ))
+        .show_with_result(move |result| {
+            let attach = match result {
+                MessageDialogResult::Ok => true,
+                MessageDialogResult::Custom(label) => label == ATTACH_LABEL,
+                _ => false,
+            };
+            if attach {
+                if let Err(e) = open(&handle, &share) {
+                    warn(&handle, e);
+                }
+            }
+        });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn shares_start_a_draft_with_the_text_and_link() {
+        let link = Url::parse(
+            "onyx://share?text=What+changed+here%3F&url=https%3A%2F%2Fwiki.example.com%2Fpto&title=PTO+Policy&file=%2Ftmp%2Fa.pdf&file=file%3A%2F%2F%2Ftmp%2Fb%2520c.txt&to=current",
+        )
+        .unwrap();
+        let share = parse(&link);
+        #[cfg(unix)]
+        assert_eq!(
+            share.files,
+            vec![PathBuf::from("/tmp/a.pdf"), PathBuf::from("/tmp/b c.txt")]
+        );
+        assert!(share.current);
+        assert_eq!(
+            draft(&share).unwrap(),
+            "What changed here?\n\n[PTO Policy](https://wiki.example.com/pto)"
+        );
+
+        let page = Share {
+            text: Some("https://wiki.example.com/pto".to_string()),
+            url: Some("https://wiki.example.com/pto".to_string()),
+            ..Share::default()
+        };
+        assert_eq!(draft(&page).unwrap(), "https://wiki.example.com/pto");
+
+        let local = Share {
+            url: Some("file:///etc/passwd".to_string()),
+            ..Share::default()
+        };
+        assert!(draft(&local).is_err());
+        assert_eq!(draft(&Share::default()).unwrap(), "");
+    }
+
+    #[test]
+    fn shared_files_are_typed_by_extension() {
+        assert_eq!(mime_type(Path::new("Q3 Report.PDF")), "application/pdf");
+        assert_eq!(mime_type(Path::new("notes.md")), "text/markdown");
+        assert_eq!(mime_type(Path::new("archive")), "application/octet-stream");
+    }
+}
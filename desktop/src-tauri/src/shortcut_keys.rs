@@ -0,0 +1,358 @@
+// Shortcuts the keyboard layout hides from the menus. The menus find a
+// shortcut's key the way each platform does, mostly by the character it
+// types, so on a layout that doesn't type that character (a Cyrillic one,
+// or AZERTY with its brackets behind AltGr) `CmdOrCtrl+R` or `CmdOrCtrl+[`
+// silently stops working when the layout switches. `shortcut_keys.js`
+// watches key presses in server pages and fires the shortcuts the menus
+// missed, finding the key by its US position as `shortcuts.key_matching`
+// says (see `KeyMatching`). A press both of them catch is only acted on
+// once. Settings records shortcuts with `capture_shortcut`, which names the
+// pressed key the same way.
+
+use crate::assets::{script, Script};
+use crate::config::{ConfigState, KeyMatching, ShortcutSettings};
+use crate::menu::{
+    MENU_ALWAYS_ON_TOP_ID, MENU_CHEAT_SHEET_ID, MENU_DICTATION_ID, MENU_FOCUS_MODE_ID,
+    MENU_GO_BACK_ID, MENU_GO_FORWARD_ID, MENU_INSERT_SNIPPET_ID, MENU_MINI_CHAT_ID,
+    MENU_NEW_CHAT_ID, MENU_NEW_WINDOW_ID, MENU_OPEN_SETTINGS_ID, MENU_RELOAD_ID, MENU_ZOOM_IN_ID,
+    MENU_ZOOM_OUT_ID, MENU_ZOOM_RESET_ID,
+};
+use crate::shortcuts::{bound, display_keys, key_label, parse, unavailable, Problem};
+use crate::window::is_bundled_page_window;
+use muda::accelerator::Modifiers;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, Webview, WebviewWindow};
+
+/// The menu item each bound shortcut's action is. Close Window is the
+/// platform's own item, which the page can't press.
+const MENU_IDS: [(&str, &str); 15] = [
+    ("New Chat", MENU_NEW_CHAT_ID),
+    ("New Window", MENU_NEW_WINDOW_ID),
+    ("Settings", MENU_OPEN_SETTINGS_ID),
+    ("Always on Top", MENU_ALWAYS_ON_TOP_ID),
+    ("Mini Chat", MENU_MINI_CHAT_ID),
+    ("Focus Mode", MENU_FOCUS_MODE_ID),
+    ("Insert Snippet", MENU_INSERT_SNIPPET_ID),
+    ("Dictation", MENU_DICTATION_ID),
+    ("Reload", MENU_RELOAD_ID),
+    ("Back", MENU_GO_BACK_ID),
+    ("Forward", MENU_GO_FORWARD_ID),
+    ("Zoom In", MENU_ZOOM_IN_ID),
+    ("Zoom Out", MENU_ZOOM_OUT_ID),
+    ("Actual Size", MENU_ZOOM_RESET_ID),
+    ("Keyboard Shortcuts", MENU_CHEAT_SHEET_ID),
+];
+
+/// How far apart the menu and the page can report the same press.
+const SAME_PRESS: Duration = Duration::from_millis(300);
+
+/// Keys that only modify others, which a shortcut can't end with.
+const MODIFIER_KEYS: [&str; 6] = ["Shift", "Control", "Alt", "AltGraph", "Meta", "OS"];
+
+/// A bound shortcut, as `shortcut_keys.js` matches key presses against it.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct KeyBinding {
+    /// The menu item it presses.
+    id: &'static str,
+    /// Where the key is, as a DOM `KeyboardEvent.code` (`BracketLeft`).
+    code: String,
+    /// What the key types on a US layout, lowercased, if it types one
+    /// character.
+    character: Option<String>,
+    /// Held with it, of `ctrl`, `alt`, `shift` and `meta`, in that order.
+    modifiers: Vec<&'static str>,
+}
+
+/// `accelerator` (already `for_platform`) as the page matches it.
+fn binding(id: &'static str, accelerator: &str) -> Option<KeyBinding> {
+    let parsed = parse(accelerator)?;
+    let code = parsed.key().to_string();
+    let label = key_label(&code);
+    let mods = parsed.modifiers();
+    let modifiers = [
+        (mods.contains(Modifiers::CONTROL), "ctrl"),
+        (mods.contains(Modifiers::ALT), "alt"),
+        (mods.contains(Modifiers::SHIFT), "shift"),
+        (mods.intersects(Modifiers::SUPER | Modifiers::META), "meta"),
+    ];
+    Some(KeyBinding {
+        id,
+        character: (label.chars().count() == 1).then(|| label.to_lowercase()),
+        code,
+        modifiers: modifiers
+            .into_iter()
+            .filter_map(|(held, name)| held.then_some(name))
+            .collect(),
+    })
+}
+
+/// Every bound shortcut the page can press.
+pub fn bindings(shortcuts: &ShortcutSettings) -> Vec<KeyBinding> {
+    bound(shortcuts)
+        .into_iter()
+        .filter_map(|(action, accelerator)| {
+            let &(_, id) = MENU_IDS.iter().find(|(known, _)| *known == action)?;
+            binding(id, &accelerator)
+        })
+        .collect()
+}
+
+fn set_page_bindings(webview: &Webview, shortcuts: &ShortcutSettings) {
+    let (Ok(bindings), Ok(matching)) = (
+        serde_json::to_string(&bindings(shortcuts)),
+        serde_json::to_string(&shortcuts.key_matching),
+    ) else {
+        return;
+    };
+    crate::watchdog::eval(
+        webview,
+        format!("window.__ONYX_SHORTCUT_KEYS__?.set({bindings}, {matching})"),
+        "apply shortcut keys",
+    );
+}
+
+pub fn inject_shortcut_keys_script(webview: &Webview) {
+    // Bundled pages, like Settings recording a shortcut, keep their keys.
+    if is_bundled_page_window(webview.label()) {
+        return;
+    }
+    let app = webview.app_handle();
+    let script = script(app, Script::ShortcutKeys);
+    if crate::watchdog::eval(webview, &*script, "inject shortcut keys script") {
+        set_page_bindings(webview, &app.state::<ConfigState>().config().shortcuts);
+    }
+}
+
+/// Give `window`'s page the shortcuts as they're configured now.
+pub fn apply_shortcut_keys(app: &AppHandle, window: &WebviewWindow) {
+    set_page_bindings(
+        window.as_ref(),
+        &app.state::<ConfigState>().config().shortcuts,
+    );
+}
+
+/// Managed state: the last shortcut acted on, and when.
+#[derive(Default)]
+pub struct RecentPress(Mutex<Option<(String, Instant)>>);
+
+impl RecentPress {
+    /// Whether menu item `id` should act now, rather than being the same
+    /// press reported a second time.
+    pub fn first(&self, id: &str) -> bool {
+        if !MENU_IDS.iter().any(|(_, known)| *known == id) {
+            return true;
+        }
+        let mut last = self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let now = Instant::now();
+        if let Some((last_id, at)) = last.as_ref() {
+            if last_id == id && now.duration_since(*at) < SAME_PRESS {
+                return false;
+            }
+        }
+        *last = Some((id.to_string(), now));
+        true
+    }
+}
+
+/// Act on the shortcut for menu item `id`, pressed in the page where the
+/// menus missed it (see `shortcut_keys.js`)
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn press_shortcut(app: AppHandle, id: String) {
+    if MENU_IDS.iter().any(|(_, known)| *known == id) {
+        crate::handle_menu_event(&app, &id);
+    }
+}
+
+/// A key press in Settings, from its DOM `KeyboardEvent`.
+#[derive(Debug, Deserialize)]
+pub struct KeyPress {
+    key: String,
+    code: String,
+    /// Held with it, of `ctrl`, `alt`, `shift` and `meta`.
+    #[serde(default)]
+    modifiers: Vec<String>,
+}
+
+/// The accelerator name for `character`, if it's one a US layout types
+/// unshifted.
+fn character_key(character: &str) -> Option<String> {
+    let mut chars = character.chars();
+    let (Some(c), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+    if c.is_ascii_alphanumeric() {
+        return Some(c.to_ascii_uppercase().to_string());
+    }
+    let name = match c {
+        ',' => "Comma",
+        '.' => "Period",
+        '/' => "Slash",
+        '\\' => "Backslash",
+        '-' => "Minus",
+        '=' => "Equal",
+        '[' => "BracketLeft",
+        ']' => "BracketRight",
+        '`' => "Backquote",
+        ';' => "Semicolon",
+        '\'' => "Quote",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// The accelerator name for the key at `code`.
+fn code_key(code: &str) -> String {
+    code.strip_prefix("Key")
+        .or_else(|| code.strip_prefix("Digit"))
+        .filter(|rest| rest.len() == 1)
+        .unwrap_or(code)
+        .to_string()
+}
+
+/// `press` as an accelerator, with its key named by what it types or where
+/// it is, as `matching` says.
+fn accelerator_for_press(
+    press: &KeyPress,
+    matching: KeyMatching,
+    macos: bool,
+) -> Result<String, String> {
+    if MODIFIER_KEYS.contains(&press.key.as_str()) {
+        return Err("Press a key along with the modifiers".to_string());
+    }
+    let held = |name: &str| press.modifiers.iter().any(|held| held == name);
+    let (command, control, windows_key) = if macos {
+        (held("meta"), held("ctrl"), false)
+    } else {
+        (held("ctrl"), false, held("meta"))
+    };
+    let function_key = press.code.len() > 1
+        && press.code.starts_with('F')
+        && press.code[1..].bytes().all(|b| b.is_ascii_digit());
+    if !(command || control || windows_key || held("alt") || function_key) {
+        return Err(if macos {
+            "Shortcuts need \u{2318}, \u{2303} or \u{2325}".to_string()
+        } else {
+            "Shortcuts need Ctrl or Alt".to_string()
+        });
+    }
+
+    let key = match matching {
+        KeyMatching::Character => {
+            character_key(&press.key).unwrap_or_else(|| code_key(&press.code))
+        }
+        KeyMatching::Position => code_key(&press.code),
+    };
+    let parts = [
+        (command, "CmdOrCtrl"),
+        (control, "Ctrl"),
+        (windows_key, "Super"),
+        (held("alt"), "Alt"),
+        (held("shift"), "Shift"),
+    ];
+    let accelerator = parts
+        .into_iter()
+        .filter_map(|(held, name)| held.then_some(name))
+        .chain(std::iter::once(key.as_str()))
+        .collect::<Vec<_>>()
+        .join("+");
+
+    let keys = display_keys(&accelerator, macos);
+    match unavailable(&accelerator) {
+        None => Ok(accelerator),
+        Some(Problem::Invalid) => Err(format!("{keys} can't be used as a shortcut")),
+        Some(Problem::System) => Err(format!("{keys} is reserved by the system")),
+        Some(Problem::TakenBy(action)) => Err(format!("{keys} is already used by {action}")),
+    }
+}
+
+/// The accelerator for a key press in Settings, with its key named the way
+/// `key_matching` finds it, or why it can't be a shortcut
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn capture_shortcut(press: KeyPress, key_matching: KeyMatching) -> Result<String, String> {
+    accelerator_for_press(&press, key_matching, cfg!(target_os = "macos"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortcuts_carry_their_key_position_and_character() {
+        let bindings = bindings(&ShortcutSettings::default().for_platform());
+        let back = bindings
+            .iter()
+            .find(|binding| binding.id == MENU_GO_BACK_ID);
+        let command = if cfg!(target_os = "macos") {
+            "meta"
+        } else {
+            "ctrl"
+        };
+        assert_eq!(
+            back,
+            Some(&KeyBinding {
+                id: MENU_GO_BACK_ID,
+                code: "BracketLeft".to_string(),
+                character: Some("[".to_string()),
+                modifiers: vec![command],
+            })
+        );
+        let new_window = bindings
+            .iter()
+            .find(|binding| binding.id == MENU_NEW_WINDOW_ID);
+        assert!(new_window.is_some_and(|binding| binding.code == "KeyN"
+            && binding.character.as_deref() == Some("n")
+            && binding.modifiers.contains(&"shift")));
+        // Every bound shortcut but Close Window.
+        assert_eq!(bindings.len(), MENU_IDS.len());
+    }
+
+    #[test]
+    fn pressed_keys_are_named_by_character_or_position() {
+        let press = |key: &str, code: &str, modifiers: &[&str]| KeyPress {
+            key: key.to_string(),
+            code: code.to_string(),
+            modifiers: modifiers.iter().map(ToString::to_string).collect(),
+        };
+        let character = KeyMatching::Character;
+        let position = KeyMatching::Position;
+
+        // AZERTY's A key sits where QWERTY has Q.
+        let azerty_a = press("a", "KeyQ", &["ctrl", "shift"]);
+        assert_eq!(
+            accelerator_for_press(&azerty_a, character, false),
+            Ok("CmdOrCtrl+Shift+A".to_string())
+        );
+        assert_eq!(
+            accelerator_for_press(&azerty_a, position, false),
+            Ok("CmdOrCtrl+Shift+Q".to_string())
+        );
+        // A Cyrillic letter falls back to its key's position.
+        assert_eq!(
+            accelerator_for_press(&press("\u{44b}", "KeyS", &["meta", "alt"]), character, true),
+            Ok("CmdOrCtrl+Alt+S".to_string())
+        );
+        assert_eq!(
+            accelerator_for_press(&press("F6", "F6", &[]), character, false),
+            Ok("F6".to_string())
+        );
+
+        assert!(accelerator_for_press(&press("k", "KeyK", &[]), character, false).is_err());
+        assert!(accelerator_for_press(
+            &press("Control", "ControlLeft", &["ctrl"]),
+            character,
+            false
+        )
+        .is_err());
+        assert_eq!(
+            accelerator_for_press(&press("[", "BracketLeft", &["ctrl"]), character, false),
+            Err("Ctrl+[ is already used by Back".to_string())
+        );
+    }
+}
@@ -0,0 +1,391 @@
+// Checking the configurable shortcuts before the menus bind them. Menus
+// drop an accelerator they can't parse without saying so, and two items
+// with the same one leave which fires up to the platform, so a typo or a
+// clash used to cost a shortcut silently. Here each one is parsed the way
+// the menus will parse it, and compared with the ones bound before it, the
+// menus' fixed ones, and the ones the system keeps for itself. A shortcut
+// that fails is left unbound while the others still work, and the user is
+// told which ones and offered Settings to rebind them. What's left, with
+// the fixed ones, is what the cheat sheet (see `cheat_sheet.rs`) lists.
+
+use crate::config::{ConfigState, ShortcutSettings};
+use muda::accelerator::Accelerator;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::{
+    DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult,
+};
+
+const OPEN_SETTINGS_LABEL: &str = "Open Settings";
+const DISMISS_LABEL: &str = "Dismiss";
+
+/// The View menu's accelerators (see `menu::build_view_menu`).
+pub const RELOAD_ACCELERATOR: &str = "CmdOrCtrl+R";
+pub const GO_BACK_ACCELERATOR: &str = "CmdOrCtrl+[";
+pub const GO_FORWARD_ACCELERATOR: &str = "CmdOrCtrl+]";
+pub const ZOOM_IN_ACCELERATOR: &str = "CmdOrCtrl+=";
+pub const ZOOM_OUT_ACCELERATOR: &str = "CmdOrCtrl+-";
+pub const ZOOM_RESET_ACCELERATOR: &str = "CmdOrCtrl+0";
+/// Help > Keyboard Shortcuts.
+pub const CHEAT_SHEET_ACCELERATOR: &str = "CmdOrCtrl+/";
+
+/// Bound by the app's menus whatever the settings say.
+const FIXED: [(&str, &str); 8] = [
+    ("Reload", RELOAD_ACCELERATOR),
+    ("Back", GO_BACK_ACCELERATOR),
+    ("Forward", GO_FORWARD_ACCELERATOR),
+    ("Zoom In", ZOOM_IN_ACCELERATOR),
+    ("Zoom Out", ZOOM_OUT_ACCELERATOR),
+    ("Actual Size", ZOOM_RESET_ACCELERATOR),
+    ("Close Window", "CmdOrCtrl+W"),
+    ("Keyboard Shortcuts", CHEAT_SHEET_ACCELERATOR),
+];
+
+/// Taken by the system (or by the standard app menu) before the app ever
+/// sees them.
+#[cfg(target_os = "macos")]
+const SYSTEM: [&str; 6] = [
+    "CmdOrCtrl+Q",
+    "CmdOrCtrl+H",
+    "CmdOrCtrl+M",
+    "CmdOrCtrl+Tab",
+    "CmdOrCtrl+Space",
+    "CmdOrCtrl+Alt+Esc",
+];
+#[cfg(not(target_os = "macos"))]
+const SYSTEM: [&str; 4] = ["Alt+F4", "Alt+Tab", "Ctrl+Alt+Delete", "Ctrl+Esc"];
+
+/// `accelerator` parsed for comparing. The menus read `Cmd` and
+/// `CmdOrCtrl` as different modifiers on macOS, though both are the
+/// Command key there.
+pub fn parse(accelerator: &str) -> Option<Accelerator> {
+    accelerator
+        .split('+')
+        .map(|part| match part.trim().to_ascii_lowercase().as_str() {
+            "cmd" | "command" | "super" => "CmdOrCtrl",
+            _ => part,
+        })
+        .collect::<Vec<_>>()
+        .join("+")
+        .parse()
+        .ok()
+}
+
+/// Why a shortcut was left unbound.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Problem {
+    Invalid,
+    /// Already bound to another of the app's actions.
+    TakenBy(&'static str),
+    /// Kept by the system.
+    System,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ShortcutProblem {
+    pub action: &'static str,
+    pub accelerator: String,
+    pub problem: Problem,
+}
+
+impl ShortcutProblem {
+    pub fn describe(&self) -> String {
+        let Self {
+            action,
+            accelerator,
+            problem,
+        } = self;
+        match problem {
+            Problem::Invalid => format!("{action}: \u{201c}{accelerator}\u{201d} isn't a shortcut"),
+            Problem::TakenBy(other) => {
+                format!("{action}: {accelerator} is already used by {other}")
+            }
+            Problem::System => format!("{action}: {accelerator} is reserved by the system"),
+        }
+    }
+}
+
+/// The settings' shortcuts, by the action they're for.
+const fn configurable(shortcuts: &mut ShortcutSettings) -> [(&'static str, &mut String); 8] {
+    [
+        ("New Chat", &mut shortcuts.new_chat),
+        ("New Window", &mut shortcuts.new_window),
+        ("Settings", &mut shortcuts.open_settings),
+        ("Always on Top", &mut shortcuts.always_on_top),
+        ("Mini Chat", &mut shortcuts.mini_chat),
+        ("Focus Mode", &mut shortcuts.focus_mode),
+        ("Insert Snippet", &mut shortcuts.insert_snippet),
+        ("Dictation", &mut shortcuts.dictation),
+    ]
+}
+
+/// `shortcuts` as this platform should bind them (see
+/// `ShortcutSettings::for_platform`), with any that can't be bound cleared,
+/// and what was wrong with those. Earlier shortcuts win a clash.
+pub fn check(shortcuts: &ShortcutSettings) -> (ShortcutSettings, Vec<ShortcutProblem>) {
+    let mut checked = shortcuts.for_platform();
+    let mut bound: Vec<(&'static str, Accelerator)> = FIXED
+        .iter()
+        .filter_map(|&(action, accelerator)| Some((action, parse(accelerator)?)))
+        .collect();
+    let system: Vec<Accelerator> = SYSTEM.iter().filter_map(|s| parse(s)).collect();
+    let mut problems = Vec::new();
+
+    for (action, accelerator) in configurable(&mut checked) {
+        // What the menus will make of it decides whether it's valid.
+        let parsed = accelerator
+            .parse::<Accelerator>()
+            .ok()
+            .and_then(|_| parse(accelerator));
+        let problem = match parsed {
+            None => Problem::Invalid,
+            Some(parsed) if system.contains(&parsed) => Problem::System,
+            Some(parsed) => {
+                if let Some(&(other, _)) = bound.iter().find(|(_, other)| *other == parsed) {
+                    Problem::TakenBy(other)
+                } else {
+                    bound.push((action, parsed));
+                    continue;
+                }
+            }
+        };
+        problems.push(ShortcutProblem {
+            action,
+            accelerator: std::mem::take(accelerator),
+            problem,
+        });
+    }
+    (checked, problems)
+}
+
+/// Why `accelerator` can't be given to one of the settings' shortcuts, for
+/// the ones `check` decides before looking at the other settings.
+pub fn unavailable(accelerator: &str) -> Option<Problem> {
+    let Some(parsed) = accelerator
+        .parse::<Accelerator>()
+        .ok()
+        .and_then(|_| parse(accelerator))
+    else {
+        return Some(Problem::Invalid);
+    };
+    if SYSTEM.iter().any(|system| parse(system) == Some(parsed)) {
+        return Some(Problem::System);
+    }
+    FIXED
+        .iter()
+        .find(|(_, fixed)| parse(fixed) == Some(parsed))
+        .map(|&(action, _)| Problem::TakenBy(action))
+}
+
+/// The ones of `shortcuts` that can be bound, as `check` leaves them.
+pub fn bindable(shortcuts: &ShortcutSettings) -> ShortcutSettings {
+    check(shortcuts).0
+}
+
+/// A bound shortcut, as the cheat sheet shows it.
+#[derive(Debug, Serialize)]
+pub struct ActiveShortcut {
+    action: &'static str,
+    /// `⇧⌘N` on macOS, `Ctrl+Shift+N` elsewhere.
+    keys: String,
+    /// Changed from the default in settings.
+    customized: bool,
+}
+
+/// How `key` (an accelerator's last part) is printed on the keyboard.
+pub fn key_label(key: &str) -> String {
+    let lower = key.to_ascii_lowercase();
+    let label = match lower.as_str() {
+        "comma" => ",",
+        "period" => ".",
+        "slash" => "/",
+        "backslash" => "\\",
+        "minus" => "-",
+        "equal" | "plus" => "=",
+        "bracketleft" => "[",
+        "bracketright" => "]",
+        "backquote" => "`",
+        "semicolon" => ";",
+        "quote" => "'",
+        "escape" | "esc" => "Esc",
+        "space" => "Space",
+        "enter" | "return" => "Enter",
+        "tab" => "Tab",
+        _ => {
+            let key = lower
+                .strip_prefix("key")
+                .or_else(|| lower.strip_prefix("digit"))
+                .filter(|rest| rest.len() == 1)
+                .unwrap_or(key);
+            return if key.chars().count() == 1 {
+                key.to_uppercase()
+            } else {
+                key.to_string()
+            };
+        }
+    };
+    label.to_string()
+}
+
+/// `accelerator` the way each platform writes shortcuts: modifier symbols in
+/// the standard order on macOS, names joined by `+` elsewhere.
+pub fn display_keys(accelerator: &str, macos: bool) -> String {
+    let mut parts: Vec<&str> = accelerator.split('+').map(str::trim).collect();
+    let key = parts.pop().map(key_label).unwrap_or_default();
+    let has = |names: &[&str]| {
+        parts
+            .iter()
+            .any(|part| names.iter().any(|name| part.eq_ignore_ascii_case(name)))
+    };
+    let command = has(&[
+        "CmdOrCtrl",
+        "CommandOrControl",
+        "CmdOrControl",
+        "CommandOrCtrl",
+    ]);
+    let modifiers: [(bool, &str, &str); 5] = [
+        (
+            has(&["Ctrl", "Control"]) || (command && !macos),
+            "\u{2303}",
+            "Ctrl",
+        ),
+        (has(&["Alt", "Option"]), "\u{2325}", "Alt"),
+        (has(&["Shift"]), "\u{21e7}", "Shift"),
+        (has(&["Cmd", "Command", "Super"]) && !macos, "", "Super"),
+        (
+            macos && (command || has(&["Cmd", "Command", "Super"])),
+            "\u{2318}",
+            "",
+        ),
+    ];
+    let held = modifiers.iter().filter(|(held, ..)| *held);
+    if macos {
+        held.map(|(_, symbol, _)| *symbol)
+            .chain(std::iter::once(key.as_str()))
+            .collect()
+    } else {
+        held.map(|(_, _, name)| *name)
+            .chain(std::iter::once(key.as_str()))
+            .collect::<Vec<_>>()
+            .join("+")
+    }
+}
+
+/// Every shortcut the menus have bound, by action: the settings' (those
+/// `check` lets through) and then the fixed ones.
+pub fn bound(shortcuts: &ShortcutSettings) -> Vec<(&'static str, String)> {
+    let (mut checked, _) = check(shortcuts);
+    configurable(&mut checked)
+        .into_iter()
+        .filter(|(_, accelerator)| !accelerator.is_empty())
+        .map(|(action, accelerator)| (action, accelerator.clone()))
+        .chain(
+            FIXED
+                .iter()
+                .map(|&(action, accelerator)| (action, accelerator.to_string())),
+        )
+        .collect()
+}
+
+/// Every shortcut the menus have bound, as the cheat sheet lists them.
+pub fn active(shortcuts: &ShortcutSettings) -> Vec<ActiveShortcut> {
+    let macos = cfg!(target_os = "macos");
+    let mut defaults = ShortcutSettings::default().for_platform();
+    let defaults = configurable(&mut defaults);
+    bound(shortcuts)
+        .into_iter()
+        .map(|(action, accelerator)| ActiveShortcut {
+            action,
+            keys: display_keys(&accelerator, macos),
+            customized: defaults.iter().any(|(default_action, default)| {
+                *default_action == action && **default != accelerator
+            }),
+        })
+        .collect()
+}
+
+/// Tell the user which configured shortcuts were left unbound, if any,
+/// offering Settings to change them.
+pub fn report_problems(app: &AppHandle) {
+    let (_, problems) = check(&app.state::<ConfigState>().config().shortcuts);
+    if problems.is_empty() {
+        return;
+    }
+    let list = problems
+        .iter()
+        .map(|problem| format!("\u{2022} {}", problem.describe()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let handle = app.clone();
+    app.dialog()
+        .message(format!(
+            "These shortcuts couldn't be set, so they're turned off until they're changed; \
+             the others work as usual.\n\n{list}"
+        ))
+        .title("Shortcuts Not Set")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            OPEN_SETTINGS_LABEL.to_string(),
+            DISMISS_LABEL.to_string(),
+        ))
+        .show_with_result(move |result| {
+            let open = match result {
+                MessageDialogResult::Ok => true,
+                MessageDialogResult::Custom(label) => label == OPEN_SETTINGS_LABEL,
+                _ => false,
+            };
+            if open {
+                crate::window::open_settings(&handle);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_shortcuts_that_cant_be_bound_are_skipped() {
+        let shortcuts = ShortcutSettings {
+            new_chat: "CmdOrCtrl+N".to_string(),
+            new_window: "CmdOrCtrl+Shfit+N".to_string(),
+            open_settings: "CmdOrCtrl+R".to_string(),
+            always_on_top: "CmdOrCtrl+N".to_string(),
+            mini_chat: SYSTEM[0].to_string(),
+            ..ShortcutSettings::default()
+        };
+        let (checked, problems) = check(&shortcuts);
+
+        assert_eq!(checked.new_chat, shortcuts.for_platform().new_chat);
+        assert!(checked.new_window.is_empty());
+        assert!(checked.always_on_top.is_empty());
+        let problems: Vec<_> = problems
+            .iter()
+            .map(|problem| (problem.action, &problem.problem))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                ("New Window", &Problem::Invalid),
+                ("Settings", &Problem::TakenBy("Reload")),
+                ("Always on Top", &Problem::TakenBy("New Chat")),
+                ("Mini Chat", &Problem::System),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_defaults_all_bind() {
+        assert!(check(&ShortcutSettings::default()).1.is_empty());
+    }
+
+    #[test]
+    fn shortcuts_are_shown_the_platforms_way() {
+        assert_eq!(display_keys("CmdOrCtrl+Shift+N", true), "\u{21e7}\u{2318}N");
+        assert_eq!(display_keys("CmdOrCtrl+Shift+N", false), "Ctrl+Shift+N");
+        assert_eq!(display_keys("Alt+Ctrl+Comma", true), "\u{2303}\u{2325},");
+        assert_eq!(display_keys("CmdOrCtrl+/", false), "Ctrl+/");
+        assert_eq!(display_keys("Shift+F5", false), "Shift+F5");
+        assert_eq!(display_keys("Ctrl+KeyK", false), "Ctrl+K");
+    }
+}
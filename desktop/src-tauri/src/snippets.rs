@@ -0,0 +1,139 @@
+// Prompt snippets: named pieces of prompt kept in the config (`[[snippets]]`)
+// and edited in Settings → Snippets, to drop into the chat box. Edit >
+// Insert Snippet... (`shortcuts.insert_snippet`) opens a picker over the
+// page to choose one by name, and typing `;;keyword` and then a space or
+// Tab swaps in the snippet with that keyword. Both happen in the page
+// (`snippets.js`), with the snippets the desktop gives it. In a snippet's
+// text, `{{date}}` becomes today's date and `{{cursor}}` is where the caret
+// is left.
+
+use crate::assets::{script, Script};
+use crate::config::{ConfigState, Snippet};
+use crate::window::is_bundled_page_window;
+use std::collections::HashSet;
+use tauri::{AppHandle, Manager, Webview, WebviewWindow};
+
+const MAX_SNIPPETS: usize = 200;
+const MAX_TEXT_CHARS: usize = 20_000;
+const MAX_KEYWORD_CHARS: usize = 32;
+
+pub fn validate(snippets: &[Snippet]) -> Result<(), String> {
+    if snippets.len() > MAX_SNIPPETS {
+        return Err(format!("There can be up to {MAX_SNIPPETS} snippets"));
+    }
+    let mut names = HashSet::new();
+    let mut keywords = HashSet::new();
+    for snippet in snippets {
+        let name = snippet.name.trim();
+        if name.is_empty() {
+            return Err("Every snippet needs a name".to_string());
+        }
+        if !names.insert(name.to_lowercase()) {
+            return Err(format!(
+                "There are two snippets named \u{201c}{name}\u{201d}"
+            ));
+        }
+        if snippet.text.trim().is_empty() {
+            return Err(format!("The snippet \u{201c}{name}\u{201d} is empty"));
+        }
+        if snippet.text.chars().count() > MAX_TEXT_CHARS {
+            return Err(format!(
+                "The snippet \u{201c}{name}\u{201d} is longer than {MAX_TEXT_CHARS} characters"
+            ));
+        }
+        let Some(keyword) = &snippet.keyword else {
+            continue;
+        };
+        if keyword.is_empty()
+            || keyword.chars().count() > MAX_KEYWORD_CHARS
+            || !keyword
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "The snippet \u{201c}{name}\u{201d}: a keyword is up to {MAX_KEYWORD_CHARS} \
+                 letters, digits, - and _"
+            ));
+        }
+        if !keywords.insert(keyword.to_lowercase()) {
+            return Err(format!("Two snippets use the keyword ;;{keyword}"));
+        }
+    }
+    Ok(())
+}
+
+fn set_page_snippets(webview: &Webview, snippets: &[Snippet]) {
+    let Ok(snippets) = serde_json::to_string(snippets) else {
+        return;
+    };
+    crate::watchdog::eval(
+        webview,
+        format!("window.__ONYX_SNIPPETS__?.set({snippets})"),
+        "apply snippets",
+    );
+}
+
+pub fn inject_snippets_script(webview: &Webview) {
+    if is_bundled_page_window(webview.label()) {
+        return;
+    }
+    let app = webview.app_handle();
+    let script = script(app, Script::Snippets);
+    if crate::watchdog::eval(webview, &*script, "inject snippets script") {
+        set_page_snippets(webview, &app.state::<ConfigState>().config().snippets);
+    }
+}
+
+/// Give `window`'s page the snippets as they're configured now.
+pub fn apply_snippets(app: &AppHandle, window: &WebviewWindow) {
+    set_page_snippets(
+        window.as_ref(),
+        &app.state::<ConfigState>().config().snippets,
+    );
+}
+
+/// Edit > Insert Snippet...: the picker, in the focused chat window.
+pub fn pick_focused(app: &AppHandle) {
+    let windows = app.webview_windows();
+    let window = windows
+        .iter()
+        .find(|(label, window)| {
+            !is_bundled_page_window(label) && window.is_focused().unwrap_or(false)
+        })
+        .map(|(_, window)| window);
+    if let Some(window) = window {
+        crate::watchdog::eval(
+            window.as_ref(),
+            "window.__ONYX_SNIPPETS__?.pick()",
+            "open the snippet picker",
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snippets_need_distinct_names_and_keywords() {
+        let snippet = |name: &str, keyword: Option<&str>| Snippet {
+            name: name.to_string(),
+            keyword: keyword.map(str::to_string),
+            text: "Summarize this in three bullet points:\n\n{{cursor}}".to_string(),
+        };
+        assert!(validate(&[snippet("Summary", Some("tldr")), snippet("Review", None)]).is_ok());
+        assert!(validate(&[snippet("Summary", None), snippet("summary ", None)]).is_err());
+        assert!(validate(&[
+            snippet("Summary", Some("tldr")),
+            snippet("Short", Some("TLDR"))
+        ])
+        .is_err());
+        assert!(validate(&[snippet("Summary", Some(";;tldr"))]).is_err());
+        assert!(validate(&[snippet(" ", None)]).is_err());
+        assert!(validate(&[Snippet {
+            text: "  ".to_string(),
+            ..snippet("Blank", None)
+        }])
+        .is_err());
+    }
+}
@@ -0,0 +1,137 @@
+// Spellchecking in the page's text fields. Each engine handles it
+// differently: WebKitGTK ships with it off and takes its dictionaries per
+// webview context, WKWebView goes through the shared `NSSpellChecker` (one
+// language at a time), and WebView2 always follows the system's languages.
+// On top of the engine settings, `spellcheck.js` turns it off in the page's
+// fields when it's disabled, which is the only switch WebView2 has.
+
+use crate::assets::{script, Script};
+use crate::config::{validate_config, ConfigState, SpellcheckSettings};
+#[cfg(not(target_os = "windows"))]
+use crate::debug_log::log_backend_error;
+use crate::window::is_bundled_page_window;
+use tauri::{AppHandle, Manager, Webview, WebviewWindow};
+
+/// Dictionary names the way the Linux and macOS engines spell them, with an
+/// underscore (`en_US` rather than `en-US`).
+#[cfg_attr(target_os = "windows", allow(dead_code))]
+fn dictionary_names(languages: &[String]) -> Vec<String> {
+    languages
+        .iter()
+        .map(|language| language.trim().replace('-', "_"))
+        .filter(|language| !language.is_empty())
+        .collect()
+}
+
+fn set_page_spellcheck(webview: &Webview, enabled: bool) {
+    crate::watchdog::eval(
+        webview,
+        format!("window.__ONYX_SPELLCHECK__?.set({enabled})"),
+        "apply spellcheck setting",
+    );
+}
+
+pub fn inject_spellcheck_script(webview: &Webview) {
+    let app = webview.app_handle();
+    let script = script(app, Script::Spellcheck);
+    if crate::watchdog::eval(webview, &*script, "inject spellcheck script") {
+        let enabled = app.state::<ConfigState>().config().spellcheck.enabled;
+        set_page_spellcheck(webview, enabled);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn apply_to_engine(app: &AppHandle, window: &WebviewWindow, settings: &SpellcheckSettings) {
+    use webkit2gtk::{WebContextExt, WebViewExt};
+
+    let enabled = settings.enabled;
+    let languages = dictionary_names(&settings.languages);
+    if let Err(e) = window.with_webview(move |webview| {
+        let Some(context) = webview.inner().context() else {
+            return;
+        };
+        context.set_spell_checking_enabled(enabled);
+        if !languages.is_empty() {
+            let languages: Vec<&str> = languages.iter().map(String::as_str).collect();
+            context.set_spell_checking_languages(&languages);
+        }
+    }) {
+        log_backend_error(app, &format!("Failed to apply spellcheck setting: {e}"));
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn apply_to_engine(app: &AppHandle, _window: &WebviewWindow, settings: &SpellcheckSettings) {
+    use objc2_app_kit::NSSpellChecker;
+    use objc2_foundation::NSString;
+
+    let languages = dictionary_names(&settings.languages);
+    let result = app.run_on_main_thread(move || {
+        let checker = NSSpellChecker::sharedSpellChecker();
+        // Without a language of its own the checker guesses each text's.
+        let chosen = languages
+            .iter()
+            .any(|language| checker.setLanguage(&NSString::from_str(language)));
+        checker.setAutomaticallyIdentifiesLanguages(!chosen);
+    });
+    if let Err(e) = result {
+        log_backend_error(app, &format!("Failed to apply spellcheck languages: {e}"));
+    }
+}
+
+#[cfg(target_os = "windows")]
+const fn apply_to_engine(
+    _app: &AppHandle,
+    _window: &WebviewWindow,
+    _settings: &SpellcheckSettings,
+) {
+}
+
+/// Apply the configured spellchecking to `window`'s webview and current page.
+pub fn apply_spellcheck(app: &AppHandle, window: &WebviewWindow) {
+    let settings = app.state::<ConfigState>().config().spellcheck;
+    apply_to_engine(app, window, &settings);
+    set_page_spellcheck(window.as_ref(), settings.enabled);
+}
+
+/// Turn spellchecking on or off and pick its dictionaries (`en_US`,
+/// `de_DE`; empty follows the system), saved and applied to every window
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn set_spellcheck(
+    app: AppHandle,
+    state: tauri::State<ConfigState>,
+    enabled: bool,
+    languages: Vec<String>,
+) -> Result<SpellcheckSettings, String> {
+    let settings = SpellcheckSettings {
+        enabled,
+        languages: languages
+            .iter()
+            .map(|language| language.trim().to_string())
+            .filter(|language| !language.is_empty())
+            .collect(),
+    };
+    let mut config = state.config();
+    config.spellcheck = settings.clone();
+    validate_config(&config)?;
+
+    let saved = state.update_and_persist(|c| c.spellcheck = settings)?;
+    for (label, window) in app.webview_windows() {
+        if !is_bundled_page_window(&label) {
+            apply_spellcheck(&app, &window);
+        }
+    }
+    Ok(saved.spellcheck)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dictionary_names;
+
+    #[test]
+    fn dictionaries_use_underscores() {
+        let languages = ["en-US".to_string(), " de_DE ".to_string(), " ".to_string()];
+        assert_eq!(dictionary_names(&languages), ["en_US", "de_DE"]);
+    }
+}
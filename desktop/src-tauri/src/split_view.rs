@@ -0,0 +1,240 @@
+// Split view: one window hosting two webviews side by side -- say, a chat
+// next to the admin connectors page. `WebviewWindow` is strictly one window,
+// one webview, so split windows are plain `Window`s with two child webviews
+// laid out by hand (which needs Tauri's `unstable` multi-webview API). The
+// panes scale with the window on their own; the layout is only re-applied
+// when panes are swapped or the divider moves.
+
+use crate::config::{validate_server_url, ConfigState};
+use crate::debug_log::log_backend_error;
+use crate::window::STANDARD_WINDOW;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::window::WindowBuilder;
+use tauri::{
+    AppHandle, LogicalPosition, LogicalSize, Manager, Rect, Webview, WebviewBuilder, WebviewUrl,
+    Window,
+};
+use url::Url;
+
+/// Page the second pane opens when none is given.
+const DEFAULT_SECOND_PANE_PATH: &str = "/admin/connectors";
+/// Narrowest a pane can be, as a share of the window.
+const MIN_PANE_SHARE: f64 = 0.2;
+
+/// Which webview is on which side, and where the divider is.
+struct SplitLayout {
+    left: String,
+    right: String,
+    /// The left pane's share of the window width.
+    ratio: f64,
+}
+
+/// Managed state: the layout of each split window, by window label.
+#[derive(Default)]
+pub struct SplitViews(Mutex<HashMap<String, SplitLayout>>);
+
+/// Resolve a pane target: a path on the configured server, a full http(s)
+/// URL, or `default_path` on the server when none is given.
+fn resolve_pane_url(server: &Url, target: Option<&str>, default_path: &str) -> Result<Url, String> {
+    match target.map(str::trim).filter(|target| !target.is_empty()) {
+        None => server.join(default_path).map_err(|e| e.to_string()),
+        Some(path) if path.starts_with('/') => server.join(path).map_err(|e| e.to_string()),
+        Some(url) => validate_server_url(url),
+    }
+}
+
+/// Widths of the left and right panes for a window `width` wide.
+fn pane_widths(width: f64, ratio: f64) -> (f64, f64) {
+    let left = (width * ratio.clamp(MIN_PANE_SHARE, 1.0 - MIN_PANE_SHARE)).round();
+    (left, width - left)
+}
+
+/// Position both panes of `window` according to its layout.
+fn apply_layout(app: &AppHandle, window: &Window) -> Result<(), String> {
+    let state = app.state::<SplitViews>();
+    let (left, right, ratio) = state
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .get(window.label())
+        .map(|layout| (layout.left.clone(), layout.right.clone(), layout.ratio))
+        .ok_or("Not a split view window")?;
+
+    let scale = window.scale_factor().map_err(|e| e.to_string())?;
+    let size = window
+        .inner_size()
+        .map_err(|e| e.to_string())?
+        .to_logical::<f64>(scale);
+    let (left_width, right_width) = pane_widths(size.width, ratio);
+
+    for (label, x, width) in [(left, 0.0, left_width), (right, left_width, right_width)] {
+        let webview = app
+            .get_webview(&label)
+            .ok_or_else(|| format!("Pane {label} is gone"))?;
+        webview
+            .set_bounds(Rect {
+                position: LogicalPosition::new(x, 0.0).into(),
+                size: LogicalSize::new(width, size.height).into(),
+            })
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Open a new split window with `first` on the left and `second` on the
+/// right (see `resolve_pane_url` for what they can be). Blocks until both
+/// webviews exist, so it must not run on the main thread.
+pub fn open_split_view(
+    app: &AppHandle,
+    first: Option<&str>,
+    second: Option<&str>,
+) -> Result<(), String> {
+    let config = app.state::<ConfigState>().config();
+    let server = validate_server_url(&config.server_url)?;
+    let left_url = resolve_pane_url(&server, first, "/")?;
+    let right_url = resolve_pane_url(&server, second, DEFAULT_SECOND_PANE_PATH)?;
+
+    let label = format!("split-{}", uuid::Uuid::new_v4());
+    let window = WindowBuilder::new(app, &label)
+        .title(config.window_title)
+        .inner_size(STANDARD_WINDOW.width * 1.5, STANDARD_WINDOW.height)
+        .min_inner_size(STANDARD_WINDOW.min_width, STANDARD_WINDOW.min_height)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let layout = SplitLayout {
+        left: format!("{label}-left"),
+        right: format!("{label}-right"),
+        ratio: 0.5,
+    };
+    // Placeholder bounds; `apply_layout` sets the real ones.
+    for (pane, url) in [(&layout.left, left_url), (&layout.right, right_url)] {
+        let webview = window
+            .add_child(
+                WebviewBuilder::new(pane, WebviewUrl::External(url))
+                    .additional_browser_args(&crate::locale::browser_args()),
+                LogicalPosition::new(0.0, 0.0),
+                LogicalSize::new(1.0, 1.0),
+            )
+            .map_err(|e| e.to_string())?;
+        if let Err(e) = webview.set_auto_resize(true) {
+            log_backend_error(app, &format!("Failed to make split pane resizable: {e}"));
+        }
+    }
+
+    app.state::<SplitViews>()
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .insert(label, layout);
+    apply_layout(app, &window)
+}
+
+/// Fire-and-forget entry point for the menu.
+pub fn trigger_split_view(app: &AppHandle) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = open_split_view(&handle, None, None) {
+            log_backend_error(&handle, &format!("Failed to open split view: {e}"));
+        }
+    });
+}
+
+pub fn forget_window(app: &AppHandle, label: &str) {
+    app.state::<SplitViews>()
+        .0
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .remove(label);
+}
+
+fn update_layout(
+    app: &AppHandle,
+    window: &Window,
+    f: impl FnOnce(&mut SplitLayout),
+) -> Result<(), String> {
+    let state = app.state::<SplitViews>();
+    {
+        let mut views = state
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        f(views
+            .get_mut(window.label())
+            .ok_or("Not a split view window")?);
+    }
+    apply_layout(app, window)
+}
+
+/// Open a window with two panes side by side: `first` on the left, `second`
+/// on the right, each a path on the server or a full URL
+#[tauri::command]
+pub async fn open_split_view_cmd(
+    app: AppHandle,
+    first: Option<String>,
+    second: Option<String>,
+) -> Result<(), String> {
+    open_split_view(&app, first.as_deref(), second.as_deref())
+}
+
+/// Swap the two panes of the calling split window
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn swap_split_panes(webview: Webview) -> Result<(), String> {
+    update_layout(webview.app_handle(), &webview.window(), |layout| {
+        std::mem::swap(&mut layout.left, &mut layout.right);
+        layout.ratio = 1.0 - layout.ratio;
+    })
+}
+
+/// Move the divider of the calling split window; `ratio` is the left pane's
+/// share of the width
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn resize_split_view(webview: Webview, ratio: f64) -> Result<(), String> {
+    if !ratio.is_finite() {
+        return Err("Ratio must be a number".to_string());
+    }
+    update_layout(webview.app_handle(), &webview.window(), |layout| {
+        layout.ratio = ratio.clamp(MIN_PANE_SHARE, 1.0 - MIN_PANE_SHARE);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn pane_targets_resolve_against_the_server() {
+        let server = Url::parse("https://onyx.example.com").unwrap();
+        assert_eq!(
+            resolve_pane_url(&server, None, DEFAULT_SECOND_PANE_PATH)
+                .unwrap()
+                .as_str(),
+            "https://onyx.example.com/admin/connectors"
+        );
+        assert_eq!(
+            resolve_pane_url(&server, Some("/app?chatId=1"), "/")
+                .unwrap()
+                .as_str(),
+            "https://onyx.example.com/app?chatId=1"
+        );
+        assert_eq!(
+            resolve_pane_url(&server, Some("https://docs.onyx.app"), "/")
+                .unwrap()
+                .as_str(),
+            "https://docs.onyx.app/"
+        );
+        assert!(resolve_pane_url(&server, Some("file:///etc/passwd"), "/").is_err());
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn panes_split_the_width_within_limits() {
+        assert_eq!(pane_widths(1000.0, 0.5), (500.0, 500.0));
+        assert_eq!(pane_widths(1000.0, 0.05), (200.0, 800.0));
+        assert_eq!(pane_widths(1000.0, 0.95), (800.0, 200.0));
+    }
+}
@@ -0,0 +1,178 @@
+// Startup timing breakdown. Spans are recorded from `main` (config load),
+// `setup_app` (subsystem starts), and `on_page_load` (injection, first
+// navigation) until the main window finishes its first load of the server
+// page, at which point the report is frozen and logged in debug mode.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimingSpan {
+    pub name: String,
+    /// Milliseconds between process start and the start of this span.
+    pub start_ms: f64,
+    pub duration_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StartupReport {
+    /// Whether the first server page load has happened yet. Until it has,
+    /// `total_ms` is `None` and `spans` may still grow.
+    pub complete: bool,
+    pub total_ms: Option<f64>,
+    pub spans: Vec<TimingSpan>,
+}
+
+impl StartupReport {
+    /// One human-readable line per span, plus the total, for the debug log.
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .spans
+            .iter()
+            .map(|span| {
+                format!(
+                    "startup: {:<28} +{:>8.1}ms  {:>8.1}ms",
+                    span.name, span.start_ms, span.duration_ms
+                )
+            })
+            .collect();
+        if let Some(total) = self.total_ms {
+            lines.push(format!("startup: total {total:.1}ms"));
+        }
+        lines
+    }
+}
+
+#[derive(Default)]
+struct ProfilerState {
+    spans: Vec<TimingSpan>,
+    finished_at: Option<Duration>,
+}
+
+/// Managed state collecting startup spans. Created at the very top of `main`,
+/// so every offset is relative to (roughly) process start.
+pub struct StartupProfiler {
+    origin: Instant,
+    state: Mutex<ProfilerState>,
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl StartupProfiler {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            state: Mutex::new(ProfilerState::default()),
+        }
+    }
+
+    /// Run `f`, recording how long it took as a span named `name`.
+    pub fn time<T>(&self, name: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(name, start);
+        result
+    }
+
+    /// Record a span from `start` until now. Ignored once startup finished,
+    /// so per-page-load hooks don't keep growing the report forever.
+    pub fn record(&self, name: &str, start: Instant) {
+        let end = Instant::now();
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if state.finished_at.is_some() {
+            return;
+        }
+        state.spans.push(TimingSpan {
+            name: name.to_string(),
+            start_ms: millis(start.saturating_duration_since(self.origin)),
+            duration_ms: millis(end.saturating_duration_since(start)),
+        });
+    }
+
+    /// Record a span from process start until now, the first time only --
+    /// for one-off milestones like "first page load" that fire repeatedly.
+    pub fn milestone(&self, name: &str) {
+        let already_recorded = self
+            .state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .spans
+            .iter()
+            .any(|span| span.name == name);
+        if !already_recorded {
+            self.record(name, self.origin);
+        }
+    }
+
+    /// Freeze the report. Returns `true` only for the call that actually
+    /// finished it, so the caller can log the summary exactly once.
+    pub fn finish(&self) -> bool {
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if state.finished_at.is_some() {
+            return false;
+        }
+        state.finished_at = Some(self.origin.elapsed());
+        true
+    }
+
+    pub fn report(&self) -> StartupReport {
+        let state = self
+            .state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        StartupReport {
+            complete: state.finished_at.is_some(),
+            total_ms: state.finished_at.map(millis),
+            spans: state.spans.clone(),
+        }
+    }
+}
+
+/// Timing breakdown of this launch, for diagnosing slow startups.
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn get_startup_report(profiler: tauri::State<StartupProfiler>) -> StartupReport {
+    profiler.report()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StartupProfiler;
+
+    #[test]
+    fn spans_are_recorded_until_finished() {
+        let profiler = StartupProfiler::new();
+        let value = profiler.time("config_load", || 42);
+        assert_eq!(value, 42);
+        assert!(!profiler.report().complete);
+
+        assert!(profiler.finish());
+        assert!(!profiler.finish());
+        profiler.time("late", || ());
+
+        let report = profiler.report();
+        assert!(report.complete);
+        assert!(report.total_ms.is_some());
+        let names: Vec<_> = report.spans.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["config_load"]);
+    }
+
+    #[test]
+    fn milestones_are_recorded_once() {
+        let profiler = StartupProfiler::new();
+        profiler.milestone("first_page_load");
+        profiler.milestone("first_page_load");
+        let report = profiler.report();
+        assert_eq!(report.spans.len(), 1);
+        assert!(report.spans[0].start_ms.abs() < f64::EPSILON);
+    }
+}
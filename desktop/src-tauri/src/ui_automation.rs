@@ -0,0 +1,153 @@
+// Hooks for UI tests, off unless the app is started with `--automation`.
+// Every page then gets `ui_automation.js`, which gives the controls a test
+// drives stable identifiers: the web app's `data-testid`s plus a few named
+// ones (see the script), set as the element's `id` where it has none, which
+// is what each platform's accessibility tree reports (AXDOMIdentifier on
+// macOS, AutomationId on Windows). `ui_action` clicks, focuses or types into
+// an element by identifier in any window, so a test driver on any platform
+// (or a smoke test running in a page) can work the app the same way.
+
+use crate::assets::{script, Script};
+use serde::Deserialize;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Webview, WebviewWindow};
+use tokio::sync::oneshot;
+
+const ACTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Managed state: whether `--automation` was passed.
+pub struct UiAutomation {
+    enabled: bool,
+}
+
+impl UiAutomation {
+    pub const fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+}
+
+/// Register the automation state, turned on if `enabled`
+/// (`--automation`).
+pub fn manage(builder: tauri::Builder<tauri::Wry>, enabled: bool) -> tauri::Builder<tauri::Wry> {
+    builder.manage(UiAutomation::new(enabled))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UiAction {
+    Click,
+    Focus,
+    /// Focus, then enter `text` as if typed.
+    Type,
+}
+
+impl UiAction {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Click => "click",
+            Self::Focus => "focus",
+            Self::Type => "type",
+        }
+    }
+}
+
+pub fn inject_ui_automation_script(webview: &Webview) {
+    let app = webview.app_handle();
+    if !app.state::<UiAutomation>().enabled {
+        return;
+    }
+    let script = script(app, Script::UiAutomation);
+    crate::watchdog::eval(webview, &*script, "inject UI automation script");
+}
+
+/// The script that performs `action` on the element `id`, evaluating to an
+/// error message, or `null` once it's done.
+fn action_script(action: UiAction, id: &str, text: Option<&str>) -> Result<String, String> {
+    let args = serde_json::to_string(&(action.name(), id, text)).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "window.__ONYX_UI__ ? window.__ONYX_UI__.perform(...{args}) : \"The page isn't ready\""
+    ))
+}
+
+/// The page's answer: `null` when the action was done.
+fn parse_answer(result: &str) -> Result<(), String> {
+    match serde_json::from_str(result).map_err(|e| e.to_string())? {
+        serde_json::Value::Null => Ok(()),
+        serde_json::Value::String(error) => Err(error),
+        other => Err(format!("Unexpected answer from the page: {other}")),
+    }
+}
+
+async fn perform(
+    window: &WebviewWindow,
+    action: UiAction,
+    id: &str,
+    text: Option<&str>,
+) -> Result<(), String> {
+    let (tx, rx) = oneshot::channel();
+    // The callback may (in principle) run more than once; only the first
+    // answer counts.
+    let tx = Mutex::new(Some(tx));
+    window
+        .eval_with_callback(action_script(action, id, text)?, move |result| {
+            let sender = tx
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .take();
+            if let Some(sender) = sender {
+                let _ = sender.send(result);
+            }
+        })
+        .map_err(|e| e.to_string())?;
+    let result = tokio::time::timeout(ACTION_TIMEOUT, rx)
+        .await
+        .map_err(|_| "The page didn't answer".to_string())?
+        .map_err(|_| "The page didn't answer".to_string())?;
+    parse_answer(&result)
+}
+
+/// Click, focus or type into the element with identifier `id` in `window`
+/// (the calling one if left out); only with `--automation`
+#[tauri::command]
+pub async fn ui_action(
+    app: AppHandle,
+    caller: WebviewWindow,
+    window: Option<String>,
+    action: UiAction,
+    id: String,
+    text: Option<String>,
+) -> Result<(), String> {
+    if !app.state::<UiAutomation>().enabled {
+        return Err("UI automation is off; start Onyx with --automation".to_string());
+    }
+    if action == UiAction::Type && text.is_none() {
+        return Err("Nothing to type".to_string());
+    }
+    let target = match window {
+        Some(label) => app
+            .get_webview_window(&label)
+            .ok_or_else(|| format!("No window {label}"))?,
+        None => caller,
+    };
+    perform(&target, action, &id, text.as_deref()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn actions_reach_the_page_as_plain_arguments() {
+        assert_eq!(
+            action_script(UiAction::Type, "chat-input", Some("say \"hi\"")).unwrap(),
+            "window.__ONYX_UI__ ? window.__ONYX_UI__.perform(...[\"type\",\"chat-input\",\"say \\\"hi\\\"\"]) : \"The page isn't ready\""
+        );
+        assert_eq!(parse_answer("null"), Ok(()));
+        assert_eq!(
+            parse_answer("\"No element chat-input\""),
+            Err("No element chat-input".to_string())
+        );
+    }
+}
@@ -0,0 +1,187 @@
+// Health of each window's webview. Scripts reach pages through
+// fire-and-forget `eval`s, which only fail when the webview is already
+// gone -- and a wedged webview (hung renderer, page stuck in a loop) doesn't
+// fail them at all. So every `eval` goes through `eval` here, which records
+// failures per window, and a heartbeat evaluates a trivial expression in
+// each visible chat window and waits for the answer. A window that misses
+// `UNHEALTHY_AFTER` heartbeats in a row is marked unhealthy and the user is
+// offered a fresh copy of it.
+
+use crate::debug_log::log_backend_error;
+use crate::window::{is_bundled_page_window, recreate_window};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Webview, WebviewWindow};
+use tauri_plugin_dialog::{
+    DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult,
+};
+use tokio::sync::oneshot;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(5);
+const UNHEALTHY_AFTER: u32 = 2;
+const RECREATE_LABEL: &str = "Recreate Window";
+const WAIT_LABEL: &str = "Wait";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WebviewHealth {
+    failed_evals: u32,
+    last_error: Option<String>,
+    /// Heartbeats missed in a row.
+    missed_heartbeats: u32,
+    unhealthy: bool,
+}
+
+/// Managed state: `WebviewHealth` by window label, for windows that have
+/// had a failure or missed a heartbeat.
+#[derive(Default)]
+pub struct Watchdog(Mutex<BTreeMap<String, WebviewHealth>>);
+
+impl Watchdog {
+    fn update(&self, label: &str, change: impl FnOnce(&mut WebviewHealth)) -> WebviewHealth {
+        let mut windows = self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let health = windows.entry(label.to_string()).or_default();
+        change(health);
+        let health = health.clone();
+        drop(windows);
+        health
+    }
+
+    pub fn report(&self) -> BTreeMap<String, WebviewHealth> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn forget(&self, label: &str) {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(label);
+    }
+}
+
+/// Run `script` in `webview`, logging and recording a failure (`what`
+/// names the script, as in "inject titlebar script"). Returns whether the
+/// webview took it.
+pub fn eval(webview: &Webview, script: impl Into<String>, what: &str) -> bool {
+    let Err(e) = webview.eval(script) else {
+        return true;
+    };
+    let app = webview.app_handle();
+    log_backend_error(app, &format!("Failed to {what}: {e}"));
+    app.state::<Watchdog>().update(webview.label(), |health| {
+        health.failed_evals += 1;
+        health.last_error = Some(format!("{what}: {e}"));
+    });
+    false
+}
+
+/// Whether `window` answers a trivial script within `HEARTBEAT_TIMEOUT`.
+async fn responds(window: &WebviewWindow) -> bool {
+    let (tx, rx) = oneshot::channel();
+    // The callback may (in principle) run more than once; only the first
+    // answer counts.
+    let tx = Mutex::new(Some(tx));
+    let sent = window.eval_with_callback("true", move |_| {
+        let tx = tx
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .take();
+        if let Some(tx) = tx {
+            let _ = tx.send(());
+        }
+    });
+    sent.is_ok()
+        && tokio::time::timeout(HEARTBEAT_TIMEOUT, rx)
+            .await
+            .is_ok_and(|answer| answer.is_ok())
+}
+
+/// Tell the user `window` stopped responding and offer to recreate it.
+fn offer_recreate(app: &AppHandle, window: WebviewWindow) {
+    let handle = app.clone();
+    app.dialog()
+        .message(
+            "An Onyx window has stopped responding. Recreate it to reload the \
+             page in a fresh window, or wait to see if it recovers.",
+        )
+        .title("Window Not Responding")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            RECREATE_LABEL.to_string(),
+            WAIT_LABEL.to_string(),
+        ))
+        .show_with_result(move |result| {
+            let recreate = match result {
+                MessageDialogResult::Ok => true,
+                MessageDialogResult::Custom(label) => label == RECREATE_LABEL,
+                _ => false,
+            };
+            if recreate {
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = recreate_window(&handle, &window) {
+                        log_backend_error(&handle, &format!("Failed to recreate window: {e}"));
+                    }
+                });
+            }
+        });
+}
+
+async fn check(app: AppHandle, window: WebviewWindow) {
+    let answered = responds(&window).await;
+    let watchdog = app.state::<Watchdog>();
+    if answered {
+        // Only windows with something to report are tracked.
+        let recovered = watchdog
+            .report()
+            .get(window.label())
+            .is_some_and(|health| health.missed_heartbeats > 0);
+        if recovered {
+            watchdog.update(window.label(), |health| {
+                health.missed_heartbeats = 0;
+                health.unhealthy = false;
+            });
+        }
+        return;
+    }
+
+    let health = watchdog.update(window.label(), |health| {
+        health.missed_heartbeats += 1;
+        health.unhealthy = health.missed_heartbeats >= UNHEALTHY_AFTER;
+    });
+    // Asked once per hang; a window that recovers and hangs again asks again.
+    if health.missed_heartbeats == UNHEALTHY_AFTER {
+        log_backend_error(
+            &app,
+            &format!("Window {} stopped responding", window.label()),
+        );
+        offer_recreate(&app, window);
+    }
+}
+
+/// Check on every visible chat window for as long as the app runs. Hidden
+/// and minimized ones are skipped, since the system may suspend them.
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(HEARTBEAT_INTERVAL).await;
+            for (label, window) in app.webview_windows() {
+                if is_bundled_page_window(&label)
+                    || !window.is_visible().unwrap_or(false)
+                    || window.is_minimized().unwrap_or(true)
+                {
+                    continue;
+                }
+                tauri::async_runtime::spawn(check(app.clone(), window));
+            }
+        }
+    });
+}
@@ -1,20 +1,305 @@
-use crate::config::ConfigState;
+use crate::assets::{script, Script};
+#[cfg(not(target_os = "macos"))]
+use crate::config::get_data_dir;
+#[cfg(not(target_os = "linux"))]
+use crate::config::AppearanceSettings;
+#[cfg(target_os = "macos")]
+use crate::config::VibrancyMaterial;
+#[cfg(target_os = "windows")]
+use crate::config::WindowsBackdrop;
+use crate::config::{
+    validate_server_url, AppConfig, BackgroundBehavior, ConfigState, TitlebarMode, WindowPosition,
+    WindowSettings,
+};
+#[cfg(not(target_os = "linux"))]
+use crate::debug_log::log_debug;
 use crate::debug_log::{log_backend_error, maybe_open_devtools};
+use crate::route::Route;
+use crate::watchdog;
+use std::collections::{BTreeMap, HashMap};
 use std::process::Command;
+use std::sync::Mutex;
+#[cfg(target_os = "linux")]
+use std::sync::OnceLock;
 #[cfg(target_os = "macos")]
 use std::time::Duration;
-use tauri::{AppHandle, Manager, Webview, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+use tauri::{
+    AppHandle, LogicalPosition, Manager, Monitor, Webview, WebviewUrl, WebviewWindow,
+    WebviewWindowBuilder, Window,
+};
 #[cfg(target_os = "macos")]
 use tokio::time::sleep;
 use url::Url;
+#[cfg(target_os = "windows")]
+use window_vibrancy::{apply_acrylic, apply_mica, apply_tabbed};
 #[cfg(target_os = "macos")]
 use window_vibrancy::{apply_vibrancy, NSVisualEffectMaterial};
 
-#[cfg(target_os = "macos")]
-const TITLEBAR_SCRIPT: &str = include_str!("../../src/titlebar.js");
-const CHAT_LINK_INTERCEPT_SCRIPT: &str = include_str!("scripts/chat_link_intercept.js");
+pub const SETTINGS_WINDOW_LABEL: &str = "settings";
+pub const PERMISSIONS_WINDOW_LABEL: &str = "permissions";
+pub const CHEAT_SHEET_WINDOW_LABEL: &str = "shortcuts";
+pub const RECORDING_WINDOW_LABEL: &str = "recording";
+pub const LOCK_WINDOW_LABEL: &str = "lock";
+pub const OFFLINE_WINDOW_LABEL: &str = "offline";
+pub const CLIPBOARD_WINDOW_LABEL: &str = "clipboard_history";
+pub const TRANSCRIPT_WINDOW_LABEL: &str = "transcript";
+pub const ONBOARDING_WINDOW_LABEL: &str = "onboarding";
+
+/// Windows showing a bundled page rather than the server, which chat-window
+/// settings (menu bar, always on top) don't apply to.
+pub fn is_bundled_page_window(label: &str) -> bool {
+    matches!(
+        label,
+        SETTINGS_WINDOW_LABEL
+            | PERMISSIONS_WINDOW_LABEL
+            | CHEAT_SHEET_WINDOW_LABEL
+            | RECORDING_WINDOW_LABEL
+            | LOCK_WINDOW_LABEL
+            | OFFLINE_WINDOW_LABEL
+            | CLIPBOARD_WINDOW_LABEL
+            | TRANSCRIPT_WINDOW_LABEL
+            | ONBOARDING_WINDOW_LABEL
+    )
+}
+
+/// Partition of windows that share the default webview data: the main
+/// window and plain new windows.
+pub const DEFAULT_PARTITION: &str = "default";
+
+/// Managed state: the webview partition of each window opened with a
+/// session, by label. Settings kept per profile (zoom, permissions) are keyed
+/// by partition, since window labels are random per launch.
+#[derive(Default)]
+pub struct WindowPartitions(Mutex<HashMap<String, String>>);
+
+impl WindowPartitions {
+    fn insert(&self, label: &str, partition: &str) {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(label.to_string(), partition.to_string());
+    }
+
+    pub fn remove(&self, label: &str) {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(label);
+    }
+
+    pub fn partition_of(&self, label: &str) -> String {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(label)
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_PARTITION.to_string())
+    }
+}
+
+/// Managed state: partitions whose server blocked the injected titlebar
+/// (usually a strict Content-Security-Policy), with the reason. Their
+/// windows use the native titlebar instead for the rest of the run.
+#[derive(Default)]
+pub struct TitlebarFallbacks(Mutex<BTreeMap<String, String>>);
+
+impl TitlebarFallbacks {
+    /// Record `partition` as falling back; false if it already was.
+    fn insert(&self, partition: &str, reason: &str) -> bool {
+        let mut fallbacks = self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if fallbacks.contains_key(partition) {
+            return false;
+        }
+        fallbacks.insert(partition.to_string(), reason.to_string());
+        true
+    }
+
+    pub fn contains(&self, partition: &str) -> bool {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .contains_key(partition)
+    }
+
+    pub fn all(&self) -> BTreeMap<String, String> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+}
+
+const PROFILE_PARTITION_PREFIX: &str = "profile-";
+
+/// The name of the profile whose windows use `partition` (on any of its
+/// accounts), if it's one.
+pub fn profile_name(partition: &str) -> Option<&str> {
+    crate::accounts::split_account(partition)
+        .0
+        .strip_prefix(PROFILE_PARTITION_PREFIX)
+}
+
+/// The titlebar `config` asks for in `partition`: the profile's own choice,
+/// or the `[appearance]` one.
+fn configured_titlebar(config: &AppConfig, partition: &str) -> TitlebarMode {
+    profile_name(partition)
+        .and_then(|name| config.profiles.get(name))
+        .and_then(|profile| profile.titlebar)
+        .unwrap_or(config.appearance.titlebar)
+}
+
+/// The titlebar windows in `partition` get: as configured, unless the
+/// injected one already failed there.
+fn titlebar_mode(app: &AppHandle, partition: &str) -> TitlebarMode {
+    if app.state::<TitlebarFallbacks>().contains(partition) {
+        return TitlebarMode::Native;
+    }
+    configured_titlebar(&app.state::<ConfigState>().config(), partition)
+}
+
+/// Whether the window (or webview) `label` gets the native titlebar rather
+/// than the injected one. (Windows always draws its own titlebar.)
+#[cfg_attr(target_os = "windows", allow(dead_code))]
+fn uses_native_titlebar(app: &AppHandle, label: &str) -> bool {
+    let partition = app.state::<WindowPartitions>().partition_of(label);
+    titlebar_mode(app, &partition) == TitlebarMode::Native
+}
+
+/// Default and minimum inner size for a kind of window, in logical pixels.
+pub struct WindowPreset {
+    pub width: f64,
+    pub height: f64,
+    pub min_width: f64,
+    pub min_height: f64,
+}
+
+/// Regular chat windows (matches the main window in `tauri.conf.json`), as
+/// `[window]` in the config has them unless it says otherwise.
+pub const STANDARD_WINDOW: WindowPreset = WindowPreset {
+    width: 1232.0,
+    height: 800.0,
+    min_width: 800.0,
+    min_height: 600.0,
+};
+
+/// Settings and permissions.
+pub const BUNDLED_PAGE_WINDOW: WindowPreset = WindowPreset {
+    width: 560.0,
+    height: 720.0,
+    min_width: 480.0,
+    min_height: 520.0,
+};
+
+/// The keyboard shortcut overlay (see `cheat_sheet.rs`), which isn't
+/// resizable.
+pub const CHEAT_SHEET_WINDOW: WindowPreset = WindowPreset {
+    width: 440.0,
+    height: 520.0,
+    min_width: 440.0,
+    min_height: 520.0,
+};
+
+/// The push-to-talk recording indicator (see `push_to_talk.rs`).
+#[cfg(feature = "voice")]
+pub const RECORDING_WINDOW: WindowPreset = WindowPreset {
+    width: 220.0,
+    height: 56.0,
+    min_width: 220.0,
+    min_height: 56.0,
+};
+
+/// What the app shows while it's locked (see `lock.rs`), which isn't
+/// resizable.
+pub const LOCK_WINDOW: WindowPreset = WindowPreset {
+    width: 380.0,
+    height: 300.0,
+    min_width: 380.0,
+    min_height: 300.0,
+};
+
+/// The kept chats shown while the server is offline (see
+/// `offline_cache.rs`).
+#[cfg(feature = "offline-cache")]
+pub const OFFLINE_WINDOW: WindowPreset = WindowPreset {
+    width: 960.0,
+    height: 680.0,
+    min_width: 640.0,
+    min_height: 480.0,
+};
+
+/// The picker over the clipboard history (see `clipboard_history.rs`).
+#[cfg(feature = "clipboard-history")]
+pub const CLIPBOARD_WINDOW: WindowPreset = WindowPreset {
+    width: 440.0,
+    height: 540.0,
+    min_width: 360.0,
+    min_height: 360.0,
+};
+
+/// A chat transcript opened from a file (see `documents.rs`).
+pub const TRANSCRIPT_WINDOW: WindowPreset = WindowPreset {
+    width: 760.0,
+    height: 680.0,
+    min_width: 480.0,
+    min_height: 400.0,
+};
+
+/// The compact always-on-top view (see `mini_chat.rs`).
+pub const MINI_CHAT_WINDOW: WindowPreset = WindowPreset {
+    width: 380.0,
+    height: 560.0,
+    min_width: 320.0,
+    min_height: 400.0,
+};
+
+/// Hide `window` rather than closing or minimizing it, so the app keeps
+/// running in the background (`behavior` is anything but `Quit`).
+pub fn hide_to_background(app: &AppHandle, window: &Window, behavior: BackgroundBehavior) {
+    if let Err(e) = window.hide() {
+        log_backend_error(app, &format!("Failed to hide window: {e}"));
+        return;
+    }
+
+    #[cfg(target_os = "macos")]
+    if behavior == BackgroundBehavior::Tray
+        && !app
+            .windows()
+            .values()
+            .any(|window| window.is_visible().unwrap_or(false))
+    {
+        if let Err(e) = app.set_activation_policy(tauri::ActivationPolicy::Accessory) {
+            log_backend_error(app, &format!("Failed to hide Dock icon: {e}"));
+        }
+    }
+    #[cfg(not(target_os = "macos"))]
+    let _ = behavior;
+}
+
+/// Bring back the windows `hide_to_background` hid (and the Dock icon).
+fn restore_hidden_windows(app: &AppHandle) {
+    #[cfg(target_os = "macos")]
+    if let Err(e) = app.set_activation_policy(tauri::ActivationPolicy::Regular) {
+        log_backend_error(app, &format!("Failed to restore Dock icon: {e}"));
+    }
+
+    for (label, window) in app.windows() {
+        if is_bundled_page_window(&label) || window.is_visible().unwrap_or(true) {
+            continue;
+        }
+        if let Err(e) = window.unminimize() {
+            log_backend_error(app, &format!("Failed to unminimize window: {e}"));
+        }
+        if let Err(e) = window.show() {
+            log_backend_error(app, &format!("Failed to show window: {e}"));
+        }
+    }
+}
 
 pub fn focus_main_window(app: &AppHandle) {
+    restore_hidden_windows(app);
     if let Some(window) = app.get_webview_window("main") {
         if let Err(e) = window.unminimize() {
             log_backend_error(app, &format!("Failed to unminimize main window: {e}"));
@@ -31,37 +316,70 @@ pub fn focus_main_window(app: &AppHandle) {
 }
 
 pub fn trigger_new_chat(app: &AppHandle) {
-    let server_url = app.state::<ConfigState>().config().server_url;
+    let Some(url) = route_url(app, &Route::new_chat()) else {
+        return;
+    };
 
     if let Some(window) = app.get_webview_window("main") {
-        let url = format!("{server_url}/chat");
-        if let Err(e) = window.eval(format!("window.location.href = '{url}'")) {
-            log_backend_error(app, &format!("Failed to navigate to new chat: {e}"));
-        }
+        watchdog::eval(
+            window.as_ref(),
+            format!("window.location.href = '{url}'"),
+            "navigate to new chat",
+        );
     }
 }
 
-/// Focus the main window and navigate it to `/chat`, building it first if it
-/// doesn't exist. Building and navigating must happen in the same task --
-/// doing them as the two independent fire-and-forget steps `focus_main_window`
-/// / `trigger_new_chat` normally are lets the navigation run against a window
-/// that hasn't finished being created yet, silently dropping it.
+/// Focus the main window and navigate it to a new chat, building it first if
+/// it doesn't exist.
 pub fn open_chat_window(app: &AppHandle) {
-    if app.get_webview_window("main").is_some() {
+    open_route(app, &Route::new_chat());
+}
+
+/// `route` on the configured server.
+fn route_url(app: &AppHandle, route: &Route) -> Option<Url> {
+    match app.state::<ConfigState>().config().server_url.parse() {
+        Ok(server) => Some(route.url(&server)),
+        Err(e) => {
+            log_backend_error(
+                app,
+                &format!("Failed to open {route:?}: invalid server URL: {e}"),
+            );
+            None
+        }
+    }
+}
+
+/// Focus the main window and navigate it to `route` on the server, building
+/// it first if it doesn't exist. Building and navigating must happen in the
+/// same task -- doing them as the two independent fire-and-forget steps
+/// `focus_main_window` / `trigger_new_chat` normally are lets the navigation
+/// run against a window that hasn't finished being created yet, silently
+/// dropping it.
+pub fn open_route(app: &AppHandle, route: &Route) {
+    let Some(url) = route_url(app, route) else {
+        return;
+    };
+    let path = url.path().to_string();
+
+    if let Some(window) = app.get_webview_window("main") {
         focus_main_window(app);
-        trigger_new_chat(app);
+        watchdog::eval(
+            window.as_ref(),
+            format!("window.location.href = '{url}'"),
+            &format!("navigate to {path}"),
+        );
         return;
     }
 
     let handle = app.clone();
     tauri::async_runtime::spawn(async move {
-        match build_and_setup_window(&handle) {
+        match build_and_setup_window(&handle, None) {
             Ok(window) => {
-                let server_url = handle.state::<ConfigState>().config().server_url;
-                let url = format!("{server_url}/chat");
-                if let Err(e) = window.eval(format!("window.location.href = '{url}'")) {
-                    log_backend_error(&handle, &format!("Failed to navigate to new chat: {e}"));
-                }
+                watchdog::eval(
+                    window.as_ref(),
+                    format!("window.location.href = '{url}'"),
+                    &format!("navigate to {path}"),
+                );
             }
             Err(e) => {
                 log_backend_error(&handle, &format!("Failed to open new window: {e}"));
@@ -70,46 +388,400 @@ pub fn open_chat_window(app: &AppHandle) {
     });
 }
 
+struct MainWindowRebuild {
+    /// The page the new window should reopen.
+    url: Option<Url>,
+}
+
+/// Set while the main window is being rebuilt.
+static MAIN_WINDOW_REBUILD: Mutex<Option<MainWindowRebuild>> = Mutex::new(None);
+
+/// Replace `window`, whose webview has stopped responding, with a fresh one
+/// on the same page and webview data. Other windows get their replacement
+/// before the old one goes, so the app never runs out of windows; the main
+/// window has to be gone before a new one can take its label, so it's
+/// rebuilt from its `Destroyed` event (see `rebuilding_main_window`).
+pub fn recreate_window(app: &AppHandle, window: &WebviewWindow) -> Result<(), String> {
+    // Anything but a server page (a crashed-page placeholder, say) starts
+    // over from the session's server.
+    let url = window
+        .url()
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"));
+
+    if window.label() == "main" {
+        *MAIN_WINDOW_REBUILD
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(MainWindowRebuild { url });
+        return window.destroy().map_err(|e| e.to_string());
+    }
+
+    let partition = app.state::<WindowPartitions>().partition_of(window.label());
+    let (base, account) = crate::accounts::split_account(&partition);
+    let session = if base == DEFAULT_PARTITION || profile_name(base).is_some() {
+        crate::accounts::session(&app.state::<ConfigState>().config(), base, account)?
+    } else {
+        let url = url.as_ref().ok_or("The window's server is unknown")?;
+        Some(WindowSession::for_url(url.as_str())?)
+    };
+    let replacement = build_and_setup_window(app, session.as_ref())?;
+    if let Some(url) = url {
+        if let Err(e) = replacement.navigate(url) {
+            log_backend_error(app, &format!("Failed to reopen page: {e}"));
+        }
+    }
+    window.destroy().map_err(|e| e.to_string())
+}
+
+/// Whether the main window is being rebuilt, during which having no windows
+/// left mustn't quit the app.
+pub fn rebuilding_main_window() -> bool {
+    MAIN_WINDOW_REBUILD
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .is_some()
+}
+
+/// End a rebuild of the main window once the new one exists, returning the
+/// page it should reopen.
+pub fn finish_main_window_rebuild() -> Option<Url> {
+    MAIN_WINDOW_REBUILD
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .take()
+        .and_then(|rebuild| rebuild.url)
+}
+
+/// A window's own server and webview-data partition, for windows that
+/// shouldn't just open the configured server with the shared webview data.
+pub struct WindowSession {
+    pub server_url: Url,
+    /// Windows with the same partition share cookies and storage; each
+    /// partition is isolated from the others and from the default data.
+    pub partition: String,
+}
+
+impl WindowSession {
+    pub fn for_profile(config: &AppConfig, name: &str) -> Result<Self, String> {
+        let profile = config
+            .profiles
+            .get(name)
+            .ok_or_else(|| format!("Unknown profile \"{name}\""))?;
+        Ok(Self {
+            server_url: validate_server_url(&profile.server_url)?,
+            partition: format!("{PROFILE_PARTITION_PREFIX}{name}"),
+        })
+    }
+
+    /// An ad-hoc session for `server_url`, partitioned by its origin so
+    /// every window on the same server shares one login.
+    pub fn for_url(server_url: &str) -> Result<Self, String> {
+        let server_url = validate_server_url(server_url)?;
+        let partition = format!("origin-{}", server_url.origin().ascii_serialization());
+        Ok(Self {
+            server_url,
+            partition,
+        })
+    }
+}
+
+/// An identifier for a partition's webview data, the same every run and
+/// different for every partition.
+fn partition_id(partition: &str) -> uuid::Uuid {
+    uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_OID, partition.as_bytes())
+}
+
+/// A directory name for a partition, different for every partition (even
+/// where names don't tell case apart): the partition with every character a
+/// directory name can't hold made `_`, to be readable, then the partition's
+/// id. (macOS partitions by data-store identifier instead, so only the tests
+/// use it there.)
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+fn partition_dir_name(partition: &str) -> String {
+    let readable: String = partition
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{readable}-{}", partition_id(partition).simple())
+}
+
+/// Whether `WKWebView` keeps separate data stores, which it does from macOS 14.
+#[cfg(target_os = "macos")]
+fn separate_data_stores() -> bool {
+    use objc2_foundation::{NSOperatingSystemVersion, NSProcessInfo};
+
+    NSProcessInfo::processInfo().isOperatingSystemAtLeastVersion(NSOperatingSystemVersion {
+        majorVersion: 14,
+        minorVersion: 0,
+        patchVersion: 0,
+    })
+}
+
+#[cfg(target_os = "linux")]
+static TRANSPARENCY_SUPPORTED: OnceLock<bool> = OnceLock::new();
+
+/// Whether see-through windows render at all. Without a compositor (bare
+/// X11 window managers, some remote sessions) a transparent or frameless
+/// window is drawn as a black rectangle. Checked once, after GTK is up;
+/// elsewhere there's always a compositor.
+// Only has a body on Linux.
+#[cfg_attr(not(target_os = "linux"), allow(clippy::missing_const_for_fn))]
+pub fn supports_transparency() -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        *TRANSPARENCY_SUPPORTED.get_or_init(|| {
+            gtk::gdk::Screen::default().is_some_and(|screen| screen.is_composited())
+        })
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        true
+    }
+}
+
+/// Build the main window from its `tauri.conf.json` entry (which has
+/// `create: false` so Tauri leaves it to us), at the configured size,
+/// opaque and with a normal titlebar when the session can't draw
+/// transparency, and with the native titlebar if that's configured.
+pub fn create_main_window(app: &AppHandle) -> Result<WebviewWindow, String> {
+    let mut window_config = app
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|window| window.label == "main")
+        .cloned()
+        .ok_or("No main window in tauri.conf.json")?;
+    if !supports_transparency() {
+        window_config.transparent = false;
+        window_config.decorations = true;
+    }
+    if titlebar_mode(app, DEFAULT_PARTITION) == TitlebarMode::Native {
+        window_config.title_bar_style = tauri::TitleBarStyle::Visible;
+        window_config.hidden_title = false;
+    }
+
+    let settings = app
+        .state::<ConfigState>()
+        .config()
+        .window
+        .for_this_platform();
+    window_config.width = settings.width;
+    window_config.height = settings.height;
+    window_config.min_width = Some(settings.min_width);
+    window_config.min_height = Some(settings.min_height);
+    // The window state plugin puts it back where it was left, if it's been
+    // open before.
+    if let Some(position) = new_window_position(app, &settings) {
+        window_config.x = Some(position.x);
+        window_config.y = Some(position.y);
+    }
+
+    window_config.additional_browser_args = Some(crate::locale::browser_args());
+    // Kept out of sight while onboarding asks for the server.
+    window_config.visible = !crate::onboarding::is_needed(app);
+
+    WebviewWindowBuilder::from_config(app, &window_config)
+        .and_then(WebviewWindowBuilder::build)
+        .map_err(|e| e.to_string())
+}
+
+/// How far down and to the right of the window used last a new one opens
+/// with `WindowPosition::LastUsed`, in logical pixels.
+const CASCADE_OFFSET: f64 = 28.0;
+
+/// Where the chat window used last is, or was (see `remember_position`).
+static LAST_USED_POSITION: Mutex<Option<LogicalPosition<f64>>> = Mutex::new(None);
+
+/// Note where the chat window `window` is, when it's focused or moved, for
+/// new windows that open by the one used last.
+pub fn remember_position(window: &Window) {
+    if is_bundled_page_window(window.label()) {
+        return;
+    }
+    let (Ok(position), Ok(scale)) = (window.outer_position(), window.scale_factor()) else {
+        return;
+    };
+    *LAST_USED_POSITION
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(position.to_logical(scale));
+}
+
+/// Where a window of `settings`' size goes to be centered in `monitor`'s
+/// work area. One too big for it keeps its top left corner in it.
+fn centered_in(monitor: &Monitor, settings: &WindowSettings) -> LogicalPosition<f64> {
+    let area = monitor.work_area();
+    let scale = monitor.scale_factor();
+    let origin = area.position.to_logical::<f64>(scale);
+    let size = area.size.to_logical::<f64>(scale);
+    LogicalPosition::new(
+        (origin.x + (size.width - settings.width) / 2.0).max(origin.x),
+        (origin.y + (size.height - settings.height) / 2.0).max(origin.y),
+    )
+}
+
+/// Where a new chat window opens, as `settings.position` says, or `None`
+/// to leave it to the system (also when the display or the window used
+/// last can't be found).
+fn new_window_position(app: &AppHandle, settings: &WindowSettings) -> Option<LogicalPosition<f64>> {
+    match settings.position {
+        WindowPosition::System => None,
+        WindowPosition::Center => {
+            let monitor = app.primary_monitor().ok().flatten()?;
+            Some(centered_in(&monitor, settings))
+        }
+        WindowPosition::CursorDisplay => {
+            let cursor = app.cursor_position().ok()?;
+            let monitor = app.monitor_from_point(cursor.x, cursor.y).ok().flatten()?;
+            Some(centered_in(&monitor, settings))
+        }
+        WindowPosition::LastUsed => {
+            let last = (*LAST_USED_POSITION
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner))?;
+            Some(LogicalPosition::new(
+                last.x + CASCADE_OFFSET,
+                last.y + CASCADE_OFFSET,
+            ))
+        }
+    }
+}
+
+/// The builder every new chat window starts from, whether it's opened from
+/// the menu, the tray, the shortcut or the `new_window` command: titled,
+/// sized and placed as configured.
+fn chat_window_builder<'a>(
+    app: &'a AppHandle,
+    label: &str,
+    url: Url,
+    config: &AppConfig,
+) -> WebviewWindowBuilder<'a, tauri::Wry, AppHandle> {
+    let settings = config.window.for_this_platform();
+    let builder = WebviewWindowBuilder::new(app, label, WebviewUrl::External(url))
+        .title(&config.window_title)
+        .inner_size(settings.width, settings.height)
+        .min_inner_size(settings.min_width, settings.min_height)
+        .always_on_top(config.always_on_top)
+        .additional_browser_args(&crate::locale::browser_args());
+    match new_window_position(app, &settings) {
+        Some(position) => builder.position(position.x, position.y),
+        None => builder,
+    }
+}
+
 /// Build a new Onyx window (title, size, platform-specific transparency /
 /// titlebar / background-color quirks, vibrancy, the Alt-menu toggle, and
 /// devtools) and apply current settings to it. The single source of truth
 /// for window creation -- previously duplicated between the menu/tray
 /// "New Window" path and the `new_window` command, which had already drifted
 /// once (the Windows transparency fix had to be hand-applied to both).
-pub fn build_and_setup_window(app: &AppHandle) -> Result<WebviewWindow, String> {
+///
+/// Without a `session` the window opens the configured server and shares the
+/// default webview data with the main window.
+pub fn build_and_setup_window(
+    app: &AppHandle,
+    session: Option<&WindowSession>,
+) -> Result<WebviewWindow, String> {
+    if crate::kiosk::is_active() {
+        return Err(crate::kiosk::unavailable());
+    }
     let config = app.state::<ConfigState>().config();
     let window_label = format!("onyx-{}", uuid::Uuid::new_v4());
-    let url = config
-        .server_url
-        .parse()
-        .map_err(|e| format!("Invalid server URL: {e}"))?;
+    let url: Url = match session {
+        Some(session) => session.server_url.clone(),
+        None => config
+            .server_url
+            .parse()
+            .map_err(|e| format!("Invalid server URL: {e}"))?,
+    };
+    let partition = session.map_or(DEFAULT_PARTITION, |session| &session.partition);
+    let url = if crate::admin::admin_mode(&config, partition) {
+        crate::admin::admin_start().url(&url)
+    } else {
+        url
+    };
+
+    let builder = chat_window_builder(app, &window_label, url, &config);
+
+    // WKWebView can't be pointed at a directory; it takes an identifier for
+    // a separate data store instead. Older versions would ignore it and put
+    // the window on the main window's cookies, so it doesn't open at all.
+    #[cfg(target_os = "macos")]
+    let builder = match session {
+        Some(_) if !separate_data_stores() => {
+            return Err(
+                "Windows with their own cookies and storage need macOS 14 or later".to_string(),
+            );
+        }
+        Some(session) => {
+            builder.data_store_identifier(partition_id(&session.partition).into_bytes())
+        }
+        None => builder,
+    };
 
-    let builder = WebviewWindowBuilder::new(app, &window_label, WebviewUrl::External(url))
-        .title(config.window_title)
-        .inner_size(1232.0, 800.0)
-        .min_inner_size(800.0, 600.0);
+    #[cfg(not(target_os = "macos"))]
+    let builder = match session {
+        Some(session) => {
+            let data_dir = get_data_dir().ok_or("Could not determine data directory")?;
+            builder.data_directory(
+                data_dir
+                    .join("partitions")
+                    .join(partition_dir_name(&session.partition)),
+            )
+        }
+        None => builder,
+    };
 
     // Windows draws its own title bar in the system theme; a transparent
     // window leaves any unpainted region see-through, which produces the
-    // translucent-bar artifact reported on Windows.
-    #[cfg(not(target_os = "windows"))]
+    // translucent-bar artifact reported on Windows -- unless a system
+    // backdrop fills it in, so only windows that get one are transparent.
+    #[cfg(target_os = "windows")]
+    let builder = builder.transparent(
+        config.appearance.transparent && config.appearance.backdrop != WindowsBackdrop::None,
+    );
+
+    #[cfg(target_os = "macos")]
     let builder = builder.transparent(true);
 
     #[cfg(target_os = "macos")]
-    let builder = builder
-        .title_bar_style(tauri::TitleBarStyle::Overlay)
-        .hidden_title(true);
+    let native_titlebar = titlebar_mode(
+        app,
+        session.map_or(DEFAULT_PARTITION, |session| session.partition.as_str()),
+    ) == TitlebarMode::Native;
+
+    #[cfg(target_os = "macos")]
+    let builder = if native_titlebar {
+        builder.title_bar_style(tauri::TitleBarStyle::Visible)
+    } else {
+        builder
+            .title_bar_style(tauri::TitleBarStyle::Overlay)
+            .hidden_title(true)
+    };
 
     #[cfg(target_os = "linux")]
     let builder = builder.background_color(tauri::window::Color(0x1a, 0x1a, 0x2e, 0xff));
 
     let window = builder.build().map_err(|e| e.to_string())?;
 
+    if let Some(session) = session {
+        app.state::<WindowPartitions>()
+            .insert(&window_label, &session.partition);
+    }
+    crate::zoom::apply_saved_zoom(app, &window);
+    crate::auth::restore_session(app, &window);
+    crate::client_cert::answer_requests(app, &window);
+    crate::cert_trust::watch_certificate_errors(app, &window);
+
+    apply_appearance(app, &window);
     #[cfg(target_os = "macos")]
-    {
-        if let Err(e) = apply_vibrancy(&window, NSVisualEffectMaterial::Sidebar, None, None) {
-            log_backend_error(app, &format!("Failed to apply vibrancy effect: {e}"));
-        }
+    if !native_titlebar {
         inject_titlebar(window.clone());
     }
 
@@ -132,38 +804,112 @@ pub fn build_and_setup_window(app: &AppHandle) -> Result<WebviewWindow, String>
 pub fn trigger_new_window(app: &AppHandle) {
     let handle = app.clone();
     tauri::async_runtime::spawn(async move {
-        if let Err(e) = build_and_setup_window(&handle) {
+        if let Err(e) = build_and_setup_window(&handle, None) {
             log_backend_error(&handle, &format!("Failed to open new window: {e}"));
         }
     });
 }
 
+/// Keep `window` above other windows (or stop), keeping the menu checkbox
+/// in step.
+pub fn set_window_always_on_top(app: &AppHandle, window: &WebviewWindow, on_top: bool) {
+    match window.set_always_on_top(on_top) {
+        Ok(()) => crate::menu::sync_always_on_top_checkbox(app, on_top),
+        Err(e) => log_backend_error(app, &format!("Failed to set always on top: {e}")),
+    }
+}
+
+/// Flip always-on-top for the focused window (the main window if none is).
+pub fn toggle_always_on_top(app: &AppHandle) {
+    let windows = app.webview_windows();
+    let window = windows
+        .values()
+        .find(|window| window.is_focused().unwrap_or(false))
+        .or_else(|| windows.get("main"));
+    if let Some(window) = window {
+        let on_top = window.is_always_on_top().unwrap_or(false);
+        set_window_always_on_top(app, window, !on_top);
+    }
+}
+
 pub fn open_docs(app: &AppHandle) {
     if !open_in_default_browser("https://docs.onyx.app") {
         log_backend_error(app, "Failed to open docs in default browser");
     }
 }
 
-pub fn open_settings(app: &AppHandle) {
-    // Navigate main window to the settings page (index.html) with settings flag
-    let settings_url = app
-        .state::<ConfigState>()
-        .app_base_url()
-        .map(|mut url| {
-            url.set_query(None);
-            url.set_fragment(Some("settings"));
-            url.set_path("/");
-            url
-        })
-        .or_else(|| Url::parse("tauri://localhost/#settings").ok());
+/// Open a window on one of the bundled pages, or focus it if it's already
+/// open.
+fn open_bundled_page(
+    app: &AppHandle,
+    label: &'static str,
+    page: &'static str,
+    title: &'static str,
+    preset: &'static WindowPreset,
+) {
+    if let Some(window) = app.get_webview_window(label) {
+        if let Err(e) = window.unminimize() {
+            log_backend_error(app, &format!("Failed to unminimize {label} window: {e}"));
+        }
+        if let Err(e) = window.set_focus() {
+            log_backend_error(app, &format!("Failed to focus {label} window: {e}"));
+        }
+        return;
+    }
 
-    if let Some(window) = app.get_webview_window("main") {
-        if let Some(url) = settings_url {
-            if let Err(e) = window.navigate(url) {
-                log_backend_error(app, &format!("Failed to navigate to settings: {e}"));
-            }
+    // Built off the menu-event thread, like `trigger_new_window`, since
+    // creating a window synchronously from a menu handler can deadlock on
+    // Windows.
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let result = WebviewWindowBuilder::new(&handle, label, WebviewUrl::App(page.into()))
+            .title(title)
+            .inner_size(preset.width, preset.height)
+            .min_inner_size(preset.min_width, preset.min_height)
+            .additional_browser_args(&crate::locale::browser_args())
+            .build();
+
+        match result {
+            Ok(window) => maybe_open_devtools(&handle, &window),
+            Err(e) => log_backend_error(&handle, &format!("Failed to open {label}: {e}")),
         }
+    });
+}
+
+/// Open the settings window (the bundled `settings.html`).
+pub fn open_settings(app: &AppHandle) {
+    if crate::kiosk::is_active() {
+        return;
     }
+    open_bundled_page(
+        app,
+        SETTINGS_WINDOW_LABEL,
+        "settings.html",
+        "Onyx Settings",
+        &BUNDLED_PAGE_WINDOW,
+    );
+}
+
+/// Open the first-run onboarding window (the bundled `onboarding.html`).
+pub fn open_onboarding(app: &AppHandle) {
+    open_bundled_page(
+        app,
+        ONBOARDING_WINDOW_LABEL,
+        "onboarding.html",
+        "Welcome to Onyx",
+        &BUNDLED_PAGE_WINDOW,
+    );
+}
+
+/// Open the permissions window (the bundled `permissions.html`).
+pub fn open_permissions(app: &AppHandle) {
+    open_bundled_page(
+        app,
+        PERMISSIONS_WINDOW_LABEL,
+        "permissions.html",
+        "Desktop Permissions",
+        &BUNDLED_PAGE_WINDOW,
+    );
 }
 
 pub fn same_origin(left: &Url, right: &Url) -> bool {
@@ -173,7 +919,7 @@ pub fn same_origin(left: &Url, right: &Url) -> bool {
 }
 
 pub fn is_chat_session_url(url: &Url) -> bool {
-    url.path().starts_with("/app") && url.query_pairs().any(|(key, _)| key == "chatId")
+    matches!(Route::from_url(url), Some(Route::Chat { .. }))
 }
 
 pub fn should_open_in_external_browser(current_url: &Url, destination_url: &Url) -> bool {
@@ -189,13 +935,26 @@ pub fn should_open_in_external_browser(current_url: &Url, destination_url: &Url)
 }
 
 pub fn open_in_default_browser(url: &str) -> bool {
+    // A kiosk stays on the server.
+    if crate::kiosk::is_active() {
+        return false;
+    }
     #[cfg(target_os = "macos")]
     {
-        return Command::new("open").arg(url).status().is_ok();
+        return Command::new("open")
+            .arg(url)
+            .status()
+            .is_ok_and(|status| status.success());
     }
     #[cfg(target_os = "linux")]
     {
-        return Command::new("xdg-open").arg(url).status().is_ok();
+        if crate::sandbox::sandbox().is_some() {
+            return crate::sandbox::open_uri(url).is_ok();
+        }
+        return Command::new("xdg-open")
+            .arg(url)
+            .status()
+            .is_ok_and(|status| status.success());
     }
     #[cfg(target_os = "windows")]
     {
@@ -203,7 +962,7 @@ pub fn open_in_default_browser(url: &str) -> bool {
             .arg("url.dll,FileProtocolHandler")
             .arg(url)
             .status()
-            .is_ok();
+            .is_ok_and(|status| status.success());
     }
     #[allow(unreachable_code)]
     false
@@ -220,15 +979,12 @@ pub fn inject_chat_link_intercept(webview: &Webview) {
         .app_base_url()
         .map(|url| url.origin().ascii_serialization());
     let origin_json = serde_json::to_string(&trusted_origin).unwrap_or_else(|_| "null".to_string());
-    let script =
-        format!("window.__ONYX_TRUSTED_ORIGIN__ = {origin_json};\n{CHAT_LINK_INTERCEPT_SCRIPT}");
+    let script = format!(
+        "window.__ONYX_TRUSTED_ORIGIN__ = {origin_json};\n{}",
+        script(app, Script::ChatLinkIntercept)
+    );
 
-    if let Err(e) = webview.eval(&script) {
-        log_backend_error(
-            app,
-            &format!("Failed to inject chat-link-intercept script: {e}"),
-        );
-    }
+    watchdog::eval(webview, script, "inject chat-link-intercept script");
 }
 
 /// One-off titlebar re-injection on every page load, distinct from
@@ -236,52 +992,186 @@ pub fn inject_chat_link_intercept(webview: &Webview) {
 /// load and would otherwise miss later in-app navigations.
 #[cfg(target_os = "macos")]
 pub fn eval_titlebar_script(webview: &Webview) {
-    if let Err(e) = webview.eval(TITLEBAR_SCRIPT) {
-        log_backend_error(
-            webview.app_handle(),
-            &format!("Failed to inject titlebar script: {e}"),
-        );
+    if uses_native_titlebar(webview.app_handle(), webview.label()) {
+        return;
+    }
+    let script = script(webview.app_handle(), Script::Titlebar);
+    watchdog::eval(webview, &*script, "inject titlebar script");
+}
+
+#[cfg(target_os = "macos")]
+const fn ns_material(material: VibrancyMaterial) -> Option<NSVisualEffectMaterial> {
+    Some(match material {
+        VibrancyMaterial::None => return None,
+        VibrancyMaterial::Sidebar => NSVisualEffectMaterial::Sidebar,
+        VibrancyMaterial::Titlebar => NSVisualEffectMaterial::Titlebar,
+        VibrancyMaterial::Menu => NSVisualEffectMaterial::Menu,
+        VibrancyMaterial::Popover => NSVisualEffectMaterial::Popover,
+        VibrancyMaterial::HeaderView => NSVisualEffectMaterial::HeaderView,
+        VibrancyMaterial::Sheet => NSVisualEffectMaterial::Sheet,
+        VibrancyMaterial::WindowBackground => NSVisualEffectMaterial::WindowBackground,
+        VibrancyMaterial::HudWindow => NSVisualEffectMaterial::HudWindow,
+        VibrancyMaterial::ContentBackground => NSVisualEffectMaterial::ContentBackground,
+        VibrancyMaterial::UnderWindowBackground => NSVisualEffectMaterial::UnderWindowBackground,
+        VibrancyMaterial::UnderPageBackground => NSVisualEffectMaterial::UnderPageBackground,
+    })
+}
+
+/// Make `window` translucent per `appearance`: vibrancy with the chosen
+/// material and corner radius (`none` leaves it plainly transparent).
+/// Returns whether it's translucent now.
+#[cfg(target_os = "macos")]
+fn apply_translucency(
+    window: &WebviewWindow,
+    appearance: &AppearanceSettings,
+) -> Result<bool, window_vibrancy::Error> {
+    ns_material(appearance.vibrancy).map_or(Ok(true), |material| {
+        apply_vibrancy(window, material, None, appearance.corner_radius).map(|()| true)
+    })
+}
+
+/// Make `window` translucent per `appearance`: the chosen system backdrop,
+/// following the system theme. Returns whether it's translucent now.
+#[cfg(target_os = "windows")]
+fn apply_translucency(
+    window: &WebviewWindow,
+    appearance: &AppearanceSettings,
+) -> Result<bool, window_vibrancy::Error> {
+    match appearance.backdrop {
+        WindowsBackdrop::None => Ok(false),
+        WindowsBackdrop::Mica => apply_mica(window, None).map(|()| true),
+        WindowsBackdrop::Tabbed => apply_tabbed(window, None).map(|()| true),
+        WindowsBackdrop::Acrylic => apply_acrylic(window, None).map(|()| true),
+    }
+}
+
+/// Give a window the configured background: translucent where the platform
+/// and settings allow (see `apply_translucency`), otherwise a solid color in
+/// the window's theme rather than a see-through window with nothing behind
+/// the page. Linux is always solid, since compositors vary too much to rely
+/// on. Used for the main window in `setup` and for every window
+/// `build_and_setup_window` makes, so they can't drift apart.
+pub fn apply_appearance(app: &AppHandle, window: &WebviewWindow) {
+    #[cfg(not(target_os = "linux"))]
+    let translucent = {
+        let appearance = app.state::<ConfigState>().config().appearance;
+        appearance.transparent
+            && apply_translucency(window, &appearance).unwrap_or_else(|e| {
+                log_debug(
+                    app,
+                    &format!("Window effect unavailable, using a solid background: {e}"),
+                );
+                false
+            })
+    };
+    #[cfg(target_os = "linux")]
+    let translucent = false;
+    if translucent {
+        return;
+    }
+
+    let color = match window.theme() {
+        Ok(tauri::Theme::Light) => tauri::window::Color(0xff, 0xff, 0xff, 0xff),
+        _ => tauri::window::Color(0x1a, 0x1a, 0x2e, 0xff),
+    };
+    if let Err(e) = window.set_background_color(Some(color)) {
+        log_backend_error(app, &format!("Failed to make window opaque: {e}"));
     }
 }
 
 #[cfg(target_os = "macos")]
 pub fn inject_titlebar(window: WebviewWindow) {
-    let script = TITLEBAR_SCRIPT.to_string();
+    let script = script(window.app_handle(), Script::Titlebar);
     tauri::async_runtime::spawn(async move {
         // Keep trying for a few seconds to survive navigations and slow
-        // loads. Most early attempts are expected to fail (the page hasn't
-        // loaded yet), so failures here aren't logged.
+        // loads; early attempts that land before the page has loaded just
+        // don't stick. An eval only fails once the window is gone.
         let delays = [0u64, 200, 600, 1200, 2000, 4000, 6000, 8000, 10000];
         for delay in delays {
             if delay > 0 {
                 sleep(Duration::from_millis(delay)).await;
             }
-            let _ = window.eval(&script);
+            if uses_native_titlebar(window.app_handle(), window.label()) {
+                return;
+            }
+            if !watchdog::eval(window.as_ref(), &*script, "inject titlebar script") {
+                return;
+            }
         }
     });
 }
 
+/// Take down the injected titlebar in a page that still has one; the
+/// titlebar script checks the flag before remounting itself.
+#[cfg(target_os = "macos")]
+const REMOVE_TITLEBAR_SCRIPT: &str = r#"window.__ONYX_NATIVE_TITLEBAR__ = true;
+document.getElementById("onyx-desktop-titlebar")?.remove();
+document.getElementById("onyx-desktop-titlebar-style")?.remove();"#;
+
+/// The injected titlebar failed in `window` (`reason` says how): switch
+/// every window sharing its partition to the native titlebar, and build
+/// later ones with it, so they stay draggable and closable.
+pub fn fall_back_to_native_titlebar(app: &AppHandle, window: &WebviewWindow, reason: &str) {
+    let partitions = app.state::<WindowPartitions>();
+    let partition = partitions.partition_of(window.label());
+    if !app.state::<TitlebarFallbacks>().insert(&partition, reason) {
+        return;
+    }
+    log_backend_error(
+        app,
+        &format!("Injected titlebar failed in \"{partition}\", using the native one: {reason}"),
+    );
+
+    #[cfg(target_os = "macos")]
+    for (label, window) in app.webview_windows() {
+        if is_bundled_page_window(&label) || partitions.partition_of(&label) != partition {
+            continue;
+        }
+        if let Err(e) = window.set_title_bar_style(tauri::TitleBarStyle::Visible) {
+            log_backend_error(app, &format!("Failed to show the native titlebar: {e}"));
+        }
+        watchdog::eval(
+            window.as_ref(),
+            REMOVE_TITLEBAR_SCRIPT,
+            "remove the titlebar",
+        );
+    }
+}
+
 pub fn apply_settings_to_window(app: &AppHandle, window: &WebviewWindow) {
     let config = app.state::<ConfigState>().config();
 
     if let Err(e) = window.set_title(&config.window_title) {
         log_backend_error(app, &format!("Failed to set window title: {e}"));
     }
+    crate::spellcheck::apply_spellcheck(app, window);
+    crate::shortcut_keys::apply_shortcut_keys(app, window);
+    crate::snippets::apply_snippets(app, window);
+    crate::locale::apply_to_webview(app, window);
+    crate::server_headers::apply_to_webview(app, window);
 
     // Menu-bar visibility and window decorations are only configurable off macOS.
     if cfg!(target_os = "macos") {
         return;
     }
-    if !config.show_menu_bar {
-        if let Err(e) = window.hide_menu() {
-            log_backend_error(app, &format!("Failed to hide menu bar: {e}"));
-        }
+    // Applied in both directions so this also works for re-applying a config
+    // changed from the settings window, not just for a fresh window.
+    let result = if config.show_menu_bar {
+        window.show_menu()
+    } else {
+        window.hide_menu()
+    };
+    if let Err(e) = result {
+        log_backend_error(app, &format!("Failed to apply menu-bar visibility: {e}"));
     }
+    // Frameless windows need a compositor to draw properly.
     #[cfg(target_os = "linux")]
-    if config.hide_window_decorations {
-        if let Err(e) = window.set_decorations(false) {
-            log_backend_error(app, &format!("Failed to hide window decorations: {e}"));
-        }
+    if let Err(e) = window.set_decorations(
+        !config.hide_window_decorations
+            || !supports_transparency()
+            || uses_native_titlebar(app, window.label()),
+    ) {
+        log_backend_error(app, &format!("Failed to apply window decorations: {e}"));
     }
 }
 
@@ -325,6 +1215,65 @@ mod tests {
         )));
     }
 
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn url_sessions_are_partitioned_by_origin() {
+        let a = WindowSession::for_url("https://staging.example.com/chat").unwrap();
+        let b = WindowSession::for_url("https://staging.example.com/").unwrap();
+        let c = WindowSession::for_url("https://staging.example.com:8443").unwrap();
+        assert_eq!(a.partition, b.partition);
+        assert_ne!(a.partition, c.partition);
+        assert!(WindowSession::for_url("file:///etc/passwd").is_err());
+
+        let dir = partition_dir_name(&c.partition);
+        assert!(dir.starts_with("origin-https___staging.example.com_8443-"));
+    }
+
+    #[test]
+    fn partitions_never_share_a_data_directory() {
+        let names = [
+            "profile-acme corp",
+            "profile-acme_corp",
+            "profile-Acme_corp",
+            "profile-acme/corp",
+        ]
+        .map(partition_dir_name);
+        for (i, name) in names.iter().enumerate() {
+            assert!(names[i + 1..]
+                .iter()
+                .all(|other| !other.eq_ignore_ascii_case(name)));
+        }
+    }
+
+    #[test]
+    fn profiles_can_override_the_titlebar() {
+        let mut config = AppConfig::default();
+        config.appearance.titlebar = TitlebarMode::Native;
+        for (name, titlebar) in [("work", Some(TitlebarMode::Overlay)), ("home", None)] {
+            config.profiles.insert(
+                name.to_string(),
+                crate::config::ProfileSettings {
+                    server_url: "https://onyx.example.com".to_string(),
+                    titlebar,
+                    admin_mode: None,
+                    accounts: Vec::new(),
+                },
+            );
+        }
+        assert_eq!(
+            configured_titlebar(&config, "profile-work"),
+            TitlebarMode::Overlay
+        );
+        assert_eq!(
+            configured_titlebar(&config, "profile-home"),
+            TitlebarMode::Native
+        );
+        assert_eq!(
+            configured_titlebar(&config, DEFAULT_PARTITION),
+            TitlebarMode::Native
+        );
+    }
+
     #[test]
     fn should_open_in_external_browser_only_from_chat_session() {
         let chat = url("https://cloud.onyx.app/app?chatId=123");
@@ -0,0 +1,136 @@
+// Webview zoom, stepped like a browser's and saved in the config so it
+// survives restarts. Factors are saved per webview partition (see
+// `WindowPartitions`): one for the main window and plain new windows, one
+// for each profile or one-off server.
+
+use crate::config::ConfigState;
+use crate::debug_log::log_backend_error;
+use crate::window::WindowPartitions;
+use tauri::{AppHandle, Manager, WebviewWindow};
+
+/// The factors zoom in/out steps through, as in Chromium.
+const ZOOM_STEPS: [f64; 15] = [
+    0.25, 0.33, 0.5, 0.67, 0.75, 0.8, 0.9, 1.0, 1.1, 1.25, 1.5, 1.75, 2.0, 2.5, 3.0,
+];
+
+#[derive(Debug, Clone, Copy)]
+pub enum ZoomChange {
+    In,
+    Out,
+    Reset,
+}
+
+/// The next factor after `current` (which may be a hand-edited value between
+/// steps), clamped to the ends of `ZOOM_STEPS`.
+fn next_zoom(current: f64, change: ZoomChange) -> f64 {
+    match change {
+        ZoomChange::In => ZOOM_STEPS
+            .into_iter()
+            .find(|step| *step > current + f64::EPSILON)
+            .unwrap_or(ZOOM_STEPS[ZOOM_STEPS.len() - 1]),
+        ZoomChange::Out => ZOOM_STEPS
+            .into_iter()
+            .rev()
+            .find(|step| *step < current - f64::EPSILON)
+            .unwrap_or(ZOOM_STEPS[0]),
+        ZoomChange::Reset => 1.0,
+    }
+}
+
+fn zoom_key(app: &AppHandle, window: &WebviewWindow) -> String {
+    app.state::<WindowPartitions>().partition_of(window.label())
+}
+
+fn saved_zoom(app: &AppHandle, key: &str) -> f64 {
+    app.state::<ConfigState>()
+        .config()
+        .zoom
+        .get(key)
+        .copied()
+        .filter(|factor| factor.is_finite())
+        .map_or(1.0, |factor| {
+            factor.clamp(ZOOM_STEPS[0], ZOOM_STEPS[ZOOM_STEPS.len() - 1])
+        })
+}
+
+/// Restore the saved zoom factor on a newly created window.
+pub fn apply_saved_zoom(app: &AppHandle, window: &WebviewWindow) {
+    let factor = saved_zoom(app, &zoom_key(app, window));
+    if (factor - 1.0).abs() > f64::EPSILON {
+        if let Err(e) = window.set_zoom(factor) {
+            log_backend_error(app, &format!("Failed to restore zoom: {e}"));
+        }
+    }
+}
+
+/// Step `window`'s zoom and save the new factor.
+pub fn change_zoom(app: &AppHandle, window: &WebviewWindow, change: ZoomChange) {
+    let key = zoom_key(app, window);
+    let factor = next_zoom(saved_zoom(app, &key), change);
+    if let Err(e) = window.set_zoom(factor) {
+        log_backend_error(app, &format!("Failed to set zoom: {e}"));
+        return;
+    }
+
+    let result = app.state::<ConfigState>().update_and_persist(|c| {
+        if (factor - 1.0).abs() > f64::EPSILON {
+            c.zoom.insert(key, factor);
+        } else {
+            c.zoom.remove(&key);
+        }
+    });
+    if let Err(e) = result {
+        log_backend_error(app, &format!("Failed to save zoom: {e}"));
+    }
+}
+
+/// Menu/shortcut entry point: zoom the focused window, or the main one.
+pub fn change_focused_zoom(app: &AppHandle, change: ZoomChange) {
+    let windows = app.webview_windows();
+    let window = windows
+        .values()
+        .find(|window| window.is_focused().unwrap_or(false))
+        .or_else(|| windows.get("main"));
+    if let Some(window) = window {
+        change_zoom(app, window, change);
+    }
+}
+
+/// Zoom the calling window in one step
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn zoom_in(window: WebviewWindow) {
+    change_zoom(window.app_handle(), &window, ZoomChange::In);
+}
+
+/// Zoom the calling window out one step
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn zoom_out(window: WebviewWindow) {
+    change_zoom(window.app_handle(), &window, ZoomChange::Out);
+}
+
+/// Reset the calling window to 100%
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command]
+pub fn zoom_reset(window: WebviewWindow) {
+    change_zoom(window.app_handle(), &window, ZoomChange::Reset);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn zoom_steps_and_clamps() {
+        assert_eq!(next_zoom(1.0, ZoomChange::In), 1.1);
+        assert_eq!(next_zoom(1.0, ZoomChange::Out), 0.9);
+        // Off-step values snap to the neighbouring step.
+        assert_eq!(next_zoom(1.3, ZoomChange::In), 1.5);
+        assert_eq!(next_zoom(1.3, ZoomChange::Out), 1.25);
+        assert_eq!(next_zoom(3.0, ZoomChange::In), 3.0);
+        assert_eq!(next_zoom(0.25, ZoomChange::Out), 0.25);
+        assert_eq!(next_zoom(2.0, ZoomChange::Reset), 1.0);
+    }
+}
@@ -7,11 +7,58 @@
   const STYLE_ID = "onyx-desktop-titlebar-style";
   const VIEWPORT_VAR = "--onyx-desktop-viewport-height";
 
+  // Set once this profile has fallen back to the native titlebar.
+  if (window.__ONYX_NATIVE_TITLEBAR__) return;
+
+  // The first Content-Security-Policy directive the page enforced, which
+  // is usually what blocked the titlebar's styles (style-src without
+  // 'unsafe-inline').
+  let cspViolation = null;
+  document.addEventListener("securitypolicyviolation", (e) => {
+    cspViolation = cspViolation ?? e.effectiveDirective;
+  });
+
   // Wait for DOM to be ready
   if (document.readyState === "loading") {
-    document.addEventListener("DOMContentLoaded", init);
+    document.addEventListener("DOMContentLoaded", safeInit);
   } else {
-    init();
+    safeInit();
+  }
+
+  function safeInit() {
+    try {
+      init();
+    } catch (err) {
+      reportBlocked(`Titlebar script failed: ${err}`);
+    }
+  }
+
+  // Hand over to the native titlebar: a half-working overlay leaves the
+  // window with nothing to drag and the page under the traffic lights.
+  function reportBlocked(reason) {
+    if (window.__ONYX_NATIVE_TITLEBAR__) return;
+    window.__ONYX_NATIVE_TITLEBAR__ = true;
+    document.getElementById(TITLEBAR_ID)?.remove();
+    document.getElementById(STYLE_ID)?.remove();
+
+    const invoke = getInvoke();
+    if (invoke) {
+      invoke("report_titlebar_blocked", { reason }).catch(() => {});
+    }
+  }
+
+  function checkStylesApplied() {
+    if (window.__ONYX_NATIVE_TITLEBAR__) return;
+    // Focus mode gives the titlebar's strip back to the page.
+    if (document.documentElement.classList.contains("onyx-desktop-focus-mode"))
+      return;
+    const padding = getComputedStyle(document.documentElement).paddingTop;
+    if (padding === `${TITLEBAR_HEIGHT}px`) return;
+    reportBlocked(
+      cspViolation
+        ? `Content-Security-Policy blocked the titlebar (${cspViolation})`
+        : "The titlebar's styles didn't apply"
+    );
   }
 
   function getInvoke() {
@@ -200,7 +247,7 @@
   }
 
   function mountTitleBar() {
-    if (!document.body) {
+    if (!document.body || window.__ONYX_NATIVE_TITLEBAR__) {
       return;
     }
 
@@ -221,6 +268,8 @@
     const titleBar = buildTitleBar();
     document.body.insertBefore(titleBar, document.body.firstChild);
     injectStyles();
+    // Violation reports are delivered asynchronously.
+    setTimeout(checkStylesApplied, 250);
 
     // Ensure theme is applied immediately after mount
     setTimeout(() => {